@@ -1,4 +1,4 @@
 #[tokio::main(flavor = "multi_thread")]
-pub async fn main() -> miette::Result<()> {
+pub async fn main() {
     pacquet_cli::main().await
 }
@@ -1,18 +1,50 @@
 pub mod add;
+pub mod bin;
+pub mod cache;
+pub mod fetch;
+pub mod graph;
 pub mod install;
+pub mod list;
+pub mod prune;
+pub mod rebuild;
+pub mod root;
 pub mod run;
 pub mod store;
 
 use crate::State;
 use add::AddArgs;
-use clap::{Parser, Subcommand};
+use bin::BinArgs;
+use cache::CacheCommand;
+use clap::{Parser, Subcommand, ValueEnum};
+use derive_more::{Display, Error};
+use fetch::FetchArgs;
+use graph::GraphArgs;
 use install::InstallArgs;
-use miette::Context;
+use list::ListArgs;
+use miette::{Context, Diagnostic, IntoDiagnostic};
+use pacquet_diagnostics::{
+    enable_tracing_by_env, enable_tracing_by_level, set_report_hook,
+    tracing::level_filters::LevelFilter, use_color,
+};
 use pacquet_executor::execute_shell;
-use pacquet_npmrc::Npmrc;
+use pacquet_npmrc::{LogLevel, Npmrc};
 use pacquet_package_manifest::PackageManifest;
+use pacquet_registry::{ParseTimestampError, Timestamp};
+use pacquet_reporter::{
+    AppendOnlyReporter, JunitReporter, NdjsonReporter, ProgressReporter, Reporter, SilentReporter,
+};
+use pacquet_store_dir::StoreDir;
+use pacquet_workspace::WorkspaceManifest;
+use pipe_trait::Pipe;
+use prune::PruneArgs;
+use rebuild::RebuildArgs;
+use root::RootArgs;
 use run::RunArgs;
-use std::{env, path::PathBuf};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
 use store::StoreCommand;
 
 /// Experimental package manager for node.js written in rust.
@@ -28,6 +60,98 @@ pub struct CliArgs {
     /// Set working directory.
     #[clap(short = 'C', long, default_value = ".")]
     pub dir: PathBuf,
+
+    /// Run the command on the root of the workspace instead of the current project.
+    #[clap(short = 'w', long, global = true)]
+    pub workspace_root: bool,
+
+    /// What level of logs to report: silent, error, warn, info, debug, or trace.
+    #[clap(long, global = true)]
+    pub loglevel: Option<LogLevel>,
+
+    /// Shorthand for `--loglevel=debug`.
+    #[clap(short = 'v', long, global = true, conflicts_with = "loglevel")]
+    pub verbose: bool,
+
+    /// Only report errors: shorthand for `--loglevel=error` that also silences the reporter.
+    #[clap(short = 'q', long, global = true, conflicts_with_all = ["loglevel", "verbose"])]
+    pub quiet: bool,
+
+    /// How the progress of the operation is reported.
+    #[clap(long, global = true, value_enum, default_value_t = ReporterType::Default)]
+    pub reporter: ReporterType,
+
+    /// File that `--reporter=junit` writes its report to.
+    #[clap(long, global = true, value_name = "PATH", required_if_eq("reporter", "junit"))]
+    pub out: Option<PathBuf>,
+
+    /// Run the command for every project of the workspace defined by pnpm-workspace.yaml.
+    #[clap(short = 'r', long, global = true)]
+    pub recursive: bool,
+
+    /// Load the settings from this `.npmrc` file instead of the one of the current directory.
+    /// The settings of the `.npmrc` in the home directory still apply unless this file overrides
+    /// them.
+    #[clap(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Only load the settings of `--config`, without the `.npmrc` in the home directory.
+    #[clap(long, global = true, requires = "config")]
+    pub no_config_merge: bool,
+
+    /// Override the location of the store for this invocation.
+    #[clap(long, global = true)]
+    pub store_dir: Option<PathBuf>,
+
+    /// Override the directory that the dependencies are linked into (default is node_modules).
+    #[clap(long, global = true)]
+    pub modules_dir: Option<PathBuf>,
+
+    /// Only resolve to versions that were published before this date (e.g. 2023-01-01).
+    #[clap(long, global = true, value_name = "DATE", value_parser = parse_before)]
+    pub before: Option<String>,
+
+    /// Don't run the hooks of the package manager, such as `readPackage`.
+    #[clap(long, global = true)]
+    pub ignore_pnpmfile: bool,
+
+    /// Disable colors in the output, which are otherwise controlled by `NO_COLOR`, `FORCE_COLOR`,
+    /// and whether stderr is a terminal.
+    #[clap(long, global = true)]
+    pub no_color: bool,
+
+    /// Report a fatal error as a single JSON object on stderr, whatever the reporter is.
+    #[clap(long, global = true)]
+    pub json_errors: bool,
+}
+
+/// Check that the value of `--before` is a date that resolution can compare publish times with.
+fn parse_before(value: &str) -> Result<String, ParseTimestampError> {
+    value.parse::<Timestamp>()?;
+    Ok(value.to_string())
+}
+
+/// Error when `--recursive` is used with a command that doesn't support it.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("--recursive is not yet supported by this command")]
+#[diagnostic(code(pacquet_cli::unsupported_recursive))]
+pub struct UnsupportedRecursiveError;
+
+/// Value of `--reporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReporterType {
+    /// Human readable output, with the throughput and the time left of the downloads on a
+    /// terminal.
+    Default,
+    /// One JSON object per line for each event, written to stdout.
+    Ndjson,
+    /// One human readable line for each event, written to stderr. The default when stderr isn't
+    /// a terminal.
+    AppendOnly,
+    /// No output at all, not even errors. Failures are only reported by the exit code.
+    Silent,
+    /// JUnit XML of the scripts that have run, written to the file of `--out`.
+    Junit,
 }
 
 #[derive(Subcommand, Debug)]
@@ -38,6 +162,21 @@ pub enum CliCommand {
     Add(AddArgs),
     /// Install packages
     Install(InstallArgs),
+    /// Download every package in the lockfile into the store without creating node_modules
+    Fetch(FetchArgs),
+    /// Print the dependency graph of the lockfile as JSON nodes and edges
+    Graph(GraphArgs),
+    /// List the direct dependencies and their installed versions
+    #[clap(alias = "ls")]
+    List(ListArgs),
+    /// Print the modules directory
+    Root(RootArgs),
+    /// Print the directory that the bins of the dependencies are linked into
+    Bin(BinArgs),
+    /// Run the build scripts of installed packages again
+    Rebuild(RebuildArgs),
+    /// Remove the packages that package.json no longer depends on from node_modules
+    Prune(PruneArgs),
     /// Runs a package's "test" script, if one was provided.
     Test,
     /// Runs a defined package script.
@@ -47,22 +186,180 @@ pub enum CliCommand {
     /// Managing the package store.
     #[clap(subcommand)]
     Store(StoreCommand),
+    /// Managing the package metadata cache.
+    #[clap(subcommand)]
+    Cache(CacheCommand),
 }
 
 impl CliArgs {
     /// Execute the command
     pub async fn run(self) -> miette::Result<()> {
-        let CliArgs { command, dir } = self;
+        let CliArgs {
+            command,
+            dir,
+            workspace_root,
+            loglevel,
+            verbose,
+            quiet,
+            reporter,
+            out,
+            recursive,
+            config: config_path,
+            no_config_merge,
+            store_dir,
+            modules_dir,
+            before,
+            ignore_pnpmfile,
+            no_color,
+            json_errors: _,
+        } = self;
+        let color = use_color(no_color);
+        set_report_hook(color);
+        let dir = if workspace_root { find_workspace_root(dir)? } else { dir };
+        let mut config = match config_path {
+            Some(config_path) => {
+                let config = if no_config_merge {
+                    Npmrc::from_file(&config_path)
+                } else {
+                    Npmrc::from_project_file(&config_path, home::home_dir())
+                };
+                config
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("load the config file {config_path:?}"))?
+            }
+            None => Npmrc::current(env::current_dir, home::home_dir, Default::default),
+        };
+        let current_dir = env::current_dir().into_diagnostic().wrap_err("current directory")?;
+        let project_dir: PathBuf = current_dir.join(&dir).components().collect();
+        config = config.for_project(&current_dir, &project_dir);
+        if let Some(store_dir) = store_dir {
+            config.store_dir = current_dir.join(store_dir).pipe(StoreDir::from);
+        }
+        if let Some(modules_dir) = modules_dir {
+            config.set_modules_dir(current_dir.join(modules_dir));
+        }
+        if let Some(before) = before {
+            config.before = Some(before);
+        }
+        if ignore_pnpmfile {
+            config.ignore_pnpmfile = true;
+        }
+        // the global packages are a project of their own, without a lockfile for now
+        let global = matches!(
+            command,
+            CliCommand::Add(AddArgs { global: true, .. })
+                | CliCommand::List(ListArgs { global: true })
+                | CliCommand::Root(RootArgs { global: true })
+        );
+        if global {
+            config.set_modules_dir(config.global_dir.join("node_modules"));
+            config.lockfile = false;
+        }
+        if let CliCommand::Add(AddArgs { global: true, .. }) = command {
+            fs::create_dir_all(&config.global_dir)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("create the global dir {:?}", config.global_dir))?;
+        }
+        let dir = if global { config.global_dir.clone() } else { dir };
         let manifest_path = || dir.join("package.json");
-        let npmrc = || Npmrc::current(env::current_dir, home::home_dir, Default::default).leak();
-        let state = || State::init(manifest_path(), npmrc()).wrap_err("initialize the state");
+        if let CliCommand::Add(AddArgs { save_workspace_protocol: Some(protocol), .. }) = &command {
+            config.save_workspace_protocol = *protocol;
+        }
+        if let CliCommand::Install(args) = &command {
+            args.apply_to(&mut config);
+        }
+        if let CliCommand::Rebuild(RebuildArgs {
+            child_concurrency: Some(child_concurrency), ..
+        }) = command
+        {
+            config.child_concurrency = child_concurrency;
+        }
+        // only the commands that write into the store settle its layout, the others must work on
+        // a store that they can't write to
+        let writes_store = matches!(
+            command,
+            CliCommand::Install(_)
+                | CliCommand::Add(_)
+                | CliCommand::Fetch(_)
+                | CliCommand::Store(
+                    StoreCommand::Add | StoreCommand::Prune | StoreCommand::Verify { repair: true }
+                )
+        );
+        if writes_store {
+            config
+                .store_dir
+                .init_layout(config.store_layout)
+                .wrap_err("initialize the layout of the store")?;
+        }
+        let config = config.leak();
+        let silent = reporter == ReporterType::Silent;
+        // the status line is shared by every reporter, and by the logs which must not be written
+        // on it
+        let progress = (reporter == ReporterType::Default && !quiet && io::stderr().is_terminal())
+            .then(ProgressReporter::stderr);
+        let reporter = || match reporter {
+            _ if quiet => Box::new(SilentReporter) as Box<dyn Reporter>,
+            ReporterType::Default => match &progress {
+                Some(progress) => Box::new(progress.clone()) as Box<dyn Reporter>,
+                None => Box::new(AppendOnlyReporter::stderr()),
+            },
+            ReporterType::Ndjson => Box::new(NdjsonReporter::stdout()),
+            ReporterType::AppendOnly => Box::new(AppendOnlyReporter::stderr()),
+            ReporterType::Silent => Box::new(SilentReporter),
+            ReporterType::Junit => {
+                let out = out.as_ref().expect("--out is required by --reporter=junit");
+                Box::new(JunitReporter::new(current_dir.join(out)))
+            }
+        };
+        let state =
+            || State::init(manifest_path(), config, reporter()).wrap_err("initialize the state");
+
+        let timings = if env::var_os("TRACE").is_some() {
+            enable_tracing_by_env();
+            None
+        } else {
+            let loglevel = loglevel
+                .or(silent.then_some(LogLevel::Silent))
+                .or(quiet.then_some(LogLevel::Error))
+                .or(verbose.then_some(LogLevel::Debug))
+                .or_else(|| env::var("NPM_CONFIG_LOGLEVEL").ok()?.parse().ok())
+                .unwrap_or(config.loglevel);
+            let max_level = level_filter(loglevel);
+            match &progress {
+                Some(progress) => {
+                    let writer = progress.log_writer();
+                    enable_tracing_by_level(max_level, color, move || writer.clone())
+                }
+                None => enable_tracing_by_level(max_level, color, io::stderr),
+            }
+        };
+
+        let supports_recursive = matches!(
+            command,
+            CliCommand::Install(InstallArgs { check: false, .. }) | CliCommand::Run(_)
+        );
+        if recursive && !supports_recursive {
+            return Err(UnsupportedRecursiveError.into());
+        }
 
         match command {
             CliCommand::Init => {
                 PackageManifest::init(&manifest_path()).wrap_err("initialize package.json")?;
             }
-            CliCommand::Add(args) => args.run(state()?).await?,
+            CliCommand::Add(args) => args.run(state()?, workspace_root).await?,
+            CliCommand::Install(args) if args.check => args.run_check(manifest_path())?,
+            CliCommand::Install(args) if recursive => {
+                // `config` was already rebased onto the workspace root
+                args.run_recursive(&project_dir, &project_dir, config, reporter()).await?
+            }
             CliCommand::Install(args) => args.run(state()?).await?,
+            CliCommand::Fetch(args) => args.run(config, reporter()).await?,
+            CliCommand::Graph(args) => args.run()?,
+            CliCommand::List(args) => args.run(manifest_path(), config)?,
+            CliCommand::Root(args) => args.run(config),
+            CliCommand::Bin(args) => args.run(config),
+            CliCommand::Rebuild(args) => args.run(config, manifest_path(), reporter())?,
+            CliCommand::Prune(args) => args.run(config, manifest_path(), reporter())?,
             CliCommand::Test => {
                 let manifest = PackageManifest::from_path(manifest_path())
                     .wrap_err("getting the package.json in current directory")?;
@@ -71,7 +368,8 @@ impl CliArgs {
                         .wrap_err(format!("executing command: \"{0}\"", script))?;
                 }
             }
-            CliCommand::Run(args) => args.run(manifest_path())?,
+            CliCommand::Run(args) if recursive => args.run_recursive(&project_dir, &*reporter())?,
+            CliCommand::Run(args) => args.run(manifest_path(), &*reporter())?,
             CliCommand::Start => {
                 // Runs an arbitrary command specified in the package's start property of its scripts
                 // object. If no start property is specified on the scripts object, it will attempt to
@@ -86,9 +384,37 @@ impl CliArgs {
                 };
                 execute_shell(command).wrap_err(format!("executing command: \"{0}\"", command))?;
             }
-            CliCommand::Store(command) => command.run(|| npmrc())?,
+            CliCommand::Store(command) => command.run(|| config, reporter).await?,
+            CliCommand::Cache(command) => command.run(|| config)?,
+        }
+
+        if let Some(timings) = timings {
+            timings.report();
         }
 
         Ok(())
     }
 }
+
+/// Find the directory of the closest `pnpm-workspace.yaml` from `dir`, falling back to `dir`
+/// itself when it isn't part of a workspace.
+fn find_workspace_root(dir: PathBuf) -> miette::Result<PathBuf> {
+    let current_dir = env::current_dir().into_diagnostic().wrap_err("current directory")?;
+    let project_dir: PathBuf = current_dir.join(&dir).components().collect();
+    let workspace_root = WorkspaceManifest::find_from_dir(&project_dir)
+        .wrap_err("finding the workspace root")?
+        .map_or(dir, |(workspace_root, _)| workspace_root);
+    Ok(workspace_root)
+}
+
+/// Convert a [`LogLevel`] to the max level of the tracing subscriber.
+fn level_filter(loglevel: LogLevel) -> LevelFilter {
+    match loglevel {
+        LogLevel::Silent => LevelFilter::OFF,
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
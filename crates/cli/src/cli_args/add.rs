@@ -1,7 +1,9 @@
 use crate::State;
 use clap::Args;
-use miette::Context;
-use pacquet_package_manager::Add;
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_npmrc::SaveWorkspaceProtocol;
+use pacquet_package_manager::link_bins;
 use pacquet_package_manifest::DependencyGroup;
 use std::path::PathBuf;
 
@@ -78,30 +80,69 @@ pub struct AddArgs {
     /// All direct and indirect dependencies of the project are linked into this directory
     #[clap(long = "virtual-store-dir", default_value = "node_modules/.pacquet")]
     pub virtual_store_dir: Option<PathBuf>, // TODO: make use of this
+    /// Allow adding dependencies to the root of a workspace without `--workspace-root`.
+    #[clap(long)]
+    pub ignore_workspace_root_check: bool,
+    /// Install the package into node_modules without adding it to package.json.
+    #[clap(long)]
+    pub no_save: bool,
+    /// How a project of the workspace is saved: `rolling` (`workspace:^`), `true`
+    /// (`workspace:^1.0.0`), or `false` (`^1.0.0`), overriding `save-workspace-protocol` of
+    /// `.npmrc`.
+    #[clap(long, value_name = "VALUE")]
+    pub save_workspace_protocol: Option<SaveWorkspaceProtocol>,
+    /// Install the package into `global-dir` and link its bins into `global-bin-dir`, instead of
+    /// adding it to the current project.
+    #[clap(short = 'g', long)]
+    pub global: bool,
 }
 
+/// Error when `pacquet add` would add a dependency to the root of a workspace by accident.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Running this command will add the dependency to the workspace root, which might not be what you want")]
+#[diagnostic(
+    code(pacquet_cli::adding_to_root),
+    help(
+        "If you really want it in the root, run the command again with -w/--workspace-root, \
+         or with --ignore-workspace-root-check, \
+         or set `ignore-workspace-root-check=true` in .npmrc"
+    )
+)]
+pub struct AddingToRootError;
+
 impl AddArgs {
     /// Execute the subcommand.
-    pub async fn run(self, mut state: State) -> miette::Result<()> {
+    ///
+    /// `workspace_root` is whether `-w/--workspace-root` was passed.
+    pub async fn run(self, mut state: State, workspace_root: bool) -> miette::Result<()> {
         // TODO: if a package already exists in another dependency group, don't remove the existing entry.
 
-        let State { tarball_mem_cache, http_client, config, manifest, lockfile, resolved_packages } =
-            &mut state;
-
-        Add {
-            tarball_mem_cache,
-            http_client,
-            config,
-            manifest,
-            lockfile: lockfile.as_ref(),
-            list_dependency_groups: || self.dependency_options.dependency_groups(),
-            package_name: &self.package_name,
-            save_exact: self.save_exact,
-            resolved_packages,
+        let State { package_manager, project_dir, workspace_dir } = &mut state;
+
+        let is_workspace_root = workspace_dir.as_ref() == Some(project_dir);
+        let root_check_ignored = workspace_root
+            || self.ignore_workspace_root_check
+            || package_manager.config.ignore_workspace_root_check;
+        if is_workspace_root && !root_check_ignored {
+            return Err(AddingToRootError.into());
+        }
+
+        let save = !self.no_save && package_manager.config.save;
+        let dependency_groups: Vec<_> = self.dependency_options.dependency_groups().collect();
+        package_manager
+            .add(&self.package_name, &dependency_groups, self.save_exact, save)
+            .await
+            .wrap_err("adding a new package")?;
+
+        if self.global {
+            let config = package_manager.config;
+            for (name, _) in package_manager.manifest.dependencies([DependencyGroup::Prod]) {
+                link_bins(&config.modules_dir.join(name), &config.global_bin_dir)
+                    .wrap_err_with(|| format!("linking the bins of {name}"))?;
+            }
         }
-        .run()
-        .await
-        .wrap_err("adding a new package")
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,20 @@
+use clap::Args;
+use pacquet_npmrc::Npmrc;
+
+#[derive(Debug, Args)]
+pub struct BinArgs {
+    /// Print the directory that the bins of the global packages are linked into instead.
+    #[clap(short = 'g', long)]
+    pub global: bool,
+}
+
+impl BinArgs {
+    /// Execute the subcommand.
+    pub fn run(self, config: &Npmrc) {
+        let bin_dir = match self.global {
+            true => config.global_bin_dir.clone(),
+            false => config.modules_dir.join(".bin"),
+        };
+        println!("{}", bin_dir.display());
+    }
+}
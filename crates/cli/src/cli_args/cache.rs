@@ -0,0 +1,62 @@
+use clap::Subcommand;
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackumentCache;
+use std::time::Duration;
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Removes all cached package metadata.
+    Clean,
+    /// Checks that the cached package metadata can be parsed and reports entries older than
+    /// `modules-cache-max-age`.
+    Verify,
+    /// Returns the path to the metadata cache directory.
+    Dir,
+}
+
+/// Error when `pacquet cache verify` finds entries that can't be parsed.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{count} cached metadata entries are corrupted")]
+#[diagnostic(code(pacquet_cli::corrupted_cache), help("Run `pacquet cache clean` to remove them"))]
+pub struct CorruptedCacheError {
+    pub count: usize,
+}
+
+impl CacheCommand {
+    /// Execute the subcommand.
+    pub fn run<'a>(self, config: impl FnOnce() -> &'a Npmrc) -> miette::Result<()> {
+        let config = config();
+        let cache = PackumentCache::new(&config.cache_dir);
+        match self {
+            CacheCommand::Clean => {
+                cache.clean().wrap_err("cleaning the metadata cache")?;
+            }
+            CacheCommand::Verify => {
+                let max_age = Duration::from_secs(config.modules_cache_max_age * 60);
+                let report = cache.verify(max_age).wrap_err("verifying the metadata cache")?;
+                for path in &report.stale {
+                    println!("stale: {}", path.display());
+                }
+                for path in &report.invalid {
+                    println!("corrupted: {}", path.display());
+                }
+                println!(
+                    "{} valid, {} stale, {} corrupted",
+                    report.valid.len(),
+                    report.stale.len(),
+                    report.invalid.len(),
+                );
+                if !report.invalid.is_empty() {
+                    return Err(CorruptedCacheError { count: report.invalid.len() }.into());
+                }
+            }
+            CacheCommand::Dir => {
+                println!("{}", cache.root().display());
+            }
+        }
+
+        Ok(())
+    }
+}
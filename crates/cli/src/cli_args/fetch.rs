@@ -0,0 +1,45 @@
+use clap::Args;
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_lockfile::Lockfile;
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::{create_http_client, Fetch};
+use pacquet_reporter::Reporter;
+
+#[derive(Debug, Args)]
+pub struct FetchArgs {}
+
+/// Error when `pacquet fetch` or `pacquet store status` can't find a lockfile.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("No lockfile found in the current directory")]
+#[diagnostic(
+    code(pacquet_cli::no_lockfile),
+    help("This command only reads pnpm-lock.yaml, run `pacquet install` to create it")
+)]
+pub struct NoLockfileError;
+
+impl FetchArgs {
+    /// Execute the subcommand.
+    pub async fn run(
+        self,
+        config: &'static Npmrc,
+        reporter: Box<dyn Reporter>,
+    ) -> miette::Result<()> {
+        let FetchArgs {} = self;
+        let Lockfile { packages, .. } = Lockfile::load_from_current_dir()
+            .wrap_err("loading the lockfile")?
+            .ok_or(NoLockfileError)?;
+
+        Fetch {
+            http_client: &create_http_client(config),
+            config,
+            reporter: &*reporter,
+            packages: packages.as_ref(),
+        }
+        .run()
+        .await
+        .wrap_err("fetching packages")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::cli_args::fetch::NoLockfileError;
+use clap::Args;
+use miette::{Context, IntoDiagnostic};
+use pacquet_lockfile::Lockfile;
+
+#[derive(Debug, Args)]
+pub struct GraphArgs {}
+
+impl GraphArgs {
+    /// Execute the subcommand.
+    pub fn run(self) -> miette::Result<()> {
+        let GraphArgs {} = self;
+        let lockfile = Lockfile::load_from_current_dir()
+            .wrap_err("loading the lockfile")?
+            .ok_or(NoLockfileError)?;
+        let graph = serde_json::to_string_pretty(&lockfile.dependency_graph())
+            .into_diagnostic()
+            .wrap_err("serializing the dependency graph")?;
+        println!("{graph}");
+        Ok(())
+    }
+}
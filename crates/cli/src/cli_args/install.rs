@@ -1,9 +1,50 @@
 use crate::State;
-use clap::Args;
-use pacquet_package_manager::Install;
-use pacquet_package_manifest::DependencyGroup;
+use clap::{Args, ValueEnum};
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_diagnostics::tracing;
+use pacquet_lockfile::{Lockfile, OutdatedLockfileError, RootProjectSnapshot};
+use pacquet_network::{RegistryUnreachableError, ThrottledClient};
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::{
+    create_http_client, is_fetchable, Install, InstallError, InstallPlan, InstallPlanSummary,
+    InstallReport, PlannedPackage, ResolvedPackages, TarballUrlCache,
+};
+use pacquet_package_manifest::{DependencyGroup, PackageManifest, PackageManifestError};
+use pacquet_reporter::Reporter;
+use pacquet_tarball::MemCache;
+use pacquet_workspace::WorkspaceManifest;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-#[derive(Debug, Args)]
+/// Timeout of the request of `--check-registry` when `network-timeout` isn't set.
+const CHECK_REGISTRY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Type of the dependencies of `package.json`, the value of `--include` and `--omit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DependencyType {
+    /// `dependencies`.
+    Prod,
+    /// `devDependencies`.
+    Dev,
+    /// `optionalDependencies`.
+    Optional,
+}
+
+impl From<DependencyType> for DependencyGroup {
+    fn from(dependency_type: DependencyType) -> Self {
+        match dependency_type {
+            DependencyType::Prod => DependencyGroup::Prod,
+            DependencyType::Dev => DependencyGroup::Dev,
+            DependencyType::Optional => DependencyGroup::Optional,
+        }
+    }
+}
+
+#[derive(Debug, Default, Args)]
 pub struct InstallDependencyOptions {
     /// pacquet will not install any package listed in devDependencies and will remove those insofar
     /// they were already installed, if the NODE_ENV environment variable is set to production.
@@ -18,21 +59,30 @@ pub struct InstallDependencyOptions {
     /// optionalDependencies are not installed.
     #[arg(long)]
     no_optional: bool,
+    /// Only install these types of dependencies, e.g. `--include=prod,optional`. Replaces the
+    /// selection of --prod, --dev, and --no-optional.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    include: Vec<DependencyType>,
+    /// Don't install these types of dependencies, e.g. `--omit=dev`. Wins over --include.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    omit: Vec<DependencyType>,
 }
 
 impl InstallDependencyOptions {
     /// Convert the dependency options to an iterator of [`DependencyGroup`]
     /// which filters the types of dependencies to install.
     fn dependency_groups(&self) -> impl Iterator<Item = DependencyGroup> {
-        let &InstallDependencyOptions { prod, dev, no_optional } = self;
+        let InstallDependencyOptions { prod, dev, no_optional, include, omit } = self;
         let has_both = prod == dev;
-        let has_prod = has_both || prod;
-        let has_dev = has_both || dev;
-        let has_optional = !no_optional;
+        let is_included = |dependency_type: DependencyType, selected: bool| {
+            let selected =
+                if include.is_empty() { selected } else { include.contains(&dependency_type) };
+            (selected && !omit.contains(&dependency_type)).then_some(dependency_type.into())
+        };
         std::iter::empty()
-            .chain(has_prod.then_some(DependencyGroup::Prod))
-            .chain(has_dev.then_some(DependencyGroup::Dev))
-            .chain(has_optional.then_some(DependencyGroup::Optional))
+            .chain(is_included(DependencyType::Prod, has_both || *prod))
+            .chain(is_included(DependencyType::Dev, has_both || *dev))
+            .chain(is_included(DependencyType::Optional, !no_optional))
     }
 }
 
@@ -43,31 +93,369 @@ pub struct InstallArgs {
     pub dependency_options: InstallDependencyOptions,
 
     /// Don't generate a lockfile and fail if the lockfile is outdated.
-    #[clap(long)]
+    ///
+    /// This is the default when a CI environment is detected (`CI` or `CONTINUOUS_INTEGRATION`).
+    #[clap(long, overrides_with = "no_frozen_lockfile")]
     pub frozen_lockfile: bool,
+
+    /// Allow the lockfile to be updated even when a CI environment is detected.
+    #[clap(long, overrides_with = "frozen_lockfile")]
+    pub no_frozen_lockfile: bool,
+
+    /// Only check whether the lockfile is up to date with package.json, without resolving,
+    /// downloading, or writing anything. Exit with an error listing the differences if it isn't.
+    #[clap(long)]
+    pub check: bool,
+
+    /// With --recursive, stop at the first project that fails to install instead of installing
+    /// the remaining projects and reporting every failure at the end.
+    #[clap(long)]
+    pub bail: bool,
+
+    /// Resolve the dependencies and print the packages that would be added, reused, and removed,
+    /// without downloading tarballs or writing to node_modules and the store.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// How many levels of transitive dependencies to install: 0 only installs the dependencies
+    /// listed in package.json. The whole tree is installed by default.
+    #[clap(long)]
+    pub depth: Option<usize>,
+
+    /// Rebuild a node_modules that was built with different settings even when the lockfile is
+    /// frozen. Also `force=true` of `.npmrc`.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Remove the packages that are no longer depended on from node_modules after installing.
+    /// This is the default.
+    #[clap(long, overrides_with = "no_prune")]
+    pub prune: bool,
+
+    /// Keep the packages that are no longer depended on in node_modules.
+    #[clap(long, overrides_with = "prune")]
+    pub no_prune: bool,
+
+    /// Use the cached package metadata and the packages in the store whenever they are
+    /// available, and only request the registry for what is missing.
+    #[clap(long)]
+    pub prefer_offline: bool,
+
+    /// Check the downloaded tarballs and the files of the store before importing them. This is
+    /// the default. Overrides `verify-store-integrity` of `.npmrc`.
+    #[clap(long, overrides_with = "no_verify_store_integrity")]
+    pub verify_store_integrity: bool,
+
+    /// Trust the registry and the store entirely and skip every hash verification, which is
+    /// faster but lets a tampered tarball or a corrupt store go unnoticed. Only meant for stores
+    /// that nothing else writes into, such as the trusted caches of a CI.
+    #[clap(long, overrides_with = "verify_store_integrity")]
+    pub no_verify_store_integrity: bool,
+
+    /// Abort the install once the requests in flight have made no progress for this many
+    /// milliseconds. Overrides `network-timeout` of `.npmrc`.
+    #[clap(long, value_name = "MS")]
+    pub network_timeout: Option<u64>,
+
+    /// Send a HEAD request to the registry before installing, and fail right away if it is
+    /// unreachable rather than on the first package. Skipped with --prefer-offline.
+    #[clap(long)]
+    pub check_registry: bool,
+
+    /// Check the `engines` field of the packages against this version of Node.js instead of the
+    /// `node` on the `PATH`. Overrides `use-node-version` of `.npmrc`.
+    #[clap(long, value_name = "VERSION")]
+    pub use_node_version: Option<String>,
+}
+
+/// Error type of [`InstallArgs::run_check`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum CheckLockfileError {
+    #[display("No lockfile found in the current directory")]
+    #[diagnostic(code(pacquet_cli::no_lockfile))]
+    NotFound,
+
+    #[display("Checking a lockfile with multiple projects is not yet supported")]
+    #[diagnostic(code(pacquet_cli::multi_project_lockfile))]
+    MultiProject,
+
+    #[diagnostic(transparent)]
+    Outdated(#[error(source)] OutdatedLockfileError),
+}
+
+/// Error type of [`InstallArgs::run_recursive`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum RecursiveInstallError {
+    #[display("No pnpm-workspace.yaml found in {}", _0.display())]
+    #[diagnostic(code(pacquet_cli::no_workspace))]
+    NoWorkspace(#[error(not(source))] PathBuf),
+
+    #[display("Installing a workspace with a lockfile isn't supported yet")]
+    #[diagnostic(
+        code(pacquet_cli::unsupported_workspace_lockfile),
+        help("Set `lockfile=false` in .npmrc to install the projects without a lockfile")
+    )]
+    UnsupportedLockfile,
+
+    #[display("Failed to install {} of {total} projects", failures.len())]
+    #[diagnostic(code(pacquet_cli::recursive_install))]
+    Failed {
+        total: usize,
+        #[error(not(source))]
+        #[related]
+        failures: Vec<InstallProjectError>,
+    },
+}
+
+/// Error when installing a single project of a workspace.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallProjectError {
+    #[display("Failed to load the manifest of {}", project_dir.display())]
+    #[diagnostic(code(pacquet_cli::load_project_manifest))]
+    LoadManifest {
+        project_dir: PathBuf,
+        #[error(source)]
+        error: PackageManifestError,
+    },
+
+    #[display("Failed to install {}", project_dir.display())]
+    #[diagnostic(code(pacquet_cli::install_project))]
+    Install {
+        project_dir: PathBuf,
+        #[error(source)]
+        error: InstallError,
+    },
+}
+
+/// Fail right away if the registry of `config` is unreachable, see `--check-registry`.
+async fn check_registry_of(
+    http_client: &ThrottledClient,
+    config: &Npmrc,
+) -> Result<(), RegistryUnreachableError> {
+    if config.prefer_offline {
+        tracing::debug!(target: "pacquet::install", "Skip the registry check with prefer-offline");
+        return Ok(());
+    }
+    let timeout = config.network_timeout.map_or(CHECK_REGISTRY_TIMEOUT, Duration::from_millis);
+    http_client.check_registry(&config.registry, timeout).await
 }
 
 impl InstallArgs {
+    /// Override the settings of `config` with the flags that were passed.
+    pub fn apply_to(&self, config: &mut Npmrc) {
+        if self.prefer_offline {
+            config.prefer_offline = true;
+        }
+        if self.verify_store_integrity {
+            config.verify_store_integrity = true;
+        }
+        if self.no_verify_store_integrity {
+            config.verify_store_integrity = false;
+        }
+        if self.force {
+            config.force = true;
+        }
+        if let Some(network_timeout) = self.network_timeout {
+            config.network_timeout = Some(network_timeout);
+        }
+        if let Some(use_node_version) = &self.use_node_version {
+            config.use_node_version = Some(use_node_version.clone());
+        }
+    }
+
+    /// Whether to install with a frozen lockfile, taking the CI environment into account.
+    ///
+    /// The CI default doesn't apply to a `lockfile` that can't be installed frozen, such as one
+    /// with local tarballs.
+    fn frozen_lockfile(&self, lockfile: Option<&Lockfile>) -> bool {
+        let can_be_frozen = lockfile.map_or(true, is_fetchable);
+        self.frozen_lockfile || (!self.no_frozen_lockfile && is_ci() && can_be_frozen)
+    }
+
     pub async fn run(self, state: State) -> miette::Result<()> {
-        let State { tarball_mem_cache, http_client, config, manifest, lockfile, resolved_packages } =
-            &state;
-        let InstallArgs { dependency_options, frozen_lockfile } = self;
-
-        Install {
-            tarball_mem_cache,
-            http_client,
-            config,
-            manifest,
-            lockfile: lockfile.as_ref(),
-            dependency_groups: dependency_options.dependency_groups(),
-            frozen_lockfile,
-            resolved_packages,
+        let State { mut package_manager, .. } = state;
+        let frozen_lockfile = self.frozen_lockfile(package_manager.lockfile.as_ref());
+        let InstallArgs { dependency_options, dry_run, depth, no_prune, check_registry, .. } = self;
+        if check_registry {
+            check_registry_of(&package_manager.http_client, package_manager.config).await?;
+        }
+        package_manager.depth = depth;
+        package_manager.prune = !no_prune;
+        let dependency_groups = dependency_options.dependency_groups();
+
+        if dry_run {
+            let plan = package_manager
+                .plan_install(dependency_groups, frozen_lockfile)
+                .await
+                .wrap_err("installing dependencies")?;
+            print_install_plan(&plan.summarize(&package_manager.config.virtual_store_dir));
+        } else {
+            let report = package_manager
+                .install(dependency_groups, frozen_lockfile)
+                .await
+                .wrap_err("installing dependencies")?;
+            print_install_report(&report);
         }
-        .run()
-        .await;
 
         Ok(())
     }
+
+    /// Execute `pacquet --recursive install`.
+    ///
+    /// Every project of the workspace at `workspace_root` is installed into its own `node_modules`,
+    /// sharing the store, the HTTP client and the tarball cache. `config_dir` is the directory
+    /// `config` was loaded from, its paths are rebased onto each project.
+    ///
+    /// The workspace lockfile can't be read or written yet, so this fails when the lockfile is
+    /// enabled rather than creating one lockfile per project.
+    pub async fn run_recursive(
+        self,
+        workspace_root: &Path,
+        config_dir: &Path,
+        config: &'static Npmrc,
+        reporter: Box<dyn Reporter>,
+    ) -> miette::Result<()> {
+        if config.lockfile {
+            return Err(RecursiveInstallError::UnsupportedLockfile.into());
+        }
+        let frozen_lockfile = self.frozen_lockfile(None);
+        let InstallArgs {
+            dependency_options, bail, dry_run, depth, no_prune, check_registry, ..
+        } = self;
+        let workspace_manifest = WorkspaceManifest::load_from_dir(workspace_root)
+            .wrap_err("loading pnpm-workspace.yaml")?
+            .ok_or_else(|| RecursiveInstallError::NoWorkspace(workspace_root.to_path_buf()))?;
+        let projects = workspace_manifest
+            .find_projects(workspace_root)
+            .wrap_err("finding the projects of the workspace")?;
+
+        let http_client = create_http_client(config);
+        if check_registry {
+            check_registry_of(&http_client, config).await?;
+        }
+        let tarball_mem_cache = MemCache::new();
+        let total = projects.len();
+        let mut failures = Vec::new();
+        let project_configs = projects
+            .iter()
+            .map(|project_dir| config.for_project(config_dir, project_dir))
+            .collect::<Vec<_>>()
+            .leak();
+
+        for (project_dir, config) in projects.into_iter().zip(&*project_configs) {
+            tracing::info!(target: "pacquet::install", project = ?project_dir, "Install project");
+            let plan = dry_run.then(InstallPlan::new);
+            if plan.is_some() {
+                println!("{}:", project_dir.display());
+            }
+            let result = match PackageManifest::from_path(project_dir.join("package.json")) {
+                Err(error) => Err(InstallProjectError::LoadManifest { project_dir, error }),
+                Ok(manifest) => Install {
+                    tarball_mem_cache: &tarball_mem_cache,
+                    http_client: &http_client,
+                    config,
+                    reporter: &*reporter,
+                    manifest: &manifest,
+                    workspace_manifest: Some(&workspace_manifest),
+                    workspace_dir: Some(workspace_root),
+                    lockfile: None,
+                    dependency_groups: dependency_options.dependency_groups(),
+                    frozen_lockfile,
+                    resolved_packages: &ResolvedPackages::new(),
+                    tarball_url_cache: &TarballUrlCache::new(),
+                    dry_run: plan.as_ref(),
+                    package_hook: None,
+                    depth,
+                    prune: !no_prune,
+                }
+                .run()
+                .await
+                .map_err(|error| InstallProjectError::Install { project_dir, error }),
+            };
+            if let (Ok(()), Some(plan)) = (&result, plan) {
+                print_install_plan(&plan.summarize(&config.virtual_store_dir));
+            }
+
+            match result {
+                Ok(()) => {}
+                Err(error) if bail => return Err(error.into()),
+                Err(error) => failures.push(error),
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        Err(RecursiveInstallError::Failed { total, failures }.into())
+    }
+
+    /// Execute `pacquet install --check`.
+    pub fn run_check(self, manifest_path: PathBuf) -> miette::Result<()> {
+        let manifest = PackageManifest::from_path(manifest_path)
+            .wrap_err("getting the package.json in current directory")?;
+        let lockfile = Lockfile::load_from_current_dir()
+            .wrap_err("loading the lockfile")?
+            .ok_or(CheckLockfileError::NotFound)?;
+        let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+            return Err(CheckLockfileError::MultiProject.into());
+        };
+
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+        let drift = project_snapshot.drift_from_manifest(&manifest, groups);
+        if drift.is_empty() {
+            return Ok(());
+        }
+
+        Err(CheckLockfileError::Outdated(OutdatedLockfileError { drift }).into())
+    }
+}
+
+/// Whether the current process runs in a CI environment, in which case the lockfile is frozen by
+/// default.
+fn is_ci() -> bool {
+    let is_set = |name| env::var(name).is_ok_and(|value| !matches!(value.as_str(), "" | "false"));
+    is_set("CI") || is_set("CONTINUOUS_INTEGRATION")
+}
+
+/// Log the summary of a finished `pacquet install`. The warnings themselves were already logged
+/// as they occurred.
+fn print_install_report(report: &InstallReport) {
+    let InstallReport { warnings, added, removed, reused } = report;
+    tracing::info!(
+        target: "pacquet::install",
+        "Packages: +{added} -{removed}, {reused} reused from the store, {} warnings",
+        warnings.len(),
+    );
+}
+
+/// Print the result of `pacquet install --dry-run`.
+fn print_install_plan(summary: &InstallPlanSummary) {
+    let InstallPlanSummary { added, reused, removed, added_size } = summary;
+    let print_packages = |prefix: &str, packages: &[PlannedPackage]| {
+        for PlannedPackage { name, version, .. } in packages {
+            println!("{prefix} {name} {version}");
+        }
+    };
+
+    println!("Packages to add: {}", added.len());
+    print_packages("+", added);
+    println!("Packages to reuse: {}", reused.len());
+    print_packages("=", reused);
+    println!("Packages to remove: {}", removed.len());
+    for virtual_store_name in removed {
+        println!("- {virtual_store_name}");
+    }
+
+    let unknown = added.iter().filter(|package| package.unpacked_size.is_none()).count();
+    if unknown == 0 {
+        println!("Total size: {added_size} bytes");
+    } else {
+        println!("Total size: {added_size} bytes (unknown for {unknown} packages)");
+    }
 }
 
 #[cfg(test)]
@@ -76,6 +464,43 @@ mod tests {
     use pacquet_package_manifest::DependencyGroup;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn apply_to_should_override_the_config() {
+        use crate::cli_args::{CliArgs, CliCommand};
+        use clap::Parser;
+        let install_args = |args: &[&str]| {
+            let args = ["pacquet", "install"].iter().chain(args);
+            match CliArgs::parse_from(args).command {
+                CliCommand::Install(install_args) => install_args,
+                command => panic!("unexpected command: {command:?}"),
+            }
+        };
+
+        let mut config = Npmrc::new();
+        install_args(&[]).apply_to(&mut config);
+        assert_eq!(
+            (config.prefer_offline, config.force, config.network_timeout),
+            (false, false, None)
+        );
+
+        install_args(&[
+            "--prefer-offline",
+            "--force",
+            "--no-verify-store-integrity",
+            "--network-timeout=500",
+            "--use-node-version=18.0.0",
+        ])
+        .apply_to(&mut config);
+        assert!(config.prefer_offline);
+        assert!(config.force);
+        assert!(!config.verify_store_integrity);
+        assert_eq!(config.network_timeout, Some(500));
+        assert_eq!(config.use_node_version.as_deref(), Some("18.0.0"));
+
+        install_args(&["--verify-store-integrity"]).apply_to(&mut config);
+        assert!(config.verify_store_integrity);
+    }
+
     #[test]
     fn dependency_options_to_dependency_groups() {
         use DependencyGroup::{Dev, Optional, Prod};
@@ -84,50 +509,129 @@ mod tests {
 
         // no flags -> prod + dev + optional
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: false, dev: false, no_optional: false }),
+            create_list(InstallDependencyOptions {
+                prod: false,
+                dev: false,
+                no_optional: false,
+                ..Default::default()
+            }),
             [Prod, Dev, Optional],
         );
 
         // --prod -> prod + optional
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: true, dev: false, no_optional: false }),
+            create_list(InstallDependencyOptions {
+                prod: true,
+                dev: false,
+                no_optional: false,
+                ..Default::default()
+            }),
             [Prod, Optional],
         );
 
         // --dev -> dev + optional
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: false, dev: true, no_optional: false }),
+            create_list(InstallDependencyOptions {
+                prod: false,
+                dev: true,
+                no_optional: false,
+                ..Default::default()
+            }),
             [Dev, Optional],
         );
 
         // --no-optional -> prod + dev
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: false, dev: false, no_optional: true }),
+            create_list(InstallDependencyOptions {
+                prod: false,
+                dev: false,
+                no_optional: true,
+                ..Default::default()
+            }),
             [Prod, Dev],
         );
 
         // --prod --no-optional -> prod
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: true, dev: false, no_optional: true }),
+            create_list(InstallDependencyOptions {
+                prod: true,
+                dev: false,
+                no_optional: true,
+                ..Default::default()
+            }),
             [Prod],
         );
 
         // --dev --no-optional -> dev
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: false, dev: true, no_optional: true }),
+            create_list(InstallDependencyOptions {
+                prod: false,
+                dev: true,
+                no_optional: true,
+                ..Default::default()
+            }),
             [Dev],
         );
 
         // --prod --dev -> prod + dev + optional
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: true, dev: true, no_optional: false }),
+            create_list(InstallDependencyOptions {
+                prod: true,
+                dev: true,
+                no_optional: false,
+                ..Default::default()
+            }),
             [Prod, Dev, Optional],
         );
 
         // --prod --dev --no-optional -> prod + dev
         assert_eq!(
-            create_list(InstallDependencyOptions { prod: true, dev: true, no_optional: true }),
+            create_list(InstallDependencyOptions {
+                prod: true,
+                dev: true,
+                no_optional: true,
+                ..Default::default()
+            }),
             [Prod, Dev],
         );
     }
+
+    #[test]
+    fn include_and_omit_to_dependency_groups() {
+        use DependencyGroup::{Dev, Optional, Prod};
+        let create_list = |include: &[DependencyType], omit: &[DependencyType]| {
+            let options = InstallDependencyOptions {
+                include: include.to_vec(),
+                omit: omit.to_vec(),
+                ..Default::default()
+            };
+            options.dependency_groups().collect::<Vec<_>>()
+        };
+
+        // --include=prod,optional -> prod + optional
+        assert_eq!(
+            create_list(&[DependencyType::Prod, DependencyType::Optional], &[]),
+            [Prod, Optional]
+        );
+
+        // --omit=dev -> prod + optional
+        assert_eq!(create_list(&[], &[DependencyType::Dev]), [Prod, Optional]);
+
+        // --include=dev --omit=optional -> dev
+        assert_eq!(create_list(&[DependencyType::Dev], &[DependencyType::Optional]), [Dev]);
+
+        // --include=dev,prod --omit=dev -> prod
+        assert_eq!(
+            create_list(&[DependencyType::Dev, DependencyType::Prod], &[DependencyType::Dev]),
+            [Prod]
+        );
+
+        // --include replaces --prod
+        let options = InstallDependencyOptions {
+            prod: true,
+            include: vec![DependencyType::Dev],
+            ..Default::default()
+        };
+        assert_eq!(options.dependency_groups().collect::<Vec<_>>(), [Dev]);
+    }
 }
@@ -0,0 +1,55 @@
+use clap::Args;
+use miette::Context;
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use serde_json::Value;
+use std::{fs, path::PathBuf};
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// List the global packages of `global-dir` instead of the dependencies of the project.
+    #[clap(short = 'g', long)]
+    pub global: bool,
+}
+
+impl ListArgs {
+    /// Execute the subcommand.
+    ///
+    /// Print the direct dependencies of `package.json` by group, with the versions that are
+    /// installed in [`modules_dir`](Npmrc::modules_dir).
+    pub fn run(self, manifest_path: PathBuf, config: &Npmrc) -> miette::Result<()> {
+        // a global dir without packages may not have a package.json yet
+        if self.global && !manifest_path.exists() {
+            return Ok(());
+        }
+        let manifest =
+            PackageManifest::from_path(manifest_path).wrap_err("getting the package.json")?;
+
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+        let mut sections = Vec::new();
+        for group in groups {
+            let mut dependencies: Vec<_> = manifest.dependencies([group]).collect();
+            if dependencies.is_empty() {
+                continue;
+            }
+            dependencies.sort();
+            let lines = dependencies.into_iter().map(|(name, spec)| {
+                let manifest_path = config.modules_dir.join(name).join("package.json");
+                let version = fs::read_to_string(manifest_path)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+                    .and_then(|manifest| Some(manifest.get("version")?.as_str()?.to_string()));
+                match version {
+                    Some(version) => format!("{name} {version}"),
+                    None => format!("{name} {spec} (not installed)"),
+                }
+            });
+            let header: &str = group.into();
+            sections.push(format!("{header}:\n{}", lines.collect::<Vec<_>>().join("\n")));
+        }
+        if !sections.is_empty() {
+            println!("{}", sections.join("\n\n"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use clap::Args;
+use miette::Context;
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::PruneModulesDir;
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use pacquet_reporter::Reporter;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct PruneArgs {
+    /// Also remove the devDependencies.
+    #[clap(long)]
+    pub prod: bool,
+
+    /// Also remove the optionalDependencies.
+    #[clap(long)]
+    pub no_optional: bool,
+}
+
+impl PruneArgs {
+    /// The dependency groups of `package.json` to keep.
+    fn dependency_groups(&self) -> impl Iterator<Item = DependencyGroup> {
+        let &PruneArgs { prod, no_optional } = self;
+        std::iter::once(DependencyGroup::Prod)
+            .chain((!prod).then_some(DependencyGroup::Dev))
+            .chain((!no_optional).then_some(DependencyGroup::Optional))
+    }
+
+    /// Execute the subcommand.
+    ///
+    /// The packages that are kept are those that the direct dependencies link to in
+    /// `node_modules`, that is the graph that the last install resolved, from the lockfile or
+    /// from the registry. Nothing is downloaded.
+    pub fn run(
+        self,
+        config: &'static Npmrc,
+        manifest_path: PathBuf,
+        reporter: Box<dyn Reporter>,
+    ) -> miette::Result<()> {
+        let manifest = PackageManifest::from_path(manifest_path)
+            .wrap_err("getting the package.json in current directory")?;
+
+        PruneModulesDir {
+            config,
+            reporter: &*reporter,
+            manifest: &manifest,
+            dependency_groups: self.dependency_groups(),
+        }
+        .run()
+        .wrap_err("pruning node_modules")
+    }
+}
@@ -0,0 +1,37 @@
+use clap::Args;
+use miette::Context;
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::Rebuild;
+use pacquet_package_manifest::PackageManifest;
+use pacquet_reporter::Reporter;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct RebuildArgs {
+    /// Names of the packages to rebuild. Every installed package with build scripts is rebuilt
+    /// when none is given.
+    pub packages: Vec<String>,
+
+    /// The maximum number of packages whose build scripts run at the same time, overriding
+    /// `child-concurrency` of `.npmrc`.
+    #[clap(long)]
+    pub child_concurrency: Option<usize>,
+}
+
+impl RebuildArgs {
+    /// Execute the subcommand.
+    pub fn run(
+        self,
+        config: &'static Npmrc,
+        manifest_path: PathBuf,
+        reporter: Box<dyn Reporter>,
+    ) -> miette::Result<()> {
+        let RebuildArgs { packages, .. } = self;
+        let manifest = PackageManifest::from_path(manifest_path)
+            .wrap_err("getting the package.json in current directory")?;
+
+        Rebuild { config, manifest: &manifest, packages: &packages, reporter: &*reporter }
+            .run()
+            .wrap_err("rebuilding packages")
+    }
+}
@@ -0,0 +1,19 @@
+use clap::Args;
+use pacquet_npmrc::Npmrc;
+
+#[derive(Debug, Args)]
+pub struct RootArgs {
+    /// Print the modules directory of the global packages instead.
+    #[clap(short = 'g', long)]
+    pub global: bool,
+}
+
+impl RootArgs {
+    /// Execute the subcommand.
+    ///
+    /// The global modules directory is already in [`modules_dir`](Npmrc::modules_dir) when
+    /// `--global` is set.
+    pub fn run(self, config: &Npmrc) {
+        println!("{}", config.modules_dir.display());
+    }
+}
@@ -1,8 +1,16 @@
 use clap::Args;
-use miette::Context;
-use pacquet_executor::execute_shell;
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_diagnostics::tracing;
+use pacquet_executor::{execute_script, ExecutorError};
 use pacquet_package_manifest::PackageManifest;
-use std::path::PathBuf;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_workspace::WorkspaceManifest;
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 #[derive(Debug, Args)]
 pub struct RunArgs {
@@ -17,25 +25,140 @@ pub struct RunArgs {
     /// execution chain.
     #[clap(long)]
     pub if_present: bool,
+
+    /// With --recursive, stop at the first project whose script fails instead of running the
+    /// script of the remaining projects and reporting every failure at the end.
+    #[clap(long)]
+    pub bail: bool,
+}
+
+/// Error when a script exits with a failure.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("The {script} script of {package} failed")]
+#[diagnostic(code(pacquet_cli::run_script))]
+pub struct RunScriptError {
+    pub package: String,
+    pub script: String,
+    #[error(source)]
+    pub error: ExecutorError,
+}
+
+/// Error of `pacquet -r run`.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum RecursiveRunError {
+    #[display("No pnpm-workspace.yaml found in {}", _0.display())]
+    #[diagnostic(code(pacquet_cli::no_workspace))]
+    NoWorkspace(#[error(not(source))] PathBuf),
+
+    #[display("None of the projects of the workspace has a {_0:?} script")]
+    #[diagnostic(code(pacquet_cli::no_script))]
+    NoScript(#[error(not(source))] String),
+
+    #[display("The {script} script failed in {} of {total} projects", failures.len())]
+    #[diagnostic(code(pacquet_cli::recursive_run))]
+    Failed {
+        script: String,
+        total: usize,
+        #[error(not(source))]
+        #[related]
+        failures: Vec<RunScriptError>,
+    },
 }
 
 impl RunArgs {
     /// Execute the subcommand.
-    pub fn run(self, manifest_path: PathBuf) -> miette::Result<()> {
-        let RunArgs { command, args, if_present } = self;
+    pub fn run(self, manifest_path: PathBuf, reporter: &dyn Reporter) -> miette::Result<()> {
+        let RunArgs { command, args, if_present, .. } = self;
 
         let manifest = PackageManifest::from_path(manifest_path)
             .wrap_err("getting the package.json in current directory")?;
 
         if let Some(script) = manifest.script(&command, if_present)? {
-            let mut command = script.to_string();
-            // append an empty space between script and additional args
-            command.push(' ');
-            // then append the additional args
-            command.push_str(&args.join(" "));
-            execute_shell(command.trim())?;
+            run_script(&manifest, &command, script, &args, reporter)?;
         }
 
         Ok(())
     }
+
+    /// Execute `pacquet -r run`: run the script in every project of the workspace at
+    /// `workspace_root` that defines it, except the workspace root itself like pnpm.
+    ///
+    /// Each script is reported as an [`Event::Script`] of its project.
+    pub fn run_recursive(
+        self,
+        workspace_root: &Path,
+        reporter: &dyn Reporter,
+    ) -> miette::Result<()> {
+        let RunArgs { command, args, if_present, bail } = self;
+        let workspace_manifest = WorkspaceManifest::load_from_dir(workspace_root)
+            .wrap_err("loading pnpm-workspace.yaml")?
+            .ok_or_else(|| RecursiveRunError::NoWorkspace(workspace_root.to_path_buf()))?;
+        let projects = workspace_manifest
+            .find_projects(workspace_root)
+            .wrap_err("finding the projects of the workspace")?;
+
+        let mut total = 0;
+        let mut failures = Vec::new();
+        for project_dir in projects.iter().filter(|dir| *dir != workspace_root) {
+            let manifest = PackageManifest::from_path(project_dir.join("package.json"))
+                .wrap_err_with(|| format!("loading the manifest of {}", project_dir.display()))?;
+            let Some(script) = manifest.script(&command, true)? else { continue };
+            tracing::info!(target: "pacquet::run", project = ?project_dir, ?command, "Run script");
+            total += 1;
+            match run_script(&manifest, &command, script, &args, reporter) {
+                Ok(()) => {}
+                Err(error) if bail => return Err(error.into()),
+                Err(error) => failures.push(error),
+            }
+        }
+
+        if total == 0 && !if_present {
+            return Err(RecursiveRunError::NoScript(command).into());
+        }
+        if failures.is_empty() {
+            return Ok(());
+        }
+        Err(RecursiveRunError::Failed { script: command, total, failures }.into())
+    }
+}
+
+/// Run the `name` script of the project of `manifest`, whose command is `script`, with `args`
+/// appended, and report it as an [`Event::Script`].
+fn run_script(
+    manifest: &PackageManifest,
+    name: &str,
+    script: &str,
+    args: &[String],
+    reporter: &dyn Reporter,
+) -> Result<(), RunScriptError> {
+    let project_dir = manifest.path().parent().expect("package.json is in a directory");
+    let package = project_name(manifest, project_dir);
+    let command = format!("{script} {}", args.join(" "));
+
+    let start = Instant::now();
+    let result = execute_script(command.trim(), project_dir, [("npm_lifecycle_event", name)]);
+    let (exit_code, error) = match &result {
+        Ok(()) => (Some(0), None),
+        Err(ExecutorError::ExitStatus(status)) => (status.code(), None),
+        Err(error) => (None, Some(error.to_string())),
+    };
+    reporter.emit(Event::Script {
+        package: package.clone(),
+        name: name.to_string(),
+        exit_code,
+        duration_ms: start.elapsed().as_millis() as u64,
+        error,
+    });
+    result.map_err(|error| RunScriptError { package, script: name.to_string(), error })
+}
+
+/// `{name}@{version}` of the project, or its directory when it has no name.
+fn project_name(manifest: &PackageManifest, project_dir: &Path) -> String {
+    let field = |field| manifest.value().get(field).and_then(Value::as_str);
+    match (field("name"), field("version")) {
+        (Some(name), Some(version)) => format!("{name}@{version}"),
+        (Some(name), None) => name.to_string(),
+        (None, _) => project_dir.display().to_string(),
+    }
 }
@@ -1,11 +1,70 @@
+use crate::cli_args::fetch::NoLockfileError;
 use clap::Subcommand;
-use miette::Context;
+use derive_more::{Display, Error};
+use miette::{Context, Diagnostic};
+use pacquet_lockfile::{Lockfile, PkgNameVerPeer};
 use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::{create_http_client, RepairStore, StoreRepair, VerifyVirtualStore};
+use pacquet_registry::PackumentCache;
+use pacquet_reporter::Reporter;
+use ssri::Integrity;
+use std::path::PathBuf;
+
+/// Error when `pacquet store status` finds modified files.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{count} files in the virtual store don't match the store")]
+#[diagnostic(
+    code(pacquet_cli::modified_files),
+    help("Remove node_modules and run `pacquet install` to restore them")
+)]
+pub struct ModifiedFilesError {
+    pub count: usize,
+}
+
+/// Error when `pacquet store path --package` is given a package that isn't installed.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{package} is not installed")]
+#[diagnostic(code(pacquet_cli::package_not_installed), help("Run `pacquet install` first"))]
+pub struct PackageNotInstalledError {
+    #[error(not(source))]
+    pub package: String,
+}
+
+/// Error when `pacquet store path --package --index` can't find the integrity of a package.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("The integrity of {package} is unknown")]
+#[diagnostic(
+    code(pacquet_cli::unknown_integrity),
+    help("The integrity is read from pnpm-lock.yaml or from the metadata cache")
+)]
+pub struct UnknownIntegrityError {
+    #[error(not(source))]
+    pub package: String,
+}
+
+/// Error when `pacquet store verify` finds corrupt files.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{count} files in the store don't match their hashes")]
+#[diagnostic(
+    code(pacquet_cli::corrupt_store),
+    help("Run `pacquet store verify --repair` to restore or remove them")
+)]
+pub struct CorruptStoreError {
+    pub count: usize,
+}
 
 #[derive(Debug, Subcommand)]
 pub enum StoreCommand {
-    /// Checks for modified packages in the store.
-    Store,
+    /// Checks for files in the virtual store that were modified since they were imported from
+    /// the store.
+    Status,
+    /// Recomputes the hashes of every file in the store and reports the files that don't match.
+    Verify {
+        /// Restore the corrupt files by fetching the packages of pnpm-lock.yaml that contain them
+        /// again, and remove the ones that can't be restored.
+        #[clap(long)]
+        repair: bool,
+    },
     /// Functionally equivalent to pnpm add, except this adds new packages to the store directly
     /// without modifying any projects or files outside of the store.
     Add,
@@ -15,15 +74,72 @@ pub enum StoreCommand {
     /// dependencies are made redundant.
     Prune,
     /// Returns the path to the active store directory.
-    Path,
+    Path {
+        /// Return the path of an installed package in the virtual store instead, e.g.
+        /// `foo@1.0.0`.
+        #[clap(long, value_name = "NAME@VERSION")]
+        package: Option<PkgNameVerPeer>,
+        /// Return the path of the index file of the package in the store instead.
+        #[clap(long, requires = "package")]
+        index: bool,
+    },
 }
 
 impl StoreCommand {
     /// Execute the subcommand.
-    pub fn run<'a>(self, config: impl FnOnce() -> &'a Npmrc) -> miette::Result<()> {
+    pub async fn run(
+        self,
+        config: impl FnOnce() -> &'static Npmrc,
+        reporter: impl FnOnce() -> Box<dyn Reporter>,
+    ) -> miette::Result<()> {
         match self {
-            StoreCommand::Store => {
-                panic!("Not implemented")
+            StoreCommand::Status => {
+                let config = config();
+                let Lockfile { packages, .. } = Lockfile::load_from_current_dir()
+                    .wrap_err("loading the lockfile")?
+                    .ok_or(NoLockfileError)?;
+                let modified_files = VerifyVirtualStore {
+                    store_dir: &config.store_dir,
+                    virtual_store_dir: &config.virtual_store_dir,
+                    packages: packages.as_ref(),
+                }
+                .run()
+                .wrap_err("verifying the virtual store")?;
+                for path in &modified_files {
+                    println!("{}", path.display());
+                }
+                if !modified_files.is_empty() {
+                    return Err(ModifiedFilesError { count: modified_files.len() }.into());
+                }
+            }
+            StoreCommand::Verify { repair: false } => {
+                let corrupt_files = config().store_dir.verify().wrap_err("verifying the store")?;
+                for corrupt_file in &corrupt_files {
+                    println!("{}", corrupt_file.path.display());
+                }
+                if !corrupt_files.is_empty() {
+                    return Err(CorruptStoreError { count: corrupt_files.len() }.into());
+                }
+            }
+            StoreCommand::Verify { repair: true } => {
+                let config = config();
+                let lockfile =
+                    Lockfile::load_from_current_dir().wrap_err("loading the lockfile")?;
+                let StoreRepair { repaired, removed } = RepairStore {
+                    http_client: &create_http_client(config),
+                    config,
+                    reporter: &*reporter(),
+                    packages: lockfile.as_ref().and_then(|lockfile| lockfile.packages.as_ref()),
+                }
+                .run()
+                .await
+                .wrap_err("repairing the store")?;
+                for path in &repaired {
+                    println!("repaired: {}", path.display());
+                }
+                for path in &removed {
+                    println!("removed: {}", path.display());
+                }
             }
             StoreCommand::Add => {
                 panic!("Not implemented")
@@ -31,11 +147,62 @@ impl StoreCommand {
             StoreCommand::Prune => {
                 config().store_dir.prune().wrap_err("pruning store")?;
             }
-            StoreCommand::Path => {
+            StoreCommand::Path { package: None, .. } => {
                 println!("{}", config().store_dir.display());
             }
+            StoreCommand::Path { package: Some(package), index } => {
+                let config = config();
+                let path = if index {
+                    let integrity = package_integrity(config, &package)?;
+                    config.store_dir.index_file_path(&integrity)
+                } else {
+                    package_dir(config, &package)?
+                };
+                println!("{}", path.display());
+            }
         }
 
         Ok(())
     }
 }
+
+/// Directory of an installed package in the virtual store.
+fn package_dir(
+    config: &Npmrc,
+    package: &PkgNameVerPeer,
+) -> Result<PathBuf, PackageNotInstalledError> {
+    let dir = config
+        .virtual_store_dir
+        .join(package.to_virtual_store_name())
+        .join("node_modules")
+        .join(package.name.to_string());
+    if !dir.exists() {
+        return Err(PackageNotInstalledError { package: package.to_string() });
+    }
+    Ok(dir)
+}
+
+/// Integrity of a package from the lockfile, or from the metadata cache when the lockfile
+/// doesn't have the package.
+fn package_integrity(config: &Npmrc, package: &PkgNameVerPeer) -> miette::Result<Integrity> {
+    let from_lockfile = Lockfile::load_from_current_dir()
+        .wrap_err("loading the lockfile")?
+        .and_then(|lockfile| lockfile.packages)
+        .and_then(|packages| {
+            packages
+                .into_iter()
+                .find(|(dependency_path, _)| &dependency_path.package_specifier == package)
+                .and_then(|(_, snapshot)| snapshot.resolution.integrity().cloned())
+        });
+    if let Some(integrity) = from_lockfile {
+        return Ok(integrity);
+    }
+
+    let name = package.name.to_string();
+    let version = package.suffix.version().to_string();
+    PackumentCache::new(&config.cache_dir)
+        .read(config.registry_for(&name), &name)
+        .and_then(|mut cached| cached.versions.remove(&version))
+        .and_then(|package_version| package_version.dist.integrity)
+        .ok_or_else(|| UnknownIntegrityError { package: package.to_string() }.into())
+}
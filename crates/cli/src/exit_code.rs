@@ -0,0 +1,69 @@
+use miette::Report;
+use pacquet_lockfile::OutdatedLockfileError;
+use pacquet_package_manager::InstallError;
+use tokio::task::JoinError;
+
+/// Any failure that doesn't have a more specific code.
+pub const FAILURE: i32 = 1;
+
+/// The lockfile is missing or is out of date with package.json, while it was required to be
+/// up to date (`--frozen-lockfile`, `--check`).
+pub const OUTDATED_LOCKFILE: i32 = 3;
+
+/// pacquet has panicked, which is a bug, see [`set_panic_hook`](pacquet_diagnostics::set_panic_hook).
+pub const PANIC: i32 = 101;
+
+/// Exit code of a command that failed with `report`.
+///
+/// * `0`: success.
+/// * `1` ([`FAILURE`]): any other failure.
+/// * `2`: invalid arguments, reported by clap before the command runs.
+/// * `3` ([`OUTDATED_LOCKFILE`]): the lockfile can't be used as is.
+/// * `101` ([`PANIC`]): a bug in pacquet that made it panic, including in a task whose failure
+///   is reported as an error.
+pub fn exit_code(report: &Report) -> i32 {
+    let panicked = report
+        .chain()
+        .any(|error| error.downcast_ref::<JoinError>().is_some_and(JoinError::is_panic));
+    if panicked {
+        return PANIC;
+    }
+    let outdated_lockfile = report.chain().any(|error| {
+        error.is::<OutdatedLockfileError>()
+            || matches!(error.downcast_ref(), Some(InstallError::MissingLockfile))
+    });
+    if outdated_lockfile {
+        OUTDATED_LOCKFILE
+    } else {
+        FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miette::{miette, WrapErr};
+    use pacquet_tarball::TarballError;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_pick_the_code_of_the_cause() {
+        let report = Err::<(), _>(InstallError::MissingLockfile).wrap_err("install").unwrap_err();
+        assert_eq!(exit_code(&report), OUTDATED_LOCKFILE);
+
+        let report = Err::<(), _>(OutdatedLockfileError { drift: Vec::new() })
+            .wrap_err("check the lockfile")
+            .unwrap_err();
+        assert_eq!(exit_code(&report), OUTDATED_LOCKFILE);
+
+        assert_eq!(exit_code(&miette!("something went wrong")), FAILURE);
+    }
+
+    #[test]
+    fn should_pick_the_code_of_a_panicked_task() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let error = runtime.block_on(runtime.spawn(async { panic!("bug") })).unwrap_err();
+        let report = Report::new(TarballError::TaskJoin(error));
+        assert_eq!(exit_code(&report), PANIC);
+    }
+}
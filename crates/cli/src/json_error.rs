@@ -0,0 +1,47 @@
+use miette::{Diagnostic, Report};
+use pacquet_diagnostics::CollapsedReport;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// Describe a fatal error as a JSON object for `--json-errors`.
+///
+/// `code` is the code of the diagnostic (`null` if it has none), `message` is the outermost
+/// message, and `context` lists the messages of the causes that aren't already part of the
+/// message before them.
+pub fn json_error(report: Report) -> Value {
+    let report = CollapsedReport::from(report);
+    let mut context = Vec::new();
+    let mut cause = report.source();
+    while let Some(error) = cause {
+        context.push(error.to_string());
+        cause = error.source();
+    }
+    json!({
+        "code": report.code().map(|code| code.to_string()),
+        "message": report.to_string(),
+        "context": context,
+        "help": report.help().map(|help| help.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miette::{miette, WrapErr};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_describe_the_error() {
+        let report = Err::<(), _>(miette!(code = "pacquet_test::failure", "bar is broken"))
+            .wrap_err("installing foo")
+            .unwrap_err();
+        let received = json_error(report);
+        let expected = json!({
+            "code": "pacquet_test::failure",
+            "message": "installing foo",
+            "context": ["bar is broken"],
+            "help": null,
+        });
+        assert_eq!(received, expected);
+    }
+}
@@ -1,14 +1,41 @@
 mod cli_args;
+mod exit_code;
+mod json_error;
 mod state;
 
 use clap::Parser;
-use cli_args::CliArgs;
-use miette::set_panic_hook;
-use pacquet_diagnostics::enable_tracing_by_env;
+use cli_args::{CliArgs, ReporterType};
+use exit_code::{exit_code, PANIC};
+use futures_util::FutureExt;
+use json_error::json_error;
+use miette::Report;
+use pacquet_diagnostics::{set_panic_hook, CollapsedReport};
+use pipe_trait::Pipe;
 use state::State;
+use std::{panic::AssertUnwindSafe, process};
 
-pub async fn main() -> miette::Result<()> {
-    enable_tracing_by_env();
+/// Run pacquet with the arguments of the process.
+///
+/// A failure is reported on stderr, unless the reporter is silent, and ends the process with the
+/// code from [`exit_code`]. Invalid arguments exit with code 2. Panics are reported on stderr
+/// even with the silent reporter and exit with code 101.
+pub async fn main() {
     set_panic_hook();
-    CliArgs::parse().run().await
+    let args = CliArgs::parse();
+    let silent = args.reporter == ReporterType::Silent;
+    let json_errors = args.json_errors;
+    let Ok(result) = AssertUnwindSafe(args.run()).catch_unwind().await else {
+        // the panic has already been reported by the hook
+        process::exit(PANIC)
+    };
+    let Err(report) = result else { return };
+    let code = exit_code(&report);
+    if json_errors {
+        eprintln!("{}", json_error(report));
+    } else if !silent {
+        // the same as what returning the error from `main` would print
+        eprintln!("Error: {:?}", CollapsedReport::from(report).pipe(Report::new));
+    }
+    // with the silent reporter, the exit code is the only report of the failure
+    process::exit(code)
 }
@@ -1,28 +1,23 @@
 use derive_more::{Display, Error};
 use miette::Diagnostic;
 use pacquet_lockfile::{LoadLockfileError, Lockfile};
-use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
-use pacquet_package_manager::ResolvedPackages;
+use pacquet_package_manager::{check_package_manager, PackageManager, PackageManagerError};
 use pacquet_package_manifest::{PackageManifest, PackageManifestError};
-use pacquet_tarball::MemCache;
+use pacquet_reporter::Reporter;
+use pacquet_workspace::{LoadWorkspaceManifestError, WorkspaceManifest};
 use pipe_trait::Pipe;
-use std::path::PathBuf;
+use std::{env, io, path::PathBuf};
 
 /// Application state when running `pacquet run` or `pacquet install`.
 pub struct State {
-    /// Shared cache that store downloaded tarballs.
-    pub tarball_mem_cache: MemCache,
-    /// HTTP client to make HTTP requests.
-    pub http_client: ThrottledClient,
-    /// Configuration read from `.npmrc`
-    pub config: &'static Npmrc,
-    /// Data from the `package.json` file.
-    pub manifest: PackageManifest,
-    /// Data from the `pnpm-lock.yaml` file.
-    pub lockfile: Option<Lockfile>,
-    /// In-memory cache for packages that have started resolving dependencies.
-    pub resolved_packages: ResolvedPackages,
+    /// Package manager of the project, with the manifest, the workspace manifest, and the lockfile
+    /// loaded.
+    pub package_manager: PackageManager,
+    /// Absolute path of the directory that contains the `package.json` file.
+    pub project_dir: PathBuf,
+    /// Directory of the closest `pnpm-workspace.yaml` file.
+    pub workspace_dir: Option<PathBuf>,
 }
 
 /// Error type of [`State::init`].
@@ -34,22 +29,49 @@ pub enum InitStateError {
 
     #[diagnostic(transparent)]
     LoadLockfile(#[error(source)] LoadLockfileError),
+
+    #[display("Failed to get current_dir: {_0}")]
+    #[diagnostic(code(pacquet_cli::current_dir))]
+    CurrentDir(#[error(source)] io::Error),
+
+    #[diagnostic(transparent)]
+    LoadWorkspaceManifest(#[error(source)] LoadWorkspaceManifestError),
+
+    #[diagnostic(transparent)]
+    PackageManager(#[error(source)] PackageManagerError),
 }
 
 impl State {
     /// Initialize the application state.
-    pub fn init(manifest_path: PathBuf, config: &'static Npmrc) -> Result<Self, InitStateError> {
-        Ok(State {
-            config,
-            manifest: manifest_path
-                .pipe(PackageManifest::create_if_needed)
-                .map_err(InitStateError::LoadManifest)?,
-            lockfile: call_load_lockfile(config.lockfile, Lockfile::load_from_current_dir)
-                .map_err(InitStateError::LoadLockfile)?,
-            http_client: ThrottledClient::new_from_cpu_count(),
-            tarball_mem_cache: MemCache::new(),
-            resolved_packages: ResolvedPackages::new(),
-        })
+    pub fn init(
+        manifest_path: PathBuf,
+        config: &'static Npmrc,
+        reporter: Box<dyn Reporter>,
+    ) -> Result<Self, InitStateError> {
+        let manifest = manifest_path
+            .pipe(PackageManifest::create_if_needed)
+            .map_err(InitStateError::LoadManifest)?;
+        if let Some(package_manager) = manifest.package_manager() {
+            check_package_manager(package_manager, config.package_manager_strict)
+                .map_err(InitStateError::PackageManager)?;
+        }
+        let project_dir: PathBuf = env::current_dir()
+            .map_err(InitStateError::CurrentDir)?
+            .join(manifest.dir())
+            .components()
+            .collect();
+        let (workspace_dir, workspace_manifest) = WorkspaceManifest::find_from_dir(&project_dir)
+            .map_err(InitStateError::LoadWorkspaceManifest)?
+            .unzip();
+
+        let mut package_manager = PackageManager::new(config, manifest).with_reporter(reporter);
+        package_manager.workspace_manifest = workspace_manifest;
+        package_manager.workspace_dir = workspace_dir.clone();
+        package_manager.lockfile =
+            call_load_lockfile(config.lockfile, Lockfile::load_from_current_dir)
+                .map_err(InitStateError::LoadLockfile)?;
+
+        Ok(State { package_manager, project_dir, workspace_dir })
     }
 }
 
@@ -45,3 +45,32 @@ pub fn index_file_contents(
         .map(|entry| (suffix(&entry), content(&entry)))
         .collect()
 }
+
+/// Describe every entry under `dir` by its path relative to `dir`: the target of a symlink, the
+/// contents of a file, or nothing for a directory. Symlinks are not followed.
+pub fn tree_snapshot(dir: &Path) -> BTreeMap<String, Option<String>> {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .map(|entry| entry.expect("get entry"))
+        .map(|entry| {
+            let suffix = entry
+                .path()
+                .strip_prefix(dir)
+                .expect("strip dir prefix from entry path to create suffix")
+                .to_str()
+                .expect("convert entry suffix to UTF-8")
+                .replace('\\', "/");
+            let description = if entry.path_is_symlink() {
+                let target = std::fs::read_link(entry.path()).expect("read link");
+                Some(format!("-> {}", target.display()))
+            } else if entry.file_type().is_file() {
+                let content = std::fs::read(entry.path()).expect("read file");
+                Some(String::from_utf8_lossy(&content).into_owned())
+            } else {
+                None
+            };
+            (suffix, description)
+        })
+        .collect()
+}
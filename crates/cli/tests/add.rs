@@ -1,12 +1,26 @@
 use assert_cmd::prelude::*;
 use command_extra::CommandExtra;
+use pacquet_lockfile::{
+    DependencyPath, GitResolution, Lockfile, LockfileResolution, PkgName, ResolvedDependencySpec,
+    RootProjectSnapshot, TarballResolution,
+};
 use pacquet_package_manifest::{DependencyGroup, PackageManifest};
 use pacquet_testing_utils::{
-    bin::{AddMockedRegistry, CommandTempCwd},
-    fs::{get_all_folders, get_filenames_in_folder},
+    bin::{pacquet_command, AddFakeRegistry, AddMockedRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+    fixtures::{FASTIFY_ERROR_TARBALL, HELLO_BIN_TARBALL},
+    fs::{get_all_folders, get_filenames_in_folder, is_symlink_or_junction},
+    git::init_git_repo,
 };
+use pipe_trait::Pipe;
 use pretty_assertions::assert_eq;
-use std::{env, ffi::OsStr, fs, path::PathBuf};
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
 use tempfile::TempDir;
 
 fn exec_pacquet_in_temp_cwd<Args>(args: Args) -> (TempDir, PathBuf, AddMockedRegistry)
@@ -20,6 +34,39 @@ where
     (root, workspace, npmrc_info)
 }
 
+/// Parse the `pnpm-lock.yaml` of `workspace`.
+fn read_lockfile(workspace: &Path) -> Lockfile {
+    workspace
+        .join("pnpm-lock.yaml")
+        .pipe(fs::read_to_string)
+        .expect("read pnpm-lock.yaml")
+        .pipe_as_ref(Lockfile::from_yaml_str)
+        .expect("parse pnpm-lock.yaml")
+        .expect("lockfile isn't empty")
+}
+
+/// Get the version of the dependency `name` and the resolution of the package it resolves to from
+/// the lockfile of a single project.
+fn locked_dependency<'a>(
+    lockfile: &'a Lockfile,
+    name: &str,
+) -> (&'a ResolvedDependencySpec, &'a LockfileResolution) {
+    let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+        panic!("expected the lockfile of a single project");
+    };
+    let name: PkgName = name.parse().expect("parse package name");
+    let (_, spec) = project_snapshot
+        .dependencies_by_groups([DependencyGroup::Prod, DependencyGroup::Dev])
+        .find(|(alias, _)| **alias == name)
+        .expect("find the dependency in the lockfile");
+    let dependency_path = DependencyPath {
+        custom_registry: None,
+        package_specifier: spec.version.to_package_specifier(&name),
+    };
+    let package = &lockfile.packages.as_ref().expect("packages")[&dependency_path];
+    (spec, &package.resolution)
+}
+
 #[test]
 fn should_install_all_dependencies() {
     let (root, workspace, anchor) =
@@ -134,3 +181,367 @@ fn should_add_peer_dependency() {
         .any(|(k, _)| k == "@pnpm.e2e/hello-world-js-bin"));
     drop((root, anchor)); // cleanup
 }
+
+#[test]
+fn before_should_add_the_latest_version_published_before_the_date() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").published("2022-01-01T00:00:00.000Z"),
+            FakePackage::new("foo", "1.1.0").published("2023-02-01T00:00:00.000Z"),
+        ]);
+
+    eprintln!("Executing pacquet add foo --before=2023-01-01...");
+    pacquet.with_args(["add", "foo", "--before=2023-01-01"]).assert().success();
+
+    eprintln!("Make sure the version published before the date is saved and installed");
+    let manifest = PackageManifest::from_path(workspace.join("package.json")).unwrap();
+    let dependencies: Vec<_> = manifest.dependencies([DependencyGroup::Prod]).collect();
+    assert_eq!(dependencies, [("foo", "^1.0.0")]);
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(virtual_store_dir.join("foo@1.0.0").exists());
+    assert!(!virtual_store_dir.join("foo@1.1.0").exists());
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn should_add_local_tarball() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Creating the tarball...");
+    fs::create_dir(workspace.join("vendor")).expect("create vendor dir");
+    fs::write(workspace.join("vendor/error-3.3.0.tgz"), FASTIFY_ERROR_TARBALL)
+        .expect("write the tarball");
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "lockfile=true"))
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet add ./vendor/error-3.3.0.tgz...");
+    pacquet.with_args(["add", "./vendor/error-3.3.0.tgz"]).assert().success();
+
+    eprintln!("Make sure the dependency is saved as a file: specifier");
+    let manifest = PackageManifest::from_path(workspace.join("package.json")).unwrap();
+    let dependencies: Vec<_> = manifest.dependencies([DependencyGroup::Prod]).collect();
+    assert_eq!(dependencies, [("@fastify/error", "file:vendor/error-3.3.0.tgz")]);
+
+    eprintln!("Make sure the package is installed");
+    let symlink_path = workspace.join("node_modules/@fastify/error");
+    assert!(is_symlink_or_junction(&symlink_path).unwrap());
+    assert!(symlink_path.join("package.json").exists());
+    assert!(workspace.join("node_modules/.pnpm/@fastify+error@3.3.0").exists());
+
+    eprintln!("Make sure the lockfile records a tarball resolution of the file: URL");
+    let lockfile = read_lockfile(&workspace);
+    let (spec, resolution) = locked_dependency(&lockfile, "@fastify/error");
+    assert_eq!(spec.specifier, "file:vendor/error-3.3.0.tgz");
+    assert_eq!(spec.version.to_string(), "3.3.0");
+    let expected_resolution = TarballResolution {
+        tarball: "file:vendor/error-3.3.0.tgz".to_string(),
+        integrity: "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==".parse().ok(),
+    };
+    assert_eq!(resolution, &LockfileResolution::Tarball(expected_resolution));
+
+    eprintln!("Make sure the local tarball is installed again with the lockfile, even in CI");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    pacquet_command(&workspace).with_env("CI", "true").with_arg("install").assert().success();
+    assert!(symlink_path.join("package.json").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_add_tarball_url() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+    let url = format!("{}foo/-/foo-1.0.0.tgz", registry.url());
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "lockfile=true"))
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet add {url}...");
+    pacquet.with_args(["add", &url]).assert().success();
+
+    eprintln!("Make sure the dependency is saved as the URL");
+    let manifest = PackageManifest::from_path(workspace.join("package.json")).unwrap();
+    let dependencies: Vec<_> = manifest.dependencies([DependencyGroup::Prod]).collect();
+    assert_eq!(dependencies, [("@fastify/error", url.as_str())]);
+
+    eprintln!("Make sure the package is installed");
+    let symlink_path = workspace.join("node_modules/@fastify/error");
+    assert!(is_symlink_or_junction(&symlink_path).unwrap());
+    assert!(symlink_path.join("package.json").exists());
+    assert!(workspace.join("node_modules/.pnpm/@fastify+error@3.3.0").exists());
+
+    eprintln!("Make sure the tarball is downloaded only once");
+    registry.mock("/foo/-/foo-1.0.0.tgz").assert();
+
+    eprintln!("Make sure the lockfile records a tarball resolution of the URL");
+    let lockfile = read_lockfile(&workspace);
+    let (spec, resolution) = locked_dependency(&lockfile, "@fastify/error");
+    assert_eq!(spec.specifier, url);
+    assert_eq!(spec.version.to_string(), "3.3.0");
+    let expected_resolution = TarballResolution {
+        tarball: url.clone(),
+        integrity: "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==".parse().ok(),
+    };
+    assert_eq!(resolution, &LockfileResolution::Tarball(expected_resolution));
+
+    eprintln!("Make sure the lockfile installs");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    pacquet_command(&workspace).with_args(["install", "--frozen-lockfile"]).assert().success();
+    assert!(symlink_path.join("package.json").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_add_global_package_and_link_its_bins() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+    let global_dir = root.path().join("global");
+    let global_bin_dir = root.path().join("global-bin");
+
+    eprintln!("Setting the global directories in .npmrc...");
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(
+            format!(
+                "global-dir={}\nglobal-bin-dir={}\n",
+                global_dir.display(),
+                global_bin_dir.display(),
+            )
+            .as_bytes(),
+        )
+        .expect("append to .npmrc");
+
+    eprintln!("Creating the tarball...");
+    let tarball_path = workspace.join("hello-bin-1.0.0.tgz");
+    fs::write(&tarball_path, HELLO_BIN_TARBALL).expect("write the tarball");
+
+    eprintln!("Executing pacquet add --global...");
+    pacquet.with_args(["add", "--global"]).with_arg(&tarball_path).assert().success();
+
+    eprintln!("Make sure the package is installed into the global dir");
+    let manifest = PackageManifest::from_path(global_dir.join("package.json")).unwrap();
+    let names: Vec<_> =
+        manifest.dependencies([DependencyGroup::Prod]).map(|(name, _)| name).collect();
+    assert_eq!(names, ["hello-bin"]);
+    assert!(global_dir.join("node_modules/hello-bin/cli.js").exists());
+    assert!(!workspace.join("package.json").exists());
+    assert!(!workspace.join("node_modules").exists());
+
+    eprintln!("Make sure the bin is linked into the global bin dir");
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(global_bin_dir.join("hello-bin")).unwrap(),
+        global_dir.join("node_modules/hello-bin/cli.js"),
+    );
+    #[cfg(windows)]
+    assert!(global_bin_dir.join("hello-bin.cmd").exists());
+
+    eprintln!("Make sure pacquet list --global lists the package");
+    let output = pacquet_command(&workspace)
+        .with_args(["list", "--global"])
+        .output()
+        .expect("run pacquet list");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim_end(), "dependencies:\nhello-bin 1.0.0");
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_add_git_dependency() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+    let repo_dir = root.path().join("repo");
+    let commits = init_git_repo(&repo_dir, "foo", &["1.0.0", "1.1.0", "2.0.0"]);
+    let specifier = format!("git+file://{}#semver:^1.0.0", repo_dir.display());
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "lockfile=true"))
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet add {specifier}...");
+    pacquet.with_args(["add", &specifier]).assert().success();
+
+    eprintln!("Make sure the specifier is saved as is");
+    let manifest = PackageManifest::from_path(workspace.join("package.json")).unwrap();
+    let dependencies: Vec<_> = manifest.dependencies([DependencyGroup::Prod]).collect();
+    assert_eq!(dependencies, [("foo", specifier.as_str())]);
+
+    eprintln!("Make sure the highest matching tag is installed");
+    let installed_manifest = workspace.join("node_modules/foo/package.json");
+    let installed_manifest: serde_json::Value =
+        fs::read_to_string(installed_manifest).unwrap().pipe_as_ref(serde_json::from_str).unwrap();
+    assert_eq!(installed_manifest["version"], "1.1.0");
+    assert!(workspace.join("node_modules/.pnpm/foo@1.1.0").exists());
+
+    eprintln!("Make sure the commit of the tag is pinned in the lockfile");
+    let lockfile = read_lockfile(&workspace);
+    let (spec, resolution) = locked_dependency(&lockfile, "foo");
+    assert_eq!(spec.specifier, specifier);
+    assert_eq!(spec.version.to_string(), "1.1.0");
+    let expected_resolution = GitResolution {
+        repo: format!("file://{}", repo_dir.display()),
+        commit: commits[1].clone(),
+    };
+    assert_eq!(resolution, &LockfileResolution::Git(expected_resolution));
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_refuse_to_add_to_workspace_root() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    fs::write(workspace.join("package.json"), r#"{ "name": "root", "private": true }"#)
+        .expect("write to package.json");
+    let pacquet_in_workspace = || pacquet_command(&workspace);
+
+    eprintln!("CASE: no flag");
+    let output = pacquet.with_args(["add", "foo"]).output().expect("run pacquet add");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("workspace root"));
+    assert!(!workspace.join("node_modules/foo").exists());
+
+    eprintln!("CASE: --ignore-workspace-root-check");
+    pacquet_in_workspace()
+        .with_args(["add", "foo", "--ignore-workspace-root-check"])
+        .assert()
+        .success();
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+
+    eprintln!("CASE: -w from a workspace project");
+    let project_dir = workspace.join("packages/a");
+    fs::create_dir_all(&project_dir).expect("create project directory");
+    fs::write(project_dir.join("package.json"), "{}").expect("write to package.json");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    pacquet_in_workspace().with_args(["-C", "packages/a", "-w", "add", "foo"]).assert().success();
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(!project_dir.join("node_modules").exists());
+    let manifest = PackageManifest::from_path(workspace.join("package.json")).unwrap();
+    let dependencies: Vec<_> = manifest.dependencies([DependencyGroup::Prod]).collect();
+    assert_eq!(dependencies, [("foo", "^1.0.0")]);
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn should_not_save_with_no_save() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+    let manifest_path = workspace.join("package.json");
+    fs::write(&manifest_path, "{}").expect("write to package.json");
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "lockfile=true"))
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet add foo --no-save...");
+    pacquet.with_args(["add", "foo", "--no-save"]).assert().success();
+
+    eprintln!("Make sure the package is installed");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+
+    eprintln!("Make sure package.json is untouched");
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), "{}");
+
+    eprintln!("Make sure the lockfile is updated");
+    let lockfile = read_lockfile(&workspace);
+    let (spec, resolution) = locked_dependency(&lockfile, "foo");
+    assert_eq!(spec.specifier, "^1.0.0");
+    assert_eq!(spec.version.to_string(), "1.0.0");
+    assert!(matches!(resolution, LockfileResolution::Registry(_)));
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_not_save_with_save_false_in_npmrc() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let manifest_path = workspace.join("package.json");
+    fs::write(&manifest_path, "{}").expect("write to package.json");
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&npmrc_info.npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "save=false"))
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet add foo...");
+    pacquet.with_args(["add", "foo"]).assert().success();
+
+    eprintln!("Make sure the package is installed but package.json is untouched");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), "{}");
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn should_save_workspace_protocol_for_workspace_packages() {
+    let CommandTempCwd { root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    fs::write(workspace.join("package.json"), r#"{ "name": "root", "private": true }"#)
+        .expect("write to package.json");
+    let project_dir = workspace.join("packages/a");
+    let sibling_dir = workspace.join("packages/b");
+    for (dir, manifest) in [
+        (&project_dir, r#"{ "name": "a", "version": "1.0.0" }"#),
+        (&sibling_dir, r#"{ "name": "b", "version": "1.2.0" }"#),
+    ] {
+        fs::create_dir_all(dir).expect("create project directory");
+        fs::write(dir.join("package.json"), manifest).expect("write to package.json");
+    }
+    fs::copy(&npmrc_info.npmrc_path, project_dir.join(".npmrc")).expect("copy .npmrc");
+    let pacquet_add = |args: &[&str]| {
+        pacquet_command(&project_dir).with_arg("add").with_args(args).assert().success();
+        let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+        let dependencies: Vec<_> = manifest
+            .dependencies([DependencyGroup::Prod])
+            .map(|(name, spec)| (name.to_string(), spec.to_string()))
+            .collect();
+        dependencies
+    };
+    let expected = |spec: &str| vec![("b".to_string(), spec.to_string())];
+
+    eprintln!("CASE: rolling by default");
+    assert_eq!(pacquet_add(&["b"]), expected("workspace:^"));
+    let symlink_path = project_dir.join("node_modules/b");
+    assert!(is_symlink_or_junction(&symlink_path).unwrap());
+    assert_eq!(fs::canonicalize(&symlink_path).unwrap(), fs::canonicalize(&sibling_dir).unwrap());
+
+    eprintln!("CASE: rolling with --save-exact");
+    assert_eq!(pacquet_add(&["b", "--save-exact"]), expected("workspace:*"));
+
+    eprintln!("CASE: --save-workspace-protocol=true");
+    assert_eq!(pacquet_add(&["b", "--save-workspace-protocol=true"]), expected("workspace:^1.2.0"),);
+
+    drop((root, npmrc_info)); // cleanup
+}
@@ -0,0 +1,48 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::bin::{pacquet_command, CommandTempCwd};
+use pretty_assertions::assert_eq;
+use std::{fs, path::Path};
+
+fn pacquet_bin(dir: &Path, args: &[&str]) -> String {
+    let output = pacquet_command(dir).with_args(args).output().expect("run pacquet bin");
+    dbg!(&output);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).expect("stdout is valid UTF-8").trim_end().to_string()
+}
+
+#[test]
+fn should_print_the_bin_dir() {
+    let CommandTempCwd { root, workspace, .. } = CommandTempCwd::init();
+    let workspace = workspace.canonicalize().unwrap();
+    fs::create_dir(workspace.join("project")).expect("create project directory");
+
+    eprintln!("CASE: current directory");
+    let bin_dir = workspace.join("node_modules/.bin");
+    assert_eq!(pacquet_bin(&workspace, &["bin"]), bin_dir.display().to_string());
+
+    eprintln!("CASE: --dir");
+    let bin_dir = workspace.join("project/node_modules/.bin");
+    assert_eq!(pacquet_bin(&workspace, &["-C", "project", "bin"]), bin_dir.display().to_string());
+
+    eprintln!("CASE: --modules-dir");
+    let bin_dir = workspace.join("deps/.bin");
+    assert_eq!(
+        pacquet_bin(&workspace, &["--modules-dir", "deps", "bin"]),
+        bin_dir.display().to_string(),
+    );
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn should_print_the_global_bin_dir() {
+    let CommandTempCwd { root, workspace, .. } = CommandTempCwd::init();
+    let global_bin_dir = root.path().join("global-bin");
+    fs::write(workspace.join(".npmrc"), format!("global-bin-dir={}\n", global_bin_dir.display()))
+        .expect("write to .npmrc");
+
+    let stdout = pacquet_bin(&workspace, &["bin", "-g"]);
+    assert_eq!(stdout, global_bin_dir.display().to_string());
+
+    drop(root); // cleanup
+}
@@ -0,0 +1,67 @@
+use command_extra::CommandExtra;
+use pacquet_registry::PackumentCache;
+use pacquet_testing_utils::bin::CommandTempCwd;
+use pipe_trait::Pipe;
+use pretty_assertions::assert_eq;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Handle the slight difference between OSes.
+fn canonicalize(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        path.to_path_buf()
+    } else {
+        dunce::canonicalize(path).expect("canonicalize path")
+    }
+}
+
+#[test]
+fn cache_dir_should_return_metadata_dir_from_npmrc() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating .npmrc...");
+    fs::write(workspace.join(".npmrc"), "cache-dir=foo/bar").expect("write to .npmrc");
+
+    eprintln!("Executing pacquet cache dir...");
+    let output = pacquet.with_args(["cache", "dir"]).output().expect("run pacquet cache dir");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Stdout");
+    let normalize = |path: &str| path.replace('\\', "/");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end().pipe(normalize),
+        canonicalize(&workspace).join("foo/bar/metadata").to_string_lossy().pipe_as_ref(normalize),
+    );
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn cache_clean_should_remove_cached_metadata() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating .npmrc and a cached entry...");
+    fs::write(workspace.join(".npmrc"), "cache-dir=../pacquet-cache\nstore-dir=../pacquet-store")
+        .expect("write to .npmrc");
+    let cache = PackumentCache::new(&root.path().join("pacquet-cache"));
+    let entry = cache.entry_path("https://registry.npmjs.org/", "foo");
+    fs::create_dir_all(entry.parent().unwrap()).expect("create cache dir");
+    fs::write(&entry, r#"{ "name": "foo", "dist-tags": {}, "versions": {} }"#)
+        .expect("write cached entry");
+    fs::create_dir_all(root.path().join("pacquet-store/v3")).expect("create store dir");
+
+    eprintln!("Executing pacquet cache clean...");
+    let output = pacquet.with_args(["cache", "clean"]).output().expect("run pacquet cache clean");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Make sure only the metadata cache is removed");
+    assert!(!entry.exists());
+    assert!(!cache.root().exists());
+    assert!(root.path().join("pacquet-store/v3").exists());
+
+    drop(root); // cleanup
+}
@@ -0,0 +1,123 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_lockfile::{Lockfile, RootProjectSnapshot};
+use pacquet_testing_utils::{
+    bin::{AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use pipe_trait::Pipe;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+use text_block_macros::text_block_fnl;
+
+const WORKSPACE_MANIFEST: &str = text_block_fnl! {
+    "packages:"
+    "  - packages/*"
+    "catalog:"
+    "  foo: ^1.0.0"
+    "catalogs:"
+    "  next:"
+    "    foo: ^2.0.0"
+};
+
+fn registry_packages() -> [FakePackage; 3] {
+    [
+        FakePackage::new("foo", "1.0.0"),
+        FakePackage::new("foo", "1.1.0"),
+        FakePackage::new("foo", "2.0.0"),
+    ]
+}
+
+#[test]
+fn should_resolve_default_catalog() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&registry_packages());
+    let AddFakeRegistry { registry, npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), WORKSPACE_MANIFEST)
+        .expect("write to pnpm-workspace.yaml");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "catalog:" } }"#)
+        .expect("write to package.json");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure the version from the default catalog is installed");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(virtual_store_dir.join("foo@1.1.0").exists());
+    assert!(!virtual_store_dir.join("foo@2.0.0").exists());
+
+    eprintln!("Make sure the lockfile records the version from the catalog");
+    let lockfile = workspace
+        .join("pnpm-lock.yaml")
+        .pipe(fs::read_to_string)
+        .expect("read pnpm-lock.yaml")
+        .pipe_as_ref(Lockfile::from_yaml_str)
+        .expect("parse pnpm-lock.yaml")
+        .expect("lockfile isn't empty");
+    let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+        panic!("expected the lockfile of a single project");
+    };
+    let dependency =
+        &project_snapshot.dependencies.as_ref().expect("dependencies")[&"foo".parse().unwrap()];
+    assert_eq!(dependency.specifier, "catalog:");
+    assert_eq!(dependency.version.to_string(), "1.1.0");
+    assert!(lockfile.packages.expect("packages").contains_key(&"/foo@1.1.0".parse().unwrap()));
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_resolve_named_catalog() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&registry_packages());
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), WORKSPACE_MANIFEST)
+        .expect("write to pnpm-workspace.yaml");
+    let project_dir = workspace.join("packages/a");
+    fs::create_dir_all(&project_dir).expect("create project directory");
+    fs::write(project_dir.join("package.json"), r#"{ "dependencies": { "foo": "catalog:next" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet -r install...");
+    pacquet.with_args(["-r", "install"]).assert().success();
+
+    eprintln!("Make sure the version from the named catalog is installed");
+    let virtual_store_dir = project_dir.join("node_modules/.pnpm");
+    assert!(virtual_store_dir.join("foo@2.0.0").exists());
+    assert!(!virtual_store_dir.join("foo@1.1.0").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_fail_on_unknown_catalog() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&registry_packages());
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), WORKSPACE_MANIFEST)
+        .expect("write to pnpm-workspace.yaml");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "catalog:legacy" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    let output = pacquet.with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("foo@catalog:legacy"));
+
+    drop((root, registry)); // cleanup
+}
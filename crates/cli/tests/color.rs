@@ -0,0 +1,62 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use std::fs;
+
+const ESCAPE: char = '\x1b';
+
+/// Run `pacquet -v install` on a project with a dependency that exists and one that doesn't,
+/// with `envs` set, and return the stderr with the logs and the diagnostic.
+fn failed_install_stderr(args: &[&str], envs: &[(&str, &str)]) -> String {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    let package_json = r#"{ "dependencies": { "foo": "^1.0.0", "missing": "^1.0.0" } }"#;
+    fs::write(workspace.join("package.json"), package_json).expect("write to package.json");
+
+    eprintln!("Executing pacquet {args:?} -v install with {envs:?}...");
+    let pacquet = pacquet.without_env("NO_COLOR").without_env("FORCE_COLOR");
+    let output = envs
+        .iter()
+        .fold(pacquet, |pacquet, (key, value)| pacquet.with_env(key, value))
+        .with_args(args)
+        .with_args(["-v", "install"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+
+    drop((root, registry)); // cleanup
+    let stderr = String::from_utf8(output.stderr).expect("stderr is valid UTF-8");
+    assert!(stderr.contains("DEBUG"));
+    assert!(stderr.contains("missing"));
+    stderr
+}
+
+#[test]
+fn no_color_env_should_remove_escape_sequences() {
+    let stderr = failed_install_stderr(&[], &[("NO_COLOR", "1")]);
+    assert!(!stderr.contains(ESCAPE));
+}
+
+#[test]
+fn no_color_flag_should_win_over_force_color() {
+    let stderr = failed_install_stderr(&["--no-color"], &[("FORCE_COLOR", "1")]);
+    assert!(!stderr.contains(ESCAPE));
+}
+
+#[test]
+fn force_color_should_color_output_that_is_not_a_terminal() {
+    let stderr = failed_install_stderr(&[], &[("FORCE_COLOR", "1")]);
+    assert!(stderr.contains(ESCAPE));
+}
+
+#[test]
+fn output_that_is_not_a_terminal_should_not_be_colored() {
+    let stderr = failed_install_stderr(&[], &[]);
+    assert!(!stderr.contains(ESCAPE));
+}
@@ -0,0 +1,94 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use std::fs;
+
+#[test]
+fn config_should_replace_the_npmrc_of_the_current_directory() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Moving .npmrc out of the workspace and writing another one...");
+    let config_path = root.path().join("custom.npmrc");
+    fs::rename(&npmrc_path, &config_path).expect("move .npmrc");
+    fs::write(&npmrc_path, "registry=http://127.0.0.1:9/\n").expect("write to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet --config={config_path:?} install...");
+    let output = pacquet
+        .with_arg("--config")
+        .with_arg(&config_path)
+        .with_arg("install")
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(workspace.join("node_modules/foo").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn config_should_keep_the_settings_of_the_home_directory() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Moving .npmrc out of the workspace and writing a user-level one...");
+    let config_path = root.path().join("custom.npmrc");
+    fs::rename(npmrc_path, &config_path).expect("move .npmrc");
+    let home_dir = root.path().join("home");
+    fs::create_dir(&home_dir).expect("create the home directory");
+    fs::write(home_dir.join(".npmrc"), "modules-dir=user_modules\n").expect("write to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet --config={config_path:?} install...");
+    let output = pacquet
+        .with_arg("--config")
+        .with_arg(&config_path)
+        .with_arg("install")
+        .with_env("HOME", &home_dir)
+        .with_env("USERPROFILE", &home_dir)
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(workspace.join("user_modules/foo").exists());
+
+    eprintln!("Executing pacquet --config={config_path:?} --no-config-merge install...");
+    let output = pacquet_command(&workspace)
+        .with_arg("--config")
+        .with_arg(&config_path)
+        .with_args(["--no-config-merge", "install"])
+        .with_env("HOME", &home_dir)
+        .with_env("USERPROFILE", &home_dir)
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(workspace.join("node_modules/foo").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn config_should_fail_when_the_file_is_missing() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    fs::write(workspace.join("package.json"), "{}").expect("write to package.json");
+    let output = pacquet
+        .with_args(["--config", "missing.npmrc", "install"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing.npmrc"));
+
+    drop(root); // cleanup
+}
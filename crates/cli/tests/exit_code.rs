@@ -0,0 +1,144 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    process::Output,
+};
+use text_block_macros::text_block_fnl;
+
+const LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  foo:"
+    "    specifier: 1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+};
+
+/// Run `pacquet --reporter=silent install {args}` with `manifest` and, if any, `lockfile`, then
+/// make sure that nothing but the exit code reports the outcome.
+fn silent_install(manifest: &str, lockfile: Option<&str>, args: &[&str]) -> Option<i32> {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+    fs::write(workspace.join("package.json"), manifest).expect("write to package.json");
+    if let Some(lockfile) = lockfile {
+        fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+    }
+
+    eprintln!("Executing pacquet --reporter=silent install {args:?}...");
+    let output: Output = pacquet
+        .with_args(["--reporter=silent", "install"])
+        .with_args(args)
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    drop((root, registry)); // cleanup
+    output.status.code()
+}
+
+#[test]
+fn frozen_lockfile_install_should_exit_with_0_on_success() {
+    let manifest = r#"{ "dependencies": { "foo": "1.0.0" } }"#;
+    assert_eq!(silent_install(manifest, Some(LOCKFILE), &["--frozen-lockfile"]), Some(0));
+}
+
+#[test]
+fn outdated_lockfile_should_exit_with_3() {
+    let manifest = r#"{ "dependencies": { "foo": "^1.0.0" } }"#;
+    eprintln!("CASE: --frozen-lockfile");
+    assert_eq!(silent_install(manifest, Some(LOCKFILE), &["--frozen-lockfile"]), Some(3));
+    eprintln!("CASE: --check");
+    assert_eq!(silent_install(manifest, Some(LOCKFILE), &["--check"]), Some(3));
+}
+
+#[test]
+fn missing_lockfile_should_exit_with_3() {
+    let manifest = r#"{ "dependencies": { "foo": "1.0.0" } }"#;
+    assert_eq!(silent_install(manifest, None, &["--frozen-lockfile"]), Some(3));
+}
+
+#[test]
+fn other_failures_should_exit_with_1_instead_of_panicking() {
+    eprintln!("CASE: no version satisfies the range");
+    let manifest = r#"{ "dependencies": { "foo": "^2.0.0" } }"#;
+    assert_eq!(silent_install(manifest, None, &[]), Some(1));
+
+    eprintln!("CASE: the lockfile resolves a package from a directory");
+    let manifest = r#"{ "dependencies": { "foo": "1.0.0" } }"#;
+    let lockfile = LOCKFILE.replace(
+        "resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}",
+        "resolution: {directory: ../foo, type: directory}",
+    );
+    assert_eq!(silent_install(manifest, Some(&lockfile), &["--frozen-lockfile"]), Some(1));
+}
+
+#[test]
+fn invalid_arguments_should_exit_with_2() {
+    let CommandTempCwd { pacquet, root, .. } = CommandTempCwd::init();
+    let output = pacquet
+        .with_args(["--reporter=silent", "install", "--no-such-flag"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(2));
+    drop(root); // cleanup
+}
+
+#[test]
+fn adding_a_missing_package_should_exit_with_1() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+    fs::write(workspace.join("package.json"), "{}").expect("write to package.json");
+
+    eprintln!("Executing pacquet --reporter=silent add no-such-package...");
+    let output = pacquet
+        .with_args(["--reporter=silent", "add", "no-such-package"])
+        .output()
+        .expect("run pacquet add");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stderr.is_empty());
+    assert_eq!(fs::read_to_string(workspace.join("package.json")).unwrap(), "{}");
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn panics_should_be_reported_even_when_silent() {
+    let CommandTempCwd { pacquet, root, .. } = CommandTempCwd::init();
+
+    // `store add` isn't implemented and panics for now
+    eprintln!("Executing pacquet --reporter=silent store add...");
+    let output = pacquet
+        .with_args(["--reporter=silent", "store", "add"])
+        .output()
+        .expect("run pacquet store add");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("pacquet panicked: Not implemented"));
+    assert!(stderr.contains("This is a bug in pacquet, please report it"));
+
+    drop(root); // cleanup
+}
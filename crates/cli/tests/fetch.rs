@@ -0,0 +1,95 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+    fs::{get_all_files, is_symlink_or_junction},
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+use text_block_macros::text_block_fnl;
+
+const LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  foo:"
+    "    specifier: ^1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /bar@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+    "  /foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dependencies:"
+    "      bar: 1.0.0"
+    "    dev: false"
+};
+
+#[test]
+fn fetch_should_populate_store_without_node_modules() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json and pnpm-lock.yaml...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet fetch...");
+    pacquet.with_arg("fetch").assert().success();
+
+    eprintln!("Make sure the store is populated");
+    let index_files: Vec<_> = get_all_files(&store_dir)
+        .into_iter()
+        .filter(|path| path.ends_with("-index.json"))
+        .collect();
+    dbg!(&index_files);
+    assert!(!index_files.is_empty());
+    registry.mock("/foo/-/foo-1.0.0.tgz").assert();
+    registry.mock("/bar/-/bar-1.0.0.tgz").assert();
+
+    eprintln!("Make sure node_modules is not created");
+    assert!(!workspace.join("node_modules").exists());
+
+    eprintln!("Shutting down the registry...");
+    drop(registry);
+
+    eprintln!("Enabling lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install --frozen-lockfile without a registry...");
+    pacquet_command(&workspace).with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    eprintln!("Make sure the package is installed");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(workspace.join("node_modules/.pnpm/bar@1.0.0").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn fetch_should_fail_without_lockfile() {
+    let CommandTempCwd { pacquet, root, .. } = CommandTempCwd::init();
+
+    eprintln!("Executing pacquet fetch...");
+    let output = pacquet.with_arg("fetch").output().expect("run pacquet fetch");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No lockfile found"));
+
+    drop(root); // cleanup
+}
@@ -0,0 +1,71 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::bin::CommandTempCwd;
+use pretty_assertions::assert_eq;
+use std::fs;
+use text_block_macros::text_block_fnl;
+
+const LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  foo:"
+    "    specifier: ^1.0.0"
+    "    version: 1.0.0"
+    ""
+    "devDependencies:"
+    "  bar:"
+    "    specifier: ^1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /bar@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: true"
+    "  /foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dependencies:"
+    "      bar: 1.0.0"
+    "    dev: false"
+};
+
+#[test]
+fn graph_should_print_nodes_and_edges_of_the_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating pnpm-lock.yaml...");
+    fs::write(workspace.join("pnpm-lock.yaml"), LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet graph...");
+    let output = pacquet.with_arg("graph").output().expect("run pacquet graph");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Make sure the graph has the expected nodes and edges");
+    let received: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse the graph");
+    let expected = serde_json::json!({
+        "nodes": [
+            { "id": "." },
+            { "id": "bar@1.0.0", "name": "bar", "version": "1.0.0" },
+            { "id": "foo@1.0.0", "name": "foo", "version": "1.0.0" },
+        ],
+        "edges": [
+            { "from": ".", "to": "bar@1.0.0", "alias": "bar", "type": "dev" },
+            { "from": ".", "to": "foo@1.0.0", "alias": "foo", "type": "prod" },
+            { "from": "foo@1.0.0", "to": "bar@1.0.0", "alias": "bar", "type": "prod" },
+        ],
+    });
+    assert_eq!(received, expected);
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn graph_should_fail_without_lockfile() {
+    let CommandTempCwd { pacquet, root, .. } = CommandTempCwd::init();
+    let output = pacquet.with_arg("graph").output().expect("run pacquet graph");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No lockfile found"));
+    drop(root); // cleanup
+}
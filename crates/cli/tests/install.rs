@@ -3,16 +3,23 @@ pub use _utils::*;
 
 use assert_cmd::prelude::*;
 use command_extra::CommandExtra;
+use pacquet_store_dir::StoreDir;
 use pacquet_testing_utils::{
-    bin::{AddMockedRegistry, CommandTempCwd},
+    bin::{pacquet_command, AddFakeRegistry, AddMockedRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
     fixtures::{BIG_LOCKFILE, BIG_MANIFEST},
     fs::{get_all_files, get_all_folders, is_symlink_or_junction},
+    git::git,
 };
 use pipe_trait::Pipe;
+use pretty_assertions::assert_eq;
 use std::{
     fs::{self, OpenOptions},
     io::Write,
+    thread,
+    time::{Duration, Instant},
 };
+use text_block_macros::text_block_fnl;
 
 #[test]
 fn should_install_dependencies() {
@@ -192,3 +199,1542 @@ fn should_install_circular_dependencies() {
 
     drop((root, mock_instance)); // cleanup
 }
+
+#[test]
+fn frozen_lockfile_should_link_dependency_cycles_once() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0"), FakePackage::new("bar", "2.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Enabling the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Creating package.json and a pnpm-lock.yaml with a dependency cycle...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    let lockfile = text_block_fnl! {
+        "lockfileVersion: '6.0'"
+        ""
+        "dependencies:"
+        "  foo:"
+        "    specifier: ^1.0.0"
+        "    version: 1.0.0"
+        ""
+        "packages:"
+        "  /foo@1.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dependencies:"
+        "      bar: 2.0.0"
+        "      foo: 1.0.0"
+        "    dev: false"
+        "  /bar@2.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dependencies:"
+        "      foo: 1.0.0"
+        "    dev: false"
+    };
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install --frozen-lockfile...");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    eprintln!("Make sure both packages are linked to each other once");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    let foo_dir = virtual_store_dir.join("foo@1.0.0/node_modules/foo");
+    let bar_dir = virtual_store_dir.join("bar@2.0.0/node_modules/bar");
+    assert!(!is_symlink_or_junction(&foo_dir).unwrap());
+    assert!(!is_symlink_or_junction(&bar_dir).unwrap());
+    let resolve = |path: &str| workspace.join(path).canonicalize().expect("resolve the symlink");
+    assert_eq!(resolve("node_modules/foo"), foo_dir.canonicalize().unwrap());
+    assert_eq!(
+        resolve("node_modules/.pnpm/foo@1.0.0/node_modules/bar"),
+        bar_dir.canonicalize().unwrap()
+    );
+    assert_eq!(
+        resolve("node_modules/.pnpm/bar@2.0.0/node_modules/foo"),
+        foo_dir.canonicalize().unwrap()
+    );
+    assert!(foo_dir.join("package.json").exists());
+    assert!(bar_dir.join("package.json").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn should_install_package_that_depends_on_itself() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0").dependencies([("foo", "^1.0.0")])]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    let foo_dir = workspace.join("node_modules/.pnpm/foo@1.0.0/node_modules/foo");
+    assert!(!is_symlink_or_junction(&foo_dir).unwrap());
+    assert!(foo_dir.join("package.json").exists());
+    assert!(workspace.join("node_modules/foo/package.json").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn registry_replace_should_rewrite_tarball_host() {
+    let public_tarball = "http://registry.npmjs.org/foo/-/foo-1.0.0.tgz";
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0")
+            .field("dist", serde_json::json!({ "tarball": public_tarball }))]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+
+    eprintln!("Replacing registry.npmjs.org with the host of the fake registry...");
+    let mirror_host = registry.url().split_once("://").unwrap().1.trim_end_matches('/').to_string();
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(
+            format!("lockfile=true\nregistry-replace=registry.npmjs.org->{mirror_host}\n")
+                .as_bytes(),
+        )
+        .expect("append to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("CASE: the tarball URL of the metadata");
+    pacquet.with_args(["install", "--no-frozen-lockfile"]).assert().success();
+    assert!(workspace.join("node_modules/foo/package.json").exists());
+    registry.mock("/foo/-/foo-1.0.0.tgz").assert();
+
+    eprintln!("CASE: the tarball URL of the lockfile");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    fs::remove_dir_all(store_dir).expect("remove the store");
+    let lockfile = text_block_fnl! {
+        "lockfileVersion: '6.0'"
+        ""
+        "dependencies:"
+        "  foo:"
+        "    specifier: ^1.0.0"
+        "    version: 1.0.0"
+        ""
+        "packages:"
+        "  /foo@1.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==, tarball: 'http://registry.npmjs.org/foo/-/foo-1.0.0.tgz'}"
+        "    dev: false"
+    };
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+    pacquet_command(&workspace).with_args(["install", "--frozen-lockfile"]).assert().success();
+    assert!(workspace.join("node_modules/foo/package.json").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+const CHECK_LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  foo:"
+    "    specifier: ^1.0.0"
+    "    version: 1.0.0"
+    "  bar:"
+    "    specifier: ^2.0.0"
+    "    version: 2.0.0"
+    ""
+    "packages:"
+    "  /foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+    "  /bar@2.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+};
+
+#[test]
+fn check_should_succeed_on_up_to_date_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating package.json and pnpm-lock.yaml...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.0.0",
+            "bar": "^2.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing command...");
+    pacquet.with_args(["install", "--check"]).assert().success();
+
+    eprintln!("Make sure nothing is installed");
+    assert!(!workspace.join("node_modules").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn ci_should_default_to_frozen_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating .npmrc, package.json and an outdated pnpm-lock.yaml...");
+    fs::write(workspace.join(".npmrc"), "lockfile=true\nstore-dir=../pacquet-store\n")
+        .expect("write to .npmrc");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.1.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install with CI=true...");
+    let output =
+        pacquet.with_env("CI", "true").with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(3));
+
+    eprintln!("Make sure the lockfile is reported as outdated");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("The lockfile is not up to date with package.json"));
+    assert!(stderr.contains("~ foo: ^1.0.0 -> ^1.1.0 (dependencies)"));
+    assert!(!workspace.join("node_modules").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn frozen_lockfile_should_print_lockfile_diff_of_new_dependency() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating .npmrc, package.json with a new dependency and pnpm-lock.yaml...");
+    fs::write(workspace.join(".npmrc"), "lockfile=true\nstore-dir=../pacquet-store\n")
+        .expect("write to .npmrc");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.0.0",
+            "bar": "^2.0.0",
+            "baz": "^3.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install --frozen-lockfile...");
+    let output = pacquet.with_args(["install", "--frozen-lockfile"]).output().expect("run pacquet");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(3));
+
+    eprintln!("Make sure the diff of the lockfile lists the new dependency");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diff: Vec<_> = stderr
+        .lines()
+        .skip_while(|line| !line.contains("Changes to bring pnpm-lock.yaml up to date:"))
+        .skip(1)
+        .take(3)
+        .map(|line| line.trim_start_matches([' ', '│']))
+        .collect();
+    assert_eq!(diff, ["dependencies:", "+    baz:", "+      specifier: ^3.0.0"]);
+    assert!(!workspace.join("node_modules").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn depth_should_limit_transitive_dependencies() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0").dependencies([("baz", "^1.0.0")]),
+            FakePackage::new("baz", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --depth=0...");
+    pacquet.with_args(["install", "--depth=0"]).assert().success();
+
+    eprintln!("Make sure only the direct dependency is installed");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(virtual_store_dir.join("foo@1.0.0").is_dir());
+    assert!(!virtual_store_dir.join("foo@1.0.0/node_modules/bar").exists());
+    assert!(!virtual_store_dir.join("bar@1.0.0").exists());
+    assert!(!virtual_store_dir.join("baz@1.0.0").exists());
+    assert!(!registry.mock("/bar").matched());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn should_hoist_transitive_dependencies() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0")
+                .dependencies([("bar", "^1.0.0"), ("eslint-plugin-foo", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("eslint-plugin-foo", "1.0.0"),
+        ]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure bar can be resolved from the context of foo");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert_eq!(
+        fs::read_link(virtual_store_dir.join("node_modules/bar")).unwrap(),
+        virtual_store_dir.join("bar@1.0.0/node_modules/bar"),
+    );
+    assert!(virtual_store_dir.join("node_modules/bar/package.json").is_file());
+    assert!(!workspace.join("node_modules/bar").exists());
+
+    eprintln!("Make sure eslint plugins are hoisted to the root");
+    assert_eq!(
+        fs::read_link(workspace.join("node_modules/eslint-plugin-foo")).unwrap(),
+        virtual_store_dir.join("eslint-plugin-foo@1.0.0/node_modules/eslint-plugin-foo"),
+    );
+
+    eprintln!("Opting bar out of hoisting...");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"hoist-pattern=*,!bar\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install...");
+    pacquet_command(&workspace).with_arg("install").assert().success();
+
+    eprintln!("Make sure bar is no longer hoisted");
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar").is_dir());
+    assert!(!virtual_store_dir.join("node_modules/bar").exists());
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/eslint-plugin-foo")).unwrap());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn changing_hoist_pattern_should_rebuild_node_modules() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    let modules_yaml_path = workspace.join("node_modules/.modules.yaml");
+    let install = || {
+        pacquet_command(&workspace).with_arg("install").assert().success();
+    };
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+    assert!(is_symlink_or_junction(&virtual_store_dir.join("node_modules/bar")).unwrap());
+    let modules_yaml = fs::read_to_string(&modules_yaml_path).expect("read .modules.yaml");
+    assert!(modules_yaml.contains("hoistPattern:\n- '*'\n"));
+
+    eprintln!("Installing again with the same settings keeps node_modules");
+    let marker_path = virtual_store_dir.join("marker");
+    fs::write(&marker_path, "").expect("write the marker");
+    install();
+    assert!(marker_path.exists());
+
+    eprintln!("Changing hoist-pattern...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"hoist-pattern=*,!bar\n")
+        .expect("append to .npmrc");
+    install();
+
+    eprintln!("Make sure node_modules is rebuilt without the hoisted bar");
+    assert!(!marker_path.exists());
+    assert!(!virtual_store_dir.join("node_modules/bar").exists());
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").is_file());
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    let modules_yaml = fs::read_to_string(&modules_yaml_path).expect("read .modules.yaml");
+    assert!(modules_yaml.contains("hoistPattern:\n- '*'\n- '!bar'\n"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn changing_node_linker_should_regenerate_node_modules() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+    let modules_yaml_path = workspace.join("node_modules/.modules.yaml");
+    let marker_path = workspace.join("node_modules/.pnpm/marker");
+    let install = |args: &[&str]| {
+        pacquet_command(&workspace)
+            .with_arg("install")
+            .with_args(args)
+            .output()
+            .expect("run pacquet install")
+    };
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+    let modules_yaml = fs::read_to_string(&modules_yaml_path).expect("read .modules.yaml");
+    assert!(modules_yaml.contains("nodeLinker: isolated\n"));
+
+    eprintln!("Switching node-linker...");
+    fs::write(&marker_path, "").expect("write the marker");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"node-linker=hoisted\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Make sure a frozen lockfile refuses to regenerate node_modules");
+    let output = install(&["--frozen-lockfile"]);
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pacquet_package_manager::incompatible_modules_dir"));
+    assert!(marker_path.exists());
+
+    eprintln!("Make sure the layout is regenerated otherwise");
+    let output = install(&[]);
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(!marker_path.exists());
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    let modules_yaml = fs::read_to_string(&modules_yaml_path).expect("read .modules.yaml");
+    assert!(modules_yaml.contains("nodeLinker: hoisted\n"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn prefer_offline_should_not_request_the_registry_after_an_online_install() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { mut registry, cache_dir, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install to populate the cache and the store...");
+    pacquet.with_arg("install").assert().success();
+    assert!(cache_dir.join("metadata").is_dir());
+
+    eprintln!("Removing every route of the registry and node_modules...");
+    registry.server().reset();
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+
+    eprintln!("Executing pacquet install --prefer-offline...");
+    let output = pacquet_command(&workspace)
+        .with_args(["install", "--prefer-offline"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Make sure the dependencies are installed");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(virtual_store_dir.join("foo@1.0.0/node_modules/foo/package.json").is_file());
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").is_file());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn no_verify_store_integrity_should_trust_the_store_with_prefer_offline() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { mut registry, store_dir, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install to populate the cache and the store...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Corrupting package.json in the store, removing every route of the registry...");
+    let store_dir = StoreDir::new(store_dir);
+    let integrity = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==";
+    let cas_paths = store_dir
+        .read_index_file(&integrity.parse().expect("parse integrity"))
+        .expect("read index file")
+        .cas_paths(&store_dir)
+        .expect("find every file in the store");
+    let corrupt_path = &cas_paths["package.json"];
+    // replace the file rather than write through it, which would also change node_modules
+    fs::remove_file(corrupt_path).expect("remove package.json from the store");
+    fs::write(corrupt_path, "{}").expect("corrupt package.json");
+    registry.server().reset();
+    let install = |args: &[&str]| {
+        fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+        pacquet_command(&workspace)
+            .with_args(["install", "--prefer-offline"])
+            .with_args(args)
+            .output()
+            .expect("run pacquet install")
+    };
+
+    eprintln!("Executing pacquet install --prefer-offline --no-verify-store-integrity...");
+    let output = install(&["--no-verify-store-integrity"]);
+    dbg!(&output);
+    assert!(output.status.success());
+    let package_json = workspace.join("node_modules/.pnpm/foo@1.0.0/node_modules/foo/package.json");
+    assert_eq!(fs::read_to_string(package_json).expect("read package.json"), "{}");
+
+    eprintln!("Executing pacquet install --prefer-offline...");
+    let output = install(&[]);
+    dbg!(&output);
+    assert!(!output.status.success(), "the corrupt file should be downloaded again");
+    assert!(!corrupt_path.exists(), "the corrupt file should be removed from the store");
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn should_install_aliased_dependencies() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("lodash", "4.17.21"),
+            FakePackage::new("foo", "1.0.0").dependencies([("my-bar", "npm:bar@^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "my-lodash": "npm:lodash@^4",
+            "foo": "^1.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_args(["install"]).assert().success();
+
+    eprintln!("Make sure the direct alias links to the real package");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert_eq!(
+        fs::read_link(workspace.join("node_modules/my-lodash")).unwrap(),
+        virtual_store_dir.join("lodash@4.17.21/node_modules/lodash"),
+    );
+    assert!(!workspace.join("node_modules/lodash").exists());
+
+    eprintln!("Make sure the transitive alias links to the real package");
+    assert_eq!(
+        fs::read_link(virtual_store_dir.join("foo@1.0.0/node_modules/my-bar")).unwrap(),
+        virtual_store_dir.join("bar@1.0.0/node_modules/bar"),
+    );
+
+    registry.mock("/lodash").assert();
+    registry.mock("/bar").assert();
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn integrity_mismatch_should_suggest_a_remedy() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0"), FakePackage::new("bar", "2.0.0")]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Enabling the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Creating package.json and a pnpm-lock.yaml with a wrong integrity...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.0.0",
+            "bar": "^2.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    let wrong_integrity = "sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg==";
+    let lockfile = CHECK_LOCKFILE.replacen(
+        "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==",
+        wrong_integrity,
+        1,
+    );
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install --frozen-lockfile...");
+    let output =
+        pacquet.with_args(["install", "--frozen-lockfile"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+
+    eprintln!("Make sure the error has a code, is only printed once, and suggests a remedy");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" "); // undo the line wrapping
+    assert!(stderr.contains("pacquet_tarball::verify_checksum_error"));
+    assert_eq!(stderr.matches("Failed to verify the integrity of").count(), 1);
+    assert!(stderr.contains("help: The tarball doesn't match the integrity"));
+    assert!(stderr.contains("update its integrity in pnpm-lock.yaml and try again"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn resolutions_should_force_transitive_versions() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("bar", "1.1.0"),
+        ]);
+
+    eprintln!("Creating package.json with resolutions...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.0.0",
+        },
+        "resolutions": {
+            "**/bar": "1.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure the transitive dependency is forced to the resolution");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert_eq!(
+        fs::read_link(virtual_store_dir.join("foo@1.0.0/node_modules/bar")).unwrap(),
+        virtual_store_dir.join("bar@1.0.0/node_modules/bar"),
+    );
+    assert!(!virtual_store_dir.join("bar@1.1.0").exists());
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn prefer_frozen_lockfile_should_skip_resolution_of_up_to_date_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0"), FakePackage::new("bar", "2.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Enabling the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\nprefer-frozen-lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Creating package.json and pnpm-lock.yaml...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.0.0",
+            "bar": "^2.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure the packages are installed from the lockfile alone");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/bar")).unwrap());
+    // the tarballs aren't checked: foo and bar share one, which only one of them may download
+    for path in ["/foo", "/foo/1.0.0", "/bar", "/bar/2.0.0"] {
+        assert!(!registry.mock(path).matched(), "{path} shouldn't be requested");
+    }
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn prefer_frozen_lockfile_should_resolve_outdated_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.1.0"), FakePackage::new("bar", "2.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Enabling the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\nprefer-frozen-lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Creating package.json and an outdated pnpm-lock.yaml...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.1.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure the dependencies are resolved from the registry");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(workspace.join("node_modules/.pnpm/foo@1.1.0").is_dir());
+    assert!(!workspace.join("node_modules/bar").exists());
+    registry.mock("/foo").assert();
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn check_should_fail_with_diff_on_outdated_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating package.json and pnpm-lock.yaml...");
+    let package_json_content = serde_json::json!({
+        "dependencies": {
+            "foo": "^1.1.0",
+            "baz": "^3.0.0",
+        },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), CHECK_LOCKFILE).expect("write to pnpm-lock.yaml");
+
+    eprintln!("Executing command...");
+    let output = pacquet.with_args(["install", "--check"]).output().expect("run pacquet");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(3));
+
+    eprintln!("Make sure the diff is reported");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("+ baz@^3.0.0 (dependencies)"));
+    assert!(stderr.contains("- bar@^2.0.0 (dependencies)"));
+    assert!(stderr.contains("~ foo: ^1.0.0 -> ^1.1.0 (dependencies)"));
+
+    eprintln!("Make sure nothing is installed");
+    assert!(!workspace.join("node_modules").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn strict_peer_dependencies_should_ignore_optional_peers() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0")
+                .peer_dependencies([("bar", "^1.0.0")])
+                .field("peerDependenciesMeta", serde_json::json!({ "bar": { "optional": true } })),
+            FakePackage::new("baz", "1.0.0").peer_dependencies([("bar", "^1.0.0")]),
+        ]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Enabling strict-peer-dependencies...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"auto-install-peers=false\nstrict-peer-dependencies=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("CASE: the only missing peer is optional");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    let output = pacquet.with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("CASE: a required peer is missing");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "baz": "1.0.0" } }"#)
+        .expect("write to package.json");
+    let output =
+        pacquet_command(&workspace).with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("baz@1.0.0 has missing peer dependencies: bar@^1.0.0"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn peer_dependency_rules_should_silence_peer_problems() {
+    let CommandTempCwd { root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").peer_dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("baz", "1.0.0").peer_dependencies([("bar", "^2.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+    let install = |manifest: serde_json::Value, npmrc: &str| {
+        fs::write(workspace.join("package.json"), manifest.to_string())
+            .expect("write to package.json");
+        fs::write(&npmrc_path, npmrc).expect("write to .npmrc");
+        let output =
+            pacquet_command(&workspace).with_arg("install").output().expect("run pacquet install");
+        dbg!(&output);
+        output
+    };
+    let npmrc =
+        fs::read_to_string(&npmrc_path).expect("read .npmrc") + "auto-install-peers=false\n";
+    let strict_npmrc = npmrc.clone() + "strict-peer-dependencies=true\n";
+
+    eprintln!("CASE: a missing peer is warned about");
+    let manifest = serde_json::json!({ "dependencies": { "foo": "1.0.0" } });
+    let output = install(manifest, &npmrc);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Missing peer dependencies"));
+
+    eprintln!("CASE: a missing peer listed in ignoreMissing is not warned about");
+    let manifest = serde_json::json!({
+        "dependencies": { "foo": "1.0.0" },
+        "pnpm": { "peerDependencyRules": { "ignoreMissing": ["bar"] } },
+    });
+    let output = install(manifest, &npmrc);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("peer dependencies"));
+
+    eprintln!("CASE: an unmet peer fails strict mode");
+    let manifest = serde_json::json!({ "dependencies": { "bar": "1.0.0", "baz": "1.0.0" } });
+    let output = install(manifest, &strict_npmrc);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("baz@1.0.0 has unmet peer dependencies: bar@^2.0.0 (found 1.0.0)"));
+
+    eprintln!("CASE: an unmet peer is accepted via allowedVersions");
+    let manifest = serde_json::json!({
+        "dependencies": { "bar": "1.0.0", "baz": "1.0.0" },
+        "pnpm": { "peerDependencyRules": { "allowedVersions": { "bar": "1" } } },
+    });
+    let output = install(manifest, &strict_npmrc);
+    assert!(output.status.success());
+    assert!(workspace.join("node_modules/baz").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn store_dir_flag_should_override_npmrc() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, .. } = npmrc_info;
+    let npmrc_text = fs::read_to_string(&npmrc_path).expect("read .npmrc");
+    let scratch_store_dir = root.path().join("scratch-store");
+
+    eprintln!("Executing pacquet install --store-dir...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    pacquet.with_args(["install", "--store-dir", "../scratch-store"]).assert().success();
+
+    eprintln!("Make sure the package is installed from the overridden store");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+    assert!(!get_all_files(&scratch_store_dir.join("v3/files")).is_empty());
+    assert!(!store_dir.exists());
+
+    eprintln!("Make sure the override is not persisted");
+    assert_eq!(fs::read_to_string(&npmrc_path).expect("read .npmrc"), npmrc_text);
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn modules_dir_flag_should_relocate_the_tree() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --modules-dir=deps...");
+    pacquet.with_args(["install", "--modules-dir=deps"]).assert().success();
+
+    eprintln!("Make sure the tree is built under deps/");
+    let virtual_store_dir = workspace.join("deps/.pnpm");
+    assert_eq!(
+        fs::read_link(workspace.join("deps/foo")).unwrap(),
+        virtual_store_dir.join("foo@1.0.0/node_modules/foo"),
+    );
+    assert_eq!(
+        fs::read_link(virtual_store_dir.join("foo@1.0.0/node_modules/bar")).unwrap(),
+        virtual_store_dir.join("bar@1.0.0/node_modules/bar"),
+    );
+    assert!(!workspace.join("node_modules").exists());
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn dry_run_should_print_plan_without_installing() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, store_dir, cache_dir, .. } = npmrc_info;
+
+    eprintln!("Creating package.json and a stale virtual store entry...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    fs::create_dir_all(workspace.join("node_modules/.pnpm/old@1.0.0")).expect("create old entry");
+    let node_modules_before = get_all_folders(&workspace.join("node_modules"));
+
+    eprintln!("Executing pacquet install --dry-run...");
+    let output = pacquet.with_args(["install", "--dry-run"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Make sure the plan is printed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Packages to add: 2\n+ bar 1.0.0\n+ foo 1.0.0\n"));
+    assert!(stdout.contains("Packages to remove: 1\n- old@1.0.0\n"));
+    assert!(stdout.contains("Total size: "));
+
+    eprintln!("Make sure nothing is downloaded or written");
+    assert_eq!(get_all_folders(&workspace.join("node_modules")), node_modules_before);
+    assert!(!registry.mock("/foo/-/foo-1.0.0.tgz").matched());
+    assert!(!registry.mock("/bar/-/bar-1.0.0.tgz").matched());
+    assert!(!store_dir.exists());
+    assert!(!cache_dir.join("metadata").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn invalid_manifest_should_point_at_the_field() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating package.json with dependencies of the wrong type...");
+    fs::write(workspace.join("package.json"), "{\n  \"dependencies\": [\"foo\"]\n}\n")
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    let output = pacquet.with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+
+    eprintln!("Make sure the error names the field");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" "); // undo the line wrapping
+    assert!(stderr.contains("pacquet_package_manifest::invalid_manifest"));
+    assert!(stderr.contains("expected `dependencies` to be an object of strings"));
+    assert!(stderr.contains("package.json at line 2 column 19"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn incompatible_package_manager_should_fail_unless_not_strict() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Creating package.json that asks for pnpm@7...");
+    let package_json_content = serde_json::json!({
+        "packageManager": "pnpm@7.33.0",
+        "dependencies": { "foo": "^1.0.0" },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    let output = pacquet.with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" "); // undo the line wrapping
+    assert!(stderr.contains("configured to use pnpm@7.33.0"));
+    assert!(!workspace.join("node_modules").exists());
+
+    eprintln!("Disabling package-manager-strict...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"package-manager-strict=false\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install...");
+    let output =
+        pacquet_command(&workspace).with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pnpm@7.33.0"));
+    assert!(workspace.join("node_modules/foo").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn should_fail_early_when_the_store_runs_out_of_space() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            // the integrity differs from the one of foo, so that the size of bar is counted
+            // separately
+            FakePackage::new("bar", "1.0.0").field(
+                "dist",
+                serde_json::json!({
+                    "unpackedSize": 1u64 << 60,
+                    "integrity": "sha512-2CxOtSYcuciqmFXt1n0b0QSC9BUphY2SUJTRc/pmKqkf85vFsYhhUnNIQCHfsW/YKEz2hMzw/Hlb46ovwebBgQ==",
+                }),
+            ),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    let output = pacquet.with_arg("install").output().expect("execute pacquet install");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprintln!("STDERR:\n{stderr}\n");
+    assert!(stderr.contains("pacquet_package_manager::insufficient_space"));
+
+    eprintln!("Make sure that no tarball was downloaded");
+    assert!(!registry.mock("/foo/-/foo-1.0.0.tgz").matched());
+    assert!(!registry.mock("/bar/-/bar-1.0.0.tgz").matched());
+    assert!(!workspace.join("node_modules/.pnpm/foo@1.0.0").exists());
+    assert!(!workspace.join("node_modules/.pnpm/bar@1.0.0").exists());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn network_timeout_should_abort_a_stalled_download() {
+    let mut cdn = mockito::Server::new();
+    let tarball_url = format!("{}/foo-1.0.0.tgz", cdn.url());
+    let _tarball_mock = cdn
+        .mock("GET", "/foo-1.0.0.tgz")
+        .with_chunked_body(|writer| {
+            writer.write_all(b"stalled")?;
+            thread::sleep(Duration::from_secs(5));
+            Ok(())
+        })
+        .create();
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0")
+            .field("dist", serde_json::json!({ "tarball": tarball_url }))]);
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --network-timeout=500...");
+    let started = Instant::now();
+    let output = pacquet
+        .with_args(["install", "--network-timeout=500"])
+        .output()
+        .expect("execute pacquet install");
+    let elapsed = started.elapsed();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprintln!("STDERR:\n{stderr}\n");
+    assert!(!output.status.success());
+    assert!(elapsed < Duration::from_secs(4), "took {elapsed:?}");
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert!(stderr.contains("pacquet_network::network_timeout"));
+    assert!(stderr.contains(&tarball_url));
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn check_registry_should_fail_fast_on_unreachable_registry() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+    // bind then drop a listener to find a port that nothing listens on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let registry = format!("http://127.0.0.1:{port}/");
+
+    eprintln!("Creating package.json and .npmrc...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    let npmrc =
+        format!("registry={registry}\nstore-dir=../pacquet-store\ncache-dir=../pacquet-cache\n");
+    fs::write(workspace.join(".npmrc"), npmrc).expect("write to .npmrc");
+
+    eprintln!("Executing pacquet install --check-registry...");
+    let started = Instant::now();
+    let output =
+        pacquet.with_args(["install", "--check-registry"]).output().expect("run pacquet install");
+    let elapsed = started.elapsed();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprintln!("STDERR:\n{stderr}\n");
+    assert!(!output.status.success());
+    assert!(elapsed < Duration::from_secs(5), "took {elapsed:?}");
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert!(stderr.contains(&format!("The registry {registry} is unreachable")));
+    assert!(stderr.contains("pacquet_network::registry_unreachable"));
+    assert!(!workspace.join("node_modules").exists());
+
+    eprintln!("CASE: the check is skipped with --prefer-offline");
+    let output = pacquet_command(&workspace)
+        .with_args(["install", "--check-registry", "--prefer-offline"])
+        .output()
+        .expect("run pacquet install");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprintln!("STDERR:\n{stderr}\n");
+    assert!(!output.status.success()); // foo can't be resolved either way
+    assert!(!stderr.contains("pacquet_network::registry_unreachable"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn check_registry_should_pass_on_reachable_registry() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --check-registry...");
+    pacquet.with_args(["install", "--check-registry"]).assert().success();
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/foo")).unwrap());
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn use_node_version_should_decide_engine_compatibility() {
+    let package =
+        FakePackage::new("foo", "1.0.0").field("engines", serde_json::json!({ "node": "^18.0.0" }));
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[package]);
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("CASE: the package supports the Node.js version");
+    let output = pacquet
+        .with_args(["install", "--use-node-version=18.0.0"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("requires node"));
+    assert!(workspace.join("node_modules/foo").exists());
+
+    eprintln!("CASE: the package doesn't support the Node.js version");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    let output = pacquet_command(&workspace)
+        .with_args(["install", "--use-node-version=20.0.0"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("foo@1.0.0 requires node ^18.0.0, but the Node.js version is 20.0.0"));
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn installing_the_same_lockfile_twice_should_produce_the_same_tree() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0"),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("baz", "1.0.0"),
+            FakePackage::new("qux", "2.0.0"),
+        ]);
+
+    eprintln!("Creating package.json, pnpm-lock.yaml and patching .npmrc...");
+    fs::write(
+        workspace.join("package.json"),
+        serde_json::json!({
+            "dependencies": { "foo": "^1.0.0", "baz": "^1.0.0" },
+            "devDependencies": { "qux": "^2.0.0", "alias": "npm:qux@2.0.0" },
+        })
+        .to_string(),
+    )
+    .expect("write to package.json");
+    let lockfile = text_block_fnl! {
+        "lockfileVersion: '6.0'"
+        ""
+        "dependencies:"
+        "  baz:"
+        "    specifier: ^1.0.0"
+        "    version: 1.0.0"
+        "  foo:"
+        "    specifier: ^1.0.0"
+        "    version: 1.0.0(baz@1.0.0)"
+        ""
+        "devDependencies:"
+        "  alias:"
+        "    specifier: npm:qux@2.0.0"
+        "    version: /qux@2.0.0"
+        "  qux:"
+        "    specifier: ^2.0.0"
+        "    version: 2.0.0"
+        ""
+        "packages:"
+        ""
+        "  /bar@1.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        ""
+        "  /baz@1.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dependencies:"
+        "      bar: 1.0.0"
+        "    dev: false"
+        ""
+        "  /foo@1.0.0(baz@1.0.0):"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    peerDependencies:"
+        "      baz: ^1.0.0"
+        "    dependencies:"
+        "      bar: 1.0.0"
+        "      baz: 1.0.0"
+        "    dev: false"
+        ""
+        "  /qux@2.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dependencies:"
+        "      bar: 1.0.0"
+        "    dev: true"
+    };
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+    OpenOptions::new()
+        .append(true)
+        .open(workspace.join(".npmrc"))
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    let node_modules = workspace.join("node_modules");
+
+    eprintln!("Installing for the first time...");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+    let first = tree_snapshot(&node_modules);
+    dbg!(first.keys().collect::<Vec<_>>());
+    for path in [
+        "foo",
+        "baz",
+        "qux",
+        "alias",
+        ".pnpm/foo@1.0.0_baz@1.0.0/node_modules/foo",
+        ".pnpm/foo@1.0.0_baz@1.0.0/node_modules/bar",
+        ".pnpm/foo@1.0.0_baz@1.0.0/node_modules/baz",
+        ".pnpm/baz@1.0.0/node_modules/bar",
+        ".pnpm/qux@2.0.0/node_modules/bar",
+        ".pnpm/bar@1.0.0/node_modules/bar/package.json",
+    ] {
+        assert!(first.contains_key(path), "{path} should be installed");
+    }
+
+    eprintln!("Installing for the second time...");
+    fs::remove_dir_all(&node_modules).expect("remove node_modules");
+    pacquet_command(&workspace).with_args(["install", "--frozen-lockfile"]).assert().success();
+    assert_eq!(tree_snapshot(&node_modules), first);
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn frozen_lockfile_should_accept_lockfile_v5() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0"), FakePackage::new("bar", "2.0.0")]);
+
+    eprintln!("Creating package.json, a v5 pnpm-lock.yaml and patching .npmrc...");
+    fs::write(
+        workspace.join("package.json"),
+        r#"{ "dependencies": { "foo": "^1.0.0", "bar": "2.0.0" } }"#,
+    )
+    .expect("write to package.json");
+    let lockfile = text_block_fnl! {
+        "lockfileVersion: 5.4"
+        ""
+        "specifiers:"
+        "  bar: 2.0.0"
+        "  foo: ^1.0.0"
+        ""
+        "dependencies:"
+        "  bar: 2.0.0"
+        "  foo: 1.0.0_bar@2.0.0"
+        ""
+        "packages:"
+        ""
+        "  /bar/2.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        ""
+        "  /foo/1.0.0_bar@2.0.0:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    peerDependencies:"
+        "      bar: ^2.0.0"
+        "    dependencies:"
+        "      bar: 2.0.0"
+        "    dev: false"
+    };
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+    OpenOptions::new()
+        .append(true)
+        .open(workspace.join(".npmrc"))
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install --frozen-lockfile...");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(virtual_store_dir.join("foo@1.0.0_bar@2.0.0/node_modules/foo/package.json").exists());
+    assert!(virtual_store_dir.join("foo@1.0.0_bar@2.0.0/node_modules/bar").exists());
+    assert!(workspace.join("node_modules/foo").exists());
+    assert!(workspace.join("node_modules/bar").exists());
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn before_should_resolve_versions_published_before_the_date() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").published("2022-01-01T00:00:00.000Z"),
+            FakePackage::new("foo", "1.1.0").published("2023-02-01T00:00:00.000Z"),
+            FakePackage::new("bar", "1.0.0").published("2022-06-01T00:00:00.000Z"),
+            FakePackage::new("bar", "2.0.0").published("2023-06-01T00:00:00.000Z"),
+        ]);
+
+    eprintln!("Creating package.json...");
+    fs::write(
+        workspace.join("package.json"),
+        r#"{ "dependencies": { "foo": "^1.0.0", "bar": "latest" } }"#,
+    )
+    .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --before=2023-01-01...");
+    pacquet.with_args(["install", "--before=2023-01-01"]).assert().success();
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(virtual_store_dir.join("foo@1.0.0").exists());
+    assert!(!virtual_store_dir.join("foo@1.1.0").exists());
+    assert!(virtual_store_dir.join("bar@1.0.0").exists());
+    assert!(!virtual_store_dir.join("bar@2.0.0").exists());
+
+    eprintln!("Make sure an invalid date is rejected");
+    let output = pacquet_command(&workspace)
+        .with_args(["install", "--before=yesterday"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid date \"yesterday\""));
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn directory_dependency_should_only_import_published_files() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+
+    eprintln!("Creating the directory dependency...");
+    let dependency_dir = workspace.join("packages/local-dep");
+    for (path, content) in [
+        (
+            "package.json",
+            r#"{ "name": "local-dep", "version": "1.0.0", "files": ["lib", "!lib/*.test.js"] }"#,
+        ),
+        ("README.md", "# local-dep"),
+        ("lib/index.js", "module.exports = 42"),
+        ("lib/index.test.js", "test()"),
+        ("src/index.ts", "export default 42"),
+        ("node_modules/dep/index.js", "module.exports = 0"),
+    ] {
+        let path = dependency_dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).expect("create parent dir");
+        fs::write(path, content).expect("write file");
+    }
+
+    eprintln!("Creating package.json...");
+    let manifest = serde_json::json!({
+        "dependencies": {
+            "local-dep": "file:packages/local-dep",
+        },
+    });
+    fs::write(workspace.join("package.json"), manifest.to_string()).expect("write package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure only the published files are imported");
+    let symlink_path = workspace.join("node_modules/local-dep");
+    assert!(is_symlink_or_junction(&symlink_path).unwrap());
+    let virtual_path = workspace.join("node_modules/.pnpm/local-dep@1.0.0/node_modules/local-dep");
+    let mut files = get_all_files(&virtual_path);
+    files.sort();
+    assert_eq!(files, ["README.md", "lib/index.js", "package.json"]);
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn prepared_git_dependency_should_be_reused_from_store() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating a repository whose package is built by prepare...");
+    let repo_dir = root.path().join("repo");
+    let build_log = root.path().join("build.log");
+    fs::create_dir_all(&repo_dir).expect("create repository directory");
+    let prepare = format!("echo built > built.txt && echo prepare >> '{}'", build_log.display());
+    let manifest = serde_json::json!({
+        "name": "foo",
+        "version": "1.0.0",
+        "scripts": { "prepare": prepare },
+        "devDependencies": { "typescript": "^5.0.0" },
+    });
+    fs::write(repo_dir.join("package.json"), manifest.to_string()).expect("write package.json");
+    git(&repo_dir, &["init", "--quiet"]);
+    git(&repo_dir, &["add", "package.json"]);
+    git(&repo_dir, &["commit", "--quiet", "--message", "1.0.0"]);
+    let commit = git(&repo_dir, &["rev-parse", "HEAD"]);
+
+    eprintln!("Creating package.json...");
+    let specifier = format!("git+file://{}#{commit}", repo_dir.display());
+    let package_json_content = serde_json::json!({ "dependencies": { "foo": specifier } });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+
+    let build_count = || fs::read_to_string(&build_log).unwrap_or_default().lines().count();
+    let built_file = workspace.join("node_modules/foo/built.txt");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+    assert_eq!(fs::read_to_string(&built_file).unwrap().trim(), "built");
+    assert_eq!(build_count(), 1);
+
+    eprintln!("Executing pacquet install again without node_modules...");
+    fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    pacquet_command(&workspace).with_arg("install").assert().success();
+    assert_eq!(fs::read_to_string(&built_file).unwrap().trim(), "built");
+    assert_eq!(build_count(), 1, "prepare only runs on the first install");
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn include_and_omit_should_select_top_level_dependency_types() {
+    let CommandTempCwd { root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("prod-dep", "1.0.0"),
+            FakePackage::new("dev-dep", "1.0.0"),
+            FakePackage::new("optional-dep", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    let manifest = serde_json::json!({
+        "dependencies": { "prod-dep": "^1.0.0" },
+        "devDependencies": { "dev-dep": "^1.0.0" },
+        "optionalDependencies": { "optional-dep": "^1.0.0" },
+    });
+    fs::write(workspace.join("package.json"), manifest.to_string()).expect("write to package.json");
+
+    let installed_with = |args: &[&str]| {
+        let modules_dir = workspace.join("node_modules");
+        if modules_dir.exists() {
+            fs::remove_dir_all(&modules_dir).expect("remove node_modules");
+        }
+        eprintln!("Executing pacquet install {args:?}...");
+        pacquet_command(&workspace).with_arg("install").with_args(args).assert().success();
+        ["prod-dep", "dev-dep", "optional-dep"]
+            .into_iter()
+            .filter(|name| modules_dir.join(name).exists())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(installed_with(&["--include=prod,optional"]), ["prod-dep", "optional-dep"]);
+    assert_eq!(installed_with(&["--omit=dev"]), ["prod-dep", "optional-dep"]);
+    assert_eq!(installed_with(&["--omit=dev,optional"]), ["prod-dep"]);
+    assert_eq!(installed_with(&["--include=dev", "--omit=optional"]), ["dev-dep"]);
+    assert_eq!(installed_with(&["--include=prod,dev", "--omit=dev"]), ["prod-dep"]);
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn prune_should_remove_dependencies_removed_from_manifest() {
+    let CommandTempCwd { root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("baz", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    let install = |manifest: serde_json::Value, args: &[&str]| {
+        fs::write(workspace.join("package.json"), manifest.to_string())
+            .expect("write to package.json");
+        eprintln!("Executing pacquet install {args:?}...");
+        pacquet_command(&workspace).with_arg("install").with_args(args).assert().success();
+    };
+    let modules_dir = workspace.join("node_modules");
+    let virtual_store_dir = modules_dir.join(".pnpm");
+
+    install(serde_json::json!({ "dependencies": { "foo": "^1.0.0", "baz": "^1.0.0" } }), &[]);
+    assert!(is_symlink_or_junction(&modules_dir.join("foo")).unwrap());
+    assert!(virtual_store_dir.join("bar@1.0.0").exists());
+
+    eprintln!("Removing foo with --no-prune keeps it");
+    install(serde_json::json!({ "dependencies": { "baz": "^1.0.0" } }), &["--no-prune"]);
+    assert!(is_symlink_or_junction(&modules_dir.join("foo")).unwrap());
+
+    eprintln!("Installing with --prune removes foo and its dependencies");
+    install(serde_json::json!({ "dependencies": { "baz": "^1.0.0" } }), &["--prune"]);
+    assert!(!modules_dir.join("foo").exists());
+    assert!(!is_symlink_or_junction(&modules_dir.join("foo")).unwrap_or(false));
+    assert!(!virtual_store_dir.join("foo@1.0.0").exists());
+    assert!(!virtual_store_dir.join("bar@1.0.0").exists());
+    assert!(is_symlink_or_junction(&modules_dir.join("baz")).unwrap());
+    assert!(virtual_store_dir.join("baz@1.0.0/node_modules/baz").exists());
+
+    drop((root, registry)); // cleanup
+}
@@ -0,0 +1,196 @@
+use command_extra::CommandExtra;
+use pacquet_lockfile::{Lockfile, RootProjectSnapshot};
+use pacquet_testing_utils::{
+    bin::{AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use pipe_trait::Pipe;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+fn install_with_args(args: &[&str]) -> String {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet {args:?} install...");
+    let output = pacquet.with_args(args).with_arg("install").output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(workspace.join("node_modules/foo").exists());
+
+    drop((root, registry)); // cleanup
+    String::from_utf8(output.stderr).expect("stderr is valid UTF-8")
+}
+
+#[test]
+fn loglevel_silent_should_suppress_info_lines() {
+    let stderr = install_with_args(&["--loglevel=silent"]);
+    assert!(!stderr.contains("Import package"));
+}
+
+#[test]
+fn loglevel_debug_should_show_info_lines() {
+    let stderr = install_with_args(&["--loglevel=debug"]);
+    assert!(stderr.contains("Import package"));
+}
+
+#[test]
+fn verbose_should_show_info_lines() {
+    let stderr = install_with_args(&["-v"]);
+    assert!(stderr.contains("Import package"));
+}
+
+#[test]
+fn quiet_should_suppress_output_on_success() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet --quiet install --reporter=ndjson...");
+    let output = pacquet
+        .with_args(["--quiet", "install", "--reporter=ndjson"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(workspace.join("node_modules/foo").exists());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn quiet_should_still_report_errors() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^2.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet -q install...");
+    let output = pacquet.with_args(["-q", "install"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn silent_reporter_should_suppress_output_but_not_side_effects() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+    let AddFakeRegistry { registry, npmrc_path, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Enabling the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install --reporter=silent...");
+    let output =
+        pacquet.with_args(["install", "--reporter=silent"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    eprintln!("Make sure the whole tree is installed");
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    assert!(workspace.join("node_modules/foo").exists());
+    assert!(virtual_store_dir.join("foo@1.0.0/node_modules/bar").exists());
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").exists());
+
+    eprintln!("Make sure the lockfile is written");
+    let lockfile = workspace
+        .join("pnpm-lock.yaml")
+        .pipe(fs::read_to_string)
+        .expect("read pnpm-lock.yaml")
+        .pipe_as_ref(Lockfile::from_yaml_str)
+        .expect("parse pnpm-lock.yaml")
+        .expect("lockfile isn't empty");
+    let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+        panic!("expected the lockfile of a single project");
+    };
+    let dependency =
+        &project_snapshot.dependencies.as_ref().expect("dependencies")[&"foo".parse().unwrap()];
+    assert_eq!(
+        (dependency.specifier.as_str(), dependency.version.to_string().as_str()),
+        ("^1.0.0", "1.0.0")
+    );
+    let mut packages: Vec<_> =
+        lockfile.packages.iter().flatten().map(|(path, _)| path.to_string()).collect();
+    packages.sort();
+    assert_eq!(packages, ["/bar@1.0.0", "/foo@1.0.0"]);
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn silent_reporter_should_report_errors_by_exit_code_only() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "missing": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --reporter=silent...");
+    let output =
+        pacquet.with_args(["install", "--reporter=silent"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn loglevel_debug_should_summarize_slowest_packages() {
+    let stderr = install_with_args(&["--loglevel=debug"]);
+    let summary: Vec<_> =
+        stderr.lines().skip_while(|line| !line.ends_with("Slowest packages:")).skip(1).collect();
+    dbg!(&summary);
+    let line = summary
+        .iter()
+        .find(|line| line.contains("foo@1.0.0 took "))
+        .expect("foo is in the summary");
+    let (_, timing) = line.split_once(" took ").unwrap();
+    assert!(!timing.starts_with("0.0ns"), "duration is non-zero: {timing}");
+    for phase in ["resolve", "download", "extract", "link"] {
+        assert!(timing.contains(phase), "{phase} is measured: {timing}");
+    }
+}
+
+#[test]
+fn loglevel_info_should_not_summarize_packages() {
+    let stderr = install_with_args(&[]);
+    assert!(!stderr.contains("Slowest packages:"));
+}
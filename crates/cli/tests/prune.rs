@@ -0,0 +1,64 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_package_manager::symlink_package;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, CommandTempCwd},
+    fake_registry::FakePackage,
+    fs::is_symlink_or_junction,
+};
+use std::fs;
+
+#[test]
+fn prune_should_remove_extraneous_packages() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("baz", "1.0.0"),
+        ]);
+
+    eprintln!("Installing foo and the dev dependency baz...");
+    let package_json_content = serde_json::json!({
+        "dependencies": { "foo": "^1.0.0" },
+        "devDependencies": { "baz": "^1.0.0" },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Adding a symlink out of the virtual store and an extraneous package...");
+    let modules_dir = workspace.join("node_modules");
+    let virtual_store_dir = modules_dir.join(".pnpm");
+    fs::create_dir_all(workspace.join("vendor/extra")).expect("create vendor/extra");
+    symlink_package(&workspace.join("vendor/extra"), &modules_dir.join("extra"))
+        .expect("link extra");
+    fs::create_dir_all(virtual_store_dir.join("old@1.0.0/node_modules/old"))
+        .expect("create old@1.0.0");
+
+    let prune = |args: &[&str]| {
+        pacquet_command(&workspace).with_arg("prune").with_args(args).assert().success();
+    };
+
+    eprintln!("Executing pacquet prune...");
+    prune(&[]);
+    assert!(!virtual_store_dir.join("old@1.0.0").exists());
+    assert!(
+        is_symlink_or_junction(&modules_dir.join("extra")).unwrap(),
+        "a symlink that the install didn't create should be kept",
+    );
+    assert!(workspace.join("vendor/extra").is_dir(), "the target of the symlink should be kept");
+    assert!(is_symlink_or_junction(&modules_dir.join("foo")).unwrap());
+    assert!(is_symlink_or_junction(&modules_dir.join("baz")).unwrap());
+    assert!(virtual_store_dir.join("foo@1.0.0/node_modules/foo/package.json").is_file());
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").is_file());
+    assert!(virtual_store_dir.join("baz@1.0.0/node_modules/baz/package.json").is_file());
+
+    eprintln!("Executing pacquet prune --prod...");
+    prune(&["--prod"]);
+    assert!(!modules_dir.join("baz").exists());
+    assert!(!virtual_store_dir.join("baz@1.0.0").exists());
+    assert!(is_symlink_or_junction(&modules_dir.join("foo")).unwrap());
+    assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").is_file());
+
+    drop((root, npmrc_info)); // cleanup
+}
@@ -0,0 +1,188 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use pretty_assertions::assert_eq;
+use std::{fs, path::Path, process::Output};
+
+/// Give the installed package a `postinstall` script that logs its name and a timestamp to
+/// `build.log` of the project.
+fn add_postinstall_script(package_dir: &Path, name: &str) {
+    let script = r#"echo "$npm_package_name $(date +%s%N)" >> "$INIT_CWD/build.log""#;
+    set_postinstall_script(package_dir, name, script);
+}
+
+/// Replace the `package.json` of the installed package with one that has a `postinstall` script.
+///
+/// The file is replaced rather than edited because it may be linked to the store.
+fn set_postinstall_script(package_dir: &Path, name: &str, script: &str) {
+    let manifest = serde_json::json!({
+        "name": name,
+        "version": "1.0.0",
+        "scripts": { "postinstall": script },
+    });
+    let manifest_path = package_dir.join("package.json");
+    fs::remove_file(&manifest_path).expect("remove package.json");
+    fs::write(&manifest_path, manifest.to_string()).expect("write package.json");
+}
+
+/// List the lines of `build.log` as names and timestamps.
+fn read_build_log(workspace: &Path) -> Vec<(String, u128)> {
+    fs::read_to_string(workspace.join("build.log"))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| {
+            let (name, timestamp) = line.split_once(' ').expect("name and timestamp");
+            (name.to_string(), timestamp.parse().expect("parse timestamp"))
+        })
+        .collect()
+}
+
+fn names(log: &[(String, u128)]) -> Vec<&str> {
+    log.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+fn pacquet_rebuild(workspace: &Path, args: &[&str]) -> Output {
+    let output = pacquet_command(workspace)
+        .with_arg("rebuild")
+        .with_args(args)
+        .output()
+        .expect("run pacquet rebuild");
+    dbg!(&output);
+    output
+}
+
+#[test]
+fn should_rerun_build_scripts_in_dependency_order() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("zed", "^1.0.0")]),
+            FakePackage::new("zed", "1.0.0"),
+        ]);
+
+    eprintln!("Installing foo and adding build scripts...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    pacquet.with_arg("install").assert().success();
+    let virtual_store_dir = workspace.join("node_modules/.pnpm");
+    add_postinstall_script(&virtual_store_dir.join("foo@1.0.0/node_modules/foo"), "foo");
+    add_postinstall_script(&virtual_store_dir.join("zed@1.0.0/node_modules/zed"), "zed");
+
+    eprintln!("Executing pacquet rebuild...");
+    assert!(pacquet_rebuild(&workspace, &[]).status.success());
+    let log = read_build_log(&workspace);
+    assert_eq!(names(&log), ["zed", "foo"]);
+
+    eprintln!("Executing pacquet rebuild foo...");
+    assert!(pacquet_rebuild(&workspace, &["foo"]).status.success());
+    let new_log = read_build_log(&workspace);
+    assert_eq!(names(&new_log), ["zed", "foo", "foo"]);
+    assert!(new_log[2].1 > log[1].1, "the timestamp should be updated");
+
+    eprintln!("Executing pacquet rebuild with zed in neverBuiltDependencies...");
+    let package_json_content = serde_json::json!({
+        "dependencies": { "foo": "^1.0.0" },
+        "pnpm": { "neverBuiltDependencies": ["zed"] },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    assert!(pacquet_rebuild(&workspace, &[]).status.success());
+    assert_eq!(names(&read_build_log(&workspace)), ["zed", "foo", "foo", "foo"]);
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn child_concurrency_should_bound_parallel_builds() {
+    let names = ["a", "b", "c"];
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&names.map(|name| FakePackage::new(name, "1.0.0")));
+
+    eprintln!("Installing packages and adding build scripts...");
+    let package_json_content = serde_json::json!({
+        "dependencies": { "a": "^1.0.0", "b": "^1.0.0", "c": "^1.0.0" },
+    });
+    fs::write(workspace.join("package.json"), package_json_content.to_string())
+        .expect("write to package.json");
+    pacquet.with_arg("install").assert().success();
+    let script =
+        r#"echo start >> "$INIT_CWD/build.log"; sleep 0.5; echo end >> "$INIT_CWD/build.log""#;
+    for name in names {
+        let package_dir =
+            workspace.join(format!("node_modules/.pnpm/{name}@1.0.0/node_modules/{name}"));
+        set_postinstall_script(&package_dir, name, script);
+    }
+    let read_build_log = || {
+        let log = fs::read_to_string(workspace.join("build.log")).expect("read build.log");
+        fs::remove_file(workspace.join("build.log")).expect("remove build.log");
+        log.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+
+    eprintln!("Executing pacquet rebuild --child-concurrency=1...");
+    assert!(pacquet_rebuild(&workspace, &["--child-concurrency=1"]).status.success());
+    assert_eq!(read_build_log(), ["start", "end", "start", "end", "start", "end"]);
+
+    eprintln!("Executing pacquet rebuild --child-concurrency=3...");
+    assert!(pacquet_rebuild(&workspace, &["--child-concurrency=3"]).status.success());
+    assert_eq!(read_build_log(), ["start", "start", "start", "end", "end", "end"]);
+
+    drop((root, npmrc_info)); // cleanup
+}
+
+#[test]
+fn should_fail_to_rebuild_a_package_that_is_not_installed() {
+    let CommandTempCwd { root, workspace, .. } = CommandTempCwd::init();
+
+    fs::write(workspace.join("package.json"), "{}").expect("write to package.json");
+    let output = pacquet_rebuild(&workspace, &["foo"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pacquet_package_manager::package_not_installed"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn ndjson_reporter_should_stream_build_script_output_and_exit_code() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+
+    eprintln!("Installing foo and adding a failing build script...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+    pacquet.with_arg("install").assert().success();
+    let package_dir = workspace.join("node_modules/.pnpm/foo@1.0.0/node_modules/foo");
+    set_postinstall_script(&package_dir, "foo", "echo building; echo oops >&2; exit 3");
+
+    eprintln!("Executing pacquet rebuild --reporter=ndjson...");
+    let output = pacquet_rebuild(&workspace, &["--reporter=ndjson"]);
+    assert!(!output.status.success());
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .expect("stdout is valid UTF-8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("parse event"))
+        .collect();
+    // stdout and stderr are read separately, so their lines may be interleaved either way
+    let mut outputs: Vec<_> = events
+        .iter()
+        .filter(|value| value["event"] == "script-output")
+        .map(|value| (value["stream"].to_string(), value["chunk"].to_string()))
+        .collect();
+    outputs.sort();
+    assert_eq!(
+        outputs,
+        [
+            (r#""stderr""#.to_string(), r#""oops""#.to_string()),
+            (r#""stdout""#.to_string(), r#""building""#.to_string()),
+        ],
+    );
+    let script = events.iter().find(|value| value["event"] == "script").expect("script event");
+    assert_eq!(script["package"], "foo@1.0.0");
+    assert_eq!(script["name"], "postinstall");
+    assert_eq!(script["exitCode"], 3);
+    assert!(script["durationMs"].is_u64());
+
+    drop((root, npmrc_info)); // cleanup
+}
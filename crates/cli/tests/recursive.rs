@@ -0,0 +1,123 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+    fs::is_symlink_or_junction,
+};
+use std::{fs, io::Write, path::Path};
+
+fn create_project(dir: &Path, manifest: &str) {
+    fs::create_dir_all(dir).expect("create project directory");
+    fs::write(dir.join("package.json"), manifest).expect("write to package.json");
+}
+
+#[test]
+fn recursive_install_should_install_every_workspace_project() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[FakePackage::new("foo", "1.0.0"), FakePackage::new("bar", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    create_project(&workspace, r#"{ "name": "root", "private": true }"#);
+    create_project(&workspace.join("packages/a"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#);
+    create_project(&workspace.join("packages/b"), r#"{ "dependencies": { "bar": "^1.0.0" } }"#);
+
+    eprintln!("Executing pacquet -r install...");
+    pacquet.with_args(["-r", "install"]).assert().success();
+
+    eprintln!("Make sure every project has its dependencies installed");
+    assert!(is_symlink_or_junction(&workspace.join("packages/a/node_modules/foo")).unwrap());
+    assert!(is_symlink_or_junction(&workspace.join("packages/b/node_modules/bar")).unwrap());
+    assert!(!workspace.join("packages/a/node_modules/bar").exists());
+    assert!(!workspace.join("packages/b/node_modules/foo").exists());
+
+    eprintln!("Make sure the store is shared");
+    // Every fake tarball is the same file, the second project finds it in the store.
+    registry.mock("/foo/-/foo-1.0.0.tgz").assert();
+    assert!(!registry.mock("/bar/-/bar-1.0.0.tgz").matched());
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn recursive_install_should_report_every_failure_at_the_end() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    create_project(&workspace.join("packages/a"), r#"{ "dependencies": { "missing": "1.0.0" } }"#);
+    create_project(&workspace.join("packages/b"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#);
+
+    eprintln!("CASE: failures are collected");
+    let output = pacquet.with_args(["-r", "install"]).output().expect("run pacquet -r install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to install 1 of 2 projects"));
+    assert!(is_symlink_or_junction(&workspace.join("packages/b/node_modules/foo")).unwrap());
+
+    eprintln!("CASE: --bail stops at the first failure");
+    fs::remove_dir_all(workspace.join("packages/b/node_modules")).expect("remove node_modules");
+    let output = pacquet_command(&workspace)
+        .with_args(["-r", "install", "--bail"])
+        .output()
+        .expect("run pacquet -r install --bail");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(!workspace.join("packages/b/node_modules").exists());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn recursive_should_fail_without_workspace_manifest() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+    create_project(&workspace, "{}");
+
+    let output = pacquet.with_args(["-r", "install"]).output().expect("run pacquet -r install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No pnpm-workspace.yaml found"));
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn recursive_install_should_refuse_lockfile() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Creating the workspace with the lockfile enabled...");
+    fs::OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .and_then(|mut npmrc| writeln!(npmrc, "lockfile=true"))
+        .expect("append to .npmrc");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    create_project(&workspace.join("packages/a"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#);
+    create_project(&workspace.join("packages/b"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#);
+
+    eprintln!("Executing pacquet -r install...");
+    let output = pacquet.with_args(["-r", "install"]).output().expect("run pacquet -r install");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Installing a workspace with a lockfile isn't supported yet"));
+
+    eprintln!("Make sure no project is installed and no lockfile is written");
+    for project in ["packages/a", "packages/b"] {
+        assert!(!workspace.join(project).join("node_modules").exists());
+        assert!(!workspace.join(project).join("pnpm-lock.yaml").exists());
+    }
+    assert!(!registry.mock("/foo").matched());
+
+    drop(root); // cleanup
+}
@@ -0,0 +1,235 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+use std::fs;
+
+#[test]
+fn ndjson_reporter_should_emit_install_events() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]).own_tarball(),
+            FakePackage::new("bar", "1.0.0").own_tarball(),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet --reporter=ndjson install...");
+    let output =
+        pacquet.with_args(["--reporter=ndjson", "install"]).output().expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Parsing the event stream...");
+    let lines: Vec<Value> = String::from_utf8(output.stdout)
+        .expect("stdout is valid UTF-8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is a JSON object"))
+        .collect();
+    dbg!(&lines);
+    assert!(lines.iter().all(|line| line["timestamp"].is_u64()));
+
+    let mut first_occurrences: Vec<&str> = Vec::new();
+    for line in &lines {
+        let event = line["event"].as_str().expect("event is a string");
+        if !first_occurrences.contains(&event) {
+            first_occurrences.push(event);
+        }
+    }
+    assert_eq!(
+        first_occurrences,
+        [
+            "resolution-started",
+            "package-resolved",
+            "tarball-download-started",
+            "tarball-downloaded",
+            "package-linked",
+            "install-done",
+        ],
+    );
+
+    let linked: Vec<&str> = lines
+        .iter()
+        .filter(|line| line["event"] == "package-linked")
+        .map(|line| line["name"].as_str().unwrap())
+        .collect();
+    assert!(linked.contains(&"foo"));
+    assert!(linked.contains(&"bar"));
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn append_only_reporter_should_write_plain_lines() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } = CommandTempCwd::init()
+        .add_fake_registry(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]).own_tarball(),
+            FakePackage::new("bar", "1.0.0").own_tarball(),
+        ]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet --reporter=append-only install...");
+    let output = pacquet
+        .with_args(["--reporter=append-only", "install"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert!(output.status.success());
+
+    eprintln!("Make sure the output has no carriage return and ends with the done line");
+    let stderr = String::from_utf8(output.stderr).expect("stderr is valid UTF-8");
+    assert!(!stderr.contains('\r'));
+    assert!(stderr.contains("Resolved foo@1.0.0 ("));
+    assert!(stderr.contains("Downloaded bar@1.0.0 ("));
+    assert!(stderr.contains("Linked foo@1.0.0 ("));
+    let last_lines: Vec<_> = stderr.lines().rev().take(2).collect();
+    assert_eq!(
+        last_lines,
+        [
+            " INFO Packages: +2 -0, 0 reused from the store, 0 warnings",
+            "Done: resolved 2, downloaded 2, linked 2",
+        ]
+    );
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn json_errors_should_describe_the_failure_even_when_silent() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json with a dependency that doesn't exist...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "missing": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Executing pacquet install --reporter=silent --json-errors...");
+    let output = pacquet
+        .with_args(["install", "--reporter=silent", "--json-errors"])
+        .output()
+        .expect("run pacquet install");
+    dbg!(&output);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    eprintln!("Make sure stderr is a single JSON object with the code of the error");
+    let stderr = String::from_utf8(output.stderr).expect("stderr is valid UTF-8");
+    assert_eq!(stderr.lines().count(), 1);
+    let error: Value = serde_json::from_str(&stderr).expect("parse the error");
+    dbg!(&error);
+    assert_eq!(error["code"], "pacquet_registry::not_found");
+    assert!(error["message"].is_string());
+    assert!(error["context"].to_string().contains("missing"));
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn junit_reporter_should_write_a_test_case_per_project() {
+    let CommandTempCwd { pacquet, root, workspace, .. } = CommandTempCwd::init();
+
+    eprintln!("Creating the workspace...");
+    fs::write(workspace.join("pnpm-workspace.yaml"), "packages:\n  - packages/*\n")
+        .expect("write to pnpm-workspace.yaml");
+    fs::write(workspace.join("package.json"), r#"{ "scripts": { "test": "exit 3" } }"#)
+        .expect("write to package.json");
+    for (name, script) in [("a", "exit 0"), ("b", "exit 1"), ("c", "")] {
+        let dir = workspace.join("packages").join(name);
+        fs::create_dir_all(&dir).expect("create project directory");
+        let manifest = if script.is_empty() {
+            format!(r#"{{ "name": "{name}", "version": "1.0.0" }}"#)
+        } else {
+            format!(
+                r#"{{ "name": "{name}", "version": "1.0.0", "scripts": {{ "test": "{script}" }} }}"#
+            )
+        };
+        fs::write(dir.join("package.json"), manifest).expect("write to package.json");
+    }
+
+    eprintln!("Executing pacquet -r run test --reporter=junit --out results.xml...");
+    let output = pacquet
+        .with_args(["-r", "run", "test", "--reporter=junit", "--out", "results.xml"])
+        .output()
+        .expect("run pacquet -r run test");
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("The test script failed in 1 of 2 projects"));
+
+    eprintln!("Make sure every project with the script is a test case");
+    let xml = fs::read_to_string(workspace.join("results.xml")).expect("read results.xml");
+    eprintln!("XML:\n{xml}");
+    let test_cases: Vec<&str> =
+        xml.lines().map(str::trim).filter(|line| line.starts_with("<testcase ")).collect();
+    assert_eq!(test_cases.len(), 2);
+    assert!(test_cases[0].starts_with(r#"<testcase name="a@1.0.0" classname="test""#));
+    assert!(test_cases[0].ends_with("/>"));
+    assert!(test_cases[1].starts_with(r#"<testcase name="b@1.0.0" classname="test""#));
+    assert!(xml.contains(r#"<failure message="Exited with code 1"/>"#));
+    assert!(xml.contains(r#"<testsuites name="pacquet" tests="2" failures="1""#));
+
+    drop(root); // cleanup
+}
+
+#[cfg(unix)]
+#[test]
+fn default_reporter_should_keep_logs_off_the_status_line() {
+    use rustix::pty::{grantpt, openpt, ptsname, unlockpt, OpenptFlags};
+    use std::{fs::File, io::Read, process::Stdio};
+
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { registry, .. } = npmrc_info;
+
+    eprintln!("Creating package.json...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .expect("write to package.json");
+
+    eprintln!("Opening a terminal for stderr...");
+    let master = openpt(OpenptFlags::RDWR | OpenptFlags::NOCTTY).expect("open a pseudo terminal");
+    grantpt(&master).expect("grant the pseudo terminal");
+    unlockpt(&master).expect("unlock the pseudo terminal");
+    let slave_path = ptsname(&master, Vec::new()).expect("name the pseudo terminal");
+    let slave = File::options()
+        .read(true)
+        .write(true)
+        .open(slave_path.to_str().expect("path is valid UTF-8"))
+        .expect("open the pseudo terminal");
+
+    eprintln!("Executing pacquet install with the logs of the info level...");
+    let mut pacquet = pacquet.with_args(["install", "--loglevel=info"]);
+    pacquet.stdout(Stdio::null()).stderr(slave);
+    let mut child = pacquet.spawn().expect("spawn pacquet install");
+    drop(pacquet); // close the terminal on this side, so that reading ends with the process
+    let mut stderr = Vec::new();
+    let mut master = File::from(master);
+    let mut buffer = [0; 4096];
+    // once the process is done, the read fails rather than returning 0 on some platforms
+    while let Ok(len @ 1..) = master.read(&mut buffer) {
+        stderr.extend_from_slice(&buffer[..len]);
+    }
+    let status = child.wait().expect("wait for pacquet install");
+    let stderr = String::from_utf8_lossy(&stderr);
+    dbg!(&stderr);
+    assert!(status.success());
+    assert!(stderr.contains("Downloaded "), "the status line should be shown");
+    assert!(stderr.contains("INFO"), "the logs should be shown");
+    let glued = stderr
+        .split('\n')
+        .filter_map(|line| line.rsplit("\r\x1b[K").next())
+        .find(|segment| segment.contains("Downloaded ") && segment.contains("INFO"));
+    assert_eq!(glued, None, "a log was written on the status line");
+
+    drop((root, registry)); // cleanup
+}
@@ -0,0 +1,54 @@
+use command_extra::CommandExtra;
+use pacquet_testing_utils::bin::{pacquet_command, CommandTempCwd};
+use pretty_assertions::assert_eq;
+use std::{fs, path::Path};
+
+fn pacquet_root(dir: &Path, args: &[&str]) -> String {
+    let output =
+        pacquet_command(dir).with_args(args).with_arg("root").output().expect("run pacquet root");
+    dbg!(&output);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).expect("stdout is valid UTF-8").trim_end().to_string()
+}
+
+#[test]
+fn should_print_the_modules_dir() {
+    let CommandTempCwd { root, workspace, .. } = CommandTempCwd::init();
+    let workspace = workspace.canonicalize().unwrap();
+    fs::create_dir(workspace.join("project")).expect("create project directory");
+
+    eprintln!("CASE: current directory");
+    let modules_dir = workspace.join("node_modules");
+    assert_eq!(pacquet_root(&workspace, &[]), modules_dir.display().to_string());
+
+    eprintln!("CASE: --dir");
+    let modules_dir = workspace.join("project/node_modules");
+    assert_eq!(pacquet_root(&workspace, &["--dir", "project"]), modules_dir.display().to_string());
+
+    eprintln!("CASE: --modules-dir");
+    let modules_dir = workspace.join("deps");
+    assert_eq!(
+        pacquet_root(&workspace, &["--modules-dir", "deps"]),
+        modules_dir.display().to_string(),
+    );
+
+    drop(root); // cleanup
+}
+
+#[test]
+fn should_print_the_global_modules_dir() {
+    let CommandTempCwd { root, workspace, .. } = CommandTempCwd::init();
+    let global_dir = root.path().join("global");
+    fs::write(workspace.join(".npmrc"), format!("global-dir={}\n", global_dir.display()))
+        .expect("write to .npmrc");
+
+    let output = pacquet_command(&workspace)
+        .with_args(["root", "--global"])
+        .output()
+        .expect("run pacquet root --global");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.trim_end(), global_dir.join("node_modules").display().to_string());
+
+    drop(root); // cleanup
+}
@@ -0,0 +1,111 @@
+use assert_cmd::prelude::*;
+use command_extra::CommandExtra;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, AddFakeRegistry, CommandTempCwd},
+    fake_registry::{FakePackage, FakeRegistry},
+    fs::is_symlink_or_junction,
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+use text_block_macros::text_block_fnl;
+
+const SCOPED_LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  '@private/foo':"
+    "    specifier: 1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /@private/foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+};
+
+const UNSCOPED_LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  bar:"
+    "    specifier: 1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /bar@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+};
+
+/// Add `@private:registry` and `extra` to the `.npmrc` file.
+fn add_scoped_registry(npmrc_path: &Path, scoped_registry: &FakeRegistry, extra: &str) {
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(format!("@private:registry={}\n{extra}", scoped_registry.url()).as_bytes())
+        .expect("append to .npmrc");
+}
+
+/// Run `pacquet install --frozen-lockfile` with `manifest` and `lockfile` from a clean state.
+fn frozen_install(workspace: &Path, store_dir: &Path, manifest: &str, lockfile: &str) {
+    if store_dir.exists() {
+        fs::remove_dir_all(store_dir).expect("remove the store");
+    }
+    if workspace.join("node_modules").exists() {
+        fs::remove_dir_all(workspace.join("node_modules")).expect("remove node_modules");
+    }
+    fs::write(workspace.join("package.json"), manifest).expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), lockfile).expect("write to pnpm-lock.yaml");
+    pacquet_command(workspace).with_args(["install", "--frozen-lockfile"]).assert().success();
+}
+
+#[test]
+fn frozen_install_should_fetch_tarballs_from_scoped_registry() {
+    let CommandTempCwd { root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("bar", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+    let scoped_registry = FakeRegistry::start(&[FakePackage::new("@private/foo", "1.0.0")]);
+    add_scoped_registry(&npmrc_path, &scoped_registry, "lockfile=true\n");
+
+    eprintln!("CASE: scoped package");
+    let manifest = r#"{ "dependencies": { "@private/foo": "1.0.0" } }"#;
+    frozen_install(&workspace, &store_dir, manifest, SCOPED_LOCKFILE);
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/@private/foo")).unwrap());
+    scoped_registry.mock("/@private/foo/-/foo-1.0.0.tgz").assert();
+
+    eprintln!("CASE: unscoped package");
+    let manifest = r#"{ "dependencies": { "bar": "1.0.0" } }"#;
+    frozen_install(&workspace, &store_dir, manifest, UNSCOPED_LOCKFILE);
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/bar")).unwrap());
+    registry.mock("/bar/-/bar-1.0.0.tgz").assert();
+
+    drop((root, scoped_registry)); // cleanup
+}
+
+#[test]
+fn install_should_fetch_metadata_from_scoped_registry() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("bar", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+    let scoped_registry = FakeRegistry::start(&[FakePackage::new("@private/foo", "1.0.0")]);
+    add_scoped_registry(&npmrc_path, &scoped_registry, "");
+
+    eprintln!("Creating package.json...");
+    let manifest = r#"{ "dependencies": { "@private/foo": "^1.0.0", "bar": "^1.0.0" } }"#;
+    fs::write(workspace.join("package.json"), manifest).expect("write to package.json");
+
+    eprintln!("Executing pacquet install...");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure each package is resolved from its registry");
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/@private/foo")).unwrap());
+    assert!(is_symlink_or_junction(&workspace.join("node_modules/bar")).unwrap());
+    scoped_registry.mock("/@private/foo").assert();
+    registry.mock("/bar").assert();
+
+    drop((root, scoped_registry)); // cleanup
+}
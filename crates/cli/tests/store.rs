@@ -1,11 +1,32 @@
+use assert_cmd::prelude::*;
 use command_extra::CommandExtra;
-use pacquet_testing_utils::bin::CommandTempCwd;
+use pacquet_store_dir::StoreDir;
+use pacquet_testing_utils::{
+    bin::{pacquet_command, AddFakeRegistry, CommandTempCwd},
+    fake_registry::FakePackage,
+};
 use pipe_trait::Pipe;
 use pretty_assertions::assert_eq;
 use std::{
-    fs,
+    fs::{self, OpenOptions},
+    io::Write,
     path::{Path, PathBuf},
 };
+use text_block_macros::text_block_fnl;
+
+const LOCKFILE: &str = text_block_fnl! {
+    "lockfileVersion: '6.0'"
+    ""
+    "dependencies:"
+    "  foo:"
+    "    specifier: 1.0.0"
+    "    version: 1.0.0"
+    ""
+    "packages:"
+    "  /foo@1.0.0:"
+    "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+    "    dev: false"
+};
 
 /// Handle the slight difference between OSes.
 ///
@@ -41,3 +62,284 @@ fn store_path_should_return_store_dir_from_npmrc() {
 
     drop(root); // cleanup
 }
+
+#[test]
+fn store_path_should_return_the_locations_of_a_package() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+
+    eprintln!("Installing from the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), LOCKFILE).expect("write to pnpm-lock.yaml");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    let store_path = |args: &[&str]| {
+        pacquet_command(&workspace)
+            .with_args(["store", "path"])
+            .with_args(args)
+            .output()
+            .expect("run pacquet store path")
+    };
+    let printed_path = |output: &std::process::Output| {
+        dbg!(output);
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).trim_end().pipe(PathBuf::from)
+    };
+
+    eprintln!("CASE: virtual store path");
+    let package_dir = store_path(&["--package", "foo@1.0.0"]).pipe_ref(printed_path);
+    assert_eq!(
+        canonicalize(&package_dir),
+        canonicalize(&workspace.join("node_modules/.pnpm/foo@1.0.0/node_modules/foo")),
+    );
+    assert!(package_dir.join("package.json").is_file());
+
+    eprintln!("CASE: index file path");
+    let index_file_path = store_path(&["--package", "foo@1.0.0", "--index"]).pipe_ref(printed_path);
+    let integrity = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==";
+    assert_eq!(
+        canonicalize(&index_file_path),
+        StoreDir::new(store_dir)
+            .index_file_path(&integrity.parse().expect("parse integrity"))
+            .pipe_as_ref(canonicalize),
+    );
+    assert!(index_file_path.is_file());
+
+    eprintln!("CASE: package that isn't installed");
+    let output = store_path(&["--package", "foo@2.0.0"]);
+    dbg!(&output);
+    assert!(!output.status.success());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn store_status_should_report_modified_files() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, registry, .. } = npmrc_info;
+
+    eprintln!("Installing from the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), LOCKFILE).expect("write to pnpm-lock.yaml");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    let store_status = || {
+        pacquet_command(&workspace)
+            .with_args(["store", "status"])
+            .output()
+            .expect("run pacquet store status")
+    };
+
+    eprintln!("CASE: nothing is modified");
+    let output = store_status();
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    eprintln!("CASE: a file is modified");
+    let package_dir = workspace.join("node_modules/.pnpm/foo@1.0.0/node_modules/foo");
+    fs::write(package_dir.join("package.json"), "{}").expect("modify package.json");
+    let output = store_status();
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let modified_files: Vec<_> = stdout.lines().map(|line| line.replace('\\', "/")).collect();
+    assert_eq!(modified_files.len(), 1);
+    assert!(
+        modified_files[0].ends_with("node_modules/.pnpm/foo@1.0.0/node_modules/foo/package.json")
+    );
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn store_verify_repair_should_restore_corrupt_files() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+
+    eprintln!("Installing from the lockfile...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"lockfile=true\n")
+        .expect("append to .npmrc");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    fs::write(workspace.join("pnpm-lock.yaml"), LOCKFILE).expect("write to pnpm-lock.yaml");
+    pacquet.with_args(["install", "--frozen-lockfile"]).assert().success();
+
+    let store_verify = |args: &[&str]| {
+        pacquet_command(&workspace)
+            .with_args(["store", "verify"])
+            .with_args(args)
+            .output()
+            .expect("run pacquet store verify")
+    };
+    // the printed paths go through `{workspace}/..`, and the removed files don't exist anymore
+    let normalize = |path: &Path| {
+        let parent = path.parent().expect("file in the store has a parent");
+        canonicalize(parent).join(path.file_name().expect("file in the store has a name"))
+    };
+    let stdout_lines = |output: &std::process::Output| -> Vec<String> {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| match line.split_once(": ") {
+                Some((status, path)) => {
+                    format!("{status}: {}", normalize(Path::new(path)).display())
+                }
+                None => normalize(Path::new(line)).display().to_string(),
+            })
+            .collect()
+    };
+
+    eprintln!("CASE: nothing is corrupt");
+    let output = store_verify(&[]);
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    eprintln!("Corrupting files...");
+    let store_dir = StoreDir::new(store_dir);
+    let integrity = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==";
+    let cas_paths = store_dir
+        .read_index_file(&integrity.parse().expect("parse integrity"))
+        .expect("read index file")
+        .cas_paths(&store_dir)
+        .expect("find every file in the store");
+    let corrupt_path = &cas_paths["package.json"];
+    let original = fs::read(corrupt_path).expect("read package.json from the store");
+    // replace the file rather than write through it, which would also change node_modules
+    fs::remove_file(corrupt_path).expect("remove package.json from the store");
+    fs::write(corrupt_path, "{}").expect("corrupt package.json");
+    let (orphan_path, _) = store_dir.write_cas_file(b"ORPHAN", false).expect("write orphan");
+    fs::write(&orphan_path, "TAMPERED").expect("corrupt orphan");
+
+    eprintln!("CASE: files are corrupt");
+    let output = store_verify(&[]);
+    dbg!(&output);
+    assert!(!output.status.success());
+    let corrupt_path = &normalize(corrupt_path);
+    let orphan_path = &normalize(&orphan_path);
+    let mut expected = vec![corrupt_path.display().to_string(), orphan_path.display().to_string()];
+    expected.sort();
+    assert_eq!(stdout_lines(&output), expected);
+
+    eprintln!("CASE: repair");
+    let output = store_verify(&["--repair"]);
+    dbg!(&output);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout_lines(&output),
+        [
+            format!("repaired: {}", corrupt_path.display()),
+            format!("removed: {}", orphan_path.display()),
+        ],
+    );
+    assert_eq!(fs::read(corrupt_path).expect("read restored package.json"), original);
+    assert!(!orphan_path.exists());
+    assert!(store_verify(&[]).status.success());
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn store_layout_should_be_written_into_new_stores() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+
+    eprintln!("Choosing the 2+2 layout...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"store-layout=2+2\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet install...");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    pacquet.with_arg("install").assert().success();
+
+    eprintln!("Make sure the files are two directories deep");
+    assert_eq!(
+        fs::read_to_string(store_dir.join("v3/store.json")).expect("read store.json"),
+        r#"{"layout":"2+2"}"#,
+    );
+    let store = StoreDir::new(&store_dir);
+    let integrity = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==";
+    let cas_paths = store
+        .read_index_file(&integrity.parse().expect("parse integrity"))
+        .expect("read index file")
+        .cas_paths(&store)
+        .expect("find every file in the store");
+    let cas_path = &cas_paths["package.json"];
+    assert!(cas_path.is_file());
+    let dirs = cas_path.parent().unwrap().strip_prefix(store_dir.join("v3/files")).unwrap();
+    assert_eq!(dirs.iter().count(), 2);
+
+    eprintln!("Make sure the store is read with the same layout");
+    pacquet_command(&workspace).with_args(["store", "verify"]).assert().success();
+
+    drop((root, registry)); // cleanup
+}
+
+#[test]
+fn store_layout_should_only_be_settled_by_commands_that_write_the_store() {
+    let CommandTempCwd { pacquet, root, workspace, npmrc_info, .. } =
+        CommandTempCwd::init().add_fake_registry(&[FakePackage::new("foo", "1.0.0")]);
+    let AddFakeRegistry { npmrc_path, store_dir, registry, .. } = npmrc_info;
+    let pacquet_with_args =
+        |args: &[&str]| pacquet_command(&workspace).with_args(args).output().expect("run pacquet");
+
+    eprintln!("Choosing the 2+2 layout...");
+    OpenOptions::new()
+        .append(true)
+        .open(npmrc_path)
+        .expect("open .npmrc to append")
+        .write_all(b"store-layout=2+2\n")
+        .expect("append to .npmrc");
+
+    eprintln!("Executing pacquet store path...");
+    let output = pacquet.with_args(["store", "path"]).output().expect("run pacquet store path");
+    dbg!(&output);
+    assert!(output.status.success());
+    assert!(!store_dir.join("v3").exists());
+
+    eprintln!("Corrupting store.json...");
+    fs::create_dir_all(store_dir.join("v3")).expect("create the store");
+    fs::write(store_dir.join("v3/store.json"), r#"{"layout":"3"}"#).expect("write store.json");
+
+    eprintln!("Make sure the commands that write the store refuse it");
+    fs::write(workspace.join("package.json"), r#"{ "dependencies": { "foo": "1.0.0" } }"#)
+        .expect("write to package.json");
+    let output = pacquet_with_args(&["install"]);
+    dbg!(&output);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to parse the layout of the store"));
+    assert!(!workspace.join("node_modules/foo").exists());
+
+    eprintln!("Make sure the other commands still work");
+    assert!(pacquet_with_args(&["store", "path"]).status.success());
+
+    drop((root, registry)); // cleanup
+}
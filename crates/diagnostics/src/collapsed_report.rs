@@ -0,0 +1,122 @@
+use miette::{Diagnostic, LabeledSpan, Report, Severity, SourceCode};
+use std::{error::Error, fmt};
+
+/// Wrapper of a [`Report`] whose chain of causes skips the messages that are already part of the
+/// message before them.
+///
+/// Wrapper errors that are transparent over their source repeat its message, so the same
+/// message would otherwise be printed once for every layer.
+#[derive(Debug)]
+pub struct CollapsedReport {
+    report: Report,
+    causes: Option<Box<Cause>>,
+}
+
+#[derive(Debug)]
+struct Cause {
+    message: String,
+    source: Option<Box<Cause>>,
+}
+
+impl From<Report> for CollapsedReport {
+    fn from(report: Report) -> Self {
+        let mut messages: Vec<String> = Vec::new();
+        let mut previous = report.to_string();
+        for cause in report.chain().skip(1) {
+            let message = cause.to_string();
+            if !previous.contains(&message) {
+                messages.push(message.clone());
+            }
+            previous = message;
+        }
+
+        let causes = messages
+            .into_iter()
+            .rev()
+            .fold(None, |source, message| Some(Box::new(Cause { message, source })));
+        CollapsedReport { report, causes }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Cause {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|cause| cause as &(dyn Error + 'static))
+    }
+}
+
+impl fmt::Display for CollapsedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.report, f)
+    }
+}
+
+impl Error for CollapsedReport {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.causes.as_deref().map(|cause| cause as &(dyn Error + 'static))
+    }
+}
+
+impl Diagnostic for CollapsedReport {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.report.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.report.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.report.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.report.related()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miette::{miette, WrapErr};
+
+    #[test]
+    fn should_skip_repeated_causes() {
+        let report: Report = Err::<(), _>(miette!("Integrity check failed"))
+            .wrap_err("Failed to verify foo: Integrity check failed")
+            .wrap_err("Failed to verify foo: Integrity check failed")
+            .wrap_err("Failed to verify bar")
+            .wrap_err("installing dependencies")
+            .unwrap_err();
+        let collapsed = CollapsedReport::from(report);
+
+        assert_eq!(collapsed.to_string(), "installing dependencies");
+        let mut causes = Vec::new();
+        let mut cause = collapsed.source();
+        while let Some(error) = cause {
+            causes.push(error.to_string());
+            cause = error.source();
+        }
+        assert_eq!(
+            causes,
+            ["Failed to verify bar", "Failed to verify foo: Integrity check failed"]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use miette::MietteHandlerOpts;
+use std::{
+    env,
+    ffi::OsString,
+    io::{stderr, IsTerminal},
+};
+
+/// Decide whether the diagnostics and logs written to stderr are colored.
+///
+/// `no_color` (`--no-color`) always disables colors. Otherwise a `FORCE_COLOR` other than `0` or
+/// `false` enables them, a non-empty `NO_COLOR` disables them, and they are only used when stderr
+/// is a terminal.
+pub fn use_color(no_color: bool) -> bool {
+    decide_color(
+        no_color,
+        env::var_os("NO_COLOR"),
+        env::var_os("FORCE_COLOR"),
+        stderr().is_terminal(),
+    )
+}
+
+fn decide_color(
+    no_color: bool,
+    no_color_env: Option<OsString>,
+    force_color_env: Option<OsString>,
+    is_terminal: bool,
+) -> bool {
+    if no_color {
+        return false;
+    }
+    if let Some(force_color) = force_color_env {
+        return force_color != "0" && force_color != "false";
+    }
+    if no_color_env.is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    is_terminal
+}
+
+/// Render the diagnostics of miette with or without colors.
+pub fn set_report_hook(color: bool) {
+    let _ = miette::set_hook(Box::new(move |_| {
+        let opts = MietteHandlerOpts::new().color(color);
+        // hyperlinks are escape sequences too
+        let opts = if color { opts } else { opts.terminal_links(false) };
+        Box::new(opts.build())
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decide_color() {
+        let case = |no_color: bool,
+                    no_color_env: Option<&str>,
+                    force_color_env: Option<&str>,
+                    is_terminal: bool,
+                    expected: bool| {
+            eprintln!("CASE: {no_color:?} {no_color_env:?} {force_color_env:?} {is_terminal:?}");
+            let received = super::decide_color(
+                no_color,
+                no_color_env.map(OsString::from),
+                force_color_env.map(OsString::from),
+                is_terminal,
+            );
+            assert_eq!(received, expected);
+        };
+
+        case(false, None, None, true, true);
+        case(false, None, None, false, false);
+        case(true, None, None, true, false);
+        case(true, None, Some("1"), true, false);
+        case(false, Some("1"), None, true, false);
+        case(false, Some(""), None, true, true);
+        case(false, None, Some("1"), false, true);
+        case(false, Some("1"), Some("1"), false, true);
+        case(false, None, Some("0"), true, false);
+        case(false, None, Some("false"), true, false);
+    }
+}
@@ -1,6 +1,14 @@
+mod collapsed_report;
+mod color;
 mod local_tracing;
+mod package_timing;
+mod panic_hook;
 
 pub use miette;
 pub use tracing;
 
-pub use local_tracing::enable_tracing_by_env;
+pub use collapsed_report::CollapsedReport;
+pub use color::{set_report_hook, use_color};
+pub use local_tracing::{enable_tracing_by_env, enable_tracing_by_level};
+pub use package_timing::{PackageTiming, PackageTimings, TimingLayer, TIMING_TARGET};
+pub use panic_hook::{current_package, set_panic_hook, PanicContextLayer};
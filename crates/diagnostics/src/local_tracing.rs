@@ -1,7 +1,11 @@
+use crate::{PackageTimings, PanicContextLayer, TIMING_TARGET};
 use std::str::FromStr;
 
-use tracing::Level;
-use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter, Layer};
+use tracing::{level_filters::LevelFilter, Level};
+use tracing_subscriber::{
+    fmt::{format::FmtSpan, MakeWriter},
+    EnvFilter, Layer,
+};
 
 pub fn enable_tracing_by_env() {
     let Ok(trace_var) = std::env::var("TRACE") else { return };
@@ -11,12 +15,57 @@ pub fn enable_tracing_by_env() {
 
     tracing_subscriber::registry()
         .with(layer)
+        .with(panic_context_layer())
         .with(fmt::layer().pretty().with_file(true).with_span_events(FmtSpan::CLOSE))
         .init();
 
     tracing::trace!("enable_tracing_by_env");
 }
 
+/// Print human readable messages up to `max_level` to `writer`, colored if `ansi` is true.
+///
+/// From the debug level, the durations of the installed packages are also collected into the
+/// returned [`PackageTimings`].
+pub fn enable_tracing_by_level<Writer>(
+    max_level: LevelFilter,
+    ansi: bool,
+    writer: Writer,
+) -> Option<PackageTimings>
+where
+    Writer: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    use tracing_subscriber::{filter::Targets, fmt, prelude::*};
+
+    let timings = (max_level >= LevelFilter::DEBUG).then(PackageTimings::new);
+    let timing_layer = timings.as_ref().map(|timings| {
+        timings.layer().with_filter(Targets::new().with_target(TIMING_TARGET, Level::DEBUG))
+    });
+
+    tracing_subscriber::registry()
+        .with(timing_layer)
+        .with(panic_context_layer())
+        .with(
+            fmt::layer()
+                .with_writer(writer)
+                .with_ansi(ansi)
+                .without_time()
+                .with_target(false)
+                .with_filter(max_level),
+        )
+        .init();
+
+    timings
+}
+
+/// [`PanicContextLayer`] with the `package` spans enabled whatever the log level is.
+fn panic_context_layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    use tracing_subscriber::filter::Targets;
+    PanicContextLayer.with_filter(Targets::new().with_target(TIMING_TARGET, Level::DEBUG))
+}
+
 fn common_layer(trace_var: &str) -> Box<dyn Layer<tracing_subscriber::Registry> + Send + Sync> {
     if let Ok(default_level) = Level::from_str(trace_var) {
         tracing_subscriber::filter::Targets::new()
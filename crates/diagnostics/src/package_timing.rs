@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Target of the spans that [`PackageTimings`] measures.
+///
+/// A span named `package` covers the installation of one package, whose `name@version` is its
+/// `package` field. Spans under it (`resolve`, `download`, `extract`, `link`, ...) are the phases
+/// of that installation.
+pub const TIMING_TARGET: &str = "pacquet::timing";
+
+/// How many packages [`PackageTimings::report`] lists.
+const SLOWEST_PACKAGES: usize = 10;
+
+/// Durations of the installation of a package, and of its phases.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageTiming {
+    pub total: Duration,
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl PackageTiming {
+    fn add(&mut self, other: PackageTiming) {
+        self.total += other.total;
+        for (phase, duration) in other.phases {
+            self.add_phase(phase, duration);
+        }
+    }
+
+    fn add_phase(&mut self, phase: &'static str, duration: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+}
+
+impl fmt::Display for PackageTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1?}", self.total)?;
+        for (index, (phase, duration)) in self.phases.iter().enumerate() {
+            let separator = if index == 0 { " (" } else { ", " };
+            write!(f, "{separator}{phase} {duration:.1?}")?;
+        }
+        if !self.phases.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Durations of the packages installed so far, collected from the spans of [`TIMING_TARGET`]
+/// by [`layer`](Self::layer).
+#[derive(Debug, Clone, Default)]
+pub struct PackageTimings(Arc<Mutex<HashMap<String, PackageTiming>>>);
+
+impl PackageTimings {
+    /// Create an empty collection.
+    pub fn new() -> Self {
+        PackageTimings::default()
+    }
+
+    /// Tracing layer that adds the durations of the spans to this collection.
+    pub fn layer(&self) -> TimingLayer {
+        TimingLayer { timings: self.clone() }
+    }
+
+    /// The packages from the slowest to the fastest.
+    pub fn slowest(&self) -> Vec<(String, PackageTiming)> {
+        let mut timings: Vec<_> = self
+            .0
+            .lock()
+            .expect("lock timings")
+            .iter()
+            .map(|(package, timing)| (package.clone(), timing.clone()))
+            .collect();
+        timings.sort_by(|(a_package, a), (b_package, b)| {
+            b.total.cmp(&a.total).then_with(|| a_package.cmp(b_package))
+        });
+        timings
+    }
+
+    /// Emit the slowest packages at debug level.
+    pub fn report(&self) {
+        let slowest = self.slowest();
+        if slowest.is_empty() {
+            return;
+        }
+        tracing::debug!(target: TIMING_TARGET, "Slowest packages:");
+        for (package, timing) in slowest.into_iter().take(SLOWEST_PACKAGES) {
+            tracing::debug!(target: TIMING_TARGET, "  {package} took {timing}");
+        }
+    }
+
+    fn add(&self, package: String, timing: PackageTiming) {
+        self.0.lock().expect("lock timings").entry(package).or_default().add(timing);
+    }
+}
+
+/// Layer created by [`PackageTimings::layer`].
+#[derive(Debug)]
+pub struct TimingLayer {
+    timings: PackageTimings,
+}
+
+/// Extension of the spans of [`TIMING_TARGET`].
+struct SpanTiming {
+    start: Instant,
+    package: Option<String>,
+    phases: PackageTiming,
+}
+
+/// Reads the `package` field of a span.
+pub(crate) struct PackageVisitor<'a>(pub(crate) &'a mut Option<String>);
+
+impl<'a> Visit for PackageVisitor<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "package" {
+            *self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "package" {
+            *self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+pub(crate) fn is_package_span(metadata: &tracing::Metadata<'_>) -> bool {
+    metadata.target() == TIMING_TARGET && metadata.name() == "package"
+}
+
+impl<S> Layer<S> for TimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().target() != TIMING_TARGET {
+            return;
+        }
+        let Some(span) = ctx.span(id) else { return };
+        let mut package = None;
+        attrs.record(&mut PackageVisitor(&mut package));
+        let timing =
+            SpanTiming { start: Instant::now(), package, phases: PackageTiming::default() };
+        span.extensions_mut().insert(timing);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            values.record(&mut PackageVisitor(&mut timing.package));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else { return };
+        let elapsed = timing.start.elapsed();
+
+        if is_package_span(span.metadata()) {
+            if let Some(package) = timing.package {
+                self.timings.add(package, PackageTiming { total: elapsed, ..timing.phases });
+            }
+            return;
+        }
+
+        let Some(package_span) = span.scope().skip(1).find(|span| is_package_span(span.metadata()))
+        else {
+            return;
+        };
+        let mut extensions = package_span.extensions_mut();
+        if let Some(package_timing) = extensions.get_mut::<SpanTiming>() {
+            package_timing.phases.add_phase(span.name(), elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::thread::sleep;
+    use tracing::field::Empty;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn should_measure_package_spans_and_their_phases() {
+        let timings = PackageTimings::new();
+        let subscriber = tracing_subscriber::registry().with(timings.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for (package, delay) in [("fast@1.0.0", 1), ("slow@1.0.0", 20)] {
+                let span =
+                    tracing::debug_span!(target: "pacquet::timing", "package", package = Empty);
+                let _enter = span.enter();
+                tracing::debug_span!(target: "pacquet::timing", "resolve")
+                    .in_scope(|| sleep(Duration::from_millis(1)));
+                span.record("package", package);
+                tracing::debug_span!(target: "pacquet::timing", "link")
+                    .in_scope(|| sleep(Duration::from_millis(delay)));
+            }
+            // not measured
+            let _unnamed = tracing::debug_span!(target: "pacquet::timing", "package").entered();
+            let _other = tracing::debug_span!("package", package = "other@1.0.0").entered();
+        });
+
+        let slowest = timings.slowest();
+        let packages: Vec<_> = slowest.iter().map(|(package, _)| package.as_str()).collect();
+        assert_eq!(packages, ["slow@1.0.0", "fast@1.0.0"]);
+        for (_, timing) in &slowest {
+            let phases: Vec<_> = timing.phases.iter().map(|(phase, _)| *phase).collect();
+            assert_eq!(phases, ["resolve", "link"]);
+            assert!(timing
+                .phases
+                .iter()
+                .all(|(_, duration)| *duration >= Duration::from_millis(1)));
+            assert!(timing.total >= timing.phases.iter().map(|(_, duration)| *duration).sum());
+        }
+    }
+
+    #[test]
+    fn display_should_list_phases() {
+        let timing = PackageTiming {
+            total: Duration::from_millis(3),
+            phases: vec![
+                ("download", Duration::from_millis(2)),
+                ("link", Duration::from_millis(1)),
+            ],
+        };
+        assert_eq!(timing.to_string(), "3.0ms (download 2.0ms, link 1.0ms)");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::package_timing::{is_package_span, PackageVisitor};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    panic::{self, PanicInfo},
+};
+use tracing::{
+    span::{Attributes, Id, Record},
+    Span, Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, Registry},
+    Layer,
+};
+
+/// Package of a `package` span, see [`TIMING_TARGET`](crate::TIMING_TARGET).
+struct SpanPackage(Option<String>);
+
+/// Layer that remembers the package of each `package` span of
+/// [`TIMING_TARGET`](crate::TIMING_TARGET), so that the hook of [`set_panic_hook`] can tell which
+/// package was being installed.
+#[derive(Debug, Default)]
+pub struct PanicContextLayer;
+
+impl<S> Layer<S> for PanicContextLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !is_package_span(attrs.metadata()) {
+            return;
+        }
+        let Some(span) = ctx.span(id) else { return };
+        let mut package = None;
+        attrs.record(&mut PackageVisitor(&mut package));
+        span.extensions_mut().insert(SpanPackage(package));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanPackage(package)) = extensions.get_mut::<SpanPackage>() {
+            values.record(&mut PackageVisitor(package));
+        }
+    }
+}
+
+/// The package that the current thread is installing, according to the `package` spans recorded
+/// by [`PanicContextLayer`].
+pub fn current_package() -> Option<String> {
+    Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let span = dispatch.downcast_ref::<Registry>()?.span(id)?;
+            let package =
+                span.scope().find_map(|span| span.extensions().get::<SpanPackage>()?.0.clone());
+            package
+        })
+        .flatten()
+}
+
+/// Report a panic on stderr whatever the reporter is.
+///
+/// The report names the package that was being installed, if any, and asks to file a bug. The
+/// hook doesn't end the process: the caller decides on the exit code once the panic reaches it.
+pub fn set_panic_hook() {
+    panic::set_hook(Box::new(move |info| {
+        eprintln!("{}", panic_message(info, current_package().as_deref()));
+        let backtrace = Backtrace::capture();
+        if backtrace.status() == BacktraceStatus::Captured {
+            eprintln!("{backtrace}");
+        }
+    }));
+}
+
+fn panic_message(info: &PanicInfo<'_>, package: Option<&str>) -> String {
+    let payload = info.payload();
+    let reason = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown reason");
+    let context = package.map(|package| format!(" while installing {package}")).unwrap_or_default();
+    let location = info.location().map(|location| format!(" at {location}")).unwrap_or_default();
+    format!(
+        "pacquet panicked{context}: {reason}{location}\n\
+         This is a bug in pacquet, please report it with the command that you ran at {}/issues",
+        env!("CARGO_PKG_REPOSITORY"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{env, process::Command};
+    use tracing::field::Empty;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn current_package_should_follow_package_spans() {
+        let subscriber = tracing_subscriber::registry().with(PanicContextLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(current_package(), None);
+            let span = tracing::debug_span!(target: "pacquet::timing", "package", package = Empty);
+            let _enter = span.enter();
+            assert_eq!(current_package(), None);
+            span.record("package", "foo@1.0.0");
+            tracing::debug_span!(target: "pacquet::timing", "link").in_scope(|| {
+                assert_eq!(current_package().as_deref(), Some("foo@1.0.0"));
+            });
+        });
+    }
+
+    #[test]
+    fn panic_hook_should_name_the_package() {
+        // the hook is global, so it is set in a process of its own instead of among the tests
+        // that run in parallel
+        const CHILD_ENV: &str = "PACQUET_PANIC_HOOK_TEST_CHILD";
+        if env::var_os(CHILD_ENV).is_some() {
+            set_panic_hook();
+            let subscriber = tracing_subscriber::registry().with(PanicContextLayer);
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!(target: "pacquet::timing", "package", package = "foo@1.0.0")
+                    .in_scope(|| panic!("unsupported resolution"));
+            });
+            return;
+        }
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "panic_hook::tests::panic_hook_should_name_the_package"])
+            .arg("--nocapture")
+            .env(CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        eprintln!("STDERR:\n{stderr}");
+        assert!(stderr
+            .contains("pacquet panicked while installing foo@1.0.0: unsupported resolution at "));
+        assert!(stderr.contains("please report it"));
+    }
+}
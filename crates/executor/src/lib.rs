@@ -1,6 +1,12 @@
 use derive_more::{Display, Error};
 use miette::Diagnostic;
-use std::process::Command;
+use std::{
+    ffi::OsStr,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
 
 #[derive(Debug, Display, Error, Diagnostic)]
 #[non_exhaustive]
@@ -12,6 +18,10 @@ pub enum ExecutorError {
     #[display("Process exits with an error: {_0}")]
     #[diagnostic(code(pacquet_executor::wait_process))]
     WaitProcess(#[error(source)] std::io::Error),
+
+    #[display("Process exits with {_0}")]
+    #[diagnostic(code(pacquet_executor::exit_status))]
+    ExitStatus(#[error(not(source))] std::process::ExitStatus),
 }
 
 pub fn execute_shell(command: &str) -> Result<(), ExecutorError> {
@@ -22,3 +32,107 @@ pub fn execute_shell(command: &str) -> Result<(), ExecutorError> {
 
     Ok(())
 }
+
+/// Run `command` in `current_dir` with extra environment variables, and fail when it exits with
+/// a non-zero status.
+pub fn execute_script<Key, Value>(
+    command: &str,
+    current_dir: &Path,
+    envs: impl IntoIterator<Item = (Key, Value)>,
+) -> Result<(), ExecutorError>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(current_dir)
+        .envs(envs)
+        .spawn()
+        .map_err(ExecutorError::SpawnCommand)?
+        .wait()
+        .map_err(ExecutorError::WaitProcess)?;
+
+    if !status.success() {
+        return Err(ExecutorError::ExitStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Like [`execute_script`], but the lines that `command` writes to stdout and stderr are passed
+/// to `on_stdout` and `on_stderr` (without the line break) instead of being inherited.
+pub fn execute_script_with_output<Key, Value>(
+    command: &str,
+    current_dir: &Path,
+    envs: impl IntoIterator<Item = (Key, Value)>,
+    on_stdout: impl Fn(&str) + Sync,
+    on_stderr: impl Fn(&str) + Sync,
+) -> Result<(), ExecutorError>
+where
+    Key: AsRef<OsStr>,
+    Value: AsRef<OsStr>,
+{
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(current_dir)
+        .envs(envs)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(ExecutorError::SpawnCommand)?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    thread::scope(|scope| {
+        scope.spawn(|| forward_lines(stdout, &on_stdout));
+        forward_lines(stderr, &on_stderr);
+    });
+    let status = child.wait().map_err(ExecutorError::WaitProcess)?;
+
+    if !status.success() {
+        return Err(ExecutorError::ExitStatus(status));
+    }
+
+    Ok(())
+}
+
+/// Pass each line of `reader` to `on_line` until the end of the stream.
+fn forward_lines(reader: impl Read, on_line: impl Fn(&str)) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    // a read error means the pipe is broken, which the exit status will tell about
+    while reader.read_until(b'\n', &mut line).is_ok_and(|size| size > 0) {
+        let text = String::from_utf8_lossy(&line);
+        on_line(text.trim_end_matches(['\r', '\n']));
+        line.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    #[test]
+    fn should_pass_output_lines() {
+        let stdout = Mutex::new(Vec::new());
+        let stderr = Mutex::new(Vec::new());
+        let result = execute_script_with_output(
+            "echo one; echo two >&2; printf three; exit 2",
+            Path::new("."),
+            [("UNUSED", "")],
+            |line| stdout.lock().unwrap().push(line.to_string()),
+            |line| stderr.lock().unwrap().push(line.to_string()),
+        );
+        assert!(
+            matches!(result, Err(ExecutorError::ExitStatus(status)) if status.code() == Some(2))
+        );
+        assert_eq!(stdout.into_inner().unwrap(), ["one", "three"]);
+        assert_eq!(stderr.into_inner().unwrap(), ["two"]);
+    }
+}
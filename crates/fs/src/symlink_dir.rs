@@ -3,9 +3,42 @@ use std::{io, path::Path};
 /// Create a symlink to a directory.
 ///
 /// The `link` path will be a symbolic link pointing to `original`.
+///
+/// On Windows, a junction is created instead because symlinks may require elevated privileges.
+/// If the file system doesn't support junctions (e.g. network drives), a symlink is attempted
+/// before giving up with the error of the junction.
 pub fn symlink_dir(original: &Path, link: &Path) -> io::Result<()> {
     #[cfg(unix)]
     return std::os::unix::fs::symlink(original, link);
     #[cfg(windows)]
-    return junction::create(original, link); // junctions instead of symlinks because symlinks may require elevated privileges.
+    return match junction::create(original, link) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => {
+            std::os::windows::fs::symlink_dir(original, link).map_err(|_| error)
+        }
+        result => result,
+    };
+}
+
+#[cfg(windows)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_link_directory_without_elevated_privileges() {
+        let dir = tempdir().unwrap();
+        let original = dir.path().join("original");
+        fs::create_dir(&original).unwrap();
+        fs::write(original.join("file.txt"), "content").unwrap();
+
+        let link = dir.path().join("link");
+        symlink_dir(&original, &link).unwrap();
+        assert!(junction::exists(&link).unwrap() || link.is_symlink());
+        assert_eq!(fs::read_to_string(link.join("file.txt")).unwrap(), "content");
+
+        let error = symlink_dir(&original, &link).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+    }
 }
@@ -0,0 +1,294 @@
+use crate::ProjectSnapshot;
+use derive_more::{Display, Error};
+use pacquet_diagnostics::miette::{self, Diagnostic};
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use std::{collections::BTreeMap, fmt};
+
+/// Difference between a dependency declared in `package.json` and the one recorded in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyDrift {
+    /// The dependency is declared in `package.json` but not recorded in the lockfile.
+    Added { group: DependencyGroup, name: String, specifier: String },
+    /// The dependency is recorded in the lockfile but no longer declared in `package.json`.
+    Removed { group: DependencyGroup, name: String, specifier: String },
+    /// The specifier in `package.json` differs from the one in the lockfile.
+    Changed { group: DependencyGroup, name: String, lockfile: String, manifest: String },
+}
+
+impl DependencyDrift {
+    /// Name of the dependency.
+    pub fn name(&self) -> &'_ str {
+        match self {
+            DependencyDrift::Added { name, .. }
+            | DependencyDrift::Removed { name, .. }
+            | DependencyDrift::Changed { name, .. } => name,
+        }
+    }
+
+    /// Group of the dependency.
+    pub fn group(&self) -> DependencyGroup {
+        match *self {
+            DependencyDrift::Added { group, .. }
+            | DependencyDrift::Removed { group, .. }
+            | DependencyDrift::Changed { group, .. } => group,
+        }
+    }
+}
+
+impl fmt::Display for DependencyDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let group: &str = self.group().into();
+        match self {
+            DependencyDrift::Added { name, specifier, .. } => {
+                write!(f, "+ {name}@{specifier} ({group})")
+            }
+            DependencyDrift::Removed { name, specifier, .. } => {
+                write!(f, "- {name}@{specifier} ({group})")
+            }
+            DependencyDrift::Changed { name, lockfile, manifest, .. } => {
+                write!(f, "~ {name}: {lockfile} -> {manifest} ({group})")
+            }
+        }
+    }
+}
+
+/// Error when the lockfile doesn't satisfy `package.json`.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display(
+    "The lockfile is not up to date with package.json:\n{}\n\nChanges to bring pnpm-lock.yaml up to date:\n{}",
+    DisplayDrift(drift),
+    DisplayYamlDiff(drift)
+)]
+#[diagnostic(
+    code(pacquet_lockfile::outdated_lockfile),
+    help("Run `pacquet install` without `--frozen-lockfile` to update the lockfile.")
+)]
+pub struct OutdatedLockfileError {
+    #[error(not(source))]
+    pub drift: Vec<DependencyDrift>,
+}
+
+struct DisplayDrift<'a>(&'a [DependencyDrift]);
+
+impl fmt::Display for DisplayDrift<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, drift) in self.0.iter().enumerate() {
+            if index != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  {drift}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The specifiers of the project snapshot that `drift` changes, as a diff of its YAML. Groups
+/// keep their order and the dependencies of a group are sorted by name.
+struct DisplayYamlDiff<'a>(&'a [DependencyDrift]);
+
+impl fmt::Display for DisplayYamlDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut groups: Vec<(DependencyGroup, BTreeMap<&str, &DependencyDrift>)> = Vec::new();
+        for drift in self.0 {
+            match groups.iter_mut().find(|(group, _)| *group == drift.group()) {
+                Some((_, dependencies)) => {
+                    dependencies.insert(drift.name(), drift);
+                }
+                None => groups.push((drift.group(), BTreeMap::from([(drift.name(), drift)]))),
+            }
+        }
+
+        let mut lines = Vec::new();
+        for (group, dependencies) in groups {
+            let group: &str = group.into();
+            lines.push(format!("   {group}:"));
+            for (name, drift) in dependencies {
+                let name = yaml_scalar(name);
+                let specifier = |specifier: &str| format!("specifier: {}", yaml_scalar(specifier));
+                match drift {
+                    DependencyDrift::Added { specifier: added, .. } => {
+                        lines.push(format!("+    {name}:"));
+                        lines.push(format!("+      {}", specifier(added)));
+                    }
+                    DependencyDrift::Removed { specifier: removed, .. } => {
+                        lines.push(format!("-    {name}:"));
+                        lines.push(format!("-      {}", specifier(removed)));
+                    }
+                    DependencyDrift::Changed { lockfile, manifest, .. } => {
+                        lines.push(format!("     {name}:"));
+                        lines.push(format!("-      {}", specifier(lockfile)));
+                        lines.push(format!("+      {}", specifier(manifest)));
+                    }
+                }
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Write `value` the way it appears in the lockfile, quoted when YAML requires it.
+fn yaml_scalar(value: &str) -> String {
+    match serde_yaml::to_string(value) {
+        Ok(yaml) => yaml.trim_end().to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+impl ProjectSnapshot {
+    /// List the differences between the dependencies declared in `manifest` and the ones
+    /// recorded in this snapshot. An empty list means the snapshot is up to date.
+    ///
+    /// Peer dependencies are ignored because they are not recorded in the project snapshot.
+    pub fn drift_from_manifest(
+        &self,
+        manifest: &PackageManifest,
+        groups: impl IntoIterator<Item = DependencyGroup>,
+    ) -> Vec<DependencyDrift> {
+        let mut drift = Vec::new();
+
+        for group in groups {
+            if group == DependencyGroup::Peer {
+                continue;
+            }
+
+            let manifest_dependencies: BTreeMap<&str, &str> =
+                manifest.dependencies([group]).collect();
+            let lockfile_dependencies: BTreeMap<String, &str> = self
+                .get_map_by_group(group)
+                .into_iter()
+                .flatten()
+                .map(|(name, spec)| (name.to_string(), spec.specifier.as_str()))
+                .collect();
+
+            for (&name, &specifier) in &manifest_dependencies {
+                match lockfile_dependencies.get(name) {
+                    None => drift.push(DependencyDrift::Added {
+                        group,
+                        name: name.to_string(),
+                        specifier: specifier.to_string(),
+                    }),
+                    Some(&lockfile) if lockfile != specifier => {
+                        drift.push(DependencyDrift::Changed {
+                            group,
+                            name: name.to_string(),
+                            lockfile: lockfile.to_string(),
+                            manifest: specifier.to_string(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            for (name, &specifier) in &lockfile_dependencies {
+                if !manifest_dependencies.contains_key(name.as_str()) {
+                    drift.push(DependencyDrift::Removed {
+                        group,
+                        name: name.clone(),
+                        specifier: specifier.to_string(),
+                    });
+                }
+            }
+        }
+
+        drift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+    use text_block_macros::text_block;
+
+    const YAML: &str = text_block! {
+        "dependencies:"
+        "  react:"
+        "    specifier: ^17.0.2"
+        "    version: 17.0.2"
+        "  lodash:"
+        "    specifier: ^4.17.0"
+        "    version: 4.17.21"
+        "devDependencies:"
+        "  typescript:"
+        "    specifier: ^5.1.6"
+        "    version: 5.1.6"
+    };
+
+    fn manifest(content: &str) -> (tempfile::TempDir, PackageManifest) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("package.json");
+        fs::write(&path, content).unwrap();
+        let manifest = PackageManifest::from_path(path).unwrap();
+        (dir, manifest)
+    }
+
+    #[test]
+    fn in_sync_manifest_should_have_no_drift() {
+        let snapshot: ProjectSnapshot = serde_yaml::from_str(YAML).unwrap();
+        let (_dir, manifest) = manifest(
+            r#"{
+                "dependencies": { "react": "^17.0.2", "lodash": "^4.17.0" },
+                "devDependencies": { "typescript": "^5.1.6" }
+            }"#,
+        );
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+        assert_eq!(snapshot.drift_from_manifest(&manifest, groups), []);
+    }
+
+    #[test]
+    fn drifted_manifest_should_list_differences() {
+        let snapshot: ProjectSnapshot = serde_yaml::from_str(YAML).unwrap();
+        let (_dir, manifest) = manifest(
+            r#"{
+                "dependencies": { "react": "^18.0.0", "express": "^4.0.0" },
+                "devDependencies": { "typescript": "^5.1.6" }
+            }"#,
+        );
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+        let drift = snapshot.drift_from_manifest(&manifest, groups);
+        dbg!(&drift);
+        let lines: Vec<String> = drift.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "+ express@^4.0.0 (dependencies)",
+                "~ react: ^17.0.2 -> ^18.0.0 (dependencies)",
+                "- lodash@^4.17.0 (dependencies)",
+            ],
+        );
+    }
+
+    #[test]
+    fn outdated_lockfile_error_should_show_yaml_diff() {
+        let snapshot: ProjectSnapshot = serde_yaml::from_str(YAML).unwrap();
+        let (_dir, manifest) = manifest(
+            r#"{
+                "dependencies": { "react": "^18.0.0", "express": "^4.0.0" },
+                "devDependencies": { "typescript": "^5.1.6", "@types/node": "*" }
+            }"#,
+        );
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+        let drift = snapshot.drift_from_manifest(&manifest, groups);
+        let message = OutdatedLockfileError { drift }.to_string();
+        eprintln!("{message}");
+        let (_, diff) = message.split_once("up to date:\n").unwrap();
+        assert_eq!(
+            diff,
+            text_block! {
+                "   dependencies:"
+                "+    express:"
+                "+      specifier: ^4.0.0"
+                "-    lodash:"
+                "-      specifier: ^4.17.0"
+                "     react:"
+                "-      specifier: ^17.0.2"
+                "+      specifier: ^18.0.0"
+                "   devDependencies:"
+                "+    '@types/node':"
+                "+      specifier: '*'"
+            },
+        );
+    }
+}
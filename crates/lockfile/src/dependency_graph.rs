@@ -0,0 +1,238 @@
+use crate::{Lockfile, PackageSnapshot, PkgName, ProjectSnapshot, RootProjectSnapshot};
+use pacquet_package_manifest::DependencyGroup;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Resolved dependency graph of a lockfile, made of the projects and packages as nodes and of
+/// the dependencies between them as edges.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyGraphNode>,
+    pub edges: Vec<DependencyGraphEdge>,
+}
+
+/// Node of a [`DependencyGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyGraphNode {
+    /// Path of the project (`.` for the root) or `{name}@{version}{peers}` of the package.
+    pub id: String,
+    /// Name of the package, `None` for a project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Resolved version of the package, `None` for a project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// Edge of a [`DependencyGraph`] from a dependent to its dependency.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyGraphEdge {
+    /// Id of the dependent node.
+    pub from: String,
+    /// Id of the dependency node.
+    pub to: String,
+    /// Name that the dependent uses for the dependency, which differs from the name of the
+    /// package when it is aliased.
+    pub alias: String,
+    /// `prod`, `dev`, `optional`, or `peer`.
+    #[serde(rename = "type", serialize_with = "serialize_group")]
+    pub group: DependencyGroup,
+}
+
+fn serialize_group<S: Serializer>(
+    group: &DependencyGroup,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(group_name(*group))
+}
+
+fn group_name(group: DependencyGroup) -> &'static str {
+    match group {
+        DependencyGroup::Prod => "prod",
+        DependencyGroup::Dev => "dev",
+        DependencyGroup::Optional => "optional",
+        DependencyGroup::Peer => "peer",
+    }
+}
+
+impl Lockfile {
+    /// Build the graph of the projects and packages of the lockfile.
+    ///
+    /// Nodes are sorted by id and edges by dependent, dependency, and alias.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let mut nodes = BTreeMap::new();
+        let mut edges = Vec::new();
+
+        let importers: Vec<(&str, &ProjectSnapshot)> = match &self.project_snapshot {
+            RootProjectSnapshot::Single(snapshot) => vec![(".", snapshot)],
+            RootProjectSnapshot::Multi(snapshot) => {
+                snapshot.importers.iter().map(|(id, snapshot)| (id.as_str(), snapshot)).collect()
+            }
+        };
+        for (id, snapshot) in importers {
+            nodes.insert(
+                id.to_string(),
+                DependencyGraphNode { id: id.to_string(), name: None, version: None },
+            );
+            for group in [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional] {
+                for (alias, spec) in snapshot.get_map_by_group(group).into_iter().flatten() {
+                    let to = spec.version.to_package_specifier(alias).to_string();
+                    edges.push(DependencyGraphEdge {
+                        from: id.to_string(),
+                        to,
+                        alias: alias.to_string(),
+                        group,
+                    });
+                }
+            }
+        }
+
+        for (dependency_path, package_snapshot) in self.packages.iter().flatten() {
+            let package_specifier = &dependency_path.package_specifier;
+            let id = package_specifier.to_string();
+            nodes.insert(
+                id.clone(),
+                DependencyGraphNode {
+                    id: id.clone(),
+                    name: Some(package_specifier.name.to_string()),
+                    version: Some(package_specifier.suffix.version().to_string()),
+                },
+            );
+            edges.extend(package_edges(&id, package_snapshot));
+        }
+
+        edges.sort_by(|a, b| {
+            (&a.from, &a.to, &a.alias, group_name(a.group)).cmp(&(
+                &b.from,
+                &b.to,
+                &b.alias,
+                group_name(b.group),
+            ))
+        });
+        DependencyGraph { nodes: nodes.into_values().collect(), edges }
+    }
+}
+
+/// List the edges from the package `id` to its dependencies.
+///
+/// Resolved peer dependencies are recorded among the `dependencies` of the snapshot, they are
+/// told apart by the `peerDependencies` field.
+fn package_edges<'a>(
+    id: &'a str,
+    package_snapshot: &'a PackageSnapshot,
+) -> impl Iterator<Item = DependencyGraphEdge> + 'a {
+    let is_peer = |alias: &PkgName| {
+        package_snapshot
+            .peer_dependencies
+            .as_ref()
+            .is_some_and(|peers| peers.contains_key(&alias.to_string()))
+    };
+    package_snapshot.resolved_dependencies().map(move |(alias, package_specifier, optional)| {
+        let group = match optional {
+            true => DependencyGroup::Optional,
+            false if is_peer(&alias) => DependencyGroup::Peer,
+            false => DependencyGroup::Prod,
+        };
+        DependencyGraphEdge {
+            from: id.to_string(),
+            to: package_specifier.to_string(),
+            alias: alias.to_string(),
+            group,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use text_block_macros::text_block;
+
+    const LOCKFILE: &str = text_block! {
+        "lockfileVersion: '6.0'"
+        "dependencies:"
+        "  react-dom:"
+        "    specifier: ^17.0.2"
+        "    version: 17.0.2(react@17.0.2)"
+        "  react:"
+        "    specifier: ^17.0.2"
+        "    version: 17.0.2"
+        "devDependencies:"
+        "  my-tslib:"
+        "    specifier: npm:tslib@^2.6.0"
+        "    version: /tslib@2.6.0"
+        "packages:"
+        "  /js-tokens@4.0.0:"
+        "    resolution: {integrity: sha512-RdJUflcE3cUzKiMqQgsCu06FPu9UdIJO0beYbPhHN4k6apgJtifcoCtT9bcxOpYBtpD2kCM6Sbzg4CausW/PKQ==}"
+        "  /loose-envify@1.4.0:"
+        "    resolution: {integrity: sha512-lyuxPGr/Wfhrlem2CL/UcnUc1zcqKAImBDzukY7Y5F/yQiNdko6+fRLevlw1HgMySw7f611UIY408EtxRSoK3Q==}"
+        "    dependencies:"
+        "      js-tokens: 4.0.0"
+        "  /react-dom@17.0.2(react@17.0.2):"
+        "    resolution: {integrity: sha512-s4h96KtLDUQlsENhMn1ar8t2bEa+q/YAtj8pPPdIjPDGBDIVNsrD9aXNWqspUe6AzKCIG0C1HZZLqLV7qpOBGA==}"
+        "    peerDependencies:"
+        "      react: 17.0.2"
+        "    dependencies:"
+        "      loose-envify: 1.4.0"
+        "      react: 17.0.2"
+        "    optionalDependencies:"
+        "      js-tokens: 4.0.0"
+        "  /react@17.0.2:"
+        "    resolution: {integrity: sha512-gnhPt75i/dq/z3/6q/0asP78D0u592D5L1pd7M8P+dck6Fu/jJeL6iVVK23fptSUZj8Vjf++7wXA8UNclGQcbA==}"
+        "    dependencies:"
+        "      loose-envify: 1.4.0"
+        "  /tslib@2.6.0:"
+        "    resolution: {integrity: sha512-7At1WUettjcSRHXCyYtTselblcHl9PJFFVKiCAy/bY97+BPZXSQ2wbq0P9s8tK2G7dFQfNnlJnPAiArVBVBsfA==}"
+    };
+
+    fn node(id: &str, name: Option<&str>, version: Option<&str>) -> DependencyGraphNode {
+        DependencyGraphNode {
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            version: version.map(str::to_string),
+        }
+    }
+
+    fn edge(from: &str, to: &str, alias: &str, group: DependencyGroup) -> DependencyGraphEdge {
+        DependencyGraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            alias: alias.to_string(),
+            group,
+        }
+    }
+
+    #[test]
+    fn dependency_graph() {
+        use DependencyGroup::{Dev, Optional, Peer, Prod};
+
+        let lockfile: Lockfile = serde_yaml::from_str(LOCKFILE).unwrap();
+        let graph = lockfile.dependency_graph();
+        dbg!(&graph);
+
+        assert_eq!(
+            graph.nodes,
+            [
+                node(".", None, None),
+                node("js-tokens@4.0.0", Some("js-tokens"), Some("4.0.0")),
+                node("loose-envify@1.4.0", Some("loose-envify"), Some("1.4.0")),
+                node("react-dom@17.0.2(react@17.0.2)", Some("react-dom"), Some("17.0.2")),
+                node("react@17.0.2", Some("react"), Some("17.0.2")),
+                node("tslib@2.6.0", Some("tslib"), Some("2.6.0")),
+            ]
+        );
+        assert_eq!(
+            graph.edges,
+            [
+                edge(".", "react-dom@17.0.2(react@17.0.2)", "react-dom", Prod),
+                edge(".", "react@17.0.2", "react", Prod),
+                edge(".", "tslib@2.6.0", "my-tslib", Dev),
+                edge("loose-envify@1.4.0", "js-tokens@4.0.0", "js-tokens", Prod),
+                edge("react-dom@17.0.2(react@17.0.2)", "js-tokens@4.0.0", "js-tokens", Optional),
+                edge("react-dom@17.0.2(react@17.0.2)", "loose-envify@1.4.0", "loose-envify", Prod),
+                edge("react-dom@17.0.2(react@17.0.2)", "react@17.0.2", "react", Peer),
+                edge("react@17.0.2", "loose-envify@1.4.0", "loose-envify", Prod),
+            ]
+        );
+    }
+}
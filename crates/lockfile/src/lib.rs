@@ -1,6 +1,9 @@
 mod comver;
+mod dependency_drift;
+mod dependency_graph;
 mod dependency_path;
 mod load_lockfile;
+mod lockfile_v5;
 mod lockfile_version;
 mod multi_project_snapshot;
 mod package_snapshot;
@@ -14,10 +17,15 @@ mod project_snapshot;
 mod resolution;
 mod resolved_dependency;
 mod root_project_snapshot;
+mod save_lockfile;
+mod sorted_map;
 
 pub use comver::*;
+pub use dependency_drift::*;
+pub use dependency_graph::*;
 pub use dependency_path::*;
 pub use load_lockfile::*;
+pub use lockfile_v5::*;
 pub use lockfile_version::*;
 pub use multi_project_snapshot::*;
 pub use package_snapshot::*;
@@ -31,6 +39,7 @@ pub use project_snapshot::*;
 pub use resolution::*;
 pub use resolved_dependency::*;
 pub use root_project_snapshot::*;
+pub use save_lockfile::*;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -38,8 +47,8 @@ use std::collections::HashMap;
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LockfileSettings {
-    auto_install_peers: bool,
-    exclude_links_from_lockfile: bool,
+    pub auto_install_peers: bool,
+    pub exclude_links_from_lockfile: bool,
 }
 
 /// * Specification: <https://github.com/pnpm/spec/blob/master/lockfile/6.0.md>
@@ -52,15 +61,37 @@ pub struct Lockfile {
     pub settings: Option<LockfileSettings>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub never_built_dependencies: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub overrides: Option<HashMap<String, String>>,
     #[serde(flatten)]
     pub project_snapshot: RootProjectSnapshot,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub packages: Option<HashMap<DependencyPath, PackageSnapshot>>,
 }
 
 impl Lockfile {
     /// Base file name of the lockfile.
     const FILE_NAME: &str = "pnpm-lock.yaml";
+
+    /// Create a lockfile of the current `lockfileVersion` without settings nor overrides.
+    pub fn new(
+        project_snapshot: RootProjectSnapshot,
+        packages: HashMap<DependencyPath, PackageSnapshot>,
+    ) -> Self {
+        Lockfile {
+            lockfile_version: LockfileVersion::try_from(ComVer::new(6, 0))
+                .expect("6.0 is compatible with 6.x"),
+            settings: None,
+            never_built_dependencies: None,
+            overrides: None,
+            project_snapshot,
+            packages: (!packages.is_empty()).then_some(packages),
+        }
+    }
 }
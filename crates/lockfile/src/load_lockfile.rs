@@ -1,7 +1,8 @@
-use crate::Lockfile;
+use crate::{lockfile_v5::parse_lockfile_version, Lockfile, LockfileV5, MigrateLockfileV5Error};
 use derive_more::{Display, Error};
 use pacquet_diagnostics::miette::{self, Diagnostic};
 use pipe_trait::Pipe;
+use serde::Deserialize;
 use std::{
     env, fs,
     io::{self, ErrorKind},
@@ -22,6 +23,9 @@ pub enum LoadLockfileError {
     #[display("Failed to parse lockfile content as YAML: {_0}")]
     #[diagnostic(code(pacquet_lockfile::parse_yaml))]
     ParseYaml(serde_yaml::Error),
+
+    #[diagnostic(transparent)]
+    MigrateV5(#[error(source)] MigrateLockfileV5Error),
 }
 
 impl Lockfile {
@@ -34,6 +38,33 @@ impl Lockfile {
             Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
             Err(error) => return error.pipe(LoadLockfileError::ReadFile).pipe(Err),
         };
-        content.pipe_as_ref(serde_yaml::from_str).map_err(LoadLockfileError::ParseYaml)
+        Lockfile::from_yaml_str(&content)
+    }
+
+    /// Parse the content of a lockfile.
+    ///
+    /// A lockfile of `lockfileVersion` 5 is [migrated](LockfileV5::migrate), so that it is
+    /// written back as a v6 lockfile.
+    pub fn from_yaml_str(content: &str) -> Result<Option<Self>, LoadLockfileError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct VersionProbe {
+            #[serde(default)]
+            lockfile_version: serde_yaml::Value,
+        }
+
+        let probe: Option<VersionProbe> =
+            serde_yaml::from_str(content).map_err(LoadLockfileError::ParseYaml)?;
+        let is_v5 = probe
+            .and_then(|probe| parse_lockfile_version(&probe.lockfile_version))
+            .is_some_and(|comver| comver.major == 5);
+        if !is_v5 {
+            return content.pipe(serde_yaml::from_str).map_err(LoadLockfileError::ParseYaml);
+        }
+        serde_yaml::from_str::<LockfileV5>(content)
+            .map_err(LoadLockfileError::ParseYaml)?
+            .migrate()
+            .map(Some)
+            .map_err(LoadLockfileError::MigrateV5)
     }
 }
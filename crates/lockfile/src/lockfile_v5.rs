@@ -0,0 +1,510 @@
+use crate::{
+    ComVer, DependencyPath, Lockfile, LockfileVersion, MultiProjectSnapshot, PackageSnapshot,
+    PackageSnapshotDependency, ParseDependencyPathError, ParsePkgVerPeerError, PkgName, PkgVerPeer,
+    ProjectSnapshot, ResolvedDependencyMap, ResolvedDependencySpec,
+};
+use derive_more::{Display, Error};
+use pacquet_diagnostics::miette::{self, Diagnostic};
+use serde::{Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// Lockfile of `lockfileVersion: 5.x`, which is written by pnpm 7 and older.
+///
+/// It differs from [`Lockfile`] in the syntax of the keys of `packages` (`/{name}/{version}`
+/// instead of `/{name}@{version}`), in the syntax of the peer suffix (`_{peer}+{peer}` instead of
+/// `({peer})({peer})`), and in the specifiers of the projects, which are in a separate
+/// `specifiers` map. Use [`migrate`](Self::migrate) to convert it.
+///
+/// Specification: <https://github.com/pnpm/spec/blob/master/lockfile/5.2.md>
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockfileV5 {
+    #[serde(deserialize_with = "deserialize_lockfile_version")]
+    pub lockfile_version: LockfileVersion<5>,
+    pub never_built_dependencies: Option<Vec<String>>,
+    pub overrides: Option<HashMap<String, String>>,
+    pub importers: Option<HashMap<String, ProjectSnapshotV5>>,
+    #[serde(flatten)]
+    pub project_snapshot: ProjectSnapshotV5,
+    pub packages: Option<HashMap<String, Mapping>>,
+}
+
+/// Snapshot of a project in a [`LockfileV5`].
+///
+/// The values of the dependency maps are versions with a peer suffix of the v5 syntax, or
+/// dependency paths of the v5 syntax for the aliased dependencies.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSnapshotV5 {
+    pub specifiers: Option<HashMap<String, String>>,
+    pub dependencies: Option<HashMap<PkgName, String>>,
+    pub optional_dependencies: Option<HashMap<PkgName, String>>,
+    pub dev_dependencies: Option<HashMap<PkgName, String>>,
+    pub dependencies_meta: Option<Value>,
+    pub publish_directory: Option<String>,
+}
+
+/// Error when migrating a [`LockfileV5`] to a [`Lockfile`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum MigrateLockfileV5Error {
+    #[display("Invalid dependency path {path:?}")]
+    #[diagnostic(code(pacquet_lockfile::invalid_v5_dependency_path))]
+    InvalidDependencyPath {
+        #[error(not(source))]
+        path: String,
+    },
+
+    #[display("The peer suffix of {value:?} is hashed, so its peers can't be recovered")]
+    #[diagnostic(
+        code(pacquet_lockfile::hashed_peer_suffix),
+        help("Regenerate the lockfile with pnpm 8 or newer")
+    )]
+    HashedPeerSuffix {
+        #[error(not(source))]
+        value: String,
+    },
+
+    #[display("Failed to convert the version {value:?}: {error}")]
+    #[diagnostic(code(pacquet_lockfile::convert_v5_version))]
+    ParseVerPeer {
+        value: String,
+        #[error(source)]
+        error: ParsePkgVerPeerError,
+    },
+
+    #[display("Failed to convert the dependency path {path:?}: {error}")]
+    #[diagnostic(code(pacquet_lockfile::convert_v5_dependency_path))]
+    ParseDependencyPath {
+        path: String,
+        #[error(source)]
+        error: ParseDependencyPathError,
+    },
+
+    #[display("The dependency {name} has no specifier")]
+    #[diagnostic(code(pacquet_lockfile::missing_specifier))]
+    MissingSpecifier {
+        #[error(not(source))]
+        name: String,
+    },
+
+    #[display("Failed to convert the snapshot of {path:?}: {error}")]
+    #[diagnostic(code(pacquet_lockfile::convert_v5_package_snapshot))]
+    ParsePackageSnapshot {
+        path: String,
+        #[error(source)]
+        error: serde_yaml::Error,
+    },
+}
+
+/// `lockfileVersion` of a v5 lockfile is usually a number, like `5.4`, rather than a string.
+fn deserialize_lockfile_version<'de, D>(deserializer: D) -> Result<LockfileVersion<5>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Value::deserialize(deserializer)?;
+    let comver = parse_lockfile_version(&value)
+        .ok_or_else(|| serde::de::Error::custom(format!("Invalid lockfileVersion: {value:?}")))?;
+    LockfileVersion::try_from(comver).map_err(serde::de::Error::custom)
+}
+
+/// Parse the value of `lockfileVersion`, be it a number or a string.
+pub(crate) fn parse_lockfile_version(value: &Value) -> Option<ComVer> {
+    let text = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => text.clone(),
+        _ => return None,
+    };
+    match text.parse() {
+        Ok(comver) => Some(comver),
+        Err(_) => text.parse().ok().map(|major| ComVer::new(major, 0)),
+    }
+}
+
+/// Convert the v5 peer suffix syntax of `value` (`{version}_{peer}+{peer}`) to the v6 one
+/// (`{version}({peer})({peer})`).
+///
+/// The slash of a scoped peer is a plus sign in v5, so `18.0.0_@types+node@18.7.19+react@17.0.2`
+/// has the peers `@types/node@18.7.19` and `react@17.0.2`.
+fn convert_ver_peer(value: &str) -> Result<PkgVerPeer, MigrateLockfileV5Error> {
+    let converted = match value.split_once('_') {
+        None => value.to_string(),
+        Some((version, suffix)) => {
+            let mut converted = version.to_string();
+            let mut scope: Option<&str> = None;
+            for segment in suffix.split('+') {
+                let peer = match scope.take() {
+                    Some(scope) => format!("{scope}/{segment}"),
+                    None if segment.starts_with('@') && !segment[1..].contains('@') => {
+                        scope = Some(segment);
+                        continue;
+                    }
+                    None => segment.to_string(),
+                };
+                if !peer.get(1..).is_some_and(|rest| rest.contains('@')) {
+                    return Err(MigrateLockfileV5Error::HashedPeerSuffix {
+                        value: value.to_string(),
+                    });
+                }
+                converted.push('(');
+                converted.push_str(&peer);
+                converted.push(')');
+            }
+            if scope.is_some() {
+                return Err(MigrateLockfileV5Error::HashedPeerSuffix { value: value.to_string() });
+            }
+            converted
+        }
+    };
+    PkgVerPeer::parse_strict(&converted)
+        .map_err(|error| MigrateLockfileV5Error::ParseVerPeer { value: value.to_string(), error })
+}
+
+/// Convert a v5 dependency path (`{custom_registry}/{name}/{version}_{peers}`) to a
+/// [`DependencyPath`].
+fn convert_dependency_path(path: &str) -> Result<DependencyPath, MigrateLockfileV5Error> {
+    let invalid = || MigrateLockfileV5Error::InvalidDependencyPath { path: path.to_string() };
+    let (custom_registry, specifier) = path.split_once('/').ok_or_else(invalid)?;
+    let scope_len =
+        if specifier.starts_with('@') { specifier.find('/').ok_or_else(invalid)? + 1 } else { 0 };
+    let slash = scope_len + specifier[scope_len..].find('/').ok_or_else(invalid)?;
+    let (name, ver_peer) = (&specifier[..slash], &specifier[slash + 1..]);
+    let ver_peer = convert_ver_peer(ver_peer)?;
+    format!("{custom_registry}/{name}@{ver_peer}").parse().map_err(|error| {
+        MigrateLockfileV5Error::ParseDependencyPath { path: path.to_string(), error }
+    })
+}
+
+/// Convert a value of a dependency map, which is either a version or, for an aliased dependency,
+/// a dependency path.
+fn convert_dependency(value: &str) -> Result<PackageSnapshotDependency, MigrateLockfileV5Error> {
+    if value.contains('/') {
+        convert_dependency_path(value).map(PackageSnapshotDependency::from)
+    } else {
+        convert_ver_peer(value).map(PackageSnapshotDependency::from)
+    }
+}
+
+/// Convert the values of the `dependencies` and `optionalDependencies` of a package snapshot, the
+/// other fields are the same in v6.
+fn convert_package_snapshot(
+    path: &str,
+    mut snapshot: Mapping,
+) -> Result<PackageSnapshot, MigrateLockfileV5Error> {
+    for field in ["dependencies", "optionalDependencies"] {
+        let Some(Value::Mapping(dependencies)) = snapshot.get_mut(field) else { continue };
+        for (_, value) in dependencies.iter_mut() {
+            let Value::String(version) = value else {
+                return Err(MigrateLockfileV5Error::InvalidDependencyPath {
+                    path: path.to_string(),
+                });
+            };
+            *value = Value::String(convert_dependency(version)?.to_string());
+        }
+    }
+    // the keys of the model are deserialized from borrowed strings, which `serde_yaml::Value`
+    // can't lend
+    serde_yaml::to_string(&snapshot).and_then(|snapshot| serde_yaml::from_str(&snapshot)).map_err(
+        |error| MigrateLockfileV5Error::ParsePackageSnapshot { path: path.to_string(), error },
+    )
+}
+
+impl ProjectSnapshotV5 {
+    /// Merge the specifiers into the dependency maps and convert the versions.
+    pub fn migrate(self) -> Result<ProjectSnapshot, MigrateLockfileV5Error> {
+        let ProjectSnapshotV5 {
+            specifiers,
+            dependencies,
+            optional_dependencies,
+            dev_dependencies,
+            dependencies_meta,
+            publish_directory,
+        } = self;
+        let specifiers = specifiers.unwrap_or_default();
+        let convert_map = |map: Option<HashMap<PkgName, String>>| {
+            map.map(|map| {
+                map.into_iter()
+                    .map(|(name, version)| {
+                        let specifier =
+                            specifiers.get(&name.to_string()).cloned().ok_or_else(|| {
+                                MigrateLockfileV5Error::MissingSpecifier { name: name.to_string() }
+                            })?;
+                        let version = convert_dependency(&version)?;
+                        Ok((name, ResolvedDependencySpec { specifier, version }))
+                    })
+                    .collect::<Result<ResolvedDependencyMap, _>>()
+            })
+            .transpose()
+        };
+        Ok(ProjectSnapshot {
+            specifiers: None,
+            dependencies: convert_map(dependencies)?,
+            optional_dependencies: convert_map(optional_dependencies)?,
+            dev_dependencies: convert_map(dev_dependencies)?,
+            dependencies_meta,
+            publish_directory,
+        })
+    }
+}
+
+impl LockfileV5 {
+    /// Convert the lockfile to the v6 model, whose serialization is a `lockfileVersion: '6.0'`
+    /// lockfile.
+    pub fn migrate(self) -> Result<Lockfile, MigrateLockfileV5Error> {
+        let LockfileV5 {
+            lockfile_version: _,
+            never_built_dependencies,
+            overrides,
+            importers,
+            project_snapshot,
+            packages,
+        } = self;
+
+        let project_snapshot = match importers {
+            Some(importers) => importers
+                .into_iter()
+                .map(|(id, snapshot)| Ok((id, snapshot.migrate()?)))
+                .collect::<Result<_, MigrateLockfileV5Error>>()
+                .map(|importers| MultiProjectSnapshot { importers })?
+                .into(),
+            None => project_snapshot.migrate()?.into(),
+        };
+
+        let packages = packages
+            .map(|packages| {
+                packages
+                    .into_iter()
+                    .map(|(path, snapshot)| {
+                        let dependency_path = convert_dependency_path(&path)?;
+                        let snapshot = convert_package_snapshot(&path, snapshot)?;
+                        Ok((dependency_path, snapshot))
+                    })
+                    .collect::<Result<_, MigrateLockfileV5Error>>()
+            })
+            .transpose()?;
+
+        Ok(Lockfile {
+            lockfile_version: LockfileVersion::try_from(ComVer::new(6, 0))
+                .expect("6.0 is compatible with 6.x"),
+            settings: None,
+            never_built_dependencies,
+            overrides,
+            project_snapshot,
+            packages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RootProjectSnapshot;
+    use pretty_assertions::assert_eq;
+    use text_block_macros::text_block;
+
+    const V5_YAML: &str = text_block! {
+        "lockfileVersion: 5.4"
+        ""
+        "specifiers:"
+        "  '@types/node': ^18.7.19"
+        "  react: ^17.0.2"
+        "  react-dom: ^17.0.2"
+        "  react-alias: npm:react@^17.0.2"
+        "  ts-node: 10.9.1"
+        "  typescript: ^5.1.6"
+        ""
+        "dependencies:"
+        "  react: 17.0.2"
+        "  react-dom: 17.0.2_react@17.0.2"
+        ""
+        "optionalDependencies:"
+        "  '@types/node': 18.7.19"
+        ""
+        "devDependencies:"
+        "  react-alias: /react/17.0.2"
+        "  ts-node: 10.9.1_@types+node@18.7.19+typescript@5.1.6"
+        "  typescript: 5.1.6"
+        ""
+        "packages:"
+        ""
+        "  /@types/node/18.7.19:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        "    optional: true"
+        ""
+        "  /react-dom/17.0.2_react@17.0.2:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    peerDependencies:"
+        "      react: 17.0.2"
+        "    dependencies:"
+        "      react: 17.0.2"
+        "    dev: false"
+        ""
+        "  /react/17.0.2:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        ""
+        "  /ts-node/10.9.1_@types+node@18.7.19+typescript@5.1.6:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    hasBin: true"
+        "    peerDependencies:"
+        "      '@types/node': '*'"
+        "      typescript: '>=2.7'"
+        "    dependencies:"
+        "      '@types/node': 18.7.19"
+        "      typescript: 5.1.6"
+        "    dev: true"
+        ""
+        "  /typescript/5.1.6:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    hasBin: true"
+        "    dev: true"
+    };
+
+    const V6_YAML: &str = text_block! {
+        "lockfileVersion: '6.0'"
+        ""
+        "dependencies:"
+        "  react:"
+        "    specifier: ^17.0.2"
+        "    version: 17.0.2"
+        "  react-dom:"
+        "    specifier: ^17.0.2"
+        "    version: 17.0.2(react@17.0.2)"
+        ""
+        "optionalDependencies:"
+        "  '@types/node':"
+        "    specifier: ^18.7.19"
+        "    version: 18.7.19"
+        ""
+        "devDependencies:"
+        "  react-alias:"
+        "    specifier: npm:react@^17.0.2"
+        "    version: /react@17.0.2"
+        "  ts-node:"
+        "    specifier: 10.9.1"
+        "    version: 10.9.1(@types/node@18.7.19)(typescript@5.1.6)"
+        "  typescript:"
+        "    specifier: ^5.1.6"
+        "    version: 5.1.6"
+        ""
+        "packages:"
+        ""
+        "  /@types/node@18.7.19:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        "    optional: true"
+        ""
+        "  /react-dom@17.0.2(react@17.0.2):"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    peerDependencies:"
+        "      react: 17.0.2"
+        "    dependencies:"
+        "      react: 17.0.2"
+        "    dev: false"
+        ""
+        "  /react@17.0.2:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    dev: false"
+        ""
+        "  /ts-node@10.9.1(@types/node@18.7.19)(typescript@5.1.6):"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    hasBin: true"
+        "    peerDependencies:"
+        "      '@types/node': '*'"
+        "      typescript: '>=2.7'"
+        "    dependencies:"
+        "      '@types/node': 18.7.19"
+        "      typescript: 5.1.6"
+        "    dev: true"
+        ""
+        "  /typescript@5.1.6:"
+        "    resolution: {integrity: sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==}"
+        "    hasBin: true"
+        "    dev: true"
+    };
+
+    #[test]
+    fn migrate_v5_fixture() {
+        let v5: LockfileV5 = serde_yaml::from_str(V5_YAML).unwrap();
+        let migrated = v5.migrate().unwrap();
+        let expected: Lockfile = serde_yaml::from_str(V6_YAML).unwrap();
+        assert_eq!(migrated, expected);
+
+        let written = serde_yaml::to_string(&migrated).unwrap();
+        eprintln!("WRITTEN:\n{written}");
+        assert!(written.starts_with("lockfileVersion: '6.0'\n"));
+        let reloaded = Lockfile::from_yaml_str(&written).unwrap().unwrap();
+        assert_eq!(reloaded, expected);
+    }
+
+    #[test]
+    fn migrate_v5_importers() {
+        let yaml = text_block! {
+            "lockfileVersion: 5.3"
+            "importers:"
+            "  packages/foo:"
+            "    specifiers:"
+            "      react: ^17.0.2"
+            "    dependencies:"
+            "      react: 17.0.2"
+        };
+        let RootProjectSnapshot::Multi(MultiProjectSnapshot { importers }) =
+            serde_yaml::from_str::<LockfileV5>(yaml).unwrap().migrate().unwrap().project_snapshot
+        else {
+            panic!("expecting importers");
+        };
+        let dependencies = importers["packages/foo"].dependencies.as_ref().unwrap();
+        let react = &dependencies[&"react".parse::<PkgName>().unwrap()];
+        assert_eq!(react.specifier, "^17.0.2");
+        assert_eq!(react.version.to_string(), "17.0.2");
+    }
+
+    #[test]
+    fn convert_dependency_paths() {
+        fn case(input: &str, output: &str) {
+            eprintln!("CASE: {input:?}");
+            assert_eq!(convert_dependency_path(input).unwrap().to_string(), output);
+        }
+
+        case("/react/17.0.2", "/react@17.0.2");
+        case("/@types/node/18.7.19", "/@types/node@18.7.19");
+        case("/react-dom/17.0.2_react@17.0.2", "/react-dom@17.0.2(react@17.0.2)");
+        case(
+            "/ts-node/10.9.1_@types+node@18.7.19+typescript@5.1.6",
+            "/ts-node@10.9.1(@types/node@18.7.19)(typescript@5.1.6)",
+        );
+        case(
+            "/@scope/foo/1.0.0-rc.0_@scope+bar@2.0.0+@scope+baz@3.0.0",
+            "/@scope/foo@1.0.0-rc.0(@scope/bar@2.0.0)(@scope/baz@3.0.0)",
+        );
+        case("registry.npmjs.org/react/17.0.2", "registry.npmjs.org/react@17.0.2");
+    }
+
+    #[test]
+    fn convert_dependency_paths_err() {
+        fn case(input: &str) -> MigrateLockfileV5Error {
+            eprintln!("CASE: {input:?}");
+            convert_dependency_path(input).unwrap_err()
+        }
+
+        assert!(matches!(case("react"), MigrateLockfileV5Error::InvalidDependencyPath { .. }));
+        assert!(matches!(case("/react"), MigrateLockfileV5Error::InvalidDependencyPath { .. }));
+        assert!(matches!(
+            case("/ts-node/10.9.1_xl7wyiapi7jo5c2pfz5vjm55na"),
+            MigrateLockfileV5Error::HashedPeerSuffix { .. },
+        ));
+        assert!(matches!(case("/react/latest"), MigrateLockfileV5Error::ParseVerPeer { .. }));
+    }
+
+    #[test]
+    fn parse_lockfile_versions() {
+        let parse = |yaml: &str| parse_lockfile_version(&serde_yaml::from_str(yaml).unwrap());
+        assert_eq!(parse("5.4"), Some(ComVer::new(5, 4)));
+        assert_eq!(parse("'5.3'"), Some(ComVer::new(5, 3)));
+        assert_eq!(parse("5"), Some(ComVer::new(5, 0)));
+        assert_eq!(parse("'6.0'"), Some(ComVer::new(6, 0)));
+        assert_eq!(parse("[]"), None);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{LockfileResolution, PackageSnapshotDependency, PkgName};
+use crate::{
+    DependencyPath, LockfileResolution, PackageSnapshotDependency, PkgName, PkgNameVerPeer,
+    PkgVerPeer,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -20,7 +23,10 @@ pub struct PackageSnapshot {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version: Option<String>, // TODO: name and version are required on non-default registry, create a struct for it
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub engines: Option<HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu: Option<Vec<String>>,
@@ -39,17 +45,61 @@ pub struct PackageSnapshot {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bundled_dependencies: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub peer_dependencies: Option<HashMap<String, String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub peer_dependencies_meta: Option<HashMap<String, LockfilePeerDependencyMetaValue>>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub dependencies: Option<HashMap<PkgName, PackageSnapshotDependency>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub optional_dependencies: Option<HashMap<String, String>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub transitive_peer_dependencies: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dev: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub optional: Option<bool>,
 }
+
+impl PackageSnapshot {
+    /// List the `dependencies` and the `optionalDependencies` of the package with the specifiers
+    /// of the packages they resolve to, and whether they are optional.
+    ///
+    /// Optional dependencies that can't be parsed are skipped.
+    pub fn resolved_dependencies(
+        &self,
+    ) -> impl Iterator<Item = (PkgName, PkgNameVerPeer, bool)> + '_ {
+        let dependencies =
+            self.dependencies.iter().flatten().map(|(alias, version)| {
+                (alias.clone(), version.to_package_specifier(alias), false)
+            });
+        let optional_dependencies =
+            self.optional_dependencies.iter().flatten().filter_map(|(alias, version)| {
+                let alias: PkgName = alias.parse().ok()?;
+                let version: PackageSnapshotDependency = version
+                    .parse::<PkgVerPeer>()
+                    .map(PackageSnapshotDependency::from)
+                    .or_else(|_| {
+                        version.parse::<DependencyPath>().map(PackageSnapshotDependency::from)
+                    })
+                    .ok()?;
+                let package_specifier = version.to_package_specifier(&alias);
+                Some((alias, package_specifier, true))
+            });
+        dependencies.chain(optional_dependencies)
+    }
+}
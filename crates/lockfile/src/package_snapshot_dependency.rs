@@ -1,8 +1,12 @@
-use crate::{DependencyPath, PkgVerPeer};
+use crate::{DependencyPath, PkgName, PkgNameVerPeer, PkgVerPeer};
 use derive_more::{Display, From, TryInto};
 use serde::{Deserialize, Serialize};
 
-/// Value of [`PackageSnapshot::dependencies`](crate::PackageSnapshot::dependencies).
+/// Value of [`PackageSnapshot::dependencies`](crate::PackageSnapshot::dependencies) and type of
+/// [`ResolvedDependencySpec::version`](crate::ResolvedDependencySpec::version).
+///
+/// A dependency that is aliased (`"{alias}": "npm:{name}@{range}"`) is recorded as a
+/// [`DependencyPath`] of the real package.
 #[derive(Debug, Display, Clone, PartialEq, Eq, From, TryInto, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum PackageSnapshotDependency {
@@ -10,6 +14,20 @@ pub enum PackageSnapshotDependency {
     DependencyPath(DependencyPath),
 }
 
+impl PackageSnapshotDependency {
+    /// Get the specifier of the package that `name` depends on.
+    pub fn to_package_specifier(&self, name: &PkgName) -> PkgNameVerPeer {
+        match self {
+            PackageSnapshotDependency::PkgVerPeer(ver_peer) => {
+                PkgNameVerPeer::new(name.clone(), ver_peer.clone())
+            }
+            PackageSnapshotDependency::DependencyPath(dependency_path) => {
+                dependency_path.package_specifier.clone()
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,6 +68,21 @@ mod tests {
         case!("registry.npmjs.com/@docusaurus/react-loadable@5.5.2" => DependencyPath);
     }
 
+    #[test]
+    fn to_package_specifier() {
+        let name: PkgName = "my-lodash".parse().unwrap();
+        let case = |input: &str, expected: &str| {
+            eprintln!("CASE: {input:?}");
+            let snapshot_dependency: PackageSnapshotDependency =
+                serde_yaml::from_str(input).unwrap();
+            assert_eq!(snapshot_dependency.to_package_specifier(&name).to_string(), expected);
+        };
+
+        case("4.17.21", "my-lodash@4.17.21");
+        case("/lodash@4.17.21", "lodash@4.17.21");
+        case("/@types/lodash@4.14.195", "@types/lodash@4.14.195");
+    }
+
     #[test]
     fn string_matches_yaml() {
         fn case(input: &'static str) {
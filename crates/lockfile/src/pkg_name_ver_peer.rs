@@ -1,24 +1,59 @@
-use crate::{ParsePkgNameSuffixError, ParsePkgVerPeerError, PkgNameSuffix, PkgVerPeer};
+use crate::{
+    pkg_ver_peer::split_peers, ParsePkgNameSuffixError, ParsePkgVerPeerError, PkgNameSuffix,
+    PkgVerPeer,
+};
 
 /// Syntax: `{name}@{version}({peers})`
 ///
 /// Example: `react-json-view@1.21.3(@types/react@17.0.49)(react-dom@17.0.2)(react@17.0.2)`
 ///
-/// **NOTE:** The suffix isn't guaranteed to be correct. It is only assumed to be.
+/// **NOTE:** The suffix isn't guaranteed to be correct. It is only assumed to be, unless it is
+/// parsed by [`parse_strict`](PkgNameSuffix::parse_strict).
 pub type PkgNameVerPeer = PkgNameSuffix<PkgVerPeer>;
 
 /// Error when parsing [`PkgNameVerPeer`] from a string.
 pub type ParsePkgNameVerPeerError = ParsePkgNameSuffixError<ParsePkgVerPeerError>;
 
 impl PkgNameVerPeer {
+    /// Parse like [`FromStr`](std::str::FromStr), but with the suffix parsed by
+    /// [`PkgVerPeer::parse_strict`].
+    pub fn parse_strict(value: &str) -> Result<Self, ParsePkgNameVerPeerError> {
+        let PkgNameSuffix { name, suffix } = value.parse::<PkgNameVerPeer>()?;
+        let suffix = PkgVerPeer::parse_strict(&suffix.to_string())
+            .map_err(ParsePkgNameSuffixError::ParseSuffixFailure)?;
+        Ok(PkgNameVerPeer::new(name, suffix))
+    }
+
     /// Construct the name of the corresponding subdirectory in the virtual store directory.
+    ///
+    /// The peers are sorted first, like pnpm does, so that the same set of peers always leads to
+    /// the same directory.
     pub fn to_virtual_store_name(&self) -> String {
+        let name_ver_peer =
+            format!("{}@{}{}", self.name, self.suffix.version(), sort_peers(self.suffix.peer()));
         // the code below is far from optimal,
         // optimization requires parser combinator
-        self.to_string().replace('/', "+").replace(")(", "_").replace('(', "_").replace(')', "")
+        name_ver_peer.replace('/', "+").replace(")(", "_").replace('(', "_").replace(')', "")
     }
 }
 
+/// Sort the parenthesized segments of `peer`, and the peers of each segment in turn. A malformed
+/// `peer` is left as is.
+fn sort_peers(peer: &str) -> String {
+    let Ok(peers) = split_peers(peer) else {
+        return peer.to_string();
+    };
+    let mut peers: Vec<String> = peers
+        .into_iter()
+        .map(|peer| match peer.find('(') {
+            Some(index) => format!("{}{}", &peer[..index], sort_peers(&peer[index..])),
+            None => peer.to_string(),
+        })
+        .collect();
+    peers.sort();
+    peers.iter().flat_map(|peer| ["(", peer, ")"]).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,6 +93,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_strict() {
+        let input = "ts-node@10.9.1(@types/node@18.7.19)(typescript@5.1.6)";
+        assert_eq!(PkgNameVerPeer::parse_strict(input).unwrap(), input.parse().unwrap());
+
+        let input = "ts-node@10.9.1(@types/node)(typescript@5.1.6)";
+        input.parse::<PkgNameVerPeer>().expect("lenient parse accepts it");
+        let error = PkgNameVerPeer::parse_strict(input).unwrap_err();
+        dbg!(&error);
+        assert!(matches!(
+            error,
+            ParsePkgNameSuffixError::ParseSuffixFailure(ParsePkgVerPeerError::InvalidPeer {
+                peer,
+                ..
+            }) if peer == "@types/node"
+        ));
+    }
+
     #[test]
     fn to_virtual_store_name() {
         fn case(input: &'static str, expected: &'static str) {
@@ -82,4 +135,24 @@ mod tests {
             "@babel+plugin-proposal-object-rest-spread@7.12.1_@babel+core@7.12.9",
         );
     }
+
+    #[test]
+    fn to_virtual_store_name_should_not_depend_on_peer_order() {
+        let store_name = |input: &str| {
+            let name_ver_peer: PkgNameVerPeer = input.parse().unwrap();
+            name_ver_peer.to_virtual_store_name()
+        };
+        let sorted = "ts-node@10.9.1(@types/node@18.7.19)(typescript@5.1.6)";
+        let reversed = "ts-node@10.9.1(typescript@5.1.6)(@types/node@18.7.19)";
+        assert_eq!(store_name(reversed), store_name(sorted));
+        assert_eq!(store_name(reversed), "ts-node@10.9.1_@types+node@18.7.19_typescript@5.1.6");
+        eprintln!("The original order is kept by the other representations");
+        assert_eq!(reversed.parse::<PkgNameVerPeer>().unwrap().to_string(), reversed);
+
+        eprintln!("The peers of peers are sorted too");
+        assert_eq!(
+            store_name("a@1.0.0(c@1.0.0)(b@1.0.0(e@1.0.0)(d@1.0.0))"),
+            store_name("a@1.0.0(b@1.0.0(d@1.0.0)(e@1.0.0))(c@1.0.0)"),
+        );
+    }
 }
@@ -1,14 +1,15 @@
+use crate::{ParsePkgNameVerPeerError, PkgNameVerPeer};
 use derive_more::{Display, Error};
 use node_semver::{SemverError, Version};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-/// Suffix type of [`PkgNameVerPeer`](crate::PkgNameVerPeer) and
-/// type of [`ResolvedDependencySpec::version`](crate::ResolvedDependencySpec::version).
+/// Suffix type of [`PkgNameVerPeer`](crate::PkgNameVerPeer).
 ///
 /// Example: `1.21.3(@types/react@17.0.49)(react-dom@17.0.2)(react@17.0.2)`
 ///
-/// **NOTE:** The peer part isn't guaranteed to be correct. It is only assumed to be.
+/// **NOTE:** The peer part isn't guaranteed to be correct. It is only assumed to be, unless the
+/// suffix is parsed by [`parse_strict`](Self::parse_strict).
 #[derive(Debug, Display, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[display("{version}{peer}")]
 #[serde(try_from = "&'de str", into = "String")]
@@ -33,6 +34,47 @@ impl PkgVerPeer {
         let PkgVerPeer { version, peer } = self;
         (version, peer)
     }
+
+    /// Parse like [`FromStr`], then make sure that each parenthesized segment of the peer part is
+    /// a well-formed [`PkgNameVerPeer`] whose peers are well-formed in turn.
+    pub fn parse_strict(value: &str) -> Result<Self, ParsePkgVerPeerError> {
+        let ver_peer: PkgVerPeer = value.parse()?;
+        for peer in split_peers(&ver_peer.peer)? {
+            PkgNameVerPeer::parse_strict(peer).map_err(|error| {
+                ParsePkgVerPeerError::InvalidPeer { peer: peer.to_string(), error: Box::new(error) }
+            })?;
+        }
+        Ok(ver_peer)
+    }
+}
+
+/// Split `(a@1.0.0)(b@2.0.0(c@3.0.0))` into `a@1.0.0` and `b@2.0.0(c@3.0.0)`.
+pub(crate) fn split_peers(peer: &str) -> Result<Vec<&str>, ParsePkgVerPeerError> {
+    let mut peers = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, char) in peer.char_indices() {
+        match char {
+            '(' => {
+                if depth == 0 {
+                    start = index + 1;
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or(ParsePkgVerPeerError::MismatchParenthesis)?;
+                if depth == 0 {
+                    peers.push(&peer[start..index]);
+                }
+            }
+            _ if depth == 0 => return Err(ParsePkgVerPeerError::MismatchParenthesis),
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(ParsePkgVerPeerError::MismatchParenthesis);
+    }
+    Ok(peers)
 }
 
 /// Error when parsing [`PkgVerPeer`] from a string.
@@ -42,6 +84,13 @@ pub enum ParsePkgVerPeerError {
     ParseVersionFailure(#[error(source)] SemverError),
     #[display("Mismatch parenthesis")]
     MismatchParenthesis,
+    // not `{error}`, which would make the bounds of the derived `Display` recursive
+    #[display("Invalid peer {peer:?}: {}", error.to_string())]
+    InvalidPeer {
+        peer: String,
+        #[error(source)]
+        error: Box<ParsePkgNameVerPeerError>,
+    },
 }
 
 impl FromStr for PkgVerPeer {
@@ -153,6 +202,41 @@ mod tests {
         case!("a.b.c" => "Failed to parse the version part: Failed to parse version.", ParsePkgVerPeerError::ParseVersionFailure(_));
     }
 
+    #[test]
+    fn parse_strict_ok() {
+        fn case(input: &'static str) {
+            eprintln!("CASE: {input:?}");
+            let lenient: PkgVerPeer = input.parse().unwrap();
+            assert_eq!(PkgVerPeer::parse_strict(input).unwrap(), lenient);
+        }
+
+        case("1.21.3(@types/react@17.0.49)(react-dom@17.0.2)(react@17.0.2)");
+        case("1.21.3(react@17.0.2)");
+        case("2.27.5(@typescript-eslint/parser@5.59.0(eslint@8.39.0))(eslint@8.39.0)");
+        case("1.21.3");
+    }
+
+    #[test]
+    fn parse_strict_err() {
+        macro_rules! case {
+            ($input:expr => $message:expr, $variant:pat) => {{
+                let input = $input;
+                eprintln!("CASE: {input:?}");
+                input.parse::<PkgVerPeer>().expect("lenient parse accepts it");
+                let error = PkgVerPeer::parse_strict(input).unwrap_err();
+                dbg!(&error);
+                assert_eq!(error.to_string(), $message);
+                assert!(matches!(error, $variant));
+            }};
+        }
+        case!("1.21.3(react@17.0.2))(react-dom@17.0.2)" => "Mismatch parenthesis", ParsePkgVerPeerError::MismatchParenthesis);
+        case!("1.21.3((react@17.0.2)" => "Mismatch parenthesis", ParsePkgVerPeerError::MismatchParenthesis);
+        case!("1.21.3(react@17.0.2)react-dom(react-dom@17.0.2)" => "Mismatch parenthesis", ParsePkgVerPeerError::MismatchParenthesis);
+        case!("1.21.3(react)" => r#"Invalid peer "react": Suffix is missing"#, ParsePkgVerPeerError::InvalidPeer { .. });
+        case!("1.21.3(react@latest)" => r#"Invalid peer "react@latest": Failed to parse suffix: Failed to parse the version part: Failed to parse version."#, ParsePkgVerPeerError::InvalidPeer { .. });
+        case!("1.21.3(eslint-plugin@1.0.0(eslint))" => r#"Invalid peer "eslint-plugin@1.0.0(eslint)": Failed to parse suffix: Invalid peer "eslint": Suffix is missing"#, ParsePkgVerPeerError::InvalidPeer { .. });
+    }
+
     #[test]
     fn deserialize_ok() {
         fn case<Ver, Peer>(input: &'static str, (expected_version, expected_peer): (Ver, Peer))
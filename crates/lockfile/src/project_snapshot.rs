@@ -7,13 +7,25 @@ use std::collections::HashMap;
 #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectSnapshot {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub specifiers: Option<HashMap<String, String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub dependencies: Option<ResolvedDependencyMap>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub optional_dependencies: Option<ResolvedDependencyMap>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::sorted_map::serialize_option"
+    )]
     pub dev_dependencies: Option<ResolvedDependencyMap>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dependencies_meta: Option<serde_yaml::Value>, // TODO: DependenciesMeta
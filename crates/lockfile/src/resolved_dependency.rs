@@ -1,4 +1,4 @@
-use crate::{PkgName, PkgVerPeer};
+use crate::{PackageSnapshotDependency, PkgName};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -12,5 +12,5 @@ pub type ResolvedDependencyMap = HashMap<PkgName, ResolvedDependencySpec>;
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct ResolvedDependencySpec {
     pub specifier: String,
-    pub version: PkgVerPeer,
+    pub version: PackageSnapshotDependency,
 }
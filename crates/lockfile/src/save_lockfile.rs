@@ -0,0 +1,77 @@
+use crate::Lockfile;
+use derive_more::{Display, Error};
+use pacquet_diagnostics::miette::{self, Diagnostic};
+use std::{fs, io, path::Path};
+
+/// Error when writing lockfile to the filesystem.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum SaveLockfileError {
+    #[display("Failed to serialize lockfile content as YAML: {_0}")]
+    #[diagnostic(code(pacquet_lockfile::serialize_yaml))]
+    SerializeYaml(serde_yaml::Error),
+
+    #[display("Failed to write lockfile content: {_0}")]
+    #[diagnostic(code(pacquet_lockfile::write_file))]
+    WriteFile(io::Error),
+}
+
+impl Lockfile {
+    /// Write the lockfile into `dir`, replacing the existing one.
+    pub fn save_to_dir(&self, dir: &Path) -> Result<(), SaveLockfileError> {
+        let content = serde_yaml::to_string(self).map_err(SaveLockfileError::SerializeYaml)?;
+        fs::write(dir.join(Lockfile::FILE_NAME), content).map_err(SaveLockfileError::WriteFile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ComVer, LockfileVersion, PkgVerPeer, ProjectSnapshot, ResolvedDependencySpec,
+        RootProjectSnapshot,
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+    use text_block_macros::text_block;
+
+    #[test]
+    fn save_to_dir() {
+        let dir = tempdir().unwrap();
+        let react = ResolvedDependencySpec {
+            specifier: "^17.0.2".to_string(),
+            version: "17.0.2".parse::<PkgVerPeer>().unwrap().into(),
+        };
+        let project_snapshot = ProjectSnapshot {
+            dependencies: Some([("react".parse().unwrap(), react)].into()),
+            ..Default::default()
+        };
+        let packages = [(
+            "/react@17.0.2".parse().unwrap(),
+            serde_yaml::from_str("resolution: {integrity: sha512-gnhPt75i/dq/z3/6q/0asP78D0u592D5L1pd7M8P+dck6Fu/jJeL6iVVK23fptSUZj8Vjf++7wXA8UNclGQcbA==}").unwrap(),
+        )];
+        let lockfile =
+            Lockfile::new(RootProjectSnapshot::Single(project_snapshot), packages.into());
+        assert_eq!(
+            lockfile.lockfile_version,
+            LockfileVersion::try_from(ComVer::new(6, 0)).unwrap()
+        );
+
+        lockfile.save_to_dir(dir.path()).unwrap();
+        let content = fs::read_to_string(dir.path().join("pnpm-lock.yaml")).unwrap();
+        eprintln!("CONTENT:\n{content}");
+        let expected = text_block! {
+            "lockfileVersion: '6.0'"
+            "dependencies:"
+            "  react:"
+            "    specifier: ^17.0.2"
+            "    version: 17.0.2"
+            "packages:"
+            "  /react@17.0.2:"
+            "    resolution:"
+            "      integrity: sha512-gnhPt75i/dq/z3/6q/0asP78D0u592D5L1pd7M8P+dck6Fu/jJeL6iVVK23fptSUZj8Vjf++7wXA8UNclGQcbA=="
+        };
+        assert_eq!(content.trim_end(), expected);
+        assert_eq!(Lockfile::from_yaml_str(&content).unwrap(), Some(lockfile));
+    }
+}
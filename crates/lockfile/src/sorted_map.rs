@@ -0,0 +1,84 @@
+//! Serialize the maps of the lockfile with their keys in order.
+//!
+//! The maps are [`HashMap`]s, whose iteration order differs from one map to another even with the
+//! same entries. Sorting the keys keeps `pnpm-lock.yaml` identical across runs and machines, so
+//! that its diffs only show actual changes.
+
+use serde::{Serialize, Serializer};
+use std::{collections::HashMap, fmt::Display};
+
+/// Serialize `map` with its entries sorted by their keys as strings.
+pub(crate) fn serialize<S, Key, Value>(
+    map: &HashMap<Key, Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    Key: Display + Serialize,
+    Value: Serialize,
+{
+    let mut entries: Vec<_> = map.iter().map(|entry| (entry.0.to_string(), entry)).collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    serializer.collect_map(entries.into_iter().map(|(_, entry)| entry))
+}
+
+/// Like [`serialize`], for the maps that can be absent.
+pub(crate) fn serialize_option<S, Key, Value>(
+    map: &Option<HashMap<Key, Value>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    Key: Display + Serialize,
+    Value: Serialize,
+{
+    match map {
+        Some(map) => serialize(map, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ComVer, DependencyPath, Lockfile, ProjectSnapshot, RootProjectSnapshot};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn lockfile(names: impl Iterator<Item = usize>) -> Lockfile {
+        let mut specifiers = HashMap::new();
+        let mut packages = HashMap::new();
+        for index in names {
+            let name = format!("package-{index:02}");
+            specifiers.insert(name.clone(), "^1.0.0".to_string());
+            let dependency_path: DependencyPath = format!("/{name}@1.0.0").parse().unwrap();
+            let snapshot =
+                "resolution: {integrity: sha512-aaaa}\npeerDependencies: {a: '1', b: '1', c: '1'}";
+            packages.insert(dependency_path, serde_yaml::from_str(snapshot).unwrap());
+        }
+        Lockfile {
+            lockfile_version: ComVer::new(6, 0).try_into().unwrap(),
+            settings: None,
+            never_built_dependencies: None,
+            overrides: None,
+            project_snapshot: RootProjectSnapshot::Single(ProjectSnapshot {
+                specifiers: Some(specifiers),
+                ..Default::default()
+            }),
+            packages: Some(packages),
+        }
+    }
+
+    #[test]
+    fn should_serialize_regardless_of_insertion_order() {
+        let forward = serde_yaml::to_string(&lockfile(0..20)).unwrap();
+        let backward = serde_yaml::to_string(&lockfile((0..20).rev())).unwrap();
+        assert_eq!(forward, backward);
+
+        let package_lines: Vec<_> =
+            forward.lines().filter(|line| line.starts_with("  /package-")).collect();
+        let mut sorted = package_lines.clone();
+        sorted.sort();
+        assert_eq!(package_lines.len(), 20);
+        assert_eq!(package_lines, sorted);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+/// Values of the `Authorization` header of each registry, keyed by the "nerf darted" URL of the
+/// registry (`//{host}/{path}/`, the same format as the keys of `.npmrc`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AuthHeaders {
+    by_uri: HashMap<String, String>,
+    always_auth: HashSet<String>,
+}
+
+impl AuthHeaders {
+    /// Create [`AuthHeaders`] from `_authToken` settings.
+    ///
+    /// Each item is a pair of a registry URI (e.g. `//registry.example.com/`) and a token.
+    pub fn from_tokens<'a, Tokens>(tokens: Tokens) -> Self
+    where
+        Tokens: IntoIterator<Item = (&'a String, &'a String)>,
+    {
+        let by_uri = tokens
+            .into_iter()
+            .map(|(uri, token)| (nerf_dart(uri), format!("Bearer {token}")))
+            .collect();
+        AuthHeaders { by_uri, always_auth: HashSet::new() }
+    }
+
+    /// Mark the registries at `uris` as `always-auth`: their credentials are also sent with
+    /// requests made on their behalf to other hosts (see [`AuthHeaders::for_request`]).
+    pub fn with_always_auth<'a, Uris>(mut self, uris: Uris) -> Self
+    where
+        Uris: IntoIterator<Item = &'a String>,
+    {
+        self.always_auth.extend(uris.into_iter().map(|uri| nerf_dart(uri)));
+        self
+    }
+
+    /// Whether there are no credentials at all.
+    pub fn is_empty(&self) -> bool {
+        self.by_uri.is_empty()
+    }
+
+    /// Find the `Authorization` header for `url`.
+    ///
+    /// The registry with the longest URI that prefixes `url` wins. URLs on other hosts (e.g. a
+    /// public CDN serving the tarballs) get `None`.
+    pub fn for_url(&self, url: &str) -> Option<&str> {
+        self.find(url).map(|(_, header)| header)
+    }
+
+    /// Find the `Authorization` header for `url` requested on behalf of `registry`.
+    ///
+    /// Same as [`AuthHeaders::for_url`], except that a URL on another host gets the credentials
+    /// of `registry` when that registry is `always-auth`.
+    pub fn for_request(&self, registry: &str, url: &str) -> Option<&str> {
+        self.for_url(url).or_else(|| {
+            let (uri, header) = self.find(registry)?;
+            self.always_auth.contains(uri).then_some(header)
+        })
+    }
+
+    /// Find the URI and the header of the registry with the longest URI that prefixes `url`.
+    fn find(&self, url: &str) -> Option<(&str, &str)> {
+        let url = nerf_dart(url);
+        self.by_uri
+            .iter()
+            .filter(|(uri, _)| url.starts_with(uri.as_str()))
+            .max_by_key(|(uri, _)| uri.len())
+            .map(|(uri, header)| (uri.as_str(), header.as_str()))
+    }
+}
+
+/// Remove the scheme, the query, and the fragment of a URL, and add a trailing `/` to a URL that
+/// ends with a directory.
+fn nerf_dart(url: &str) -> String {
+    let url = url.split_once("//").map_or(url, |(_, rest)| rest);
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    // only a segment of the path can name a file: a bare host like `npm.pkg.github.com` has dots
+    // too, but must get its trailing `/`, or its token would prefix `npm.pkg.github.com.evil.io`
+    let is_file = url
+        .split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .is_some_and(|last| last.contains('.'));
+    if url.ends_with('/') || is_file {
+        format!("//{url}")
+    } else {
+        format!("//{url}/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn auth_headers(tokens: &[(&str, &str)]) -> AuthHeaders {
+        let tokens: Vec<(String, String)> =
+            tokens.iter().map(|(uri, token)| (uri.to_string(), token.to_string())).collect();
+        AuthHeaders::from_tokens(tokens.iter().map(|(uri, token)| (uri, token)))
+    }
+
+    #[test]
+    fn for_url() {
+        let headers = auth_headers(&[
+            ("//registry.example.com/", "root-token"),
+            ("//registry.example.com/private", "private-token"),
+            ("//localhost:4873/", "local-token"),
+        ]);
+
+        let case = |url: &str, expected: Option<&str>| {
+            eprintln!("CASE: {url:?} => {expected:?}");
+            assert_eq!(headers.for_url(url), expected);
+        };
+
+        case("https://registry.example.com", Some("Bearer root-token"));
+        case("https://registry.example.com/foo", Some("Bearer root-token"));
+        case("https://registry.example.com/foo/-/foo-1.0.0.tgz", Some("Bearer root-token"));
+        case("https://registry.example.com/private/foo", Some("Bearer private-token"));
+        case("https://registry.example.com/private", Some("Bearer private-token"));
+        case("https://registry.example.com/privateer/foo", Some("Bearer root-token"));
+        case("http://localhost:4873/foo?write=true", Some("Bearer local-token"));
+        case("http://localhost:4874/foo", None);
+        case("https://cdn.example.com/foo/-/foo-1.0.0.tgz", None);
+    }
+
+    #[test]
+    fn host_only_uri_should_not_match_lookalike_hosts() {
+        let headers = auth_headers(&[("//npm.pkg.github.com", "github-token")]);
+
+        let case = |url: &str, expected: Option<&str>| {
+            eprintln!("CASE: {url:?} => {expected:?}");
+            assert_eq!(headers.for_url(url), expected);
+        };
+
+        case("https://npm.pkg.github.com/foo", Some("Bearer github-token"));
+        case("https://npm.pkg.github.com", Some("Bearer github-token"));
+        case("https://npm.pkg.github.com.evil.io/foo", None);
+        case("https://npm.pkg.github.com.evil.io", None);
+    }
+
+    #[test]
+    fn for_request() {
+        let always_auth = ["https://registry.example.com".to_string()];
+        let headers = auth_headers(&[
+            ("//registry.example.com/", "root-token"),
+            ("//npm.private.com/", "private-token"),
+        ])
+        .with_always_auth(&always_auth);
+
+        let case = |registry: &str, url: &str, expected: Option<&str>| {
+            eprintln!("CASE: {registry:?}, {url:?} => {expected:?}");
+            assert_eq!(headers.for_request(registry, url), expected);
+        };
+
+        let cdn_url = "https://cdn.example.com/foo/-/foo-1.0.0.tgz";
+        case("https://registry.example.com/", cdn_url, Some("Bearer root-token"));
+        case("https://npm.private.com/", cdn_url, None);
+        case(
+            "https://npm.private.com/",
+            "https://npm.private.com/foo",
+            Some("Bearer private-token"),
+        );
+        case(
+            "https://registry.example.com/",
+            "https://npm.private.com/foo",
+            Some("Bearer private-token"),
+        );
+        case("https://unknown.com/", cdn_url, None);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::ThrottledClient;
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use std::time::Duration;
+
+/// Error type of [`ThrottledClient::check_registry`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("The registry {registry} is unreachable: {error}")]
+#[diagnostic(
+    code(pacquet_network::registry_unreachable),
+    help("Check the network connection and the `registry` setting of .npmrc")
+)]
+pub struct RegistryUnreachableError {
+    pub registry: String,
+    #[error(source)]
+    pub error: reqwest::Error,
+}
+
+impl ThrottledClient {
+    /// Send a `HEAD` request to the root of `registry` and fail if no response comes within
+    /// `timeout`.
+    ///
+    /// Any response counts, whatever its status, as some registries don't serve their root. Only
+    /// the failures to connect or to get an answer in time tell the registry is unreachable.
+    ///
+    /// This mirrors `MockInstanceOptions::is_registry_ready` of the registry mock, which can't be
+    /// reused here as it lives in a test task and panics on errors other than connection failures,
+    /// whereas the check reports them all, timeouts included.
+    pub async fn check_registry(
+        &self,
+        registry: &str,
+        timeout: Duration,
+    ) -> Result<(), RegistryUnreachableError> {
+        self.run_with_permit(|client| {
+            self.authorize(registry, client.head(registry)).timeout(timeout).send()
+        })
+        .await
+        .map(drop)
+        .map_err(|error| RegistryUnreachableError { registry: registry.to_string(), error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+        time::Instant,
+    };
+
+    #[tokio::test]
+    async fn check_registry_should_fail_fast_on_dead_registry() {
+        // bind then drop a listener to find a port that nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let registry = format!("http://127.0.0.1:{port}/");
+        let start = Instant::now();
+        let error = ThrottledClient::default()
+            .check_registry(&registry, Duration::from_secs(10))
+            .await
+            .unwrap_err();
+        eprintln!("ERROR: {error}");
+        assert_eq!(error.registry, registry);
+        assert!(error.to_string().starts_with(&format!("The registry {registry} is unreachable")));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn check_registry_should_accept_any_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let registry = format!("http://{}/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").unwrap();
+        });
+        ThrottledClient::default()
+            .check_registry(&registry, Duration::from_secs(10))
+            .await
+            .expect("a registry that answers is reachable");
+        server.join().unwrap();
+    }
+}
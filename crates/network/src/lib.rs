@@ -1,25 +1,94 @@
+mod auth_headers;
+mod check_registry;
+mod network_activity;
+
+pub use auth_headers::*;
+pub use check_registry::*;
+pub use network_activity::*;
+
 use pipe_trait::Pipe;
-use reqwest::Client;
-use std::future::IntoFuture;
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    redirect, Client, RequestBuilder,
+};
+use std::{env::consts, future::IntoFuture};
 use tokio::sync::Semaphore;
 
+/// How many redirects a request follows before it fails.
+pub const MAX_REDIRECTS: usize = 10;
+
+/// The `user-agent` header that requests are sent with unless the `user-agent` setting overrides
+/// it, e.g. `pacquet/0.0.1 (linux/x86_64)`.
+pub fn default_user_agent() -> String {
+    format!("pacquet/{} ({}/{})", env!("CARGO_PKG_VERSION"), consts::OS, consts::ARCH)
+}
+
+/// Build the underlying [`Client`] that sends `user_agent` with every request.
+///
+/// When `decompress` is set, the client accepts gzip and brotli encoded responses and decodes
+/// them according to their `content-encoding`.
+fn build_client(user_agent: HeaderValue, decompress: bool) -> Client {
+    // reqwest removes the sensitive headers of cross-host redirects regardless of the policy
+    Client::builder()
+        .redirect(redirect::Policy::limited(MAX_REDIRECTS))
+        .user_agent(user_agent)
+        .gzip(decompress)
+        .brotli(decompress)
+        .build()
+        .expect("build the HTTP client")
+}
+
 /// Wrapper around [`Client`] with concurrent request limit enforced by the [`Semaphore`] mechanism.
+///
+/// Redirects are followed up to [`MAX_REDIRECTS`] times. Like npm, the `Authorization` header is
+/// dropped when a redirect leads to another host or port, so that the credentials of a registry
+/// aren't sent to the CDN that serves its tarballs. Every request carries the
+/// [`default_user_agent`] unless [`ThrottledClient::with_user_agent`] replaced it.
+///
+/// Metadata is requested compressed, see [`ThrottledClient::run_metadata_with_permit`]. Tarballs
+/// are requested without `accept-encoding`: they are gzip archives already, which are
+/// decompressed when they are extracted.
 #[derive(Debug)]
 pub struct ThrottledClient {
     semaphore: Semaphore,
     client: Client,
+    metadata_client: Client,
+    auth_headers: AuthHeaders,
+    activity: NetworkActivity,
 }
 
 impl ThrottledClient {
     /// Acquire a permit and run `proc` with the underlying [`Client`].
     pub async fn run_with_permit<Proc, ProcFuture>(&self, proc: Proc) -> ProcFuture::Output
+    where
+        Proc: FnOnce(&Client) -> ProcFuture,
+        ProcFuture: IntoFuture,
+    {
+        self.run_client_with_permit(&self.client, proc).await
+    }
+
+    /// Like [`ThrottledClient::run_with_permit`], but the [`Client`] asks for gzip or brotli
+    /// encoded responses and decodes their bodies.
+    pub async fn run_metadata_with_permit<Proc, ProcFuture>(&self, proc: Proc) -> ProcFuture::Output
+    where
+        Proc: FnOnce(&Client) -> ProcFuture,
+        ProcFuture: IntoFuture,
+    {
+        self.run_client_with_permit(&self.metadata_client, proc).await
+    }
+
+    async fn run_client_with_permit<Proc, ProcFuture>(
+        &self,
+        client: &Client,
+        proc: Proc,
+    ) -> ProcFuture::Output
     where
         Proc: FnOnce(&Client) -> ProcFuture,
         ProcFuture: IntoFuture,
     {
         let permit =
             self.semaphore.acquire().await.expect("semaphore shouldn't have been closed this soon");
-        let result = proc(&self.client).await;
+        let result = proc(client).await;
         drop(permit);
         result
     }
@@ -30,8 +99,59 @@ impl ThrottledClient {
     pub fn new_from_cpu_count() -> Self {
         const MIN_PERMITS: usize = 16;
         let semaphore = num_cpus::get().max(MIN_PERMITS).pipe(Semaphore::new);
-        let client = Client::new();
-        ThrottledClient { semaphore, client }
+        let user_agent =
+            HeaderValue::from_str(&default_user_agent()).expect("valid default user agent");
+        let client = build_client(user_agent.clone(), false);
+        let metadata_client = build_client(user_agent, true);
+        ThrottledClient {
+            semaphore,
+            client,
+            metadata_client,
+            auth_headers: AuthHeaders::default(),
+            activity: NetworkActivity::default(),
+        }
+    }
+
+    /// Requests in flight made through this client, see [`NetworkActivity::start`].
+    pub fn activity(&self) -> &NetworkActivity {
+        &self.activity
+    }
+
+    /// Send the credentials of the matching registry along with every request made by
+    /// [`ThrottledClient::authorize`].
+    pub fn with_auth_headers(self, auth_headers: AuthHeaders) -> Self {
+        ThrottledClient { auth_headers, ..self }
+    }
+
+    /// Send `user_agent` as the `user-agent` header of every request, metadata and tarballs alike.
+    pub fn with_user_agent(self, user_agent: HeaderValue) -> Self {
+        ThrottledClient {
+            client: build_client(user_agent.clone(), false),
+            metadata_client: build_client(user_agent, true),
+            ..self
+        }
+    }
+
+    /// Add the `Authorization` header of the registry `url` belongs to, if there is one.
+    pub fn authorize(&self, url: &str, request: RequestBuilder) -> RequestBuilder {
+        match self.auth_headers.for_url(url) {
+            Some(header) => request.header(AUTHORIZATION, header),
+            None => request,
+        }
+    }
+
+    /// Add the `Authorization` header for `url` requested on behalf of `registry`, see
+    /// [`AuthHeaders::for_request`].
+    pub fn authorize_for(
+        &self,
+        registry: &str,
+        url: &str,
+        request: RequestBuilder,
+    ) -> RequestBuilder {
+        match self.auth_headers.for_request(registry, url) {
+            Some(header) => request.header(AUTHORIZATION, header),
+            None => request,
+        }
     }
 }
 
@@ -0,0 +1,160 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use reqwest::Response;
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Error type of [`NetworkActivity::stalled`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display(
+    "No network progress was made for {} ms, requests in flight: {}",
+    timeout.as_millis(),
+    in_flight.join(", ")
+)]
+#[diagnostic(
+    code(pacquet_network::network_timeout),
+    help("Check the network connection, or raise `network-timeout`")
+)]
+pub struct NetworkTimeoutError {
+    pub timeout: Duration,
+    /// URLs of the requests that were in flight, in the order that they started.
+    pub in_flight: Vec<String>,
+}
+
+/// Requests in flight and the last time that the network made progress.
+///
+/// Starting a request, receiving a chunk of a response body, and completing a request all count as
+/// progress.
+#[derive(Debug)]
+pub struct NetworkActivity {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    in_flight: BTreeMap<u64, String>,
+    next_id: u64,
+    last_progress: Instant,
+}
+
+impl Default for NetworkActivity {
+    fn default() -> Self {
+        let state = State { in_flight: BTreeMap::new(), next_id: 0, last_progress: Instant::now() };
+        NetworkActivity { state: Mutex::new(state) }
+    }
+}
+
+impl NetworkActivity {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("lock the state of the network activity")
+    }
+
+    /// Record a request to `url` as in flight until the returned guard is dropped.
+    pub fn start(&self, url: impl Into<String>) -> InFlightRequest<'_> {
+        let mut state = self.state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.in_flight.insert(id, url.into());
+        state.last_progress = Instant::now();
+        InFlightRequest { activity: self, id }
+    }
+
+    /// Record progress of a request in flight.
+    fn progress(&self) {
+        self.state().last_progress = Instant::now();
+    }
+
+    /// URLs of the requests in flight, in the order that they started.
+    pub fn in_flight(&self) -> Vec<String> {
+        self.state().in_flight.values().cloned().collect()
+    }
+
+    /// Resolve once requests have been in flight for `timeout` without any progress.
+    ///
+    /// Time spent without any request in flight, e.g. while extracting or linking packages,
+    /// doesn't count.
+    pub async fn stalled(&self, timeout: Duration) -> NetworkTimeoutError {
+        loop {
+            let wait = {
+                let mut state = self.state();
+                if state.in_flight.is_empty() {
+                    // the window starts over with the next request
+                    state.last_progress = Instant::now();
+                    timeout
+                } else {
+                    let idle = state.last_progress.elapsed();
+                    if idle >= timeout {
+                        let in_flight = state.in_flight.values().cloned().collect();
+                        return NetworkTimeoutError { timeout, in_flight };
+                    }
+                    timeout - idle
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Guard of a request in flight, see [`NetworkActivity::start`].
+#[must_use]
+#[derive(Debug)]
+pub struct InFlightRequest<'a> {
+    activity: &'a NetworkActivity,
+    id: u64,
+}
+
+impl<'a> InFlightRequest<'a> {
+    /// Read the whole body of `response`, recording every chunk as progress.
+    pub async fn bytes(&self, mut response: Response) -> reqwest::Result<Vec<u8>> {
+        let capacity = response.content_length().unwrap_or(0) as usize;
+        let mut body = Vec::with_capacity(capacity);
+        while let Some(chunk) = response.chunk().await? {
+            self.activity.progress();
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+impl<'a> Drop for InFlightRequest<'a> {
+    fn drop(&mut self) {
+        let mut state = self.activity.state();
+        state.in_flight.remove(&self.id);
+        state.last_progress = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn should_report_stalled_requests() {
+        let activity = NetworkActivity::default();
+        let timeout = Duration::from_millis(50);
+
+        let first = activity.start("https://example.com/foo");
+        let second = activity.start("https://example.com/bar");
+        drop(first);
+        assert_eq!(activity.in_flight(), ["https://example.com/bar"]);
+
+        let started = Instant::now();
+        let error = activity.stalled(timeout).await;
+        assert!(started.elapsed() >= timeout);
+        assert_eq!(error.in_flight, ["https://example.com/bar"]);
+        drop(second);
+        assert!(activity.in_flight().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_not_time_out_without_requests_in_flight() {
+        let activity = NetworkActivity::default();
+        let timeout = Duration::from_millis(20);
+        let result = tokio::time::timeout(timeout * 5, activity.stalled(timeout)).await;
+        assert!(result.is_err(), "stalled() should still be pending");
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Npmrc, SaveWorkspaceProtocol};
+use pacquet_store_dir::{StoreDir, StoreLayout};
+use std::path::PathBuf;
+
+/// Builder of [`Npmrc`], created by [`Npmrc::builder`].
+///
+/// Every setting that isn't set explicitly has the same default as an empty `.npmrc`, which
+/// matches pnpm except for [`lockfile`](Npmrc::lockfile).
+#[must_use]
+#[derive(Debug, Clone, Default)]
+pub struct NpmrcBuilder {
+    config: Npmrc,
+}
+
+macro_rules! setters {
+    ($($(#[$attr:meta])* $name:ident: $ty:ty;)*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(mut self, $name: $ty) -> Self {
+                self.config.$name = $name;
+                self
+            }
+        )*
+    };
+}
+
+impl NpmrcBuilder {
+    /// Set [`store_dir`](Npmrc::store_dir).
+    pub fn store_dir(mut self, store_dir: impl Into<PathBuf>) -> Self {
+        self.config.store_dir = StoreDir::new(store_dir);
+        self
+    }
+
+    /// Set [`cache_dir`](Npmrc::cache_dir).
+    pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.config.cache_dir = cache_dir.into();
+        self
+    }
+
+    /// Set [`global_dir`](Npmrc::global_dir).
+    pub fn global_dir(mut self, global_dir: impl Into<PathBuf>) -> Self {
+        self.config.global_dir = global_dir.into();
+        self
+    }
+
+    /// Set [`global_bin_dir`](Npmrc::global_bin_dir).
+    pub fn global_bin_dir(mut self, global_bin_dir: impl Into<PathBuf>) -> Self {
+        self.config.global_bin_dir = global_bin_dir.into();
+        self
+    }
+
+    /// Set [`modules_dir`](Npmrc::modules_dir).
+    pub fn modules_dir(mut self, modules_dir: impl Into<PathBuf>) -> Self {
+        self.config.modules_dir = modules_dir.into();
+        self
+    }
+
+    /// Set [`virtual_store_dir`](Npmrc::virtual_store_dir).
+    pub fn virtual_store_dir(mut self, virtual_store_dir: impl Into<PathBuf>) -> Self {
+        self.config.virtual_store_dir = virtual_store_dir.into();
+        self
+    }
+
+    /// Set [`registry`](Npmrc::registry). A trailing slash is appended if missing.
+    pub fn registry(mut self, registry: impl Into<String>) -> Self {
+        let mut registry = registry.into();
+        if !registry.ends_with('/') {
+            registry.push('/');
+        }
+        self.config.registry = registry;
+        self
+    }
+
+    setters! {
+        /// Set [`hoist`](Npmrc::hoist).
+        hoist: bool;
+        /// Set [`hoist_pattern`](Npmrc::hoist_pattern).
+        hoist_pattern: Vec<String>;
+        /// Set [`public_hoist_pattern`](Npmrc::public_hoist_pattern).
+        public_hoist_pattern: Vec<String>;
+        /// Set [`symlink`](Npmrc::symlink).
+        symlink: bool;
+        /// Set [`modules_cache_max_age`](Npmrc::modules_cache_max_age).
+        modules_cache_max_age: u64;
+        /// Set [`lockfile`](Npmrc::lockfile).
+        lockfile: bool;
+        /// Set [`prefer_frozen_lockfile`](Npmrc::prefer_frozen_lockfile).
+        prefer_frozen_lockfile: bool;
+        /// Set [`prefer_offline`](Npmrc::prefer_offline).
+        prefer_offline: bool;
+        /// Set [`verify_store_integrity`](Npmrc::verify_store_integrity).
+        verify_store_integrity: bool;
+        /// Set [`force`](Npmrc::force).
+        force: bool;
+        /// Set [`network_timeout`](Npmrc::network_timeout).
+        network_timeout: Option<u64>;
+        /// Set [`auto_install_peers`](Npmrc::auto_install_peers).
+        auto_install_peers: bool;
+        /// Set [`dedupe_peer_dependents`](Npmrc::dedupe_peer_dependents).
+        dedupe_peer_dependents: bool;
+        /// Set [`strict_peer_dependencies`](Npmrc::strict_peer_dependencies).
+        strict_peer_dependencies: bool;
+        /// Set [`resolve_peers_from_workspace_root`](Npmrc::resolve_peers_from_workspace_root).
+        resolve_peers_from_workspace_root: bool;
+        /// Set [`child_concurrency`](Npmrc::child_concurrency).
+        child_concurrency: usize;
+        /// Set [`use_node_version`](Npmrc::use_node_version).
+        use_node_version: Option<String>;
+        /// Set [`before`](Npmrc::before).
+        before: Option<String>;
+        /// Set [`ignore_pnpmfile`](Npmrc::ignore_pnpmfile).
+        ignore_pnpmfile: bool;
+        /// Set [`save_workspace_protocol`](Npmrc::save_workspace_protocol).
+        save_workspace_protocol: SaveWorkspaceProtocol;
+        /// Set [`store_layout`](Npmrc::store_layout).
+        store_layout: StoreLayout;
+        /// Set [`user_agent`](Npmrc::user_agent).
+        user_agent: Option<String>;
+    }
+
+    /// Finish building.
+    pub fn build(self) -> Npmrc {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeLinker, PackageImportMethod};
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    #[test]
+    fn should_have_pnpm_defaults() {
+        let config = Npmrc::builder().build();
+        let current_dir = env::current_dir().unwrap();
+        assert!(config.hoist);
+        assert_eq!(config.hoist_pattern, ["*"]);
+        assert_eq!(config.public_hoist_pattern, ["*eslint*", "*prettier*"]);
+        assert!(!config.shamefully_hoist);
+        assert_eq!(config.modules_dir, current_dir.join("node_modules"));
+        assert_eq!(config.node_linker, NodeLinker::Isolated);
+        assert!(config.symlink);
+        assert_eq!(config.virtual_store_dir, current_dir.join("node_modules/.pnpm"));
+        assert_eq!(config.package_import_method, PackageImportMethod::Auto);
+        assert_eq!(config.modules_cache_max_age, 10080);
+        assert!(config.prefer_frozen_lockfile);
+        assert!(!config.lockfile_include_tarball_url);
+        assert_eq!(config.registry, "https://registry.npmjs.org/");
+        assert!(config.auto_install_peers);
+        assert!(config.dedupe_peer_dependents);
+        assert!(!config.strict_peer_dependencies);
+        assert!(config.resolve_peers_from_workspace_root);
+    }
+
+    #[test]
+    fn should_override_defaults() {
+        let config = Npmrc::builder()
+            .store_dir("/store")
+            .virtual_store_dir("/project/node_modules/.pnpm")
+            .registry("https://registry.example.com")
+            .hoist(false)
+            .auto_install_peers(false)
+            .build();
+        assert_eq!(config.store_dir, StoreDir::new("/store"));
+        assert_eq!(config.virtual_store_dir, PathBuf::from("/project/node_modules/.pnpm"));
+        assert_eq!(config.registry, "https://registry.example.com/");
+        assert!(!config.hoist);
+        assert!(!config.auto_install_peers);
+        assert!(config.symlink);
+    }
+}
@@ -1,6 +1,11 @@
 use pacquet_store_dir::StoreDir;
 use serde::{de, Deserialize, Deserializer};
-use std::{env, path::PathBuf, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::PathBuf,
+    str::FromStr,
+};
 
 #[cfg(windows)]
 use std::{path::Component, path::Path};
@@ -79,6 +84,57 @@ pub fn default_store_dir() -> StoreDir {
     }
 }
 
+/// The directory of the global packages and their bins, like `PNPM_HOME` of pnpm.
+///
+/// If the $PNPM_HOME env variable is set, then $PNPM_HOME
+/// If the $XDG_DATA_HOME env variable is set, then $XDG_DATA_HOME/pnpm
+/// On Windows: ~/AppData/Local/pnpm
+/// On macOS: ~/Library/pnpm
+/// On Linux: ~/.local/share/pnpm
+fn default_pnpm_home() -> PathBuf {
+    if let Ok(pnpm_home) = env::var("PNPM_HOME") {
+        return PathBuf::from(pnpm_home);
+    }
+
+    if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
+        return PathBuf::from(xdg_data_home).join("pnpm");
+    }
+
+    let home_dir = home::home_dir().expect("Home directory is not available");
+
+    match env::consts::OS {
+        "windows" => home_dir.join("AppData/Local/pnpm"),
+        "macos" => home_dir.join("Library/pnpm"),
+        _ => home_dir.join(".local/share/pnpm"),
+    }
+}
+
+pub fn default_global_dir() -> PathBuf {
+    default_pnpm_home().join("global")
+}
+
+pub fn default_global_bin_dir() -> PathBuf {
+    default_pnpm_home()
+}
+
+/// If the $XDG_CACHE_HOME env variable is set, then $XDG_CACHE_HOME/pnpm
+/// On Windows: ~/AppData/Local/pnpm-cache
+/// On macOS: ~/Library/Caches/pnpm
+/// On Linux: ~/.cache/pnpm
+pub fn default_cache_dir() -> PathBuf {
+    if let Ok(xdg_cache_home) = env::var("XDG_CACHE_HOME") {
+        return PathBuf::from(xdg_cache_home).join("pnpm");
+    }
+
+    let home_dir = home::home_dir().expect("Home directory is not available");
+
+    match env::consts::OS {
+        "windows" => home_dir.join("AppData/Local/pnpm-cache"),
+        "macos" => home_dir.join("Library/Caches/pnpm"),
+        _ => home_dir.join(".cache/pnpm"),
+    }
+}
+
 pub fn default_modules_dir() -> PathBuf {
     // TODO: find directory with package.json
     env::current_dir().expect("current directory is unavailable").join("node_modules")
@@ -97,6 +153,10 @@ pub fn default_modules_cache_max_age() -> u64 {
     10080
 }
 
+pub fn default_child_concurrency() -> usize {
+    5
+}
+
 pub fn deserialize_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: Deserializer<'de>,
@@ -105,6 +165,43 @@ where
     bool::from_str(&s).map_err(de::Error::custom)
 }
 
+/// Split a comma separated list of patterns, e.g. `*,!foo`.
+pub fn deserialize_patterns<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(s.split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Deserialize a comma-separated list of `from-host->to-host` into a map from host to host.
+pub fn deserialize_host_replacements<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (from, to) = entry
+                .split_once("->")
+                .map(|(from, to)| (from.trim(), to.trim()))
+                .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+                .ok_or_else(|| {
+                    de::Error::custom(format!("expected `from-host->to-host`, found {entry:?}"))
+                })?;
+            Ok((from.to_string(), to.to_string()))
+        })
+        .collect()
+}
+
 pub fn deserialize_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: Deserializer<'de>,
@@ -113,6 +210,21 @@ where
     u64::from_str(&s).map_err(de::Error::custom)
 }
 
+pub fn deserialize_optional_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_u64(deserializer).map(Some)
+}
+
+pub fn deserialize_usize<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    usize::from_str(&s).map_err(de::Error::custom)
+}
+
 pub fn deserialize_pathbuf<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
 where
     D: Deserializer<'de>,
@@ -148,6 +260,64 @@ where
     Ok(format!("{s}/"))
 }
 
+/// Collect the `@scope:registry` entries, keyed by scope (`@scope`).
+///
+/// Every registry receives a trailing "/" like [`deserialize_registry`].
+pub fn deserialize_scoped_registries<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = HashMap::<String, String>::deserialize(deserializer)?;
+    let scoped_registries = entries
+        .into_iter()
+        .filter_map(|(key, registry)| {
+            let scope = key.strip_suffix(":registry")?;
+            scope.starts_with('@').then_some(())?;
+            let registry = if registry.ends_with('/') { registry } else { format!("{registry}/") };
+            Some((scope.to_string(), registry))
+        })
+        .collect();
+    Ok(scoped_registries)
+}
+
+/// Collect the `//{host}/{path}/:_authToken` entries, keyed by registry URI (`//{host}/{path}/`).
+pub fn deserialize_auth_tokens<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = HashMap::<String, String>::deserialize(deserializer)?;
+    let auth_tokens = entries
+        .into_iter()
+        .filter_map(|(key, token)| {
+            let uri = key.strip_suffix(":_authToken")?;
+            uri.starts_with("//").then_some(())?;
+            Some((uri.to_string(), token))
+        })
+        .collect();
+    Ok(auth_tokens)
+}
+
+/// Collect the registry URIs (`//{host}/{path}/`) of the `//{host}/{path}/:always-auth=true`
+/// entries.
+pub fn deserialize_always_auth_registries<'de, D>(
+    deserializer: D,
+) -> Result<HashSet<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = HashMap::<String, String>::deserialize(deserializer)?;
+    let mut registries = HashSet::new();
+    for (key, value) in entries {
+        let Some(uri) = key.strip_suffix(":always-auth") else { continue };
+        if uri.starts_with("//") && bool::from_str(&value).map_err(de::Error::custom)? {
+            registries.insert(uri.to_string());
+        }
+    }
+    Ok(registries)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
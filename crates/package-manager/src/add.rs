@@ -1,4 +1,9 @@
-use crate::{Install, ResolvedPackages};
+use crate::{
+    fetch_git_package, fetch_tarball_url, find_workspace_package, is_local_tarball, is_tarball_url,
+    read_local_tarball, FindWorkspacePackageError, GitSpecifier, Install, InstallError,
+    InstallGitPackageError, InstallLocalTarballError, InstallTarballUrlError, PackageHook,
+    ResolveGitError, ResolvedPackages, TarballUrlCache, WorkspacePackage,
+};
 use derive_more::{Display, Error};
 use miette::Diagnostic;
 use pacquet_lockfile::Lockfile;
@@ -6,8 +11,11 @@ use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
 use pacquet_package_manifest::PackageManifestError;
 use pacquet_package_manifest::{DependencyGroup, PackageManifest};
-use pacquet_registry::{PackageTag, PackageVersion};
+use pacquet_registry::{Package, PackageTag, PackageVersion, ParseTimestampError, RegistryError};
+use pacquet_reporter::Reporter;
 use pacquet_tarball::MemCache;
+use pacquet_workspace::WorkspaceManifest;
+use std::{fs, path::Path};
 
 /// This subroutine does everything `pacquet add` is supposed to do.
 #[must_use]
@@ -18,22 +26,57 @@ where
 {
     pub tarball_mem_cache: &'a MemCache,
     pub resolved_packages: &'a ResolvedPackages,
+    pub tarball_url_cache: &'a TarballUrlCache,
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub manifest: &'a mut PackageManifest,
+    pub workspace_manifest: Option<&'a WorkspaceManifest>,
+    /// Root directory of the workspace of `workspace_manifest`. A package that is a project of
+    /// the workspace is saved according to
+    /// [`save_workspace_protocol`](Npmrc::save_workspace_protocol) instead of being resolved
+    /// from the registry.
+    pub workspace_dir: Option<&'a Path>,
     pub lockfile: Option<&'a Lockfile>,
     pub list_dependency_groups: ListDependencyGroups, // must be a function because it is called multiple times
-    pub package_name: &'a str, // TODO: 1. support version range, 2. multiple arguments, 3. name this `packages`
+    pub package_name: &'a str, // TODO: 1. support version range, 2. multiple arguments, 3. name this `packages`. May also be a path to or a URL of a `.tgz` file.
     pub save_exact: bool,      // TODO: add `save-exact` to `.npmrc`, merge configs, and remove this
+    /// When false, the package is installed but `manifest` is neither modified nor saved.
+    pub save: bool,
+    pub package_hook: Option<&'a dyn PackageHook>,
 }
 
 /// Error type of [`Add`].
 #[derive(Debug, Display, Error, Diagnostic)]
 pub enum AddError {
     #[display("Failed to add package to manifest: {_0}")]
+    #[diagnostic(code(pacquet_package_manager::add_dependency_to_manifest))]
     AddDependencyToManifest(#[error(source)] PackageManifestError),
     #[display("Failed save the manifest file: {_0}")]
+    #[diagnostic(code(pacquet_package_manager::save_manifest))]
     SaveManifest(#[error(source)] PackageManifestError),
+    #[diagnostic(transparent)]
+    ResolveLatest(#[error(source)] RegistryError),
+    #[diagnostic(transparent)]
+    InvalidBefore(#[error(source)] ParseTimestampError),
+    #[display("No version of {name} was published before {before}")]
+    #[diagnostic(
+        code(pacquet_package_manager::no_version_before),
+        help("Pick a later date for `before`")
+    )]
+    NoVersionBefore { name: String, before: String },
+    #[diagnostic(transparent)]
+    Install(#[error(source)] InstallError),
+    #[diagnostic(transparent)]
+    ReadLocalTarball(#[error(source)] InstallLocalTarballError),
+    #[diagnostic(transparent)]
+    FetchTarballUrl(#[error(source)] InstallTarballUrlError),
+    #[diagnostic(transparent)]
+    ResolveGit(#[error(source)] ResolveGitError),
+    #[diagnostic(transparent)]
+    FetchGitPackage(#[error(source)] InstallGitPackageError),
+    #[diagnostic(transparent)]
+    FindWorkspacePackage(#[error(source)] FindWorkspacePackageError),
 }
 
 impl<'a, ListDependencyGroups, DependencyGroupList>
@@ -47,27 +90,60 @@ where
             tarball_mem_cache,
             http_client,
             config,
+            reporter,
             manifest,
+            workspace_manifest,
+            workspace_dir,
             lockfile,
             list_dependency_groups,
             package_name,
             save_exact,
+            save,
             resolved_packages,
+            tarball_url_cache,
+            package_hook,
         } = self;
 
-        let latest_version = PackageVersion::fetch_from_registry(
-            package_name,
-            PackageTag::Latest, // TODO: add support for specifying tags
-            http_client,
-            &config.registry,
-        )
-        .await
-        .expect("resolve latest tag"); // TODO: properly propagate this error
+        // without saving, the package is added to a copy that is only used to install it
+        let mut unsaved_manifest;
+        let manifest = if save {
+            manifest
+        } else {
+            unsaved_manifest = manifest.clone();
+            &mut unsaved_manifest
+        };
+
+        let (package_name, version_range) = if is_tarball_url(package_name) {
+            let (package_version, _) =
+                fetch_tarball_url(http_client, &config.store_dir, tarball_url_cache, package_name)
+                    .await
+                    .map_err(AddError::FetchTarballUrl)?;
+            (package_version.name, package_name.to_string())
+        } else if is_local_tarball(package_name) {
+            let tarball_path = Path::new(package_name);
+            let (package_version, _) = read_local_tarball(&config.store_dir, tarball_path)
+                .map_err(AddError::ReadLocalTarball)?;
+            let project_dir = manifest.dir();
+            let specifier = file_specifier(project_dir, tarball_path);
+            (package_version.name, specifier)
+        } else if let Some(git_specifier) = GitSpecifier::parse(package_name) {
+            let resolution = git_specifier.resolve().map_err(AddError::ResolveGit)?;
+            let (package_version, _) = fetch_git_package(&config.store_dir, &resolution)
+                .map_err(AddError::FetchGitPackage)?;
+            (package_version.name, package_name.to_string())
+        } else if let Some(workspace_package) =
+            find_sibling(workspace_dir, workspace_manifest, manifest, package_name)?
+        {
+            let specifier = workspace_package.specifier(config.save_workspace_protocol, save_exact);
+            (package_name.to_string(), specifier)
+        } else {
+            let latest_version = resolve_latest(package_name, http_client, config).await?;
+            (package_name.to_string(), latest_version.serialize(save_exact))
+        };
 
-        let version_range = latest_version.serialize(save_exact);
         for dependency_group in list_dependency_groups() {
             manifest
-                .add_dependency(package_name, &version_range, dependency_group)
+                .add_dependency(&package_name, &version_range, dependency_group)
                 .map_err(AddError::AddDependencyToManifest)?;
         }
 
@@ -75,17 +151,96 @@ where
             tarball_mem_cache,
             http_client,
             config,
+            reporter,
             manifest,
+            workspace_manifest,
+            workspace_dir,
             lockfile,
-            dependency_groups: list_dependency_groups(),
+            // the whole project is installed so that the lockfile describes all of it
+            dependency_groups: [
+                DependencyGroup::Prod,
+                DependencyGroup::Dev,
+                DependencyGroup::Optional,
+            ],
             frozen_lockfile: false,
             resolved_packages,
+            tarball_url_cache,
+            dry_run: None,
+            package_hook,
+            depth: None,
+            // extraneous packages are only removed by `pacquet install`
+            prune: false,
         }
         .run()
-        .await;
+        .await
+        .map_err(AddError::Install)?;
 
-        manifest.save().map_err(AddError::SaveManifest)?;
+        if save {
+            manifest.save().map_err(AddError::SaveManifest)?;
+        }
 
         Ok(())
     }
 }
+
+/// Resolve the `latest` tag of package `name`, among the versions that were published before
+/// [`before`](Npmrc::before) when it is set, like the install that follows does.
+async fn resolve_latest(
+    name: &str,
+    http_client: &ThrottledClient,
+    config: &Npmrc,
+) -> Result<PackageVersion, AddError> {
+    let registry = config.registry_for(name);
+    let Some(before) = &config.before else {
+        // TODO: add support for specifying tags
+        return PackageVersion::fetch_from_registry(
+            name,
+            PackageTag::Latest,
+            http_client,
+            registry,
+        )
+        .await
+        .map_err(AddError::ResolveLatest);
+    };
+    let before = before.parse().map_err(AddError::InvalidBefore)?;
+    Package::fetch_full_from_registry(name, http_client, registry)
+        .await
+        .map_err(AddError::ResolveLatest)?
+        .tagged_version_before(&PackageTag::Latest, before)
+        .cloned()
+        .ok_or_else(|| AddError::NoVersionBefore {
+            name: name.to_string(),
+            before: before.to_string(),
+        })
+}
+
+/// Find the project of the workspace named `name`, unless it is the project of `manifest`.
+fn find_sibling(
+    workspace_dir: Option<&Path>,
+    workspace_manifest: Option<&WorkspaceManifest>,
+    manifest: &PackageManifest,
+    name: &str,
+) -> Result<Option<WorkspacePackage>, AddError> {
+    let (Some(workspace_dir), Some(workspace_manifest)) = (workspace_dir, workspace_manifest)
+    else {
+        return Ok(None);
+    };
+    if manifest.value().get("name").and_then(|value| value.as_str()) == Some(name) {
+        return Ok(None);
+    }
+    find_workspace_package(workspace_dir, workspace_manifest, name)
+        .map_err(AddError::FindWorkspacePackage)
+}
+
+/// Create the `file:` specifier of `tarball_path`, relative to `project_dir` when the tarball is
+/// inside the project, absolute otherwise.
+fn file_specifier(project_dir: &Path, tarball_path: &Path) -> String {
+    let Ok(tarball_path) = fs::canonicalize(tarball_path) else {
+        return format!("file:{}", tarball_path.display());
+    };
+    let path = fs::canonicalize(project_dir.join("."))
+        .ok()
+        .and_then(|project_dir| tarball_path.strip_prefix(project_dir).ok())
+        .unwrap_or(&tarball_path);
+    format!("file:{}", path.display().to_string().replace('\\', "/"))
+}
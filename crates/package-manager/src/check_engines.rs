@@ -0,0 +1,110 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use node_semver::{Range, Version};
+use pacquet_npmrc::Npmrc;
+use std::{collections::HashMap, process::Command, sync::OnceLock};
+
+/// Error type of [`check_engines`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{name}@{version} requires node {range}, but the Node.js version is {node_version}")]
+#[diagnostic(
+    code(pacquet_package_manager::unsupported_engine),
+    help("Set `use-node-version` to check against the Node.js that will run the packages")
+)]
+pub struct UnsupportedEngineError {
+    pub name: String,
+    pub version: String,
+    pub range: String,
+    pub node_version: String,
+}
+
+/// Version of Node.js that the `engines` field of packages is checked against:
+/// [`use_node_version`](Npmrc::use_node_version) if set, the version of the `node` on the `PATH`
+/// otherwise.
+///
+/// Return `None` if neither is available, or if `use-node-version` isn't a valid version.
+pub fn node_version(config: &Npmrc) -> Option<Version> {
+    if let Some(use_node_version) = &config.use_node_version {
+        let version = use_node_version.trim().trim_start_matches('v').parse();
+        if version.is_err() {
+            tracing::warn!(target: "pacquet::engines", ?use_node_version, "Ignore invalid use-node-version");
+        }
+        return version.ok();
+    }
+    host_node_version().cloned()
+}
+
+/// Version of the `node` on the `PATH`, which is only asked for once.
+fn host_node_version() -> Option<&'static Version> {
+    static HOST_NODE_VERSION: OnceLock<Option<Version>> = OnceLock::new();
+    HOST_NODE_VERSION
+        .get_or_init(|| {
+            let output = Command::new("node").arg("--version").output().ok()?;
+            let stdout = String::from_utf8(output.stdout).ok()?;
+            stdout.trim().trim_start_matches('v').parse().ok()
+        })
+        .as_ref()
+}
+
+/// Check the `node` entry of the `engines` field of package `name@version` against
+/// `node_version`.
+///
+/// Nothing is checked without a Node.js version, and neither are ranges that can't be parsed.
+pub fn check_engines(
+    name: &str,
+    version: &str,
+    engines: Option<&HashMap<String, String>>,
+    node_version: Option<&Version>,
+) -> Result<(), UnsupportedEngineError> {
+    let (Some(range), Some(node_version)) =
+        (engines.and_then(|engines| engines.get("node")), node_version)
+    else {
+        return Ok(());
+    };
+    let Ok(parsed_range) = range.parse::<Range>() else { return Ok(()) };
+    if node_version.satisfies(&parsed_range) {
+        return Ok(());
+    }
+    Err(UnsupportedEngineError {
+        name: name.to_string(),
+        version: version.to_string(),
+        range: range.clone(),
+        node_version: node_version.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn use_node_version_should_override_host() {
+        let config = Npmrc::builder().use_node_version(Some("v18.19.0".to_string())).build();
+        assert_eq!(node_version(&config), Some(Version::parse("18.19.0").unwrap()));
+
+        let config = Npmrc::builder().use_node_version(Some("lts".to_string())).build();
+        assert_eq!(node_version(&config), None);
+    }
+
+    #[test]
+    fn check_engines_should_compare_node_version() {
+        let engines: HashMap<_, _> =
+            [("node".to_string(), "^18.0.0".to_string()), ("npm".to_string(), ">=99".to_string())]
+                .into();
+        let check = |node_version: &str| {
+            let node_version = Version::parse(node_version).unwrap();
+            check_engines("foo", "1.0.0", Some(&engines), Some(&node_version))
+        };
+
+        check("18.0.0").unwrap();
+        check("18.19.0").unwrap();
+        let error = check("20.0.0").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "foo@1.0.0 requires node ^18.0.0, but the Node.js version is 20.0.0",
+        );
+        check_engines("foo", "1.0.0", Some(&engines), None).unwrap();
+        check_engines("foo", "1.0.0", None, Some(&Version::parse("20.0.0").unwrap())).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_registry::PackageVersion;
+use pacquet_store_dir::StoreDir;
+use std::{collections::HashSet, path::Path};
+
+/// Estimated number of bytes that a file occupies on disk beyond its contents, since files take
+/// up whole blocks.
+const FILE_OVERHEAD: u64 = 4096;
+
+/// Error type of [`check_free_space`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display(
+    "Not enough space for the store at {}: {required} bytes are needed but only {available} bytes are available",
+    store_dir.display()
+)]
+#[diagnostic(
+    code(pacquet_package_manager::insufficient_space),
+    help("Free up some disk space, or set `store-dir` to a directory on another disk")
+)]
+pub struct InsufficientSpaceError {
+    pub store_dir: StoreDir,
+    pub required: u64,
+    pub available: u64,
+}
+
+/// Estimate how many bytes the files of a package take in the store, or `None` if the registry
+/// doesn't report the size of the package.
+fn package_store_size(package: &PackageVersion) -> Option<u64> {
+    let unpacked_size = package.dist.unpacked_size? as u64;
+    let file_count = package.dist.file_count.unwrap_or(1) as u64;
+    Some(unpacked_size.saturating_add(file_count.saturating_mul(FILE_OVERHEAD)))
+}
+
+/// Estimate how many bytes the store needs for `packages` from the `dist.unpackedSize` and
+/// `dist.fileCount` that the registry reports.
+///
+/// A package is counted once however many times it occurs in `packages`. Packages whose files are
+/// already in the store aren't counted, neither are the packages that the registry doesn't report
+/// a size for.
+pub fn estimate_store_size<'a>(
+    store_dir: &StoreDir,
+    packages: impl IntoIterator<Item = &'a PackageVersion>,
+) -> u64 {
+    let mut counted = HashSet::new();
+    packages
+        .into_iter()
+        .filter(|package| counted.insert(package.to_virtual_store_name()))
+        .filter(|package| match &package.dist.integrity {
+            Some(integrity) => !store_dir.index_file_path(integrity).is_file(),
+            None => true,
+        })
+        .filter_map(package_store_size)
+        .fold(0, u64::saturating_add)
+}
+
+/// Fail if the filesystem of `store_dir` doesn't have enough space for the tarballs of
+/// `packages`, which are about to be downloaded, see [`estimate_store_size`].
+///
+/// Nothing is checked on platforms where the available space can't be determined.
+pub fn check_free_space<'a>(
+    store_dir: &StoreDir,
+    packages: impl IntoIterator<Item = &'a PackageVersion>,
+) -> Result<(), InsufficientSpaceError> {
+    let Some(available) = available_space(&store_dir.tmp()) else {
+        return Ok(());
+    };
+    let required = estimate_store_size(store_dir, packages);
+    tracing::debug!(target: "pacquet::install", required, available, "Check free space of the store");
+    if available < required {
+        return Err(InsufficientSpaceError { store_dir: store_dir.clone(), required, available });
+    }
+    Ok(())
+}
+
+/// Bytes available to unprivileged users on the filesystem of `path`, which may not exist yet.
+#[cfg(unix)]
+fn available_space(path: &Path) -> Option<u64> {
+    let path = path.ancestors().find(|path| path.exists()).unwrap_or(Path::new("."));
+    let stats = rustix::fs::statvfs(path).ok()?;
+    Some(stats.f_bavail.saturating_mul(stats.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn available_space(_: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pipe_trait::Pipe;
+    use pretty_assertions::assert_eq;
+    use ssri::{Algorithm, IntegrityOpts};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn package(
+        name: &str,
+        unpacked_size: Option<usize>,
+        file_count: Option<usize>,
+    ) -> PackageVersion {
+        let integrity = IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(name).result();
+        serde_json::json!({
+            "name": name,
+            "version": "1.0.0",
+            "dist": {
+                "integrity": integrity.to_string(),
+                "tarball": format!("https://registry.npmjs.org/{name}/-/{name}-1.0.0.tgz"),
+                "unpackedSize": unpacked_size,
+                "fileCount": file_count,
+            },
+        })
+        .pipe(serde_json::from_value)
+        .unwrap()
+    }
+
+    #[test]
+    fn estimate_should_add_overhead_per_file() {
+        let dir = tempdir().unwrap();
+        let store_dir = StoreDir::new(dir.path());
+        let packages = [
+            package("foo", Some(1000), Some(2)),
+            package("bar", Some(10), None),
+            package("baz", None, Some(3)),
+        ];
+        assert_eq!(
+            estimate_store_size(&store_dir, &packages),
+            1000 + 2 * FILE_OVERHEAD + 10 + FILE_OVERHEAD,
+        );
+    }
+
+    #[test]
+    fn estimate_should_count_a_shared_dependency_once() {
+        let dir = tempdir().unwrap();
+        let store_dir = StoreDir::new(dir.path());
+        // `shared` is depended on by both `foo` and `bar`
+        let shared = package("shared", Some(1000), Some(1));
+        let packages = [
+            package("foo", Some(10), Some(1)),
+            shared.clone(),
+            package("bar", Some(10), Some(1)),
+            shared,
+        ];
+        assert_eq!(estimate_store_size(&store_dir, &packages), 1000 + 10 + 10 + 3 * FILE_OVERHEAD,);
+    }
+
+    #[test]
+    fn estimate_should_skip_packages_in_the_store() {
+        let dir = tempdir().unwrap();
+        let store_dir = StoreDir::new(dir.path());
+        let stored = package("foo", Some(1000), Some(1));
+        let index_file_path = store_dir.index_file_path(stored.dist.integrity.as_ref().unwrap());
+        fs::create_dir_all(index_file_path.parent().unwrap()).unwrap();
+        fs::write(&index_file_path, "{}").unwrap();
+
+        let packages = [stored, package("bar", Some(10), Some(1))];
+        assert_eq!(estimate_store_size(&store_dir, &packages), 10 + FILE_OVERHEAD);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_free_space_should_compare_with_available_space() {
+        let dir = tempdir().unwrap();
+        let store_dir = StoreDir::new(dir.path().join("store"));
+        check_free_space(&store_dir, &[package("foo", Some(0), Some(0))]).unwrap();
+        let error =
+            check_free_space(&store_dir, &[package("foo", Some(usize::MAX), None)]).unwrap_err();
+        assert_eq!(error.required, u64::MAX);
+        assert!(error.available < u64::MAX);
+    }
+}
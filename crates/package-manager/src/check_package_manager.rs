@@ -0,0 +1,121 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use node_semver::Range;
+
+/// Versions of pnpm whose lockfile and `node_modules` layout pacquet produces.
+const COMPATIBLE_PNPM_VERSIONS: &str = "^8.0.0";
+
+/// Error type of [`check_package_manager`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum PackageManagerError {
+    #[display("The packageManager field of package.json is invalid: {value:?}")]
+    #[diagnostic(
+        code(pacquet_package_manager::invalid_package_manager),
+        help("The field should be in the form of `{{name}}@{{version}}`, such as `pnpm@8.6.0`")
+    )]
+    Invalid { value: String },
+
+    #[display("This project is configured to use {value}, which pacquet can't stand in for")]
+    #[diagnostic(
+        code(pacquet_package_manager::incompatible_package_manager),
+        help(
+            "pacquet is compatible with pnpm@{COMPATIBLE_PNPM_VERSIONS}, set `package-manager-strict=false` to only warn"
+        )
+    )]
+    Incompatible { value: String },
+}
+
+/// Check that pacquet can be used in place of the package manager that the `packageManager`
+/// field of `package.json` asks for.
+///
+/// Any version of pacquet is accepted, and so are the versions of pnpm that pacquet is
+/// compatible with. Other package managers and versions are an error when `strict` is `true`
+/// and a warning otherwise.
+pub fn check_package_manager(
+    package_manager: &str,
+    strict: bool,
+) -> Result<(), PackageManagerError> {
+    let Err(error) = check_compatibility(package_manager) else { return Ok(()) };
+
+    if strict {
+        return Err(error);
+    }
+
+    tracing::warn!(target: "pacquet::package_manager", ?package_manager, "{error}");
+    Ok(())
+}
+
+fn check_compatibility(package_manager: &str) -> Result<(), PackageManagerError> {
+    let invalid = || PackageManagerError::Invalid { value: package_manager.to_string() };
+    let (name, version) = package_manager.split_once('@').ok_or_else(invalid)?;
+    // the version may end with the hash of the package manager, e.g. `pnpm@8.6.0+sha256.abc`
+    let version = version.split_once('+').map_or(version, |(version, _)| version);
+    let version: Range = version.parse().map_err(|_| invalid())?;
+
+    let compatible = match name {
+        "pacquet" => true,
+        "pnpm" => {
+            COMPATIBLE_PNPM_VERSIONS.parse::<Range>().expect("valid range").allows_all(&version)
+        }
+        _ => false,
+    };
+    if compatible {
+        Ok(())
+    } else {
+        Err(PackageManagerError::Incompatible { value: package_manager.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compatible_package_managers() {
+        let case = |package_manager: &str| {
+            eprintln!("CASE: {package_manager:?}");
+            check_package_manager(package_manager, true).expect("compatible package manager");
+        };
+
+        case("pnpm@8.6.0");
+        case("pnpm@8.x");
+        case("pnpm@8.15.4+sha256.b2b4b9a2ffd4e4fc5909c3b6d41680d0a6e0ed8a4e4d0ce3cbe7e7bd24bcbb2b");
+        case("pacquet@0.2.1");
+    }
+
+    #[test]
+    fn incompatible_package_managers() {
+        let case = |package_manager: &str| {
+            eprintln!("CASE: {package_manager:?}");
+            let error = check_package_manager(package_manager, true).expect_err("incompatible");
+            dbg!(&error);
+            assert!(matches!(error, PackageManagerError::Incompatible { .. }));
+        };
+
+        case("pnpm@7.33.0");
+        case("pnpm@9.0.0");
+        case("pnpm@>=8");
+        case("yarn@3.6.1");
+        case("npm@9.8.0");
+    }
+
+    #[test]
+    fn invalid_package_managers() {
+        let case = |package_manager: &str| {
+            eprintln!("CASE: {package_manager:?}");
+            let error = check_package_manager(package_manager, true).expect_err("invalid");
+            dbg!(&error);
+            assert!(matches!(error, PackageManagerError::Invalid { .. }));
+        };
+
+        case("pnpm");
+        case("pnpm@latest");
+    }
+
+    #[test]
+    fn incompatible_package_manager_should_only_warn_when_not_strict() {
+        check_package_manager("yarn@3.6.1", false).expect("only a warning");
+        check_package_manager("pnpm", false).expect("only a warning");
+    }
+}
@@ -0,0 +1,291 @@
+use derive_more::{Display, Error};
+use globset::{Glob, GlobMatcher};
+use miette::Diagnostic;
+use node_semver::Range;
+use pacquet_package_manifest::PackageManifest;
+use pacquet_registry::PackageVersion;
+use pacquet_reporter::{Event, Reporter};
+
+/// Error type of [`check_peer_dependencies`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("{package} has {}", describe_problems(missing, unmet))]
+#[diagnostic(
+    code(pacquet_package_manager::missing_peer_dependencies),
+    help("Add the missing peers to package.json, allow them with `pnpm.peerDependencyRules`, or set `strict-peer-dependencies=false`")
+)]
+pub struct MissingPeerDependenciesError {
+    pub package: String,
+    #[error(not(source))]
+    pub missing: Vec<String>,
+    /// Peers whose available version range doesn't overlap the required range, as
+    /// `{name}@{range} (found {available})`.
+    #[error(not(source))]
+    pub unmet: Vec<String>,
+}
+
+fn describe_problems(missing: &[String], unmet: &[String]) -> String {
+    let missing =
+        (!missing.is_empty()).then(|| format!("missing peer dependencies: {}", missing.join(", ")));
+    let unmet =
+        (!unmet.is_empty()).then(|| format!("unmet peer dependencies: {}", unmet.join(", ")));
+    missing.into_iter().chain(unmet).collect::<Vec<_>>().join(" and ")
+}
+
+/// Exceptions to the peer dependency checks, read from `pnpm.peerDependencyRules` of
+/// `package.json`.
+///
+/// Patterns of `ignoreMissing` may use `*`. Keys of `allowedVersions` are either `foo` or
+/// `{parent}>foo`, for the peers of `parent` only.
+#[derive(Debug, Default)]
+pub struct PeerDependencyRules {
+    ignore_missing: Vec<GlobMatcher>,
+    allowed_versions: Vec<AllowedVersions>,
+}
+
+#[derive(Debug)]
+struct AllowedVersions {
+    parent: Option<String>,
+    name: String,
+    version_range: String,
+}
+
+impl PeerDependencyRules {
+    /// Read the peer dependency rules of `manifest`.
+    pub fn from_manifest(manifest: &PackageManifest) -> Self {
+        let ignore_missing = manifest
+            .ignored_missing_peers()
+            .filter_map(|pattern| match Glob::new(pattern) {
+                Ok(glob) => Some(glob.compile_matcher()),
+                Err(error) => {
+                    tracing::warn!(target: "pacquet::install", ?pattern, %error, "Ignore invalid pattern of peerDependencyRules.ignoreMissing");
+                    None
+                }
+            })
+            .collect();
+        let allowed_versions = manifest
+            .allowed_peer_versions()
+            .map(|(key, version_range)| {
+                let (parent, name) = match key.rsplit_once('>') {
+                    Some((parent, name)) => (Some(parent.to_string()), name),
+                    None => (None, key),
+                };
+                AllowedVersions {
+                    parent,
+                    name: name.to_string(),
+                    version_range: version_range.to_string(),
+                }
+            })
+            .collect();
+        PeerDependencyRules { ignore_missing, allowed_versions }
+    }
+
+    /// Whether peer `name` may be missing.
+    fn ignores_missing(&self, name: &str) -> bool {
+        self.ignore_missing.iter().any(|matcher| matcher.is_match(name))
+    }
+
+    /// Range that satisfies peer `name` of `package`: `version_range` extended with the allowed
+    /// versions. `None` if it can't be parsed.
+    fn peer_range(&self, package: &str, name: &str, version_range: &str) -> Option<Range> {
+        let allowed = self
+            .allowed_versions
+            .iter()
+            .filter(|allowed| allowed.name == name)
+            .filter(|allowed| allowed.parent.as_deref().map_or(true, |parent| parent == package))
+            .map(|allowed| allowed.version_range.as_str());
+        let ranges: Vec<&str> = [version_range].into_iter().chain(allowed).collect();
+        ranges.join(" || ").parse().ok()
+    }
+}
+
+/// Check that every required peer dependency of `package` is available, and that the version
+/// range it is available with overlaps the range of the peer.
+///
+/// `available_range` returns the version range that a peer is declared with by whoever provides
+/// it, `None` if nobody does. Ranges that aren't semver, such as `file:` specifiers, are assumed
+/// to satisfy the peer.
+///
+/// Peers marked as optional in `peerDependenciesMeta` or ignored by `rules` are never reported
+/// as missing. Problems are an error when `strict` is `true` and a warning, which is also sent to
+/// `reporter`, otherwise.
+pub fn check_peer_dependencies<'a>(
+    package: &PackageVersion,
+    available_range: impl Fn(&str) -> Option<&'a str>,
+    rules: &PeerDependencyRules,
+    strict: bool,
+    reporter: &dyn Reporter,
+) -> Result<(), MissingPeerDependenciesError> {
+    let mut missing = Vec::new();
+    let mut unmet = Vec::new();
+    for (name, range) in package.required_peer_dependencies() {
+        let Some(available) = available_range(name) else {
+            if !rules.ignores_missing(name) {
+                missing.push(format!("{name}@{range}"));
+            }
+            continue;
+        };
+        let peer_range = rules.peer_range(&package.name, name, range);
+        let available_range = available.parse::<Range>().ok();
+        if let (Some(peer_range), Some(available_range)) = (peer_range, available_range) {
+            if !peer_range.allows_any(&available_range) {
+                unmet.push(format!("{name}@{range} (found {available})"));
+            }
+        }
+    }
+
+    if missing.is_empty() && unmet.is_empty() {
+        return Ok(());
+    }
+
+    missing.sort();
+    unmet.sort();
+    let error =
+        MissingPeerDependenciesError { package: package.to_virtual_store_name(), missing, unmet };
+
+    if strict {
+        return Err(error);
+    }
+
+    let MissingPeerDependenciesError { package, missing, unmet } = &error;
+    if !missing.is_empty() {
+        tracing::warn!(target: "pacquet::install", ?package, ?missing, "Missing peer dependencies");
+    }
+    if !unmet.is_empty() {
+        tracing::warn!(target: "pacquet::install", ?package, ?unmet, "Unmet peer dependencies");
+    }
+    reporter.emit(Event::Warning { message: error.to_string() });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pacquet_reporter::SilentReporter;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    fn check_silently<'a>(
+        package: &PackageVersion,
+        available_range: impl Fn(&str) -> Option<&'a str>,
+        rules: &PeerDependencyRules,
+        strict: bool,
+    ) -> Result<(), MissingPeerDependenciesError> {
+        check_peer_dependencies(package, available_range, rules, strict, &SilentReporter)
+    }
+
+    fn package_with_peers(peer_dependencies_meta: &str) -> PackageVersion {
+        serde_json::from_str(&format!(
+            r#"{{
+                "name": "foo",
+                "version": "1.0.0",
+                "dist": {{ "tarball": "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz" }},
+                "peerDependencies": {{ "react": "^18.0.0" }},
+                "peerDependenciesMeta": {peer_dependencies_meta}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn optional_missing_peer_should_pass_strict_mode() {
+        let package = package_with_peers(r#"{ "react": { "optional": true } }"#);
+        check_silently(&package, |_| None, &PeerDependencyRules::default(), true)
+            .expect("optional peer is not required");
+    }
+
+    #[test]
+    fn required_missing_peer_should_fail_strict_mode() {
+        let package = package_with_peers("{}");
+        let error = check_silently(&package, |_| None, &PeerDependencyRules::default(), true)
+            .expect_err("missing peer");
+        dbg!(&error);
+        assert_eq!(error.package, "foo@1.0.0");
+        assert_eq!(error.missing, ["react@^18.0.0"]);
+    }
+
+    #[test]
+    fn required_missing_peer_should_only_warn_without_strict_mode() {
+        #[derive(Default)]
+        struct Warnings(Mutex<Vec<String>>);
+        impl Reporter for Warnings {
+            fn warn(&self, message: &str) {
+                self.0.lock().unwrap().push(message.to_string());
+            }
+        }
+
+        let package = package_with_peers("{}");
+        let warnings = Warnings::default();
+        let rules = PeerDependencyRules::default();
+        check_peer_dependencies(&package, |_| None, &rules, false, &warnings)
+            .expect("only a warning");
+        assert_eq!(
+            *warnings.0.lock().unwrap(),
+            ["foo@1.0.0 has missing peer dependencies: react@^18.0.0"],
+        );
+    }
+
+    #[test]
+    fn available_peer_should_pass_strict_mode() {
+        let package = package_with_peers("{}");
+        let rules = PeerDependencyRules::default();
+        check_silently(&package, |name| (name == "react").then_some("^18.2.0"), &rules, true)
+            .expect("peer available");
+    }
+
+    fn rules(ignore_missing: &[&str], allowed_versions: &[(&str, &str)]) -> PeerDependencyRules {
+        let ignore_missing = ignore_missing
+            .iter()
+            .map(|pattern| Glob::new(pattern).unwrap().compile_matcher())
+            .collect();
+        let allowed_versions = allowed_versions
+            .iter()
+            .map(|(key, version_range)| {
+                let (parent, name) = match key.rsplit_once('>') {
+                    Some((parent, name)) => (Some(parent.to_string()), name),
+                    None => (None, *key),
+                };
+                AllowedVersions {
+                    parent,
+                    name: name.to_string(),
+                    version_range: version_range.to_string(),
+                }
+            })
+            .collect();
+        PeerDependencyRules { ignore_missing, allowed_versions }
+    }
+
+    #[test]
+    fn ignored_missing_peer_should_pass_strict_mode() {
+        let package = package_with_peers("{}");
+        check_silently(&package, |_| None, &rules(&["re*"], &[]), true)
+            .expect("missing peer is ignored");
+        check_silently(&package, |_| None, &rules(&["vue"], &[]), true)
+            .expect_err("another peer is ignored");
+    }
+
+    #[test]
+    fn unmet_peer_should_fail_strict_mode() {
+        let package = package_with_peers("{}");
+        let error = check_silently(&package, |_| Some("^17.0.0"), &rules(&[], &[]), true)
+            .expect_err("unmet peer");
+        dbg!(&error);
+        assert_eq!(error.missing, Vec::<String>::new());
+        assert_eq!(error.unmet, ["react@^18.0.0 (found ^17.0.0)"]);
+        assert_eq!(
+            error.to_string(),
+            "foo@1.0.0 has unmet peer dependencies: react@^18.0.0 (found ^17.0.0)",
+        );
+    }
+
+    #[test]
+    fn allowed_versions_should_satisfy_peer() {
+        let package = package_with_peers("{}");
+        let check = |allowed_versions| {
+            check_silently(&package, |_| Some("17.0.2"), &rules(&[], allowed_versions), true)
+        };
+        check(&[("react", "17")]).expect("allowed for every package");
+        check(&[("foo>react", "17")]).expect("allowed for foo");
+        check(&[("bar>react", "17")]).expect_err("only allowed for bar");
+        check(&[("react", "16")]).expect_err("only another version is allowed");
+    }
+}
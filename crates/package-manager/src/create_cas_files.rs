@@ -10,7 +10,15 @@ use std::{
 
 /// Error type for [`create_cas_files`].
 #[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
 pub enum CreateCasFilesError {
+    #[display("Only package-import-method=auto is supported yet, but {dir_path:?} requires {import_method:?}")]
+    #[diagnostic(
+        code(pacquet_package_manager::unsupported_import_method),
+        help("Remove `package-import-method` from .npmrc or set it to `auto`")
+    )]
+    UnsupportedImportMethod { import_method: PackageImportMethod, dir_path: PathBuf },
+
     #[diagnostic(transparent)]
     LinkFile(#[error(source)] LinkFileError),
 }
@@ -18,25 +26,76 @@ pub enum CreateCasFilesError {
 /// If `dir_path` doesn't exist, create and populate it with files from `cas_paths`.
 ///
 /// If `dir_path` already exists, do nothing.
+///
+/// Files are linked in parallel, except for paths that only differ in case: on a case-insensitive
+/// file system only one of them can exist, so they are linked one after another in the order of
+/// their paths and the first one always wins.
 pub fn create_cas_files(
     import_method: PackageImportMethod,
     dir_path: &Path,
     cas_paths: &HashMap<String, PathBuf>,
 ) -> Result<(), CreateCasFilesError> {
-    assert_eq!(
-        import_method,
-        PackageImportMethod::Auto,
-        "Only PackageImportMethod::Auto is currently supported, but {dir_path:?} requires {import_method:?}",
-    );
+    if import_method != PackageImportMethod::Auto {
+        return Err(CreateCasFilesError::UnsupportedImportMethod {
+            import_method,
+            dir_path: dir_path.to_path_buf(),
+        });
+    }
 
     if dir_path.exists() {
         return Ok(());
     }
 
-    cas_paths
+    let mut entries: Vec<_> = cas_paths.iter().collect();
+    entries.sort();
+    let mut case_conflicts = HashMap::<String, usize>::new();
+    for (cleaned_entry, _) in &entries {
+        *case_conflicts.entry(cleaned_entry.to_lowercase()).or_default() += 1;
+    }
+    let (conflicting, distinct): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|(cleaned_entry, _)| case_conflicts[&cleaned_entry.to_lowercase()] > 1);
+
+    let link = |(cleaned_entry, store_path): &(&String, &PathBuf)| {
+        link_file(store_path, &dir_path.join(cleaned_entry))
+    };
+    distinct
         .par_iter()
-        .try_for_each(|(cleaned_entry, store_path)| {
-            link_file(store_path, &dir_path.join(cleaned_entry))
-        })
+        .map(link)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .chain(conflicting.iter().map(link))
+        .collect::<Result<(), _>>()
         .map_err(CreateCasFilesError::LinkFile)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn paths_that_only_differ_in_case_should_be_linked_in_order() {
+        let dir = tempdir().unwrap();
+        let store_dir = dir.path().join("store");
+        fs::create_dir_all(&store_dir).unwrap();
+        let cas_paths: HashMap<_, _> = ["README.md", "readme.md", "index.js"]
+            .into_iter()
+            .map(|entry| {
+                let store_path = store_dir.join(entry.replace('.', "-"));
+                fs::write(&store_path, entry).unwrap();
+                (entry.to_string(), store_path)
+            })
+            .collect();
+
+        let package_dir = dir.path().join("package");
+        create_cas_files(PackageImportMethod::Auto, &package_dir, &cas_paths).unwrap();
+        assert_eq!(fs::read_to_string(package_dir.join("index.js")).unwrap(), "index.js");
+        // on a case-insensitive file system, both paths point to the first one
+        assert_eq!(fs::read_to_string(package_dir.join("README.md")).unwrap(), "README.md");
+        let readme = fs::read_to_string(package_dir.join("readme.md")).unwrap();
+        assert!(["README.md", "readme.md"].contains(&readme.as_str()), "{readme:?}");
+    }
+}
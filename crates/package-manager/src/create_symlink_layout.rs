@@ -1,31 +1,114 @@
-use crate::symlink_package;
-use pacquet_lockfile::{PackageSnapshotDependency, PkgName, PkgNameVerPeer};
+use crate::{symlink_package, SymlinkPackageError};
+use pacquet_lockfile::{PackageSnapshotDependency, PkgName};
 use rayon::prelude::*;
 use std::{collections::HashMap, path::Path};
 
 /// Create symlink layout of dependencies for a package in a virtual dir.
 ///
+/// The symlinks are created in parallel, if several of them fail, the error of the first
+/// dependency by name is returned.
+///
+/// A package that depends on itself would be linked at its own directory, such a dependency is
+/// skipped since the package can already import itself. Dependency cycles between different
+/// packages need no special treatment: each virtual dir only links to the directories of its
+/// dependencies, so following the links never loops.
+///
 /// **NOTE:** `virtual_node_modules_dir` is assumed to already exist.
 pub fn create_symlink_layout(
     dependencies: &HashMap<PkgName, PackageSnapshotDependency>,
     virtual_root: &Path,
     virtual_node_modules_dir: &Path,
-) {
-    dependencies.par_iter().for_each(|(name, spec)| {
-        let virtual_store_name = match spec {
-            PackageSnapshotDependency::PkgVerPeer(ver_peer) => {
-                let package_specifier = PkgNameVerPeer::new(name.clone(), ver_peer.clone()); // TODO: remove copying here
-                package_specifier.to_virtual_store_name()
-            }
-            PackageSnapshotDependency::DependencyPath(dependency_path) => {
-                dependency_path.package_specifier.to_virtual_store_name()
+) -> Result<(), SymlinkPackageError> {
+    let mut dependencies: Vec<_> = dependencies.iter().collect();
+    dependencies.sort_by_cached_key(|(name, _)| name.to_string());
+    dependencies
+        .par_iter()
+        .map(|(name, spec)| {
+            // the package of an aliased dependency is stored under its real name
+            let package_specifier = spec.to_package_specifier(name);
+            let target = virtual_root
+                .join(package_specifier.to_virtual_store_name())
+                .join("node_modules")
+                .join(package_specifier.name.to_string());
+            let symlink_path = virtual_node_modules_dir.join(name.to_string());
+            if target == symlink_path {
+                tracing::debug!(target: "pacquet::install", ?symlink_path, "Skip self dependency");
+                return Ok(());
             }
-        };
-        let name_str = name.to_string();
-        symlink_package(
-            &virtual_root.join(virtual_store_name).join("node_modules").join(&name_str),
-            &virtual_node_modules_dir.join(&name_str),
-        )
-        .expect("symlink pkg successful"); // TODO: properly propagate this error
-    });
+            symlink_package(&target, &symlink_path)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_fail_when_dependency_path_is_occupied() {
+        let dir = tempdir().unwrap();
+        let virtual_root = dir.path().join("node_modules/.pnpm");
+        let virtual_node_modules_dir = virtual_root.join("bar@1.0.0/node_modules");
+        fs::create_dir_all(&virtual_node_modules_dir).unwrap();
+        fs::write(virtual_node_modules_dir.join("foo"), "not a symlink").unwrap();
+
+        let dependencies = HashMap::from([(
+            "foo".parse::<PkgName>().unwrap(),
+            PackageSnapshotDependency::PkgVerPeer("1.0.0".parse().unwrap()),
+        )]);
+        let error = create_symlink_layout(&dependencies, &virtual_root, &virtual_node_modules_dir)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            SymlinkPackageError::PathOccupied { symlink_path }
+                if symlink_path == virtual_node_modules_dir.join("foo")
+        ));
+    }
+
+    #[test]
+    fn should_link_aliased_dependency_to_real_package() {
+        let dir = tempdir().unwrap();
+        let virtual_root = dir.path().join("node_modules/.pnpm");
+        let virtual_node_modules_dir = virtual_root.join("bar@1.0.0/node_modules");
+        fs::create_dir_all(&virtual_node_modules_dir).unwrap();
+
+        let dependencies = HashMap::from([(
+            "my-lodash".parse::<PkgName>().unwrap(),
+            PackageSnapshotDependency::DependencyPath("/lodash@4.17.21".parse().unwrap()),
+        )]);
+        create_symlink_layout(&dependencies, &virtual_root, &virtual_node_modules_dir).unwrap();
+        assert_eq!(
+            fs::read_link(virtual_node_modules_dir.join("my-lodash")).unwrap(),
+            virtual_root.join("lodash@4.17.21/node_modules/lodash"),
+        );
+    }
+
+    #[test]
+    fn should_skip_self_dependency() {
+        let dir = tempdir().unwrap();
+        let virtual_root = dir.path().join("node_modules/.pnpm");
+        let virtual_node_modules_dir = virtual_root.join("foo@1.0.0/node_modules");
+        fs::create_dir_all(virtual_node_modules_dir.join("foo")).unwrap();
+
+        let dependencies = HashMap::from([
+            (
+                "foo".parse::<PkgName>().unwrap(),
+                PackageSnapshotDependency::PkgVerPeer("1.0.0".parse().unwrap()),
+            ),
+            (
+                "bar".parse::<PkgName>().unwrap(),
+                PackageSnapshotDependency::PkgVerPeer("2.0.0".parse().unwrap()),
+            ),
+        ]);
+        create_symlink_layout(&dependencies, &virtual_root, &virtual_node_modules_dir).unwrap();
+        assert!(fs::symlink_metadata(virtual_node_modules_dir.join("foo")).unwrap().is_dir());
+        assert_eq!(
+            fs::read_link(virtual_node_modules_dir.join("bar")).unwrap(),
+            virtual_root.join("bar@2.0.0/node_modules/bar"),
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{create_cas_files, create_symlink_layout, CreateCasFilesError};
+use crate::{create_cas_files, create_symlink_layout, CreateCasFilesError, SymlinkPackageError};
 use derive_more::{Display, Error};
 use miette::Diagnostic;
 use pacquet_lockfile::{DependencyPath, PackageSnapshot};
@@ -32,6 +32,9 @@ pub enum CreateVirtualDirError {
 
     #[diagnostic(transparent)]
     CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
+    SymlinkLayout(#[error(source)] SymlinkPackageError),
 }
 
 impl<'a> CreateVirtualDirBySnapshot<'a> {
@@ -65,8 +68,103 @@ impl<'a> CreateVirtualDirBySnapshot<'a> {
         // 2. Create the symlink layout
         if let Some(dependencies) = &package_snapshot.dependencies {
             create_symlink_layout(dependencies, virtual_store_dir, &virtual_node_modules_dir)
+                .map_err(CreateVirtualDirError::SymlinkLayout)?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_fail_when_virtual_store_dir_cannot_be_created() {
+        let dir = tempdir().unwrap();
+        let occupied = dir.path().join("occupied");
+        fs::write(&occupied, "not a directory").unwrap();
+        let virtual_store_dir = occupied.join(".pnpm");
+
+        let dependency_path: DependencyPath = "/foo@1.0.0".parse().unwrap();
+        let package_snapshot: PackageSnapshot = serde_json::from_str(
+            r#"{ "resolution": { "type": "directory", "directory": "foo" } }"#,
+        )
+        .unwrap();
+        let error = CreateVirtualDirBySnapshot {
+            virtual_store_dir: &virtual_store_dir,
+            cas_paths: &HashMap::new(),
+            import_method: PackageImportMethod::Auto,
+            dependency_path: &dependency_path,
+            package_snapshot: &package_snapshot,
+        }
+        .run()
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            CreateVirtualDirError::CreateNodeModulesDir { dir, .. }
+                if dir == virtual_store_dir.join("foo@1.0.0/node_modules")
+        ));
+    }
+
+    #[test]
+    fn should_tolerate_concurrent_creation_of_the_same_dependency() {
+        let dir = tempdir().unwrap();
+        let store_dir = dir.path().join("store");
+        fs::create_dir_all(&store_dir).unwrap();
+        let cas_paths: HashMap<_, _> = (0..32)
+            .map(|index| {
+                let store_path = store_dir.join(format!("{index:02}"));
+                fs::write(&store_path, format!("file {index}")).unwrap();
+                (format!("lib/{index}.js"), store_path)
+            })
+            .collect();
+        let virtual_store_dir = dir.path().join("node_modules/.pnpm");
+        let dependency_path: DependencyPath = "/foo@1.0.0".parse().unwrap();
+        let package_snapshot: PackageSnapshot = serde_json::from_str(
+            r#"{
+                "resolution": { "type": "directory", "directory": "foo" },
+                "dependencies": { "bar": "1.0.0", "baz": "2.0.0" }
+            }"#,
+        )
+        .unwrap();
+
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..16)
+                .map(|_| {
+                    scope.spawn(|| {
+                        CreateVirtualDirBySnapshot {
+                            virtual_store_dir: &virtual_store_dir,
+                            cas_paths: &cas_paths,
+                            import_method: PackageImportMethod::Auto,
+                            dependency_path: &dependency_path,
+                            package_snapshot: &package_snapshot,
+                        }
+                        .run()
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap().unwrap();
+            }
+        });
+
+        let virtual_node_modules_dir = virtual_store_dir.join("foo@1.0.0/node_modules");
+        for (entry, store_path) in &cas_paths {
+            assert_eq!(
+                fs::read_to_string(virtual_node_modules_dir.join("foo").join(entry)).unwrap(),
+                fs::read_to_string(store_path).unwrap(),
+            );
+        }
+        assert_eq!(
+            fs::read_link(virtual_node_modules_dir.join("bar")).unwrap(),
+            virtual_store_dir.join("bar@1.0.0/node_modules/bar"),
+        );
+        assert_eq!(
+            fs::read_link(virtual_node_modules_dir.join("baz")).unwrap(),
+            virtual_store_dir.join("baz@2.0.0/node_modules/baz"),
+        );
+    }
+}
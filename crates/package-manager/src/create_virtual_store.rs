@@ -1,8 +1,9 @@
-use crate::InstallPackageBySnapshot;
+use crate::{InstallPackageBySnapshot, InstallPackageBySnapshotError};
 use futures_util::future;
 use pacquet_lockfile::{DependencyPath, PackageSnapshot, RootProjectSnapshot};
 use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
+use pacquet_reporter::Reporter;
 use pipe_trait::Pipe;
 use std::collections::HashMap;
 
@@ -11,29 +12,38 @@ use std::collections::HashMap;
 pub struct CreateVirtualStore<'a> {
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub packages: Option<&'a HashMap<DependencyPath, PackageSnapshot>>,
     pub project_snapshot: &'a RootProjectSnapshot,
 }
 
 impl<'a> CreateVirtualStore<'a> {
     /// Execute the subroutine.
-    pub async fn run(self) {
-        let CreateVirtualStore { http_client, config, packages, project_snapshot } = self;
+    pub async fn run(self) -> Result<(), InstallPackageBySnapshotError> {
+        let CreateVirtualStore { http_client, config, reporter, packages, project_snapshot } = self;
 
-        let packages = packages.unwrap_or_else(|| {
-            dbg!(project_snapshot);
-            todo!("check project_snapshot, error if it's not empty, do nothing if empty");
-        });
+        // a lockfile without packages has nothing to put into the virtual store
+        let Some(packages) = packages else {
+            tracing::debug!(target: "pacquet::install", ?project_snapshot, "No packages in the lockfile");
+            return Ok(());
+        };
 
         packages
             .iter()
             .map(|(dependency_path, package_snapshot)| async move {
-                InstallPackageBySnapshot { http_client, config, dependency_path, package_snapshot }
-                    .run()
-                    .await
-                    .unwrap(); // TODO: properly propagate this error
+                InstallPackageBySnapshot {
+                    http_client,
+                    config,
+                    reporter,
+                    dependency_path,
+                    package_snapshot,
+                }
+                .run()
+                .await
             })
-            .pipe(future::join_all)
-            .await;
+            .pipe(future::try_join_all)
+            .await?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,63 @@
+use crate::{FetchPackageBySnapshot, FetchPackageBySnapshotError};
+use derive_more::{Display, Error};
+use futures_util::future;
+use miette::Diagnostic;
+use pacquet_lockfile::{DependencyPath, PackageSnapshot};
+use pacquet_network::ThrottledClient;
+use pacquet_npmrc::Npmrc;
+use pacquet_reporter::{Event, Reporter};
+use pipe_trait::Pipe;
+use std::collections::HashMap;
+
+/// This subroutine does everything `pacquet fetch` is supposed to do.
+///
+/// Every package in the lockfile is downloaded and extracted into the store directory.
+/// Neither the virtual store nor `node_modules` is created.
+#[must_use]
+pub struct Fetch<'a> {
+    pub http_client: &'a ThrottledClient,
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub packages: Option<&'a HashMap<DependencyPath, PackageSnapshot>>,
+}
+
+/// Error type of [`Fetch`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum FetchError {
+    #[diagnostic(transparent)]
+    FetchPackage(#[error(source)] FetchPackageBySnapshotError),
+}
+
+impl<'a> Fetch<'a> {
+    /// Execute the subroutine.
+    pub async fn run(self) -> Result<(), FetchError> {
+        let Fetch { http_client, config, reporter, packages } = self;
+
+        tracing::info!(target: "pacquet::fetch", "Start all");
+        reporter.emit(Event::ResolutionStarted);
+
+        packages
+            .into_iter()
+            .flatten()
+            .map(|(dependency_path, package_snapshot)| async move {
+                FetchPackageBySnapshot {
+                    http_client,
+                    config,
+                    reporter,
+                    dependency_path,
+                    package_snapshot,
+                }
+                .run()
+                .await
+                .map_err(FetchError::FetchPackage)
+            })
+            .pipe(future::try_join_all)
+            .await?;
+
+        tracing::info!(target: "pacquet::fetch", "Complete all");
+        reporter.emit(Event::InstallDone);
+
+        Ok(())
+    }
+}
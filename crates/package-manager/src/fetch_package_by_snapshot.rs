@@ -0,0 +1,140 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_lockfile::{DependencyPath, LockfileResolution, PackageSnapshot, PkgNameVerPeer};
+use pacquet_network::ThrottledClient;
+use pacquet_npmrc::Npmrc;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_tarball::{DownloadTarballToStore, TarballError};
+use pipe_trait::Pipe;
+use std::{borrow::Cow, collections::HashMap, path::PathBuf};
+
+/// Error type of [`FetchPackageBySnapshot`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum FetchPackageBySnapshotError {
+    #[display("{dependency_path} has no integrity in the lockfile")]
+    #[diagnostic(
+        code(pacquet_package_manager::missing_lockfile_integrity),
+        help("Add the integrity of the tarball to pnpm-lock.yaml")
+    )]
+    MissingIntegrity { dependency_path: String },
+
+    #[display("{dependency_path} is resolved from a {resolution}, which isn't supported yet")]
+    #[diagnostic(code(pacquet_package_manager::unsupported_resolution))]
+    UnsupportedResolution { dependency_path: String, resolution: &'static str },
+
+    #[diagnostic(transparent)]
+    DownloadTarball(#[error(source)] TarballError),
+}
+
+/// This subroutine downloads a package tarball from a lockfile snapshot and extracts it into the
+/// store directory.
+///
+/// The download is skipped if the package is already in the store.
+#[must_use]
+pub struct FetchPackageBySnapshot<'a> {
+    pub http_client: &'a ThrottledClient,
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub dependency_path: &'a DependencyPath,
+    pub package_snapshot: &'a PackageSnapshot,
+}
+
+impl<'a> FetchPackageBySnapshot<'a> {
+    /// Execute the subroutine.
+    ///
+    /// Return the paths of the files of the package in the store directory.
+    pub async fn run(self) -> Result<HashMap<String, PathBuf>, FetchPackageBySnapshotError> {
+        let FetchPackageBySnapshot {
+            http_client,
+            config,
+            reporter,
+            dependency_path,
+            package_snapshot,
+        } = self;
+        let PackageSnapshot { resolution, deprecated, .. } = package_snapshot;
+        let DependencyPath { custom_registry, package_specifier } = dependency_path;
+
+        let name = package_specifier.name.to_string();
+        let version = package_specifier.suffix.version().to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+        if let Some(message) = deprecated {
+            tracing::warn!(target: "pacquet::install", "{name}@{version} is deprecated: {message}");
+            reporter.emit(Event::Deprecated {
+                name: name.clone(),
+                version: version.clone(),
+                message: message.clone(),
+            });
+        }
+
+        let registry = match custom_registry {
+            Some(registry) => registry.as_str(),
+            None => config.registry_for(&package_specifier.name.to_string()),
+        };
+
+        let unsupported = |resolution| FetchPackageBySnapshotError::UnsupportedResolution {
+            dependency_path: dependency_path.to_string(),
+            resolution,
+        };
+        let (tarball_url, integrity) = match resolution {
+            LockfileResolution::Tarball(tarball_resolution)
+                if tarball_resolution.tarball.starts_with("file:") =>
+            {
+                return Err(unsupported("local tarball"));
+            }
+            LockfileResolution::Tarball(tarball_resolution) => {
+                let integrity = tarball_resolution.integrity.as_ref().ok_or_else(|| {
+                    FetchPackageBySnapshotError::MissingIntegrity {
+                        dependency_path: dependency_path.to_string(),
+                    }
+                })?;
+                (tarball_resolution.tarball.as_str().pipe(Cow::Borrowed), integrity)
+            }
+            LockfileResolution::Registry(registry_resolution) => {
+                let PkgNameVerPeer { name, suffix: ver_peer } = package_specifier;
+                let registry = registry.strip_suffix('/').unwrap_or(registry);
+                let version = ver_peer.version();
+                let bare_name = name.bare.as_str();
+                let tarball_url = format!("{registry}/{name}/-/{bare_name}-{version}.tgz");
+                let integrity = &registry_resolution.integrity;
+                (Cow::Owned(tarball_url), integrity)
+            }
+            LockfileResolution::Directory(_) => return Err(unsupported("directory")),
+            LockfileResolution::Git(_) => return Err(unsupported("git repository")),
+        };
+        let tarball_url = match config.replace_tarball_host(&tarball_url) {
+            Cow::Owned(replaced) => Cow::Owned(replaced),
+            Cow::Borrowed(_) => tarball_url,
+        };
+
+        if config.store_dir.index_file_path(integrity).is_file() {
+            reporter.emit(Event::TarballReused { name: name.clone(), version: version.clone() });
+        } else {
+            // the lockfile doesn't record the unpacked size
+            let event = Event::TarballDownloadStarted {
+                name: name.clone(),
+                version: version.clone(),
+                size: None,
+            };
+            reporter.emit(event);
+        }
+
+        let cas_paths = DownloadTarballToStore {
+            http_client,
+            store_dir: &config.store_dir,
+            package_integrity: integrity,
+            package_unpacked_size: None,
+            package_url: &tarball_url,
+            registry,
+            recorded_integrity: None, // `integrity` is the recorded one
+            verify_store_integrity: config.verify_store_integrity,
+        }
+        .run_without_mem_cache()
+        .await
+        .map_err(FetchPackageBySnapshotError::DownloadTarball)?;
+
+        reporter.emit(Event::TarballDownloaded { name, version, url: tarball_url.to_string() });
+
+        Ok(cas_paths)
+    }
+}
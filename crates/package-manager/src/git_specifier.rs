@@ -0,0 +1,234 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_lockfile::GitResolution;
+use std::{io, process::Command};
+
+/// Which revision of a repository a [`GitSpecifier`] points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitReference {
+    /// No `#` suffix: the default branch.
+    Head,
+    /// `#{commit}` with a full commit hash.
+    Commit(String),
+    /// `#semver:{range}`: the highest tag that satisfies the range.
+    Semver(String),
+    /// `#{name}`: a branch or a tag.
+    Named(String),
+}
+
+/// A dependency specifier that points to a git repository, such as `github:user/repo#commit` or
+/// `git+https://example.com/repo.git#semver:^1.0.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSpecifier {
+    /// URL that can be passed to `git clone`.
+    pub repo: String,
+    pub reference: GitReference,
+}
+
+/// Error type of [`GitSpecifier::resolve`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum ResolveGitError {
+    #[display("Failed to run git: {_0}")]
+    #[diagnostic(code(pacquet_package_manager::run_git), help("Is git installed?"))]
+    RunGit(#[error(source)] io::Error),
+
+    #[display("git {command} failed: {stderr}")]
+    #[diagnostic(code(pacquet_package_manager::git_failed))]
+    GitFailed { command: String, stderr: String },
+
+    #[display("No revision of {repo} matches {reference:?}")]
+    #[diagnostic(code(pacquet_package_manager::no_matching_git_revision))]
+    NoMatchingRevision { repo: String, reference: GitReference },
+}
+
+impl GitSpecifier {
+    /// Parse `specifier`, returning `None` if it doesn't point to a git repository.
+    ///
+    /// A repository that starts with `-` is refused, as git would take it for an option.
+    pub fn parse(specifier: &str) -> Option<Self> {
+        let (repo, reference) = match specifier.split_once('#') {
+            Some((repo, reference)) => (repo, Some(reference)),
+            None => (specifier, None),
+        };
+
+        let repo = if let Some(path) = repo.strip_prefix("github:") {
+            format!("https://github.com/{path}.git")
+        } else if let Some(url) = repo.strip_prefix("git+") {
+            url.to_string()
+        } else if repo.starts_with("git://") {
+            repo.to_string()
+        } else {
+            return None;
+        };
+        if repo.starts_with('-') {
+            return None;
+        }
+
+        let reference = match reference {
+            None | Some("") => GitReference::Head,
+            Some(reference) => match reference.strip_prefix("semver:") {
+                Some(range) => GitReference::Semver(range.to_string()),
+                None if is_commit_hash(reference) => GitReference::Commit(reference.to_string()),
+                None => GitReference::Named(reference.to_string()),
+            },
+        };
+
+        Some(GitSpecifier { repo, reference })
+    }
+
+    /// Pin the specifier to a commit by querying the refs of the remote repository.
+    pub fn resolve(&self) -> Result<GitResolution, ResolveGitError> {
+        let GitSpecifier { repo, reference } = self;
+        let resolution =
+            |commit: &str| GitResolution { repo: repo.clone(), commit: commit.to_string() };
+
+        if let GitReference::Commit(commit) = reference {
+            return Ok(resolution(commit));
+        }
+
+        let output = run_git(&["ls-remote", "--", repo])?;
+        let refs = parse_ls_remote(&output);
+        let no_match = || ResolveGitError::NoMatchingRevision {
+            repo: repo.clone(),
+            reference: reference.clone(),
+        };
+
+        let commit = match reference {
+            GitReference::Commit(_) => unreachable!("returned above"),
+            GitReference::Head => find_ref(&refs, "HEAD"),
+            GitReference::Named(name) => find_ref(&refs, &format!("refs/heads/{name}"))
+                .or_else(|| find_ref(&refs, &format!("refs/tags/{name}"))),
+            GitReference::Semver(range) => {
+                let range: node_semver::Range = range.parse().map_err(|_| no_match())?;
+                refs.iter()
+                    .filter_map(|(_, name)| {
+                        let tag = name.strip_prefix("refs/tags/")?;
+                        let tag = tag.strip_suffix("^{}").unwrap_or(tag);
+                        let version = tag.strip_prefix('v').unwrap_or(tag);
+                        let version: node_semver::Version = version.parse().ok()?;
+                        version.satisfies(&range).then_some((version, tag))
+                    })
+                    .max_by(|(a, _), (b, _)| a.cmp(b))
+                    .and_then(|(_, tag)| find_ref(&refs, &format!("refs/tags/{tag}")))
+            }
+        };
+
+        commit.map(resolution).ok_or_else(no_match)
+    }
+}
+
+/// Run `git` with `args` and return its stdout.
+pub(crate) fn run_git(args: &[&str]) -> Result<String, ResolveGitError> {
+    let output = Command::new("git").args(args).output().map_err(ResolveGitError::RunGit)?;
+    if !output.status.success() {
+        return Err(ResolveGitError::GitFailed {
+            command: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn is_commit_hash(reference: &str) -> bool {
+    reference.len() == 40 && reference.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Parse the output of `git ls-remote` into pairs of commit and ref name.
+fn parse_ls_remote(output: &str) -> Vec<(&str, &str)> {
+    output.lines().filter_map(|line| line.split_once('\t')).collect()
+}
+
+/// Find the commit of `name`, preferring the peeled commit (`{name}^{}`) of an annotated tag.
+fn find_ref<'a>(refs: &[(&'a str, &str)], name: &str) -> Option<&'a str> {
+    let peeled = format!("{name}^{{}}");
+    let find =
+        |name: &str| refs.iter().find(|(_, ref_name)| *ref_name == name).map(|(commit, _)| *commit);
+    find(&peeled).or_else(|| find(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pacquet_testing_utils::git::init_git_repo;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse() {
+        let case = |specifier: &str, expected: Option<(&str, GitReference)>| {
+            eprintln!("CASE: {specifier:?}");
+            let expected = expected
+                .map(|(repo, reference)| GitSpecifier { repo: repo.to_string(), reference });
+            assert_eq!(GitSpecifier::parse(specifier), expected);
+        };
+
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        case(
+            &format!("github:user/repo#{commit}"),
+            Some(("https://github.com/user/repo.git", GitReference::Commit(commit.to_string()))),
+        );
+        case("github:user/repo", Some(("https://github.com/user/repo.git", GitReference::Head)));
+        case(
+            "git+https://example.com/repo.git#semver:^1.0.0",
+            Some(("https://example.com/repo.git", GitReference::Semver("^1.0.0".to_string()))),
+        );
+        case(
+            "git://example.com/repo.git#main",
+            Some(("git://example.com/repo.git", GitReference::Named("main".to_string()))),
+        );
+        case("^1.0.0", None);
+        case("file:foo.tgz", None);
+        case("git+--upload-pack=touch /tmp/pwned", None);
+    }
+
+    #[test]
+    fn resolve_should_not_pass_the_repo_as_an_option() {
+        let dir = tempdir().unwrap();
+        let marker = dir.path().join("pwned");
+        let specifier = GitSpecifier {
+            repo: format!("--upload-pack=touch {}", marker.display()),
+            reference: GitReference::Head,
+        };
+        let error = specifier.resolve().expect_err("the repo doesn't exist");
+        dbg!(&error);
+        assert!(matches!(error, ResolveGitError::GitFailed { .. }));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn find_ref_should_prefer_peeled_tags() {
+        let output =
+            "aaa\tHEAD\nbbb\trefs/heads/main\nccc\trefs/tags/v1.0.0\nddd\trefs/tags/v1.0.0^{}\n";
+        let refs = parse_ls_remote(output);
+        assert_eq!(find_ref(&refs, "HEAD"), Some("aaa"));
+        assert_eq!(find_ref(&refs, "refs/heads/main"), Some("bbb"));
+        assert_eq!(find_ref(&refs, "refs/tags/v1.0.0"), Some("ddd"));
+        assert_eq!(find_ref(&refs, "refs/tags/v2.0.0"), None);
+    }
+
+    #[test]
+    fn resolve_should_pin_commit() {
+        let dir = tempdir().unwrap();
+        let commits = init_git_repo(dir.path(), "foo", &["1.0.0", "1.1.0", "2.0.0"]);
+        let repo = format!("file://{}", dir.path().display());
+
+        let case = |reference: &str, expected: &str| {
+            eprintln!("CASE: {reference:?} => {expected:?}");
+            let specifier = GitSpecifier::parse(&format!("git+{repo}#{reference}")).unwrap();
+            assert_eq!(
+                specifier.resolve().unwrap(),
+                GitResolution { repo: repo.clone(), commit: expected.to_string() }
+            );
+        };
+
+        case("semver:^1.0.0", &commits[1]);
+        case("semver:*", &commits[2]);
+        case("v1.0.0", &commits[0]);
+        case("", &commits[2]);
+        case(&commits[0], &commits[0]);
+
+        let specifier = GitSpecifier::parse(&format!("git+{repo}#semver:^3.0.0")).unwrap();
+        assert!(matches!(specifier.resolve(), Err(ResolveGitError::NoMatchingRevision { .. })));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{symlink_package, SymlinkPackageError};
+use dashmap::DashMap;
+use pacquet_lockfile::{DependencyPath, PackageSnapshot, PkgNameVerPeer, ProjectSnapshot};
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::DependencyGroup;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Packages of an install that may be hoisted by [`HoistDependencies`], keyed by the name that
+/// their dependents require them by.
+///
+/// When several packages are required by the same name, the one closest to the root project
+/// wins. Direct dependencies have a depth of 0 and are never hoisted, they are already linked in
+/// the root `node_modules`.
+#[derive(Debug, Default)]
+pub struct HoistCandidates {
+    packages: DashMap<String, HoistCandidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct HoistCandidate {
+    depth: usize,
+    virtual_store_name: String,
+    name: String,
+}
+
+impl HoistCandidates {
+    /// Create an empty list of candidates.
+    pub fn new() -> Self {
+        HoistCandidates::default()
+    }
+
+    /// Record that the package `name` installed at `virtual_store_name` is required as `alias`,
+    /// `depth` levels below the root project.
+    pub fn insert(&self, alias: &str, depth: usize, virtual_store_name: String, name: String) {
+        let candidate = HoistCandidate { depth, virtual_store_name, name };
+        self.packages
+            .entry(alias.to_string())
+            .and_modify(|existing| {
+                if candidate < *existing {
+                    *existing = candidate.clone();
+                }
+            })
+            .or_insert(candidate);
+    }
+
+    /// Collect the packages of a lockfile that are reachable from the dependencies of
+    /// `dependency_groups` of `project_snapshot`.
+    pub fn from_lockfile(
+        project_snapshot: &ProjectSnapshot,
+        packages: Option<&HashMap<DependencyPath, PackageSnapshot>>,
+        dependency_groups: impl IntoIterator<Item = DependencyGroup>,
+    ) -> Self {
+        let candidates = HoistCandidates::new();
+        let snapshots: HashMap<&PkgNameVerPeer, &PackageSnapshot> = packages
+            .into_iter()
+            .flatten()
+            .map(|(dependency_path, snapshot)| (&dependency_path.package_specifier, snapshot))
+            .collect();
+
+        let mut queue: VecDeque<(String, PkgNameVerPeer, usize)> = project_snapshot
+            .dependencies_by_groups(dependency_groups)
+            .map(|(alias, spec)| (alias.to_string(), spec.version.to_package_specifier(alias), 0))
+            .collect();
+        let mut visited = HashSet::new();
+        while let Some((alias, package_specifier, depth)) = queue.pop_front() {
+            candidates.insert(
+                &alias,
+                depth,
+                package_specifier.to_virtual_store_name(),
+                package_specifier.name.to_string(),
+            );
+            if !visited.insert(package_specifier.clone()) {
+                continue;
+            }
+            let Some(snapshot) = snapshots.get(&package_specifier) else { continue };
+            queue.extend(snapshot.resolved_dependencies().map(|(alias, package_specifier, _)| {
+                (alias.to_string(), package_specifier, depth + 1)
+            }));
+        }
+
+        candidates
+    }
+}
+
+/// This subroutine links the transitive dependencies of an install into
+/// `node_modules/.pnpm/node_modules` (`hoist-pattern`) or into the root `node_modules`
+/// (`public-hoist-pattern`).
+///
+/// Node.js looks up the ancestors of a package for the modules that it requires, so a package in
+/// the virtual store can require the packages in `node_modules/.pnpm/node_modules` even when it
+/// doesn't declare them. The packages in the root `node_modules` are available to the project too.
+#[must_use]
+pub struct HoistDependencies<'a> {
+    pub config: &'static Npmrc,
+    pub candidates: &'a HoistCandidates,
+}
+
+impl<'a> HoistDependencies<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<(), SymlinkPackageError> {
+        let HoistDependencies { config, candidates } = self;
+
+        let everything = ["*".to_string()];
+        let public_hoist_pattern: &[String] =
+            if config.shamefully_hoist { &everything } else { &config.public_hoist_pattern };
+
+        let mut candidates: Vec<_> = candidates.packages.iter().collect();
+        candidates.sort_by(|a, b| a.key().cmp(b.key()));
+        for candidate in candidates {
+            let alias = candidate.key();
+            let HoistCandidate { depth, virtual_store_name, name } = candidate.value();
+            if *depth == 0 {
+                continue;
+            }
+
+            let symlink_dir = if matches_patterns(public_hoist_pattern, alias) {
+                config.modules_dir.clone()
+            } else if config.hoist && matches_patterns(&config.hoist_pattern, alias) {
+                config.virtual_store_dir.join("node_modules")
+            } else {
+                continue;
+            };
+
+            let symlink_target =
+                config.virtual_store_dir.join(virtual_store_name).join("node_modules").join(name);
+            tracing::debug!(target: "pacquet::hoist", ?alias, ?symlink_dir, "Hoist package");
+            symlink_package(&symlink_target, &symlink_dir.join(alias))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `name` matches the positive patterns and none of the negative (`!`) ones.
+///
+/// When every pattern is negative, the positive pattern is implied to be `*`.
+fn matches_patterns(patterns: &[String], name: &str) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let (negative, positive): (Vec<&str>, Vec<&str>) =
+        patterns.iter().map(String::as_str).partition(|pattern| pattern.starts_with('!'));
+    let included =
+        positive.is_empty() || positive.iter().any(|pattern| matches_glob(pattern, name));
+    included && !negative.iter().any(|pattern| matches_glob(&pattern[1..], name))
+}
+
+/// Match `name` against `pattern` in which `*` stands for any sequence of characters.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+    for part in parts {
+        let Some(index) = rest.find(part) else { return false };
+        rest = &rest[index + part.len()..];
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn glob() {
+        let case = |pattern: &str, name: &str, expected: bool| {
+            eprintln!("CASE: {pattern:?}, {name:?}");
+            assert_eq!(matches_glob(pattern, name), expected);
+        };
+
+        case("*", "foo", true);
+        case("*", "@scope/foo", true);
+        case("foo", "foo", true);
+        case("foo", "foobar", false);
+        case("*eslint*", "eslint", true);
+        case("*eslint*", "eslint-plugin-foo", true);
+        case("*eslint*", "@typescript-eslint/parser", true);
+        case("*eslint*", "prettier", false);
+        case("@types/*", "@types/node", true);
+        case("@types/*", "types", false);
+        case("a*b*c", "abc", true);
+        case("a*b*c", "aXbYc", true);
+        case("a*b*c", "acb", false);
+        case("ab*ba", "aba", false);
+    }
+
+    #[test]
+    fn patterns() {
+        let case = |patterns: &[&str], name: &str, expected: bool| {
+            eprintln!("CASE: {patterns:?}, {name:?}");
+            let patterns: Vec<String> =
+                patterns.iter().map(|pattern| pattern.to_string()).collect();
+            assert_eq!(matches_patterns(&patterns, name), expected);
+        };
+
+        case(&[], "foo", false);
+        case(&["*"], "foo", true);
+        case(&["*", "!foo"], "foo", false);
+        case(&["*", "!foo"], "bar", true);
+        case(&["!foo"], "bar", true);
+        case(&["!foo"], "foo", false);
+        case(&["bar"], "foo", false);
+    }
+
+    #[test]
+    fn closest_candidate_should_win() {
+        let candidates = HoistCandidates::new();
+        candidates.insert("foo", 2, "foo@2.0.0".to_string(), "foo".to_string());
+        candidates.insert("foo", 1, "foo@1.0.0".to_string(), "foo".to_string());
+        candidates.insert("foo", 3, "foo@3.0.0".to_string(), "foo".to_string());
+        let candidate = candidates.packages.get("foo").unwrap();
+        assert_eq!(candidate.virtual_store_name, "foo@1.0.0");
+    }
+}
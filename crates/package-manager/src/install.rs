@@ -1,11 +1,41 @@
-use crate::{InstallFrozenLockfile, InstallWithoutLockfile, ResolvedPackages};
-use pacquet_lockfile::Lockfile;
-use pacquet_network::ThrottledClient;
-use pacquet_npmrc::Npmrc;
+use crate::{
+    InstallFrozenLockfile, InstallFrozenLockfileError, InstallPlan, InstallWithoutLockfile,
+    InstallWithoutLockfileError, IntegrityIndex, LockfileBuilder, ModulesYaml, ModulesYamlError,
+    PackageHook, PlannedPackage, PruneModulesDir, PruneModulesDirError, ResolutionCache,
+    ResolvedPackages, TarballUrlCache, ValidateModulesDir,
+};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_lockfile::{
+    DependencyDrift, Lockfile, LockfileResolution, OutdatedLockfileError, RootProjectSnapshot,
+    SaveLockfileError,
+};
+use pacquet_network::{NetworkTimeoutError, ThrottledClient};
+use pacquet_npmrc::{NodeLinker, Npmrc};
 use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use pacquet_reporter::{Event, Reporter};
 use pacquet_tarball::MemCache;
+use pacquet_workspace::WorkspaceManifest;
+use std::{path::Path, time::Duration};
 
 /// This subroutine does everything `pacquet install` is supposed to do.
+///
+/// When installing without a lockfile, the install fails before downloading anything when the
+/// store doesn't have enough free space for the resolved packages, see
+/// [`check_free_space`](crate::check_free_space).
+///
+/// When [`network_timeout`](Npmrc::network_timeout) is set, the install is aborted once the
+/// requests in flight have made no progress for that long.
+///
+/// The settings that `node_modules` is built with are recorded in its `.modules.yaml`. When they
+/// don't match the current ones, `node_modules` is rebuilt, see [`ValidateModulesDir`].
+///
+/// When the dependencies are resolved, they are written to `pnpm-lock.yaml` next to `package.json`,
+/// unless the lockfile is disabled or only a part of the dependencies is installed (in dry-run
+/// mode, with a [`depth`](Self::depth), or with some of the dependency groups).
+///
+/// When [`prune`](Self::prune) is set, the packages that are no longer depended on are removed
+/// from `node_modules` afterwards, see [`PruneModulesDir`].
 #[must_use]
 pub struct Install<'a, DependencyGroupList>
 where
@@ -13,12 +43,66 @@ where
 {
     pub tarball_mem_cache: &'a MemCache,
     pub resolved_packages: &'a ResolvedPackages,
+    pub tarball_url_cache: &'a TarballUrlCache,
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub manifest: &'a PackageManifest,
+    pub workspace_manifest: Option<&'a WorkspaceManifest>,
+    /// Root directory of the workspace of `workspace_manifest`, where the projects that
+    /// `workspace:` dependencies refer to are looked for.
+    pub workspace_dir: Option<&'a Path>,
     pub lockfile: Option<&'a Lockfile>,
     pub dependency_groups: DependencyGroupList,
     pub frozen_lockfile: bool,
+    /// When set, nothing is downloaded, written to the store, or linked. The packages that
+    /// would be installed are recorded into the plan instead.
+    pub dry_run: Option<&'a InstallPlan>,
+    /// Rewrites the metadata of each package as it is resolved. It isn't called for packages
+    /// installed from a frozen lockfile.
+    pub package_hook: Option<&'a dyn PackageHook>,
+    /// How many levels of transitive dependencies are installed, see
+    /// [`InstallWithoutLockfile::depth`]. It is ignored when installing from a frozen lockfile.
+    pub depth: Option<usize>,
+    /// Remove the extraneous packages from `node_modules` after installing.
+    pub prune: bool,
+}
+
+/// Error type of [`Install`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallError {
+    #[diagnostic(transparent)]
+    InstallWithoutLockfile(#[error(source)] InstallWithoutLockfileError),
+
+    #[diagnostic(transparent)]
+    InstallFrozenLockfile(#[error(source)] InstallFrozenLockfileError),
+
+    #[diagnostic(transparent)]
+    OutdatedLockfile(#[error(source)] OutdatedLockfileError),
+
+    #[display("Cannot install with a frozen lockfile because pnpm-lock.yaml doesn't exist")]
+    #[diagnostic(
+        code(pacquet_package_manager::missing_lockfile),
+        help("Run `pacquet install` without `--frozen-lockfile` to resolve the dependencies")
+    )]
+    MissingLockfile,
+
+    #[display("Installing from the lockfile of a workspace isn't supported yet")]
+    #[diagnostic(code(pacquet_package_manager::unsupported_workspace_lockfile))]
+    UnsupportedWorkspaceLockfile,
+
+    #[diagnostic(transparent)]
+    NetworkTimeout(#[error(source)] NetworkTimeoutError),
+
+    #[diagnostic(transparent)]
+    ModulesYaml(#[error(source)] ModulesYamlError),
+
+    #[diagnostic(transparent)]
+    PruneModulesDir(#[error(source)] PruneModulesDirError),
+
+    #[diagnostic(transparent)]
+    SaveLockfile(#[error(source)] SaveLockfileError),
 }
 
 impl<'a, DependencyGroupList> Install<'a, DependencyGroupList>
@@ -26,64 +110,208 @@ where
     DependencyGroupList: IntoIterator<Item = DependencyGroup>,
 {
     /// Execute the subroutine.
-    pub async fn run(self) {
+    pub async fn run(self) -> Result<(), InstallError> {
+        let Install { config, http_client, reporter, .. } = self;
+        let result = match config.network_timeout {
+            None => self.install().await,
+            Some(network_timeout) => tokio::select! {
+                result = self.install() => result,
+                error = http_client.activity().stalled(Duration::from_millis(network_timeout)) => {
+                    Err(InstallError::NetworkTimeout(error))
+                }
+            },
+        };
+        if let Err(error) = &result {
+            reporter.emit(Event::Error { message: error.to_string() });
+        }
+        result
+    }
+
+    async fn install(self) -> Result<(), InstallError> {
         let Install {
             tarball_mem_cache,
             resolved_packages,
+            tarball_url_cache,
             http_client,
             config,
+            reporter,
             manifest,
+            workspace_manifest,
+            workspace_dir,
             lockfile,
             dependency_groups,
             frozen_lockfile,
+            dry_run,
+            package_hook,
+            depth,
+            prune,
         } = self;
+        let dependency_groups: Vec<_> = dependency_groups.into_iter().collect();
 
         tracing::info!(target: "pacquet::install", "Start all");
+        reporter.emit(Event::ResolutionStarted);
+
+        if dry_run.is_none() {
+            ValidateModulesDir { config, frozen_lockfile }
+                .run()
+                .map_err(InstallError::ModulesYaml)?;
+        }
+        if config.node_linker != NodeLinker::Isolated {
+            tracing::warn!(
+                target: "pacquet::install",
+                node_linker = ?config.node_linker,
+                "Only the isolated node-linker is supported, node_modules is built with it",
+            );
+        }
+
+        // an up-to-date lockfile is installed as if `--frozen-lockfile` was passed, which skips
+        // resolution entirely, unless it has packages that can't be installed from a lockfile yet
+        let frozen_lockfile = match (config.lockfile, lockfile) {
+            (true, Some(lockfile)) if !frozen_lockfile && config.prefer_frozen_lockfile => {
+                lockfile_drift(lockfile, manifest).is_empty() && is_fetchable(lockfile)
+            }
+            _ => frozen_lockfile,
+        };
 
         match (config.lockfile, frozen_lockfile, lockfile) {
-            (false, _, _) => {
+            (false, _, _) | (true, false, _) => {
+                let resolution_cache = &ResolutionCache::new();
+                let integrity_index = lockfile.map(IntegrityIndex::new);
+                let whole_project =
+                    [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional]
+                        .iter()
+                        .all(|group| dependency_groups.contains(group));
+                let lockfile_builder =
+                    (config.lockfile && dry_run.is_none() && depth.is_none() && whole_project)
+                        .then(LockfileBuilder::new);
                 InstallWithoutLockfile {
                     tarball_mem_cache,
                     resolved_packages,
+                    tarball_url_cache,
+                    resolution_cache,
                     http_client,
                     config,
+                    reporter,
                     manifest,
-                    dependency_groups,
+                    workspace_manifest,
+                    workspace_dir,
+                    dependency_groups: dependency_groups.iter().copied(),
+                    dry_run,
+                    package_hook,
+                    depth,
+                    integrity_index: integrity_index.as_ref(),
+                    lockfile_builder: lockfile_builder.as_ref(),
                 }
                 .run()
-                .await;
-            }
-            (true, false, Some(_)) | (true, false, None) | (true, true, None) => {
-                unimplemented!();
+                .await
+                .map_err(InstallError::InstallWithoutLockfile)?;
+
+                if let Some(lockfile_builder) = lockfile_builder {
+                    let project_dir = manifest.dir();
+                    lockfile_builder
+                        .build(config, manifest)
+                        .save_to_dir(project_dir)
+                        .map_err(InstallError::SaveLockfile)?;
+                }
             }
+            (true, true, None) => return Err(InstallError::MissingLockfile),
             (true, true, Some(lockfile)) => {
                 let Lockfile { lockfile_version, project_snapshot, packages, .. } = lockfile;
                 assert_eq!(lockfile_version.major, 6); // compatibility check already happens at serde, but this still helps preventing programmer mistakes.
+                if let RootProjectSnapshot::Multi(_) = project_snapshot {
+                    return Err(InstallError::UnsupportedWorkspaceLockfile);
+                }
 
-                InstallFrozenLockfile {
-                    http_client,
-                    config,
-                    project_snapshot,
-                    packages: packages.as_ref(),
-                    dependency_groups,
+                let drift = lockfile_drift(lockfile, manifest);
+                if !drift.is_empty() {
+                    return Err(InstallError::OutdatedLockfile(OutdatedLockfileError { drift }));
+                }
+
+                if let Some(plan) = dry_run {
+                    for dependency_path in packages.iter().flat_map(|packages| packages.keys()) {
+                        let specifier = &dependency_path.package_specifier;
+                        let package = PlannedPackage {
+                            name: specifier.name.to_string(),
+                            version: specifier.suffix.version().to_string(),
+                            unpacked_size: None,
+                            file_count: None,
+                            integrity: None,
+                        };
+                        plan.insert(specifier.to_virtual_store_name(), package);
+                    }
+                } else {
+                    InstallFrozenLockfile {
+                        http_client,
+                        config,
+                        reporter,
+                        project_snapshot,
+                        packages: packages.as_ref(),
+                        dependency_groups: dependency_groups.iter().copied(),
+                    }
+                    .run()
+                    .await
+                    .map_err(InstallError::InstallFrozenLockfile)?;
                 }
-                .run()
-                .await;
             }
         }
 
+        if prune && dry_run.is_none() {
+            PruneModulesDir {
+                config,
+                reporter,
+                manifest,
+                dependency_groups: dependency_groups.iter().copied(),
+            }
+            .run()
+            .map_err(InstallError::PruneModulesDir)?;
+        }
+
+        if dry_run.is_none() {
+            ModulesYaml::from_config(config)
+                .save(&config.modules_dir)
+                .map_err(InstallError::ModulesYaml)?;
+        }
+
         tracing::info!(target: "pacquet::install", "Complete all");
+        reporter.emit(Event::InstallDone);
+
+        Ok(())
     }
 }
 
+/// Differences between the dependencies of `manifest` and the root project of `lockfile`.
+fn lockfile_drift(lockfile: &Lockfile, manifest: &PackageManifest) -> Vec<DependencyDrift> {
+    // TODO: check the importers of a workspace lockfile
+    let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+        return Vec::new();
+    };
+    let groups = [DependencyGroup::Prod, DependencyGroup::Dev, DependencyGroup::Optional];
+    project_snapshot.drift_from_manifest(manifest, groups)
+}
+
+/// Whether [`InstallFrozenLockfile`] can fetch every package of `lockfile`, which is needed to
+/// install it frozen.
+pub fn is_fetchable(lockfile: &Lockfile) -> bool {
+    lockfile.packages.iter().flatten().all(|(_, package)| match &package.resolution {
+        LockfileResolution::Registry(_) => true,
+        LockfileResolution::Tarball(resolution) => !resolution.tarball.starts_with("file:"),
+        LockfileResolution::Directory(_) | LockfileResolution::Git(_) => false,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use pacquet_npmrc::Npmrc;
     use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+    use pacquet_registry::PackageVersion;
     use pacquet_registry_mock::AutoMockInstance;
-    use pacquet_testing_utils::fs::{get_all_folders, is_symlink_or_junction};
-    use std::env;
+    use pacquet_reporter::DefaultReporter;
+    use pacquet_testing_utils::{
+        fake_registry::{FakePackage, FakeRegistry},
+        fs::{get_all_folders, is_symlink_or_junction},
+    };
+    use std::{env, path::PathBuf};
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -117,7 +345,10 @@ mod tests {
             tarball_mem_cache: &Default::default(),
             http_client: &Default::default(),
             config,
+            reporter: &DefaultReporter,
             manifest: &manifest,
+            workspace_manifest: None,
+            workspace_dir: None,
             lockfile: None,
             dependency_groups: [
                 DependencyGroup::Prod,
@@ -126,9 +357,15 @@ mod tests {
             ],
             frozen_lockfile: false,
             resolved_packages: &Default::default(),
+            tarball_url_cache: &Default::default(),
+            dry_run: None,
+            package_hook: None,
+            depth: None,
+            prune: true,
         }
         .run()
-        .await;
+        .await
+        .unwrap();
 
         // Make sure the package is installed
         let path = project_root.join("node_modules/@pnpm.e2e/hello-world-js-bin");
@@ -145,4 +382,200 @@ mod tests {
 
         drop((dir, mock_instance)); // cleanup
     }
+
+    /// What the tests that install from a [`FakeRegistry`] change about the install.
+    #[derive(Default)]
+    struct FakeInstall<'a> {
+        ignore_pnpmfile: bool,
+        package_hook: Option<&'a dyn PackageHook>,
+        resolved_packages: Option<&'a ResolvedPackages>,
+        depth: Option<usize>,
+    }
+
+    impl<'a> FakeInstall<'a> {
+        /// Install the `dependencies` of a project in `dir` from `registry` and return the
+        /// virtual store directory.
+        async fn run(
+            self,
+            dir: &Path,
+            registry: &FakeRegistry,
+            dependencies: &[(&str, &str)],
+        ) -> PathBuf {
+            let FakeInstall { ignore_pnpmfile, package_hook, resolved_packages, depth } = self;
+            let project_root = dir.join("project");
+            let modules_dir = project_root.join("node_modules");
+            let virtual_store_dir = modules_dir.join(".pnpm");
+
+            std::fs::create_dir_all(&project_root).unwrap();
+            let manifest_path = project_root.join("package.json");
+            let mut manifest = PackageManifest::create_if_needed(manifest_path).unwrap();
+            for (name, version_range) in dependencies {
+                manifest.add_dependency(name, version_range, DependencyGroup::Prod).unwrap();
+            }
+
+            let mut config = Npmrc::new();
+            config.store_dir = dir.join("pacquet-store").into();
+            config.modules_dir = modules_dir;
+            config.virtual_store_dir = virtual_store_dir.clone();
+            config.registry = registry.url();
+            config.ignore_pnpmfile = ignore_pnpmfile;
+            let config = config.leak();
+
+            Install {
+                tarball_mem_cache: &Default::default(),
+                http_client: &Default::default(),
+                config,
+                reporter: &DefaultReporter,
+                manifest: &manifest,
+                workspace_manifest: None,
+                workspace_dir: None,
+                lockfile: None,
+                dependency_groups: [DependencyGroup::Prod],
+                frozen_lockfile: false,
+                resolved_packages: resolved_packages.unwrap_or(&Default::default()),
+                tarball_url_cache: &Default::default(),
+                dry_run: None,
+                package_hook,
+                depth,
+                prune: true,
+            }
+            .run()
+            .await
+            .unwrap();
+
+            virtual_store_dir
+        }
+    }
+
+    /// Hook that removes the dependency on `bar` of every package.
+    struct RemoveBar;
+
+    impl PackageHook for RemoveBar {
+        fn read_package(&self, package: &mut PackageVersion) {
+            if let Some(dependencies) = &mut package.dependencies {
+                dependencies.remove("bar");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn package_hook_should_rewrite_dependencies() {
+        let registry = FakeRegistry::start(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0"), ("baz", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+            FakePackage::new("baz", "1.0.0"),
+        ]);
+        let dir = tempdir().unwrap();
+
+        let virtual_store_dir =
+            FakeInstall { package_hook: Some(&RemoveBar), ..Default::default() }
+                .run(dir.path(), &registry, &[("foo", "^1.0.0")])
+                .await;
+
+        let foo_node_modules = virtual_store_dir.join("foo@1.0.0/node_modules");
+        assert!(is_symlink_or_junction(&foo_node_modules.join("baz")).unwrap());
+        assert!(!foo_node_modules.join("bar").exists());
+        assert!(!virtual_store_dir.join("bar@1.0.0").exists());
+        assert!(!registry.mock("/bar").matched());
+
+        drop(dir); // cleanup
+    }
+
+    #[tokio::test]
+    async fn ignore_pnpmfile_should_bypass_package_hook() {
+        let registry = FakeRegistry::start(&[
+            FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+            FakePackage::new("bar", "1.0.0"),
+        ]);
+        let dir = tempdir().unwrap();
+
+        let virtual_store_dir = FakeInstall {
+            ignore_pnpmfile: true,
+            package_hook: Some(&RemoveBar),
+            ..Default::default()
+        }
+        .run(dir.path(), &registry, &[("foo", "^1.0.0")])
+        .await;
+
+        let foo_node_modules = virtual_store_dir.join("foo@1.0.0/node_modules");
+        assert!(is_symlink_or_junction(&foo_node_modules.join("bar")).unwrap());
+        assert!(virtual_store_dir.join("bar@1.0.0/node_modules/bar/package.json").exists());
+
+        drop(dir); // cleanup
+    }
+
+    #[tokio::test]
+    async fn depth_should_count_from_the_shallowest_level() {
+        let registry = FakeRegistry::start(&[
+            FakePackage::new("a", "1.0.0").dependencies([("b", "^1.0.0")]),
+            FakePackage::new("b", "1.0.0").dependencies([("c", "^1.0.0")]),
+            FakePackage::new("c", "1.0.0"),
+        ]);
+        let dir = tempdir().unwrap();
+
+        // as if `a` had already been reached through another package, one level deeper
+        let resolved_packages = ResolvedPackages::new();
+        resolved_packages.insert("a@1.0.0".to_string(), 1);
+
+        let virtual_store_dir = FakeInstall {
+            resolved_packages: Some(&resolved_packages),
+            depth: Some(2),
+            ..Default::default()
+        }
+        .run(dir.path(), &registry, &[("a", "^1.0.0")])
+        .await;
+
+        let path = virtual_store_dir.join("b@1.0.0/node_modules/c");
+        assert!(is_symlink_or_junction(&path).unwrap());
+        assert_eq!(resolved_packages.get("a@1.0.0").as_deref(), Some(&0));
+
+        drop(dir); // cleanup
+    }
+
+    #[tokio::test]
+    async fn should_fetch_shared_dependency_once() {
+        let registry = FakeRegistry::start(&[
+            FakePackage::new("a", "1.0.0").dependencies([("c", "^1.0.0")]),
+            FakePackage::new("b", "1.0.0").dependencies([("c", "^1.0.0")]),
+            FakePackage::new("c", "1.0.0"),
+        ]);
+        let dir = tempdir().unwrap();
+
+        let virtual_store_dir = FakeInstall::default()
+            .run(dir.path(), &registry, &[("a", "^1.0.0"), ("b", "^1.0.0")])
+            .await;
+
+        for dependent in ["a@1.0.0", "b@1.0.0"] {
+            let path = virtual_store_dir.join(dependent).join("node_modules/c");
+            assert!(is_symlink_or_junction(&path).unwrap());
+        }
+        registry.mock("/c").assert(); // exactly one hit
+
+        drop(dir); // cleanup
+    }
+
+    #[tokio::test]
+    async fn should_fetch_dependency_with_different_ranges_once() {
+        let registry = FakeRegistry::start(&[
+            FakePackage::new("a", "1.0.0").dependencies([("c", "^1.0.0")]),
+            FakePackage::new("b", "1.0.0").dependencies([("c", "~1.2.0")]),
+            FakePackage::new("c", "1.2.3"),
+            FakePackage::new("c", "1.3.0"),
+        ]);
+        let dir = tempdir().unwrap();
+
+        let virtual_store_dir = FakeInstall::default()
+            .run(dir.path(), &registry, &[("a", "^1.0.0"), ("b", "^1.0.0")])
+            .await;
+
+        for (dependent, dependency) in [("a@1.0.0", "c@1.3.0"), ("b@1.0.0", "c@1.2.3")] {
+            let path = virtual_store_dir.join(dependent).join("node_modules/c");
+            let target = std::fs::canonicalize(path).unwrap();
+            let expected = virtual_store_dir.join(dependency).join("node_modules/c");
+            assert_eq!(target, std::fs::canonicalize(expected).unwrap());
+        }
+        registry.mock("/c").assert(); // exactly one hit, for both ranges
+
+        drop(dir); // cleanup
+    }
 }
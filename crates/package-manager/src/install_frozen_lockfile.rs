@@ -1,12 +1,20 @@
-use crate::{CreateVirtualStore, SymlinkDirectDependencies};
+use crate::{
+    CreateVirtualStore, HoistCandidates, HoistDependencies, InstallPackageBySnapshotError,
+    SymlinkDirectDependencies, SymlinkPackageError,
+};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
 use pacquet_lockfile::{DependencyPath, PackageSnapshot, RootProjectSnapshot};
 use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
 use pacquet_package_manifest::DependencyGroup;
+use pacquet_reporter::Reporter;
 use std::collections::HashMap;
 
 /// This subroutine installs dependencies from a frozen lockfile.
 ///
+/// The caller is responsible for checking that the lockfile is up to date with `package.json`.
+///
 /// **Brief overview:**
 /// * Iterate over each package in [`Self::packages`].
 /// * Fetch a tarball of each package.
@@ -14,6 +22,7 @@ use std::collections::HashMap;
 /// * Import (by reflink, hardlink, or copy) the files from the store dir to each `node_modules/.pacquet/{name}@{version}/node_modules/{name}/`.
 /// * Create dependency symbolic links in each `node_modules/.pacquet/{name}@{version}/node_modules/`.
 /// * Create a symbolic link at each `node_modules/{name}`.
+/// * Hoist the transitive dependencies, see [`HoistDependencies`].
 #[must_use]
 pub struct InstallFrozenLockfile<'a, DependencyGroupList>
 where
@@ -21,31 +30,65 @@ where
 {
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub project_snapshot: &'a RootProjectSnapshot,
     pub packages: Option<&'a HashMap<DependencyPath, PackageSnapshot>>,
     pub dependency_groups: DependencyGroupList,
 }
 
+/// Error type of [`InstallFrozenLockfile`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallFrozenLockfileError {
+    #[diagnostic(transparent)]
+    CreateVirtualStore(#[error(source)] InstallPackageBySnapshotError),
+
+    #[diagnostic(transparent)]
+    SymlinkDirectDependencies(#[error(source)] SymlinkPackageError),
+
+    #[diagnostic(transparent)]
+    HoistDependencies(#[error(source)] SymlinkPackageError),
+}
+
 impl<'a, DependencyGroupList> InstallFrozenLockfile<'a, DependencyGroupList>
 where
     DependencyGroupList: IntoIterator<Item = DependencyGroup>,
 {
     /// Execute the subroutine.
-    pub async fn run(self) {
+    pub async fn run(self) -> Result<(), InstallFrozenLockfileError> {
         let InstallFrozenLockfile {
             http_client,
             config,
+            reporter,
             project_snapshot,
             packages,
             dependency_groups,
         } = self;
 
-        // TODO: check if the lockfile is out-of-date
+        CreateVirtualStore { http_client, config, reporter, packages, project_snapshot }
+            .run()
+            .await
+            .map_err(InstallFrozenLockfileError::CreateVirtualStore)?;
 
-        assert!(config.prefer_frozen_lockfile, "Non frozen lockfile is not yet supported");
+        let dependency_groups: Vec<DependencyGroup> = dependency_groups.into_iter().collect();
+        SymlinkDirectDependencies {
+            config,
+            reporter,
+            project_snapshot,
+            dependency_groups: dependency_groups.iter().copied(),
+        }
+        .run()
+        .map_err(InstallFrozenLockfileError::SymlinkDirectDependencies)?;
 
-        CreateVirtualStore { http_client, config, packages, project_snapshot }.run().await;
+        // TODO: hoist the dependencies of a workspace lockfile
+        if let RootProjectSnapshot::Single(project_snapshot) = project_snapshot {
+            let candidates =
+                HoistCandidates::from_lockfile(project_snapshot, packages, dependency_groups);
+            HoistDependencies { config, candidates: &candidates }
+                .run()
+                .map_err(InstallFrozenLockfileError::HoistDependencies)?;
+        }
 
-        SymlinkDirectDependencies { config, project_snapshot, dependency_groups }.run();
+        Ok(())
     }
 }
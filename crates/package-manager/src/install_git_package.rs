@@ -0,0 +1,301 @@
+use crate::{
+    create_cas_files, git_specifier::run_git, package_files, symlink_package, CreateCasFilesError,
+    PackageFilesError, ResolveGitError, SymlinkPackageError,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STD, Engine};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_executor::{execute_script, ExecutorError};
+use pacquet_fs::file_mode;
+use pacquet_lockfile::GitResolution;
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackageVersion;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_store_dir::{
+    PackageFileInfo, PackageFilesIndex, StoreDir, WriteCasFileError, WriteIndexFileError,
+};
+use serde_json::Value;
+use ssri::{Algorithm, Integrity, IntegrityOpts};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Error type of [`fetch_git_package`] and [`InstallGitPackage`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallGitPackageError {
+    #[display("Failed to create a temporary directory: {_0}")]
+    #[diagnostic(code(pacquet_package_manager::create_temp_dir))]
+    CreateTempDir(#[error(source)] io::Error),
+
+    #[diagnostic(transparent)]
+    Git(#[error(source)] ResolveGitError),
+
+    #[display("{commit:?} is not a commit of {repo}")]
+    #[diagnostic(code(pacquet_package_manager::invalid_git_commit))]
+    InvalidCommit { repo: String, commit: String },
+
+    #[display("Failed to read {path:?} from the repository: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_git_file))]
+    ReadFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[diagnostic(transparent)]
+    WriteCasFile(#[error(source)] WriteCasFileError),
+
+    #[display("The repository {repo} has no package.json")]
+    #[diagnostic(code(pacquet_package_manager::missing_package_json))]
+    MissingPackageJson { repo: String },
+
+    #[display("Failed to parse the package.json of {repo}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::parse_package_json))]
+    ParsePackageJson {
+        repo: String,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+
+    #[display("The prepare script of {repo} failed: {error}")]
+    #[diagnostic(code(pacquet_package_manager::prepare_git_package))]
+    Prepare {
+        repo: String,
+        #[error(source)]
+        error: ExecutorError,
+    },
+
+    #[diagnostic(transparent)]
+    PackageFiles(#[error(source)] PackageFilesError),
+
+    #[diagnostic(transparent)]
+    WriteIndexFile(#[error(source)] WriteIndexFileError),
+
+    #[diagnostic(transparent)]
+    CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
+    SymlinkPackage(#[error(source)] SymlinkPackageError),
+}
+
+/// Check out `resolution` into a temporary directory, write its files into the store, and read
+/// its `package.json`.
+///
+/// If the package has a `prepare` script, the script is run in the checkout and only the files
+/// that the package would publish are written. These files are indexed in the store under a key
+/// of the commit and the devDependencies, so that the script runs only once per commit.
+pub fn fetch_git_package(
+    store_dir: &StoreDir,
+    resolution: &GitResolution,
+) -> Result<(PackageVersion, HashMap<String, PathBuf>), InstallGitPackageError> {
+    let GitResolution { repo, commit } = resolution;
+    // `checkout` can't take a revision after `--`, so a commit that git would take for an option
+    // is refused instead
+    if commit.starts_with('-') {
+        return Err(InstallGitPackageError::InvalidCommit {
+            repo: repo.clone(),
+            commit: commit.clone(),
+        });
+    }
+    let checkout = tempfile::tempdir().map_err(InstallGitPackageError::CreateTempDir)?;
+    let checkout_dir = checkout.path().to_str().expect("temporary path must be valid UTF-8");
+    run_git(&["clone", "--quiet", "--", repo, checkout_dir])
+        .map_err(InstallGitPackageError::Git)?;
+    run_git(&["-C", checkout_dir, "checkout", "--quiet", commit])
+        .map_err(InstallGitPackageError::Git)?;
+
+    let parse_error =
+        |error| InstallGitPackageError::ParsePackageJson { repo: repo.clone(), error };
+    let manifest_path = checkout.path().join("package.json");
+    let manifest = match fs::read_to_string(&manifest_path) {
+        Ok(manifest) => manifest,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(InstallGitPackageError::MissingPackageJson { repo: repo.clone() });
+        }
+        Err(error) => return Err(InstallGitPackageError::ReadFile { path: manifest_path, error }),
+    };
+    let manifest: Value = serde_json::from_str(&manifest).map_err(parse_error)?;
+
+    let cas_paths = match manifest.pointer("/scripts/prepare").and_then(Value::as_str) {
+        Some(prepare) => {
+            prepare_git_package(store_dir, resolution, checkout.path(), &manifest, prepare)?
+        }
+        None => import_checkout(store_dir, checkout.path())?,
+    };
+
+    let Some(manifest_path) = cas_paths.get("package.json") else {
+        return Err(InstallGitPackageError::MissingPackageJson { repo: repo.clone() });
+    };
+    let manifest = fs::read_to_string(manifest_path)
+        .map_err(|error| InstallGitPackageError::ReadFile { path: manifest_path.clone(), error })?;
+    let mut package_version: PackageVersion =
+        serde_json::from_str(&manifest).map_err(parse_error)?;
+    package_version.dist.tarball = format!("git+{repo}#{commit}");
+
+    Ok((package_version, cas_paths))
+}
+
+/// Write every file of the checkout at `dir` into the store.
+fn import_checkout(
+    store_dir: &StoreDir,
+    dir: &Path,
+) -> Result<HashMap<String, PathBuf>, InstallGitPackageError> {
+    let mut cas_paths = HashMap::new();
+    let entries = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file());
+    for entry in entries {
+        let read_error =
+            |error| InstallGitPackageError::ReadFile { path: entry.path().to_path_buf(), error };
+        let buffer = fs::read(entry.path()).map_err(read_error)?;
+        let executable = is_executable(entry.path()).map_err(read_error)?;
+        let (cas_path, _) = store_dir
+            .write_cas_file(&buffer, executable)
+            .map_err(InstallGitPackageError::WriteCasFile)?;
+        let file_path = entry
+            .path()
+            .strip_prefix(dir)
+            .expect("entry is inside the checkout")
+            .components()
+            .collect::<PathBuf>()
+            .to_string_lossy()
+            .replace('\\', "/");
+        cas_paths.insert(file_path, cas_path);
+    }
+    Ok(cas_paths)
+}
+
+/// Reuse the prepared files of `resolution` from the store, or run `prepare` in the checkout at
+/// `dir` and write the files that the package would publish into the store.
+fn prepare_git_package(
+    store_dir: &StoreDir,
+    resolution: &GitResolution,
+    dir: &Path,
+    manifest: &Value,
+    prepare: &str,
+) -> Result<HashMap<String, PathBuf>, InstallGitPackageError> {
+    let GitResolution { repo, commit } = resolution;
+    let key = prepared_package_key(resolution, manifest);
+    if let Some(cas_paths) =
+        store_dir.read_index_file(&key).and_then(|index| index.cas_paths(store_dir))
+    {
+        tracing::info!(target: "pacquet::git", ?repo, ?commit, "Reuse the prepared package from the store");
+        return Ok(cas_paths);
+    }
+
+    tracing::info!(target: "pacquet::git", ?repo, ?commit, "Run prepare script");
+    let name = manifest.get("name").and_then(Value::as_str).unwrap_or("");
+    let version = manifest.get("version").and_then(Value::as_str).unwrap_or("");
+    let envs = [
+        ("npm_lifecycle_event", "prepare"),
+        ("npm_package_name", name),
+        ("npm_package_version", version),
+    ];
+    execute_script(prepare, dir, envs)
+        .map_err(|error| InstallGitPackageError::Prepare { repo: repo.clone(), error })?;
+
+    let files = package_files(dir, manifest).map_err(InstallGitPackageError::PackageFiles)?;
+    let mut cas_paths = HashMap::with_capacity(files.len());
+    let mut index = PackageFilesIndex { files: HashMap::with_capacity(files.len()) };
+    for file in files {
+        let path = dir.join(&file);
+        let read_error = |error| InstallGitPackageError::ReadFile { path: path.clone(), error };
+        let buffer = fs::read(&path).map_err(read_error)?;
+        let executable = is_executable(&path).map_err(read_error)?;
+        let (cas_path, file_hash) = store_dir
+            .write_cas_file(&buffer, executable)
+            .map_err(InstallGitPackageError::WriteCasFile)?;
+        let file_info = PackageFileInfo {
+            checked_at: None,
+            integrity: format!("sha512-{}", BASE64_STD.encode(file_hash)),
+            mode: if executable { 0o755 } else { 0o644 },
+            size: Some(buffer.len() as u64),
+        };
+        index.files.insert(file.clone(), file_info);
+        cas_paths.insert(file, cas_path);
+    }
+    store_dir.write_index_file(&key, &index).map_err(InstallGitPackageError::WriteIndexFile)?;
+
+    Ok(cas_paths)
+}
+
+/// Key of the prepared files of a git package in the store.
+///
+/// What `prepare` builds depends on the commit and on the devDependencies it may use, so both
+/// are part of the key. The devDependencies are the ranges of the manifest, as they aren't
+/// resolved for the build.
+fn prepared_package_key(resolution: &GitResolution, manifest: &Value) -> Integrity {
+    let GitResolution { repo, commit } = resolution;
+    let dev_dependencies: BTreeMap<&str, &str> = manifest
+        .get("devDependencies")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, range)| Some((name.as_str(), range.as_str()?)))
+        .collect();
+    let mut key = format!("prepared git+{repo}#{commit}");
+    for (name, range) in dev_dependencies {
+        key.push_str(&format!("\n{name}@{range}"));
+    }
+    IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(key).result()
+}
+
+#[cfg(unix)]
+pub(crate) fn is_executable(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(file_mode::is_all_exec(fs::metadata(path)?.permissions().mode()))
+}
+
+#[cfg(windows)]
+pub(crate) fn is_executable(_: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// This subroutine installs a package from a git repository pinned by a [`GitResolution`].
+///
+/// Only the `prepare` script is run, see [`fetch_git_package`]. The devDependencies are not
+/// installed for it, so it can only use the tools that are already on the `PATH`.
+#[must_use]
+pub struct InstallGitPackage<'a> {
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub node_modules_dir: &'a Path,
+    pub resolution: &'a GitResolution,
+}
+
+impl<'a> InstallGitPackage<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<PackageVersion, InstallGitPackageError> {
+        let InstallGitPackage { config, reporter, node_modules_dir, resolution } = self;
+
+        let (package_version, cas_paths) = fetch_git_package(&config.store_dir, resolution)?;
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+
+        let save_path = config
+            .virtual_store_dir
+            .join(package_version.to_virtual_store_name())
+            .join("node_modules")
+            .join(name);
+        let symlink_path = node_modules_dir.join(name);
+
+        tracing::info!(target: "pacquet::import", ?save_path, ?symlink_path, "Import package");
+
+        create_cas_files(config.package_import_method, &save_path, &cas_paths)
+            .map_err(InstallGitPackageError::CreateCasFiles)?;
+
+        symlink_package(&save_path, &symlink_path)
+            .map_err(InstallGitPackageError::SymlinkPackage)?;
+
+        reporter.emit(Event::PackageLinked { name: name.clone(), version, path: symlink_path });
+
+        Ok(package_version)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    create_cas_files, install_git_package::is_executable, package_files, symlink_package,
+    CreateCasFilesError, PackageFilesError, SymlinkPackageError,
+};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackageVersion;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_store_dir::{StoreDir, WriteCasFileError};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Error type of [`read_local_directory`] and [`InstallLocalDirectory`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallLocalDirectoryError {
+    #[display("Failed to read {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_local_file))]
+    ReadFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse the package.json of {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::parse_package_json))]
+    ParsePackageJson {
+        path: PathBuf,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+
+    #[diagnostic(transparent)]
+    PackageFiles(#[error(source)] PackageFilesError),
+
+    #[diagnostic(transparent)]
+    WriteCasFile(#[error(source)] WriteCasFileError),
+
+    #[diagnostic(transparent)]
+    CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
+    SymlinkPackage(#[error(source)] SymlinkPackageError),
+}
+
+/// Write the files that the package at `path` would publish into the store and read its
+/// `package.json`.
+///
+/// Which files are published is decided by [`package_files`], so `node_modules` and whatever
+/// `files` or `.npmignore` leave out aren't imported. The returned [`PackageVersion`] has its
+/// `dist` filled with a `file:` URL and no integrity.
+pub fn read_local_directory(
+    store_dir: &StoreDir,
+    path: &Path,
+) -> Result<(PackageVersion, HashMap<String, PathBuf>), InstallLocalDirectoryError> {
+    let read_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |error| InstallLocalDirectoryError::ReadFile { path, error }
+    };
+    let manifest_path = path.join("package.json");
+    let manifest = fs::read_to_string(&manifest_path).map_err(read_error(&manifest_path))?;
+    let parse_error =
+        |error| InstallLocalDirectoryError::ParsePackageJson { path: path.to_path_buf(), error };
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).map_err(parse_error)?;
+    let files = package_files(path, &manifest).map_err(InstallLocalDirectoryError::PackageFiles)?;
+
+    let mut cas_paths = HashMap::new();
+    for file in files {
+        let file_path = path.join(&file);
+        let buffer = fs::read(&file_path).map_err(read_error(&file_path))?;
+        let executable = is_executable(&file_path).map_err(read_error(&file_path))?;
+        let (cas_path, _) = store_dir
+            .write_cas_file(&buffer, executable)
+            .map_err(InstallLocalDirectoryError::WriteCasFile)?;
+        cas_paths.insert(file, cas_path);
+    }
+
+    let mut package_version: PackageVersion =
+        serde_json::from_value(manifest).map_err(parse_error)?;
+    package_version.dist.tarball = format!("file:{}", path.display());
+    package_version.dist.integrity = None;
+
+    Ok((package_version, cas_paths))
+}
+
+/// This subroutine installs a package from a directory on the local file system.
+///
+/// It is the counterpart of [`InstallLocalTarball`](crate::InstallLocalTarball) for `file:`
+/// specifiers that point to a directory.
+#[must_use]
+pub struct InstallLocalDirectory<'a> {
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub node_modules_dir: &'a Path,
+    pub path: &'a Path,
+}
+
+impl<'a> InstallLocalDirectory<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<PackageVersion, InstallLocalDirectoryError> {
+        let InstallLocalDirectory { config, reporter, node_modules_dir, path } = self;
+
+        let (package_version, cas_paths) = read_local_directory(&config.store_dir, path)?;
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+
+        let save_path = config
+            .virtual_store_dir
+            .join(package_version.to_virtual_store_name())
+            .join("node_modules")
+            .join(name);
+        let symlink_path = node_modules_dir.join(name);
+
+        tracing::info!(target: "pacquet::import", ?save_path, ?symlink_path, "Import package");
+
+        create_cas_files(config.package_import_method, &save_path, &cas_paths)
+            .map_err(InstallLocalDirectoryError::CreateCasFiles)?;
+
+        symlink_package(&save_path, &symlink_path)
+            .map_err(InstallLocalDirectoryError::SymlinkPackage)?;
+
+        reporter.emit(Event::PackageLinked { name: name.clone(), version, path: symlink_path });
+
+        Ok(package_version)
+    }
+}
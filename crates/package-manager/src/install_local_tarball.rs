@@ -0,0 +1,125 @@
+use crate::{create_cas_files, symlink_package, CreateCasFilesError, SymlinkPackageError};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackageVersion;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_store_dir::StoreDir;
+use pacquet_tarball::{ImportLocalTarball, TarballError};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Whether `specifier` (the argument of `pacquet add`) is a path to a local tarball.
+pub fn is_local_tarball(specifier: &str) -> bool {
+    [".tgz", ".tar.gz", ".tar"].iter().any(|extension| specifier.ends_with(extension))
+}
+
+/// Error type of [`read_local_tarball`] and [`InstallLocalTarball`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallLocalTarballError {
+    #[diagnostic(transparent)]
+    ImportTarball(#[error(source)] TarballError),
+
+    #[display("The tarball {path:?} has no package.json")]
+    #[diagnostic(code(pacquet_package_manager::missing_package_json))]
+    MissingPackageJson { path: PathBuf },
+
+    #[display("Failed to read the package.json of {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_package_json))]
+    ReadPackageJson {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse the package.json of {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::parse_package_json))]
+    ParsePackageJson {
+        path: PathBuf,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+
+    #[diagnostic(transparent)]
+    CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
+    SymlinkPackage(#[error(source)] SymlinkPackageError),
+}
+
+/// Extract the tarball at `path` into the store and read its `package.json`.
+///
+/// The returned [`PackageVersion`] has its `dist` filled with a `file:` URL and the integrity
+/// computed from the tarball.
+pub fn read_local_tarball(
+    store_dir: &StoreDir,
+    path: &Path,
+) -> Result<(PackageVersion, HashMap<String, PathBuf>), InstallLocalTarballError> {
+    let (integrity, cas_paths) = ImportLocalTarball { store_dir, path }
+        .run()
+        .map_err(InstallLocalTarballError::ImportTarball)?;
+
+    let path = path.to_path_buf();
+    let Some(manifest_path) = cas_paths.get("package.json") else {
+        return Err(InstallLocalTarballError::MissingPackageJson { path });
+    };
+    let manifest = match fs::read_to_string(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(error) => return Err(InstallLocalTarballError::ReadPackageJson { path, error }),
+    };
+    let mut package_version: PackageVersion = match serde_json::from_str(&manifest) {
+        Ok(package_version) => package_version,
+        Err(error) => return Err(InstallLocalTarballError::ParsePackageJson { path, error }),
+    };
+    package_version.dist.tarball = format!("file:{}", path.display());
+    package_version.dist.integrity = Some(integrity);
+
+    Ok((package_version, cas_paths))
+}
+
+/// This subroutine installs a package from a tarball on the local file system.
+///
+/// It is the counterpart of [`InstallPackageFromRegistry`](crate::InstallPackageFromRegistry)
+/// for `file:` specifiers that point to a `.tgz` file.
+#[must_use]
+pub struct InstallLocalTarball<'a> {
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub node_modules_dir: &'a Path,
+    pub path: &'a Path,
+}
+
+impl<'a> InstallLocalTarball<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<PackageVersion, InstallLocalTarballError> {
+        let InstallLocalTarball { config, reporter, node_modules_dir, path } = self;
+
+        let (package_version, cas_paths) = read_local_tarball(&config.store_dir, path)?;
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+
+        let save_path = config
+            .virtual_store_dir
+            .join(package_version.to_virtual_store_name())
+            .join("node_modules")
+            .join(name);
+        let symlink_path = node_modules_dir.join(name);
+
+        tracing::info!(target: "pacquet::import", ?save_path, ?symlink_path, "Import package");
+
+        create_cas_files(config.package_import_method, &save_path, &cas_paths)
+            .map_err(InstallLocalTarballError::CreateCasFiles)?;
+
+        symlink_package(&save_path, &symlink_path)
+            .map_err(InstallLocalTarballError::SymlinkPackage)?;
+
+        reporter.emit(Event::PackageLinked { name: name.clone(), version, path: symlink_path });
+
+        Ok(package_version)
+    }
+}
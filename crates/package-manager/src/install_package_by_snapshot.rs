@@ -1,12 +1,14 @@
-use crate::{CreateVirtualDirBySnapshot, CreateVirtualDirError};
+use crate::{
+    check_engines, node_version, CreateVirtualDirBySnapshot, CreateVirtualDirError,
+    FetchPackageBySnapshot, FetchPackageBySnapshotError,
+};
 use derive_more::{Display, Error};
 use miette::Diagnostic;
-use pacquet_lockfile::{DependencyPath, LockfileResolution, PackageSnapshot, PkgNameVerPeer};
+use pacquet_lockfile::{DependencyPath, PackageSnapshot};
 use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
-use pacquet_tarball::{DownloadTarballToStore, TarballError};
-use pipe_trait::Pipe;
-use std::borrow::Cow;
+use pacquet_reporter::Reporter;
+use tracing::Instrument;
 
 /// This subroutine downloads a package tarball, extracts it, installs it to a virtual dir,
 /// then creates the symlink layout for the package.
@@ -14,6 +16,7 @@ use std::borrow::Cow;
 pub struct InstallPackageBySnapshot<'a> {
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub dependency_path: &'a DependencyPath,
     pub package_snapshot: &'a PackageSnapshot,
 }
@@ -21,62 +24,63 @@ pub struct InstallPackageBySnapshot<'a> {
 /// Error type of [`InstallPackageBySnapshot`].
 #[derive(Debug, Display, Error, Diagnostic)]
 pub enum InstallPackageBySnapshotError {
-    DownloadTarball(TarballError),
-    CreateVirtualDir(CreateVirtualDirError),
+    #[diagnostic(transparent)]
+    FetchPackage(#[error(source)] FetchPackageBySnapshotError),
+
+    #[diagnostic(transparent)]
+    CreateVirtualDir(#[error(source)] CreateVirtualDirError),
 }
 
 impl<'a> InstallPackageBySnapshot<'a> {
     /// Execute the subroutine.
     pub async fn run(self) -> Result<(), InstallPackageBySnapshotError> {
-        let InstallPackageBySnapshot { http_client, config, dependency_path, package_snapshot } =
-            self;
-        let PackageSnapshot { resolution, .. } = package_snapshot;
-        let DependencyPath { custom_registry, package_specifier } = dependency_path;
-
-        let (tarball_url, integrity) = match resolution {
-            LockfileResolution::Tarball(tarball_resolution) => {
-                let integrity = tarball_resolution.integrity.as_ref().unwrap_or_else(|| {
-                    // TODO: how to handle the absent of integrity field?
-                    panic!("Current implementation requires integrity, but {dependency_path} doesn't have it");
-                });
-                (tarball_resolution.tarball.as_str().pipe(Cow::Borrowed), integrity)
-            }
-            LockfileResolution::Registry(registry_resolution) => {
-                let registry = custom_registry.as_ref().unwrap_or(&config.registry);
-                let registry = registry.strip_suffix('/').unwrap_or(registry);
-                let PkgNameVerPeer { name, suffix: ver_peer } = package_specifier;
-                let version = ver_peer.version();
-                let bare_name = name.bare.as_str();
-                let tarball_url = format!("{registry}/{name}/-/{bare_name}-{version}.tgz");
-                let integrity = &registry_resolution.integrity;
-                (Cow::Owned(tarball_url), integrity)
-            }
-            LockfileResolution::Directory(_) | LockfileResolution::Git(_) => {
-                panic!("Only TarballResolution and RegistryResolution is supported at the moment, but {dependency_path} requires {resolution:?}");
-            }
-        };
-
-        // TODO: skip when already exists in store?
-        let cas_paths = DownloadTarballToStore {
+        let InstallPackageBySnapshot {
             http_client,
-            store_dir: &config.store_dir,
-            package_integrity: integrity,
-            package_unpacked_size: None,
-            package_url: &tarball_url,
+            config,
+            reporter,
+            dependency_path,
+            package_snapshot,
+        } = self;
+
+        let package_specifier = &dependency_path.package_specifier;
+        let name = package_specifier.name.to_string();
+        let version = package_specifier.suffix.version().to_string();
+        if let Err(error) = check_engines(
+            &name,
+            &version,
+            package_snapshot.engines.as_ref(),
+            node_version(config).as_ref(),
+        ) {
+            tracing::warn!(target: "pacquet::engines", "{error}");
         }
-        .run_without_mem_cache()
-        .await
-        .map_err(InstallPackageBySnapshotError::DownloadTarball)?;
 
-        CreateVirtualDirBySnapshot {
-            virtual_store_dir: &config.virtual_store_dir,
-            cas_paths: &cas_paths,
-            import_method: config.package_import_method,
+        let package = format!("{name}@{version}");
+        let span =
+            tracing::debug_span!(target: "pacquet::timing", "package", package = package.as_str());
+
+        let cas_paths = FetchPackageBySnapshot {
+            http_client,
+            config,
+            reporter,
             dependency_path,
             package_snapshot,
         }
         .run()
-        .map_err(InstallPackageBySnapshotError::CreateVirtualDir)?;
+        .instrument(tracing::debug_span!(target: "pacquet::timing", parent: &span, "download"))
+        .await
+        .map_err(InstallPackageBySnapshotError::FetchPackage)?;
+
+        tracing::debug_span!(target: "pacquet::timing", parent: &span, "link").in_scope(|| {
+            CreateVirtualDirBySnapshot {
+                virtual_store_dir: &config.virtual_store_dir,
+                cas_paths: &cas_paths,
+                import_method: config.package_import_method,
+                dependency_path,
+                package_snapshot,
+            }
+            .run()
+            .map_err(InstallPackageBySnapshotError::CreateVirtualDir)
+        })?;
 
         Ok(())
     }
@@ -1,11 +1,64 @@
-use crate::{create_cas_files, symlink_package, CreateCasFilesError, SymlinkPackageError};
+use crate::{
+    check_engines, create_cas_files, node_version, run_package_hook, symlink_package,
+    CreateCasFilesError, InstallPlan, NpmAlias, PackageHook, PlannedPackage, SymlinkPackageError,
+};
+use dashmap::DashMap;
 use derive_more::{Display, Error};
 use miette::Diagnostic;
+use pacquet_lockfile::Lockfile;
 use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
-use pacquet_registry::{Package, PackageTag, PackageVersion, RegistryError};
+use pacquet_registry::{
+    Package, PackageTag, PackageVersion, PackumentCache, ParseTimestampError, RegistryError,
+    Timestamp,
+};
+use pacquet_reporter::{Event, Reporter};
 use pacquet_tarball::{DownloadTarballToStore, MemCache, TarballError};
-use std::{path::Path, str::FromStr};
+use pipe_trait::Pipe;
+use ssri::Integrity;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    panic,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+use tracing::{field::Empty, Instrument};
+
+/// In-memory cache of the metadata of the packages that have been resolved during an install.
+///
+/// The key is the name of a package, so that dependents which depend on it only fetch its
+/// metadata once, whatever their version ranges, even when they resolve it at the same time.
+pub type ResolutionCache = DashMap<String, Arc<OnceCell<Arc<Package>>>>;
+
+/// Integrities that the existing lockfile records, keyed by the name then the version of the
+/// packages whatever their peers. It is built once per install, see [`IntegrityIndex::new`].
+#[derive(Debug, Default)]
+pub struct IntegrityIndex(HashMap<String, HashMap<String, Integrity>>);
+
+impl IntegrityIndex {
+    /// Index the integrities of the packages of `lockfile`.
+    pub fn new(lockfile: &Lockfile) -> Self {
+        let mut index = HashMap::<_, HashMap<_, _>>::new();
+        for (dependency_path, package_snapshot) in lockfile.packages.iter().flatten() {
+            let Some(integrity) = package_snapshot.resolution.integrity() else { continue };
+            let specifier = &dependency_path.package_specifier;
+            index
+                .entry(specifier.name.to_string())
+                .or_default()
+                .entry(specifier.suffix.version().to_string())
+                .or_insert_with(|| integrity.clone());
+        }
+        IntegrityIndex(index)
+    }
+
+    /// Integrity recorded for version `version` of package `name`.
+    pub fn get(&self, name: &str, version: &str) -> Option<&'_ Integrity> {
+        self.0.get(name)?.get(version)
+    }
+}
 
 /// This subroutine executes the following and returns the package
 /// * Retrieves the package from the registry
@@ -18,19 +71,97 @@ use std::{path::Path, str::FromStr};
 #[must_use]
 pub struct InstallPackageFromRegistry<'a> {
     pub tarball_mem_cache: &'a MemCache,
+    pub resolution_cache: &'a ResolutionCache,
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub node_modules_dir: &'a Path,
+    /// Name of the dependency, which is also the name of the symlink in `node_modules_dir`.
     pub name: &'a str,
+    /// Version range or tag of the dependency, or an [`NpmAlias`] of another package.
     pub version_range: &'a str,
+    /// When set, the package is only resolved and recorded into the plan.
+    pub dry_run: Option<&'a InstallPlan>,
+    /// Rewrites the metadata of the package before it is installed.
+    pub package_hook: Option<&'a dyn PackageHook>,
+    /// Integrities of the existing lockfile. When it records the resolved version, the integrity
+    /// served by the registry must not use a weaker algorithm than the recorded one.
+    pub integrity_index: Option<&'a IntegrityIndex>,
+    /// When set, the tarball isn't downloaded right away but queued into it, so that the free
+    /// space of the store can be checked for every package of the install before any download,
+    /// see [`DownloadPackageFromRegistry`].
+    pub pending_downloads: Option<&'a PendingDownloads>,
+}
+
+/// A package resolved by [`InstallPackageFromRegistry`] whose tarball is yet to be downloaded,
+/// to be linked as `name` into `node_modules_dir`.
+#[derive(Debug)]
+pub struct PendingDownload {
+    pub node_modules_dir: PathBuf,
+    pub name: String,
+    pub package_version: PackageVersion,
+}
+
+/// Packages that [`InstallPackageFromRegistry`] resolved without downloading them, see
+/// [`InstallPackageFromRegistry::pending_downloads`].
+pub type PendingDownloads = Mutex<Vec<PendingDownload>>;
+
+/// This subroutine downloads the tarball of a package resolved from the registry into the store,
+/// imports its files into the virtual store, and links it as `name` into `node_modules_dir`.
+#[must_use]
+pub struct DownloadPackageFromRegistry<'a> {
+    pub tarball_mem_cache: &'a MemCache,
+    pub http_client: &'a ThrottledClient,
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub node_modules_dir: &'a Path,
+    /// Name of the dependency, which is also the name of the symlink in `node_modules_dir`.
+    pub name: &'a str,
+    pub package_version: &'a PackageVersion,
+    /// See [`InstallPackageFromRegistry::integrity_index`].
+    pub integrity_index: Option<&'a IntegrityIndex>,
 }
 
 /// Error type of [`InstallPackageFromRegistry`].
 #[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
 pub enum InstallPackageFromRegistryError {
+    #[diagnostic(transparent)]
     FetchFromRegistry(#[error(source)] RegistryError),
+
+    #[display("The metadata of {name}@{version} has no valid dist.integrity")]
+    #[diagnostic(
+        code(pacquet_package_manager::missing_integrity),
+        help("The registry didn't send an integrity that the tarball can be verified with")
+    )]
+    MissingIntegrity { name: String, version: String },
+
+    #[diagnostic(transparent)]
+    InvalidBefore(#[error(source)] ParseTimestampError),
+
+    #[display(
+        "No version of {name} that satisfies {version_range:?} was published before {before}"
+    )]
+    #[diagnostic(
+        code(pacquet_package_manager::no_version_before),
+        help("Pick a later date for `before`, or a range that older versions satisfy")
+    )]
+    NoVersionBefore { name: String, version_range: String, before: String },
+
+    #[display("No version of {name} satisfies {version_range:?}")]
+    #[diagnostic(
+        code(pacquet_package_manager::no_matching_version),
+        help("Check the versions of {name} that the registry has")
+    )]
+    NoMatchingVersion { name: String, version_range: String },
+
+    #[diagnostic(transparent)]
     DownloadTarballToStore(#[error(source)] TarballError),
+
+    #[diagnostic(transparent)]
     CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
     SymlinkPackage(#[error(source)] SymlinkPackageError),
 }
 
@@ -40,29 +171,130 @@ impl<'a> InstallPackageFromRegistry<'a> {
     where
         Tag: FromStr + Into<PackageTag>,
     {
-        let &InstallPackageFromRegistry { http_client, config, name, version_range, .. } = &self;
-
-        Ok(if let Ok(tag) = version_range.parse::<Tag>() {
-            let package_version = PackageVersion::fetch_from_registry(
-                name,
-                tag.into(),
-                http_client,
-                &config.registry,
-            )
-            .await
-            .map_err(InstallPackageFromRegistryError::FetchFromRegistry)?;
-            self.install_package_version(&package_version).await?;
-            package_version
-        } else {
-            let package = Package::fetch_from_registry(name, http_client, &config.registry)
-                .await
-                .map_err(InstallPackageFromRegistryError::FetchFromRegistry)?;
-            let package_version = package.pinned_version(version_range).unwrap(); // TODO: propagate error for when no version satisfies range
-            self.install_package_version(package_version).await?;
-            package_version.clone()
+        let span = tracing::debug_span!(target: "pacquet::timing", "package", package = Empty);
+        self.resolve_and_install::<Tag>(&span).instrument(span.clone()).await
+    }
+
+    async fn resolve_and_install<Tag>(
+        self,
+        span: &tracing::Span,
+    ) -> Result<PackageVersion, InstallPackageFromRegistryError>
+    where
+        Tag: FromStr + Into<PackageTag>,
+    {
+        let &InstallPackageFromRegistry { config, name, version_range, package_hook, .. } = &self;
+
+        let (package_name, version_range) = match NpmAlias::parse(version_range) {
+            Some(NpmAlias { name, version_range }) => (name, version_range),
+            None => (name, version_range),
+        };
+
+        let mut package_version = self
+            .resolve::<Tag>(package_name, version_range)
+            .instrument(tracing::debug_span!(target: "pacquet::timing", "resolve"))
+            .await?;
+
+        run_package_hook(package_hook, config, &mut package_version);
+
+        let package = format!("{}@{}", package_version.name, package_version.version);
+        span.record("package", package.as_str());
+        self.install_package_version(&package_version).await?;
+        Ok(package_version)
+    }
+
+    /// Pick the version of package `name` that satisfies `version_range` from its metadata.
+    ///
+    /// When [`before`](Npmrc::before) is set, only the versions that were published before it are
+    /// picked from, which are only known from the full metadata of the package.
+    async fn resolve<Tag>(
+        &self,
+        name: &str,
+        version_range: &str,
+    ) -> Result<PackageVersion, InstallPackageFromRegistryError>
+    where
+        Tag: FromStr + Into<PackageTag>,
+    {
+        let &InstallPackageFromRegistry { config, .. } = self;
+        let before = config
+            .before
+            .as_deref()
+            .map(str::parse::<Timestamp>)
+            .transpose()
+            .map_err(InstallPackageFromRegistryError::InvalidBefore)?;
+        let pick = |package: &Package| match (version_range.parse::<Tag>(), before) {
+            (Ok(tag), None) => package.tagged_version(&tag.into()).cloned(),
+            (Ok(tag), Some(before)) => package.tagged_version_before(&tag.into(), before).cloned(),
+            (Err(_), None) => package.pinned_version(version_range).cloned(),
+            (Err(_), Some(before)) => package.pinned_version_before(version_range, before).cloned(),
+        };
+
+        // cached abbreviated metadata has no publish times
+        let cached_version = config
+            .prefer_offline
+            .then(|| PackumentCache::new(&config.cache_dir).read(config.registry_for(name), name))
+            .flatten()
+            .filter(|package| before.is_none() || package.has_publish_times())
+            .as_ref()
+            .and_then(pick);
+        if let Some(package_version) = cached_version {
+            tracing::debug!(target: "pacquet::cache", ?name, version = %package_version.version, "Use cached metadata");
+            return Ok(package_version);
+        }
+
+        let package = self.fetch_package(name, before.is_some()).await?;
+        pick(&package).ok_or_else(|| match before {
+            Some(before) => InstallPackageFromRegistryError::NoVersionBefore {
+                name: name.to_string(),
+                version_range: version_range.to_string(),
+                before: before.to_string(),
+            },
+            None => InstallPackageFromRegistryError::NoMatchingVersion {
+                name: name.to_string(),
+                version_range: version_range.to_string(),
+            },
         })
     }
 
+    /// Fetch the metadata of package `name` from the registry once per install, see
+    /// [`ResolutionCache`]. The `full` metadata has the publish times of the versions.
+    ///
+    /// The metadata is also written to the [`PackumentCache`], except in a dry run.
+    async fn fetch_package(
+        &self,
+        name: &str,
+        full: bool,
+    ) -> Result<Arc<Package>, InstallPackageFromRegistryError> {
+        let &InstallPackageFromRegistry { resolution_cache, http_client, config, dry_run, .. } =
+            self;
+        let package = resolution_cache.entry(name.to_string()).or_default().pipe_deref(Arc::clone);
+        let package = package
+            .get_or_try_init(|| async {
+                let registry = config.registry_for(name);
+                let package = if full {
+                    Package::fetch_full_from_registry(name, http_client, registry).await
+                } else {
+                    Package::fetch_from_registry(name, http_client, registry).await
+                }
+                .map_err(InstallPackageFromRegistryError::FetchFromRegistry)?;
+                let package = Arc::new(package);
+                if dry_run.is_none() {
+                    let packument_cache = PackumentCache::new(&config.cache_dir);
+                    let (registry, name) = (registry.to_string(), name.to_string());
+                    let package = Arc::clone(&package);
+                    let task = move || packument_cache.write(&registry, &name, &package);
+                    match tokio::task::spawn_blocking(task).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(error)) => tracing::warn!(target: "pacquet::cache", "{error}"),
+                        Err(error) if error.is_panic() => panic::resume_unwind(error.into_panic()),
+                        Err(error) => tracing::warn!(target: "pacquet::cache", "{error}"),
+                    }
+                }
+                Ok(package)
+            })
+            .await?;
+        Ok(Arc::clone(package))
+    }
+
     async fn install_package_version(
         self,
         package_version: &PackageVersion,
@@ -71,43 +303,168 @@ impl<'a> InstallPackageFromRegistry<'a> {
             tarball_mem_cache,
             http_client,
             config,
+            reporter,
             node_modules_dir,
+            name: dependency_name,
+            dry_run,
+            integrity_index,
+            pending_downloads,
             ..
         } = self;
 
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+        if let Some(message) = &package_version.deprecated {
+            tracing::warn!(target: "pacquet::install", "{name}@{version} is deprecated: {message}");
+            reporter.emit(Event::Deprecated {
+                name: name.clone(),
+                version: version.clone(),
+                message: message.clone(),
+            });
+        }
+
+        if let Some(plan) = dry_run {
+            let package = PlannedPackage {
+                name: name.clone(),
+                version,
+                unpacked_size: package_version.dist.unpacked_size,
+                file_count: package_version.dist.file_count,
+                integrity: package_version.dist.integrity.clone(),
+            };
+            plan.insert(package_version.to_virtual_store_name(), package);
+            return Ok(());
+        }
+
+        if let Some(pending_downloads) = pending_downloads {
+            let pending_download = PendingDownload {
+                node_modules_dir: node_modules_dir.to_path_buf(),
+                name: dependency_name.to_string(),
+                package_version: package_version.clone(),
+            };
+            pending_downloads.lock().expect("lock pending downloads").push(pending_download);
+            return Ok(());
+        }
+
+        DownloadPackageFromRegistry {
+            tarball_mem_cache,
+            http_client,
+            config,
+            reporter,
+            node_modules_dir,
+            name: dependency_name,
+            package_version,
+            integrity_index,
+        }
+        .download()
+        .await
+    }
+}
+
+impl<'a> DownloadPackageFromRegistry<'a> {
+    /// Execute the subroutine.
+    pub async fn run(self) -> Result<(), InstallPackageFromRegistryError> {
+        let package = format!("{}@{}", self.package_version.name, self.package_version.version);
+        let span = tracing::debug_span!(target: "pacquet::timing", "package", package);
+        self.download().instrument(span).await
+    }
+
+    async fn download(self) -> Result<(), InstallPackageFromRegistryError> {
+        let DownloadPackageFromRegistry {
+            tarball_mem_cache,
+            http_client,
+            config,
+            reporter,
+            node_modules_dir,
+            name: dependency_name,
+            package_version,
+            integrity_index,
+        } = self;
+
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
         let store_folder_name = package_version.to_virtual_store_name();
 
-        // TODO: skip when it already exists in store?
+        let package_url = if config.rewrite_tarball_url {
+            package_version.tarball_url_from_registry(config.registry_for(name)).pipe(Cow::Owned)
+        } else {
+            package_version.as_tarball_url().pipe(Cow::Borrowed)
+        };
+        let package_url = match config.replace_tarball_host(&package_url) {
+            Cow::Owned(replaced) => Cow::Owned(replaced),
+            Cow::Borrowed(_) => package_url,
+        };
+
+        let engines = package_version.engines.as_ref();
+        if let Err(error) = check_engines(name, &version, engines, node_version(config).as_ref()) {
+            tracing::warn!(target: "pacquet::engines", "{error}");
+        }
+
+        let package_integrity = package_version.dist.integrity.as_ref().ok_or_else(|| {
+            InstallPackageFromRegistryError::MissingIntegrity {
+                name: name.clone(),
+                version: version.clone(),
+            }
+        })?;
+
+        // Packages that are already in the store are reused without a download.
+        if config.store_dir.index_file_path(package_integrity).is_file() {
+            reporter.emit(Event::TarballReused { name: name.clone(), version: version.clone() });
+        } else {
+            reporter.emit(Event::TarballDownloadStarted {
+                name: name.clone(),
+                version: version.clone(),
+                size: package_version.dist.unpacked_size.map(|size| size as u64),
+            });
+        }
         let cas_paths = DownloadTarballToStore {
             http_client,
             store_dir: &config.store_dir,
-            package_integrity: package_version
-                .dist
-                .integrity
-                .as_ref()
-                .expect("has integrity field"),
+            package_integrity,
             package_unpacked_size: package_version.dist.unpacked_size,
-            package_url: package_version.as_tarball_url(),
+            package_url: &package_url,
+            registry: config.registry_for(name),
+            recorded_integrity: integrity_index
+                .and_then(|integrity_index| integrity_index.get(name, &version)),
+            verify_store_integrity: config.verify_store_integrity,
         }
         .run_with_mem_cache(tarball_mem_cache)
+        .instrument(tracing::debug_span!(target: "pacquet::timing", "download"))
         .await
         .map_err(InstallPackageFromRegistryError::DownloadTarballToStore)?;
 
+        reporter.emit(Event::TarballDownloaded {
+            name: name.clone(),
+            version: version.clone(),
+            url: package_url.to_string(),
+        });
+
         let save_path = config
             .virtual_store_dir
             .join(store_folder_name)
             .join("node_modules")
             .join(&package_version.name);
 
-        let symlink_path = node_modules_dir.join(&package_version.name);
+        let symlink_path = node_modules_dir.join(dependency_name);
 
         tracing::info!(target: "pacquet::import", ?save_path, ?symlink_path, "Import package");
 
-        create_cas_files(config.package_import_method, &save_path, &cas_paths)
-            .map_err(InstallPackageFromRegistryError::CreateCasFiles)?;
+        tracing::debug_span!(target: "pacquet::timing", "link").in_scope(|| {
+            create_cas_files(config.package_import_method, &save_path, &cas_paths)
+                .map_err(InstallPackageFromRegistryError::CreateCasFiles)?;
+            // a package that depends on itself is already at the path of the symlink
+            if save_path == symlink_path {
+                return Ok(());
+            }
+            symlink_package(&save_path, &symlink_path)
+                .map_err(InstallPackageFromRegistryError::SymlinkPackage)
+        })?;
 
-        symlink_package(&save_path, &symlink_path)
-            .map_err(InstallPackageFromRegistryError::SymlinkPackage)?;
+        reporter.emit(Event::PackageLinked {
+            name: dependency_name.to_string(),
+            version,
+            path: symlink_path,
+        });
 
         Ok(())
     }
@@ -118,35 +475,30 @@ mod tests {
     use super::*;
     use node_semver::Version;
     use pacquet_npmrc::Npmrc;
-    use pacquet_store_dir::StoreDir;
-    use pipe_trait::Pipe;
+    use pacquet_reporter::DefaultReporter;
     use pretty_assertions::assert_eq;
     use std::fs;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
     use tempfile::tempdir;
+    use text_block_macros::text_block;
 
     fn create_config(store_dir: &Path, modules_dir: &Path, virtual_store_dir: &Path) -> Npmrc {
-        Npmrc {
-            hoist: false,
-            hoist_pattern: vec![],
-            public_hoist_pattern: vec![],
-            shamefully_hoist: false,
-            store_dir: StoreDir::new(store_dir),
-            modules_dir: modules_dir.to_path_buf(),
-            node_linker: Default::default(),
-            symlink: false,
-            virtual_store_dir: virtual_store_dir.to_path_buf(),
-            package_import_method: Default::default(),
-            modules_cache_max_age: 0,
-            lockfile: false,
-            prefer_frozen_lockfile: false,
-            lockfile_include_tarball_url: false,
-            registry: "https://registry.npmjs.com/".to_string(),
-            auto_install_peers: false,
-            dedupe_peer_dependents: false,
-            strict_peer_dependencies: false,
-            resolve_peers_from_workspace_root: false,
-        }
+        Npmrc::builder()
+            .hoist(false)
+            .hoist_pattern(vec![])
+            .public_hoist_pattern(vec![])
+            .store_dir(store_dir)
+            .cache_dir(PathBuf::new())
+            .modules_dir(modules_dir)
+            .symlink(false)
+            .virtual_store_dir(virtual_store_dir)
+            .modules_cache_max_age(0)
+            .prefer_frozen_lockfile(false)
+            .registry("https://registry.npmjs.com/")
+            .auto_install_peers(false)
+            .dedupe_peer_dependents(false)
+            .resolve_peers_from_workspace_root(false)
+            .build()
     }
 
     #[tokio::test]
@@ -161,11 +513,17 @@ mod tests {
         let http_client = ThrottledClient::new_from_cpu_count();
         let package = InstallPackageFromRegistry {
             tarball_mem_cache: &Default::default(),
+            resolution_cache: &Default::default(),
             config,
+            reporter: &DefaultReporter,
             http_client: &http_client,
             name: "fast-querystring",
             version_range: "1.0.0",
             node_modules_dir: modules_dir.path(),
+            dry_run: None,
+            package_hook: None,
+            integrity_index: None,
+            pending_downloads: None,
         }
         .run::<Version>()
         .await
@@ -190,4 +548,26 @@ mod tests {
             virtual_store_path
         );
     }
+
+    #[test]
+    fn integrity_index() {
+        let lockfile: Lockfile = serde_yaml::from_str(text_block! {
+            "lockfileVersion: '6.0'"
+            "packages:"
+            "  /react-dom@17.0.2(react@17.0.2):"
+            "    resolution: {integrity: sha512-s4h96KtLDUQlsENhMn1ar8t2bEa+q/YAtj8pPPdIjPDGBDIVNsrD9aXNWqspUe6AzKCIG0C1HZZLqLV7qpOBGA==}"
+            "  /react@17.0.2:"
+            "    resolution: {integrity: sha512-gnhPt75i/dq/z3/6q/0asP78D0u592D5L1pd7M8P+dck6Fu/jJeL6iVVK23fptSUZj8Vjf++7wXA8UNclGQcbA==}"
+        })
+        .unwrap();
+        let integrity_index = IntegrityIndex::new(&lockfile);
+        let integrity = |name, version| integrity_index.get(name, version).map(ToString::to_string);
+        assert_eq!(
+            integrity("react-dom", "17.0.2").as_deref(),
+            Some("sha512-s4h96KtLDUQlsENhMn1ar8t2bEa+q/YAtj8pPPdIjPDGBDIVNsrD9aXNWqspUe6AzKCIG0C1HZZLqLV7qpOBGA=="),
+        );
+        assert!(integrity("react", "17.0.2").is_some());
+        assert_eq!(integrity("react", "18.0.0"), None);
+        assert_eq!(integrity("vue", "17.0.2"), None);
+    }
 }
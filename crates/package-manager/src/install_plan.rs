@@ -0,0 +1,120 @@
+use dashmap::DashMap;
+use ssri::Integrity;
+use std::{fs, path::Path};
+
+/// A package that an install would place in the virtual store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedPackage {
+    pub name: String,
+    /// Resolved version, or the specifier for packages that aren't resolved in dry-run mode
+    /// (local tarballs and git repositories).
+    pub version: String,
+    /// Size of the unpacked package according to the registry, if known.
+    pub unpacked_size: Option<usize>,
+    /// Number of files in the package according to the registry, if known.
+    pub file_count: Option<usize>,
+    /// Integrity of the tarball, if known.
+    pub integrity: Option<Integrity>,
+}
+
+/// Packages collected by [`Install`](crate::Install) in dry-run mode instead of being downloaded
+/// and linked.
+///
+/// The keys are the virtual store names of the packages.
+#[derive(Debug, Default)]
+pub struct InstallPlan {
+    packages: DashMap<String, PlannedPackage>,
+}
+
+/// Result of [`InstallPlan::summarize`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InstallPlanSummary {
+    /// Packages that aren't in the virtual store yet.
+    pub added: Vec<PlannedPackage>,
+    /// Packages that are already in the virtual store.
+    pub reused: Vec<PlannedPackage>,
+    /// Virtual store entries that are no longer depended on.
+    pub removed: Vec<String>,
+    /// Sum of the known unpacked sizes of [`added`](Self::added).
+    pub added_size: usize,
+}
+
+impl InstallPlan {
+    /// Create an empty plan.
+    pub fn new() -> Self {
+        InstallPlan::default()
+    }
+
+    /// Record a package under its virtual store name.
+    pub(crate) fn insert(&self, virtual_store_name: String, package: PlannedPackage) {
+        self.packages.insert(virtual_store_name, package);
+    }
+
+    /// Compare the plan with the contents of `virtual_store_dir`.
+    pub fn summarize(&self, virtual_store_dir: &Path) -> InstallPlanSummary {
+        let mut summary = InstallPlanSummary::default();
+
+        for entry in self.packages.iter() {
+            let (virtual_store_name, package) = entry.pair();
+            if virtual_store_dir.join(virtual_store_name).exists() {
+                summary.reused.push(package.clone());
+            } else {
+                summary.added_size += package.unpacked_size.unwrap_or(0);
+                summary.added.push(package.clone());
+            }
+        }
+
+        summary.removed = fs::read_dir(virtual_store_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.starts_with('.') && !self.packages.contains_key(name))
+            .collect();
+
+        let by_name = |a: &PlannedPackage, b: &PlannedPackage| {
+            (&a.name, &a.version).cmp(&(&b.name, &b.version))
+        };
+        summary.added.sort_by(by_name);
+        summary.reused.sort_by(by_name);
+        summary.removed.sort();
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn summarize_should_compare_with_virtual_store() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("bar@1.0.0")).unwrap();
+        fs::create_dir(dir.path().join("old@1.0.0")).unwrap();
+        fs::write(dir.path().join(".lock.yaml"), "").unwrap();
+
+        let package = |name: &str, unpacked_size| PlannedPackage {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            unpacked_size,
+            file_count: None,
+            integrity: None,
+        };
+        let plan = InstallPlan::new();
+        plan.insert("foo@1.0.0".to_string(), package("foo", Some(100)));
+        plan.insert("baz@1.0.0".to_string(), package("baz", None));
+        plan.insert("bar@1.0.0".to_string(), package("bar", Some(50)));
+
+        assert_eq!(
+            plan.summarize(dir.path()),
+            InstallPlanSummary {
+                added: vec![package("baz", None), package("foo", Some(100))],
+                reused: vec![package("bar", Some(50))],
+                removed: vec!["old@1.0.0".to_string()],
+                added_size: 100,
+            },
+        );
+    }
+}
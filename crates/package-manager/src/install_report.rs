@@ -0,0 +1,137 @@
+use pacquet_reporter::{Event, Reporter};
+use std::{collections::HashSet, sync::Mutex};
+
+/// Something that may be wrong with an installation that has succeeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// An installed package is deprecated, `message` is the reason given by its authors.
+    Deprecated { name: String, version: String, message: String },
+    /// Any other warning, such as an unmet peer dependency.
+    Other { message: String },
+}
+
+/// Outcome of [`PackageManager::install`](crate::PackageManager::install).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallReport {
+    /// Warnings in the order they were reported.
+    pub warnings: Vec<Warning>,
+    /// Number of packages that have been placed into the virtual store.
+    pub added: usize,
+    /// Number of packages that have been removed from the virtual store, see
+    /// [`PruneModulesDir`](crate::PruneModulesDir).
+    pub removed: usize,
+    /// Number of the [added](Self::added) packages whose tarballs were already in the store.
+    pub reused: usize,
+}
+
+/// Reporter that builds an [`InstallReport`] from the events of an installation and forwards
+/// them to `inner`.
+pub(crate) struct InstallReportCollector<'a> {
+    inner: &'a dyn Reporter,
+    report: Mutex<InstallReport>,
+    deprecated: Mutex<HashSet<(String, String)>>,
+    added: Mutex<HashSet<(String, String)>>,
+    reused: Mutex<HashSet<(String, String)>>,
+}
+
+impl<'a> InstallReportCollector<'a> {
+    pub(crate) fn new(inner: &'a dyn Reporter) -> Self {
+        InstallReportCollector {
+            inner,
+            report: Mutex::default(),
+            deprecated: Mutex::default(),
+            added: Mutex::default(),
+            reused: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn into_report(self) -> InstallReport {
+        let mut report = self.report.into_inner().expect("lock report");
+        report.added = self.added.into_inner().expect("lock added packages").len();
+        report.reused = self.reused.into_inner().expect("lock reused packages").len();
+        report
+    }
+}
+
+impl<'a> Reporter for InstallReportCollector<'a> {
+    fn emit(&self, event: Event) {
+        match &event {
+            Event::Deprecated { name, version, message } => {
+                // a package is resolved once for every package that depends on it
+                let package = (name.clone(), version.clone());
+                if self.deprecated.lock().expect("lock deprecated packages").insert(package) {
+                    self.report.lock().expect("lock report").warnings.push(Warning::Deprecated {
+                        name: name.clone(),
+                        version: version.clone(),
+                        message: message.clone(),
+                    });
+                }
+            }
+            Event::Warning { message } => {
+                let warning = Warning::Other { message: message.clone() };
+                self.report.lock().expect("lock report").warnings.push(warning);
+            }
+            Event::TarballReused { name, version } => {
+                let package = (name.clone(), version.clone());
+                self.reused.lock().expect("lock reused packages").insert(package);
+            }
+            Event::PackageRemoved { .. } => self.report.lock().expect("lock report").removed += 1,
+            Event::TarballDownloaded { name, version, .. } => {
+                let package = (name.clone(), version.clone());
+                self.added.lock().expect("lock added packages").insert(package);
+            }
+            _ => {}
+        }
+        self.inner.emit(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pacquet_reporter::SilentReporter;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_collect_warnings_and_counts() {
+        let collector = InstallReportCollector::new(&SilentReporter);
+        let string = |value: &str| value.to_string();
+        let downloaded = |name: &str| Event::TarballDownloaded {
+            name: string(name),
+            version: string("1.0.0"),
+            url: format!("https://registry.npmjs.org/{name}/-/{name}-1.0.0.tgz"),
+        };
+        let deprecated = || Event::Deprecated {
+            name: string("foo"),
+            version: string("1.0.0"),
+            message: string("use bar instead"),
+        };
+        collector.emit(deprecated());
+        collector.emit(deprecated());
+        collector.emit(downloaded("foo"));
+        collector.emit(Event::TarballReused { name: string("bar"), version: string("1.0.0") });
+        collector.emit(Event::TarballReused { name: string("bar"), version: string("1.0.0") });
+        collector.emit(downloaded("bar"));
+        collector.emit(downloaded("bar"));
+        collector.emit(Event::Warning { message: string("careful") });
+        collector.emit(Event::PackageRemoved { path: "node_modules/.pnpm/old@1.0.0".into() });
+
+        assert_eq!(
+            collector.into_report(),
+            InstallReport {
+                warnings: vec![
+                    Warning::Deprecated {
+                        name: string("foo"),
+                        version: string("1.0.0"),
+                        message: string("use bar instead"),
+                    },
+                    Warning::Other { message: string("careful") },
+                ],
+                added: 2,
+                removed: 1,
+                reused: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    create_cas_files, is_local_tarball, symlink_package, CreateCasFilesError, SymlinkPackageError,
+};
+use dashmap::DashMap;
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_network::ThrottledClient;
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackageVersion;
+use pacquet_reporter::{Event, Reporter};
+use pacquet_store_dir::StoreDir;
+use pacquet_tarball::{ImportRemoteTarball, TarballError};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Whether `specifier` (the argument of `pacquet add` or a range of `package.json`) is the URL
+/// of a tarball, like `https://example.com/foo-1.0.0.tgz`.
+pub fn is_tarball_url(specifier: &str) -> bool {
+    let is_http = specifier.starts_with("https://") || specifier.starts_with("http://");
+    let path = specifier.split(['?', '#']).next().unwrap_or_default();
+    is_http && is_local_tarball(path)
+}
+
+/// Tarballs fetched by [`fetch_tarball_url`], by URL, so that a tarball that is both resolved and
+/// installed, like by `pacquet add`, is only downloaded once.
+pub type TarballUrlCache = DashMap<String, (PackageVersion, HashMap<String, PathBuf>)>;
+
+/// Error type of [`fetch_tarball_url`] and [`InstallTarballUrl`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallTarballUrlError {
+    #[diagnostic(transparent)]
+    ImportTarball(#[error(source)] TarballError),
+
+    #[display("The tarball {url} has no package.json")]
+    #[diagnostic(code(pacquet_package_manager::missing_package_json))]
+    MissingPackageJson { url: String },
+
+    #[display("Failed to read the package.json of {url}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_package_json))]
+    ReadPackageJson {
+        url: String,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse the package.json of {url}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::parse_package_json))]
+    ParsePackageJson {
+        url: String,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+
+    #[diagnostic(transparent)]
+    CreateCasFiles(#[error(source)] CreateCasFilesError),
+
+    #[diagnostic(transparent)]
+    SymlinkPackage(#[error(source)] SymlinkPackageError),
+}
+
+/// Download the tarball at `url` into the store and read its `package.json`, unless `cache`
+/// already has it.
+///
+/// The returned [`PackageVersion`] has its `dist` filled with `url` and the integrity computed
+/// from the tarball, which is what a [`TarballResolution`](pacquet_lockfile::TarballResolution)
+/// of the lockfile records.
+pub async fn fetch_tarball_url(
+    http_client: &ThrottledClient,
+    store_dir: &'static StoreDir,
+    cache: &TarballUrlCache,
+    url: &str,
+) -> Result<(PackageVersion, HashMap<String, PathBuf>), InstallTarballUrlError> {
+    if let Some(fetched) = cache.get(url) {
+        tracing::info!(target: "pacquet::download", ?url, "Already fetched");
+        return Ok(fetched.clone());
+    }
+
+    let (integrity, cas_paths) = ImportRemoteTarball { http_client, store_dir, url }
+        .run()
+        .await
+        .map_err(InstallTarballUrlError::ImportTarball)?;
+
+    let url = url.to_string();
+    let Some(manifest_path) = cas_paths.get("package.json") else {
+        return Err(InstallTarballUrlError::MissingPackageJson { url });
+    };
+    let manifest = match fs::read_to_string(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(error) => return Err(InstallTarballUrlError::ReadPackageJson { url, error }),
+    };
+    let mut package_version: PackageVersion = match serde_json::from_str(&manifest) {
+        Ok(package_version) => package_version,
+        Err(error) => return Err(InstallTarballUrlError::ParsePackageJson { url, error }),
+    };
+    package_version.dist.tarball = url.clone();
+    package_version.dist.integrity = Some(integrity);
+
+    cache.insert(url.clone(), (package_version.clone(), cas_paths.clone()));
+    Ok((package_version, cas_paths))
+}
+
+/// This subroutine installs a package from the URL of a tarball.
+///
+/// It is the counterpart of [`InstallLocalTarball`](crate::InstallLocalTarball) for the
+/// specifiers that [`is_tarball_url`] accepts.
+#[must_use]
+pub struct InstallTarballUrl<'a> {
+    pub http_client: &'a ThrottledClient,
+    pub config: &'static Npmrc,
+    pub tarball_url_cache: &'a TarballUrlCache,
+    pub reporter: &'a dyn Reporter,
+    pub node_modules_dir: &'a Path,
+    pub url: &'a str,
+}
+
+impl<'a> InstallTarballUrl<'a> {
+    /// Execute the subroutine.
+    pub async fn run(self) -> Result<PackageVersion, InstallTarballUrlError> {
+        let InstallTarballUrl {
+            http_client,
+            config,
+            tarball_url_cache,
+            reporter,
+            node_modules_dir,
+            url,
+        } = self;
+
+        let (package_version, cas_paths) =
+            fetch_tarball_url(http_client, &config.store_dir, tarball_url_cache, url).await?;
+        let name = &package_version.name;
+        let version = package_version.version.to_string();
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+
+        let save_path = config
+            .virtual_store_dir
+            .join(package_version.to_virtual_store_name())
+            .join("node_modules")
+            .join(name);
+        let symlink_path = node_modules_dir.join(name);
+
+        tracing::info!(target: "pacquet::import", ?save_path, ?symlink_path, "Import package");
+
+        create_cas_files(config.package_import_method, &save_path, &cas_paths)
+            .map_err(InstallTarballUrlError::CreateCasFiles)?;
+
+        symlink_package(&save_path, &symlink_path)
+            .map_err(InstallTarballUrlError::SymlinkPackage)?;
+
+        reporter.emit(Event::PackageLinked { name: name.clone(), version, path: symlink_path });
+
+        Ok(package_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_detect_tarball_urls() {
+        assert!(is_tarball_url("https://example.com/foo-1.0.0.tgz"));
+        assert!(is_tarball_url("http://example.com/foo/-/foo-1.0.0.tar.gz"));
+        assert!(is_tarball_url("https://example.com/foo-1.0.0.tgz?token=abc"));
+        assert!(!is_tarball_url("./foo-1.0.0.tgz"));
+        assert!(!is_tarball_url("https://example.com/foo"));
+        assert!(!is_tarball_url("git+https://example.com/foo.git"));
+    }
+}
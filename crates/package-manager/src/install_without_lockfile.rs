@@ -1,43 +1,134 @@
-use crate::InstallPackageFromRegistry;
+use crate::{
+    check_free_space, check_peer_dependencies, find_workspace_package, is_tarball_url,
+    registry_resolution, run_package_hook, symlink_package, DownloadPackageFromRegistry,
+    FindWorkspacePackageError, GitSpecifier, HoistCandidates, HoistDependencies, InstallGitPackage,
+    InstallGitPackageError, InstallLocalDirectory, InstallLocalDirectoryError, InstallLocalTarball,
+    InstallLocalTarballError, InstallPackageFromRegistry, InstallPackageFromRegistryError,
+    InstallPlan, InstallTarballUrl, InstallTarballUrlError, InsufficientSpaceError, IntegrityIndex,
+    LockfileBuilder, MissingPeerDependenciesError, Overrides, PackageHook, PeerDependencyRules,
+    PendingDownload, PendingDownloads, PlannedPackage, ResolutionCache, ResolveGitError,
+    SymlinkPackageError, TarballUrlCache,
+};
 use async_recursion::async_recursion;
-use dashmap::DashSet;
+use dashmap::{mapref::entry::Entry, DashMap};
+use derive_more::{Display, Error};
 use futures_util::future;
+use miette::Diagnostic;
 use node_semver::Version;
+use pacquet_lockfile::{DirectoryResolution, TarballResolution};
 use pacquet_network::ThrottledClient;
 use pacquet_npmrc::Npmrc;
 use pacquet_package_manifest::{DependencyGroup, PackageManifest};
 use pacquet_registry::PackageVersion;
+use pacquet_reporter::Reporter;
 use pacquet_tarball::MemCache;
+use pacquet_workspace::{ResolveCatalogError, WorkspaceManifest};
 use pipe_trait::Pipe;
+use std::{mem, path::Path};
 
 /// In-memory cache for packages that have started resolving dependencies.
 ///
-/// The contents of set is the package's virtual_store_name.
+/// The keys of the map are the packages' virtual_store_name, and the values are the smallest
+/// levels at which the packages have been reached.
 /// e.g. `@pnpm.e2e/dep-1@1.0.0` →  `@pnpm.e2e+dep-1@1.0.0`
-pub type ResolvedPackages = DashSet<String>;
+pub type ResolvedPackages = DashMap<String, usize>;
 
 /// This subroutine install packages from a `package.json` without reading or writing a lockfile.
 ///
-/// **Brief overview for each package:**
-/// * Fetch a tarball of the package.
-/// * Extract the tarball into the store directory.
-/// * Import (by reflink, hardlink, or copy) the files from the store dir to `node_modules/.pacquet/{name}@{version}/node_modules/{name}/`.
-/// * Create dependency symbolic links in `node_modules/.pacquet/{name}@{version}/node_modules/`.
-/// * Create a symbolic link at `node_modules/{name}`.
-/// * Repeat the process for the dependencies of the package.
+/// **Brief overview:**
+/// * Resolve the whole dependency tree from the registry.
+/// * Check that the store has enough free space for the packages to download, see [`check_free_space`].
+/// * For each package:
+///   * Fetch a tarball of the package.
+///   * Extract the tarball into the store directory.
+///   * Import (by reflink, hardlink, or copy) the files from the store dir to `node_modules/.pacquet/{name}@{version}/node_modules/{name}/`.
+///   * Create dependency symbolic links in `node_modules/.pacquet/{name}@{version}/node_modules/`.
+///   * Create a symbolic link at `node_modules/{name}`.
+/// * Hoist the transitive dependencies, see [`HoistDependencies`].
+///
+/// Direct dependencies that aren't from the registry (`file:`, tarball URLs, and git) are
+/// fetched as they are resolved.
+///
+/// The version ranges of the dependencies are replaced according to the [`Overrides`] of the
+/// manifest.
 #[must_use]
 pub struct InstallWithoutLockfile<'a, DependencyGroupList> {
     pub tarball_mem_cache: &'a MemCache,
     pub resolved_packages: &'a ResolvedPackages,
+    pub tarball_url_cache: &'a TarballUrlCache,
+    /// Metadata of the dependencies, shared by the packages that depend on them.
+    pub resolution_cache: &'a ResolutionCache,
     pub http_client: &'a ThrottledClient,
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub manifest: &'a PackageManifest,
+    pub workspace_manifest: Option<&'a WorkspaceManifest>,
+    /// Root directory of the workspace of `workspace_manifest`, where the projects that
+    /// `workspace:` dependencies refer to are looked for.
+    pub workspace_dir: Option<&'a Path>,
     pub dependency_groups: DependencyGroupList,
+    /// When set, packages are resolved and recorded into the plan instead of being installed.
+    pub dry_run: Option<&'a InstallPlan>,
+    /// Rewrites the metadata of each package as it is resolved.
+    pub package_hook: Option<&'a dyn PackageHook>,
+    /// How many levels of transitive dependencies are installed: `Some(0)` only installs the
+    /// dependencies of `package.json`, `None` installs the whole tree.
+    pub depth: Option<usize>,
+    /// Integrities of the existing lockfile, which isn't installed from, but whose integrities
+    /// the tarballs are checked against, see [`InstallPackageFromRegistry::integrity_index`].
+    pub integrity_index: Option<&'a IntegrityIndex>,
+    /// When set, the resolved packages are recorded into it to write the lockfile.
+    pub lockfile_builder: Option<&'a LockfileBuilder>,
+}
+
+/// Error type of [`InstallWithoutLockfile`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InstallWithoutLockfileError {
+    #[diagnostic(transparent)]
+    InstallPackageFromRegistry(#[error(source)] InstallPackageFromRegistryError),
+
+    #[diagnostic(transparent)]
+    InstallLocalTarball(#[error(source)] InstallLocalTarballError),
+
+    #[diagnostic(transparent)]
+    InstallTarballUrl(#[error(source)] InstallTarballUrlError),
+
+    #[diagnostic(transparent)]
+    InstallLocalDirectory(#[error(source)] InstallLocalDirectoryError),
+
+    #[diagnostic(transparent)]
+    ResolveGit(#[error(source)] ResolveGitError),
+
+    #[diagnostic(transparent)]
+    InstallGitPackage(#[error(source)] InstallGitPackageError),
+
+    #[diagnostic(transparent)]
+    MissingPeerDependencies(#[error(source)] MissingPeerDependenciesError),
+
+    #[diagnostic(transparent)]
+    ResolveCatalog(#[error(source)] ResolveCatalogError),
+
+    #[diagnostic(transparent)]
+    InsufficientSpace(#[error(source)] InsufficientSpaceError),
+
+    #[diagnostic(transparent)]
+    HoistDependencies(#[error(source)] SymlinkPackageError),
+
+    #[display("{name} is not a project of the workspace")]
+    #[diagnostic(code(pacquet_package_manager::workspace_package_not_found))]
+    WorkspacePackageNotFound { name: String },
+
+    #[diagnostic(transparent)]
+    FindWorkspacePackage(#[error(source)] FindWorkspacePackageError),
+
+    #[diagnostic(transparent)]
+    LinkWorkspacePackage(#[error(source)] SymlinkPackageError),
 }
 
 impl<'a, DependencyGroupList> InstallWithoutLockfile<'a, DependencyGroupList> {
     /// Execute the subroutine.
-    pub async fn run(self)
+    pub async fn run(self) -> Result<(), InstallWithoutLockfileError>
     where
         DependencyGroupList: IntoIterator<Item = DependencyGroup>,
     {
@@ -45,58 +136,295 @@ impl<'a, DependencyGroupList> InstallWithoutLockfile<'a, DependencyGroupList> {
             tarball_mem_cache,
             http_client,
             config,
+            reporter,
             manifest,
+            workspace_manifest,
+            workspace_dir,
             dependency_groups,
             resolved_packages,
+            tarball_url_cache,
+            resolution_cache,
+            dry_run,
+            package_hook,
+            depth,
+            integrity_index,
+            lockfile_builder,
         } = self;
 
-        let _: Vec<()> = manifest
-            .dependencies(dependency_groups.into_iter())
-            .map(|(name, version_range)| async move {
-                let dependency = InstallPackageFromRegistry {
-                    tarball_mem_cache,
-                    http_client,
-                    config,
-                    node_modules_dir: &config.modules_dir,
-                    name,
-                    version_range,
-                }
-                .run::<Version>()
-                .await
-                .unwrap();
+        let this = &InstallWithoutLockfile {
+            tarball_mem_cache,
+            http_client,
+            config,
+            reporter,
+            manifest,
+            workspace_manifest,
+            workspace_dir,
+            dependency_groups: (),
+            resolved_packages,
+            tarball_url_cache,
+            resolution_cache,
+            dry_run,
+            package_hook,
+            depth,
+            integrity_index,
+            lockfile_builder,
+        };
+        let overrides = &Overrides::from_manifest(manifest);
+        let peer_dependency_rules = &PeerDependencyRules::from_manifest(manifest);
+        let hoist_candidates = &HoistCandidates::new();
+        let pending_downloads = &PendingDownloads::default();
 
-                InstallWithoutLockfile {
-                    tarball_mem_cache,
-                    http_client,
-                    config,
-                    manifest,
-                    dependency_groups: (),
-                    resolved_packages,
+        dependency_groups
+            .into_iter()
+            .flat_map(|group| {
+                manifest
+                    .dependencies([group])
+                    .map(move |(name, specifier)| (group, name, specifier))
+            })
+            .map(|(group, name, specifier)| async move {
+                let version_range = match workspace_manifest {
+                    Some(workspace_manifest) => workspace_manifest
+                        .resolve_catalog(name, specifier)
+                        .map_err(InstallWithoutLockfileError::ResolveCatalog)?,
+                    None => specifier,
+                };
+                let version_range = overrides.apply(name, version_range, None);
+                if version_range.starts_with("workspace:") {
+                    return this.link_workspace_package(name);
+                }
+                let is_local = version_range.starts_with("file:")
+                    || is_tarball_url(version_range)
+                    || GitSpecifier::parse(version_range).is_some();
+                if let (Some(plan), true) = (dry_run, is_local) {
+                    // Resolving these requires downloading, extracting, or cloning them into the store.
+                    let package = PlannedPackage {
+                        name: name.to_string(),
+                        version: version_range.to_string(),
+                        unpacked_size: None,
+                        file_count: None,
+                        integrity: None,
+                    };
+                    plan.insert(format!("{name}@{version_range}"), package);
+                    return Ok(());
                 }
-                .install_dependencies_from_registry(&dependency)
-                .await;
+                let (mut dependency, resolution) = if let Some(relative_path) =
+                    version_range.strip_prefix("file:")
+                {
+                    let project_dir = manifest.dir();
+                    let path = project_dir.join(relative_path);
+                    if path.is_dir() {
+                        let dependency = InstallLocalDirectory {
+                            config,
+                            reporter,
+                            node_modules_dir: &config.modules_dir,
+                            path: &path,
+                        }
+                        .run()
+                        .map_err(InstallWithoutLockfileError::InstallLocalDirectory)?;
+                        let resolution =
+                            DirectoryResolution { directory: relative_path.to_string() };
+                        (dependency, Some(resolution.into()))
+                    } else {
+                        let dependency = InstallLocalTarball {
+                            config,
+                            reporter,
+                            node_modules_dir: &config.modules_dir,
+                            path: &path,
+                        }
+                        .run()
+                        .map_err(InstallWithoutLockfileError::InstallLocalTarball)?;
+                        // the path is kept relative to the project, unlike the one of `dist`
+                        let resolution = TarballResolution {
+                            tarball: version_range.to_string(),
+                            integrity: dependency.dist.integrity.clone(),
+                        };
+                        (dependency, Some(resolution.into()))
+                    }
+                } else if is_tarball_url(version_range) {
+                    let dependency = InstallTarballUrl {
+                        http_client,
+                        config,
+                        tarball_url_cache,
+                        reporter,
+                        node_modules_dir: &config.modules_dir,
+                        url: version_range,
+                    }
+                    .run()
+                    .await
+                    .map_err(InstallWithoutLockfileError::InstallTarballUrl)?;
+                    let resolution = TarballResolution {
+                        tarball: version_range.to_string(),
+                        integrity: dependency.dist.integrity.clone(),
+                    };
+                    (dependency, Some(resolution.into()))
+                } else if let Some(git_specifier) = GitSpecifier::parse(version_range) {
+                    let resolution =
+                        git_specifier.resolve().map_err(InstallWithoutLockfileError::ResolveGit)?;
+                    let dependency = InstallGitPackage {
+                        config,
+                        reporter,
+                        node_modules_dir: &config.modules_dir,
+                        resolution: &resolution,
+                    }
+                    .run()
+                    .map_err(InstallWithoutLockfileError::InstallGitPackage)?;
+                    (dependency, Some(resolution.into()))
+                } else {
+                    let dependency = InstallPackageFromRegistry {
+                        tarball_mem_cache,
+                        resolution_cache,
+                        http_client,
+                        config,
+                        reporter,
+                        node_modules_dir: &config.modules_dir,
+                        name,
+                        version_range,
+                        dry_run,
+                        package_hook,
+                        integrity_index,
+                        pending_downloads: Some(pending_downloads),
+                    }
+                    .run::<Version>()
+                    .await
+                    .map_err(InstallWithoutLockfileError::InstallPackageFromRegistry)?;
+                    let resolution = registry_resolution(&dependency, config);
+                    (dependency, Some(resolution))
+                };
+                // Packages from the registry have already been through the hook.
+                if is_local {
+                    run_package_hook(package_hook, config, &mut dependency);
+                }
+                if let (Some(lockfile_builder), Some(resolution)) = (lockfile_builder, resolution) {
+                    lockfile_builder.add_package(&dependency, resolution);
+                    lockfile_builder.add_direct_dependency(group, name, specifier, &dependency);
+                }
+
+                hoist_candidates.insert(
+                    name,
+                    0,
+                    dependency.to_virtual_store_name(),
+                    dependency.name.clone(),
+                );
+                this.check_peer_dependencies(peer_dependency_rules, &dependency, None)?;
+                this.install_dependencies_from_registry(
+                    overrides,
+                    peer_dependency_rules,
+                    hoist_candidates,
+                    pending_downloads,
+                    &dependency,
+                    0,
+                )
+                .await
             })
-            .pipe(future::join_all)
-            .await;
+            .pipe(future::try_join_all)
+            .await?;
+
+        if dry_run.is_none() {
+            let downloads =
+                mem::take(&mut *pending_downloads.lock().expect("lock the pending downloads"));
+            check_free_space(
+                &config.store_dir,
+                downloads.iter().map(|download| &download.package_version),
+            )
+            .map_err(InstallWithoutLockfileError::InsufficientSpace)?;
+            downloads
+                .iter()
+                .map(|PendingDownload { node_modules_dir, name, package_version }| {
+                    DownloadPackageFromRegistry {
+                        tarball_mem_cache,
+                        http_client,
+                        config,
+                        reporter,
+                        node_modules_dir,
+                        name,
+                        package_version,
+                        integrity_index,
+                    }
+                    .run()
+                })
+                .pipe(future::try_join_all)
+                .await
+                .map_err(InstallWithoutLockfileError::InstallPackageFromRegistry)?;
+
+            HoistDependencies { config, candidates: hoist_candidates }
+                .run()
+                .map_err(InstallWithoutLockfileError::HoistDependencies)?;
+        }
+
+        Ok(())
     }
 }
 
 impl<'a> InstallWithoutLockfile<'a, ()> {
+    /// Link the project of the workspace named `name` into the modules directory.
+    ///
+    /// The dependencies of the project are left to its own install.
+    fn link_workspace_package(&self, name: &str) -> Result<(), InstallWithoutLockfileError> {
+        let InstallWithoutLockfile { config, workspace_manifest, workspace_dir, dry_run, .. } =
+            self;
+        let not_found =
+            || InstallWithoutLockfileError::WorkspacePackageNotFound { name: name.to_string() };
+        let (Some(workspace_dir), Some(workspace_manifest)) = (workspace_dir, workspace_manifest)
+        else {
+            return Err(not_found());
+        };
+        let package = find_workspace_package(workspace_dir, workspace_manifest, name)
+            .map_err(InstallWithoutLockfileError::FindWorkspacePackage)?
+            .ok_or_else(not_found)?;
+        if dry_run.is_some() {
+            return Ok(());
+        }
+        symlink_package(&package.dir, &config.modules_dir.join(name))
+            .map_err(InstallWithoutLockfileError::LinkWorkspacePackage)
+    }
+
     /// Install dependencies of a dependency.
+    ///
+    /// `level` is the number of packages between `package` and the root project.
     #[async_recursion]
-    async fn install_dependencies_from_registry(&self, package: &PackageVersion) {
+    async fn install_dependencies_from_registry(
+        &self,
+        overrides: &Overrides,
+        peer_dependency_rules: &PeerDependencyRules,
+        hoist_candidates: &HoistCandidates,
+        pending_downloads: &PendingDownloads,
+        package: &PackageVersion,
+        level: usize,
+    ) -> Result<(), InstallWithoutLockfileError> {
         let InstallWithoutLockfile {
             tarball_mem_cache,
             http_client,
             config,
+            reporter,
             resolved_packages,
+            resolution_cache,
+            dry_run,
+            package_hook,
+            depth,
+            integrity_index,
+            lockfile_builder,
             ..
         } = self;
 
-        // This package has already resolved, there is no need to reinstall again.
-        if !resolved_packages.insert(package.to_virtual_store_name()) {
-            tracing::info!(target: "pacquet::install", package = ?package.to_virtual_store_name(), "Skip subset");
-            return;
+        if depth.is_some_and(|depth| level >= depth) {
+            tracing::info!(target: "pacquet::install", package = ?package.to_virtual_store_name(), "Skip subset beyond depth");
+            return Ok(());
+        }
+
+        // This package has already resolved, there is no need to reinstall again, unless it is
+        // now reached at a shallower level, which leaves more levels of its dependencies within
+        // the depth.
+        match resolved_packages.entry(package.to_virtual_store_name()) {
+            Entry::Occupied(mut entry) if depth.is_some() && level < *entry.get() => {
+                entry.insert(level);
+            }
+            Entry::Occupied(_) => {
+                tracing::info!(target: "pacquet::install", package = ?package.to_virtual_store_name(), "Skip subset");
+                return Ok(());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(level);
+            }
         }
 
         let node_modules_path = self
@@ -110,22 +438,95 @@ impl<'a> InstallWithoutLockfile<'a, ()> {
         package
             .dependencies(self.config.auto_install_peers)
             .map(|(name, version_range)| async {
+                let version_range = overrides.apply(name, version_range, Some(package));
                 let dependency = InstallPackageFromRegistry {
                     tarball_mem_cache,
+                    resolution_cache,
                     http_client,
                     config,
+                    reporter: *reporter,
                     node_modules_dir: &node_modules_path,
                     name,
                     version_range,
+                    dry_run: *dry_run,
+                    package_hook: *package_hook,
+                    integrity_index: *integrity_index,
+                    pending_downloads: Some(pending_downloads),
                 }
                 .run::<Version>()
                 .await
-                .unwrap(); // TODO: proper error propagation
-                self.install_dependencies_from_registry(&dependency).await;
+                .map_err(InstallWithoutLockfileError::InstallPackageFromRegistry)?;
+                if let Some(lockfile_builder) = lockfile_builder {
+                    lockfile_builder
+                        .add_package(&dependency, registry_resolution(&dependency, config));
+                    lockfile_builder.add_dependency(package, name, &dependency);
+                }
+
+                hoist_candidates.insert(
+                    name,
+                    level + 1,
+                    dependency.to_virtual_store_name(),
+                    dependency.name.clone(),
+                );
+                self.check_peer_dependencies(peer_dependency_rules, &dependency, Some(package))?;
+                self.install_dependencies_from_registry(
+                    overrides,
+                    peer_dependency_rules,
+                    hoist_candidates,
+                    pending_downloads,
+                    &dependency,
+                    level + 1,
+                )
+                .await
             })
-            .pipe(future::join_all)
-            .await;
+            .pipe(future::try_join_all)
+            .await?;
 
         tracing::info!(target: "pacquet::install", node_modules = ?node_modules_path, "Complete subset");
+
+        Ok(())
+    }
+
+    /// Check the peer dependencies of `package` when they aren't installed automatically.
+    ///
+    /// Peers are resolved from the dependencies of `parent` (or the root project when `package`
+    /// is a direct dependency) and, if `resolve-peers-from-workspace-root` is set, from the
+    /// dependencies of the root project.
+    fn check_peer_dependencies(
+        &self,
+        rules: &PeerDependencyRules,
+        package: &PackageVersion,
+        parent: Option<&PackageVersion>,
+    ) -> Result<(), InstallWithoutLockfileError> {
+        let InstallWithoutLockfile { config, reporter, manifest, .. } = self;
+
+        if config.auto_install_peers {
+            return Ok(());
+        }
+
+        let declared_by_root = |name: &str| {
+            manifest
+                .dependencies([
+                    DependencyGroup::Prod,
+                    DependencyGroup::Dev,
+                    DependencyGroup::Optional,
+                ])
+                .find(|(dependency, _)| *dependency == name)
+                .map(|(_, version_range)| version_range)
+        };
+        let available_range = |name: &str| match parent {
+            None => declared_by_root(name),
+            Some(parent) => parent
+                .dependencies(false)
+                .find(|(dependency, _)| *dependency == name)
+                .map(|(_, version_range)| version_range)
+                .or_else(|| {
+                    config.resolve_peers_from_workspace_root.then(|| declared_by_root(name))?
+                }),
+        };
+
+        let strict = config.strict_peer_dependencies;
+        check_peer_dependencies(package, available_range, rules, strict, *reporter)
+            .map_err(InstallWithoutLockfileError::MissingPeerDependencies)
     }
 }
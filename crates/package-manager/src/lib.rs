@@ -1,27 +1,81 @@
 mod add;
+mod check_engines;
+mod check_free_space;
+mod check_package_manager;
+mod check_peer_dependencies;
 mod create_cas_files;
 mod create_symlink_layout;
 mod create_virtual_dir_by_snapshot;
 mod create_virtual_store;
+mod fetch;
+mod fetch_package_by_snapshot;
+mod git_specifier;
+mod hoist_dependencies;
 mod install;
 mod install_frozen_lockfile;
+mod install_git_package;
+mod install_local_directory;
+mod install_local_tarball;
 mod install_package_by_snapshot;
 mod install_package_from_registry;
+mod install_plan;
+mod install_report;
+mod install_tarball_url;
 mod install_without_lockfile;
+mod link_bins;
 mod link_file;
+mod lockfile_builder;
+mod modules_yaml;
+mod npm_alias;
+mod overrides;
+mod package_files;
+mod package_hook;
+mod package_manager;
+mod prune_modules_dir;
+mod rebuild;
+mod repair_store;
 mod symlink_direct_dependencies;
 mod symlink_package;
+mod verify_virtual_store;
+mod workspace_package;
 
 pub use add::*;
+pub use check_engines::*;
+pub use check_free_space::*;
+pub use check_package_manager::*;
+pub use check_peer_dependencies::*;
 pub use create_cas_files::*;
 pub use create_symlink_layout::*;
 pub use create_virtual_dir_by_snapshot::*;
 pub use create_virtual_store::*;
+pub use fetch::*;
+pub use fetch_package_by_snapshot::*;
+pub use git_specifier::*;
+pub use hoist_dependencies::*;
 pub use install::*;
 pub use install_frozen_lockfile::*;
+pub use install_git_package::*;
+pub use install_local_directory::*;
+pub use install_local_tarball::*;
 pub use install_package_by_snapshot::*;
 pub use install_package_from_registry::*;
+pub use install_plan::*;
+pub use install_report::*;
+pub use install_tarball_url::*;
 pub use install_without_lockfile::*;
+pub use link_bins::*;
 pub use link_file::*;
+pub use lockfile_builder::*;
+pub use modules_yaml::*;
+pub use npm_alias::*;
+pub use overrides::*;
+pub use package_files::*;
+pub use package_hook::*;
+pub use package_manager::*;
+pub use prune_modules_dir::*;
+pub use rebuild::*;
+pub use repair_store::*;
 pub use symlink_direct_dependencies::*;
 pub use symlink_package::*;
+pub use verify_virtual_store::*;
+pub use workspace_package::*;
@@ -0,0 +1,187 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_package_manifest::{PackageManifest, PackageManifestError};
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Error type of [`link_bins`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum LinkBinsError {
+    #[diagnostic(transparent)]
+    LoadManifest(#[error(source)] PackageManifestError),
+
+    #[display("Failed to create the bin directory at {dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::create_bin_dir))]
+    CreateBinDir {
+        dir: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to link the bin {bin_path:?} to {target:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::link_bin))]
+    LinkBin {
+        bin_path: PathBuf,
+        target: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+}
+
+/// The commands that a package declares in the `bin` field of its `package.json`, with the
+/// paths of their files relative to the package.
+///
+/// A string `bin` is a single command named after the package without its scope. Names and
+/// paths that would end up outside of the bin directory or the package are skipped.
+pub fn package_bins(manifest: &Value) -> Vec<(String, PathBuf)> {
+    let unscoped = |name: &str| name.rsplit('/').next().unwrap_or_default().to_string();
+    let bins: Vec<(String, &str)> = match manifest.get("bin") {
+        Some(Value::String(path)) => manifest
+            .get("name")
+            .and_then(Value::as_str)
+            .map(|name| vec![(unscoped(name), path.as_str())])
+            .unwrap_or_default(),
+        Some(Value::Object(bins)) => {
+            bins.iter().filter_map(|(name, path)| Some((unscoped(name), path.as_str()?))).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let is_valid_name =
+        |name: &str| !matches!(name, "" | "." | "..") && !name.contains(['/', '\\']);
+    let is_inside = |path: &Path| {
+        path.components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    };
+    bins.into_iter()
+        .map(|(name, path)| (name, PathBuf::from(path)))
+        .filter(|(name, path)| {
+            let valid = is_valid_name(name) && is_inside(path);
+            if !valid {
+                tracing::warn!(target: "pacquet::bins", ?name, ?path, "Skip invalid bin");
+            }
+            valid
+        })
+        .collect()
+}
+
+/// Link the commands of the package at `package_dir` into `bin_dir`, replacing the existing
+/// commands of the same names, and return the paths of the links.
+///
+/// On POSIX platforms, each link is a symlink to the file of the command, which is made
+/// executable. On Windows, it is a `.cmd` shim that runs the file with `node`.
+pub fn link_bins(package_dir: &Path, bin_dir: &Path) -> Result<Vec<PathBuf>, LinkBinsError> {
+    let manifest = PackageManifest::from_path(package_dir.join("package.json"))
+        .map_err(LinkBinsError::LoadManifest)?;
+    let bins = package_bins(manifest.value());
+    if bins.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    fs::create_dir_all(bin_dir)
+        .map_err(|error| LinkBinsError::CreateBinDir { dir: bin_dir.to_path_buf(), error })?;
+
+    bins.into_iter()
+        .map(|(name, path)| {
+            let target = package_dir.join(path);
+            let bin_path = link_bin(&target, bin_dir, &name).map_err(|(bin_path, error)| {
+                LinkBinsError::LinkBin { bin_path, target: target.clone(), error }
+            })?;
+            tracing::info!(target: "pacquet::bins", ?bin_path, ?target, "Link bin");
+            Ok(bin_path)
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn link_bin(target: &Path, bin_dir: &Path, name: &str) -> Result<PathBuf, (PathBuf, io::Error)> {
+    use pacquet_fs::file_mode::{is_all_exec, make_file_executable};
+    use std::os::unix::fs::{symlink, MetadataExt};
+
+    let bin_path = bin_dir.join(name);
+    let link = || {
+        let file = fs::File::open(target)?;
+        if !is_all_exec(file.metadata()?.mode()) {
+            make_file_executable(&file)?;
+        }
+        match fs::remove_file(&bin_path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => {}
+        }
+        symlink(target, &bin_path)
+    };
+    link().map_err(|error| (bin_path.clone(), error))?;
+    Ok(bin_path)
+}
+
+#[cfg(windows)]
+fn link_bin(target: &Path, bin_dir: &Path, name: &str) -> Result<PathBuf, (PathBuf, io::Error)> {
+    let bin_path = bin_dir.join(format!("{name}.cmd"));
+    let shim = format!("@node \"{}\" %*\r\n", target.display());
+    fs::write(&bin_path, shim).map_err(|error| (bin_path.clone(), error))?;
+    Ok(bin_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn should_list_bins_of_both_forms() {
+        let manifest = json!({ "name": "@scope/foo", "bin": "./cli.js" });
+        assert_eq!(package_bins(&manifest), [("foo".to_string(), PathBuf::from("./cli.js"))]);
+
+        let manifest = json!({
+            "name": "foo",
+            "bin": { "foo": "bin/foo.js", "@scope/bar": "bin/bar.js" },
+        });
+        let mut bins = package_bins(&manifest);
+        bins.sort();
+        assert_eq!(
+            bins,
+            [
+                ("bar".to_string(), PathBuf::from("bin/bar.js")),
+                ("foo".to_string(), PathBuf::from("bin/foo.js")),
+            ]
+        );
+
+        assert_eq!(package_bins(&json!({ "name": "foo" })), []);
+    }
+
+    #[test]
+    fn should_skip_bins_outside_of_their_directories() {
+        let manifest = json!({
+            "name": "foo",
+            "bin": { "..": "cli.js", "foo": "../../cli.js", "bar": "/etc/passwd", "baz": "cli.js" },
+        });
+        assert_eq!(package_bins(&manifest), [("baz".to_string(), PathBuf::from("cli.js"))]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_link_executable_bins() {
+        use std::os::unix::fs::MetadataExt;
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("foo");
+        fs::create_dir_all(package_dir.join("bin")).unwrap();
+        fs::write(package_dir.join("package.json"), r#"{ "name": "foo", "bin": "bin/foo.js" }"#)
+            .unwrap();
+        fs::write(package_dir.join("bin/foo.js"), "#!/usr/bin/env node\n").unwrap();
+
+        let bin_dir = dir.path().join("bin");
+        let linked = link_bins(&package_dir, &bin_dir).unwrap();
+        assert_eq!(linked, [bin_dir.join("foo")]);
+        assert_eq!(fs::read_link(bin_dir.join("foo")).unwrap(), package_dir.join("bin/foo.js"));
+        let mode = fs::metadata(bin_dir.join("foo")).unwrap().mode();
+        assert!(pacquet_fs::file_mode::is_all_exec(mode));
+
+        eprintln!("Linking again replaces the existing link");
+        link_bins(&package_dir, &bin_dir).unwrap();
+    }
+}
@@ -9,24 +9,39 @@ use std::{
 #[derive(Debug, Display, Error, Diagnostic)]
 pub enum LinkFileError {
     #[display("cannot create directory at {dirname:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::create_dir))]
     CreateDir {
         dirname: PathBuf,
         #[error(source)]
         error: io::Error,
     },
     #[display("fail to create a link from {from:?} to {to:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::create_link))]
     CreateLink {
         from: PathBuf,
         to: PathBuf,
         #[error(source)]
         error: io::Error,
     },
+    #[display("the file system at {to:?} supports neither copying nor hard linking from {from:?}: {error}")]
+    #[diagnostic(
+        code(pacquet_package_manager::unsupported_link),
+        help("Make sure the store and node_modules are writable by the current user")
+    )]
+    Unsupported {
+        from: PathBuf,
+        to: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
 }
 
 /// Reflink or copy a single file.
 ///
-/// * If `target_link` already exists, do nothing.
+/// * If `target_link` already exists, or another thread creates it at the same time, do nothing.
 /// * If parent dir of `target_link` doesn't exist, it will be created.
+/// * If the file system refuses both reflink and copy, a hard link is attempted before failing
+///   with [`LinkFileError::Unsupported`].
 pub fn link_file(source_file: &Path, target_link: &Path) -> Result<(), LinkFileError> {
     if target_link.exists() {
         return Ok(());
@@ -42,13 +57,21 @@ pub fn link_file(source_file: &Path, target_link: &Path) -> Result<(), LinkFileE
     // TODO: add hardlink (https://github.com/pnpm/pacquet/issues/174)
     // NOTE: do not hardlink packages with postinstall
 
-    reflink_copy::reflink_or_copy(source_file, target_link).map_err(|error| {
-        LinkFileError::CreateLink {
-            from: source_file.to_path_buf(),
-            to: target_link.to_path_buf(),
-            error,
-        }
-    })?;
+    let error = match reflink_copy::reflink_or_copy(source_file, target_link) {
+        Ok(_) => return Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => return Ok(()),
+        Err(error) => error,
+    };
+
+    let from = source_file.to_path_buf();
+    let to = target_link.to_path_buf();
+    if !matches!(error.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::Unsupported) {
+        return Err(LinkFileError::CreateLink { from, to, error });
+    }
 
-    Ok(())
+    match fs::hard_link(source_file, target_link) {
+        Ok(()) => Ok(()),
+        Err(hard_link_error) if hard_link_error.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(_) => Err(LinkFileError::Unsupported { from, to, error }),
+    }
 }
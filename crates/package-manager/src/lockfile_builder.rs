@@ -0,0 +1,272 @@
+use dashmap::DashMap;
+use pacquet_lockfile::{
+    DependencyPath, Lockfile, LockfileResolution, LockfileSettings, PackageSnapshot,
+    PackageSnapshotDependency, PkgName, PkgVerPeer, ProjectSnapshot, RegistryResolution,
+    ResolvedDependencyMap, ResolvedDependencySpec, RootProjectSnapshot, TarballResolution,
+};
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use pacquet_registry::PackageVersion;
+use std::collections::{HashMap, HashSet};
+
+/// Packages resolved by [`InstallWithoutLockfile`](crate::InstallWithoutLockfile), from which
+/// [`Install`](crate::Install) writes `pnpm-lock.yaml`.
+///
+/// Packages whose names can't be written to the lockfile are left out of it, so the next install
+/// resolves them again.
+#[derive(Debug, Default)]
+pub struct LockfileBuilder {
+    direct_dependencies: DashMap<(DependencyGroup, PkgName), ResolvedDependencySpec>,
+    packages: DashMap<DependencyPath, PackageSnapshot>,
+}
+
+impl LockfileBuilder {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        LockfileBuilder::default()
+    }
+
+    /// Record a dependency of `package.json`, which is declared as `alias` with `specifier`.
+    pub(crate) fn add_direct_dependency(
+        &self,
+        group: DependencyGroup,
+        alias: &str,
+        specifier: &str,
+        package: &PackageVersion,
+    ) {
+        let (Ok(alias), Some(version)) = (alias.parse(), snapshot_dependency(alias, package))
+        else {
+            return;
+        };
+        let specifier = specifier.to_string();
+        self.direct_dependencies
+            .insert((group, alias), ResolvedDependencySpec { specifier, version });
+    }
+
+    /// Record a resolved package, unless it has already been.
+    pub(crate) fn add_package(&self, package: &PackageVersion, resolution: LockfileResolution) {
+        let Some(dependency_path) = dependency_path(package) else { return };
+        self.packages.entry(dependency_path).or_insert_with(|| PackageSnapshot {
+            resolution,
+            id: None,
+            name: None,
+            version: None,
+            engines: package.engines.clone(),
+            cpu: None,
+            os: None,
+            libc: None,
+            deprecated: package.deprecated.clone(),
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: package.peer_dependencies.clone(),
+            peer_dependencies_meta: None,
+            dependencies: None,
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        });
+    }
+
+    /// Record that `parent`, which must have been [added](Self::add_package), depends on
+    /// `package` as `alias`.
+    pub(crate) fn add_dependency(
+        &self,
+        parent: &PackageVersion,
+        alias: &str,
+        package: &PackageVersion,
+    ) {
+        let (Some(parent), Ok(alias), Some(version)) =
+            (dependency_path(parent), alias.parse(), snapshot_dependency(alias, package))
+        else {
+            return;
+        };
+        if let Some(mut snapshot) = self.packages.get_mut(&parent) {
+            snapshot.dependencies.get_or_insert_with(HashMap::new).insert(alias, version);
+        }
+    }
+
+    /// Create the lockfile of the project of `manifest`.
+    ///
+    /// The packages that are only depended on by the `devDependencies` are marked as `dev`, and
+    /// those that are only depended on by the other dependencies are marked as not `dev`.
+    pub fn build(self, config: &Npmrc, manifest: &PackageManifest) -> Lockfile {
+        let LockfileBuilder { direct_dependencies, packages } = self;
+        let mut packages: HashMap<_, _> = packages.into_iter().collect();
+
+        let mut groups = HashMap::<DependencyGroup, ResolvedDependencyMap>::new();
+        for ((group, alias), spec) in direct_dependencies {
+            groups.entry(group).or_default().insert(alias, spec);
+        }
+        let mut take_group = |group| groups.remove(&group).filter(|map| !map.is_empty());
+        let project_snapshot = ProjectSnapshot {
+            dependencies: take_group(DependencyGroup::Prod),
+            optional_dependencies: take_group(DependencyGroup::Optional),
+            dev_dependencies: take_group(DependencyGroup::Dev),
+            ..Default::default()
+        };
+
+        let reachable = |maps: &[&Option<ResolvedDependencyMap>]| {
+            let mut reachable = HashSet::<DependencyPath>::new();
+            let mut queue: Vec<DependencyPath> = maps
+                .iter()
+                .copied()
+                .flatten()
+                .flatten()
+                .map(|(alias, spec)| to_dependency_path(alias, &spec.version))
+                .collect();
+            while let Some(dependency_path) = queue.pop() {
+                let Some(snapshot) = packages.get(&dependency_path) else { continue };
+                queue.extend(
+                    snapshot
+                        .dependencies
+                        .iter()
+                        .flatten()
+                        .map(|(alias, version)| to_dependency_path(alias, version))
+                        .filter(|dependency_path| !reachable.contains(dependency_path)),
+                );
+                reachable.insert(dependency_path);
+            }
+            reachable
+        };
+        let prod =
+            reachable(&[&project_snapshot.dependencies, &project_snapshot.optional_dependencies]);
+        let dev = reachable(&[&project_snapshot.dev_dependencies]);
+        for (dependency_path, snapshot) in packages.iter_mut() {
+            snapshot.dev = match (prod.contains(dependency_path), dev.contains(dependency_path)) {
+                (true, false) => Some(false),
+                (false, true) => Some(true),
+                _ => None,
+            };
+        }
+
+        let mut lockfile = Lockfile::new(RootProjectSnapshot::Single(project_snapshot), packages);
+        lockfile.settings = Some(LockfileSettings {
+            auto_install_peers: config.auto_install_peers,
+            exclude_links_from_lockfile: false,
+        });
+        let overrides: HashMap<_, _> = manifest
+            .overrides()
+            .map(|(selector, version_range)| (selector.to_string(), version_range.to_string()))
+            .collect();
+        lockfile.overrides = (!overrides.is_empty()).then_some(overrides);
+        lockfile
+    }
+}
+
+/// Resolution of a package from the registry, which only records the URL of its tarball if
+/// [`lockfile_include_tarball_url`](Npmrc::lockfile_include_tarball_url) is set.
+pub(crate) fn registry_resolution(package: &PackageVersion, config: &Npmrc) -> LockfileResolution {
+    match &package.dist.integrity {
+        Some(integrity) if !config.lockfile_include_tarball_url => {
+            RegistryResolution { integrity: integrity.clone() }.into()
+        }
+        integrity => TarballResolution {
+            tarball: package.as_tarball_url().to_string(),
+            integrity: integrity.clone(),
+        }
+        .into(),
+    }
+}
+
+/// Key of `package` in the `packages` of the lockfile.
+fn dependency_path(package: &PackageVersion) -> Option<DependencyPath> {
+    format!("/{}@{}", package.name, package.version).parse().ok()
+}
+
+/// How a dependency on `package` declared as `alias` is written, see [`PackageSnapshotDependency`].
+fn snapshot_dependency(alias: &str, package: &PackageVersion) -> Option<PackageSnapshotDependency> {
+    if alias == package.name {
+        package.version.to_string().parse::<PkgVerPeer>().ok().map(Into::into)
+    } else {
+        dependency_path(package).map(Into::into)
+    }
+}
+
+/// Key in the `packages` of the lockfile of the package that `alias` depends on as `version`.
+fn to_dependency_path(alias: &PkgName, version: &PackageSnapshotDependency) -> DependencyPath {
+    DependencyPath { custom_registry: None, package_specifier: version.to_package_specifier(alias) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pipe_trait::Pipe;
+    use pretty_assertions::assert_eq;
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> PackageVersion {
+        serde_json::json!({
+            "name": name,
+            "version": version,
+            "dist": {
+                "integrity": "sha512-gnhPt75i/dq/z3/6q/0asP78D0u592D5L1pd7M8P+dck6Fu/jJeL6iVVK23fptSUZj8Vjf++7wXA8UNclGQcbA==",
+                "tarball": format!("https://registry.npmjs.org/{name}/-/{name}-{version}.tgz"),
+            },
+            "dependencies": dependencies.iter().copied().collect::<HashMap<_, _>>(),
+        })
+        .pipe(serde_json::from_value)
+        .unwrap()
+    }
+
+    #[test]
+    fn build() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = PackageManifest::create_if_needed(dir.path().join("package.json")).unwrap();
+        let config = Npmrc::new().leak();
+
+        let shared = package("shared", "1.0.0", &[]);
+        let prod = package("prod", "1.0.0", &[("shared", "^1.0.0")]);
+        let dev = package("dev", "2.0.0", &[("shared", "^1.0.0"), ("dev-only", "^1.0.0")]);
+        let dev_only = package("dev-only", "1.0.0", &[]);
+
+        let builder = LockfileBuilder::new();
+        for package in [&shared, &prod, &dev, &dev_only] {
+            builder.add_package(package, registry_resolution(package, config));
+        }
+        builder.add_direct_dependency(DependencyGroup::Prod, "prod", "^1.0.0", &prod);
+        builder.add_direct_dependency(DependencyGroup::Dev, "alias", "npm:dev@^2.0.0", &dev);
+        builder.add_dependency(&prod, "shared", &shared);
+        builder.add_dependency(&dev, "shared", &shared);
+        builder.add_dependency(&dev, "dev-only", &dev_only);
+        let lockfile = builder.build(config, &manifest);
+
+        let RootProjectSnapshot::Single(project_snapshot) = &lockfile.project_snapshot else {
+            panic!("expected a single project");
+        };
+        let version = |map: &Option<ResolvedDependencyMap>, alias: &str| {
+            let spec = &map.as_ref().unwrap()[&alias.parse().unwrap()];
+            (spec.specifier.clone(), spec.version.to_string())
+        };
+        assert_eq!(
+            version(&project_snapshot.dependencies, "prod"),
+            ("^1.0.0".to_string(), "1.0.0".to_string()),
+        );
+        assert_eq!(
+            version(&project_snapshot.dev_dependencies, "alias"),
+            ("npm:dev@^2.0.0".to_string(), "/dev@2.0.0".to_string()),
+        );
+        assert_eq!(project_snapshot.optional_dependencies, None);
+
+        let packages = lockfile.packages.as_ref().unwrap();
+        let dev_flag = |dependency_path: &str| packages[&dependency_path.parse().unwrap()].dev;
+        assert_eq!(dev_flag("/prod@1.0.0"), Some(false));
+        assert_eq!(dev_flag("/dev@2.0.0"), Some(true));
+        assert_eq!(dev_flag("/dev-only@1.0.0"), Some(true));
+        assert_eq!(dev_flag("/shared@1.0.0"), None);
+        assert_eq!(
+            packages[&"/dev@2.0.0".parse().unwrap()]
+                .dependencies
+                .as_ref()
+                .unwrap()
+                .get(&"shared".parse().unwrap())
+                .map(ToString::to_string),
+            Some("1.0.0".to_string()),
+        );
+        assert!(matches!(
+            packages[&"/shared@1.0.0".parse().unwrap()].resolution,
+            LockfileResolution::Registry(_),
+        ));
+    }
+}
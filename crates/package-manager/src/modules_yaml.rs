@@ -0,0 +1,357 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_npmrc::{NodeLinker, Npmrc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Version of the layout of `node_modules` that pacquet creates, the same as the one of pnpm.
+pub const LAYOUT_VERSION: u32 = 5;
+
+/// Content of `node_modules/.modules.yaml`, the settings that `node_modules` was built with.
+///
+/// The fields that pnpm writes and pacquet doesn't use are ignored when reading the file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModulesYaml {
+    pub layout_version: u32,
+    #[serde(default)]
+    pub package_manager: String,
+    #[serde(default)]
+    pub node_linker: NodeLinker,
+    /// The `v3` directory of the store, like pnpm writes it.
+    pub store_dir: PathBuf,
+    /// Relative to the directory of `.modules.yaml`, like pnpm writes it.
+    pub virtual_store_dir: PathBuf,
+    /// `None` when hoisting is disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hoist_pattern: Option<Vec<String>>,
+    #[serde(default)]
+    pub public_hoist_pattern: Vec<String>,
+    /// The default registry is keyed by `default`, the others by their scopes.
+    #[serde(default)]
+    pub registries: BTreeMap<String, String>,
+}
+
+/// Error type of [`ModulesYaml`] and [`ValidateModulesDir`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum ModulesYamlError {
+    #[display("Failed to read {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_modules_yaml))]
+    ReadFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse {path:?}: {error}")]
+    #[diagnostic(
+        code(pacquet_package_manager::parse_modules_yaml),
+        help("Remove the node_modules directory and install again")
+    )]
+    ParseYaml {
+        path: PathBuf,
+        #[error(source)]
+        error: serde_yaml::Error,
+    },
+
+    #[display("Failed to write {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::write_modules_yaml))]
+    WriteFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display(
+        "{modules_dir:?} was built with different settings ({}) and can't be rebuilt with a frozen lockfile",
+        settings.join(", ")
+    )]
+    #[diagnostic(
+        code(pacquet_package_manager::incompatible_modules_dir),
+        help("Run the install with --force to rebuild node_modules")
+    )]
+    Incompatible { modules_dir: PathBuf, settings: Vec<&'static str> },
+
+    #[display("Failed to remove {path:?} to rebuild it: {error}")]
+    #[diagnostic(code(pacquet_package_manager::remove_modules_dir))]
+    RemoveDir {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+}
+
+impl ModulesYaml {
+    /// Name of the file in the modules directory.
+    pub const FILE_NAME: &'static str = ".modules.yaml";
+
+    /// The settings of `config` that `node_modules` is built with.
+    pub fn from_config(config: &Npmrc) -> Self {
+        let virtual_store_dir = config
+            .virtual_store_dir
+            .strip_prefix(&config.modules_dir)
+            .map_or_else(|_| config.virtual_store_dir.clone(), Path::to_path_buf);
+        let public_hoist_pattern = if config.shamefully_hoist {
+            vec!["*".to_string()]
+        } else {
+            config.public_hoist_pattern.clone()
+        };
+        let registries = config
+            .scoped_registries
+            .iter()
+            .map(|(scope, registry)| (scope.clone(), registry.clone()))
+            .chain([("default".to_string(), config.registry.clone())])
+            .collect();
+        ModulesYaml {
+            layout_version: LAYOUT_VERSION,
+            package_manager: format!("pacquet@{}", env!("CARGO_PKG_VERSION")),
+            node_linker: config.node_linker,
+            store_dir: PathBuf::from(config.store_dir.display().to_string()).join("v3"),
+            virtual_store_dir,
+            hoist_pattern: config.hoist.then(|| config.hoist_pattern.clone()),
+            public_hoist_pattern,
+            registries,
+        }
+    }
+
+    /// Load the `.modules.yaml` of `modules_dir`, if there is one.
+    pub fn load(modules_dir: &Path) -> Result<Option<Self>, ModulesYamlError> {
+        let path = modules_dir.join(ModulesYaml::FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(ModulesYamlError::ReadFile { path, error }),
+        };
+        serde_yaml::from_str(&content)
+            .map(Some)
+            .map_err(|error| ModulesYamlError::ParseYaml { path, error })
+    }
+
+    /// Write this file into `modules_dir`.
+    pub fn save(&self, modules_dir: &Path) -> Result<(), ModulesYamlError> {
+        let path = modules_dir.join(ModulesYaml::FILE_NAME);
+        let content = serde_yaml::to_string(self).expect("serialize .modules.yaml");
+        fs::create_dir_all(modules_dir)
+            .and_then(|()| fs::write(&path, content))
+            .map_err(|error| ModulesYamlError::WriteFile { path, error })
+    }
+
+    /// The settings that differ between this file and `expected` and make the `node_modules`
+    /// that it describes unusable as is.
+    pub fn incompatibilities(&self, expected: &ModulesYaml) -> Vec<&'static str> {
+        let checks = [
+            ("layout-version", self.layout_version == expected.layout_version),
+            ("node-linker", self.node_linker == expected.node_linker),
+            ("store-dir", same_store_dir(&self.store_dir, &expected.store_dir)),
+            ("virtual-store-dir", self.virtual_store_dir == expected.virtual_store_dir),
+            ("hoist-pattern", self.hoist_pattern == expected.hoist_pattern),
+            ("public-hoist-pattern", self.public_hoist_pattern == expected.public_hoist_pattern),
+            ("registry", self.registries == expected.registries),
+        ];
+        checks.into_iter().filter(|(_, same)| !same).map(|(setting, _)| setting).collect()
+    }
+}
+
+/// Whether `a` and `b` are the same store, regardless of trailing separators, `.` components, and
+/// of the `v3` directory that the files written by older versions of pacquet left out.
+fn same_store_dir(a: &Path, b: &Path) -> bool {
+    let normalize = |path: &Path| {
+        let path: PathBuf = path.components().collect();
+        if path.ends_with("v3") {
+            path
+        } else {
+            path.join("v3")
+        }
+    };
+    normalize(a) == normalize(b)
+}
+
+/// This subroutine compares the `.modules.yaml` of [`modules_dir`](Npmrc::modules_dir) with the
+/// settings of `config` before an install.
+///
+/// When `node_modules` was built with incompatible settings, for example another store or
+/// another hoist pattern, it is removed along with the virtual store, so that the install builds
+/// them again from scratch. With a frozen lockfile, nothing is removed and the install fails
+/// instead, unless [`force`](Npmrc::force) is set.
+#[must_use]
+pub struct ValidateModulesDir<'a> {
+    pub config: &'a Npmrc,
+    pub frozen_lockfile: bool,
+}
+
+impl<'a> ValidateModulesDir<'a> {
+    /// Execute the subroutine.
+    ///
+    /// Return whether `node_modules` has been removed.
+    pub fn run(self) -> Result<bool, ModulesYamlError> {
+        let ValidateModulesDir { config, frozen_lockfile } = self;
+        let Some(modules_yaml) = ModulesYaml::load(&config.modules_dir)? else {
+            return Ok(false);
+        };
+        let incompatibilities = modules_yaml.incompatibilities(&ModulesYaml::from_config(config));
+        if incompatibilities.is_empty() {
+            return Ok(false);
+        }
+        if frozen_lockfile && !config.force {
+            return Err(ModulesYamlError::Incompatible {
+                modules_dir: config.modules_dir.clone(),
+                settings: incompatibilities,
+            });
+        }
+
+        tracing::warn!(
+            target: "pacquet::install",
+            modules_dir = ?config.modules_dir,
+            "node_modules was built with different settings ({}), rebuilding it",
+            incompatibilities.join(", "),
+        );
+        for dir in [&config.virtual_store_dir, &config.modules_dir] {
+            match fs::remove_dir_all(dir) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    return Err(ModulesYamlError::RemoveDir { path: dir.clone(), error });
+                }
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    fn config(modules_dir: &Path) -> Npmrc {
+        let mut config = Npmrc::new();
+        config.store_dir = modules_dir.join("../pacquet-store").into();
+        config.set_modules_dir(modules_dir.to_path_buf());
+        config.registry = "https://registry.npmjs.org/".to_string();
+        config
+    }
+
+    #[test]
+    fn should_save_and_load() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let modules_yaml = ModulesYaml::from_config(&config(&modules_dir));
+        assert_eq!(modules_yaml.virtual_store_dir, PathBuf::from(".pnpm"));
+        assert_eq!(modules_yaml.store_dir, modules_dir.join("../pacquet-store/v3"));
+        assert_eq!(
+            modules_yaml.registries,
+            BTreeMap::from([("default".to_string(), "https://registry.npmjs.org/".to_string())])
+        );
+
+        assert_eq!(ModulesYaml::load(&modules_dir).unwrap(), None);
+        modules_yaml.save(&modules_dir).unwrap();
+        assert_eq!(ModulesYaml::load(&modules_dir).unwrap(), Some(modules_yaml));
+    }
+
+    #[test]
+    fn should_read_modules_yaml_of_pnpm() {
+        let dir = tempdir().unwrap();
+        let content = r#"
+            hoistPattern:
+              - '*'
+            hoistedDependencies: {}
+            included:
+              dependencies: true
+            layoutVersion: 5
+            nodeLinker: isolated
+            packageManager: pnpm@8.9.0
+            publicHoistPattern:
+              - '*eslint*'
+            registries:
+              default: https://registry.npmjs.org/
+            skipped: []
+            storeDir: /home/user/.local/share/pnpm/store/v3
+            virtualStoreDir: .pnpm
+        "#;
+        fs::write(dir.path().join(".modules.yaml"), content).unwrap();
+        let modules_yaml = ModulesYaml::load(dir.path()).unwrap().unwrap();
+        assert_eq!(modules_yaml.package_manager, "pnpm@8.9.0");
+        assert_eq!(modules_yaml.hoist_pattern, Some(vec!["*".to_string()]));
+        assert_eq!(modules_yaml.virtual_store_dir, PathBuf::from(".pnpm"));
+    }
+
+    #[test]
+    fn should_keep_modules_dir_of_pnpm_with_the_same_store() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let config = config(&modules_dir);
+        let content = format!(
+            "
+            hoistPattern:
+              - '*'
+            layoutVersion: 5
+            nodeLinker: isolated
+            packageManager: pnpm@8.9.0
+            publicHoistPattern:
+              - '*eslint*'
+              - '*prettier*'
+            registries:
+              default: https://registry.npmjs.org/
+            storeDir: {}/./v3/
+            virtualStoreDir: .pnpm
+            ",
+            config.store_dir.display(),
+        );
+        fs::create_dir_all(&modules_dir).unwrap();
+        fs::write(modules_dir.join(".modules.yaml"), content).unwrap();
+
+        let removed = ValidateModulesDir { config: &config, frozen_lockfile: false }.run().unwrap();
+        assert!(!removed);
+        assert!(modules_dir.join(".modules.yaml").exists());
+    }
+
+    #[test]
+    fn should_compare_store_dirs_regardless_of_their_spelling() {
+        let same = |a: &str, b: &str| same_store_dir(Path::new(a), Path::new(b));
+        assert!(same("/store/v3", "/store/v3"));
+        assert!(same("/store/v3/", "/store/./v3"));
+        assert!(same("/store", "/store/v3"));
+        assert!(!same("/store/v3", "/other-store/v3"));
+    }
+
+    #[test]
+    fn should_list_incompatible_settings() {
+        let dir = tempdir().unwrap();
+        let mut config = config(&dir.path().join("node_modules"));
+        let before = ModulesYaml::from_config(&config);
+        assert_eq!(before.incompatibilities(&ModulesYaml::from_config(&config)), [] as [&str; 0]);
+
+        config.hoist_pattern = vec!["foo".to_string()];
+        config.store_dir = dir.path().join("other-store").into();
+        config.node_linker = NodeLinker::Hoisted;
+        let after = ModulesYaml::from_config(&config);
+        assert_eq!(before.incompatibilities(&after), ["node-linker", "store-dir", "hoist-pattern"]);
+    }
+
+    #[test]
+    fn frozen_lockfile_should_only_rebuild_with_force() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let mut config = config(&modules_dir);
+        ModulesYaml::from_config(&config).save(&modules_dir).unwrap();
+        config.node_linker = NodeLinker::Hoisted;
+        let validate =
+            |config: &Npmrc, frozen_lockfile| ValidateModulesDir { config, frozen_lockfile }.run();
+
+        let error = validate(&config, true).unwrap_err();
+        assert!(matches!(
+            error,
+            ModulesYamlError::Incompatible { settings, .. } if settings == ["node-linker"]
+        ));
+        assert!(modules_dir.join(".modules.yaml").exists());
+
+        config.force = true;
+        assert!(validate(&config, true).unwrap());
+        assert!(!modules_dir.exists());
+    }
+}
@@ -0,0 +1,45 @@
+/// A dependency specifier that installs a package of the registry under another name, such as
+/// `npm:lodash@^4` in `"my-lodash": "npm:lodash@^4"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NpmAlias<'a> {
+    /// Name of the package on the registry.
+    pub name: &'a str,
+    /// Version range of the package, `*` when the specifier has none.
+    pub version_range: &'a str,
+}
+
+impl<'a> NpmAlias<'a> {
+    /// Parse `specifier`, returning `None` if it doesn't start with `npm:`.
+    pub fn parse(specifier: &'a str) -> Option<Self> {
+        let spec = specifier.strip_prefix("npm:")?;
+        // the `@` of a scope is not a separator
+        let (name, version_range) = match spec.get(1..).and_then(|rest| rest.split_once('@')) {
+            Some((rest, version_range)) => (&spec[..rest.len() + 1], version_range),
+            None => (spec, "*"),
+        };
+        Some(NpmAlias { name, version_range })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse() {
+        let case = |specifier: &str, expected: Option<(&str, &str)>| {
+            eprintln!("CASE: {specifier:?}");
+            let expected = expected.map(|(name, version_range)| NpmAlias { name, version_range });
+            assert_eq!(NpmAlias::parse(specifier), expected);
+        };
+
+        case("npm:lodash@^4", Some(("lodash", "^4")));
+        case("npm:lodash@4.17.21", Some(("lodash", "4.17.21")));
+        case("npm:lodash", Some(("lodash", "*")));
+        case("npm:@types/node@^18.0.0", Some(("@types/node", "^18.0.0")));
+        case("npm:@types/node", Some(("@types/node", "*")));
+        case("^4.17.21", None);
+        case("file:lodash.tgz", None);
+    }
+}
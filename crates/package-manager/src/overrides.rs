@@ -0,0 +1,213 @@
+use node_semver::{Range, Version};
+use pacquet_package_manifest::PackageManifest;
+use pacquet_registry::PackageVersion;
+
+/// Version ranges that replace the ones declared by dependents, read from `pnpm.overrides` and
+/// the Yarn-style `resolutions` of `package.json`.
+///
+/// Supported selectors are `foo`, `foo@{range}`, and `{parent}>foo` where the parent may have a
+/// range too. The keys of `resolutions` (`foo`, `**/foo`, `parent/foo`) are converted into these
+/// selectors, and an entry of `pnpm.overrides` wins over a `resolutions` entry with the same
+/// selector.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    rules: Vec<OverrideRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector {
+    name: String,
+    version_range: Option<String>,
+}
+
+#[derive(Debug)]
+struct OverrideRule {
+    parent: Option<Selector>,
+    target: Selector,
+    version_range: String,
+}
+
+impl Overrides {
+    /// Read the overrides of `manifest`.
+    pub fn from_manifest(manifest: &PackageManifest) -> Self {
+        let resolutions = manifest
+            .resolutions()
+            .map(|(key, version_range)| (convert_resolution_key(key), version_range));
+        let overrides =
+            manifest.overrides().map(|(key, version_range)| (key.to_string(), version_range));
+
+        let mut rules: Vec<OverrideRule> = Vec::new();
+        for (key, version_range) in resolutions.chain(overrides) {
+            let (parent, target) = match key.rsplit_once('>') {
+                Some((parent, target)) => (Some(parse_selector(parent)), parse_selector(target)),
+                None => (None, parse_selector(&key)),
+            };
+            let rule = OverrideRule { parent, target, version_range: version_range.to_string() };
+            match rules
+                .iter_mut()
+                .find(|old| old.parent == rule.parent && old.target == rule.target)
+            {
+                Some(old) => *old = rule,
+                None => rules.push(rule),
+            }
+        }
+
+        Overrides { rules }
+    }
+
+    /// Get the version range to install for dependency `name` declared with `version_range` by
+    /// `parent` (`None` for the dependencies of the project).
+    ///
+    /// Rules with a parent selector take precedence over the ones without.
+    pub fn apply<'a>(
+        &'a self,
+        name: &str,
+        version_range: &'a str,
+        parent: Option<&PackageVersion>,
+    ) -> &'a str {
+        let matches_target = |rule: &&OverrideRule| rule.target.matches_range(name, version_range);
+        let matches_parent = |rule: &&OverrideRule| match (&rule.parent, parent) {
+            (Some(selector), Some(parent)) => {
+                selector.matches_version(&parent.name, &parent.version)
+            }
+            _ => false,
+        };
+        self.rules
+            .iter()
+            .filter(matches_target)
+            .find(matches_parent)
+            .or_else(|| self.rules.iter().filter(matches_target).find(|rule| rule.parent.is_none()))
+            .map_or(version_range, |rule| rule.version_range.as_str())
+    }
+}
+
+impl Selector {
+    /// Whether a dependency on `name` declared with `version_range` is selected.
+    ///
+    /// A selector with a range only selects declared ranges that are entirely within it.
+    fn matches_range(&self, name: &str, version_range: &str) -> bool {
+        if self.name != name {
+            return false;
+        }
+        let Some(selector_range) = &self.version_range else { return true };
+        match (selector_range.parse::<Range>(), version_range.parse::<Range>()) {
+            (Ok(selector_range), Ok(range)) => selector_range.allows_all(&range),
+            _ => selector_range == version_range,
+        }
+    }
+
+    /// Whether the package `name@version` is selected.
+    fn matches_version(&self, name: &str, version: &Version) -> bool {
+        self.name == name
+            && self.version_range.as_ref().map_or(true, |version_range| {
+                version_range.parse::<Range>().is_ok_and(|range| range.satisfies(version))
+            })
+    }
+}
+
+/// Parse `foo` or `foo@{range}`, the `@` of a scope is not a separator.
+fn parse_selector(selector: &str) -> Selector {
+    let (name, version_range) = match selector.get(1..).and_then(|rest| rest.split_once('@')) {
+        Some((rest, version_range)) => (&selector[..rest.len() + 1], Some(version_range)),
+        None => (selector, None),
+    };
+    Selector { name: name.to_string(), version_range: version_range.map(str::to_string) }
+}
+
+/// Convert a key of `resolutions` into a selector of `pnpm.overrides`.
+///
+/// `**/foo` selects `foo` everywhere and `a/b/foo` selects `foo` when it is a dependency of `b`.
+/// Only the direct parent is kept, `**` between the names is ignored.
+fn convert_resolution_key(key: &str) -> String {
+    let mut names = Vec::new();
+    let mut segments = key.split('/').filter(|segment| *segment != "**");
+    while let Some(segment) = segments.next() {
+        match segment.starts_with('@').then(|| segments.next()).flatten() {
+            Some(bare_name) => names.push(format!("{segment}/{bare_name}")),
+            None => names.push(segment.to_string()),
+        }
+    }
+
+    match names.as_slice() {
+        [.., parent, target] => format!("{parent}>{target}"),
+        [target] => target.clone(),
+        [] => key.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn manifest(content: &str) -> (tempfile::TempDir, PackageManifest) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("package.json");
+        fs::write(&path, content).unwrap();
+        let manifest = PackageManifest::from_path(path).unwrap();
+        (dir, manifest)
+    }
+
+    fn package(name: &str, version: &str) -> PackageVersion {
+        serde_json::from_value(serde_json::json!({ "name": name, "version": version })).unwrap()
+    }
+
+    #[test]
+    fn convert_resolution_keys() {
+        let case = |key: &str, expected: &str| {
+            eprintln!("CASE: {key:?}");
+            assert_eq!(convert_resolution_key(key), expected);
+        };
+
+        case("foo", "foo");
+        case("**/foo", "foo");
+        case("@scope/foo", "@scope/foo");
+        case("bar/foo", "bar>foo");
+        case("bar/**/foo", "bar>foo");
+        case("a/bar/foo", "bar>foo");
+        case("@scope/bar/foo", "@scope/bar>foo");
+        case("bar/@scope/foo", "bar>@scope/foo");
+    }
+
+    #[test]
+    fn should_apply_resolutions() {
+        let (_dir, manifest) =
+            manifest(r#"{ "resolutions": { "**/foo": "1.2.3", "bar/baz": "^2.0.0" } }"#);
+        let overrides = Overrides::from_manifest(&manifest);
+        let bar = package("bar", "1.0.0");
+        let qux = package("qux", "1.0.0");
+
+        assert_eq!(overrides.apply("foo", "^1.0.0", None), "1.2.3");
+        assert_eq!(overrides.apply("foo", "^1.0.0", Some(&qux)), "1.2.3");
+        assert_eq!(overrides.apply("baz", "^1.0.0", Some(&bar)), "^2.0.0");
+        assert_eq!(overrides.apply("baz", "^1.0.0", Some(&qux)), "^1.0.0");
+        assert_eq!(overrides.apply("other", "^1.0.0", Some(&qux)), "^1.0.0");
+    }
+
+    #[test]
+    fn overrides_should_win_over_resolutions() {
+        let (_dir, manifest) = manifest(
+            r#"{
+                "resolutions": { "foo": "1.0.0", "bar": "1.0.0" },
+                "pnpm": { "overrides": { "foo": "2.0.0" } }
+            }"#,
+        );
+        let overrides = Overrides::from_manifest(&manifest);
+        assert_eq!(overrides.apply("foo", "^1.0.0", None), "2.0.0");
+        assert_eq!(overrides.apply("bar", "^2.0.0", None), "1.0.0");
+    }
+
+    #[test]
+    fn should_match_version_ranges_of_selectors() {
+        let (_dir, manifest) = manifest(
+            r#"{ "pnpm": { "overrides": { "foo@<2": "1.5.0", "bar@^1>baz": "3.0.0" } } }"#,
+        );
+        let overrides = Overrides::from_manifest(&manifest);
+        assert_eq!(overrides.apply("foo", "^1.0.0", None), "1.5.0");
+        assert_eq!(overrides.apply("foo", "^2.0.0", None), "^2.0.0");
+        assert_eq!(overrides.apply("baz", "^1.0.0", Some(&package("bar", "1.2.0"))), "3.0.0");
+        assert_eq!(overrides.apply("baz", "^1.0.0", Some(&package("bar", "2.0.0"))), "^1.0.0");
+    }
+}
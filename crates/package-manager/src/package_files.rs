@@ -0,0 +1,277 @@
+use derive_more::{Display, Error};
+use globset::{GlobBuilder, GlobMatcher};
+use miette::Diagnostic;
+use pipe_trait::Pipe;
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Directories that are never part of a package.
+const IGNORED_DIRS: &[&str] = &["node_modules", ".git", ".svn", ".hg", "CVS"];
+
+/// Files that are never part of a package, wherever they are.
+const IGNORED_FILES: &[&str] = &[
+    ".npmignore",
+    ".gitignore",
+    ".npmrc",
+    ".DS_Store",
+    "npm-debug.log",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+];
+
+/// Error type of [`package_files`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum PackageFilesError {
+    #[display("Failed to list the files of {dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::list_package_files))]
+    ListFiles {
+        dir: PathBuf,
+        #[error(source)]
+        error: walkdir::Error,
+    },
+
+    #[display("Failed to read {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_ignore_file))]
+    ReadIgnoreFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Invalid pattern {pattern:?} in the files of {dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::invalid_files_pattern))]
+    InvalidPattern {
+        dir: PathBuf,
+        pattern: String,
+        #[error(source)]
+        error: globset::Error,
+    },
+}
+
+/// List the files that `npm publish` would put into the package at `dir`, whose `package.json`
+/// is `manifest`.
+///
+/// * `package.json`, the README, the license, and `main` are always included.
+/// * `node_modules`, VCS directories, lockfiles, and ignore files are never included.
+/// * When `manifest` has `files`, only what these patterns match is included.
+/// * Otherwise, everything is included except what the `.npmignore` of `dir` matches, or its
+///   `.gitignore` when there is no `.npmignore`.
+///
+/// Paths are relative to `dir`, separated by `/`, and sorted.
+pub fn package_files(dir: &Path, manifest: &Value) -> Result<Vec<String>, PackageFilesError> {
+    let invalid_pattern = |pattern: &str, error| PackageFilesError::InvalidPattern {
+        dir: dir.to_path_buf(),
+        pattern: pattern.to_string(),
+        error,
+    };
+    let allowlist = match manifest.get("files").and_then(Value::as_array) {
+        Some(files) => files
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|pattern| {
+                Rule::new(pattern, true).map_err(|error| invalid_pattern(pattern, error))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, _>>()?
+            .pipe(Some),
+        None => None,
+    };
+    let ignore_rules = match allowlist {
+        Some(_) => Vec::new(),
+        None => read_ignore_file(dir)?
+            .lines()
+            .map(|pattern| {
+                Rule::new(pattern, false).map_err(|error| invalid_pattern(pattern, error))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let main = manifest
+        .get("main")
+        .and_then(Value::as_str)
+        .map(|main| main.trim_start_matches("./").to_string());
+
+    let mut files = Vec::new();
+    let entries = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !(entry.file_type().is_dir() && is_ignored_dir(entry.file_name())));
+    for entry in entries {
+        let entry = entry
+            .map_err(|error| PackageFilesError::ListFiles { dir: dir.to_path_buf(), error })?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(dir)
+            .expect("entry is inside the directory")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let included = if is_always_included(&relative) || main.as_deref() == Some(&relative) {
+            true
+        } else if IGNORED_FILES.iter().any(|name| entry.file_name() == *name) {
+            false
+        } else if let Some(allowlist) = &allowlist {
+            Rule::last_match(allowlist, &relative).unwrap_or(false)
+        } else {
+            !Rule::last_match(&ignore_rules, &relative).unwrap_or(false)
+        };
+        if included {
+            files.push(relative);
+        }
+    }
+    Ok(files)
+}
+
+fn is_ignored_dir(name: &std::ffi::OsStr) -> bool {
+    IGNORED_DIRS.iter().any(|ignored| name == *ignored)
+}
+
+/// Whether `path` is `package.json`, a README, or a license at the root of the package.
+fn is_always_included(path: &str) -> bool {
+    if path == "package.json" {
+        return true;
+    }
+    if path.contains('/') {
+        return false;
+    }
+    let name = path.to_ascii_uppercase();
+    ["README", "LICENSE", "LICENCE"].iter().any(|prefix| {
+        name.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Content of the `.npmignore` at the root of `dir`, or of its `.gitignore` if there is none.
+fn read_ignore_file(dir: &Path) -> Result<String, PackageFilesError> {
+    for name in [".npmignore", ".gitignore"] {
+        let path = dir.join(name);
+        match fs::read_to_string(&path) {
+            Ok(content) => return Ok(content),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(PackageFilesError::ReadIgnoreFile { path, error }),
+        }
+    }
+    Ok(String::new())
+}
+
+/// A pattern of `files` or of an ignore file.
+struct Rule {
+    matcher: GlobMatcher,
+    negated: bool,
+}
+
+impl Rule {
+    /// Parse a line of an ignore file, or an entry of `files` if `anchored` (which is relative to
+    /// the root even without a `/`). Return `None` for blank lines and comments.
+    fn new(pattern: &str, anchored: bool) -> Result<Option<Self>, globset::Error> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return Ok(None);
+        }
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        let pattern = match pattern.strip_prefix('/') {
+            Some(pattern) => pattern.to_string(),
+            None if anchored || pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{pattern}"),
+        };
+        let matcher = GlobBuilder::new(&pattern).literal_separator(true).build()?.compile_matcher();
+        Ok(Some(Rule { matcher, negated }))
+    }
+
+    /// Whether `path`, or a directory that contains it, matches the pattern.
+    fn matches(&self, path: &str) -> bool {
+        let mut ancestor = path;
+        loop {
+            if self.matcher.is_match(ancestor) {
+                return true;
+            }
+            match ancestor.rsplit_once('/') {
+                Some((parent, _)) => ancestor = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Whether the last rule of `rules` that matches `path` includes it (for `files`) or ignores
+    /// it (for ignore files), `None` if no rule matches.
+    fn last_match(rules: &[Rule], path: &str) -> Option<bool> {
+        rules.iter().rev().find(|rule| rule.matches(path)).map(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn create_files(dir: &Path, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+    }
+
+    const FILES: &[&str] = &[
+        "package.json",
+        "README.md",
+        "LICENSE",
+        "index.js",
+        "lib/a.js",
+        "lib/a.test.js",
+        "src/a.ts",
+        "node_modules/dep/index.js",
+        ".git/HEAD",
+        "pnpm-lock.yaml",
+    ];
+
+    #[test]
+    fn files_should_be_an_allowlist() {
+        let dir = tempdir().unwrap();
+        create_files(dir.path(), FILES);
+        let manifest = json!({ "main": "./index.js", "files": ["lib", "!lib/*.test.js"] });
+        assert_eq!(
+            package_files(dir.path(), &manifest).unwrap(),
+            ["LICENSE", "README.md", "index.js", "lib/a.js", "package.json"],
+        );
+    }
+
+    #[test]
+    fn npmignore_should_exclude_files() {
+        let dir = tempdir().unwrap();
+        create_files(dir.path(), FILES);
+        fs::write(dir.path().join(".npmignore"), "# sources\nsrc/\n*.test.js\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "lib\n").unwrap();
+        assert_eq!(
+            package_files(dir.path(), &json!({})).unwrap(),
+            ["LICENSE", "README.md", "index.js", "lib/a.js", "package.json"],
+        );
+    }
+
+    #[test]
+    fn gitignore_should_be_used_without_npmignore() {
+        let dir = tempdir().unwrap();
+        create_files(dir.path(), FILES);
+        fs::write(dir.path().join(".gitignore"), "/lib\n").unwrap();
+        assert_eq!(
+            package_files(dir.path(), &json!({})).unwrap(),
+            ["LICENSE", "README.md", "index.js", "package.json", "src/a.ts"],
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use pacquet_npmrc::Npmrc;
+use pacquet_registry::PackageVersion;
+
+/// Hook to rewrite the metadata of packages as they are resolved, like the `readPackage` hook of
+/// `.pnpmfile.cjs`.
+///
+/// The hook runs before a package is installed and before its dependencies are resolved, so it
+/// can add or remove dependencies or fix bad metadata.
+pub trait PackageHook: Send + Sync {
+    /// Modify `package` in place.
+    fn read_package(&self, package: &mut PackageVersion);
+}
+
+/// Run `hook` on `package` unless [`ignore_pnpmfile`](Npmrc::ignore_pnpmfile) is set.
+///
+/// `package` is the copy of the resolution that is being installed, so the packages resolved
+/// from the same metadata don't see the changes. The name, version, and dist of the package
+/// locate it in the store and the virtual store, so a hook can't change them: such changes are
+/// reverted with a warning.
+pub(crate) fn run_package_hook(
+    hook: Option<&dyn PackageHook>,
+    config: &Npmrc,
+    package: &mut PackageVersion,
+) {
+    let Some(hook) = hook.filter(|_| !config.ignore_pnpmfile) else { return };
+    let mut rewritten = package.clone();
+    hook.read_package(&mut rewritten);
+
+    let identity_changed = rewritten.name != package.name
+        || rewritten.version != package.version
+        || rewritten.dist.tarball != package.dist.tarball
+        || rewritten.dist.integrity != package.dist.integrity;
+    if identity_changed {
+        tracing::warn!(
+            target: "pacquet::hooks",
+            name = ?package.name,
+            version = %package.version,
+            "The package hook can't change the name, version, or dist of a package",
+        );
+    }
+    *package = PackageVersion {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        dist: package.dist.clone(),
+        ..rewritten
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    struct Rewrite;
+    impl PackageHook for Rewrite {
+        fn read_package(&self, package: &mut PackageVersion) {
+            package.name = "other".to_string();
+            package.version = "2.0.0".parse().unwrap();
+            package.dependencies = Some(HashMap::from([("bar".to_string(), "^1.0.0".to_string())]));
+        }
+    }
+
+    fn package() -> PackageVersion {
+        serde_json::from_str(r#"{ "name": "foo", "version": "1.0.0" }"#).unwrap()
+    }
+
+    #[test]
+    fn should_only_rewrite_metadata() {
+        let mut package = package();
+        run_package_hook(Some(&Rewrite), &Npmrc::new(), &mut package);
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.version.to_string(), "1.0.0");
+        assert_eq!(
+            package.dependencies,
+            Some(HashMap::from([("bar".to_string(), "^1.0.0".to_string())]))
+        );
+    }
+
+    #[test]
+    fn should_not_run_with_ignore_pnpmfile() {
+        let mut package = package();
+        let config = Npmrc::builder().ignore_pnpmfile(true).build();
+        run_package_hook(Some(&Rewrite), &config, &mut package);
+        assert_eq!(package.dependencies, None);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    install_report::InstallReportCollector, Add, AddError, Install, InstallError, InstallPlan,
+    InstallReport, PackageHook, ResolvedPackages, TarballUrlCache,
+};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_lockfile::Lockfile;
+use pacquet_network::{AuthHeaders, ThrottledClient};
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::{DependencyGroup, PackageManifest, PackageManifestError};
+use pacquet_reporter::{DefaultReporter, Reporter};
+use pacquet_tarball::MemCache;
+use pacquet_workspace::WorkspaceManifest;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Create an HTTP client that sends the credentials from `config` to the matching registries,
+/// and the `user-agent` of `config` if there is one.
+pub fn create_http_client(config: &Npmrc) -> ThrottledClient {
+    let always_auth = config.auth_tokens.keys().filter(|_| config.always_auth);
+    let auth_headers = AuthHeaders::from_tokens(&config.auth_tokens)
+        .with_always_auth(always_auth.chain(&config.always_auth_registries));
+    let http_client = ThrottledClient::new_from_cpu_count().with_auth_headers(auth_headers);
+    let Some(user_agent) = &config.user_agent else { return http_client };
+    match user_agent.parse() {
+        Ok(user_agent) => http_client.with_user_agent(user_agent),
+        Err(error) => {
+            tracing::warn!(target: "pacquet::network", ?user_agent, %error, "Ignore invalid user-agent");
+            http_client
+        }
+    }
+}
+
+/// Entry point for using pacquet as a library.
+///
+/// It owns the state that the subroutines of an operation share (HTTP client, caches, manifest,
+/// and lockfile) and exposes the operations of the CLI as methods.
+pub struct PackageManager {
+    pub config: &'static Npmrc,
+    pub reporter: Box<dyn Reporter>,
+    pub http_client: ThrottledClient,
+    pub tarball_mem_cache: MemCache,
+    pub resolved_packages: ResolvedPackages,
+    pub tarball_url_cache: TarballUrlCache,
+    pub manifest: PackageManifest,
+    pub workspace_manifest: Option<WorkspaceManifest>,
+    /// Root directory of the workspace of [`workspace_manifest`](Self::workspace_manifest).
+    pub workspace_dir: Option<PathBuf>,
+    pub lockfile: Option<Lockfile>,
+    pub package_hook: Option<Box<dyn PackageHook>>,
+    /// How many levels of transitive dependencies [`install`](Self::install) installs, `None`
+    /// for the whole tree.
+    pub depth: Option<usize>,
+    /// Whether [`install`](Self::install) removes the packages that are no longer depended on
+    /// from `node_modules`, true by default.
+    pub prune: bool,
+}
+
+/// Error type of [`PackageManager::remove`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum RemoveError {
+    #[display("Cannot remove {name:?}: no such dependency found in package.json")]
+    #[diagnostic(code(pacquet_package_manager::dependency_not_found))]
+    NotFound { name: String },
+
+    #[display("Failed to remove {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::remove_symlink))]
+    RemoveSymlink {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed save the manifest file: {_0}")]
+    #[diagnostic(code(pacquet_package_manager::save_manifest))]
+    SaveManifest(#[error(source)] PackageManifestError),
+}
+
+impl PackageManager {
+    /// Create a package manager for the project of `manifest`, without a workspace or a lockfile.
+    pub fn new(config: &'static Npmrc, manifest: PackageManifest) -> Self {
+        PackageManager {
+            config,
+            reporter: Box::new(DefaultReporter),
+            http_client: create_http_client(config),
+            tarball_mem_cache: MemCache::new(),
+            resolved_packages: ResolvedPackages::new(),
+            tarball_url_cache: TarballUrlCache::new(),
+            manifest,
+            workspace_manifest: None,
+            workspace_dir: None,
+            lockfile: None,
+            package_hook: None,
+            depth: None,
+            prune: true,
+        }
+    }
+
+    /// Set where the events of the operations are reported.
+    pub fn with_reporter(mut self, reporter: Box<dyn Reporter>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Set the workspace the project belongs to: its root directory and its
+    /// `pnpm-workspace.yaml`.
+    pub fn with_workspace_manifest(
+        mut self,
+        workspace_dir: PathBuf,
+        workspace_manifest: WorkspaceManifest,
+    ) -> Self {
+        self.workspace_dir = Some(workspace_dir);
+        self.workspace_manifest = Some(workspace_manifest);
+        self
+    }
+
+    /// Set the `pnpm-lock.yaml` of the project.
+    pub fn with_lockfile(mut self, lockfile: Lockfile) -> Self {
+        self.lockfile = Some(lockfile);
+        self
+    }
+
+    /// Set a hook that rewrites the metadata of each package as it is resolved.
+    pub fn with_package_hook(mut self, package_hook: impl PackageHook + 'static) -> Self {
+        self.package_hook = Some(Box::new(package_hook));
+        self
+    }
+
+    /// Limit how many levels of transitive dependencies are installed.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Install the dependencies of `dependency_groups`, like `pacquet install`.
+    ///
+    /// The events are still sent to [`reporter`](Self::reporter), the returned report
+    /// summarizes them.
+    pub async fn install(
+        &self,
+        dependency_groups: impl IntoIterator<Item = DependencyGroup>,
+        frozen_lockfile: bool,
+    ) -> Result<InstallReport, InstallError> {
+        let collector = InstallReportCollector::new(&*self.reporter);
+        self.run_install(&collector, dependency_groups, frozen_lockfile, None).await?;
+        Ok(collector.into_report())
+    }
+
+    /// Resolve the dependencies of `dependency_groups` without installing them, like
+    /// `pacquet install --dry-run`.
+    pub async fn plan_install(
+        &self,
+        dependency_groups: impl IntoIterator<Item = DependencyGroup>,
+        frozen_lockfile: bool,
+    ) -> Result<InstallPlan, InstallError> {
+        let plan = InstallPlan::new();
+        self.run_install(&*self.reporter, dependency_groups, frozen_lockfile, Some(&plan)).await?;
+        Ok(plan)
+    }
+
+    async fn run_install(
+        &self,
+        reporter: &dyn Reporter,
+        dependency_groups: impl IntoIterator<Item = DependencyGroup>,
+        frozen_lockfile: bool,
+        dry_run: Option<&InstallPlan>,
+    ) -> Result<(), InstallError> {
+        Install {
+            tarball_mem_cache: &self.tarball_mem_cache,
+            resolved_packages: &self.resolved_packages,
+            tarball_url_cache: &self.tarball_url_cache,
+            http_client: &self.http_client,
+            config: self.config,
+            reporter,
+            manifest: &self.manifest,
+            workspace_manifest: self.workspace_manifest.as_ref(),
+            workspace_dir: self.workspace_dir.as_deref(),
+            lockfile: self.lockfile.as_ref(),
+            dependency_groups,
+            frozen_lockfile,
+            dry_run,
+            package_hook: self.package_hook.as_deref(),
+            depth: self.depth,
+            prune: self.prune,
+        }
+        .run()
+        .await
+    }
+
+    /// Add `spec` to `dependency_groups` of `package.json` and install it, like `pacquet add`.
+    ///
+    /// `spec` is the name of a package on the registry, a path to a tarball, or a git specifier.
+    /// When `save` is false, the package is installed without modifying `package.json`.
+    pub async fn add(
+        &mut self,
+        spec: &str,
+        dependency_groups: &[DependencyGroup],
+        save_exact: bool,
+        save: bool,
+    ) -> Result<(), AddError> {
+        Add {
+            tarball_mem_cache: &self.tarball_mem_cache,
+            resolved_packages: &self.resolved_packages,
+            tarball_url_cache: &self.tarball_url_cache,
+            http_client: &self.http_client,
+            config: self.config,
+            reporter: &*self.reporter,
+            manifest: &mut self.manifest,
+            workspace_manifest: self.workspace_manifest.as_ref(),
+            workspace_dir: self.workspace_dir.as_deref(),
+            lockfile: self.lockfile.as_ref(),
+            list_dependency_groups: || dependency_groups.iter().copied(),
+            package_name: spec,
+            save_exact,
+            save,
+            package_hook: self.package_hook.as_deref(),
+        }
+        .run()
+        .await
+    }
+
+    /// Remove `name` from `package.json` and unlink it from `node_modules`.
+    ///
+    /// **NOTE:** the package is left in the virtual store.
+    pub fn remove(&mut self, name: &str) -> Result<(), RemoveError> {
+        if self.manifest.remove_dependency(name).is_empty() {
+            return Err(RemoveError::NotFound { name: name.to_string() });
+        }
+
+        let symlink_path = self.config.modules_dir.join(name);
+        remove_symlink(&symlink_path)
+            .map_err(|error| RemoveError::RemoveSymlink { path: symlink_path, error })?;
+
+        self.manifest.save().map_err(RemoveError::SaveManifest)
+    }
+}
+
+/// Remove a symlink or a junction if it exists.
+pub(crate) fn remove_symlink(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(());
+    }
+    fs::remove_file(path).or_else(|_| fs::remove_dir(path))
+}
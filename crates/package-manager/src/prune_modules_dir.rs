@@ -0,0 +1,251 @@
+use crate::{package_manager::remove_symlink, symlink_package::is_link};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use pacquet_reporter::{Event, Reporter};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Error type of [`PruneModulesDir`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum PruneModulesDirError {
+    #[display("Failed to read the directory at {dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_modules_dir))]
+    ReadDir {
+        dir: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to remove the extraneous package at {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::remove_extraneous_package))]
+    Remove {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+}
+
+/// This subroutine removes the packages that an install has left behind in `node_modules` but
+/// that `package.json` no longer depends on.
+///
+/// The entries of the virtual store that are reachable from the symlinks of the direct
+/// dependencies in [`dependency_groups`](Self::dependency_groups) are kept, the other entries are
+/// removed. So are the symlinks in `node_modules` into the virtual store that are neither direct
+/// dependencies nor publicly hoisted packages of a kept entry, and the hoisted symlinks in the
+/// virtual store that are left dangling. The symlinks that point elsewhere weren't created by an
+/// install and are kept.
+#[must_use]
+pub struct PruneModulesDir<'a, DependencyGroupList>
+where
+    DependencyGroupList: IntoIterator<Item = DependencyGroup>,
+{
+    pub config: &'a Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub manifest: &'a PackageManifest,
+    pub dependency_groups: DependencyGroupList,
+}
+
+impl<'a, DependencyGroupList> PruneModulesDir<'a, DependencyGroupList>
+where
+    DependencyGroupList: IntoIterator<Item = DependencyGroup>,
+{
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<(), PruneModulesDirError> {
+        let PruneModulesDir { config, reporter, manifest, dependency_groups } = self;
+        let direct_dependencies: HashSet<&str> =
+            manifest.dependencies(dependency_groups).map(|(name, _)| name).collect();
+        let Ok(virtual_store_dir) = fs::canonicalize(&config.virtual_store_dir) else {
+            return Ok(()); // nothing has been installed
+        };
+        // name of the virtual store entry that `path` resolves into, if any, even a removed one
+        let entry_of = |path: &Path| -> Option<String> {
+            let target = resolve_path(path)?;
+            let relative = target.strip_prefix(&virtual_store_dir).ok()?;
+            relative.iter().next()?.to_str().map(str::to_string)
+        };
+
+        let top_level = list_packages(&config.modules_dir)?;
+        let mut pending: Vec<String> = top_level
+            .iter()
+            .filter(|(name, _)| direct_dependencies.contains(name.as_str()))
+            .filter_map(|(_, path)| entry_of(path))
+            .collect();
+        let mut reachable = HashSet::new();
+        while let Some(entry) = pending.pop() {
+            if reachable.contains(&entry) {
+                continue;
+            }
+            let dependencies =
+                list_packages(&config.virtual_store_dir.join(&entry).join("node_modules"))?;
+            pending.extend(dependencies.iter().filter_map(|(_, path)| entry_of(path)));
+            reachable.insert(entry);
+        }
+
+        for (name, path) in &top_level {
+            let extraneous = !direct_dependencies.contains(name.as_str())
+                && is_link(path)
+                && entry_of(path).is_some_and(|entry| !reachable.contains(&entry));
+            if extraneous {
+                tracing::info!(target: "pacquet::prune", ?path, "Remove extraneous symlink");
+                remove_symlink(path)
+                    .map_err(|error| PruneModulesDirError::Remove { path: path.clone(), error })?;
+            }
+        }
+
+        for entry in read_dir_names(&config.virtual_store_dir)? {
+            if entry.starts_with('.') || entry == "node_modules" || reachable.contains(&entry) {
+                continue;
+            }
+            let path = config.virtual_store_dir.join(&entry);
+            if !path.is_dir() {
+                continue; // such as `lock.yaml` of pnpm
+            }
+            tracing::info!(target: "pacquet::prune", ?path, "Remove extraneous package");
+            fs::remove_dir_all(&path)
+                .map_err(|error| PruneModulesDirError::Remove { path: path.clone(), error })?;
+            reporter.emit(Event::PackageRemoved { path });
+        }
+
+        for (_, path) in list_packages(&config.virtual_store_dir.join("node_modules"))? {
+            if is_link(&path) && !path.exists() {
+                tracing::debug!(target: "pacquet::prune", ?path, "Remove dangling hoisted symlink");
+                remove_symlink(&path)
+                    .map_err(|error| PruneModulesDirError::Remove { path: path.clone(), error })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Canonical path that `path` resolves to. When `path` is a dangling symlink, its target is
+/// resolved from the closest ancestor of the target that still exists.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(resolved) = fs::canonicalize(path) {
+        return Some(resolved);
+    }
+    let target = path.parent()?.join(fs::read_link(path).ok()?);
+    let mut missing = Vec::new();
+    let mut ancestor = target.as_path();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(ancestor) {
+            return Some(missing.into_iter().rev().fold(resolved, |path, name| path.join(name)));
+        }
+        missing.push(ancestor.file_name()?);
+        ancestor = ancestor.parent()?;
+    }
+}
+
+/// Names of the entries of `dir`, or nothing if `dir` doesn't exist.
+fn read_dir_names(dir: &Path) -> Result<Vec<String>, PruneModulesDirError> {
+    let read_dir_error = |error| PruneModulesDirError::ReadDir { dir: dir.to_path_buf(), error };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(read_dir_error(error)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(read_dir_error)?;
+        names.extend(entry.file_name().into_string().ok());
+    }
+    Ok(names)
+}
+
+/// The packages of the `node_modules` directory `dir` by their names, scoped packages included.
+/// The entries whose names start with a dot are skipped.
+fn list_packages(dir: &Path) -> Result<Vec<(String, PathBuf)>, PruneModulesDirError> {
+    let mut packages = Vec::new();
+    for name in read_dir_names(dir)? {
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = dir.join(&name);
+        if !name.starts_with('@') {
+            packages.push((name, path));
+            continue;
+        }
+        for unscoped in read_dir_names(&path)? {
+            packages.push((format!("{name}/{unscoped}"), path.join(unscoped)));
+        }
+    }
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symlink_package;
+    use pacquet_reporter::SilentReporter;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_remove_unreachable_packages() {
+        let dir = tempdir().unwrap();
+        let mut config = Npmrc::new();
+        config.set_modules_dir(dir.path().join("node_modules"));
+        let virtual_store_dir = config.virtual_store_dir.clone();
+        let package_dir = |entry: &str, name: &str| {
+            let path = virtual_store_dir.join(entry).join("node_modules").join(name);
+            fs::create_dir_all(&path).unwrap();
+            path
+        };
+        let link = |target: &Path, path: PathBuf| symlink_package(target, &path).unwrap();
+
+        // foo -> @scope/bar; old -> baz
+        let foo = package_dir("foo@1.0.0", "foo");
+        let bar = package_dir("@scope+bar@1.0.0", "@scope/bar");
+        let old = package_dir("old@1.0.0", "old");
+        let baz = package_dir("baz@1.0.0", "baz");
+        link(&bar, virtual_store_dir.join("foo@1.0.0/node_modules/@scope/bar"));
+        link(&baz, virtual_store_dir.join("old@1.0.0/node_modules/baz"));
+        link(&foo, config.modules_dir.join("foo"));
+        link(&old, config.modules_dir.join("old"));
+        link(&bar, config.modules_dir.join("@scope/bar")); // publicly hoisted
+        link(&baz, config.modules_dir.join("baz")); // publicly hoisted
+        link(&bar, virtual_store_dir.join("node_modules/@scope/bar"));
+        link(&baz, virtual_store_dir.join("node_modules/baz"));
+        fs::write(virtual_store_dir.join("lock.yaml"), "").unwrap();
+        // a symlink of the user, and one into an entry that is already gone
+        let linked = dir.path().join("linked");
+        fs::create_dir_all(&linked).unwrap();
+        link(&linked, config.modules_dir.join("linked"));
+        let gone = package_dir("gone@1.0.0", "gone");
+        link(&gone, config.modules_dir.join("gone"));
+        fs::remove_dir_all(virtual_store_dir.join("gone@1.0.0")).unwrap();
+
+        let mut manifest = PackageManifest::create_if_needed(dir.path().join("package.json"))
+            .expect("create package.json");
+        manifest.add_dependency("foo", "^1.0.0", DependencyGroup::Prod).unwrap();
+        PruneModulesDir {
+            config: &config,
+            reporter: &SilentReporter,
+            manifest: &manifest,
+            dependency_groups: [DependencyGroup::Prod],
+        }
+        .run()
+        .unwrap();
+
+        let mut entries = read_dir_names(&virtual_store_dir).unwrap();
+        entries.sort();
+        assert_eq!(entries, ["@scope+bar@1.0.0", "foo@1.0.0", "lock.yaml", "node_modules"]);
+        let mut top_level: Vec<_> =
+            list_packages(&config.modules_dir).unwrap().into_iter().map(|(name, _)| name).collect();
+        top_level.sort();
+        assert_eq!(top_level, ["@scope/bar", "foo", "linked"]);
+        assert!(linked.is_dir());
+        let hoisted: Vec<_> = list_packages(&virtual_store_dir.join("node_modules"))
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(hoisted, ["@scope/bar"]);
+    }
+}
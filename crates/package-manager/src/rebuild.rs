@@ -0,0 +1,387 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_executor::{execute_script_with_output, ExecutorError};
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manifest::PackageManifest;
+use pacquet_reporter::{Event, Reporter, ScriptStream};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Instant,
+};
+
+/// Lifecycle scripts that build a package, in the order that they run.
+const BUILD_SCRIPTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+
+/// This subroutine does everything `pacquet rebuild` is supposed to do.
+///
+/// The build scripts (`preinstall`, `install`, and `postinstall`) of the packages in the virtual
+/// store are run again, the dependencies of a package before the package itself. A package with a
+/// `binding.gyp` but without an `install` or `preinstall` script runs `node-gyp rebuild`, like npm.
+///
+/// Up to [`child_concurrency`](Npmrc::child_concurrency) packages are built at the same time.
+///
+/// The allowlist of `package.json` is honored: when `pnpm.onlyBuiltDependencies` is set only the
+/// packages that it lists are built, and the packages of `pnpm.neverBuiltDependencies` never are.
+///
+/// The output of every script is reported line by line as [`Event::ScriptOutput`], and its exit
+/// code and duration as [`Event::Script`].
+#[must_use]
+pub struct Rebuild<'a> {
+    pub config: &'static Npmrc,
+    pub manifest: &'a PackageManifest,
+    /// Names of the packages to rebuild. Every package with build scripts is rebuilt when empty.
+    pub packages: &'a [String],
+    pub reporter: &'a dyn Reporter,
+}
+
+/// Error type of [`Rebuild`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum RebuildError {
+    #[display("Failed to read the virtual store at {path:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::read_virtual_store))]
+    ReadVirtualStore {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("{name} is not installed")]
+    #[diagnostic(
+        code(pacquet_package_manager::package_not_installed),
+        help("Run `pacquet install` first")
+    )]
+    NotInstalled { name: String },
+
+    #[display("The {script} script of {name} failed")]
+    #[diagnostic(code(pacquet_package_manager::build_script))]
+    BuildScript {
+        name: String,
+        script: &'static str,
+        #[error(source)]
+        error: ExecutorError,
+    },
+}
+
+/// Package found in the virtual store.
+#[derive(Debug)]
+struct InstalledPackage {
+    name: String,
+    dir: PathBuf,
+    manifest: Value,
+    /// Virtual store names of the dependencies.
+    dependencies: Vec<String>,
+}
+
+impl InstalledPackage {
+    /// Commands of the build scripts in the order that they run.
+    fn build_scripts(&self) -> Vec<(&'static str, &str)> {
+        let script = |name: &str| self.manifest.get("scripts")?.get(name)?.as_str();
+        let mut scripts: Vec<_> = BUILD_SCRIPTS
+            .into_iter()
+            .filter_map(|name| script(name).map(|command| (name, command)))
+            .collect();
+        let has_install = scripts.iter().any(|(name, _)| matches!(*name, "preinstall" | "install"));
+        if !has_install && self.dir.join("binding.gyp").is_file() {
+            let index = scripts.iter().take_while(|(name, _)| *name == "preinstall").count();
+            scripts.insert(index, ("install", "node-gyp rebuild"));
+        }
+        scripts
+    }
+}
+
+impl<'a> Rebuild<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<(), RebuildError> {
+        let Rebuild { config, manifest, packages, reporter } = self;
+
+        let installed = read_virtual_store(&config.virtual_store_dir)?;
+        if let Some(name) =
+            packages.iter().find(|name| !installed.values().any(|package| &package.name == *name))
+        {
+            return Err(RebuildError::NotInstalled { name: name.clone() });
+        }
+
+        let only_built: Option<HashSet<&str>> =
+            manifest.only_built_dependencies().map(Iterator::collect);
+        let never_built: HashSet<&str> = manifest.never_built_dependencies().collect();
+        let is_allowed = |name: &str| {
+            only_built.as_ref().map_or(true, |only_built| only_built.contains(name))
+                && !never_built.contains(name)
+        };
+        let is_requested =
+            |name: &str| packages.is_empty() || packages.iter().any(|package| package == name);
+
+        let project_dir = manifest.dir();
+        // the scripts run in the directories of the packages
+        let project_dir =
+            fs::canonicalize(project_dir).unwrap_or_else(|_| project_dir.to_path_buf());
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for virtual_store_name in installed.keys() {
+            visit(virtual_store_name, &installed, &mut visited, &mut order);
+        }
+
+        let mut builds: Vec<Build> = Vec::new();
+        for virtual_store_name in order {
+            let package = &installed[virtual_store_name];
+            let name = package.name.as_str();
+            if !is_requested(name) {
+                continue;
+            }
+            let scripts = package.build_scripts();
+            if scripts.is_empty() {
+                continue;
+            }
+            if !is_allowed(name) {
+                tracing::warn!(target: "pacquet::rebuild", ?name, "Skip the build scripts of a package that isn't allowed to run them");
+                continue;
+            }
+
+            // packages earlier in the order that this one depends on, directly or not
+            let mut dependencies = HashSet::new();
+            let mut dependency_order = Vec::new();
+            visit(virtual_store_name, &installed, &mut dependencies, &mut dependency_order);
+            let waits_for = builds
+                .iter()
+                .enumerate()
+                .filter(|(_, build)| dependencies.contains(build.virtual_store_name))
+                .map(|(index, _)| index)
+                .collect();
+            builds.push(Build { virtual_store_name, package, scripts, waits_for });
+        }
+
+        run_builds(&builds, config.child_concurrency.max(1), &project_dir, reporter)
+    }
+}
+
+/// Build scripts of a package to run.
+struct Build<'a> {
+    virtual_store_name: &'a str,
+    package: &'a InstalledPackage,
+    scripts: Vec<(&'static str, &'a str)>,
+    /// Indices of the builds that must complete before this one starts.
+    waits_for: Vec<usize>,
+}
+
+impl<'a> Build<'a> {
+    fn run(&self, project_dir: &Path, reporter: &dyn Reporter) -> Result<(), RebuildError> {
+        let Build { virtual_store_name, package, scripts, .. } = self;
+        let name = package.name.as_str();
+        let version = package.manifest.get("version").and_then(Value::as_str).unwrap_or("");
+        let package_id = format!("{name}@{version}");
+        for &(script, command) in scripts {
+            tracing::info!(target: "pacquet::rebuild", ?virtual_store_name, ?script, "Run build script");
+            let envs = [
+                ("npm_lifecycle_event", OsStr::new(script)),
+                ("npm_package_name", OsStr::new(name)),
+                ("npm_package_version", OsStr::new(version)),
+                ("INIT_CWD", project_dir.as_os_str()),
+            ];
+            let output = |stream: ScriptStream| {
+                let package_id = &package_id;
+                move |line: &str| {
+                    tracing::info!(target: "pacquet::script", "{package_id} {script}: {line}");
+                    reporter.emit(Event::ScriptOutput {
+                        package: package_id.clone(),
+                        name: script.to_string(),
+                        stream,
+                        chunk: line.to_string(),
+                    });
+                }
+            };
+            let start = Instant::now();
+            let result = execute_script_with_output(
+                command,
+                &package.dir,
+                envs,
+                output(ScriptStream::Stdout),
+                output(ScriptStream::Stderr),
+            );
+            let (exit_code, error) = match &result {
+                Ok(()) => (Some(0), None),
+                Err(ExecutorError::ExitStatus(status)) => (status.code(), None),
+                Err(error) => (None, Some(error.to_string())),
+            };
+            reporter.emit(Event::Script {
+                package: package_id.clone(),
+                name: script.to_string(),
+                exit_code,
+                duration_ms: start.elapsed().as_millis() as u64,
+                error,
+            });
+            result.map_err(|error| RebuildError::BuildScript {
+                name: name.to_string(),
+                script,
+                error,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Run up to `concurrency` builds at the same time, each after the builds that it waits for.
+///
+/// No build starts after one has failed, the running ones are waited for and the first error is
+/// returned.
+fn run_builds(
+    builds: &[Build],
+    concurrency: usize,
+    project_dir: &Path,
+    reporter: &dyn Reporter,
+) -> Result<(), RebuildError> {
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        let mut started = vec![false; builds.len()];
+        let mut done = vec![false; builds.len()];
+        let mut running = 0;
+        let mut error = None;
+        loop {
+            for (index, build) in builds.iter().enumerate() {
+                if error.is_some() || running >= concurrency {
+                    break;
+                }
+                if started[index] || !build.waits_for.iter().all(|&index| done[index]) {
+                    continue;
+                }
+                started[index] = true;
+                running += 1;
+                let sender = sender.clone();
+                scope.spawn(move || sender.send((index, build.run(project_dir, reporter))));
+            }
+            if running == 0 {
+                break;
+            }
+            let (index, result) = receiver.recv().expect("a build is running");
+            running -= 1;
+            done[index] = true;
+            if let Err(build_error) = result {
+                error.get_or_insert(build_error);
+            }
+        }
+        error.map_or(Ok(()), Err)
+    })
+}
+
+/// Add `virtual_store_name` to `order` after its dependencies.
+fn visit<'a>(
+    virtual_store_name: &'a str,
+    installed: &'a BTreeMap<String, InstalledPackage>,
+    visited: &mut HashSet<&'a str>,
+    order: &mut Vec<&'a str>,
+) {
+    let Some(package) = installed.get(virtual_store_name) else { return };
+    if !visited.insert(virtual_store_name) {
+        return;
+    }
+    for dependency in &package.dependencies {
+        visit(dependency, installed, visited, order);
+    }
+    order.push(virtual_store_name);
+}
+
+/// List the packages of the virtual store, keyed by their virtual store names.
+fn read_virtual_store(
+    virtual_store_dir: &Path,
+) -> Result<BTreeMap<String, InstalledPackage>, RebuildError> {
+    let read_dir = |path: &Path| {
+        fs::read_dir(path)
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|error| RebuildError::ReadVirtualStore { path: path.to_path_buf(), error })
+    };
+
+    let mut installed = BTreeMap::new();
+    if !virtual_store_dir.is_dir() {
+        return Ok(installed);
+    }
+    for entry in read_dir(virtual_store_dir)? {
+        let Ok(virtual_store_name) = entry.file_name().into_string() else { continue };
+        let Some(name) = package_name(&virtual_store_name) else { continue };
+        let node_modules_dir = entry.path().join("node_modules");
+        let dir = node_modules_dir.join(&name);
+        let Some(manifest) = fs::read_to_string(dir.join("package.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        else {
+            continue;
+        };
+
+        let mut links = Vec::new();
+        for entry in read_dir(&node_modules_dir)? {
+            if entry.file_name().to_string_lossy().starts_with('@') && entry.path().is_dir() {
+                links.extend(read_dir(&entry.path())?.into_iter().map(|entry| entry.path()));
+            } else {
+                links.push(entry.path());
+            }
+        }
+        let dependencies = links
+            .into_iter()
+            .filter(|link| link != &dir)
+            .filter_map(|link| fs::read_link(link).ok())
+            .filter_map(|target| {
+                let relative = target.strip_prefix(virtual_store_dir).ok()?;
+                relative.components().next()?.as_os_str().to_str().map(str::to_string)
+            })
+            .filter(|dependency| dependency != &virtual_store_name)
+            .collect();
+
+        let package = InstalledPackage { name, dir, manifest, dependencies };
+        installed.insert(virtual_store_name, package);
+    }
+
+    Ok(installed)
+}
+
+/// Extract the name of the package from its virtual store name,
+/// e.g. `@pnpm.e2e+dep-1@1.0.0` → `@pnpm.e2e/dep-1`.
+fn package_name(virtual_store_name: &str) -> Option<String> {
+    let index = virtual_store_name.get(1..)?.find('@')? + 1;
+    let name = &virtual_store_name[..index];
+    if name.starts_with('@') {
+        Some(name.replacen('+', "/", 1))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn package_name_from_virtual_store_name() {
+        assert_eq!(package_name("foo@1.0.0").as_deref(), Some("foo"));
+        assert_eq!(package_name("@pnpm.e2e+dep-1@1.0.0").as_deref(), Some("@pnpm.e2e/dep-1"));
+        assert_eq!(package_name("react-dom@17.0.2(react@17.0.2)").as_deref(), Some("react-dom"));
+        assert_eq!(package_name("node_modules"), None);
+        assert_eq!(package_name("lock.yaml"), None);
+    }
+
+    #[test]
+    fn binding_gyp_should_imply_node_gyp_rebuild() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("binding.gyp"), "{}").unwrap();
+        let package = |manifest: Value| InstalledPackage {
+            name: "foo".to_string(),
+            dir: dir.path().to_path_buf(),
+            manifest,
+            dependencies: Vec::new(),
+        };
+
+        let scripts = serde_json::json!({ "scripts": { "postinstall": "echo done" } });
+        assert_eq!(
+            package(scripts).build_scripts(),
+            [("install", "node-gyp rebuild"), ("postinstall", "echo done")],
+        );
+
+        let scripts = serde_json::json!({ "scripts": { "install": "make" } });
+        assert_eq!(package(scripts).build_scripts(), [("install", "make")]);
+    }
+}
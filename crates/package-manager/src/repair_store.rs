@@ -0,0 +1,122 @@
+use crate::FetchPackageBySnapshot;
+use derive_more::{Display, Error};
+use futures_util::future;
+use miette::Diagnostic;
+use pacquet_lockfile::{DependencyPath, PackageSnapshot};
+use pacquet_network::ThrottledClient;
+use pacquet_npmrc::Npmrc;
+use pacquet_reporter::Reporter;
+use pacquet_store_dir::{CorruptFile, VerifyStoreError};
+use pipe_trait::Pipe;
+use ssri::Integrity;
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+/// This subroutine does everything `pacquet store verify --repair` is supposed to do.
+///
+/// Every corrupt file in the store is removed, then the tarballs that the removed files were
+/// extracted from are fetched again to restore them. A tarball can only be fetched when a snapshot
+/// in the lockfile resolves to its integrity, the index files of the other tarballs are removed so
+/// that the next install fetches them again.
+#[must_use]
+pub struct RepairStore<'a> {
+    pub http_client: &'a ThrottledClient,
+    pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
+    pub packages: Option<&'a HashMap<DependencyPath, PackageSnapshot>>,
+}
+
+/// Result of [`RepairStore`].
+#[derive(Debug, Default)]
+pub struct StoreRepair {
+    /// Corrupt files whose content was restored.
+    pub repaired: Vec<PathBuf>,
+    /// Corrupt files that were removed without being restored.
+    pub removed: Vec<PathBuf>,
+}
+
+/// Error type of [`RepairStore`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum RepairStoreError {
+    #[diagnostic(transparent)]
+    VerifyStore(#[error(source)] VerifyStoreError),
+
+    #[display("Failed to remove {path:?} from the store: {error}")]
+    #[diagnostic(code(pacquet_package_manager::remove_store_file))]
+    RemoveFile {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+}
+
+impl<'a> RepairStore<'a> {
+    /// Execute the subroutine.
+    pub async fn run(self) -> Result<StoreRepair, RepairStoreError> {
+        let RepairStore { http_client, config, reporter, packages } = self;
+        let store_dir = &config.store_dir;
+
+        let corrupt_files = store_dir.verify().map_err(RepairStoreError::VerifyStore)?;
+        let remove_file = |path: PathBuf| match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(RepairStoreError::RemoveFile { path, error }),
+        };
+
+        let mut affected = Vec::<&Integrity>::new();
+        for CorruptFile { path, packages } in &corrupt_files {
+            tracing::warn!(target: "pacquet::store", ?path, "Remove corrupt file");
+            remove_file(path.clone())?;
+            for integrity in packages {
+                if !affected.contains(&integrity) {
+                    affected.push(integrity);
+                }
+            }
+        }
+
+        let snapshot_of = |integrity: &Integrity| {
+            packages.into_iter().flatten().find(|(_, package_snapshot)| {
+                package_snapshot.resolution.integrity() == Some(integrity)
+            })
+        };
+        let fetched: Vec<&Integrity> = affected
+            .iter()
+            .map(|&integrity| async move {
+                let (dependency_path, package_snapshot) = snapshot_of(integrity)?;
+                let fetch = FetchPackageBySnapshot {
+                    http_client,
+                    config,
+                    reporter,
+                    dependency_path,
+                    package_snapshot,
+                };
+                match fetch.run().await {
+                    Ok(_) => Some(integrity),
+                    Err(error) => {
+                        tracing::warn!(target: "pacquet::store", ?dependency_path, "{error}");
+                        None
+                    }
+                }
+            })
+            .pipe(future::join_all)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+
+        for integrity in affected.into_iter().filter(|integrity| !fetched.contains(integrity)) {
+            tracing::warn!(target: "pacquet::store", %integrity, "Remove the index file of a tarball that can't be fetched");
+            remove_file(store_dir.index_file_path(integrity))?;
+        }
+
+        let mut repair = StoreRepair::default();
+        for CorruptFile { path, .. } in corrupt_files {
+            if path.is_file() {
+                repair.repaired.push(path);
+            } else {
+                repair.removed.push(path);
+            }
+        }
+        Ok(repair)
+    }
+}
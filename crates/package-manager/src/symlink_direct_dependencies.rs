@@ -1,7 +1,8 @@
-use crate::symlink_package;
-use pacquet_lockfile::{PkgName, PkgNameVerPeer, RootProjectSnapshot};
+use crate::{symlink_package, SymlinkPackageError};
+use pacquet_lockfile::RootProjectSnapshot;
 use pacquet_npmrc::Npmrc;
 use pacquet_package_manifest::DependencyGroup;
+use pacquet_reporter::{Event, Reporter};
 use rayon::prelude::*;
 
 /// This subroutine creates symbolic links in the `node_modules` directory for
@@ -16,6 +17,7 @@ where
     DependencyGroupList: IntoIterator<Item = DependencyGroup>,
 {
     pub config: &'static Npmrc,
+    pub reporter: &'a dyn Reporter,
     pub project_snapshot: &'a RootProjectSnapshot,
     pub dependency_groups: DependencyGroupList,
 }
@@ -25,33 +27,52 @@ where
     DependencyGroupList: IntoIterator<Item = DependencyGroup>,
 {
     /// Execute the subroutine.
-    pub fn run(self) {
-        let SymlinkDirectDependencies { config, project_snapshot, dependency_groups } = self;
+    pub fn run(self) -> Result<(), SymlinkPackageError> {
+        let SymlinkDirectDependencies { config, reporter, project_snapshot, dependency_groups } =
+            self;
 
         let RootProjectSnapshot::Single(project_snapshot) = project_snapshot else {
             panic!("Monorepo is not yet supported"); // TODO: properly propagate this error
         };
 
-        project_snapshot
-            .dependencies_by_groups(dependency_groups)
-            .collect::<Vec<_>>()
+        // A name that is declared in several groups is linked according to the first group. The
+        // links are created in parallel, but errors and events follow the order of the names so
+        // that two installs of the same lockfile behave the same.
+        let mut dependencies = Vec::new();
+        for (name, spec) in project_snapshot.dependencies_by_groups(dependency_groups) {
+            if !dependencies.iter().any(|(existing, _)| existing == &name) {
+                dependencies.push((name, spec));
+            }
+        }
+        dependencies.sort_by_cached_key(|(name, _)| name.to_string());
+
+        let linked = dependencies
             .par_iter()
-            .for_each(|(name, spec)| {
-                // TODO: the code below is not optimal
-                let virtual_store_name =
-                    PkgNameVerPeer::new(PkgName::clone(name), spec.version.clone())
-                        .to_virtual_store_name();
+            .map(|(name, spec)| {
+                // the package of an aliased dependency is stored under its real name
+                let package_specifier = spec.version.to_package_specifier(name);
 
-                let name_str = name.to_string();
+                let symlink_path = config.modules_dir.join(name.to_string());
                 symlink_package(
                     &config
                         .virtual_store_dir
-                        .join(virtual_store_name)
+                        .join(package_specifier.to_virtual_store_name())
                         .join("node_modules")
-                        .join(&name_str),
-                    &config.modules_dir.join(&name_str),
-                )
-                .expect("symlink pkg"); // TODO: properly propagate this error
-            });
+                        .join(package_specifier.name.to_string()),
+                    &symlink_path,
+                )?;
+
+                Ok(Event::PackageLinked {
+                    name: name.to_string(),
+                    version: package_specifier.suffix.version().to_string(),
+                    path: symlink_path,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for event in linked {
+            reporter.emit(event?);
+        }
+        Ok(())
     }
 }
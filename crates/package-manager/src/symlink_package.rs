@@ -5,12 +5,15 @@ use std::{
     fs,
     io::{self, ErrorKind},
     path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 /// Error type for [`symlink_package`].
 #[derive(Debug, Display, Error, Diagnostic)]
 pub enum SymlinkPackageError {
     #[display("Failed to create directory at {dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::create_parent_dir))]
     CreateParentDir {
         dir: PathBuf,
         #[error(source)]
@@ -18,18 +21,31 @@ pub enum SymlinkPackageError {
     },
 
     #[display("Failed to create symlink at {symlink_path:?} to {symlink_target:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::symlink_dir))]
     SymlinkDir {
         symlink_target: PathBuf,
         symlink_path: PathBuf,
         #[error(source)]
         error: io::Error,
     },
+
+    #[display("Failed to create symlink at {symlink_path:?} because a file or directory that isn't a symlink already exists there")]
+    #[diagnostic(
+        code(pacquet_package_manager::symlink_path_occupied),
+        help("Remove {symlink_path:?} and try again")
+    )]
+    PathOccupied { symlink_path: PathBuf },
 }
 
 /// Create symlink for a package.
 ///
 /// * If ancestors of `symlink_path` don't exist, they will be created recursively.
-/// * If `symlink_path` already exists, skip.
+/// * If `symlink_path` already exists as a symlink to `symlink_target`, skip. This is also the
+///   case when another thread creates the same symlink at the same time.
+/// * If `symlink_path` already exists as a symlink to another target (e.g. the directory of an
+///   older version), it is replaced by a symlink to `symlink_target`. When threads race to link
+///   the same path to different targets, all of them succeed and the last one wins.
+/// * If `symlink_path` already exists as anything else, fail with [`SymlinkPackageError::PathOccupied`].
 /// * If `symlink_path` doesn't exist, a symlink pointing to `symlink_target` will be created.
 pub fn symlink_package(
     symlink_target: &Path,
@@ -45,7 +61,16 @@ pub fn symlink_package(
     }
     if let Err(error) = symlink_dir(symlink_target, symlink_path) {
         match error.kind() {
-            ErrorKind::AlreadyExists => {}
+            ErrorKind::AlreadyExists if is_link(symlink_path) => {
+                if !points_to(symlink_path, symlink_target) {
+                    replace_symlink(symlink_target, symlink_path)?;
+                }
+            }
+            ErrorKind::AlreadyExists => {
+                return Err(SymlinkPackageError::PathOccupied {
+                    symlink_path: symlink_path.to_path_buf(),
+                })
+            }
             _ => {
                 return Err(SymlinkPackageError::SymlinkDir {
                     symlink_target: symlink_target.to_path_buf(),
@@ -57,3 +82,112 @@ pub fn symlink_package(
     }
     Ok(())
 }
+
+/// Whether the symlink at `symlink_path` points to `symlink_target`.
+fn points_to(symlink_path: &Path, symlink_target: &Path) -> bool {
+    if fs::read_link(symlink_path).is_ok_and(|target| target == symlink_target) {
+        return true;
+    }
+    // junctions report their targets in the verbatim form
+    match (fs::canonicalize(symlink_path), fs::canonicalize(symlink_target)) {
+        (Ok(resolved), Ok(target)) => resolved == target,
+        _ => false,
+    }
+}
+
+/// Replace the symlink at `symlink_path` with one pointing to `symlink_target`.
+///
+/// The new symlink is created next to the old one then renamed over it, so `symlink_path` never
+/// goes missing on Unix. Windows can't rename over a junction, so the old one is removed first.
+fn replace_symlink(symlink_target: &Path, symlink_path: &Path) -> Result<(), SymlinkPackageError> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let symlink_error = |error| SymlinkPackageError::SymlinkDir {
+        symlink_target: symlink_target.to_path_buf(),
+        symlink_path: symlink_path.to_path_buf(),
+        error,
+    };
+    let file_name = symlink_path.file_name().unwrap_or_default().to_string_lossy();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp_path = symlink_path.with_file_name(format!(".{file_name}.{}-{count}", process::id()));
+    tracing::debug!(target: "pacquet::install", ?symlink_path, ?symlink_target, "Replace stale symlink");
+    symlink_dir(symlink_target, &temp_path).map_err(symlink_error)?;
+    #[cfg(windows)]
+    crate::package_manager::remove_symlink(symlink_path).map_err(symlink_error)?;
+    fs::rename(&temp_path, symlink_path).map_err(|error| {
+        let _ = fs::remove_file(&temp_path).or_else(|_| fs::remove_dir(&temp_path));
+        symlink_error(error)
+    })
+}
+
+/// Whether `path` is a symlink or (on Windows) a junction.
+pub(crate) fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_replace_symlink_to_another_target() {
+        let dir = tempdir().unwrap();
+        let old_target = dir.path().join(".pnpm/foo@1.0.0/node_modules/foo");
+        let new_target = dir.path().join(".pnpm/foo@2.0.0/node_modules/foo");
+        fs::create_dir_all(&old_target).unwrap();
+        fs::create_dir_all(&new_target).unwrap();
+        let symlink_path = dir.path().join("foo");
+        symlink_dir(&old_target, &symlink_path).unwrap();
+
+        symlink_package(&new_target, &symlink_path).unwrap();
+        assert_eq!(fs::read_link(&symlink_path).unwrap(), new_target);
+        let mut entries: Vec<_> =
+            fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        entries.sort();
+        assert_eq!(entries, [".pnpm", "foo"], "no temporary symlink should be left behind");
+    }
+
+    #[test]
+    fn should_keep_symlink_to_the_same_target() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join(".pnpm/foo@1.0.0/node_modules/foo");
+        fs::create_dir_all(&target).unwrap();
+        let symlink_path = dir.path().join("foo");
+        symlink_dir(&target, &symlink_path).unwrap();
+        let modified = fs::symlink_metadata(&symlink_path).unwrap().modified().unwrap();
+
+        symlink_package(&target, &symlink_path).unwrap();
+        assert_eq!(fs::read_link(&symlink_path).unwrap(), target);
+        assert_eq!(fs::symlink_metadata(&symlink_path).unwrap().modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn should_tolerate_concurrent_symlinks_to_different_targets() {
+        let dir = tempdir().unwrap();
+        let targets: Vec<_> = (0..16)
+            .map(|index| {
+                let target = dir.path().join(format!(".pnpm/foo@{index}.0.0/node_modules/foo"));
+                fs::create_dir_all(&target).unwrap();
+                target
+            })
+            .collect();
+        let symlink_path = dir.path().join("node_modules/foo");
+
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = targets
+                .iter()
+                .map(|target| scope.spawn(|| symlink_package(target, &symlink_path)))
+                .collect();
+            for thread in threads {
+                thread.join().unwrap().unwrap();
+            }
+        });
+
+        assert!(targets.contains(&fs::read_link(&symlink_path).unwrap()));
+        let entries: Vec<_> = fs::read_dir(dir.path().join("node_modules"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, ["foo"], "no temporary symlink should be left behind");
+    }
+}
@@ -0,0 +1,69 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_lockfile::{DependencyPath, PackageSnapshot};
+use pacquet_store_dir::StoreDir;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// This subroutine does everything `pacquet store status` is supposed to do.
+///
+/// The files that each package of the lockfile imported into the virtual store are compared with
+/// the index file of its tarball. Packages that aren't in the virtual store are skipped.
+#[must_use]
+pub struct VerifyVirtualStore<'a> {
+    pub store_dir: &'a StoreDir,
+    pub virtual_store_dir: &'a Path,
+    pub packages: Option<&'a HashMap<DependencyPath, PackageSnapshot>>,
+}
+
+/// Error type of [`VerifyVirtualStore`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum VerifyVirtualStoreError {
+    #[display("{dependency_path} is in the virtual store but its index file is not in the store")]
+    #[diagnostic(
+        code(pacquet_package_manager::missing_index_file),
+        help("Run `pacquet fetch` to restore the store")
+    )]
+    MissingIndexFile { dependency_path: String },
+}
+
+impl<'a> VerifyVirtualStore<'a> {
+    /// Execute the subroutine.
+    ///
+    /// Return the paths of the imported files that are missing or modified, sorted.
+    pub fn run(self) -> Result<Vec<PathBuf>, VerifyVirtualStoreError> {
+        let VerifyVirtualStore { store_dir, virtual_store_dir, packages } = self;
+
+        let mut modified_files = Vec::new();
+        for (dependency_path, package_snapshot) in packages.into_iter().flatten() {
+            // directory and git dependencies have no tarball to index
+            let Some(integrity) = package_snapshot.resolution.integrity() else { continue };
+
+            let package_specifier = &dependency_path.package_specifier;
+            let package_dir = virtual_store_dir
+                .join(package_specifier.to_virtual_store_name())
+                .join("node_modules")
+                .join(package_specifier.name.to_string());
+            if !package_dir.is_dir() {
+                continue;
+            }
+
+            tracing::info!(target: "pacquet::verify", ?package_dir, "Verify package");
+
+            let index = store_dir.read_index_file(integrity).ok_or_else(|| {
+                VerifyVirtualStoreError::MissingIndexFile {
+                    dependency_path: dependency_path.to_string(),
+                }
+            })?;
+            modified_files.extend(
+                index.modified_files(&package_dir).into_iter().map(|path| package_dir.join(path)),
+            );
+        }
+
+        modified_files.sort();
+        Ok(modified_files)
+    }
+}
@@ -0,0 +1,107 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pacquet_npmrc::SaveWorkspaceProtocol;
+use pacquet_package_manifest::{PackageManifest, PackageManifestError};
+use pacquet_workspace::{FindProjectsError, WorkspaceManifest};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Project of a workspace that other projects depend on by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspacePackage {
+    /// Directory of the project.
+    pub dir: PathBuf,
+    /// The `version` of its `package.json`, empty when there is none.
+    pub version: String,
+}
+
+impl WorkspacePackage {
+    /// The specifier that `pacquet add` saves for this package.
+    pub fn specifier(&self, protocol: SaveWorkspaceProtocol, save_exact: bool) -> String {
+        let WorkspacePackage { version, .. } = self;
+        match (protocol, save_exact) {
+            (SaveWorkspaceProtocol::Rolling, false) => "workspace:^".to_string(),
+            (SaveWorkspaceProtocol::Rolling, true) => "workspace:*".to_string(),
+            (SaveWorkspaceProtocol::True, false) => format!("workspace:^{version}"),
+            (SaveWorkspaceProtocol::True, true) => format!("workspace:{version}"),
+            (SaveWorkspaceProtocol::False, false) => format!("^{version}"),
+            (SaveWorkspaceProtocol::False, true) => version.clone(),
+        }
+    }
+}
+
+/// Error type of [`find_workspace_package`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum FindWorkspacePackageError {
+    #[diagnostic(transparent)]
+    FindProjects(#[error(source)] FindProjectsError),
+
+    #[display("Failed to load the manifest of {project_dir:?}: {error}")]
+    #[diagnostic(code(pacquet_package_manager::load_workspace_project))]
+    LoadManifest {
+        project_dir: PathBuf,
+        #[error(source)]
+        error: PackageManifestError,
+    },
+}
+
+/// Find the project of the workspace at `workspace_dir` whose `package.json` is named `name`.
+pub fn find_workspace_package(
+    workspace_dir: &Path,
+    workspace_manifest: &WorkspaceManifest,
+    name: &str,
+) -> Result<Option<WorkspacePackage>, FindWorkspacePackageError> {
+    let projects = workspace_manifest
+        .find_projects(workspace_dir)
+        .map_err(FindWorkspacePackageError::FindProjects)?;
+    for project_dir in projects {
+        let manifest =
+            PackageManifest::from_path(project_dir.join("package.json")).map_err(|error| {
+                FindWorkspacePackageError::LoadManifest { project_dir: project_dir.clone(), error }
+            })?;
+        let field = |field: &str| manifest.value().get(field).and_then(Value::as_str);
+        if field("name") == Some(name) {
+            let version = field("version").unwrap_or_default().to_string();
+            return Ok(Some(WorkspacePackage { dir: project_dir, version }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_find_project_by_name() {
+        let dir = tempdir().unwrap();
+        let project_dir = dir.path().join("packages/bar");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(dir.path().join("package.json"), r#"{ "name": "root" }"#).unwrap();
+        fs::write(project_dir.join("package.json"), r#"{ "name": "bar", "version": "1.2.3" }"#)
+            .unwrap();
+        let workspace_manifest =
+            WorkspaceManifest { packages: vec!["packages/*".to_string()], ..Default::default() };
+
+        let found = find_workspace_package(dir.path(), &workspace_manifest, "bar").unwrap();
+        assert_eq!(found, Some(WorkspacePackage { dir: project_dir, version: "1.2.3".into() }));
+        let found = find_workspace_package(dir.path(), &workspace_manifest, "baz").unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn specifier_should_follow_save_workspace_protocol() {
+        let package = WorkspacePackage { dir: PathBuf::from("bar"), version: "1.2.3".into() };
+        let specifier = |protocol, save_exact| package.specifier(protocol, save_exact);
+        assert_eq!(specifier(SaveWorkspaceProtocol::Rolling, false), "workspace:^");
+        assert_eq!(specifier(SaveWorkspaceProtocol::Rolling, true), "workspace:*");
+        assert_eq!(specifier(SaveWorkspaceProtocol::True, false), "workspace:^1.2.3");
+        assert_eq!(specifier(SaveWorkspaceProtocol::True, true), "workspace:1.2.3");
+        assert_eq!(specifier(SaveWorkspaceProtocol::False, false), "^1.2.3");
+        assert_eq!(specifier(SaveWorkspaceProtocol::False, true), "1.2.3");
+    }
+}
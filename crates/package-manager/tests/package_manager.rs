@@ -0,0 +1,232 @@
+use pacquet_npmrc::Npmrc;
+use pacquet_package_manager::{InstallReport, PackageManager, RemoveError, Warning};
+use pacquet_package_manifest::{DependencyGroup, PackageManifest};
+use pacquet_reporter::{Event, Reporter};
+use pacquet_tarball::MemCache;
+use pacquet_testing_utils::{
+    fake_registry::{FakePackage, FakeRegistry},
+    fixtures::FASTIFY_ERROR_TARBALL_UNPACKED_SIZE,
+    fs::is_symlink_or_junction,
+};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::{
+    fs,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn should_install_add_and_remove_through_library() {
+    let registry = FakeRegistry::start(&[
+        FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]),
+        FakePackage::new("bar", "1.0.0"),
+        FakePackage::new("baz", "1.0.0"),
+    ]);
+    let dir = tempdir().unwrap();
+    let project_dir = dir.path().join("project");
+    let modules_dir = project_dir.join("node_modules");
+    fs::create_dir_all(&project_dir).unwrap();
+    fs::write(project_dir.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .unwrap();
+
+    let mut config = Npmrc::new();
+    config.store_dir = dir.path().join("pacquet-store").into();
+    config.modules_dir = modules_dir.clone();
+    config.virtual_store_dir = modules_dir.join(".pnpm");
+    config.registry = registry.url();
+    let config = config.leak();
+
+    let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+    let mut package_manager = PackageManager::new(config, manifest);
+
+    eprintln!("Install");
+    package_manager.install([DependencyGroup::Prod], false).await.unwrap();
+    assert!(is_symlink_or_junction(&modules_dir.join("foo")).unwrap());
+    assert!(modules_dir.join(".pnpm/bar@1.0.0").exists());
+
+    eprintln!("Add");
+    package_manager.add("baz", &[DependencyGroup::Dev], true, true).await.unwrap();
+    assert!(is_symlink_or_junction(&modules_dir.join("baz")).unwrap());
+    let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+    assert_eq!(
+        manifest.dependencies([DependencyGroup::Dev]).collect::<Vec<_>>(),
+        [("baz", "1.0.0")]
+    );
+
+    eprintln!("Remove");
+    package_manager.remove("foo").unwrap();
+    assert!(!modules_dir.join("foo").exists());
+    let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+    assert_eq!(manifest.dependencies([DependencyGroup::Prod]).count(), 0);
+    assert!(matches!(
+        package_manager.remove("foo"),
+        Err(RemoveError::NotFound { name }) if name == "foo"
+    ));
+
+    drop(dir); // cleanup
+}
+
+#[tokio::test]
+async fn should_report_events_to_custom_reporter() {
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl Reporter for Recorder {
+        fn emit(&self, event: Event) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    let registry = FakeRegistry::start(&[FakePackage::new("foo", "1.0.0")]);
+    let dir = tempdir().unwrap();
+    let project_dir = dir.path().join("project");
+    let modules_dir = project_dir.join("node_modules");
+    fs::create_dir_all(&project_dir).unwrap();
+    fs::write(project_dir.join("package.json"), r#"{ "dependencies": { "foo": "^1.0.0" } }"#)
+        .unwrap();
+
+    let mut config = Npmrc::new();
+    config.store_dir = dir.path().join("pacquet-store").into();
+    config.modules_dir = modules_dir.clone();
+    config.virtual_store_dir = modules_dir.join(".pnpm");
+    config.registry = registry.url();
+    let config = config.leak();
+
+    let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+    let recorder = Recorder::default();
+    let package_manager =
+        PackageManager::new(config, manifest).with_reporter(Box::new(recorder.clone()));
+    package_manager.install([DependencyGroup::Prod], false).await.unwrap();
+
+    let name = || "foo".to_string();
+    let version = || "1.0.0".to_string();
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        [
+            Event::ResolutionStarted,
+            Event::PackageResolved { name: name(), version: version() },
+            Event::TarballDownloadStarted {
+                name: name(),
+                version: version(),
+                size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE as u64),
+            },
+            Event::TarballDownloaded {
+                name: name(),
+                version: version(),
+                url: format!("{}foo/-/foo-1.0.0.tgz", registry.url()),
+            },
+            Event::PackageLinked {
+                name: name(),
+                version: version(),
+                path: modules_dir.join("foo")
+            },
+            Event::InstallDone,
+        ]
+    );
+
+    drop(dir); // cleanup
+}
+
+#[tokio::test]
+async fn install_should_report_deprecated_dependencies() {
+    let registry = FakeRegistry::start(&[
+        FakePackage::new("foo", "1.0.0").dependencies([("bar", "^1.0.0")]).own_tarball(),
+        FakePackage::new("qux", "1.0.0").dependencies([("bar", "^1.0.0")]).own_tarball(),
+        FakePackage::new("bar", "1.0.0")
+            .field("deprecated", json!("use baz instead"))
+            .own_tarball(),
+    ]);
+    let dir = tempdir().unwrap();
+    let project_dir = dir.path().join("project");
+    let modules_dir = project_dir.join("node_modules");
+    fs::create_dir_all(&project_dir).unwrap();
+    fs::write(
+        project_dir.join("package.json"),
+        r#"{ "dependencies": { "foo": "^1.0.0", "qux": "^1.0.0" } }"#,
+    )
+    .unwrap();
+
+    let mut config = Npmrc::new();
+    config.store_dir = dir.path().join("pacquet-store").into();
+    config.modules_dir = modules_dir.clone();
+    config.virtual_store_dir = modules_dir.join(".pnpm");
+    config.registry = registry.url();
+    let config = config.leak();
+
+    let package_manager = || {
+        let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+        PackageManager::new(config, manifest)
+    };
+    let report = package_manager().install([DependencyGroup::Prod], false).await.unwrap();
+    let deprecated = Warning::Deprecated {
+        name: "bar".to_string(),
+        version: "1.0.0".to_string(),
+        message: "use baz instead".to_string(),
+    };
+    assert_eq!(report.warnings, [deprecated.clone()]);
+    assert_eq!((report.added, report.removed, report.reused), (3, 0, 0));
+
+    eprintln!("Install again with a populated store");
+    let report = package_manager().install([DependencyGroup::Prod], false).await.unwrap();
+    assert_eq!(
+        report,
+        InstallReport { warnings: vec![deprecated], added: 3, removed: 0, reused: 3 }
+    );
+
+    drop(dir); // cleanup
+}
+
+#[tokio::test]
+async fn should_install_many_packages_with_tiny_tarball_cache() {
+    let names: Vec<_> = (0..20).map(|index| format!("pkg-{index}")).collect();
+    let packages: Vec<_> = names
+        .iter()
+        .map(|name| {
+            // every package depends on the next two, so most tarballs are requested repeatedly
+            let dependencies: Vec<_> = names
+                .iter()
+                .skip_while(|other| *other != name)
+                .skip(1)
+                .take(2)
+                .map(|other| (other.as_str(), "^1.0.0"))
+                .collect();
+            FakePackage::new(name, "1.0.0").dependencies(dependencies)
+        })
+        .collect();
+    let registry = FakeRegistry::start(&packages);
+    let dir = tempdir().unwrap();
+    let project_dir = dir.path().join("project");
+    let modules_dir = project_dir.join("node_modules");
+    fs::create_dir_all(&project_dir).unwrap();
+    let dependencies: serde_json::Map<_, _> =
+        names.iter().map(|name| (name.clone(), "^1.0.0".into())).collect();
+    fs::write(
+        project_dir.join("package.json"),
+        serde_json::json!({ "dependencies": dependencies }).to_string(),
+    )
+    .unwrap();
+
+    let mut config = Npmrc::new();
+    config.store_dir = dir.path().join("pacquet-store").into();
+    config.modules_dir = modules_dir.clone();
+    config.virtual_store_dir = modules_dir.join(".pnpm");
+    config.registry = registry.url();
+    let config = config.leak();
+
+    let manifest = PackageManifest::from_path(project_dir.join("package.json")).unwrap();
+    let mut package_manager = PackageManager::new(config, manifest);
+    let max_entries = NonZeroUsize::new(2).unwrap();
+    package_manager.tarball_mem_cache = MemCache::with_max_entries(max_entries);
+    package_manager.install([DependencyGroup::Prod], false).await.unwrap();
+
+    for name in &names {
+        assert!(is_symlink_or_junction(&modules_dir.join(name)).unwrap());
+        let virtual_dir = modules_dir.join(".pnpm").join(format!("{name}@1.0.0/node_modules"));
+        assert!(virtual_dir.join(name).join("package.json").is_file(), "{name} is imported");
+    }
+    assert!(package_manager.tarball_mem_cache.len() <= max_entries.get());
+
+    drop(dir); // cleanup
+}
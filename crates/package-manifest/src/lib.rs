@@ -1,3 +1,7 @@
+mod validate;
+
+pub use validate::*;
+
 use std::{
     fs,
     io::Write,
@@ -40,9 +44,17 @@ pub enum PackageManifestError {
     #[display("Missing script: {_0:?}")]
     #[diagnostic(code(pacquet_package_manifest::no_script_error))]
     NoScript(#[error(not(source))] String),
+
+    #[diagnostic(transparent)]
+    InvalidManifest(#[error(source)] Box<InvalidManifestError>),
+
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("{_0} isn't the path of a package.json file")]
+    #[diagnostic(code(pacquet_package_manifest::invalid_path))]
+    InvalidPath(#[error(not(source))] String),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, IntoStaticStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoStaticStr)]
 pub enum DependencyGroup {
     #[strum(serialize = "dependencies")]
     Prod,
@@ -62,8 +74,10 @@ pub enum BundleDependencies {
 }
 
 /// Content of the `package.json` files and its path.
+#[derive(Clone)]
 pub struct PackageManifest {
     path: PathBuf,
+    dir: PathBuf,
     value: Value, // TODO: convert this into a proper struct + an array of keys order
 }
 
@@ -95,8 +109,15 @@ impl PackageManifest {
         Ok((manifest, contents))
     }
 
+    fn project_dir(path: &Path) -> Result<PathBuf, PackageManifestError> {
+        path.parent()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| PackageManifestError::InvalidPath(path.display().to_string()))
+    }
+
     fn read_from_file(path: &Path) -> Result<Value, PackageManifestError> {
         let contents = fs::read_to_string(path)?;
+        validate_manifest(path, &contents).map_err(Box::new)?;
         serde_json::from_str(&contents).map_err(PackageManifestError::from)
     }
 
@@ -114,24 +135,31 @@ impl PackageManifest {
             return Err(PackageManifestError::NoImporterManifestFound(path.display().to_string()));
         }
 
+        let dir = PackageManifest::project_dir(&path)?;
         let value = PackageManifest::read_from_file(&path)?;
-        Ok(PackageManifest { path, value })
+        Ok(PackageManifest { path, dir, value })
     }
 
     pub fn create_if_needed(path: PathBuf) -> Result<PackageManifest, PackageManifestError> {
+        let dir = PackageManifest::project_dir(&path)?;
         let value = if path.exists() {
             PackageManifest::read_from_file(&path)?
         } else {
             PackageManifest::write_to_file(&path).map(|(value, _)| value)?
         };
 
-        Ok(PackageManifest { path, value })
+        Ok(PackageManifest { path, dir, value })
     }
 
     pub fn path(&self) -> &'_ Path {
         &self.path
     }
 
+    /// Directory of the project, i.e. the directory that contains `package.json`.
+    pub fn dir(&self) -> &'_ Path {
+        &self.dir
+    }
+
     pub fn value(&self) -> &'_ Value {
         &self.value
     }
@@ -157,6 +185,61 @@ impl PackageManifest {
             .flat_map(|(name, version)| version.as_str().map(|value| (name.as_str(), value)))
     }
 
+    /// Value of `packageManager`, such as `pnpm@8.6.0`.
+    pub fn package_manager(&self) -> Option<&'_ str> {
+        self.value.get("packageManager").and_then(Value::as_str)
+    }
+
+    /// Entries of `pnpm.overrides`.
+    pub fn overrides(&self) -> impl Iterator<Item = (&'_ str, &'_ str)> {
+        string_entries(self.value.get("pnpm").and_then(|pnpm| pnpm.get("overrides")))
+    }
+
+    /// Names listed by `pnpm.onlyBuiltDependencies`, the only dependencies whose build scripts may
+    /// run. `None` when the field is absent, in which case every dependency may run them.
+    pub fn only_built_dependencies(&self) -> Option<impl Iterator<Item = &'_ str>> {
+        let names = self.value.get("pnpm")?.get("onlyBuiltDependencies")?.as_array()?;
+        Some(names.iter().flat_map(Value::as_str))
+    }
+
+    /// Names listed by `pnpm.neverBuiltDependencies`, the dependencies whose build scripts never
+    /// run.
+    pub fn never_built_dependencies(&self) -> impl Iterator<Item = &'_ str> {
+        self.value
+            .get("pnpm")
+            .and_then(|pnpm| pnpm.get("neverBuiltDependencies"))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .flat_map(Value::as_str)
+    }
+
+    /// Patterns listed by `pnpm.peerDependencyRules.ignoreMissing`, the peers that aren't
+    /// reported when they are missing.
+    pub fn ignored_missing_peers(&self) -> impl Iterator<Item = &'_ str> {
+        self.peer_dependency_rules()
+            .and_then(|rules| rules.get("ignoreMissing"))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .flat_map(Value::as_str)
+    }
+
+    /// Entries of `pnpm.peerDependencyRules.allowedVersions`, the versions that satisfy peers in
+    /// addition to their ranges.
+    pub fn allowed_peer_versions(&self) -> impl Iterator<Item = (&'_ str, &'_ str)> {
+        string_entries(self.peer_dependency_rules().and_then(|rules| rules.get("allowedVersions")))
+    }
+
+    fn peer_dependency_rules(&self) -> Option<&'_ Value> {
+        self.value.get("pnpm")?.get("peerDependencyRules")
+    }
+
+    /// Entries of the Yarn-style `resolutions`.
+    pub fn resolutions(&self) -> impl Iterator<Item = (&'_ str, &'_ str)> {
+        string_entries(self.value.get("resolutions"))
+    }
+
     pub fn bundle_dependencies(&self) -> Result<Option<BundleDependencies>, serde_json::Error> {
         self.value
             .get("bundleDependencies")
@@ -189,6 +272,26 @@ impl PackageManifest {
         Ok(())
     }
 
+    /// Remove `name` from every dependency group and return the groups it was removed from.
+    pub fn remove_dependency(&mut self, name: &str) -> Vec<DependencyGroup> {
+        [
+            DependencyGroup::Prod,
+            DependencyGroup::Dev,
+            DependencyGroup::Optional,
+            DependencyGroup::Peer,
+        ]
+        .into_iter()
+        .filter(|&dependency_group| {
+            let dependency_type: &str = dependency_group.into();
+            self.value
+                .get_mut(dependency_type)
+                .and_then(Value::as_object_mut)
+                .and_then(|dependencies| dependencies.remove(name))
+                .is_some()
+        })
+        .collect()
+    }
+
     pub fn script(
         &self,
         command: &str,
@@ -211,9 +314,18 @@ impl PackageManifest {
     }
 }
 
+/// List the entries of a JSON object whose values are strings.
+fn string_entries(object: Option<&Value>) -> impl Iterator<Item = (&'_ str, &'_ str)> {
+    object
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .flat_map(|(key, value)| value.as_str().map(|value| (key.as_str(), value)))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, fs::read_to_string};
+    use std::{collections::HashMap, env, fs::read_to_string};
 
     use insta::assert_snapshot;
     use pipe_trait::Pipe;
@@ -246,6 +358,17 @@ mod tests {
         assert_eq!(PackageManifest::from_path(tmp.clone()).unwrap().path, tmp);
     }
 
+    #[test]
+    fn should_know_the_project_dir() {
+        let dir = tempdir().unwrap();
+        let manifest = PackageManifest::create_if_needed(dir.path().join("package.json")).unwrap();
+        assert_eq!(manifest.dir(), dir.path());
+
+        let root = env::current_dir().unwrap().ancestors().last().unwrap().to_path_buf();
+        let error = PackageManifest::create_if_needed(root).err().unwrap();
+        assert!(matches!(error, PackageManifestError::InvalidPath(_)));
+    }
+
     #[test]
     fn should_add_dependency() {
         let dir = tempdir().unwrap();
@@ -260,6 +383,72 @@ mod tests {
         assert!(read_to_string(tmp).unwrap().contains("fastify"));
     }
 
+    #[test]
+    fn should_remove_dependency_from_every_group() {
+        let data = r#"
+        {
+            "dependencies": { "fastify": "1.0.0", "foo": "1.0.0" },
+            "devDependencies": { "fastify": "1.0.0" }
+        }
+        "#;
+        let tmp = NamedTempFile::new().unwrap();
+        write!(tmp.as_file(), "{}", data).unwrap();
+        let mut manifest = PackageManifest::create_if_needed(tmp.path().to_path_buf()).unwrap();
+
+        assert_eq!(
+            manifest.remove_dependency("fastify"),
+            [DependencyGroup::Prod, DependencyGroup::Dev],
+        );
+        let groups = [DependencyGroup::Prod, DependencyGroup::Dev];
+        assert_eq!(manifest.dependencies(groups).collect::<Vec<_>>(), [("foo", "1.0.0")]);
+        assert_eq!(manifest.remove_dependency("fastify"), []);
+    }
+
+    #[test]
+    fn should_read_built_dependencies() {
+        let data = r#"
+        {
+            "pnpm": {
+                "onlyBuiltDependencies": ["esbuild", "sharp"],
+                "neverBuiltDependencies": ["fsevents"]
+            }
+        }
+        "#;
+        let tmp = NamedTempFile::new().unwrap();
+        write!(tmp.as_file(), "{}", data).unwrap();
+        let manifest = PackageManifest::create_if_needed(tmp.path().to_path_buf()).unwrap();
+        let only_built = manifest.only_built_dependencies().map(Iterator::collect::<Vec<_>>);
+        assert_eq!(only_built, Some(vec!["esbuild", "sharp"]));
+        assert_eq!(manifest.never_built_dependencies().collect::<Vec<_>>(), ["fsevents"]);
+
+        let tmp = NamedTempFile::new().unwrap();
+        write!(tmp.as_file(), "{{}}").unwrap();
+        let manifest = PackageManifest::create_if_needed(tmp.path().to_path_buf()).unwrap();
+        assert!(manifest.only_built_dependencies().is_none());
+        assert_eq!(manifest.never_built_dependencies().count(), 0);
+    }
+
+    #[test]
+    fn should_read_peer_dependency_rules() {
+        let data = r#"
+        {
+            "pnpm": {
+                "peerDependencyRules": {
+                    "ignoreMissing": ["@babel/*", "eslint"],
+                    "allowedVersions": { "react": "17", "button>react": "16" }
+                }
+            }
+        }
+        "#;
+        let tmp = NamedTempFile::new().unwrap();
+        write!(tmp.as_file(), "{}", data).unwrap();
+        let manifest = PackageManifest::create_if_needed(tmp.path().to_path_buf()).unwrap();
+        assert_eq!(manifest.ignored_missing_peers().collect::<Vec<_>>(), ["@babel/*", "eslint"]);
+        let mut allowed_versions: Vec<_> = manifest.allowed_peer_versions().collect();
+        allowed_versions.sort();
+        assert_eq!(allowed_versions, [("button>react", "16"), ("react", "17")]);
+    }
+
     #[test]
     fn should_throw_on_missing_command() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,216 @@
+use derive_more::{Display, Error};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use serde::{
+    de::{DeserializeSeed, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{fmt, path::Path};
+
+/// Error when a `package.json` is not valid JSON or has a field of the wrong type.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Invalid {file_name} at line {line} column {column}: {message}")]
+#[diagnostic(code(pacquet_package_manifest::invalid_manifest), help("Fix the field and try again"))]
+pub struct InvalidManifestError {
+    pub file_name: String,
+    pub message: String,
+    /// Line of the error, starting from 1.
+    pub line: usize,
+    /// Column of the error, starting from 1.
+    pub column: usize,
+    #[source_code]
+    pub source_code: NamedSource,
+    #[label("here")]
+    pub span: SourceSpan,
+}
+
+/// The fields of `package.json` that are checked before it is used.
+///
+/// Only the types are checked, the values are not.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestSchema {
+    #[serde(default, deserialize_with = "deserialize_name")]
+    _name: (),
+    #[serde(default, deserialize_with = "deserialize_version")]
+    _version: (),
+    #[serde(default, deserialize_with = "deserialize_dependencies")]
+    _dependencies: (),
+    #[serde(default, deserialize_with = "deserialize_dev_dependencies")]
+    _dev_dependencies: (),
+    #[serde(default, deserialize_with = "deserialize_optional_dependencies")]
+    _optional_dependencies: (),
+    #[serde(default, deserialize_with = "deserialize_peer_dependencies")]
+    _peer_dependencies: (),
+    #[serde(default, deserialize_with = "deserialize_scripts")]
+    _scripts: (),
+    #[serde(default, deserialize_with = "deserialize_package_manager")]
+    _package_manager: (),
+}
+
+macro_rules! field_deserializers {
+    ($($function:ident: $visitor:ident($field:literal);)*) => {
+        $(
+            fn $function<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
+                deserializer.deserialize_any($visitor($field))
+            }
+        )*
+    };
+}
+
+field_deserializers! {
+    deserialize_name: StringVisitor("name");
+    deserialize_version: StringVisitor("version");
+    deserialize_dependencies: StringMapVisitor("dependencies");
+    deserialize_dev_dependencies: StringMapVisitor("devDependencies");
+    deserialize_optional_dependencies: StringMapVisitor("optionalDependencies");
+    deserialize_peer_dependencies: StringMapVisitor("peerDependencies");
+    deserialize_scripts: StringMapVisitor("scripts");
+    deserialize_package_manager: StringVisitor("packageManager");
+}
+
+/// Accept a string field.
+struct StringVisitor(&'static str);
+
+impl<'de> Visitor<'de> for StringVisitor {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "`{}` to be a string", self.0)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Accept an object whose values are strings.
+struct StringMapVisitor(&'static str);
+
+impl<'de> Visitor<'de> for StringMapVisitor {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "`{}` to be an object of strings", self.0)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            map.next_value_seed(StringValue { field: self.0, key })?;
+        }
+        Ok(())
+    }
+}
+
+/// Accept a string value of an entry of a [`StringMapVisitor`] object.
+struct StringValue {
+    field: &'static str,
+    key: String,
+}
+
+impl<'de> DeserializeSeed<'de> for StringValue {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StringValue {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "`{}.{}` to be a string", self.field, self.key)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Check the syntax of `contents` and the types of the well known fields.
+pub(crate) fn validate_manifest(path: &Path, contents: &str) -> Result<(), InvalidManifestError> {
+    let Err(error) = serde_json::from_str::<ManifestSchema>(contents) else { return Ok(()) };
+
+    let (line, column) = (error.line(), error.column());
+    let location = format!(" at line {line} column {column}");
+    let message = error.to_string();
+    let message = message.strip_suffix(&location).unwrap_or(&message).to_string();
+
+    let line_start: usize =
+        contents.split_inclusive('\n').take(line.saturating_sub(1)).map(str::len).sum();
+    let offset = (line_start + column.saturating_sub(1)).min(contents.len());
+
+    let file_name = path.display().to_string();
+    Err(InvalidManifestError {
+        source_code: NamedSource::new(&file_name, contents.to_string()),
+        file_name,
+        message,
+        line,
+        column,
+        span: offset.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn validate(contents: &str) -> Result<(), InvalidManifestError> {
+        validate_manifest(Path::new("package.json"), contents)
+    }
+
+    #[test]
+    fn should_accept_valid_manifest() {
+        let contents = r#"{
+  "name": "foo",
+  "version": "1.0.0",
+  "dependencies": { "bar": "^1.0.0" },
+  "scripts": { "test": "echo" },
+  "keywords": [],
+  "custom": { "anything": 1 }
+}"#;
+        validate(contents).unwrap();
+        validate("{}").unwrap();
+    }
+
+    #[test]
+    fn should_reject_dependencies_of_the_wrong_type() {
+        let contents = r#"{
+  "name": "foo",
+  "dependencies": ["bar"]
+}"#;
+        let error = validate(contents).unwrap_err();
+        dbg!(&error);
+        assert_eq!(
+            error.message,
+            "invalid type: sequence, expected `dependencies` to be an object of strings",
+        );
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn should_reject_versions_that_are_not_strings() {
+        let contents = r#"{
+  "devDependencies": {
+    "bar": "^1.0.0",
+    "baz": 2
+  }
+}"#;
+        let error = validate(contents).unwrap_err();
+        dbg!(&error);
+        assert_eq!(
+            error.message,
+            "invalid type: integer `2`, expected `devDependencies.baz` to be a string"
+        );
+        assert_eq!(error.line, 4);
+    }
+
+    #[test]
+    fn should_reject_invalid_json() {
+        let error = validate("{\n  \"name\": \"foo\",\n}").unwrap_err();
+        dbg!(&error);
+        assert_eq!(error.message, "trailing comma");
+        assert_eq!((error.line, error.column), (3, 1));
+    }
+}
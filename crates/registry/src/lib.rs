@@ -2,14 +2,24 @@ mod package;
 mod package_distribution;
 mod package_tag;
 mod package_version;
+mod packument_cache;
+mod timestamp;
 
 pub use package::Package;
 pub use package_distribution::PackageDistribution;
 pub use package_tag::PackageTag;
 pub use package_version::PackageVersion;
+pub use packument_cache::{
+    CleanPackumentCacheError, PackumentCache, PackumentCacheReport, VerifyPackumentCacheError,
+    WritePackumentCacheError,
+};
+pub use timestamp::{ParseTimestampError, Timestamp};
 
 use derive_more::{Display, Error, From};
 use miette::Diagnostic;
+use pacquet_network::InFlightRequest;
+use reqwest::{Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
 
 #[derive(Debug, Display, Error)]
 #[display("Failed to request {url}: {error}")]
@@ -32,14 +42,147 @@ pub enum RegistryError {
     #[diagnostic(code(pacquet_registry::missing_version_release))]
     MissingVersionRelease(String, String),
 
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("{name} is not in the registry at {url}")]
+    #[diagnostic(
+        code(pacquet_registry::not_found),
+        help("Check the name of the package and the registry that it is published to")
+    )]
+    NotFound { name: String, url: String },
+
     #[diagnostic(code(pacquet_registry::network_error))]
     Network(NetworkError), // TODO: remove derive(Error), split this variant
 
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("Request to {url} timed out: {error}")]
+    #[diagnostic(
+        code(pacquet_registry::timeout),
+        help("Check the network connection and try again")
+    )]
+    Timeout {
+        url: String,
+        #[error(source)]
+        error: reqwest::Error,
+    },
+
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("The registry refused to serve {url} with status {code}{}", colon_message(message))]
+    #[diagnostic(
+        code(pacquet_registry::auth_error),
+        help("Check the auth token of the registry in .npmrc")
+    )]
+    Auth { url: String, code: u16, message: Option<String> },
+
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("The registry responded to {url} with status {code}{}", colon_message(message))]
+    #[diagnostic(code(pacquet_registry::status_error))]
+    Status { url: String, code: u16, message: Option<String> },
+
     #[diagnostic(code(pacquet_registry::io_error))]
     Io(std::io::Error), // TODO: remove derive(Error), split this variant
 
     #[from(ignore)] // TODO: remove this after derive(From) has been removed
-    #[display("Serialization failed: {_0}")]
-    #[diagnostic(code(pacquet_registry::serialization_error))]
-    Serialization(#[error(not(source))] String),
+    #[display("The registry responded to {url} with an error: {message}")]
+    #[diagnostic(code(pacquet_registry::registry_error))]
+    Registry { url: String, message: String },
+
+    #[from(ignore)] // TODO: remove this after derive(From) has been removed
+    #[display("Failed to parse the response to {url}: {error}")]
+    #[diagnostic(code(pacquet_registry::decode_error))]
+    Decode {
+        url: String,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+}
+
+impl RegistryError {
+    /// Classify an `error` of the request to `url`.
+    pub(crate) fn from_request(url: String, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            RegistryError::Timeout { url, error }
+        } else {
+            RegistryError::Network(NetworkError { url, error })
+        }
+    }
+
+    /// Whether sending the same request again may succeed: the network failed, the request timed
+    /// out, or the registry responded with a server error or `429 Too Many Requests`.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RegistryError::Network(_) | RegistryError::Timeout { .. } => true,
+            RegistryError::Status { code, .. } => *code >= 500 || *code == 429,
+            _ => false,
+        }
+    }
+}
+
+/// Turn the unsuccessful `status` of a response from `url` into an error, where `name` is what
+/// was requested, or return the `body` of the response.
+///
+/// The message of an error body, if any, is kept in the error. Other statuses are left to
+/// [`parse_body`], as some registries answer with an error body and a successful status.
+fn check_status(
+    status: StatusCode,
+    body: Result<Vec<u8>, RegistryError>,
+    url: &str,
+    name: impl FnOnce() -> String,
+) -> Result<Vec<u8>, RegistryError> {
+    if !status.is_client_error() && !status.is_server_error() {
+        return body;
+    }
+    let url = url.to_string();
+    let code = status.as_u16();
+    let message = || {
+        let body = body.as_deref().ok()?;
+        serde_json::from_slice::<ErrorBody>(body).ok().map(|ErrorBody { error }| error)
+    };
+    Err(match status {
+        StatusCode::NOT_FOUND => RegistryError::NotFound { name: name(), url },
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            RegistryError::Auth { url, code, message: message() }
+        }
+        _ => RegistryError::Status { url, code, message: message() },
+    })
+}
+
+/// Format the `message` of an error body to be appended to an error, if any.
+fn colon_message(message: &Option<String>) -> String {
+    message.as_ref().map(|message| format!(": {message}")).unwrap_or_default()
+}
+
+/// Read the whole body of a `response` from `url`.
+///
+/// The body has already been decompressed according to its `content-encoding` by the client,
+/// see [`ThrottledClient::run_metadata_with_permit`](pacquet_network::ThrottledClient::run_metadata_with_permit).
+async fn read_body(
+    in_flight: InFlightRequest<'_>,
+    response: Response,
+    url: &str,
+) -> Result<Vec<u8>, RegistryError> {
+    let body = in_flight
+        .bytes(response)
+        .await
+        .map_err(|error| RegistryError::from_request(url.to_string(), error))?;
+    drop(in_flight); // the request is complete once the body has been read
+    Ok(body)
+}
+
+/// Body that a registry sends instead of the requested metadata, e.g. `{ "error": "not found" }`.
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Parse the `body` of a response from `url` as `T`.
+///
+/// An error body is reported as [`RegistryError::Registry`] with the message of the server, and
+/// any other body that isn't a `T` as [`RegistryError::Decode`].
+fn parse_body<T: DeserializeOwned>(body: &[u8], url: &str) -> Result<T, RegistryError> {
+    serde_json::from_slice(body).map_err(|error| match serde_json::from_slice::<ErrorBody>(body) {
+        Ok(ErrorBody { error: message }) => {
+            RegistryError::Registry { url: url.to_string(), message }
+        }
+        Err(_) => RegistryError::Decode { url: url.to_string(), error },
+    })
 }
@@ -4,17 +4,31 @@ use std::{
 };
 
 use pacquet_network::ThrottledClient;
-use pipe_trait::Pipe;
 use serde::{Deserialize, Serialize};
 
-use crate::{package_version::PackageVersion, NetworkError, RegistryError};
+use crate::{
+    check_status, package_version::PackageVersion, parse_body, read_body, PackageTag,
+    RegistryError, Timestamp,
+};
+
+/// Value of the `accept` header that asks for the abbreviated metadata, which is all that
+/// resolution needs except for the publish times.
+const ACCEPT_ABBREVIATED: &str =
+    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
+
+/// Value of the `accept` header that asks for the full metadata.
+const ACCEPT_FULL: &str = "application/json";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Package {
     pub name: String,
-    #[serde(rename = "dist-tags")]
-    dist_tags: HashMap<String, String>,
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: HashMap<String, node_semver::Version>,
     pub versions: HashMap<String, PackageVersion>,
+    /// When each version was published, as well as `created` and `modified`. Only the full
+    /// metadata has it, see [`fetch_full_from_registry`](Package::fetch_full_from_registry).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    time: HashMap<String, String>,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub mutex: Arc<Mutex<u8>>,
@@ -27,37 +41,80 @@ impl PartialEq for Package {
 }
 
 impl Package {
+    /// Fetch the abbreviated metadata of package `name`.
     pub async fn fetch_from_registry(
         name: &str,
         http_client: &ThrottledClient,
         registry: &str,
+    ) -> Result<Self, RegistryError> {
+        Package::fetch(name, http_client, registry, ACCEPT_ABBREVIATED).await
+    }
+
+    /// Fetch the full metadata of package `name`, which is larger than the abbreviated one but
+    /// has the publish time of every version.
+    pub async fn fetch_full_from_registry(
+        name: &str,
+        http_client: &ThrottledClient,
+        registry: &str,
+    ) -> Result<Self, RegistryError> {
+        Package::fetch(name, http_client, registry, ACCEPT_FULL).await
+    }
+
+    async fn fetch(
+        name: &str,
+        http_client: &ThrottledClient,
+        registry: &str,
+        accept: &str,
     ) -> Result<Self, RegistryError> {
         let url = || format!("{registry}{name}"); // TODO: use reqwest URL directly
-        let network_error = |error| NetworkError { error, url: url() };
-        http_client
-            .run_with_permit(|client| {
-                client
-                    .get(url())
-                    .header(
-                        "accept",
-                        "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*",
-                    )
-                    .send()
+        let (in_flight, response) = http_client
+            .run_metadata_with_permit(|client| {
+                let in_flight = http_client.activity().start(url());
+                let request = client.get(url()).header("accept", accept);
+                let response = http_client.authorize(&url(), request).send();
+                async move { (in_flight, response.await) }
             })
-            .await
-            .map_err(network_error)?
-            .json::<Package>()
-            .await
-            .map_err(network_error)?
-            .pipe(Ok)
+            .await;
+        let response = response.map_err(|error| RegistryError::from_request(url(), error))?;
+        let status = response.status();
+        let body = read_body(in_flight, response, &url()).await;
+        let body = check_status(status, body, &url(), || name.to_string())?;
+        parse_body(&body, &url())
     }
 
+    /// Find the highest version that satisfies `version_range`.
+    ///
+    /// Compound ranges (`>=1.2.0 <2.0.0 || ^3.0.0`) and hyphen ranges (`1.2.0 - 1.5.0`) are handled
+    /// by [`node_semver::Range`]. An empty spec and `latest` are treated the same as `*`, i.e. the
+    /// highest stable (non-prerelease) version. A range that can't be parsed satisfies nothing.
     pub fn pinned_version(&self, version_range: &str) -> Option<&PackageVersion> {
-        let range: node_semver::Range = version_range.parse().unwrap(); // TODO: this step should have happened in PackageManifest
+        self.highest_satisfying(version_range, |_| true)
+    }
+
+    /// Like [`pinned_version`](Package::pinned_version), but only among the versions that were
+    /// published before `before`.
+    pub fn pinned_version_before(
+        &self,
+        version_range: &str,
+        before: Timestamp,
+    ) -> Option<&PackageVersion> {
+        self.highest_satisfying(version_range, |version| self.published_before(version, before))
+    }
+
+    fn highest_satisfying(
+        &self,
+        version_range: &str,
+        filter: impl Fn(&PackageVersion) -> bool,
+    ) -> Option<&PackageVersion> {
+        let version_range = match version_range.trim() {
+            "" | "latest" => "*",
+            version_range => version_range,
+        };
+        let range: node_semver::Range = version_range.parse().ok()?; // TODO: this step should have happened in PackageManifest
         let mut satisfied_versions = self
             .versions
             .values()
-            .filter(|v| v.version.satisfies(&range))
+            .filter(|v| v.version.satisfies(&range) && filter(v))
             .collect::<Vec<&PackageVersion>>();
 
         satisfied_versions.sort_by(|a, b| a.version.partial_cmp(&b.version).unwrap());
@@ -67,10 +124,86 @@ impl Package {
         satisfied_versions.last().copied()
     }
 
-    pub fn latest(&self) -> &PackageVersion {
-        let version =
-            self.dist_tags.get("latest").expect("latest tag is expected but not found for package");
-        self.versions.get(version).unwrap()
+    /// All published versions in ascending semver order, without the prereleases unless
+    /// `include_prereleases` is true.
+    pub fn versions_sorted(&self, include_prereleases: bool) -> Vec<&node_semver::Version> {
+        let mut versions: Vec<_> = self
+            .versions
+            .values()
+            .map(|version| &version.version)
+            .filter(|version| include_prereleases || !version.is_prerelease())
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// When `version` was published, if the metadata says so.
+    pub fn published_at(&self, version: &str) -> Option<Timestamp> {
+        self.time.get(version)?.parse().ok()
+    }
+
+    /// Whether the metadata has the publish times of the versions, see
+    /// [`fetch_full_from_registry`](Package::fetch_full_from_registry).
+    pub fn has_publish_times(&self) -> bool {
+        !self.time.is_empty()
+    }
+
+    /// Whether `version` is known to have been published before `before`.
+    fn published_before(&self, version: &PackageVersion, before: Timestamp) -> bool {
+        self.published_at(&version.version.to_string()).is_some_and(|time| time < before)
+    }
+
+    /// The version that the `latest` dist-tag points to.
+    pub fn latest(&self) -> Result<&PackageVersion, RegistryError> {
+        let version = self
+            .dist_tag("latest")
+            .ok_or_else(|| RegistryError::MissingLatestTag(self.name.clone()))?
+            .to_string();
+        self.versions
+            .get(&version)
+            .ok_or_else(|| RegistryError::MissingVersionRelease(version, self.name.clone()))
+    }
+
+    /// Versions of the package keyed by dist-tag (e.g. `latest`, `next`).
+    pub fn dist_tags(&self) -> &HashMap<String, node_semver::Version> {
+        &self.dist_tags
+    }
+
+    /// The version that `tag` points to.
+    pub fn dist_tag(&self, tag: &str) -> Option<&node_semver::Version> {
+        self.dist_tags.get(tag)
+    }
+
+    /// The version that [`PackageVersion::fetch_from_registry`] would return for `tag`.
+    pub fn tagged_version(&self, tag: &PackageTag) -> Option<&PackageVersion> {
+        let version = match tag {
+            PackageTag::Latest => self.dist_tag("latest")?,
+            PackageTag::Version(version) => version,
+        };
+        self.versions.get(&version.to_string())
+    }
+
+    /// Like [`tagged_version`](Package::tagged_version), but only among the versions that were
+    /// published before `before`.
+    ///
+    /// When `latest` was published later, the highest version below it that was published in
+    /// time is used instead, like npm does.
+    pub fn tagged_version_before(
+        &self,
+        tag: &PackageTag,
+        before: Timestamp,
+    ) -> Option<&PackageVersion> {
+        match tag {
+            PackageTag::Latest => {
+                let latest = self.dist_tag("latest")?;
+                self.highest_satisfying(&format!("<={latest}"), |version| {
+                    self.published_before(version, before)
+                })
+            }
+            PackageTag::Version(_) => {
+                self.tagged_version(tag).filter(|version| self.published_before(version, before))
+            }
+        }
     }
 }
 
@@ -82,7 +215,8 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
-    use crate::package_distribution::PackageDistribution;
+    use crate::{package_distribution::PackageDistribution, NetworkError};
+    use std::time::Duration;
 
     #[test]
     pub fn package_version_should_include_peers() {
@@ -97,6 +231,9 @@ mod tests {
             dependencies: Some(dependencies),
             dev_dependencies: None,
             peer_dependencies: Some(peer_dependencies),
+            peer_dependencies_meta: None,
+            engines: None,
+            deprecated: None,
         };
 
         let dependencies = |peer| version.dependencies(peer).collect::<HashMap<_, _>>();
@@ -107,6 +244,177 @@ mod tests {
         assert!(!dependencies(true).contains_key("hello-world"));
     }
 
+    fn package_with_versions(versions: &[&str]) -> Package {
+        let versions = versions
+            .iter()
+            .map(|version| {
+                let package_version = PackageVersion {
+                    name: "foo".to_string(),
+                    version: Version::parse(version).unwrap(),
+                    dist: PackageDistribution::default(),
+                    dependencies: None,
+                    dev_dependencies: None,
+                    peer_dependencies: None,
+                    peer_dependencies_meta: None,
+                    engines: None,
+                    deprecated: None,
+                };
+                (version.to_string(), package_version)
+            })
+            .collect();
+        Package {
+            name: "foo".to_string(),
+            dist_tags: HashMap::new(),
+            versions,
+            time: HashMap::new(),
+            mutex: Default::default(),
+        }
+    }
+
+    #[test]
+    pub fn pinned_version_should_pick_highest_satisfying_version() {
+        let package = package_with_versions(&[
+            "1.0.0",
+            "1.2.0",
+            "1.5.0",
+            "1.5.1",
+            "1.9.9",
+            "2.0.0",
+            "3.1.0",
+            "4.0.0-beta.1",
+        ]);
+
+        let case = |version_range: &str, expected: Option<&str>| {
+            eprintln!("CASE: {version_range:?} -> {expected:?}");
+            let received = package.pinned_version(version_range).map(|v| v.version.to_string());
+            assert_eq!(received.as_deref(), expected);
+        };
+
+        case("^1.2.0", Some("1.9.9"));
+        case("1.2.0 - 1.5.0", Some("1.5.0"));
+        case(">=1.2.0 <2.0.0 || ^3.0.0", Some("3.1.0"));
+        case(">=1.2.0 <1.5.1 || 2.0.0", Some("2.0.0"));
+        case("*", Some("3.1.0"));
+        case("latest", Some("3.1.0"));
+        case("", Some("3.1.0"));
+        case("^5.0.0", None);
+    }
+
+    #[test]
+    pub fn versions_sorted_should_follow_semver_order() {
+        let package = package_with_versions(&[
+            "1.10.0",
+            "2.0.0-rc.1",
+            "1.2.0",
+            "0.9.0",
+            "2.0.0",
+            "1.2.0-beta.10",
+            "1.2.0-beta.2",
+        ]);
+        let sorted = |include_prereleases| {
+            package
+                .versions_sorted(include_prereleases)
+                .into_iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(false), ["0.9.0", "1.2.0", "1.10.0", "2.0.0"]);
+        assert_eq!(
+            sorted(true),
+            ["0.9.0", "1.2.0-beta.2", "1.2.0-beta.10", "1.2.0", "1.10.0", "2.0.0-rc.1", "2.0.0"],
+        );
+    }
+
+    #[test]
+    pub fn versions_before_should_respect_publish_times() {
+        let package = Package {
+            dist_tags: [("latest".to_string(), Version::parse("2.0.0").unwrap())].into(),
+            time: [
+                ("created", "2020-01-01T00:00:00.000Z"),
+                ("modified", "2023-06-01T00:00:00.000Z"),
+                ("1.0.0", "2020-01-01T00:00:00.000Z"),
+                ("1.1.0", "2021-06-01T00:00:00.000Z"),
+                ("2.0.0", "2023-06-01T00:00:00.000Z"),
+                ("3.0.0-rc.1", "2022-01-01T00:00:00.000Z"),
+            ]
+            .map(|(key, time)| (key.to_string(), time.to_string()))
+            .into(),
+            // 1.2.0 has no publish time
+            ..package_with_versions(&["1.0.0", "1.1.0", "1.2.0", "2.0.0", "3.0.0-rc.1"])
+        };
+        assert!(package.has_publish_times());
+        assert!(!package_with_versions(&["1.0.0"]).has_publish_times());
+
+        let before = |date: &str| date.parse::<Timestamp>().unwrap();
+        let pinned = |version_range: &str, date: &str| {
+            let version = package.pinned_version_before(version_range, before(date));
+            version.map(|version| version.version.to_string())
+        };
+        assert_eq!(pinned("*", "2024-01-01").as_deref(), Some("2.0.0"));
+        assert_eq!(pinned("*", "2023-01-01").as_deref(), Some("1.1.0"));
+        assert_eq!(pinned("^1.0.0", "2021-06-01").as_deref(), Some("1.0.0"));
+        assert_eq!(pinned("^1.0.0", "2019-01-01"), None);
+
+        let tagged = |tag: &PackageTag, date: &str| {
+            let version = package.tagged_version_before(tag, before(date));
+            version.map(|version| version.version.to_string())
+        };
+        assert_eq!(tagged(&PackageTag::Latest, "2024-01-01").as_deref(), Some("2.0.0"));
+        assert_eq!(tagged(&PackageTag::Latest, "2023-01-01").as_deref(), Some("1.1.0"));
+        let version = PackageTag::Version(Version::parse("2.0.0").unwrap());
+        assert_eq!(tagged(&version, "2023-01-01"), None);
+        assert_eq!(tagged(&version, "2023-06-02").as_deref(), Some("2.0.0"));
+    }
+
+    #[test]
+    pub fn dist_tag_should_return_tagged_version() {
+        let package: Package = serde_json::from_str(
+            r#"{
+                "name": "foo",
+                "dist-tags": { "latest": "1.2.3", "next": "2.0.0-rc.1" },
+                "versions": {}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(package.dist_tag("latest"), Some(&Version::parse("1.2.3").unwrap()));
+        assert_eq!(package.dist_tag("next"), Some(&Version::parse("2.0.0-rc.1").unwrap()));
+        assert_eq!(package.dist_tag("beta"), None);
+        assert_eq!(package.dist_tags().len(), 2);
+
+        let package = Package {
+            dist_tags: [("latest".to_string(), Version::parse("1.5.0").unwrap())].into(),
+            ..package_with_versions(&["1.0.0", "1.5.0", "2.0.0-rc.1"])
+        };
+        let tagged_version = |tag: PackageTag| {
+            package.tagged_version(&tag).map(|version| version.version.to_string())
+        };
+        assert_eq!(tagged_version(PackageTag::Latest).as_deref(), Some("1.5.0"));
+        let version = |version: &str| PackageTag::Version(Version::parse(version).unwrap());
+        assert_eq!(tagged_version(version("1.0.0")).as_deref(), Some("1.0.0"));
+        assert_eq!(tagged_version(version("3.0.0")), None);
+
+        let package: Package =
+            serde_json::from_str(r#"{ "name": "foo", "versions": {} }"#).unwrap();
+        assert!(package.dist_tags().is_empty());
+        assert!(matches!(
+            package.latest(),
+            Err(RegistryError::MissingLatestTag(name)) if name == "foo"
+        ));
+    }
+
+    #[test]
+    pub fn latest_should_fail_when_the_tagged_version_is_missing() {
+        let package = Package {
+            dist_tags: [("latest".to_string(), Version::parse("2.0.0").unwrap())].into(),
+            ..package_with_versions(&["1.0.0"])
+        };
+        assert!(matches!(
+            package.latest(),
+            Err(RegistryError::MissingVersionRelease(version, name))
+                if version == "2.0.0" && name == "foo"
+        ));
+    }
+
     #[test]
     pub fn serialized_according_to_params() {
         let version = PackageVersion {
@@ -116,9 +424,224 @@ mod tests {
             dependencies: None,
             dev_dependencies: None,
             peer_dependencies: None,
+            peer_dependencies_meta: None,
+            engines: None,
+            deprecated: None,
         };
 
         assert_eq!(version.serialize(true), "3.2.1");
         assert_eq!(version.serialize(false), "^3.2.1");
     }
+
+    #[tokio::test]
+    async fn error_body_should_surface_as_registry_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/foo")
+            .with_status(200)
+            .with_body(r#"{"error":"not found"}"#)
+            .create_async()
+            .await;
+
+        let registry = format!("{}/", server.url());
+        let error = Package::fetch_from_registry("foo", &ThrottledClient::default(), &registry)
+            .await
+            .unwrap_err();
+        dbg!(&error);
+        let RegistryError::Registry { url, message } = error else {
+            panic!("expected a registry error");
+        };
+        assert_eq!(url, format!("{registry}foo"));
+        assert_eq!(message, "not found");
+    }
+
+    #[tokio::test]
+    async fn responses_should_map_to_error_variants() {
+        let mut server = mockito::Server::new_async().await;
+        let registry = format!("{}/", server.url());
+        let fetch = |name: &'static str| {
+            let registry = registry.clone();
+            async move {
+                Package::fetch_from_registry(name, &ThrottledClient::default(), &registry)
+                    .await
+                    .unwrap_err()
+            }
+        };
+
+        eprintln!("CASE: 404 is not found");
+        let _mock = server.mock("GET", "/missing").with_status(404).create_async().await;
+        let error = fetch("missing").await;
+        dbg!(&error);
+        assert!(matches!(error, RegistryError::NotFound { .. }));
+        assert!(!error.is_transient());
+
+        for code in [401, 403u16] {
+            eprintln!("CASE: {code} is an auth error");
+            let _mock =
+                server.mock("GET", "/private").with_status(code as usize).create_async().await;
+            let error = fetch("private").await;
+            dbg!(&error);
+            assert!(
+                matches!(error, RegistryError::Auth { code: received, .. } if received == code)
+            );
+            assert!(!error.is_transient());
+        }
+
+        for (code, transient) in [(500u16, true), (503, true), (429, true), (400, false)] {
+            eprintln!("CASE: {code} is a status error");
+            let _mock =
+                server.mock("GET", "/broken").with_status(code as usize).create_async().await;
+            let error = fetch("broken").await;
+            dbg!(&error);
+            let RegistryError::Status { url, code: received, .. } = &error else {
+                panic!("expected a status error");
+            };
+            assert_eq!(url, &format!("{registry}broken"));
+            assert_eq!(*received, code);
+            assert_eq!(error.is_transient(), transient);
+        }
+
+        eprintln!("CASE: invalid JSON is a decode error");
+        let _mock = server.mock("GET", "/garbage").with_body("not json").create_async().await;
+        let error = fetch("garbage").await;
+        dbg!(&error);
+        assert!(matches!(error, RegistryError::Decode { .. }));
+        assert!(!error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn error_statuses_should_keep_the_message_of_the_body() {
+        let mut server = mockito::Server::new_async().await;
+        let registry = format!("{}/", server.url());
+        let fetch = |name: &'static str| {
+            let registry = registry.clone();
+            async move {
+                Package::fetch_from_registry(name, &ThrottledClient::default(), &registry)
+                    .await
+                    .unwrap_err()
+            }
+        };
+
+        eprintln!("CASE: auth error with an error body");
+        let _mock = server
+            .mock("GET", "/private")
+            .with_status(401)
+            .with_body(r#"{"error":"invalid token"}"#)
+            .create_async()
+            .await;
+        let error = fetch("private").await;
+        dbg!(&error);
+        let RegistryError::Auth { message, .. } = &error else {
+            panic!("expected an auth error");
+        };
+        assert_eq!(message.as_deref(), Some("invalid token"));
+        assert!(error.to_string().ends_with("with status 401: invalid token"));
+
+        eprintln!("CASE: status error with an error body");
+        let _mock = server
+            .mock("GET", "/broken")
+            .with_status(503)
+            .with_body(r#"{"error":"under maintenance"}"#)
+            .create_async()
+            .await;
+        let error = fetch("broken").await;
+        dbg!(&error);
+        let RegistryError::Status { message, .. } = &error else {
+            panic!("expected a status error");
+        };
+        assert_eq!(message.as_deref(), Some("under maintenance"));
+        assert!(error.to_string().ends_with("with status 503: under maintenance"));
+
+        eprintln!("CASE: status error with a body that isn't an error body");
+        let _mock =
+            server.mock("GET", "/html").with_status(500).with_body("<html/>").create_async().await;
+        let error = fetch("html").await;
+        dbg!(&error);
+        let RegistryError::Status { message, .. } = &error else {
+            panic!("expected a status error");
+        };
+        assert_eq!(message, &None);
+        assert!(error.to_string().ends_with("with status 500"));
+    }
+
+    #[tokio::test]
+    async fn unreachable_registry_should_be_a_network_error() {
+        // bind then drop a listener to find a port that nothing listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let registry = format!("http://127.0.0.1:{port}/");
+        let error = Package::fetch_from_registry("foo", &ThrottledClient::default(), &registry)
+            .await
+            .unwrap_err();
+        dbg!(&error);
+        let RegistryError::Network(NetworkError { url, .. }) = &error else {
+            panic!("expected a network error");
+        };
+        assert_eq!(url, &format!("{registry}foo"));
+        assert!(error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn timed_out_request_should_be_a_timeout_error() {
+        // a listener that never accepts: the connection is made but no response ever comes
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/foo", listener.local_addr().unwrap());
+        let client = reqwest::Client::builder().timeout(Duration::from_millis(50)).build().unwrap();
+        let error = client.get(&url).send().await.unwrap_err();
+        let error = RegistryError::from_request(url.clone(), error);
+        dbg!(&error);
+        let RegistryError::Timeout { url: received, .. } = &error else {
+            panic!("expected a timeout error");
+        };
+        assert_eq!(received, &url);
+        assert!(error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn metadata_requests_should_send_the_user_agent() {
+        let mut server = mockito::Server::new_async().await;
+        let registry = format!("{}/", server.url());
+        let body = r#"{"name":"foo","dist-tags":{},"versions":{}}"#;
+
+        eprintln!("CASE: default user agent");
+        let mock = server
+            .mock("GET", "/foo")
+            .match_header("user-agent", pacquet_network::default_user_agent().as_str())
+            .with_body(body)
+            .create_async()
+            .await;
+        Package::fetch_from_registry("foo", &ThrottledClient::default(), &registry).await.unwrap();
+        mock.assert_async().await;
+
+        eprintln!("CASE: configured user agent");
+        let mock = server
+            .mock("GET", "/foo")
+            .match_header("user-agent", "corp-proxy/1.0")
+            .with_body(body)
+            .create_async()
+            .await;
+        let http_client =
+            ThrottledClient::default().with_user_agent("corp-proxy/1.0".parse().unwrap());
+        Package::fetch_from_registry("foo", &http_client, &registry).await.unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn gzip_encoded_packument_should_be_decoded() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/foo")
+            .match_header("accept-encoding", mockito::Matcher::Regex("gzip".to_string()))
+            .with_header("content-encoding", "gzip")
+            .with_body(include_bytes!("fixtures/foo-packument.json.gz"))
+            .create_async()
+            .await;
+
+        let registry = format!("{}/", server.url());
+        let package = Package::fetch_from_registry("foo", &ThrottledClient::default(), &registry)
+            .await
+            .unwrap();
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.latest().unwrap().version.to_string(), "1.0.0");
+        mock.assert_async().await;
+    }
 }
@@ -1,13 +1,22 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use ssri::Integrity;
 
+/// The `dist` field of a package version.
+///
+/// Only `tarball` is required. The other fields are missing or malformed in the metadata of some
+/// registries, those are read as `None` instead of failing the whole packument.
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageDistribution {
+    #[serde(default, deserialize_with = "deserialize_lenient")]
     pub integrity: Option<Integrity>,
+    #[serde(default, deserialize_with = "deserialize_lenient")]
     pub shasum: Option<String>,
     pub tarball: String,
+    #[serde(default, deserialize_with = "deserialize_lenient_count")]
     pub file_count: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_lenient_count")]
     pub unpacked_size: Option<usize>,
 }
 
@@ -16,3 +25,73 @@ impl PartialEq for PackageDistribution {
         self.integrity == other.integrity
     }
 }
+
+/// Deserialize a string, or `None` if the value is null, isn't a string, or can't be parsed.
+fn deserialize_lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(value.as_ref().and_then(Value::as_str).and_then(|text| text.parse().ok()))
+}
+
+/// Deserialize a non-negative number, which some registries send as a string, or `None` if the
+/// value is anything else.
+fn deserialize_lenient_count<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<Value>::deserialize(deserializer)?;
+    let count = match &value {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(text)) => text.parse().ok(),
+        _ => None,
+    };
+    Ok(count.and_then(|count| count.try_into().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_deserialize_dist_with_only_tarball() {
+        let dist: PackageDistribution = serde_json::from_str(
+            r#"{ "tarball": "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz" }"#,
+        )
+        .unwrap();
+        assert_eq!(dist.tarball, "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz");
+        assert_eq!(dist.integrity, None);
+        assert_eq!(dist.shasum, None);
+        assert_eq!(dist.file_count, None);
+        assert_eq!(dist.unpacked_size, None);
+    }
+
+    #[test]
+    fn should_tolerate_malformed_optional_fields() {
+        let dist: PackageDistribution = serde_json::from_str(
+            r#"{
+                "tarball": "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz",
+                "integrity": 42,
+                "shasum": null,
+                "fileCount": "12",
+                "unpackedSize": -1
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(dist.integrity, None);
+        assert_eq!(dist.shasum, None);
+        assert_eq!(dist.file_count, Some(12));
+        assert_eq!(dist.unpacked_size, None);
+    }
+
+    #[test]
+    fn should_require_tarball() {
+        let error = serde_json::from_str::<PackageDistribution>(r#"{ "fileCount": 1 }"#)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing field `tarball`"), "{error}");
+    }
+}
@@ -1,20 +1,79 @@
 use std::collections::HashMap;
 
 use pacquet_network::ThrottledClient;
-use pipe_trait::Pipe;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
-use crate::{package_distribution::PackageDistribution, NetworkError, PackageTag, RegistryError};
+use crate::{
+    check_status, package_distribution::PackageDistribution, parse_body, read_body, PackageTag,
+    RegistryError,
+};
+
+/// Value type of [`PackageVersion::peer_dependencies_meta`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct PeerDependencyMeta {
+    /// Whether the peer dependency may be absent.
+    #[serde(default)]
+    pub optional: bool,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageVersion {
     pub name: String,
     pub version: node_semver::Version,
+    #[serde(default)] // absent from the package.json of a local tarball
     pub dist: PackageDistribution,
     pub dependencies: Option<HashMap<String, String>>,
     pub dev_dependencies: Option<HashMap<String, String>>,
     pub peer_dependencies: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_dependencies_meta: Option<HashMap<String, PeerDependencyMeta>>,
+    /// Versions of the runtimes that the package supports, keyed by runtime (e.g. `node`).
+    ///
+    /// Some old packages have an array here, which is read as `None`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_engines"
+    )]
+    pub engines: Option<HashMap<String, String>>,
+    /// Why the version shouldn't be used anymore, set by `npm deprecate`.
+    ///
+    /// Some registries write `false` here for versions that aren't deprecated, which is read as
+    /// `None`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_deprecated"
+    )]
+    pub deprecated: Option<String>,
+}
+
+/// Deserialize [`PackageVersion::engines`], ignoring anything that isn't an object of strings.
+fn deserialize_engines<'de, D>(deserializer: D) -> Result<Option<HashMap<String, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(Value::Object(engines)) = Option::<Value>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let engines = engines
+        .into_iter()
+        .filter_map(|(runtime, range)| Some((runtime, range.as_str()?.to_string())))
+        .collect();
+    Ok(Some(engines))
+}
+
+/// Deserialize [`PackageVersion::deprecated`], ignoring anything that isn't a non-empty string.
+fn deserialize_deprecated<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(Value::String(message)) = Option::<Value>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    Ok(Some(message).filter(|message| !message.is_empty()))
 }
 
 impl PartialEq for PackageVersion {
@@ -31,24 +90,23 @@ impl PackageVersion {
         registry: &str,
     ) -> Result<Self, RegistryError> {
         let url = || format!("{registry}{name}/{tag}");
-        let network_error = |error| NetworkError { error, url: url() };
-
-        http_client
-            .run_with_permit(|client| {
-                client
-                    .get(url())
-                    .header(
-                        "accept",
-                        "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*",
-                    )
-                    .send()
+
+        let (in_flight, response) = http_client
+            .run_metadata_with_permit(|client| {
+                let in_flight = http_client.activity().start(url());
+                let request = client.get(url()).header(
+                    "accept",
+                    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*",
+                );
+                let response = http_client.authorize(&url(), request).send();
+                async move { (in_flight, response.await) }
             })
-            .await
-            .map_err(network_error)?
-            .json::<PackageVersion>()
-            .await
-            .map_err(network_error)?
-            .pipe(Ok)
+            .await;
+        let response = response.map_err(|error| RegistryError::from_request(url(), error))?;
+        let status = response.status();
+        let body = read_body(in_flight, response, &url()).await;
+        let body = check_status(status, body, &url(), || format!("{name}@{tag}"))?;
+        parse_body(&body, &url())
     }
 
     pub fn to_virtual_store_name(&self) -> String {
@@ -59,21 +117,48 @@ impl PackageVersion {
         self.dist.tarball.as_str()
     }
 
+    /// Derive the tarball URL from `registry` instead of trusting `dist.tarball`.
+    pub fn tarball_url_from_registry(&self, registry: &str) -> String {
+        let registry = registry.strip_suffix('/').unwrap_or(registry);
+        let name = &self.name;
+        let bare_name = name.rsplit_once('/').map_or(name.as_str(), |(_, bare_name)| bare_name);
+        let version = &self.version;
+        format!("{registry}/{name}/-/{bare_name}-{version}.tgz")
+    }
+
+    /// Iterate over the dependencies, optionally followed by the required (non-optional)
+    /// peer dependencies.
     pub fn dependencies(
         &self,
         with_peer_dependencies: bool,
     ) -> impl Iterator<Item = (&'_ str, &'_ str)> {
-        let dependencies = self.dependencies.iter().flatten();
-
-        let peer_dependencies = with_peer_dependencies
-            .then_some(&self.peer_dependencies)
-            .into_iter()
+        let dependencies = self
+            .dependencies
+            .iter()
             .flatten()
-            .flatten();
+            .map(|(name, version)| (name.as_str(), version.as_str()));
+
+        let peer_dependencies =
+            with_peer_dependencies.then(|| self.required_peer_dependencies()).into_iter().flatten();
+
+        dependencies.chain(peer_dependencies)
+    }
+
+    /// Whether `name` is marked as optional in `peerDependenciesMeta`.
+    pub fn is_optional_peer(&self, name: &str) -> bool {
+        self.peer_dependencies_meta
+            .as_ref()
+            .and_then(|meta| meta.get(name))
+            .map_or(false, |meta| meta.optional)
+    }
 
-        dependencies
-            .chain(peer_dependencies)
+    /// Iterate over the peer dependencies that are not marked as optional.
+    pub fn required_peer_dependencies(&self) -> impl Iterator<Item = (&'_ str, &'_ str)> {
+        self.peer_dependencies
+            .iter()
+            .flatten()
             .map(|(name, version)| (name.as_str(), version.as_str()))
+            .filter(|(name, _)| !self.is_optional_peer(name))
     }
 
     pub fn serialize(&self, save_exact: bool) -> String {
@@ -81,3 +166,97 @@ impl PackageVersion {
         format!("{0}{1}", prefix, self.version)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn package_version(name: &str, tarball: &str) -> PackageVersion {
+        PackageVersion {
+            name: name.to_string(),
+            version: node_semver::Version::parse("1.2.3").unwrap(),
+            dist: PackageDistribution { tarball: tarball.to_string(), ..Default::default() },
+            dependencies: None,
+            dev_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            engines: None,
+            deprecated: None,
+        }
+    }
+
+    #[test]
+    fn tarball_url_from_registry_should_point_at_registry() {
+        let unscoped = package_version("foo", "https://registry.npmjs.org/foo/-/foo-1.2.3.tgz");
+        assert_eq!(unscoped.as_tarball_url(), "https://registry.npmjs.org/foo/-/foo-1.2.3.tgz");
+        assert_eq!(
+            unscoped.tarball_url_from_registry("https://mirror.example.com/npm/"),
+            "https://mirror.example.com/npm/foo/-/foo-1.2.3.tgz",
+        );
+
+        let scoped =
+            package_version("@scope/bar", "https://registry.npmjs.org/@scope/bar/-/bar-1.2.3.tgz");
+        assert_eq!(
+            scoped.tarball_url_from_registry("https://mirror.example.com"),
+            "https://mirror.example.com/@scope/bar/-/bar-1.2.3.tgz",
+        );
+    }
+
+    #[test]
+    fn engines_should_ignore_malformed_values() {
+        let parse = |engines: &str| -> PackageVersion {
+            serde_json::from_str(&format!(
+                r#"{{
+                    "name": "foo",
+                    "version": "1.0.0",
+                    "dist": {{ "tarball": "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz" }},
+                    "engines": {engines}
+                }}"#,
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            parse(r#"{ "node": ">=18", "npm": 9 }"#).engines,
+            Some([("node".to_string(), ">=18".to_string())].into()),
+        );
+        assert_eq!(parse(r#"["node >=0.4"]"#).engines, None);
+    }
+
+    #[test]
+    fn deprecated_should_only_be_a_message() {
+        let parse = |deprecated: &str| -> PackageVersion {
+            serde_json::from_str(&format!(
+                r#"{{ "name": "foo", "version": "1.0.0", "deprecated": {deprecated} }}"#,
+            ))
+            .unwrap()
+        };
+        assert_eq!(parse(r#""use bar instead""#).deprecated.as_deref(), Some("use bar instead"));
+        assert_eq!(parse("false").deprecated, None);
+        assert_eq!(parse(r#""""#).deprecated, None);
+    }
+
+    #[test]
+    fn optional_peers_should_not_be_required() {
+        let version: PackageVersion = serde_json::from_str(
+            r#"{
+                "name": "foo",
+                "version": "1.0.0",
+                "dist": { "tarball": "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz" },
+                "peerDependencies": { "react": "^18.0.0", "typescript": "^5.0.0" },
+                "peerDependenciesMeta": { "typescript": { "optional": true } }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(version.is_optional_peer("typescript"));
+        assert!(!version.is_optional_peer("react"));
+        assert!(!version.is_optional_peer("lodash"));
+
+        let required: Vec<_> = version.required_peer_dependencies().collect();
+        assert_eq!(required, [("react", "^18.0.0")]);
+
+        let dependencies: Vec<_> = version.dependencies(true).collect();
+        assert_eq!(dependencies, [("react", "^18.0.0")]);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::Package;
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+
+/// On-disk cache of package metadata (packuments).
+///
+/// Each entry is a JSON file at `{cache_dir}/metadata/{registry}/{name}.json`, where `{registry}`
+/// is the host (and path) of the registry URL with `:` replaced by `+`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackumentCache {
+    root: PathBuf,
+}
+
+/// Error type of [`PackumentCache::clean`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Failed to remove {path:?}: {error}")]
+#[diagnostic(code(pacquet_registry::clean_packument_cache))]
+pub struct CleanPackumentCacheError {
+    pub path: PathBuf,
+    #[error(source)]
+    pub error: io::Error,
+}
+
+/// Error type of [`PackumentCache::write`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Failed to write {path:?}: {error}")]
+#[diagnostic(code(pacquet_registry::write_packument_cache))]
+pub struct WritePackumentCacheError {
+    pub path: PathBuf,
+    #[error(source)]
+    pub error: io::Error,
+}
+
+/// Error type of [`PackumentCache::verify`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Failed to read the packument cache: {_0}")]
+#[diagnostic(code(pacquet_registry::verify_packument_cache))]
+pub struct VerifyPackumentCacheError(#[error(source)] walkdir::Error);
+
+/// Result of [`PackumentCache::verify`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackumentCacheReport {
+    /// Entries that parse and are younger than the max age.
+    pub valid: Vec<PathBuf>,
+    /// Entries that parse but are older than the max age.
+    pub stale: Vec<PathBuf>,
+    /// Entries that can't be read or don't parse as package metadata.
+    pub invalid: Vec<PathBuf>,
+}
+
+impl PackumentCache {
+    /// Locate the packument cache inside `cache_dir`.
+    pub fn new(cache_dir: &Path) -> Self {
+        PackumentCache { root: cache_dir.join("metadata") }
+    }
+
+    /// Directory that contains every cached entry.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of the cached packument of `name` fetched from `registry`.
+    pub fn entry_path(&self, registry: &str, name: &str) -> PathBuf {
+        let registry = registry.split_once("://").map_or(registry, |(_, rest)| rest);
+        let registry = registry.trim_end_matches('/').replace(':', "+");
+        self.root.join(registry).join(format!("{name}.json"))
+    }
+
+    /// Read the cached packument of `name` fetched from `registry`, or `None` if there is no
+    /// usable entry.
+    pub fn read(&self, registry: &str, name: &str) -> Option<Package> {
+        let text = fs::read_to_string(self.entry_path(registry, name)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Cache the packument of `name` fetched from `registry`.
+    ///
+    /// The entry is written to a temporary file which then replaces the entry, so that a
+    /// concurrent [`read`](Self::read) never sees a partially written entry.
+    pub fn write(
+        &self,
+        registry: &str,
+        name: &str,
+        package: &Package,
+    ) -> Result<(), WritePackumentCacheError> {
+        let path = self.entry_path(registry, name);
+        let error = |error| WritePackumentCacheError { path: path.clone(), error };
+        let parent = path.parent().expect("entry path has a parent");
+        fs::create_dir_all(parent).map_err(error)?;
+        let mut file = NamedTempFile::new_in(parent).map_err(error)?;
+        serde_json::to_writer(&mut file, package).map_err(io::Error::from).map_err(error)?;
+        file.persist(&path).map_err(|persist_error| error(persist_error.error))?;
+        Ok(())
+    }
+
+    /// Remove every cached entry.
+    pub fn clean(&self) -> Result<(), CleanPackumentCacheError> {
+        match fs::remove_dir_all(&self.root) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(CleanPackumentCacheError { path: self.root.clone(), error })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that every cached entry parses and is not older than `max_age`.
+    pub fn verify(
+        &self,
+        max_age: Duration,
+    ) -> Result<PackumentCacheReport, VerifyPackumentCacheError> {
+        let mut report = PackumentCacheReport::default();
+        if !self.root.exists() {
+            return Ok(report);
+        }
+
+        for entry in WalkDir::new(&self.root).sort_by_file_name() {
+            let entry = entry.map_err(VerifyPackumentCacheError)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.into_path();
+            let Some(age) = read_entry(&path) else {
+                report.invalid.push(path);
+                continue;
+            };
+            if age > max_age {
+                report.stale.push(path);
+            } else {
+                report.valid.push(path);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Parse a cached entry and return its age, or `None` if it is unreadable.
+fn read_entry(path: &Path) -> Option<Duration> {
+    let text = fs::read_to_string(path).ok()?;
+    serde_json::from_str::<Package>(&text).ok()?;
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    const PACKUMENT: &str = r#"{ "name": "foo", "dist-tags": {}, "versions": {} }"#;
+
+    #[test]
+    fn entry_path() {
+        let cache = PackumentCache::new(Path::new("/cache"));
+        assert_eq!(
+            cache.entry_path("https://registry.npmjs.org/", "@scope/foo"),
+            Path::new("/cache/metadata/registry.npmjs.org/@scope/foo.json"),
+        );
+        assert_eq!(
+            cache.entry_path("http://localhost:4873/npm/", "foo"),
+            Path::new("/cache/metadata/localhost+4873/npm/foo.json"),
+        );
+    }
+
+    #[test]
+    fn write_then_read() {
+        let cache_dir = tempdir().unwrap();
+        let cache = PackumentCache::new(cache_dir.path());
+        assert!(cache.read("https://registry.npmjs.org/", "@scope/foo").is_none());
+
+        let package: Package = serde_json::from_str(PACKUMENT).unwrap();
+        cache.write("https://registry.npmjs.org/", "@scope/foo", &package).unwrap();
+        let cached = cache.read("https://registry.npmjs.org/", "@scope/foo").unwrap();
+        assert_eq!(cached.name, "foo");
+        assert!(cache.read("http://localhost:4873/", "@scope/foo").is_none());
+
+        eprintln!("CASE: corrupted entry");
+        fs::write(cache.entry_path("https://registry.npmjs.org/", "@scope/foo"), "{").unwrap();
+        assert!(cache.read("https://registry.npmjs.org/", "@scope/foo").is_none());
+    }
+
+    #[test]
+    fn verify() {
+        let cache_dir = tempdir().unwrap();
+        let cache = PackumentCache::new(cache_dir.path());
+        assert_eq!(cache.verify(Duration::ZERO).unwrap(), PackumentCacheReport::default());
+
+        let valid = cache.entry_path("https://registry.npmjs.org/", "foo");
+        let invalid = cache.entry_path("https://registry.npmjs.org/", "bar");
+        fs::create_dir_all(valid.parent().unwrap()).unwrap();
+        fs::write(&valid, PACKUMENT).unwrap();
+        fs::write(&invalid, "not json").unwrap();
+
+        eprintln!("CASE: fresh entries");
+        let report = cache.verify(Duration::from_secs(60 * 60)).unwrap();
+        assert_eq!(report.valid, [valid.clone()]);
+        assert_eq!(report.stale, [] as [PathBuf; 0]);
+        assert_eq!(report.invalid, [invalid.clone()]);
+
+        eprintln!("CASE: stale entries");
+        let report = cache.verify(Duration::ZERO).unwrap();
+        assert_eq!(report.valid, [] as [PathBuf; 0]);
+        assert_eq!(report.stale, [valid]);
+        assert_eq!(report.invalid, [invalid]);
+
+        eprintln!("CASE: clean");
+        cache.clean().unwrap();
+        assert!(!cache.root().exists());
+        cache.clean().expect("cleaning a missing cache is a no-op");
+    }
+}
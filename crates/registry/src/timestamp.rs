@@ -0,0 +1,169 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use std::{fmt, str::FromStr};
+
+/// Point in time with millisecond precision, e.g. when a version was published according to the
+/// `time` field of a packument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// Milliseconds since 1970-01-01T00:00:00Z.
+    millis: i64,
+}
+
+/// Error type of [`Timestamp::from_str`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Invalid date {input:?}")]
+#[diagnostic(
+    code(pacquet_registry::parse_timestamp),
+    help("Use a date (2023-01-01) or an ISO 8601 timestamp (2023-01-01T12:00:00.000Z)")
+)]
+pub struct ParseTimestampError {
+    #[error(not(source))]
+    pub input: String,
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    /// Parse a date (`YYYY-MM-DD`, which is midnight UTC) or an ISO 8601 timestamp
+    /// (`YYYY-MM-DDTHH:MM[:SS[.fff]]` followed by `Z` or an offset such as `+02:00`, UTC if
+    /// neither).
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse(input).ok_or_else(|| ParseTimestampError { input: input.to_string() })
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Format as `YYYY-MM-DDTHH:MM:SS.fffZ`, like the `time` field of a packument.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.millis.div_euclid(DAY);
+        let millis = self.millis.rem_euclid(DAY);
+        let (year, month, day) = civil_from_days(days);
+        let (hour, minute) = (millis / HOUR, millis % HOUR / MINUTE);
+        let (second, milli) = (millis % MINUTE / SECOND, millis % SECOND);
+        write!(f, "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}.{milli:03}Z")
+    }
+}
+
+const SECOND: i64 = 1000;
+const MINUTE: i64 = 60 * SECOND;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+fn parse(input: &str) -> Option<Timestamp> {
+    let (date, time) = match input.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (input, None),
+    };
+
+    let mut date = date.split('-');
+    let year = parse_digits(date.next()?, 4)?;
+    let month = parse_digits(date.next()?, 2)?;
+    let day = parse_digits(date.next()?, 2)?;
+    if date.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut millis = days_from_civil(year, month, day) * DAY;
+
+    let Some(time) = time else { return Some(Timestamp { millis }) };
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else if let Some(sign_index) = time.rfind(['+', '-']) {
+        let (time, offset) = time.split_at(sign_index);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = parse_digits(hours, 2)? * HOUR + parse_digits(minutes, 2)? * MINUTE;
+        (time, sign * offset)
+    } else {
+        (time, 0)
+    };
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.split(':');
+    let hour = parse_digits(time.next()?, 2)?;
+    let minute = parse_digits(time.next()?, 2)?;
+    let second = time.next().map_or(Some(0), |second| parse_digits(second, 2))?;
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let milli = match fraction {
+        "" => 0,
+        fraction if fraction.bytes().all(|byte| byte.is_ascii_digit()) => {
+            format!("{fraction:0<3}")[..3].parse().ok()?
+        }
+        _ => return None,
+    };
+
+    millis += hour * HOUR + minute * MINUTE + second * SECOND + milli - offset;
+    Some(Timestamp { millis })
+}
+
+/// Parse exactly `len` ASCII digits.
+fn parse_digits(text: &str, len: usize) -> Option<i64> {
+    if text.len() != len || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Number of days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_dates_and_timestamps() {
+        let case = |input: &str, expected: &str| {
+            eprintln!("CASE: {input:?}");
+            assert_eq!(input.parse::<Timestamp>().unwrap().to_string(), expected);
+        };
+        case("2023-01-01", "2023-01-01T00:00:00.000Z");
+        case("1970-01-01T00:00:00.000Z", "1970-01-01T00:00:00.000Z");
+        case("2015-03-24T00:12:24.039Z", "2015-03-24T00:12:24.039Z");
+        case("2024-02-29T23:59Z", "2024-02-29T23:59:00.000Z");
+        case("2024-02-29 23:59:01", "2024-02-29T23:59:01.000Z");
+        case("2023-01-01T01:30:00+02:00", "2022-12-31T23:30:00.000Z");
+        case("1969-12-31T23:59:59.5-00:30", "1970-01-01T00:29:59.500Z");
+    }
+
+    #[test]
+    fn should_reject_invalid_input() {
+        for input in ["", "2023", "2023-1-1", "2023-13-01", "2023-01-01T25:00Z", "yesterday"] {
+            eprintln!("CASE: {input:?}");
+            let error = input.parse::<Timestamp>().unwrap_err();
+            assert_eq!(error.input, input);
+        }
+    }
+
+    #[test]
+    fn should_order_chronologically() {
+        let parse = |input: &str| input.parse::<Timestamp>().unwrap();
+        assert!(parse("2022-12-31T23:59:59.999Z") < parse("2023-01-01"));
+        assert!(parse("2023-01-01T01:00:00+02:00") < parse("2023-01-01"));
+    }
+}
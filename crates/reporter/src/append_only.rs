@@ -0,0 +1,114 @@
+use crate::{write_line, Reporter};
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Reporter that writes one human readable line for each event, without cursor movements, so
+/// that the output can be captured by CI logs.
+///
+/// Warnings and errors are left to tracing, which already prints them.
+pub struct AppendOnlyReporter {
+    state: Mutex<State>,
+}
+
+struct State {
+    writer: Box<dyn Write + Send>,
+    resolved: usize,
+    downloaded: usize,
+    linked: usize,
+}
+
+impl AppendOnlyReporter {
+    /// Create a reporter that writes append-only lines to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        let state = State { writer: Box::new(writer), resolved: 0, downloaded: 0, linked: 0 };
+        AppendOnlyReporter { state: Mutex::new(state) }
+    }
+
+    /// Create a reporter that writes append-only lines to stderr.
+    pub fn stderr() -> Self {
+        AppendOnlyReporter::new(io::stderr())
+    }
+
+    /// Update the counts with `count`, then write the line returned by `line`.
+    fn write(&self, count: impl FnOnce(&mut State), line: impl FnOnce(&State) -> String) {
+        let mut state = self.state.lock().expect("lock the writer of the reporter");
+        count(&mut state);
+        let line = line(&state);
+        write_line(&mut *state.writer, line.as_bytes());
+    }
+
+    /// Count a package event and describe it along with the counts so far.
+    fn write_package(&self, action: &str, name: &str, version: &str, count: fn(&mut State)) {
+        self.write(count, |state| {
+            let State { resolved, downloaded, linked, .. } = state;
+            format!("{action} {name}@{version} (resolved {resolved}, downloaded {downloaded}, linked {linked})\n")
+        });
+    }
+}
+
+impl Reporter for AppendOnlyReporter {
+    fn resolution_started(&self) {
+        self.write(|_| {}, |_| "Resolving dependencies\n".to_string());
+    }
+
+    fn package_resolved(&self, name: &str, version: &str) {
+        self.write_package("Resolved", name, version, |state| state.resolved += 1);
+    }
+
+    fn tarball_downloaded(&self, name: &str, version: &str, _url: &str) {
+        self.write_package("Downloaded", name, version, |state| state.downloaded += 1);
+    }
+
+    fn package_linked(&self, name: &str, version: &str, _path: &Path) {
+        self.write_package("Linked", name, version, |state| state.linked += 1);
+    }
+
+    fn install_done(&self) {
+        self.write(
+            |_| {},
+            |State { resolved, downloaded, linked, .. }| {
+                format!("Done: resolved {resolved}, downloaded {downloaded}, linked {linked}\n")
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::SharedBuffer, Event};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn append_only_should_write_one_line_per_event_with_counts() {
+        let buffer = SharedBuffer::default();
+        let reporter = AppendOnlyReporter::new(buffer.clone());
+        let (name, version) = ("foo".to_string(), "1.0.0".to_string());
+        reporter.emit(Event::ResolutionStarted);
+        reporter.emit(Event::PackageResolved { name: name.clone(), version: version.clone() });
+        reporter.emit(Event::TarballDownloaded {
+            name: name.clone(),
+            version: version.clone(),
+            url: "https://registry.npmjs.org/foo/-/foo-1.0.0.tgz".to_string(),
+        });
+        reporter.emit(Event::PackageLinked { name, version, path: "node_modules/foo".into() });
+        reporter.emit(Event::Warning { message: "printed by tracing".to_string() });
+        reporter.emit(Event::InstallDone);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Resolving dependencies",
+                "Resolved foo@1.0.0 (resolved 1, downloaded 0, linked 0)",
+                "Downloaded foo@1.0.0 (resolved 1, downloaded 1, linked 0)",
+                "Linked foo@1.0.0 (resolved 1, downloaded 1, linked 1)",
+                "Done: resolved 1, downloaded 1, linked 1",
+            ],
+        );
+    }
+}
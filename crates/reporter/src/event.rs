@@ -0,0 +1,55 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Event emitted by the install pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// Dependency resolution has started.
+    ResolutionStarted,
+    /// A version of a package has been picked.
+    PackageResolved { name: String, version: String },
+    /// The tarball of a package is being downloaded. `size` is the `dist.unpackedSize` of the
+    /// package, if the registry announced it.
+    TarballDownloadStarted { name: String, version: String, size: Option<u64> },
+    /// The tarball of a package is available in the store.
+    TarballDownloaded { name: String, version: String, url: String },
+    /// The tarball of a package was already in the store, so it wasn't downloaded again. It is
+    /// followed by [`Event::TarballDownloaded`] like a downloaded tarball.
+    TarballReused { name: String, version: String },
+    /// A package that is being installed is deprecated, `message` is the reason given by its
+    /// authors.
+    Deprecated { name: String, version: String, message: String },
+    /// A package has been linked into a `node_modules` directory.
+    PackageLinked { name: String, version: String, path: PathBuf },
+    /// An entry of the virtual store that is no longer depended on has been removed.
+    PackageRemoved { path: PathBuf },
+    /// The installation has finished.
+    InstallDone,
+    /// Something may be wrong with the installation, but it goes on.
+    Warning { message: String },
+    /// The operation has failed.
+    Error { message: String },
+    /// A lifecycle script of a package has written a line.
+    #[serde(rename_all = "camelCase")]
+    ScriptOutput { package: String, name: String, stream: ScriptStream, chunk: String },
+    /// A lifecycle script of a package has exited. The exit code is `None` if it was killed by a
+    /// signal, or if it couldn't be run at all, in which case `error` tells why.
+    #[serde(rename_all = "camelCase")]
+    Script {
+        package: String,
+        name: String,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Stream of [`Event::ScriptOutput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptStream {
+    Stdout,
+    Stderr,
+}
@@ -0,0 +1,194 @@
+use crate::Reporter;
+use std::{fmt::Write, fs, path::PathBuf, sync::Mutex};
+
+/// Reporter that writes the results of the scripts as a JUnit XML file, for CI services to show
+/// them like test results.
+///
+/// Every script that exits is a test case named after its package, in a test suite named after
+/// the script. A script fails when it exits with a non-zero code, is killed by a signal, or
+/// can't be run at all. The
+/// file is written again after each script, so it is complete whenever the operation stops. The
+/// other events are discarded.
+pub struct JunitReporter {
+    path: PathBuf,
+    cases: Mutex<Vec<TestCase>>,
+}
+
+/// A script that has exited.
+struct TestCase {
+    package: String,
+    script: String,
+    exit_code: Option<i32>,
+    duration_ms: u64,
+    error: Option<String>,
+}
+
+impl JunitReporter {
+    /// Create a reporter that writes JUnit XML to the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JunitReporter { path: path.into(), cases: Mutex::default() }
+    }
+}
+
+impl Reporter for JunitReporter {
+    fn script_exited(
+        &self,
+        package: &str,
+        name: &str,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        error: Option<&str>,
+    ) {
+        let mut cases = self.cases.lock().expect("lock the test cases of the reporter");
+        cases.push(TestCase {
+            package: package.to_string(),
+            script: name.to_string(),
+            exit_code,
+            duration_ms,
+            error: error.map(str::to_string),
+        });
+        if let Err(error) = fs::write(&self.path, render(&cases)) {
+            // failing to report must not fail the operation being reported
+            eprintln!("Failed to write the JUnit report to {:?}: {error}", self.path);
+        }
+    }
+}
+
+/// Render `cases` as a JUnit XML document, with one test suite per script in the order that
+/// they first ran.
+fn render(cases: &[TestCase]) -> String {
+    let mut scripts: Vec<&str> = Vec::new();
+    for case in cases {
+        if !scripts.contains(&case.script.as_str()) {
+            scripts.push(&case.script);
+        }
+    }
+    let failures =
+        |cases: &[&TestCase]| cases.iter().filter(|case| case.exit_code != Some(0)).count();
+    let duration_ms = |cases: &[&TestCase]| cases.iter().map(|case| case.duration_ms).sum();
+
+    let all: Vec<&TestCase> = cases.iter().collect();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        r#"<testsuites name="pacquet" tests="{}" failures="{}" time="{}">"#,
+        all.len(),
+        failures(&all),
+        seconds(duration_ms(&all)),
+    )
+    .unwrap();
+    for script in scripts {
+        let suite: Vec<&TestCase> = cases.iter().filter(|case| case.script == script).collect();
+        writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" time="{}">"#,
+            escape(script),
+            suite.len(),
+            failures(&suite),
+            seconds(duration_ms(&suite)),
+        )
+        .unwrap();
+        for case in suite {
+            let TestCase { package, script, exit_code, duration_ms, error } = case;
+            let attributes = format!(
+                r#"name="{}" classname="{}" time="{}""#,
+                escape(package),
+                escape(script),
+                seconds(*duration_ms),
+            );
+            let message = match (exit_code, error) {
+                (Some(0), _) => {
+                    writeln!(xml, "    <testcase {attributes}/>").unwrap();
+                    continue;
+                }
+                (Some(code), _) => format!("Exited with code {code}"),
+                (None, Some(error)) => escape(&format!("Failed to run: {error}")),
+                (None, None) => "Killed by a signal".to_string(),
+            };
+            writeln!(xml, "    <testcase {attributes}>").unwrap();
+            writeln!(xml, r#"      <failure message="{message}"/>"#).unwrap();
+            writeln!(xml, "    </testcase>").unwrap();
+        }
+        writeln!(xml, "  </testsuite>").unwrap();
+    }
+    writeln!(xml, "</testsuites>").unwrap();
+    xml
+}
+
+/// Format milliseconds as the seconds of a `time` attribute.
+fn seconds(duration_ms: u64) -> String {
+    format!("{}.{:03}", duration_ms / 1000, duration_ms % 1000)
+}
+
+/// Escape `text` for an attribute value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Event;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+    use text_block_macros::text_block_fnl;
+
+    #[test]
+    fn junit_should_write_a_test_case_per_script() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("results.xml");
+        let reporter = JunitReporter::new(&path);
+        let script = |package: &str, name: &str, exit_code, duration_ms| Event::Script {
+            package: package.to_string(),
+            name: name.to_string(),
+            exit_code,
+            duration_ms,
+            error: None,
+        };
+        reporter.emit(Event::InstallDone);
+        assert!(!path.exists());
+        reporter.emit(script("a@1.0.0", "test", Some(0), 1234));
+        reporter.emit(script("<b>", "test", Some(1), 5));
+        reporter.emit(script("a@1.0.0", "lint", None, 20));
+        reporter.emit(Event::Script {
+            package: "c".to_string(),
+            name: "lint".to_string(),
+            exit_code: None,
+            duration_ms: 3,
+            error: Some("Failed to spawn command: <denied>".to_string()),
+        });
+
+        let received = fs::read_to_string(&path).unwrap();
+        let expected = text_block_fnl! {
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#
+            r#"<testsuites name="pacquet" tests="4" failures="3" time="1.262">"#
+            r#"  <testsuite name="test" tests="2" failures="1" time="1.239">"#
+            r#"    <testcase name="a@1.0.0" classname="test" time="1.234"/>"#
+            r#"    <testcase name="&lt;b&gt;" classname="test" time="0.005">"#
+            r#"      <failure message="Exited with code 1"/>"#
+            r#"    </testcase>"#
+            r#"  </testsuite>"#
+            r#"  <testsuite name="lint" tests="2" failures="2" time="0.023">"#
+            r#"    <testcase name="a@1.0.0" classname="lint" time="0.020">"#
+            r#"      <failure message="Killed by a signal"/>"#
+            r#"    </testcase>"#
+            r#"    <testcase name="c" classname="lint" time="0.003">"#
+            r#"      <failure message="Failed to run: Failed to spawn command: &lt;denied&gt;"/>"#
+            r#"    </testcase>"#
+            r#"  </testsuite>"#
+            r#"</testsuites>"#
+        };
+        assert_eq!(received, expected);
+    }
+}
@@ -0,0 +1,168 @@
+mod append_only;
+mod event;
+mod junit;
+mod ndjson;
+mod progress;
+mod throughput;
+
+pub use append_only::*;
+pub use event::*;
+pub use junit::*;
+pub use ndjson::*;
+pub use progress::*;
+pub use throughput::Throughput;
+
+use std::{io::Write, path::Path};
+
+/// Destination of the [`Event`]s emitted during an operation.
+///
+/// Every method does nothing by default, so an implementation only overrides the events it is
+/// interested in. [`emit`](Reporter::emit) dispatches an event to the method of its kind, and
+/// can be overridden instead to handle every event at once.
+pub trait Reporter: Send + Sync {
+    /// Dependency resolution has started.
+    fn resolution_started(&self) {}
+
+    /// Version `version` of package `name` has been picked.
+    fn package_resolved(&self, _name: &str, _version: &str) {}
+
+    /// The tarball of `name@version`, of `size` bytes once unpacked if known, is being downloaded.
+    fn tarball_download_started(&self, _name: &str, _version: &str, _size: Option<u64>) {}
+
+    /// The tarball of `name@version` downloaded from `url` is available in the store.
+    fn tarball_downloaded(&self, _name: &str, _version: &str, _url: &str) {}
+
+    /// The tarball of `name@version` was already in the store.
+    fn tarball_reused(&self, _name: &str, _version: &str) {}
+
+    /// `name@version` is deprecated because of `message`.
+    fn deprecated(&self, _name: &str, _version: &str, _message: &str) {}
+
+    /// `name@version` has been linked at `path`.
+    fn package_linked(&self, _name: &str, _version: &str, _path: &Path) {}
+
+    /// The entry of the virtual store at `path` has been removed.
+    fn package_removed(&self, _path: &Path) {}
+
+    /// The installation has finished.
+    fn install_done(&self) {}
+
+    /// Something may be wrong with the installation, but it goes on.
+    fn warn(&self, _message: &str) {}
+
+    /// The operation has failed.
+    fn error(&self, _message: &str) {}
+
+    /// The `name` script of `package` has written `chunk` to `stream`.
+    fn script_output(&self, _package: &str, _name: &str, _stream: ScriptStream, _chunk: &str) {}
+
+    /// The `name` script of `package` has exited with `exit_code` after `duration_ms`, or failed
+    /// to run because of `error`.
+    fn script_exited(
+        &self,
+        _package: &str,
+        _name: &str,
+        _exit_code: Option<i32>,
+        _duration_ms: u64,
+        _error: Option<&str>,
+    ) {
+    }
+
+    /// Report an event.
+    fn emit(&self, event: Event) {
+        match event {
+            Event::ResolutionStarted => self.resolution_started(),
+            Event::PackageResolved { name, version } => self.package_resolved(&name, &version),
+            Event::TarballDownloadStarted { name, version, size } => {
+                self.tarball_download_started(&name, &version, size)
+            }
+            Event::TarballDownloaded { name, version, url } => {
+                self.tarball_downloaded(&name, &version, &url)
+            }
+            Event::TarballReused { name, version } => self.tarball_reused(&name, &version),
+            Event::Deprecated { name, version, message } => {
+                self.deprecated(&name, &version, &message)
+            }
+            Event::PackageLinked { name, version, path } => {
+                self.package_linked(&name, &version, &path)
+            }
+            Event::PackageRemoved { path } => self.package_removed(&path),
+            Event::InstallDone => self.install_done(),
+            Event::Warning { message } => self.warn(&message),
+            Event::Error { message } => self.error(&message),
+            Event::ScriptOutput { package, name, stream, chunk } => {
+                self.script_output(&package, &name, stream, &chunk)
+            }
+            Event::Script { package, name, exit_code, duration_ms, error } => {
+                self.script_exited(&package, &name, exit_code, duration_ms, error.as_deref())
+            }
+        }
+    }
+}
+
+/// Human output only, which is handled by tracing. Events are discarded.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultReporter;
+
+impl Reporter for DefaultReporter {}
+
+/// No output at all. Events are discarded.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SilentReporter;
+
+impl Reporter for SilentReporter {}
+
+/// Write `line` and flush it.
+fn write_line(writer: &mut dyn Write, line: &[u8]) {
+    if let Err(error) = writer.write_all(line).and_then(|()| writer.flush()) {
+        // failing to report must not fail the operation being reported
+        eprintln!("Failed to write reporter event: {error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuffer(pub(crate) Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn emit_should_dispatch_to_the_method_of_the_event() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Reporter for Recorder {
+            fn package_resolved(&self, name: &str, version: &str) {
+                self.0.lock().unwrap().push(format!("resolved {name}@{version}"));
+            }
+
+            fn warn(&self, message: &str) {
+                self.0.lock().unwrap().push(format!("warn {message}"));
+            }
+        }
+
+        let recorder = Recorder::default();
+        recorder.emit(Event::ResolutionStarted);
+        recorder
+            .emit(Event::PackageResolved { name: "foo".to_string(), version: "1.0.0".to_string() });
+        recorder.emit(Event::Warning { message: "careful".to_string() });
+        recorder.emit(Event::InstallDone);
+        assert_eq!(*recorder.0.lock().unwrap(), ["resolved foo@1.0.0", "warn careful"]);
+    }
+}
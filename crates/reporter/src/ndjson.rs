@@ -0,0 +1,120 @@
+use crate::{write_line, Event, Reporter};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+/// Reporter that writes one JSON object per line for each event.
+pub struct NdjsonReporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl NdjsonReporter {
+    /// Create a reporter that writes newline delimited JSON to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        NdjsonReporter { writer: Mutex::new(Box::new(writer)) }
+    }
+
+    /// Create a reporter that writes newline delimited JSON to stdout.
+    pub fn stdout() -> Self {
+        NdjsonReporter::new(io::stdout())
+    }
+}
+
+impl Reporter for NdjsonReporter {
+    fn emit(&self, event: Event) {
+        let line = NdjsonLine {
+            timestamp: UNIX_EPOCH.elapsed().map_or(0, |x| x.as_millis() as u64),
+            event,
+        };
+        let mut line = serde_json::to_vec(&line).expect("serialize event");
+        line.push(b'\n');
+        let mut writer = self.writer.lock().expect("lock the writer of the reporter");
+        write_line(&mut *writer, &line);
+    }
+}
+
+/// A line of [`NdjsonReporter`].
+#[derive(Serialize)]
+struct NdjsonLine {
+    /// Milliseconds since the Unix epoch.
+    timestamp: u64,
+    #[serde(flatten)]
+    event: Event,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::SharedBuffer, ScriptStream};
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+
+    #[test]
+    fn ndjson_should_write_one_object_per_line() {
+        let buffer = SharedBuffer::default();
+        let reporter = NdjsonReporter::new(buffer.clone());
+        reporter.emit(Event::ResolutionStarted);
+        reporter
+            .emit(Event::PackageResolved { name: "foo".to_string(), version: "1.0.0".to_string() });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> =
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        dbg!(&lines);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "resolution-started");
+        assert!(lines[0]["timestamp"].is_u64());
+        assert_eq!(lines[1]["event"], "package-resolved");
+        assert_eq!(lines[1]["name"], "foo");
+        assert_eq!(lines[1]["version"], "1.0.0");
+    }
+
+    #[test]
+    fn ndjson_should_write_script_events_in_camel_case() {
+        let buffer = SharedBuffer::default();
+        let reporter = NdjsonReporter::new(buffer.clone());
+        let (package, name) = ("foo@1.0.0".to_string(), "postinstall".to_string());
+        reporter.emit(Event::ScriptOutput {
+            package: package.clone(),
+            name: name.clone(),
+            stream: ScriptStream::Stderr,
+            chunk: "oops".to_string(),
+        });
+        reporter.emit(Event::Script {
+            package,
+            name,
+            exit_code: Some(1),
+            duration_ms: 12,
+            error: None,
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let mut lines: Vec<Value> =
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        for line in &mut lines {
+            line.as_object_mut().unwrap().remove("timestamp");
+        }
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({
+                    "event": "script-output",
+                    "package": "foo@1.0.0",
+                    "name": "postinstall",
+                    "stream": "stderr",
+                    "chunk": "oops",
+                }),
+                serde_json::json!({
+                    "event": "script",
+                    "package": "foo@1.0.0",
+                    "name": "postinstall",
+                    "exitCode": 1,
+                    "durationMs": 12,
+                }),
+            ]
+        );
+    }
+}
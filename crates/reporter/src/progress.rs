@@ -0,0 +1,228 @@
+use crate::{
+    throughput::{format_bytes, format_duration},
+    write_line, Reporter, Throughput,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Reporter that keeps a single status line of the download phase up to date on a terminal, with
+/// the bytes downloaded so far, the throughput, and the estimated time left.
+///
+/// The line is rewritten in place each time a tarball download starts or completes, and ended
+/// when the installation is done. The other events are left to tracing, whose logs must go
+/// through [`ProgressReporter::log_writer`] so that they don't end up on the status line.
+///
+/// Clones share the same status line.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    writer: Box<dyn Write + Send>,
+    throughput: Throughput,
+    /// Sizes of the downloads in progress, by `name@version`.
+    sizes: HashMap<String, u64>,
+    shown: bool,
+}
+
+impl ProgressReporter {
+    /// Create a reporter that writes its status line to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        let state = State {
+            writer: Box::new(writer),
+            throughput: Throughput::default(),
+            sizes: HashMap::new(),
+            shown: false,
+        };
+        ProgressReporter { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Create a reporter that writes its status line to stderr.
+    pub fn stderr() -> Self {
+        ProgressReporter::new(io::stderr())
+    }
+
+    /// Create a writer of logs to the same output, which clears the status line before each log
+    /// and writes the status line again after it.
+    pub fn log_writer(&self) -> ProgressLogWriter {
+        ProgressLogWriter { state: Arc::clone(&self.state) }
+    }
+
+    /// Update the accounting with `update`, then rewrite the status line.
+    fn update(&self, update: impl FnOnce(&mut State, Instant)) {
+        let mut state = self.state.lock().expect("lock the state of the reporter");
+        update(&mut state, Instant::now());
+        if state.throughput.expected_bytes() == 0 {
+            return;
+        }
+        state.shown = true;
+        state.show();
+    }
+}
+
+impl State {
+    /// Rewrite the status line.
+    fn show(&mut self) {
+        let line = format!("\r\x1b[K{}", render(&self.throughput, Instant::now()));
+        write_line(&mut *self.writer, line.as_bytes());
+    }
+}
+
+/// Writer of logs that keeps them off the status line of a [`ProgressReporter`], see
+/// [`ProgressReporter::log_writer`].
+#[derive(Clone)]
+pub struct ProgressLogWriter {
+    state: Arc<Mutex<State>>,
+}
+
+impl Write for ProgressLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().expect("lock the state of the reporter");
+        if !state.shown {
+            return state.writer.write(buf);
+        }
+        state.writer.write_all(b"\r\x1b[K")?;
+        state.writer.write_all(buf)?;
+        // a log that isn't terminated yet is finished by the next write
+        if buf.ends_with(b"\n") {
+            state.show();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.lock().expect("lock the state of the reporter").writer.flush()
+    }
+}
+
+impl Reporter for ProgressReporter {
+    fn tarball_download_started(&self, name: &str, version: &str, size: Option<u64>) {
+        let Some(size) = size else { return };
+        self.update(|state, now| {
+            state.sizes.insert(format!("{name}@{version}"), size);
+            state.throughput.expect(size, now);
+        });
+    }
+
+    fn tarball_downloaded(&self, name: &str, version: &str, _url: &str) {
+        self.update(|state, now| {
+            if let Some(size) = state.sizes.remove(&format!("{name}@{version}")) {
+                state.throughput.record(size, now);
+            }
+        });
+    }
+
+    fn install_done(&self) {
+        let mut state = self.state.lock().expect("lock the state of the reporter");
+        if state.shown {
+            write_line(&mut *state.writer, b"\n");
+            state.shown = false;
+        }
+    }
+}
+
+/// Describe the progress of the download phase at `now`, e.g.
+/// `Downloaded 1.2 MB of 3.4 MB at 800.0 kB/s, 3s left`.
+fn render(throughput: &Throughput, now: Instant) -> String {
+    let downloaded = format_bytes(throughput.downloaded_bytes() as f64);
+    let expected = format_bytes(throughput.expected_bytes() as f64);
+    let mut line = format!("Downloaded {downloaded} of {expected}");
+    if let (Some(bytes_per_second), Some(eta)) =
+        (throughput.bytes_per_second(now), throughput.eta(now))
+    {
+        let bytes_per_second = format_bytes(bytes_per_second);
+        let eta = format_duration(eta);
+        line.push_str(&format!(" at {bytes_per_second}/s, {eta} left"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::SharedBuffer, Event};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn render_should_show_throughput_and_eta_once_known() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut throughput = Throughput::default();
+        throughput.expect(3_000_000, at(0));
+        assert_eq!(render(&throughput, at(0)), "Downloaded 0 B of 3.0 MB");
+        throughput.record(1_000_000, at(500));
+        assert_eq!(
+            render(&throughput, at(500)),
+            "Downloaded 1.0 MB of 3.0 MB at 2.0 MB/s, 1s left"
+        );
+    }
+
+    #[test]
+    fn progress_should_rewrite_the_status_line() {
+        let buffer = SharedBuffer::default();
+        let reporter = ProgressReporter::new(buffer.clone());
+        let started = |name: &str, size| Event::TarballDownloadStarted {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            size,
+        };
+        let downloaded = |name: &str| Event::TarballDownloaded {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            url: format!("https://registry.npmjs.org/{name}/-/{name}-1.0.0.tgz"),
+        };
+        reporter.emit(started("unknown", None));
+        reporter.emit(downloaded("unknown"));
+        reporter.emit(started("foo", Some(1_500)));
+        reporter.emit(downloaded("foo"));
+        reporter.emit(Event::InstallDone);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let updates: Vec<&str> = output.split("\r\x1b[K").collect();
+        dbg!(&updates);
+        assert_eq!(updates.len(), 3, "only the download of known size is shown");
+        assert_eq!(updates[1], "Downloaded 0 B of 1.5 kB");
+        assert!(updates[2].starts_with("Downloaded 1.5 kB of 1.5 kB"));
+        assert!(updates[2].ends_with('\n'));
+    }
+
+    #[test]
+    fn logs_should_be_kept_off_the_status_line() {
+        let buffer = SharedBuffer::default();
+        let reporter = ProgressReporter::new(buffer.clone());
+        let mut logs = reporter.log_writer();
+        let log = |logs: &mut ProgressLogWriter, message: &str| {
+            logs.write_all(message.as_bytes()).unwrap();
+        };
+
+        log(&mut logs, "before the downloads\n");
+        reporter.emit(Event::TarballDownloadStarted {
+            name: "foo".to_string(),
+            version: "1.0.0".to_string(),
+            size: Some(1_500),
+        });
+        log(&mut logs, "during the downloads\n");
+        reporter.emit(Event::InstallDone);
+        log(&mut logs, "after the installation\n");
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            [
+                "before the downloads\n",
+                "\r\x1b[KDownloaded 0 B of 1.5 kB",
+                "\r\x1b[Kduring the downloads\n",
+                "\r\x1b[KDownloaded 0 B of 1.5 kB",
+                "\n",
+                "after the installation\n",
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+/// Accounting of the bytes of the tarballs being downloaded, to estimate the throughput and the
+/// time left.
+///
+/// The bytes are the `dist.unpackedSize` announced by the registry, so a tarball whose size is
+/// unknown isn't accounted at all. The caller passes the current time to every method, which
+/// keeps the computation independent of the clock.
+#[derive(Debug, Default, Clone)]
+pub struct Throughput {
+    started: Option<Instant>,
+    expected: u64,
+    downloaded: u64,
+}
+
+impl Throughput {
+    /// A download of `bytes` has started at `now`.
+    pub fn expect(&mut self, bytes: u64, now: Instant) {
+        self.started.get_or_insert(now);
+        self.expected += bytes;
+    }
+
+    /// A download of `bytes` has completed at `now`.
+    pub fn record(&mut self, bytes: u64, now: Instant) {
+        self.started.get_or_insert(now);
+        self.downloaded += bytes;
+    }
+
+    /// Sum of the bytes of the downloads that have started.
+    pub fn expected_bytes(&self) -> u64 {
+        self.expected
+    }
+
+    /// Sum of the bytes of the downloads that have completed.
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.downloaded
+    }
+
+    /// Bytes downloaded per second since the first download started, or `None` until some bytes
+    /// have been downloaded.
+    pub fn bytes_per_second(&self, now: Instant) -> Option<f64> {
+        let elapsed = now.saturating_duration_since(self.started?).as_secs_f64();
+        (elapsed > 0.0 && self.downloaded > 0).then_some(self.downloaded as f64 / elapsed)
+    }
+
+    /// Time left to download the remaining expected bytes at the current throughput.
+    pub fn eta(&self, now: Instant) -> Option<Duration> {
+        let remaining = self.expected.saturating_sub(self.downloaded);
+        let bytes_per_second = self.bytes_per_second(now)?;
+        Some(Duration::from_secs_f64(remaining as f64 / bytes_per_second))
+    }
+}
+
+/// Format `bytes` with decimal units, e.g. `1.5 MB`.
+pub(crate) fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+    if bytes < 1000.0 {
+        return format!("{bytes:.0} B");
+    }
+    let mut value = bytes / 1000.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Format `duration` rounded up to the second, e.g. `1m 05s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+    match (seconds / 60, seconds % 60) {
+        (0, seconds) => format!("{seconds}s"),
+        (minutes, seconds) => format!("{minutes}m {seconds:02}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn throughput_and_eta_should_follow_the_downloaded_bytes() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut throughput = Throughput::default();
+
+        eprintln!("CASE: nothing downloaded yet");
+        throughput.expect(4_000, at(0));
+        throughput.expect(6_000, at(0));
+        assert_eq!(throughput.expected_bytes(), 10_000);
+        assert_eq!(throughput.bytes_per_second(at(0)), None);
+        assert_eq!(throughput.eta(at(500)), None);
+
+        eprintln!("CASE: first download completed");
+        throughput.record(4_000, at(2_000));
+        assert_eq!(throughput.downloaded_bytes(), 4_000);
+        assert_eq!(throughput.bytes_per_second(at(2_000)), Some(2_000.0));
+        assert_eq!(throughput.eta(at(2_000)), Some(Duration::from_secs(3)));
+
+        eprintln!("CASE: throughput slows down as time passes without new bytes");
+        assert_eq!(throughput.bytes_per_second(at(4_000)), Some(1_000.0));
+        assert_eq!(throughput.eta(at(4_000)), Some(Duration::from_secs(6)));
+
+        eprintln!("CASE: more downloads started and completed");
+        throughput.expect(10_000, at(4_000));
+        throughput.record(6_000, at(5_000));
+        assert_eq!(throughput.bytes_per_second(at(5_000)), Some(2_000.0));
+        assert_eq!(throughput.eta(at(5_000)), Some(Duration::from_secs(5)));
+
+        eprintln!("CASE: everything downloaded");
+        throughput.record(10_000, at(10_000));
+        assert_eq!(throughput.bytes_per_second(at(10_000)), Some(2_000.0));
+        assert_eq!(throughput.eta(at(10_000)), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn format_bytes_and_durations() {
+        assert_eq!(format_bytes(999.0), "999 B");
+        assert_eq!(format_bytes(1_500.0), "1.5 kB");
+        assert_eq!(format_bytes(2_345_678.0), "2.3 MB");
+        assert_eq!(format_bytes(7_000_000_000.0), "7.0 GB");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_millis(2_100)), "3s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{FileHash, StoreDir};
+use crate::{FileHash, FileType, StoreDir};
 use derive_more::{Display, Error};
 use miette::Diagnostic;
 use pacquet_fs::{ensure_file, file_mode::EXEC_MODE, EnsureFileError};
@@ -9,8 +9,7 @@ impl StoreDir {
     /// Path to a file in the store directory.
     pub fn cas_file_path(&self, hash: FileHash, executable: bool) -> PathBuf {
         let hex = format!("{hash:x}");
-        let suffix = if executable { "-exec" } else { "" };
-        self.file_path_by_hex_str(&hex, suffix)
+        self.file_path_by_hex_str(&hex, FileType::of_package_file(executable).suffix())
     }
 }
 
@@ -38,6 +37,7 @@ impl StoreDir {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::StoreLayout;
 
     #[test]
     fn cas_file_path() {
@@ -63,4 +63,26 @@ mod tests {
             "STORE_DIR/v3/files/30/9ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f-exec",
         );
     }
+
+    #[test]
+    fn write_cas_file_should_agree_with_the_layout() {
+        for layout in [StoreLayout::TwoChars, StoreLayout::TwoByTwoChars] {
+            eprintln!("LAYOUT: {layout:?}");
+            let root = tempfile::tempdir().unwrap();
+            StoreDir::new(root.path()).init_layout(layout).unwrap();
+
+            let store_dir = StoreDir::new(root.path());
+            let (file_path, file_hash) = store_dir.write_cas_file(b"hello world", false).unwrap();
+            assert_eq!(file_path, store_dir.cas_file_path(file_hash, false));
+            assert_eq!(std::fs::read(&file_path).unwrap(), b"hello world");
+
+            let files_dir = root.path().join("v3/files");
+            let dirs = file_path.parent().unwrap().strip_prefix(&files_dir).unwrap();
+            let expected_dirs: PathBuf = match layout {
+                StoreLayout::TwoChars => "30".into(),
+                StoreLayout::TwoByTwoChars => ["30", "9e"].iter().collect(),
+            };
+            assert_eq!(dirs, expected_dirs);
+        }
+    }
 }
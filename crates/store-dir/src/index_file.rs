@@ -1,10 +1,20 @@
-use crate::StoreDir;
+use crate::{FileType, StoreDir};
+use base64::{engine::general_purpose::STANDARD as BASE64_STD, Engine};
 use derive_more::{Display, Error};
 use miette::Diagnostic;
-use pacquet_fs::{ensure_file, EnsureFileError};
+use pacquet_fs::{ensure_file, file_mode, EnsureFileError};
 use serde::{Deserialize, Serialize};
 use ssri::{Algorithm, Integrity};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// How long, in milliseconds, a file of the store may have been modified after it was last
+/// checked before its content is hashed again, the same as pnpm.
+const CHECKED_AT_TOLERANCE_MS: u128 = 100;
 
 impl StoreDir {
     /// Path to an index file of a tarball.
@@ -14,7 +24,7 @@ impl StoreDir {
             matches!(algorithm, Algorithm::Sha512 | Algorithm::Sha1),
             "Only Sha1 and Sha512 are supported. {algorithm} isn't",
         ); // TODO: propagate this error
-        self.file_path_by_hex_str(&hex, "-index.json")
+        self.file_path_by_hex_str(&hex, FileType::Index.suffix())
     }
 }
 
@@ -58,10 +68,128 @@ impl StoreDir {
     }
 }
 
+impl StoreDir {
+    /// Read the index file of a tarball from the store directory.
+    ///
+    /// Return `None` if the index file doesn't exist or can't be parsed.
+    pub fn read_index_file(&self, integrity: &Integrity) -> Option<PackageFilesIndex> {
+        // `Integrity::to_hex` panics on malformed digests, those can't be in the store anyway.
+        let hash = integrity.hashes.first()?;
+        BASE64_STD.decode(&hash.digest).ok()?;
+        let file_path = self.index_file_path(integrity);
+        let index_content = fs::read_to_string(file_path).ok()?;
+        serde_json::from_str(&index_content).ok()
+    }
+}
+
+impl PackageFilesIndex {
+    /// Map each file in the index to its path in the store directory.
+    ///
+    /// Return `None` if any of the files is missing from the store directory.
+    pub fn cas_paths(&self, store_dir: &StoreDir) -> Option<HashMap<String, PathBuf>> {
+        self.files
+            .iter()
+            .map(|(entry_path, file_info)| {
+                let file_path = file_info.cas_file_path(store_dir)?;
+                file_path.is_file().then(|| (entry_path.clone(), file_path))
+            })
+            .collect()
+    }
+}
+
+impl PackageFilesIndex {
+    /// Like [`cas_paths`](Self::cas_paths), but the files are also checked, like pnpm does with
+    /// `verify-store-integrity`.
+    ///
+    /// A file whose size differs from the index is corrupt. A file that was modified after it was
+    /// last [checked](PackageFileInfo::checked_at) is hashed again and compared with its integrity.
+    /// The other files are trusted without being read. The corrupt files are removed from the
+    /// store, so that downloading the tarball again writes them anew, and `None` is returned.
+    pub fn verified_cas_paths(&self, store_dir: &StoreDir) -> Option<HashMap<String, PathBuf>> {
+        let mut cas_paths = HashMap::with_capacity(self.files.len());
+        let mut intact = true;
+        for (entry_path, file_info) in &self.files {
+            let file_path = file_info.cas_file_path(store_dir)?;
+            let metadata = fs::metadata(&file_path).ok().filter(|metadata| metadata.is_file())?;
+            if !file_info.verify(&file_path, &metadata) {
+                tracing::warn!(target: "pacquet::store", ?file_path, "Remove corrupt file from the store");
+                fs::remove_file(&file_path).ok();
+                intact = false;
+                continue;
+            }
+            cas_paths.insert(entry_path.clone(), file_path);
+        }
+        intact.then_some(cas_paths)
+    }
+}
+
+impl PackageFileInfo {
+    /// Whether the file of the store at `file_path`, with `metadata`, still matches this entry.
+    fn verify(&self, file_path: &Path, metadata: &fs::Metadata) -> bool {
+        if self.size.is_some_and(|size| size != metadata.len()) {
+            return false;
+        }
+        let modified_at = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_millis());
+        if let (Some(checked_at), Some(modified_at)) = (self.checked_at, modified_at) {
+            if modified_at <= checked_at + CHECKED_AT_TOLERANCE_MS {
+                return true;
+            }
+        }
+        #[cfg(test)]
+        tests::HASHED_FILES.with(|count| count.set(count.get() + 1));
+        let Ok(integrity) = self.integrity.parse::<Integrity>() else { return false };
+        fs::read(file_path).map_or(false, |buffer| integrity.check(buffer).is_ok())
+    }
+}
+
+impl PackageFileInfo {
+    /// Path of the file in the store directory, whether it exists or not.
+    ///
+    /// Return `None` if the integrity can't be parsed.
+    pub fn cas_file_path(&self, store_dir: &StoreDir) -> Option<PathBuf> {
+        let integrity: Integrity = self.integrity.parse().ok()?;
+        let (_, hex) = integrity.to_hex();
+        let suffix = if file_mode::is_all_exec(self.mode) { "-exec" } else { "" };
+        Some(store_dir.file_path_by_hex_str(&hex, suffix))
+    }
+}
+
+impl PackageFilesIndex {
+    /// Compare the files of a package that was imported into `package_dir` with the index.
+    ///
+    /// Return the paths (relative to `package_dir`, sorted) of the files that are missing or whose
+    /// content no longer matches their integrity.
+    pub fn modified_files(&self, package_dir: &Path) -> Vec<String> {
+        let mut modified_files: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(entry_path, file_info)| {
+                let Ok(integrity) = file_info.integrity.parse::<Integrity>() else { return true };
+                fs::read(package_dir.join(entry_path))
+                    .map_or(true, |buffer| integrity.check(buffer).is_err())
+            })
+            .map(|(entry_path, _)| entry_path.clone())
+            .collect();
+        modified_files.sort();
+        modified_files
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pretty_assertions::assert_eq;
     use ssri::IntegrityOpts;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// How many files [`PackageFileInfo::verify`] has hashed on this thread.
+        pub(super) static HASHED_FILES: Cell<usize> = Cell::new(0);
+    }
 
     #[test]
     fn index_file_path() {
@@ -73,4 +201,121 @@ mod tests {
         let expected: PathBuf = expected.split('/').collect();
         assert_eq!(&received, &expected);
     }
+
+    #[test]
+    fn read_index_file_and_cas_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let store_dir = StoreDir::new(root.path());
+        let tarball_integrity =
+            IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(b"TARBALL CONTENT").result();
+
+        eprintln!("CASE: no index file");
+        assert!(store_dir.read_index_file(&tarball_integrity).is_none());
+
+        let (file_path, file_hash) = store_dir.write_cas_file(b"FILE CONTENT", false).unwrap();
+        let index = PackageFilesIndex {
+            files: [(
+                "index.js".to_string(),
+                PackageFileInfo {
+                    checked_at: None,
+                    integrity: format!("sha512-{}", BASE64_STD.encode(file_hash)),
+                    mode: 0o644,
+                    size: Some(12),
+                },
+            )]
+            .into(),
+        };
+        store_dir.write_index_file(&tarball_integrity, &index).unwrap();
+
+        eprintln!("CASE: every file is present");
+        let cas_paths = store_dir
+            .read_index_file(&tarball_integrity)
+            .expect("read index file")
+            .cas_paths(&store_dir)
+            .expect("find every file in the store");
+        assert_eq!(cas_paths, [("index.js".to_string(), file_path.clone())].into());
+
+        eprintln!("CASE: a file is missing");
+        fs::remove_file(&file_path).unwrap();
+        assert!(store_dir
+            .read_index_file(&tarball_integrity)
+            .expect("read index file")
+            .cas_paths(&store_dir)
+            .is_none());
+    }
+
+    #[test]
+    fn modified_files() {
+        let package_dir = tempfile::tempdir().unwrap();
+        let file_info = |content: &[u8]| PackageFileInfo {
+            checked_at: None,
+            integrity: IntegrityOpts::new()
+                .algorithm(Algorithm::Sha512)
+                .chain(content)
+                .result()
+                .to_string(),
+            mode: 0o644,
+            size: Some(content.len() as u64),
+        };
+        let index = PackageFilesIndex {
+            files: [
+                ("index.js".to_string(), file_info(b"INDEX")),
+                ("lib/a.js".to_string(), file_info(b"A")),
+                ("lib/b.js".to_string(), file_info(b"B")),
+            ]
+            .into(),
+        };
+        fs::write(package_dir.path().join("index.js"), "INDEX").unwrap();
+        fs::create_dir(package_dir.path().join("lib")).unwrap();
+        fs::write(package_dir.path().join("lib/a.js"), "A").unwrap();
+        fs::write(package_dir.path().join("lib/b.js"), "B").unwrap();
+
+        eprintln!("CASE: unmodified");
+        assert_eq!(index.modified_files(package_dir.path()), Vec::<String>::new());
+
+        eprintln!("CASE: changed and missing files");
+        fs::write(package_dir.path().join("lib/a.js"), "TAMPERED").unwrap();
+        fs::remove_file(package_dir.path().join("index.js")).unwrap();
+        assert_eq!(index.modified_files(package_dir.path()), ["index.js", "lib/a.js"]);
+    }
+
+    #[test]
+    fn verified_cas_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let store_dir = StoreDir::new(root.path());
+        let (file_path, file_hash) = store_dir.write_cas_file(b"FILE CONTENT", false).unwrap();
+        let file_info = |checked_at| PackageFileInfo {
+            checked_at,
+            integrity: format!("sha512-{}", BASE64_STD.encode(file_hash)),
+            mode: 0o644,
+            size: Some(12),
+        };
+        let index = |checked_at| PackageFilesIndex {
+            files: [("index.js".to_string(), file_info(checked_at))].into(),
+        };
+        let now = UNIX_EPOCH.elapsed().unwrap().as_millis();
+        let hashed_files = || HASHED_FILES.with(Cell::get);
+        let expected: HashMap<_, _> = [("index.js".to_string(), file_path.clone())].into();
+
+        eprintln!("CASE: checked after the last modification, trusted without hashing");
+        let before = hashed_files();
+        assert_eq!(index(Some(now)).verified_cas_paths(&store_dir), Some(expected.clone()));
+        assert_eq!(hashed_files(), before);
+
+        eprintln!("CASE: modified since the last check, hashed again");
+        assert_eq!(index(Some(0)).verified_cas_paths(&store_dir), Some(expected.clone()));
+        assert_eq!(index(None).verified_cas_paths(&store_dir), Some(expected));
+        assert_eq!(hashed_files(), before + 2);
+
+        eprintln!("CASE: corrupt file with the same size");
+        fs::write(&file_path, b"FILE CORRUPT").unwrap();
+        assert_eq!(index(Some(now)).cas_paths(&store_dir).map(|paths| paths.len()), Some(1));
+        assert_eq!(index(Some(0)).verified_cas_paths(&store_dir), None);
+        assert!(!file_path.exists(), "the corrupt file should be removed");
+
+        eprintln!("CASE: corrupt file with another size");
+        fs::write(&file_path, b"CORRUPT").unwrap();
+        assert_eq!(index(Some(now + 60_000)).verified_cas_paths(&store_dir), None);
+        assert!(!file_path.exists(), "the corrupt file should be removed");
+    }
 }
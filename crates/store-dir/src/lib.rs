@@ -2,8 +2,12 @@ mod cas_file;
 mod index_file;
 mod prune;
 mod store_dir;
+mod store_path;
+mod verify;
 
 pub use cas_file::*;
 pub use index_file::*;
 pub use prune::*;
 pub use store_dir::*;
+pub use store_path::*;
+pub use verify::*;
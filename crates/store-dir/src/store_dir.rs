@@ -1,24 +1,97 @@
-use derive_more::From;
+use derive_more::{Display, Error};
+use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
 use sha2::{digest, Sha512};
-use std::path::{self, PathBuf};
+use std::{
+    fs, io,
+    path::{self, PathBuf},
+    sync::OnceLock,
+};
 
 /// Content hash of a file.
 pub type FileHash = digest::Output<Sha512>;
 
+/// How the files of a store are spread over the subdirectories of `{store}/v3/files`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StoreLayout {
+    /// `files/ab/cdef…`: 256 directories, the layout of pnpm.
+    #[default]
+    #[serde(rename = "2")]
+    TwoChars,
+    /// `files/ab/cd/ef…`: 65536 directories, for stores that are too big for the other layout.
+    #[serde(rename = "2+2")]
+    TwoByTwoChars,
+}
+
+/// Content of `{store}/v3/store.json`, which declares the layout of the store.
+///
+/// Stores without this file have the default layout.
+#[derive(Debug, Deserialize, Serialize)]
+struct StoreConfig {
+    layout: StoreLayout,
+}
+
+/// Error type of [`StoreDir::init_layout`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum InitStoreLayoutError {
+    #[display("Failed to read the layout of the store from {path:?}: {error}")]
+    #[diagnostic(code(pacquet_store_dir::read_store_layout))]
+    ReadConfig {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse the layout of the store from {path:?}: {error}")]
+    #[diagnostic(
+        code(pacquet_store_dir::parse_store_layout),
+        help("The layout is either \"2\" or \"2+2\", fix the file or remove the store")
+    )]
+    ParseConfig {
+        path: PathBuf,
+        #[error(source)]
+        error: serde_json::Error,
+    },
+
+    #[display("Failed to write the layout of the store to {path:?}: {error}")]
+    #[diagnostic(code(pacquet_store_dir::init_store_layout))]
+    WriteConfig {
+        path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+}
+
 /// Represent a store directory.
 ///
 /// * The store directory stores all files that were acquired by installing packages with pacquet or pnpm.
 /// * The files in `node_modules` directories are hardlinks or reflinks to the files in the store directory.
 /// * The store directory can and often act as a global shared cache of all installation of different workspaces.
 /// * The location of the store directory can be customized by `store-dir` field.
-#[derive(Debug, PartialEq, Eq, From, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct StoreDir {
     /// Path to the root of the store directory from which all sub-paths are derived.
     ///
     /// Consumer of this struct should interact with the sub-paths instead of this path.
     root: PathBuf,
+
+    /// Layout of the store, read from the store the first time it is needed.
+    #[serde(skip)]
+    layout: OnceLock<StoreLayout>,
+}
+
+impl From<PathBuf> for StoreDir {
+    fn from(root: PathBuf) -> Self {
+        StoreDir { root, layout: OnceLock::new() }
+    }
+}
+
+impl PartialEq for StoreDir {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
 }
 
 impl StoreDir {
@@ -37,26 +110,96 @@ impl StoreDir {
         self.root.join("v3")
     }
 
+    /// Get `{store}/v3/store.json`.
+    fn config_path(&self) -> PathBuf {
+        self.v3().join("store.json")
+    }
+
     /// The directory that contains all files from the once-installed packages.
-    fn files(&self) -> PathBuf {
+    pub(crate) fn files(&self) -> PathBuf {
         self.v3().join("files")
     }
 
+    /// Layout declared by `{store}/v3/store.json`, if any.
+    fn declared_layout(&self) -> Result<Option<StoreLayout>, InitStoreLayoutError> {
+        let path = self.config_path();
+        let config = match fs::read_to_string(&path) {
+            Ok(config) => config,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(InitStoreLayoutError::ReadConfig { path, error }),
+        };
+        let StoreConfig { layout } = serde_json::from_str(&config)
+            .map_err(|error| InitStoreLayoutError::ParseConfig { path, error })?;
+        Ok(Some(layout))
+    }
+
+    /// Layout of the store: the one declared by the store, the default one otherwise.
+    ///
+    /// A declaration that can't be read is reported by [`init_layout`](Self::init_layout), it is
+    /// only warned about here.
+    pub fn layout(&self) -> StoreLayout {
+        *self.layout.get_or_init(|| {
+            self.declared_layout()
+                .unwrap_or_else(|error| {
+                    tracing::warn!(target: "pacquet::store", %error, "Use the default layout");
+                    None
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Settle the layout of the store and return it.
+    ///
+    /// A new store gets the `preferred` layout, which is written into the store so that every
+    /// later read and write agrees with it. The layout of an existing store never changes: it
+    /// is the declared one, or the default one for the stores that declare none. A warning is
+    /// logged when it isn't the `preferred` one.
+    pub fn init_layout(&self, preferred: StoreLayout) -> Result<StoreLayout, InitStoreLayoutError> {
+        let layout = match self.declared_layout()? {
+            Some(layout) => layout,
+            None if preferred == StoreLayout::default() || self.files().exists() => {
+                StoreLayout::default()
+            }
+            None => {
+                let path = self.config_path();
+                let config = serde_json::to_string(&StoreConfig { layout: preferred })
+                    .expect("serialize store config");
+                fs::create_dir_all(self.v3())
+                    .and_then(|()| fs::write(&path, config))
+                    .map_err(|error| InitStoreLayoutError::WriteConfig { path, error })?;
+                preferred
+            }
+        };
+        if layout != preferred {
+            tracing::warn!(
+                target: "pacquet::store",
+                store_dir = ?self.root,
+                ?layout,
+                ?preferred,
+                "The existing store keeps its layout",
+            );
+        }
+        Ok(*self.layout.get_or_init(|| layout))
+    }
+
     /// Path to a file in the store directory.
     ///
     /// **Parameters:**
-    /// * `head` is the first 2 hexadecimal digit of the file address.
+    /// * `head` is the directories of the file address: its first 2 hexadecimal digits, or its
+    ///   first 2 and next 2 depending on the [layout](StoreLayout).
     /// * `tail` is the rest of the address and an optional suffix.
-    fn file_path_by_head_tail(&self, head: &str, tail: &str) -> PathBuf {
-        self.files().join(head).join(tail)
+    fn file_path_by_head_tail(&self, head: &[&str], tail: &str) -> PathBuf {
+        head.iter().fold(self.files(), |path, dir| path.join(dir)).join(tail)
     }
 
     /// Path to a file in the store directory.
     pub(crate) fn file_path_by_hex_str(&self, hex: &str, suffix: &'static str) -> PathBuf {
-        let head = &hex[..2];
-        let middle = &hex[2..];
+        let (head, middle) = match self.layout() {
+            StoreLayout::TwoChars => (vec![&hex[..2]], &hex[2..]),
+            StoreLayout::TwoByTwoChars => (vec![&hex[..2], &hex[2..4]], &hex[4..]),
+        };
         let tail = format!("{middle}{suffix}");
-        self.file_path_by_head_tail(head, &tail)
+        self.file_path_by_head_tail(&head, &tail)
     }
 
     /// Path to the temporary directory inside the store.
@@ -70,18 +213,80 @@ mod tests {
     use super::*;
     use pipe_trait::Pipe;
     use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
 
     #[test]
     fn file_path_by_head_tail() {
         let received = "/home/user/.local/share/pnpm/store"
             .pipe(StoreDir::new)
-            .file_path_by_head_tail("3e", "f722d37b016c63ac0126cfdcec");
+            .file_path_by_head_tail(&["3e"], "f722d37b016c63ac0126cfdcec");
         let expected = PathBuf::from(
             "/home/user/.local/share/pnpm/store/v3/files/3e/f722d37b016c63ac0126cfdcec",
         );
         assert_eq!(&received, &expected);
     }
 
+    #[test]
+    fn file_path_by_hex_str_should_follow_the_layout() {
+        let hex = "3ef722d37b016c63ac0126cfdcec";
+        let store_dir = StoreDir::new("STORE_DIR");
+        assert_eq!(store_dir.layout(), StoreLayout::TwoChars);
+        let expected: PathBuf =
+            "STORE_DIR/v3/files/3e/f722d37b016c63ac0126cfdcec-exec".split('/').collect();
+        assert_eq!(store_dir.file_path_by_hex_str(hex, "-exec"), expected);
+
+        let store_dir = StoreDir::new("STORE_DIR");
+        store_dir.layout.set(StoreLayout::TwoByTwoChars).unwrap();
+        let expected: PathBuf =
+            "STORE_DIR/v3/files/3e/f7/22d37b016c63ac0126cfdcec-exec".split('/').collect();
+        assert_eq!(store_dir.file_path_by_hex_str(hex, "-exec"), expected);
+    }
+
+    #[test]
+    fn init_layout_should_only_apply_to_new_stores() {
+        let dir = tempdir().unwrap();
+
+        eprintln!("CASE: a new store gets the preferred layout");
+        let store_dir = StoreDir::new(dir.path().join("new"));
+        assert_eq!(
+            store_dir.init_layout(StoreLayout::TwoByTwoChars).unwrap(),
+            StoreLayout::TwoByTwoChars
+        );
+        let reopened = StoreDir::new(dir.path().join("new"));
+        assert_eq!(reopened.layout(), StoreLayout::TwoByTwoChars);
+        assert_eq!(
+            reopened.init_layout(StoreLayout::TwoChars).unwrap(),
+            StoreLayout::TwoByTwoChars
+        );
+
+        eprintln!("CASE: an existing store without a declared layout keeps the default one");
+        let store_dir = StoreDir::new(dir.path().join("old"));
+        fs::create_dir_all(store_dir.files().join("3e")).unwrap();
+        assert_eq!(
+            store_dir.init_layout(StoreLayout::TwoByTwoChars).unwrap(),
+            StoreLayout::TwoChars
+        );
+        assert!(!store_dir.config_path().exists());
+
+        eprintln!("CASE: the default layout isn't written");
+        let store_dir = StoreDir::new(dir.path().join("default"));
+        assert_eq!(store_dir.init_layout(StoreLayout::TwoChars).unwrap(), StoreLayout::TwoChars);
+        assert!(!store_dir.config_path().exists());
+    }
+
+    #[test]
+    fn init_layout_should_reject_corrupt_declaration() {
+        let dir = tempdir().unwrap();
+        let store_dir = StoreDir::new(dir.path());
+        fs::create_dir_all(store_dir.v3()).unwrap();
+        fs::write(store_dir.config_path(), r#"{ "layout": "3" }"#).unwrap();
+
+        let error = store_dir.init_layout(StoreLayout::TwoByTwoChars).unwrap_err();
+        dbg!(&error);
+        assert!(matches!(error, InitStoreLayoutError::ParseConfig { .. }));
+        assert_eq!(store_dir.layout(), StoreLayout::TwoChars);
+    }
+
     #[test]
     fn tmp() {
         let received = StoreDir::new("/home/user/.local/share/pnpm/store").tmp();
@@ -0,0 +1,154 @@
+use crate::{StoreDir, StoreLayout};
+use ssri::{Algorithm, Integrity};
+use std::path::{Path, PathBuf};
+
+/// Kind of a file in `{store}/v3/files`, which is told by the suffix of its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    /// File of a package, addressed by the hash of its content.
+    NonExec,
+    /// Executable file of a package, addressed by the hash of its content.
+    Exec,
+    /// Index file of a tarball, addressed by the integrity of the tarball.
+    Index,
+}
+
+impl FileType {
+    /// Suffix of the file name after the hexadecimal address.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            FileType::NonExec => "",
+            FileType::Exec => "-exec",
+            FileType::Index => "-index.json",
+        }
+    }
+
+    /// Type of a file of a package.
+    pub fn of_package_file(executable: bool) -> Self {
+        if executable {
+            FileType::Exec
+        } else {
+            FileType::NonExec
+        }
+    }
+}
+
+impl StoreDir {
+    /// Path to the file of `file_type` whose address is the strongest hash of `integrity`.
+    ///
+    /// The directories of the path follow the [layout](StoreLayout) of the store.
+    pub fn store_path(&self, integrity: &Integrity, file_type: FileType) -> PathBuf {
+        let (_, hex) = integrity.to_hex();
+        self.file_path_by_hex_str(&hex, file_type.suffix())
+    }
+
+    /// Inverse of [`store_path`](Self::store_path): the algorithm, the hexadecimal hash, and the
+    /// type of the file at `path`.
+    ///
+    /// Return `None` for the paths outside of `{store}/v3/files`, the paths whose directories
+    /// don't follow the layout of the store, and the file names that aren't a hash of a known
+    /// algorithm. The algorithm is told by the length of the hash.
+    pub fn integrity_from_path(&self, path: &Path) -> Option<(Algorithm, String, FileType)> {
+        let relative = path.strip_prefix(self.files()).ok()?;
+        let mut components: Vec<&str> =
+            relative.iter().map(|component| component.to_str()).collect::<Option<_>>()?;
+        let file_name = components.pop()?;
+        let head_count = match self.layout() {
+            StoreLayout::TwoChars => 1,
+            StoreLayout::TwoByTwoChars => 2,
+        };
+        if components.len() != head_count || components.iter().any(|head| head.len() != 2) {
+            return None;
+        }
+
+        let (tail, file_type) = [FileType::Index, FileType::Exec, FileType::NonExec]
+            .into_iter()
+            .find_map(|file_type| Some((file_name.strip_suffix(file_type.suffix())?, file_type)))?;
+        let hex = components.concat() + tail;
+        if !hex.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f')) {
+            return None;
+        }
+        let algorithm = match hex.len() {
+            40 => Algorithm::Sha1,
+            64 => Algorithm::Sha256,
+            96 => Algorithm::Sha384,
+            128 => Algorithm::Sha512,
+            _ => return None,
+        };
+        Some((algorithm, hex, file_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use ssri::IntegrityOpts;
+    use tempfile::{tempdir, TempDir};
+
+    fn init_store_dir(layout: StoreLayout) -> (TempDir, StoreDir) {
+        let root = tempdir().unwrap();
+        let store_dir = StoreDir::new(root.path());
+        assert_eq!(store_dir.init_layout(layout).unwrap(), layout);
+        (root, store_dir)
+    }
+
+    #[test]
+    fn store_path_should_round_trip() {
+        let sha512 = IntegrityOpts::new().algorithm(Algorithm::Sha512).chain("content").result();
+        let sha1 = IntegrityOpts::new().algorithm(Algorithm::Sha1).chain("content").result();
+        for layout in [StoreLayout::TwoChars, StoreLayout::TwoByTwoChars] {
+            let (_root, store_dir) = init_store_dir(layout);
+            for file_type in [FileType::NonExec, FileType::Exec, FileType::Index] {
+                for integrity in [&sha512, &sha1] {
+                    eprintln!("CASE: {layout:?}, {file_type:?}, {integrity}");
+                    let path = store_dir.store_path(integrity, file_type);
+                    let (algorithm, hex) = integrity.to_hex();
+                    assert_eq!(
+                        store_dir.integrity_from_path(&path),
+                        Some((algorithm, hex, file_type))
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn store_path_should_split_the_hash_by_layout() {
+        let integrity =
+            Integrity::from_hex("3ef722d37b016c63ac0126cfdcec3ef722d37b01", Algorithm::Sha1)
+                .unwrap();
+        let (root, store_dir) = init_store_dir(StoreLayout::TwoChars);
+        let path = store_dir.store_path(&integrity, FileType::Index);
+        let expected =
+            root.path().join("v3/files/3e/f722d37b016c63ac0126cfdcec3ef722d37b01-index.json");
+        assert_eq!(path, expected);
+
+        let (root, store_dir) = init_store_dir(StoreLayout::TwoByTwoChars);
+        let path = store_dir.store_path(&integrity, FileType::Exec);
+        let expected = root.path().join("v3/files/3e/f7/22d37b016c63ac0126cfdcec3ef722d37b01-exec");
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn integrity_from_path_should_reject_foreign_paths() {
+        let hex = "3ef722d37b016c63ac0126cfdcec3ef722d37b01";
+        let (root, store_dir) = init_store_dir(StoreLayout::TwoChars);
+        let parse = |path: &str| store_dir.integrity_from_path(&root.path().join(path));
+
+        assert_eq!(
+            parse(&format!("v3/files/{}/{}", &hex[..2], &hex[2..])),
+            Some((Algorithm::Sha1, hex.to_string(), FileType::NonExec))
+        );
+        eprintln!("CASE: outside of the files directory");
+        assert_eq!(parse(&format!("v3/tmp/{}/{}", &hex[..2], &hex[2..])), None);
+        eprintln!("CASE: directories of another layout");
+        assert_eq!(parse(&format!("v3/files/{}/{}/{}", &hex[..2], &hex[2..4], &hex[4..])), None);
+        eprintln!("CASE: unknown suffix");
+        assert_eq!(parse(&format!("v3/files/{}/{}-foo", &hex[..2], &hex[2..])), None);
+        eprintln!("CASE: not a hash");
+        assert_eq!(parse("v3/files/3e/not-a-hash"), None);
+        assert_eq!(parse(&format!("v3/files/{}/{}", &hex[..2], &hex[2..30])), None);
+        assert_eq!(parse(&format!("v3/files/{}/{}", &hex[..2], hex[2..].to_uppercase())), None);
+    }
+}
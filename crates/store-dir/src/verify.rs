@@ -0,0 +1,162 @@
+use crate::{FileType, StoreDir, StoreLayout};
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use sha2::{Digest, Sha512};
+use ssri::{Algorithm, Integrity};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// File in the store whose content no longer matches the hash in its file name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptFile {
+    pub path: PathBuf,
+    /// Integrities of the tarballs whose index files list the file.
+    pub packages: Vec<Integrity>,
+}
+
+/// Error type of [`StoreDir::verify`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Failed to read {path:?} in the store: {error}")]
+#[diagnostic(code(pacquet_store_dir::verify_store))]
+pub struct VerifyStoreError {
+    pub path: PathBuf,
+    #[error(source)]
+    pub error: io::Error,
+}
+
+impl StoreDir {
+    /// Recompute the hash of every file in the store and list the files that don't match it,
+    /// sorted by path.
+    ///
+    /// Each corrupt file comes with the tarballs that it can be restored from, which are found
+    /// through the index files.
+    pub fn verify(&self) -> Result<Vec<CorruptFile>, VerifyStoreError> {
+        let mut corrupt_files = BTreeMap::<PathBuf, Vec<Integrity>>::new();
+        let mut index_files = Vec::new();
+        for path in self.list_files()? {
+            let Some((algorithm, hex, file_type)) = self.integrity_from_path(&path) else {
+                continue;
+            };
+            if file_type == FileType::Index {
+                index_files.push((algorithm, hex));
+                continue;
+            }
+            let content =
+                fs::read(&path).map_err(|error| VerifyStoreError { path: path.clone(), error })?;
+            if algorithm != Algorithm::Sha512 || format!("{:x}", Sha512::digest(content)) != hex {
+                corrupt_files.insert(path, Vec::new());
+            }
+        }
+
+        for (algorithm, hex) in index_files {
+            let Ok(integrity) = Integrity::from_hex(&hex, algorithm) else { continue };
+            let Some(index) = self.read_index_file(&integrity) else { continue };
+            for file_info in index.files.values() {
+                let Some(path) = file_info.cas_file_path(self) else { continue };
+                if let Some(packages) = corrupt_files.get_mut(&path) {
+                    if !packages.contains(&integrity) {
+                        packages.push(integrity.clone());
+                    }
+                }
+            }
+        }
+
+        let corrupt_files =
+            corrupt_files.into_iter().map(|(path, packages)| CorruptFile { path, packages });
+        Ok(corrupt_files.collect())
+    }
+
+    /// List the paths of the files in `{store}/v3/files`.
+    fn list_files(&self) -> Result<Vec<PathBuf>, VerifyStoreError> {
+        let read_dir = |path: &Path| -> Result<Vec<PathBuf>, VerifyStoreError> {
+            fs::read_dir(path)
+                .and_then(|entries| entries.map(|entry| Ok(entry?.path())).collect())
+                .map_err(|error| VerifyStoreError { path: path.to_path_buf(), error })
+        };
+        let files_dir = self.files();
+        if !files_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let depth = match self.layout() {
+            StoreLayout::TwoChars => 1,
+            StoreLayout::TwoByTwoChars => 2,
+        };
+        let mut dirs = vec![files_dir];
+        for _ in 0..depth {
+            let mut subdirs = Vec::new();
+            for dir in dirs {
+                subdirs.extend(read_dir(&dir)?.into_iter().filter(|path| path.is_dir()));
+            }
+            dirs = subdirs;
+        }
+        let mut files = Vec::new();
+        for dir in dirs {
+            files.extend(read_dir(&dir)?.into_iter().filter(|path| path.is_file()));
+        }
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PackageFileInfo, PackageFilesIndex};
+    use pretty_assertions::assert_eq;
+    use ssri::IntegrityOpts;
+
+    #[test]
+    fn verify_should_find_corrupt_files() {
+        for layout in [StoreLayout::TwoChars, StoreLayout::TwoByTwoChars] {
+            eprintln!("LAYOUT: {layout:?}");
+            verify_should_find_corrupt_files_with(layout);
+        }
+    }
+
+    fn verify_should_find_corrupt_files_with(layout: StoreLayout) {
+        let root = tempfile::tempdir().unwrap();
+        let store_dir = StoreDir::new(root.path());
+        assert_eq!(store_dir.init_layout(layout).unwrap(), layout);
+        assert_eq!(store_dir.verify().unwrap(), []);
+
+        let (intact_path, _) = store_dir.write_cas_file(b"INTACT", false).unwrap();
+        let (corrupt_path, _) = store_dir.write_cas_file(b"CORRUPT", true).unwrap();
+        let (orphan_path, _) = store_dir.write_cas_file(b"ORPHAN", false).unwrap();
+        let file_info = |content: &[u8], mode: u32| PackageFileInfo {
+            checked_at: None,
+            integrity: IntegrityOpts::new()
+                .algorithm(Algorithm::Sha512)
+                .chain(content)
+                .result()
+                .to_string(),
+            mode,
+            size: Some(content.len() as u64),
+        };
+        let index = PackageFilesIndex {
+            files: [
+                ("intact.js".to_string(), file_info(b"INTACT", 0o644)),
+                ("corrupt.sh".to_string(), file_info(b"CORRUPT", 0o755)),
+            ]
+            .into(),
+        };
+        let tarball_integrity =
+            IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(b"TARBALL CONTENT").result();
+        store_dir.write_index_file(&tarball_integrity, &index).unwrap();
+
+        eprintln!("CASE: nothing is corrupt");
+        assert_eq!(store_dir.verify().unwrap(), []);
+
+        eprintln!("CASE: files are corrupt");
+        fs::write(&corrupt_path, "TAMPERED").unwrap();
+        fs::write(&orphan_path, "TAMPERED").unwrap();
+        let mut expected = vec![
+            CorruptFile { path: corrupt_path, packages: vec![tarball_integrity] },
+            CorruptFile { path: orphan_path, packages: Vec::new() },
+        ];
+        expected.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(store_dir.verify().unwrap(), expected);
+        assert!(intact_path.is_file());
+    }
+}
@@ -1,13 +1,19 @@
+mod mem_cache;
+
+pub use mem_cache::*;
+
 use std::{
+    borrow::Cow,
     collections::HashMap,
     io::{Cursor, Read},
-    path::PathBuf,
-    sync::Arc,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    thread,
     time::UNIX_EPOCH,
 };
 
 use base64::{engine::general_purpose::STANDARD as BASE64_STD, Engine};
-use dashmap::DashMap;
 use derive_more::{Display, Error, From};
 use miette::Diagnostic;
 use pacquet_fs::file_mode;
@@ -16,9 +22,12 @@ use pacquet_store_dir::{
     PackageFileInfo, PackageFilesIndex, StoreDir, WriteCasFileError, WriteIndexFileError,
 };
 use pipe_trait::Pipe;
-use ssri::Integrity;
+use ssri::{Algorithm, Integrity, IntegrityOpts};
 use tar::Archive;
-use tokio::sync::{Notify, RwLock};
+use tokio::{
+    sync::{Notify, RwLock, Semaphore},
+    task::JoinError,
+};
 use tracing::instrument;
 use zune_inflate::{errors::InflateDecodeErrors, DeflateDecoder, DeflateOptions};
 
@@ -37,24 +46,73 @@ pub struct VerifyChecksumError {
     pub error: ssri::Error,
 }
 
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display("Failed to read {path:?}: {error}")]
+pub struct ReadLocalTarballError {
+    pub path: PathBuf,
+    #[error(source)]
+    pub error: std::io::Error,
+}
+
+#[derive(Debug, Display, Error, Diagnostic)]
+#[display(
+    "Integrity of {url} would be verified with {verified}, which is weaker than the {expected} recorded in the lockfile"
+)]
+pub struct IntegrityDowngradeError {
+    pub url: String,
+    pub expected: Algorithm,
+    pub verified: Algorithm,
+}
+
 #[derive(Debug, Display, Error, From, Diagnostic)]
 #[non_exhaustive]
 pub enum TarballError {
     #[diagnostic(code(pacquet_tarball::fetch_tarball))]
     FetchTarball(NetworkError),
 
+    #[diagnostic(code(pacquet_tarball::read_local_tarball))]
+    ReadLocalTarball(ReadLocalTarballError),
+
     #[from(ignore)]
     #[diagnostic(code(pacquet_tarball::io_error))]
     ReadTarballEntries(std::io::Error),
 
-    #[diagnostic(code(pacquet_tarball::verify_checksum_error))]
+    #[diagnostic(
+        code(pacquet_tarball::verify_checksum_error),
+        help(
+            "The tarball doesn't match the integrity from the registry or from pnpm-lock.yaml. \
+             If the package was republished under the same version, update its integrity in \
+             pnpm-lock.yaml and try again"
+        )
+    )]
     Checksum(VerifyChecksumError),
 
+    #[diagnostic(code(pacquet_tarball::integrity_downgrade))]
+    IntegrityDowngrade(IntegrityDowngradeError),
+
+    #[from(ignore)]
+    #[display("Path of the entry {_0:?} in the tarball isn't valid UTF-8")]
+    #[diagnostic(code(pacquet_tarball::invalid_entry_path))]
+    InvalidEntryPath(#[error(not(source))] PathBuf),
+
     #[from(ignore)]
     #[display("Failed to decode gzip: {_0}")]
     #[diagnostic(code(pacquet_tarball::decode_gzip))]
     DecodeGzip(InflateDecodeErrors),
 
+    #[from(ignore)]
+    #[display("The tarball is compressed with {_0}, which isn't supported")]
+    #[diagnostic(
+        code(pacquet_tarball::unsupported_compression),
+        help("Repack the tarball as a plain tar archive or with gzip")
+    )]
+    UnsupportedCompression(#[error(not(source))] TarballFormat),
+
+    #[from(ignore)]
+    #[display("The tarball is neither a tar archive nor compressed in a known format")]
+    #[diagnostic(code(pacquet_tarball::unknown_tarball_format))]
+    UnknownFormat,
+
     #[from(ignore)]
     #[display("Failed to write cafs: {_0}")]
     #[diagnostic(transparent)]
@@ -67,7 +125,7 @@ pub enum TarballError {
 
     #[from(ignore)]
     #[diagnostic(code(pacquet_tarball::task_join_error))]
-    TaskJoin(tokio::task::JoinError),
+    TaskJoin(#[error(source)] tokio::task::JoinError),
 }
 
 /// Value of the cache.
@@ -79,11 +137,6 @@ pub enum CacheValue {
     Available(Arc<HashMap<String, PathBuf>>),
 }
 
-/// Internal in-memory cache of tarballs.
-///
-/// The key of this hashmap is the url of each tarball.
-pub type MemCache = DashMap<String, Arc<RwLock<CacheValue>>>;
-
 #[instrument(skip(gz_data), fields(gz_data_len = gz_data.len()))]
 fn decompress_gzip(gz_data: &[u8], unpacked_size: Option<usize>) -> Result<Vec<u8>, TarballError> {
     let mut options = DeflateOptions::default().set_confirm_checksum(false);
@@ -97,6 +150,220 @@ fn decompress_gzip(gz_data: &[u8], unpacked_size: Option<usize>) -> Result<Vec<u
         .map_err(TarballError::DecodeGzip)
 }
 
+/// Format of a tarball, told by its first bytes.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum TarballFormat {
+    /// Uncompressed tar archive.
+    #[display("tar")]
+    Tar,
+    #[display("gzip")]
+    Gzip,
+    #[display("bzip2")]
+    Bzip2,
+    #[display("xz")]
+    Xz,
+    #[display("zstd")]
+    Zstd,
+}
+
+impl TarballFormat {
+    /// Detect the format of `tarball` from its magic bytes.
+    ///
+    /// A tar archive is only recognized by the `ustar` magic of its first header, which every
+    /// archive written since POSIX.1-1988 has.
+    pub fn detect(tarball: &[u8]) -> Option<Self> {
+        if tarball.starts_with(&[0x1f, 0x8b]) {
+            Some(TarballFormat::Gzip)
+        } else if tarball.starts_with(b"BZh") {
+            Some(TarballFormat::Bzip2)
+        } else if tarball.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(TarballFormat::Xz)
+        } else if tarball.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(TarballFormat::Zstd)
+        } else if tarball.get(257..262) == Some(b"ustar") {
+            Some(TarballFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decompress `tarball` into a tar archive according to its [format](TarballFormat).
+fn decompress_tarball(
+    tarball: &[u8],
+    unpacked_size: Option<usize>,
+) -> Result<Cow<'_, [u8]>, TarballError> {
+    match TarballFormat::detect(tarball) {
+        Some(TarballFormat::Tar) => Ok(Cow::Borrowed(tarball)),
+        Some(TarballFormat::Gzip) => decompress_gzip(tarball, unpacked_size).map(Cow::Owned),
+        Some(format) => Err(TarballError::UnsupportedCompression(format)),
+        None => Err(TarballError::UnknownFormat),
+    }
+}
+
+/// Refuse to verify a tarball against a `served` integrity whose strongest algorithm is strictly
+/// weaker than the strongest one of the `recorded` integrity, which could be a sign of a
+/// tampering mirror or proxy.
+///
+/// ssri always verifies with the strongest algorithm of an integrity, so comparing the strongest
+/// algorithms of both tells which algorithm the tarball would be verified with.
+fn check_integrity_algorithm(
+    url: &str,
+    recorded: &Integrity,
+    served: &Integrity,
+) -> Result<(), IntegrityDowngradeError> {
+    // `Algorithm` is ordered from the strongest to the weakest.
+    let expected = recorded.pick_algorithm();
+    let verified = served.pick_algorithm();
+    if verified > expected {
+        return Err(IntegrityDowngradeError { url: url.to_string(), expected, verified });
+    }
+    Ok(())
+}
+
+/// How far, in percent of the declared `unpackedSize`, the size of the extracted files may be off
+/// before a warning is logged.
+const UNPACKED_SIZE_TOLERANCE_PERCENT: u64 = 10;
+
+/// Whether the total size of the extracted files is too far off from the declared `unpackedSize`,
+/// which could be a sign of a tampered or corrupted tarball.
+fn unpacked_size_deviates(declared: usize, actual: u64) -> bool {
+    let declared = declared as u64;
+    actual.abs_diff(declared).saturating_mul(100)
+        > declared.saturating_mul(UNPACKED_SIZE_TOLERANCE_PERCENT)
+}
+
+/// Extract a tarball into the store directory and write its index file under `package_integrity`.
+///
+/// The index file is only written when `package_integrity` is the integrity of `tarball`, so a
+/// tarball that wasn't checked passes `None` and the store keeps no index for it.
+///
+/// The tarball is either a plain tar archive or gzipped, see [`TarballFormat::detect`].
+/// `package_unpacked_size` presizes the decompression buffer, and a warning is logged when the
+/// extracted files add up to a size that is far from it.
+///
+/// It returns a CAS map of files in the tarball.
+fn extract_tarball_to_store(
+    store_dir: &StoreDir,
+    package_integrity: Option<&Integrity>,
+    tarball: &[u8],
+    package_unpacked_size: Option<usize>,
+) -> Result<HashMap<String, PathBuf>, TarballError> {
+    // TODO: test the duplication of entries
+    let mut archive =
+        decompress_tarball(tarball, package_unpacked_size)?.pipe(Cursor::new).pipe(Archive::new);
+
+    let entries = archive.entries().map_err(TarballError::ReadTarballEntries)?;
+
+    let ((_, Some(capacity)) | (capacity, None)) = entries.size_hint();
+    let mut cas_paths = HashMap::<String, PathBuf>::with_capacity(capacity);
+    let mut pkg_files_idx = PackageFilesIndex { files: HashMap::with_capacity(capacity) };
+    let mut unpacked_size = 0u64;
+
+    for entry in entries {
+        let mut entry = entry.map_err(TarballError::ReadTarballEntries)?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+
+        let file_mode = entry.header().mode().map_err(TarballError::ReadTarballEntries)?;
+        let file_is_executable = file_mode::is_all_exec(file_mode);
+
+        // Read the contents of the entry
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buffer).map_err(TarballError::ReadTarballEntries)?;
+        unpacked_size += buffer.len() as u64;
+
+        let entry_path = entry.path().map_err(TarballError::ReadTarballEntries)?;
+        let cleaned_entry_path = entry_path
+            .components()
+            .skip(1)
+            .collect::<PathBuf>()
+            .into_os_string()
+            .into_string()
+            .map_err(|path| TarballError::InvalidEntryPath(path.into()))?;
+        let (file_path, file_hash) = store_dir
+            .write_cas_file(&buffer, file_is_executable)
+            .map_err(TarballError::WriteCasFile)?;
+
+        if let Some(previous) = cas_paths.insert(cleaned_entry_path.clone(), file_path) {
+            tracing::warn!(?previous, "Duplication detected. Old entry has been ejected");
+        }
+
+        let checked_at = UNIX_EPOCH.elapsed().ok().map(|x| x.as_millis());
+        let file_size = entry.header().size().ok();
+        let file_integrity = format!("sha512-{}", BASE64_STD.encode(file_hash));
+        let file_attrs = PackageFileInfo {
+            checked_at,
+            integrity: file_integrity,
+            mode: file_mode,
+            size: file_size,
+        };
+
+        if let Some(previous) = pkg_files_idx.files.insert(cleaned_entry_path, file_attrs) {
+            tracing::warn!(?previous, "Duplication detected. Old entry has been ejected");
+        }
+    }
+
+    if let Some(declared) = package_unpacked_size {
+        if unpacked_size_deviates(declared, unpacked_size) {
+            tracing::warn!(
+                integrity = ?package_integrity.map(ToString::to_string),
+                declared,
+                actual = unpacked_size,
+                "The extracted size of the tarball deviates from its declared unpackedSize, which may mean that it was tampered with or corrupted",
+            );
+        }
+    }
+
+    if let Some(package_integrity) = package_integrity {
+        store_dir
+            .write_index_file(package_integrity, &pkg_files_idx)
+            .map_err(TarballError::WriteTarballIndexFile)?;
+    }
+
+    Ok(cas_paths)
+}
+
+/// Bounded pool of threads that extract downloaded tarballs.
+///
+/// Decompressing and writing the files of a tarball is CPU and IO bound, so it runs on the blocking
+/// threads of tokio instead of holding up the async workers that drive other downloads. Tarballs
+/// wait for a permit before being extracted, which caps how many are decompressed in memory at
+/// once when downloads outpace extraction.
+#[derive(Debug)]
+pub struct ExtractionPool {
+    semaphore: Semaphore,
+}
+
+impl ExtractionPool {
+    /// Create a pool that extracts at most `size` tarballs at the same time.
+    pub fn new(size: usize) -> Self {
+        ExtractionPool { semaphore: Semaphore::new(size) }
+    }
+
+    /// Create a pool that extracts as many tarballs at the same time as there are CPUs.
+    pub fn new_from_cpu_count() -> Self {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get).pipe(ExtractionPool::new)
+    }
+
+    /// The pool shared by every [`DownloadTarballToStore`].
+    pub fn global() -> &'static Self {
+        static POOL: OnceLock<ExtractionPool> = OnceLock::new();
+        POOL.get_or_init(ExtractionPool::new_from_cpu_count)
+    }
+
+    /// Wait for a permit then run `task` on a blocking thread.
+    pub async fn run<Task, Output>(&self, task: Task) -> Result<Output, JoinError>
+    where
+        Task: FnOnce() -> Output + Send + 'static,
+        Output: Send + 'static,
+    {
+        let _permit = self.semaphore.acquire().await.expect("semaphore shouldn't have been closed");
+        tokio::task::spawn_blocking(task).await
+    }
+}
+
 /// This subroutine downloads and extracts a tarball to the store directory.
 ///
 /// It returns a CAS map of files in the tarball.
@@ -107,6 +374,16 @@ pub struct DownloadTarballToStore<'a> {
     pub package_integrity: &'a Integrity,
     pub package_unpacked_size: Option<usize>,
     pub package_url: &'a str,
+    /// Registry the package was resolved from, whose credentials may be sent with the request.
+    pub registry: &'a str,
+    /// Integrity that the lockfile recorded for the package, when
+    /// [`package_integrity`](Self::package_integrity) comes from elsewhere, such as the metadata
+    /// of a mirror. The tarball is refused if `package_integrity` uses a weaker algorithm.
+    pub recorded_integrity: Option<&'a Integrity>,
+    /// Whether to check the tarball against [`package_integrity`](Self::package_integrity) and
+    /// the files of the store that it was already extracted to, like `verify-store-integrity` of
+    /// `.npmrc`.
+    pub verify_store_integrity: bool,
 }
 
 impl<'a> DownloadTarballToStore<'a> {
@@ -147,7 +424,9 @@ impl<'a> DownloadTarballToStore<'a> {
             let cas_paths = self.run_without_mem_cache().await?.pipe(Arc::new);
             let mut cache_write = cache_lock.write().await;
             *cache_write = CacheValue::Available(Arc::clone(&cas_paths));
+            drop(cache_write);
             notify.notify_waiters();
+            mem_cache.evict();
             Ok(cas_paths)
         }
     }
@@ -160,19 +439,40 @@ impl<'a> DownloadTarballToStore<'a> {
             package_integrity,
             package_unpacked_size,
             package_url,
-            ..
+            registry,
+            recorded_integrity,
+            verify_store_integrity,
         } = self;
 
+        if let Some(recorded_integrity) = recorded_integrity {
+            check_integrity_algorithm(package_url, recorded_integrity, package_integrity)
+                .map_err(TarballError::IntegrityDowngrade)?;
+        }
+
+        if let Some(cas_paths) = store_dir.read_index_file(package_integrity).and_then(|index| {
+            if verify_store_integrity {
+                index.verified_cas_paths(store_dir)
+            } else {
+                index.cas_paths(store_dir)
+            }
+        }) {
+            tracing::info!(target: "pacquet::download", ?package_url, "Found in store");
+            return Ok(cas_paths);
+        }
+
         tracing::info!(target: "pacquet::download", ?package_url, "New cache");
 
         let network_error = |error| {
             TarballError::FetchTarball(NetworkError { url: package_url.to_string(), error })
         };
         let response = http_client
-            .run_with_permit(|client| client.get(package_url).send())
-            .await
-            .map_err(network_error)?
-            .bytes()
+            .run_with_permit(|client| {
+                let request = http_client.activity().start(package_url);
+                let response = http_client
+                    .authorize_for(registry, package_url, client.get(package_url))
+                    .send();
+                async move { request.bytes(response.await?).await }
+            })
             .await
             .map_err(network_error)?;
 
@@ -188,98 +488,157 @@ impl<'a> DownloadTarballToStore<'a> {
             Checksum(ssri::Error),
             Other(TarballError),
         }
-        let cas_paths = tokio::task::spawn(async move {
-            package_integrity.check(&response).map_err(TaskError::Checksum)?;
-
-            // TODO: move tarball extraction to its own function
-            // TODO: test it
-            // TODO: test the duplication of entries
-
-            let mut archive = decompress_gzip(&response, package_unpacked_size)
-                .map_err(TaskError::Other)?
-                .pipe(Cursor::new)
-                .pipe(Archive::new);
-
-            let entries = archive
-                .entries()
-                .map_err(TarballError::ReadTarballEntries)
-                .map_err(TaskError::Other)?
-                .filter(|entry| !entry.as_ref().unwrap().header().entry_type().is_dir());
-
-            let ((_, Some(capacity)) | (capacity, None)) = entries.size_hint();
-            let mut cas_paths = HashMap::<String, PathBuf>::with_capacity(capacity);
-            let mut pkg_files_idx = PackageFilesIndex { files: HashMap::with_capacity(capacity) };
-
-            for entry in entries {
-                let mut entry = entry.unwrap();
-
-                let file_mode = entry.header().mode().expect("get mode"); // TODO: properly propagate this error
-                let file_is_executable = file_mode::is_all_exec(file_mode);
-
-                // Read the contents of the entry
-                let mut buffer = Vec::with_capacity(entry.size() as usize);
-                entry.read_to_end(&mut buffer).unwrap();
-
-                let entry_path = entry.path().unwrap();
-                let cleaned_entry_path = entry_path
-                    .components()
-                    .skip(1)
-                    .collect::<PathBuf>()
-                    .into_os_string()
-                    .into_string()
-                    .expect("entry path must be valid UTF-8");
-                let (file_path, file_hash) = store_dir
-                    .write_cas_file(&buffer, file_is_executable)
-                    .map_err(TarballError::WriteCasFile)?;
-
-                if let Some(previous) = cas_paths.insert(cleaned_entry_path.clone(), file_path) {
-                    tracing::warn!(?previous, "Duplication detected. Old entry has been ejected");
-                }
-
-                let checked_at = UNIX_EPOCH.elapsed().ok().map(|x| x.as_millis());
-                let file_size = entry.header().size().ok();
-                let file_integrity = format!("sha512-{}", BASE64_STD.encode(file_hash));
-                let file_attrs = PackageFileInfo {
-                    checked_at,
-                    integrity: file_integrity,
-                    mode: file_mode,
-                    size: file_size,
+        let download_span = tracing::Span::current();
+        let cas_paths = ExtractionPool::global()
+            .run(move || {
+                let _span =
+                    tracing::debug_span!(target: "pacquet::timing", parent: &download_span, "extract")
+                        .entered();
+                // an unchecked tarball may not match `package_integrity`, so it gets no index file
+                // that a later install would trust
+                let checked_integrity = if verify_store_integrity {
+                    #[cfg(test)]
+                    tests::HASHED_TARBALLS.lock().unwrap().push(store_dir.display().to_string());
+                    package_integrity.check(&response).map_err(TaskError::Checksum)?;
+                    Some(&package_integrity)
+                } else {
+                    None
                 };
 
-                if let Some(previous) = pkg_files_idx.files.insert(cleaned_entry_path, file_attrs) {
-                    tracing::warn!(?previous, "Duplication detected. Old entry has been ejected");
-                }
-            }
+                extract_tarball_to_store(
+                    store_dir,
+                    checked_integrity,
+                    &response,
+                    package_unpacked_size,
+                )
+                .map_err(TaskError::Other)
+            })
+            .await
+            .map_err(TarballError::TaskJoin)?
+            .map_err(|error| match error {
+                TaskError::Checksum(error) => TarballError::Checksum(VerifyChecksumError {
+                    url: package_url.to_string(),
+                    error,
+                }),
+                TaskError::Other(error) => error,
+            })?;
+
+        if verify_store_integrity {
+            tracing::info!(target: "pacquet::download", ?package_url, "Checksum verified");
+        }
 
-            store_dir
-                .write_index_file(&package_integrity, &pkg_files_idx)
-                .map_err(TarballError::WriteTarballIndexFile)?;
+        Ok(cas_paths)
+    }
+}
 
-            Ok(cas_paths)
-        })
-        .await
-        .expect("no join error")
-        .map_err(|error| match error {
-            TaskError::Checksum(error) => {
-                TarballError::Checksum(VerifyChecksumError { url: package_url.to_string(), error })
-            }
-            TaskError::Other(error) => error,
+/// This subroutine extracts a tarball from the local file system to the store directory.
+///
+/// Unlike [`DownloadTarballToStore`], there is no expected integrity: it is computed from the
+/// content of the file and returned along with the CAS map of files in the tarball.
+#[must_use]
+pub struct ImportLocalTarball<'a> {
+    pub store_dir: &'a StoreDir,
+    pub path: &'a Path,
+}
+
+impl<'a> ImportLocalTarball<'a> {
+    /// Execute the subroutine.
+    pub fn run(self) -> Result<(Integrity, HashMap<String, PathBuf>), TarballError> {
+        let ImportLocalTarball { store_dir, path } = self;
+
+        let tarball = std::fs::read(path).map_err(|error| {
+            TarballError::ReadLocalTarball(ReadLocalTarballError {
+                path: path.to_path_buf(),
+                error,
+            })
         })?;
+        let integrity = IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(&tarball).result();
 
-        tracing::info!(target: "pacquet::download", ?package_url, "Checksum verified");
+        if let Some(cas_paths) =
+            store_dir.read_index_file(&integrity).and_then(|index| index.cas_paths(store_dir))
+        {
+            tracing::info!(target: "pacquet::import", ?path, "Found in store");
+            return Ok((integrity, cas_paths));
+        }
 
-        Ok(cas_paths)
+        let cas_paths = extract_tarball_to_store(store_dir, Some(&integrity), &tarball, None)?;
+        Ok((integrity, cas_paths))
+    }
+}
+
+/// This subroutine downloads a tarball from a URL that isn't the tarball of a registry package,
+/// such as `https://example.com/foo.tgz`, and extracts it to the store directory.
+///
+/// Like [`ImportLocalTarball`], there is no expected integrity: it is computed from the
+/// downloaded tarball and returned along with the CAS map of files in the tarball. The credentials
+/// of `.npmrc` that match the URL are sent with the request.
+#[must_use]
+pub struct ImportRemoteTarball<'a> {
+    pub http_client: &'a ThrottledClient,
+    pub store_dir: &'static StoreDir,
+    pub url: &'a str,
+}
+
+impl<'a> ImportRemoteTarball<'a> {
+    /// Execute the subroutine.
+    pub async fn run(self) -> Result<(Integrity, HashMap<String, PathBuf>), TarballError> {
+        let ImportRemoteTarball { http_client, store_dir, url } = self;
+
+        let tarball = http_client
+            .run_with_permit(|client| {
+                let request = http_client.activity().start(url);
+                let response = http_client.authorize_for(url, url, client.get(url)).send();
+                async move { request.bytes(response.await?.error_for_status()?).await }
+            })
+            .await
+            .map_err(|error| {
+                TarballError::FetchTarball(NetworkError { url: url.to_string(), error })
+            })?;
+
+        tracing::info!(target: "pacquet::download", ?url, "Download completed");
+
+        let url = url.to_string();
+        ExtractionPool::global()
+            .run(move || {
+                let integrity =
+                    IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(&tarball).result();
+                if let Some(cas_paths) = store_dir
+                    .read_index_file(&integrity)
+                    .and_then(|index| index.cas_paths(store_dir))
+                {
+                    tracing::info!(target: "pacquet::download", ?url, "Found in store");
+                    return Ok((integrity, cas_paths));
+                }
+                let cas_paths =
+                    extract_tarball_to_store(store_dir, Some(&integrity), &tarball, None)?;
+                Ok((integrity, cas_paths))
+            })
+            .await
+            .map_err(TarballError::TaskJoin)?
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use mockito::Matcher;
+    use pacquet_network::AuthHeaders;
+    use pacquet_testing_utils::{
+        fake_registry::{FakePackage, FakeRegistry},
+        fixtures::{
+            FASTIFY_ERROR_TARBALL, FASTIFY_ERROR_TARBALL_INTEGRITY,
+            FASTIFY_ERROR_TARBALL_UNPACKED_SIZE,
+        },
+    };
     use pipe_trait::Pipe;
     use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
     use tempfile::{tempdir, TempDir};
 
     use super::*;
 
+    /// Store directories of the downloaded tarballs that [`DownloadTarballToStore`] has hashed.
+    pub(super) static HASHED_TARBALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
     fn integrity(integrity_str: &str) -> Integrity {
         integrity_str.parse().expect("parse integrity string")
     }
@@ -310,7 +669,10 @@ mod tests {
             store_dir: store_path,
             package_integrity: &integrity("sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w=="),
             package_unpacked_size: Some(16697),
-            package_url: "https://registry.npmjs.org/@fastify/error/-/error-3.3.0.tgz"
+            package_url: "https://registry.npmjs.org/@fastify/error/-/error-3.3.0.tgz",
+            registry: "https://registry.npmjs.org/",
+            recorded_integrity: None,
+            verify_store_integrity: true,
         }
         .run_without_mem_cache()
         .await
@@ -341,6 +703,72 @@ mod tests {
         drop(store_dir);
     }
 
+    #[tokio::test]
+    async fn should_reject_integrity_algorithm_downgrade() {
+        let path = "/@fastify/error/-/error-3.3.0.tgz";
+        let mut registry = mockito::Server::new_async().await;
+        let package_url = format!("{}{path}", registry.url());
+        let recorded = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+        let sha1 =
+            IntegrityOpts::new().algorithm(Algorithm::Sha1).chain(FASTIFY_ERROR_TARBALL).result();
+        let http_client = ThrottledClient::default();
+        let download =
+            |package_integrity, store_path, verify_store_integrity| DownloadTarballToStore {
+                http_client: &http_client,
+                store_dir: store_path,
+                package_integrity,
+                package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+                package_url: &package_url,
+                registry: &package_url,
+                recorded_integrity: Some(&recorded),
+                verify_store_integrity,
+            };
+
+        for verify_store_integrity in [true, false] {
+            eprintln!("CASE: a sha1 integrity served in place of the recorded sha512 is refused (verify_store_integrity={verify_store_integrity})");
+            let mock = registry
+                .mock("GET", path)
+                .with_body(FASTIFY_ERROR_TARBALL)
+                .expect(0)
+                .create_async()
+                .await;
+            let (store_dir, store_path) = tempdir_with_leaked_path();
+            let error = download(&sha1, store_path, verify_store_integrity)
+                .run_without_mem_cache()
+                .await
+                .unwrap_err();
+            dbg!(&error);
+            let TarballError::IntegrityDowngrade(error) = error else {
+                panic!("expected an integrity downgrade error");
+            };
+            assert_eq!(error.expected, Algorithm::Sha512);
+            assert_eq!(error.verified, Algorithm::Sha1);
+            mock.assert_async().await;
+            drop(store_dir);
+        }
+
+        eprintln!("CASE: the recorded algorithm is accepted");
+        let mock = registry.mock("GET", path).with_body(FASTIFY_ERROR_TARBALL).create_async().await;
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        download(&recorded, store_path, true).run_without_mem_cache().await.unwrap();
+        mock.assert_async().await;
+        drop(store_dir);
+    }
+
+    #[test]
+    fn check_integrity_algorithm_should_compare_the_strongest_algorithms() {
+        let url = "https://registry.npmjs.org/@fastify/error/-/error-3.3.0.tgz";
+        let sha512 = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+        let sha1 =
+            IntegrityOpts::new().algorithm(Algorithm::Sha1).chain(FASTIFY_ERROR_TARBALL).result();
+        let both = sha512.clone().concat(sha1.clone());
+
+        check_integrity_algorithm(url, &sha512, &sha1).expect_err("sha1 is weaker than sha512");
+        check_integrity_algorithm(url, &both, &sha1).expect_err("sha1 is weaker than sha512");
+        check_integrity_algorithm(url, &sha512, &both).expect("sha512 is served");
+        check_integrity_algorithm(url, &sha1, &sha512).expect("an upgrade is fine");
+    }
+
     #[tokio::test]
     async fn should_throw_error_on_checksum_mismatch() {
         let (store_dir, store_path) = tempdir_with_leaked_path();
@@ -350,6 +778,9 @@ mod tests {
             package_integrity: &integrity("sha512-aaaan1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w=="),
             package_unpacked_size: Some(16697),
             package_url: "https://registry.npmjs.org/@fastify/error/-/error-3.3.0.tgz",
+            registry: "https://registry.npmjs.org/",
+            recorded_integrity: None,
+            verify_store_integrity: true,
         }
         .run_without_mem_cache()
         .await
@@ -357,4 +788,500 @@ mod tests {
 
         drop(store_dir);
     }
+
+    #[tokio::test]
+    async fn should_trust_tarball_without_verify_store_integrity() {
+        let registry = FakeRegistry::start(&[FakePackage::new("foo", "1.0.0")]);
+        let registry_url = registry.url();
+        let package_url = format!("{registry_url}foo/-/foo-1.0.0.tgz");
+        let package_integrity = integrity("sha512-z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg==");
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let cas_paths = DownloadTarballToStore {
+            http_client: &Default::default(),
+            store_dir: store_path,
+            package_integrity: &package_integrity,
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &package_url,
+            registry: &registry_url,
+            recorded_integrity: None,
+            verify_store_integrity: false,
+        }
+        .run_without_mem_cache()
+        .await
+        .expect("the checksum shouldn't be verified");
+        assert!(cas_paths.contains_key("package.json"));
+        assert!(!HASHED_TARBALLS.lock().unwrap().contains(&store_path.display().to_string()));
+        assert!(
+            store_path.read_index_file(&package_integrity).is_none(),
+            "the unchecked tarball shouldn't be indexed under the expected integrity",
+        );
+
+        drop(store_dir);
+    }
+
+    #[tokio::test]
+    async fn should_not_hash_the_store_without_verify_store_integrity() {
+        let registry = FakeRegistry::start(&[FakePackage::new("foo", "1.0.0")]);
+        let registry_url = registry.url();
+        let package_url = format!("{registry_url}foo/-/foo-1.0.0.tgz");
+        let package_integrity = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let http_client = Default::default();
+        let download = |verify_store_integrity| DownloadTarballToStore {
+            http_client: &http_client,
+            store_dir: store_path,
+            package_integrity: &package_integrity,
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &package_url,
+            registry: &registry_url,
+            recorded_integrity: None,
+            verify_store_integrity,
+        };
+
+        eprintln!("CASE: the verified download is hashed");
+        let first = download(true).run_without_mem_cache().await.unwrap();
+        assert!(HASHED_TARBALLS.lock().unwrap().contains(&store_path.display().to_string()));
+
+        eprintln!("CASE: the store is trusted as is");
+        let manifest_path = &first["package.json"];
+        let size = std::fs::metadata(manifest_path).unwrap().len() as usize;
+        std::fs::write(manifest_path, vec![b'x'; size]).unwrap();
+        let second = download(false).run_without_mem_cache().await.unwrap();
+        assert_eq!(first, second);
+        assert!(manifest_path.exists(), "the corrupt file would be removed by hashing it");
+        registry.mock("/foo/-/foo-1.0.0.tgz").assert(); // exactly one hit
+
+        drop(store_dir);
+    }
+
+    #[tokio::test]
+    async fn should_reuse_tarball_already_in_store() {
+        let registry = FakeRegistry::start(&[FakePackage::new("foo", "1.0.0")]);
+        let registry_url = registry.url();
+        let package_url = format!("{registry_url}foo/-/foo-1.0.0.tgz");
+        let package_integrity = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let http_client = Default::default();
+        let download = || DownloadTarballToStore {
+            http_client: &http_client,
+            store_dir: store_path,
+            package_integrity: &package_integrity,
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &package_url,
+            registry: &registry_url,
+            recorded_integrity: None,
+            verify_store_integrity: true,
+        };
+
+        eprintln!("First download");
+        let first = download().run_without_mem_cache().await.unwrap();
+
+        eprintln!("Second download");
+        let second = download().run_without_mem_cache().await.unwrap();
+
+        assert_eq!(first, second);
+        registry.mock("/foo/-/foo-1.0.0.tgz").assert(); // exactly one hit
+
+        drop(store_dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn extraction_pool_should_bound_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let pool = ExtractionPool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..8).map(|index| {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            pool.run(move || {
+                let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(count, Ordering::SeqCst);
+                thread::sleep(std::time::Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                index
+            })
+        });
+
+        let outputs = futures_util::future::try_join_all(tasks).await.unwrap();
+        assert_eq!(outputs, (0..8).collect::<Vec<_>>());
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn should_download_and_extract_tarballs_concurrently() {
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let packages: Vec<_> = names.iter().map(|name| FakePackage::new(name, "1.0.0")).collect();
+        let registry = FakeRegistry::start(&packages);
+        let registry_url = registry.url();
+        let package_integrity = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+        let http_client = ThrottledClient::new_from_cpu_count();
+        let stores: Vec<_> = names.iter().map(|_| tempdir_with_leaked_path()).collect();
+        let package_urls: Vec<_> =
+            names.iter().map(|name| format!("{registry_url}{name}/-/{name}-1.0.0.tgz")).collect();
+
+        let downloads = stores.iter().zip(&package_urls).map(|((_, store_path), package_url)| {
+            let download = DownloadTarballToStore {
+                http_client: &http_client,
+                store_dir: store_path,
+                package_integrity: &package_integrity,
+                package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+                package_url,
+                registry: &registry_url,
+                recorded_integrity: None,
+                verify_store_integrity: true,
+            };
+            async move { download.run_without_mem_cache().await }
+        });
+        let downloads: Vec<_> = futures_util::future::try_join_all(downloads).await.unwrap();
+
+        for ((_, store_path), cas_paths) in stores.iter().zip(&downloads) {
+            assert!(!cas_paths.is_empty());
+            assert!(cas_paths.values().all(|path| path.is_file()));
+            let index = store_path.read_index_file(&package_integrity).unwrap();
+            assert_eq!(index.files.len(), cas_paths.len());
+        }
+        for name in names {
+            registry.mock(&format!("/{name}/-/{name}-1.0.0.tgz")).assert();
+        }
+
+        drop(stores);
+    }
+
+    #[tokio::test]
+    async fn should_send_credentials_only_to_matching_registry() {
+        let path = "/foo/-/foo-1.0.0.tgz";
+        let mut registry = mockito::Server::new();
+        let registry_mock = registry
+            .mock("GET", path)
+            .match_header("authorization", "Bearer secret")
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+        let mut cdn = mockito::Server::new();
+        let cdn_mock = cdn
+            .mock("GET", path)
+            .match_header("authorization", Matcher::Missing)
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+
+        let tokens = [(format!("//{}/", registry.host_with_port()), "secret".to_string())];
+        let auth_headers = AuthHeaders::from_tokens(tokens.iter().map(|(uri, token)| (uri, token)));
+        let http_client = ThrottledClient::new_from_cpu_count().with_auth_headers(auth_headers);
+        let package_integrity = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+
+        for package_url in [format!("{}{path}", registry.url()), format!("{}{path}", cdn.url())] {
+            let (store_dir, store_path) = tempdir_with_leaked_path();
+            DownloadTarballToStore {
+                http_client: &http_client,
+                store_dir: store_path,
+                package_integrity: &package_integrity,
+                package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+                package_url: &package_url,
+                registry: &registry.url(),
+                recorded_integrity: None,
+                verify_store_integrity: true,
+            }
+            .run_without_mem_cache()
+            .await
+            .unwrap();
+            drop(store_dir);
+        }
+
+        registry_mock.assert();
+        cdn_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn should_send_the_configured_user_agent() {
+        let path = "/foo/-/foo-1.0.0.tgz";
+        let mut registry = mockito::Server::new();
+        let mock = registry
+            .mock("GET", path)
+            .match_header("user-agent", "corp-proxy/1.0")
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+
+        let http_client = ThrottledClient::new_from_cpu_count()
+            .with_user_agent("corp-proxy/1.0".parse().unwrap());
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        DownloadTarballToStore {
+            http_client: &http_client,
+            store_dir: store_path,
+            package_integrity: &integrity(FASTIFY_ERROR_TARBALL_INTEGRITY),
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &format!("{}{path}", registry.url()),
+            registry: &registry.url(),
+            recorded_integrity: None,
+            verify_store_integrity: true,
+        }
+        .run_without_mem_cache()
+        .await
+        .unwrap();
+
+        mock.assert();
+        drop(store_dir);
+    }
+
+    #[tokio::test]
+    async fn should_not_send_credentials_along_cross_host_redirects() {
+        let path = "/foo/-/foo-1.0.0.tgz";
+        let mut cdn = mockito::Server::new();
+        let cdn_mock = cdn
+            .mock("GET", path)
+            .match_header("authorization", Matcher::Missing)
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+        let mut registry = mockito::Server::new();
+        let registry_mock = registry
+            .mock("GET", path)
+            .match_header("authorization", "Bearer secret")
+            .with_status(302)
+            .with_header("location", &format!("{}{path}", cdn.url()))
+            .create();
+
+        let tokens = [(format!("//{}/", registry.host_with_port()), "secret".to_string())];
+        let auth_headers = AuthHeaders::from_tokens(tokens.iter().map(|(uri, token)| (uri, token)));
+        let http_client = ThrottledClient::new_from_cpu_count().with_auth_headers(auth_headers);
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let cas_paths = DownloadTarballToStore {
+            http_client: &http_client,
+            store_dir: store_path,
+            package_integrity: &integrity(FASTIFY_ERROR_TARBALL_INTEGRITY),
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &format!("{}{path}", registry.url()),
+            registry: &registry.url(),
+            recorded_integrity: None,
+            verify_store_integrity: true,
+        }
+        .run_without_mem_cache()
+        .await
+        .unwrap();
+        assert!(cas_paths.contains_key("package.json"));
+
+        registry_mock.assert();
+        cdn_mock.assert();
+        drop(store_dir);
+    }
+
+    #[tokio::test]
+    async fn should_not_decompress_tarball_sent_with_content_encoding() {
+        let path = "/foo/-/foo-1.0.0.tgz";
+        let mut cdn = mockito::Server::new();
+        let cdn_mock = cdn
+            .mock("GET", path)
+            .match_header("accept-encoding", Matcher::Missing)
+            .with_header("content-encoding", "gzip")
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let package_url = format!("{}{path}", cdn.url());
+        DownloadTarballToStore {
+            http_client: &Default::default(),
+            store_dir: store_path,
+            package_integrity: &integrity(FASTIFY_ERROR_TARBALL_INTEGRITY),
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &package_url,
+            registry: &cdn.url(),
+            recorded_integrity: None,
+            verify_store_integrity: true,
+        }
+        .run_without_mem_cache()
+        .await
+        .expect("the integrity covers the tarball as it was sent");
+
+        cdn_mock.assert();
+        drop(store_dir);
+    }
+
+    #[tokio::test]
+    async fn should_send_credentials_to_other_hosts_with_always_auth() {
+        let path = "/foo/-/foo-1.0.0.tgz";
+        let registry = mockito::Server::new();
+        let mut cdn = mockito::Server::new();
+        let cdn_mock = cdn
+            .mock("GET", path)
+            .match_header("authorization", "Bearer secret")
+            .with_body(FASTIFY_ERROR_TARBALL)
+            .create();
+
+        let registry_uri = format!("//{}/", registry.host_with_port());
+        let tokens = [(registry_uri.clone(), "secret".to_string())];
+        let auth_headers = AuthHeaders::from_tokens(tokens.iter().map(|(uri, token)| (uri, token)))
+            .with_always_auth([&registry_uri]);
+        let http_client = ThrottledClient::new_from_cpu_count().with_auth_headers(auth_headers);
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        DownloadTarballToStore {
+            http_client: &http_client,
+            store_dir: store_path,
+            package_integrity: &integrity(FASTIFY_ERROR_TARBALL_INTEGRITY),
+            package_unpacked_size: Some(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE),
+            package_url: &format!("{}{path}", cdn.url()),
+            registry: &registry.url(),
+            recorded_integrity: None,
+            verify_store_integrity: true,
+        }
+        .run_without_mem_cache()
+        .await
+        .unwrap();
+
+        cdn_mock.assert();
+        drop(store_dir);
+    }
+
+    #[test]
+    fn should_import_local_tarball() {
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let tarball_dir = tempdir().unwrap();
+        let tarball_path = tarball_dir.path().join("error-3.3.0.tgz");
+        std::fs::write(&tarball_path, FASTIFY_ERROR_TARBALL).unwrap();
+
+        let (integrity, cas_paths) =
+            ImportLocalTarball { store_dir: store_path, path: &tarball_path }.run().unwrap();
+
+        assert_eq!(integrity.to_string(), FASTIFY_ERROR_TARBALL_INTEGRITY);
+        assert!(cas_paths.contains_key("package.json"));
+        assert_eq!(store_path.read_index_file(&integrity).unwrap().files.len(), cas_paths.len());
+
+        drop((store_dir, tarball_dir));
+    }
+
+    #[tokio::test]
+    async fn should_import_remote_tarball() {
+        let registry = FakeRegistry::start(&[FakePackage::new("foo", "1.0.0")]);
+        let url = format!("{}foo/-/foo-1.0.0.tgz", registry.url());
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let (integrity, cas_paths) = ImportRemoteTarball {
+            http_client: &Default::default(),
+            store_dir: store_path,
+            url: &url,
+        }
+        .run()
+        .await
+        .unwrap();
+        assert_eq!(integrity.to_string(), FASTIFY_ERROR_TARBALL_INTEGRITY);
+        assert!(cas_paths.contains_key("package.json"));
+        assert!(store_path.read_index_file(&integrity).is_some());
+
+        eprintln!("A missing tarball is an error");
+        let missing_url = format!("{}foo/-/foo-2.0.0.tgz", registry.url());
+        let error = ImportRemoteTarball {
+            http_client: &Default::default(),
+            store_dir: store_path,
+            url: &missing_url,
+        }
+        .run()
+        .await
+        .expect_err("the tarball doesn't exist");
+        assert!(matches!(error, TarballError::FetchTarball(_)));
+
+        drop(store_dir);
+    }
+
+    #[test]
+    fn plain_tar_and_gzip_should_be_extracted_alike() {
+        let tar = decompress_gzip(FASTIFY_ERROR_TARBALL, None).unwrap();
+        assert_eq!(TarballFormat::detect(&tar), Some(TarballFormat::Tar));
+        assert_eq!(TarballFormat::detect(FASTIFY_ERROR_TARBALL), Some(TarballFormat::Gzip));
+
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let extract = |tarball: &[u8]| {
+            let integrity =
+                IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(tarball).result();
+            let cas_paths =
+                extract_tarball_to_store(store_path, Some(&integrity), tarball, None).unwrap();
+            let mut files: Vec<_> = store_path
+                .read_index_file(&integrity)
+                .unwrap()
+                .files
+                .into_iter()
+                .map(|(path, info)| (path, info.integrity, info.mode, info.size))
+                .collect();
+            files.sort();
+            (cas_paths, files)
+        };
+        let (tar_cas_paths, tar_files) = extract(&tar);
+        let (gzip_cas_paths, gzip_files) = extract(FASTIFY_ERROR_TARBALL);
+        assert!(tar_cas_paths.contains_key("package.json"));
+        assert_eq!(tar_cas_paths, gzip_cas_paths);
+        assert_eq!(tar_files, gzip_files);
+
+        drop(store_dir);
+    }
+
+    #[test]
+    fn should_reject_unsupported_tarball_formats() {
+        let (store_dir, store_path) = tempdir_with_leaked_path();
+        let extract = |tarball: &[u8]| {
+            let integrity =
+                IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(tarball).result();
+            extract_tarball_to_store(store_path, Some(&integrity), tarball, None).unwrap_err()
+        };
+        assert!(matches!(
+            extract(b"BZh91AY&SY\x00\x00"),
+            TarballError::UnsupportedCompression(TarballFormat::Bzip2)
+        ));
+        assert!(matches!(extract(b"not a tarball"), TarballError::UnknownFormat));
+        drop(store_dir);
+    }
+
+    #[test]
+    fn should_detect_deviating_unpacked_size() {
+        assert!(!unpacked_size_deviates(1000, 1000));
+        assert!(!unpacked_size_deviates(1000, 1100));
+        assert!(!unpacked_size_deviates(1000, 900));
+        assert!(unpacked_size_deviates(1000, 1101));
+        assert!(unpacked_size_deviates(1000, 899));
+        assert!(unpacked_size_deviates(0, 1));
+    }
+
+    #[test]
+    fn should_warn_when_unpacked_size_deviates() {
+        use std::{io::Write, sync::Mutex};
+
+        #[derive(Clone, Default)]
+        struct Logs(Arc<Mutex<Vec<u8>>>);
+        impl Write for Logs {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let extract = |declared_size| {
+            let logs = Logs::default();
+            let writer = logs.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(move || writer.clone())
+                .with_ansi(false)
+                .finish();
+            let (store_dir, store_path) = tempdir_with_leaked_path();
+            let package_integrity = integrity(FASTIFY_ERROR_TARBALL_INTEGRITY);
+            tracing::subscriber::with_default(subscriber, || {
+                extract_tarball_to_store(
+                    store_path,
+                    Some(&package_integrity),
+                    FASTIFY_ERROR_TARBALL,
+                    Some(declared_size),
+                )
+                .unwrap()
+            });
+            drop(store_dir);
+            let logs = logs.0.lock().unwrap().clone();
+            String::from_utf8(logs).unwrap()
+        };
+
+        let logs = extract(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE);
+        eprintln!("LOGS:\n{logs}");
+        assert!(!logs.contains("unpackedSize"));
+
+        let logs = extract(FASTIFY_ERROR_TARBALL_UNPACKED_SIZE * 4);
+        eprintln!("LOGS:\n{logs}");
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("deviates from its declared unpackedSize"));
+        assert!(logs.contains(&format!("actual={FASTIFY_ERROR_TARBALL_UNPACKED_SIZE}")));
+    }
 }
@@ -0,0 +1,180 @@
+use crate::CacheValue;
+use dashmap::DashMap;
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::RwLock;
+
+/// Internal in-memory cache of tarballs.
+///
+/// The key of each entry is the url of a tarball.
+///
+/// By default, the cache keeps every entry. With [`with_max_entries`](Self::with_max_entries),
+/// the least recently used entries whose tarball is [available](CacheValue::Available) are
+/// evicted as soon as there are too many. Entries that are still [in progress](CacheValue::InProgress)
+/// are never evicted, so the cache may exceed its cap while more downloads than that are in
+/// flight. Evicting an entry only drops the reference of the cache: the CAS maps already handed
+/// out stay alive until their users are done with them, and a tarball requested after its eviction
+/// is read back from the store index.
+#[derive(Debug, Default)]
+pub struct MemCache {
+    entries: DashMap<String, CacheEntry>,
+    max_entries: Option<NonZeroUsize>,
+    clock: AtomicU64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    value: Arc<RwLock<CacheValue>>,
+    last_used: AtomicU64,
+}
+
+impl MemCache {
+    /// Create a cache without a cap.
+    pub fn new() -> Self {
+        MemCache::default()
+    }
+
+    /// Create a cache that holds at most `max_entries` available tarballs.
+    pub fn with_max_entries(max_entries: NonZeroUsize) -> Self {
+        MemCache { max_entries: Some(max_entries), ..MemCache::default() }
+    }
+
+    /// The cap set by [`with_max_entries`](Self::with_max_entries), if any.
+    pub fn max_entries(&self) -> Option<NonZeroUsize> {
+        self.max_entries
+    }
+
+    /// Number of entries, in progress or available.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Get the entry of `url` and mark it as the most recently used.
+    pub fn get(&self, url: &str) -> Option<Arc<RwLock<CacheValue>>> {
+        let entry = self.entries.get(url)?;
+        entry.last_used.store(self.tick(), Ordering::Relaxed);
+        Some(Arc::clone(&entry.value))
+    }
+
+    /// Insert the entry of `url`, then evict entries beyond the cap.
+    ///
+    /// Return the entry that was replaced, if any.
+    pub fn insert(
+        &self,
+        url: String,
+        value: Arc<RwLock<CacheValue>>,
+    ) -> Option<Arc<RwLock<CacheValue>>> {
+        let entry = CacheEntry { value, last_used: AtomicU64::new(self.tick()) };
+        let replaced = self.entries.insert(url, entry).map(|entry| entry.value);
+        self.evict();
+        replaced
+    }
+
+    /// Evict the least recently used available entries until the cap is respected or only
+    /// entries in progress are left.
+    ///
+    /// This is called by [`insert`](Self::insert), and should be called again whenever an entry
+    /// becomes available.
+    pub fn evict(&self) {
+        let Some(max_entries) = self.max_entries else { return };
+        let excess = match self.entries.len().checked_sub(max_entries.get()) {
+            None | Some(0) => return,
+            Some(excess) => excess,
+        };
+
+        // collect first: removing while iterating would deadlock on the shard locks
+        let mut candidates: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .filter(|entry| is_available(&entry.value))
+            .map(|entry| (entry.last_used.load(Ordering::Relaxed), entry.key().clone()))
+            .collect();
+        candidates.sort_unstable();
+
+        for (_, url) in candidates.into_iter().take(excess) {
+            // the entry may have been replaced since it was collected
+            if self.entries.remove_if(&url, |_, entry| is_available(&entry.value)).is_some() {
+                tracing::debug!(target: "pacquet::download", ?url, "Evict from cache");
+            }
+        }
+    }
+}
+
+/// Whether the tarball of an entry is available, as opposed to in progress or being written.
+fn is_available(value: &RwLock<CacheValue>) -> bool {
+    value.try_read().is_ok_and(|value| matches!(*value, CacheValue::Available(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tokio::sync::Notify;
+
+    fn available() -> Arc<RwLock<CacheValue>> {
+        Arc::new(RwLock::new(CacheValue::Available(Arc::new(HashMap::new()))))
+    }
+
+    fn in_progress() -> Arc<RwLock<CacheValue>> {
+        Arc::new(RwLock::new(CacheValue::InProgress(Arc::new(Notify::new()))))
+    }
+
+    fn urls(cache: &MemCache) -> Vec<String> {
+        let mut urls: Vec<_> = cache.entries.iter().map(|entry| entry.key().clone()).collect();
+        urls.sort();
+        urls
+    }
+
+    #[test]
+    fn should_keep_everything_without_cap() {
+        let cache = MemCache::new();
+        for url in ["a", "b", "c"] {
+            cache.insert(url.to_string(), available());
+        }
+        assert_eq!(cache.max_entries(), None);
+        assert_eq!(urls(&cache), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        let cache = MemCache::with_max_entries(NonZeroUsize::new(2).unwrap());
+        cache.insert("a".to_string(), available());
+        cache.insert("b".to_string(), available());
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_string(), available());
+        assert_eq!(urls(&cache), ["a", "c"]);
+    }
+
+    #[test]
+    fn should_not_evict_in_progress() {
+        let cache = MemCache::with_max_entries(NonZeroUsize::new(1).unwrap());
+        let pending = in_progress();
+        cache.insert("a".to_string(), Arc::clone(&pending));
+        cache.insert("b".to_string(), in_progress());
+        assert_eq!(urls(&cache), ["a", "b"]);
+
+        let write = pending.try_write().unwrap();
+        cache.insert("c".to_string(), available());
+        assert_eq!(urls(&cache), ["a", "b"], "an entry being written is in progress");
+
+        drop(write);
+        *pending.try_write().unwrap() = CacheValue::Available(Arc::new(HashMap::new()));
+        cache.evict();
+        assert_eq!(urls(&cache), ["b"]);
+    }
+}
@@ -1,10 +1,30 @@
+use crate::fake_registry::{FakePackage, FakeRegistry};
 use assert_cmd::prelude::*;
 use command_extra::CommandExtra;
 use pacquet_registry_mock::AutoMockInstance;
-use std::{fs, path::PathBuf, process::Command};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 use tempfile::{tempdir, TempDir};
 use text_block_macros::text_block_fnl;
 
+/// Variables that mark a CI environment, in which the lockfile is frozen by default.
+const CI_ENV: [&str; 2] = ["CI", "CONTINUOUS_INTEGRATION"];
+
+/// Command of `pacquet` with `current_dir` as working directory, outside of a CI environment even
+/// when the tests run in one.
+pub fn pacquet_command(current_dir: impl AsRef<Path>) -> Command {
+    let pacquet = Command::cargo_bin("pacquet").expect("find the pacquet binary");
+    without_ci_env(pacquet.with_current_dir(current_dir))
+}
+
+/// Remove the [`CI_ENV`] variables from the environment of `command`.
+fn without_ci_env(command: Command) -> Command {
+    CI_ENV.into_iter().fold(command, CommandExtra::without_env)
+}
+
 /// Assets for an integration test involving spawning `pacquet` and/or `pnpm` as
 /// sub-process(es) in a temporary directory.
 pub struct CommandTempCwd<NpmrcInfo> {
@@ -23,14 +43,14 @@ pub struct CommandTempCwd<NpmrcInfo> {
 impl CommandTempCwd<()> {
     /// Create a temporary directory, a `workspace` sub-directory, a `pacquet` command,
     /// and a `pnpm` command with current dir set to the `workspace` sub-directory.
+    ///
+    /// Neither command inherits the variables of a CI environment, see [`pacquet_command`].
     pub fn init() -> Self {
         let root = tempdir().expect("create temporary directory");
         let workspace = root.path().join("workspace");
         fs::create_dir(&workspace).expect("create temporary workspace for the commands");
-        let pacquet = Command::cargo_bin("pacquet")
-            .expect("find the pacquet binary")
-            .with_current_dir(&workspace);
-        let pnpm = Command::new("pnpm").with_current_dir(&workspace);
+        let pacquet = pacquet_command(&workspace);
+        let pnpm = without_ci_env(Command::new("pnpm").with_current_dir(&workspace));
         CommandTempCwd { pacquet, pnpm, root, workspace, npmrc_info: () }
     }
 }
@@ -67,3 +87,36 @@ impl CommandTempCwd<()> {
         CommandTempCwd { pacquet, pnpm, root, workspace, npmrc_info }
     }
 }
+
+/// Information after the creation of an `.npmrc` file and a [`FakeRegistry`] from assets provided by [`CommandTempCwd`].
+#[must_use]
+pub struct AddFakeRegistry {
+    /// Path to the created `.npmrc` file.
+    pub npmrc_path: PathBuf,
+    /// Absolute path to the store directory as defined by the `.npmrc` file.
+    pub store_dir: PathBuf,
+    /// Absolute path to the cache directory as defined by the `.npmrc` file.
+    pub cache_dir: PathBuf,
+    /// The offline registry. The server will be stop when [dropped](Drop).
+    pub registry: FakeRegistry,
+}
+
+impl CommandTempCwd<()> {
+    /// Create a [`FakeRegistry`] that serves `packages` and a `.npmrc` file that defines `store-dir`,
+    /// `cache-dir`, and `registry`.
+    pub fn add_fake_registry(self, packages: &[FakePackage]) -> CommandTempCwd<AddFakeRegistry> {
+        let store_dir = self.root.path().join("pacquet-store");
+        let cache_dir = self.root.path().join("pacquet-cache");
+        let npmrc_path = self.workspace.join(".npmrc");
+        let npmrc_text = text_block_fnl! {
+            "store-dir=../pacquet-store"
+            "cache-dir=../pacquet-cache"
+        };
+        let registry = FakeRegistry::start(packages);
+        let npmrc_text = format!("registry={}\n{npmrc_text}", registry.url());
+        fs::write(&npmrc_path, npmrc_text).expect("write to .npmrc");
+        let npmrc_info = AddFakeRegistry { npmrc_path, store_dir, cache_dir, registry };
+        let CommandTempCwd { pacquet, pnpm, root, workspace, npmrc_info: () } = self;
+        CommandTempCwd { pacquet, pnpm, root, workspace, npmrc_info }
+    }
+}
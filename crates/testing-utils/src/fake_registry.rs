@@ -0,0 +1,238 @@
+use crate::fixtures::{
+    FASTIFY_ERROR_TARBALL, FASTIFY_ERROR_TARBALL_INTEGRITY, FASTIFY_ERROR_TARBALL_UNPACKED_SIZE,
+};
+use mockito::{Matcher, Mock, Server, ServerGuard};
+use serde_json::{json, Map, Value};
+use ssri::{Algorithm, IntegrityOpts};
+use std::collections::{BTreeMap, HashMap};
+
+/// Description of a package version to be served by [`FakeRegistry`].
+#[derive(Debug, Clone)]
+pub struct FakePackage {
+    pub name: String,
+    pub version: String,
+    /// Extra fields of the version manifest, such as `dependencies` or `peerDependencies`.
+    ///
+    /// The fields of a `dist` object are merged into the generated one.
+    pub fields: Map<String, Value>,
+    /// When the version was published, which goes into the `time` field of the packument.
+    pub published: Option<String>,
+    /// Whether the package has a tarball of its own instead of the shared fixture.
+    pub own_tarball: bool,
+}
+
+impl FakePackage {
+    /// Describe a package version without any dependencies.
+    pub fn new(name: &str, version: &str) -> Self {
+        FakePackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            fields: Map::new(),
+            published: None,
+            own_tarball: false,
+        }
+    }
+
+    /// Serve a tarball of its own, which only contains a `package.json` with the name and the
+    /// version of the package, instead of the shared fixture.
+    ///
+    /// Packages that share the fixture also share its files in the store, so only one of them is
+    /// downloaded when they are installed together.
+    pub fn own_tarball(mut self) -> Self {
+        self.own_tarball = true;
+        self
+    }
+
+    /// Set when the version was published, e.g. `2023-01-01T00:00:00.000Z`.
+    pub fn published(mut self, time: &str) -> Self {
+        self.published = Some(time.to_string());
+        self
+    }
+
+    /// Set an arbitrary field of the version manifest.
+    pub fn field(mut self, key: &str, value: Value) -> Self {
+        self.fields.insert(key.to_string(), value);
+        self
+    }
+
+    /// Set `"dependencies"` of the version manifest.
+    pub fn dependencies<'a>(
+        self,
+        dependencies: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        self.field("dependencies", dependency_map(dependencies))
+    }
+
+    /// Set `"peerDependencies"` of the version manifest.
+    pub fn peer_dependencies<'a>(
+        self,
+        dependencies: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        self.field("peerDependencies", dependency_map(dependencies))
+    }
+
+    /// Set `"optionalDependencies"` of the version manifest.
+    pub fn optional_dependencies<'a>(
+        self,
+        dependencies: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        self.field("optionalDependencies", dependency_map(dependencies))
+    }
+}
+
+fn dependency_map<'a>(dependencies: impl IntoIterator<Item = (&'a str, &'a str)>) -> Value {
+    dependencies
+        .into_iter()
+        .map(|(name, range)| (name.to_string(), Value::String(range.to_string())))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// Create an uncompressed tarball with a `package.json` of `name` and `version`, and return it with
+/// its unpacked size.
+fn package_tarball(name: &str, version: &str) -> (Vec<u8>, usize) {
+    let manifest = serde_json::to_vec(&json!({ "name": name, "version": version }))
+        .expect("serialize package.json");
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    let mut builder = tar::Builder::new(Vec::new());
+    builder
+        .append_data(&mut header, "package/package.json", manifest.as_slice())
+        .expect("append package.json to the tarball");
+    let tarball = builder.into_inner().expect("finish the tarball");
+    (tarball, manifest.len())
+}
+
+/// Offline npm registry backed by [`mockito`].
+///
+/// Metadata is generated from a list of [`FakePackage`]. Unless a package has its
+/// [own tarball](FakePackage::own_tarball), its tarball is the same fixture
+/// ([`FASTIFY_ERROR_TARBALL`]) as the others, the packages only differ by their metadata, which is
+/// enough to exercise resolution and the layout of `node_modules`.
+pub struct FakeRegistry {
+    server: ServerGuard,
+    mocks: HashMap<String, Mock>,
+}
+
+impl FakeRegistry {
+    /// Start a server that serves `packages`.
+    ///
+    /// For every package name, these routes are served:
+    /// * `/{name}`: the full metadata of the package, with a `time` field if any of its versions
+    ///   has a [`published`](FakePackage::published) time.
+    /// * `/{name}/{version}` and `/{name}/latest`: the metadata of a single version.
+    /// * `/{name}/-/{bare_name}-{version}.tgz`: the tarball.
+    ///
+    /// Any other path is answered with 404, like a package that isn't published.
+    pub fn start(packages: &[FakePackage]) -> Self {
+        let mut server = Server::new();
+        let url = server.url();
+
+        // mockito prefers the earliest matching mock that expects more hits, then the latest one,
+        // so this fallback must be created first and never expect a hit
+        server.mock("GET", Matcher::Any).with_status(404).expect_at_least(0).create();
+
+        let mut versions_by_name = BTreeMap::<&str, Vec<(&FakePackage, Value, Vec<u8>)>>::new();
+        for package in packages {
+            let FakePackage { name, version, fields, own_tarball, .. } = package;
+            let bare_name = name.rsplit_once('/').map_or(name.as_str(), |(_, bare)| bare);
+            let mut manifest = fields.clone();
+            manifest.insert("name".to_string(), json!(name));
+            manifest.insert("version".to_string(), json!(version));
+            let (tarball, integrity, unpacked_size) = if *own_tarball {
+                let (tarball, unpacked_size) = package_tarball(name, version);
+                let integrity =
+                    IntegrityOpts::new().algorithm(Algorithm::Sha512).chain(&tarball).result();
+                (tarball, integrity.to_string(), unpacked_size)
+            } else {
+                (
+                    FASTIFY_ERROR_TARBALL.to_vec(),
+                    FASTIFY_ERROR_TARBALL_INTEGRITY.to_string(),
+                    FASTIFY_ERROR_TARBALL_UNPACKED_SIZE,
+                )
+            };
+            let mut dist = json!({
+                "integrity": integrity,
+                "tarball": format!("{url}/{name}/-/{bare_name}-{version}.tgz"),
+                "unpackedSize": unpacked_size,
+            });
+            if let (Some(Value::Object(fields)), Value::Object(dist)) =
+                (fields.get("dist"), &mut dist)
+            {
+                dist.extend(fields.clone());
+            }
+            manifest.insert("dist".to_string(), dist);
+            versions_by_name.entry(name).or_default().push((package, manifest.into(), tarball));
+        }
+
+        let mut mocks = HashMap::new();
+        let mut serve = |path: String, content_type: &str, body: Vec<u8>| {
+            let mock = server
+                .mock("GET", path.as_str())
+                .with_status(200)
+                .with_header("content-type", content_type)
+                .with_body(body)
+                .create();
+            mocks.insert(path, mock);
+        };
+
+        for (name, versions) in versions_by_name {
+            let latest = versions
+                .iter()
+                .max_by_key(|(package, ..)| {
+                    package.version.parse::<node_semver::Version>().expect("parse version")
+                })
+                .map(|(_, manifest, _)| manifest.clone())
+                .expect("at least one version");
+            let bare_name = name.rsplit_once('/').map_or(name, |(_, bare)| bare);
+
+            let mut version_map = Map::new();
+            let mut time = Map::new();
+            for (package, manifest, tarball) in versions {
+                let version = &package.version;
+                if let Some(published) = &package.published {
+                    time.insert(version.clone(), json!(published));
+                }
+                let body = serde_json::to_vec(&manifest).expect("serialize version manifest");
+                serve(format!("/{name}/{version}"), "application/json", body);
+                serve(
+                    format!("/{name}/-/{bare_name}-{version}.tgz"),
+                    "application/octet-stream",
+                    tarball,
+                );
+                version_map.insert(version.clone(), manifest);
+            }
+
+            let mut packument = json!({
+                "name": name,
+                "dist-tags": { "latest": latest["version"] },
+                "versions": version_map,
+            });
+            if !time.is_empty() {
+                packument["time"] = time.into();
+            }
+            let body = serde_json::to_vec(&latest).expect("serialize version manifest");
+            serve(format!("/{name}/latest"), "application/json", body);
+            let body = serde_json::to_vec(&packument).expect("serialize package metadata");
+            serve(format!("/{name}"), "application/json", body);
+        }
+
+        FakeRegistry { server, mocks }
+    }
+
+    /// URL of the registry (trailing slash included).
+    pub fn url(&self) -> String {
+        format!("{}/", self.server.url())
+    }
+
+    /// Get the mock of a route in order to inspect its hits.
+    pub fn mock(&self, path: &str) -> &Mock {
+        self.mocks.get(path).unwrap_or_else(|| panic!("No mock for {path:?}"))
+    }
+
+    /// Access the underlying server to add more mocks.
+    pub fn server(&mut self) -> &mut ServerGuard {
+        &mut self.server
+    }
+}
@@ -1,2 +1,12 @@
 pub const BIG_MANIFEST: &str = include_str!("fixtures/big/package.json");
 pub const BIG_LOCKFILE: &str = include_str!("fixtures/big/pnpm-lock.yaml");
+
+/// Tarball of `@fastify/error@3.3.0`, served by [`crate::fake_registry::FakeRegistry`] for every package.
+pub const FASTIFY_ERROR_TARBALL: &[u8] = include_bytes!("fixtures/fastify-error-3.3.0.tgz");
+/// Integrity of [`FASTIFY_ERROR_TARBALL`].
+pub const FASTIFY_ERROR_TARBALL_INTEGRITY: &str = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==";
+/// Unpacked size of [`FASTIFY_ERROR_TARBALL`].
+pub const FASTIFY_ERROR_TARBALL_UNPACKED_SIZE: usize = 16697;
+
+/// Tarball of `hello-bin@1.0.0`, a package whose `bin` is a `hello-bin` command.
+pub const HELLO_BIN_TARBALL: &[u8] = include_bytes!("fixtures/hello-bin-1.0.0.tgz");
@@ -0,0 +1,34 @@
+use std::{fs, path::Path, process::Command};
+
+/// Run `git` in `dir` and return its trimmed stdout.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=pacquet", "-c", "user.email=pacquet@example.com"])
+        .args(args)
+        .output()
+        .expect("run git");
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).expect("git output is UTF-8").trim().to_string()
+}
+
+/// Create a git repository at `dir` with one commit per version of the package `name`, each
+/// tagged `v{version}` with an annotated tag.
+///
+/// Returns the commit hash of each version.
+pub fn init_git_repo(dir: &Path, name: &str, versions: &[&str]) -> Vec<String> {
+    fs::create_dir_all(dir).expect("create repository directory");
+    git(dir, &["init", "--quiet"]);
+    versions
+        .iter()
+        .map(|version| {
+            let manifest = serde_json::json!({ "name": name, "version": version });
+            fs::write(dir.join("package.json"), manifest.to_string()).expect("write package.json");
+            git(dir, &["add", "package.json"]);
+            git(dir, &["commit", "--quiet", "--message", version]);
+            git(dir, &["tag", "--annotate", &format!("v{version}"), "--message", version]);
+            git(dir, &["rev-parse", "HEAD"])
+        })
+        .collect()
+}
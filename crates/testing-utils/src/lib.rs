@@ -1,3 +1,5 @@
 pub mod bin;
+pub mod fake_registry;
 pub mod fixtures;
 pub mod fs;
+pub mod git;
@@ -0,0 +1,115 @@
+use crate::WorkspaceManifest;
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use std::collections::HashMap;
+
+/// Error type of [`WorkspaceManifest::resolve_catalog`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum ResolveCatalogError {
+    #[display(
+        "{name}@catalog:{catalog} refers to a catalog that isn't defined in pnpm-workspace.yaml"
+    )]
+    #[diagnostic(code(pacquet_workspace::unknown_catalog))]
+    UnknownCatalog { name: String, catalog: String },
+
+    #[display("Catalog {catalog:?} in pnpm-workspace.yaml has no entry for {name}")]
+    #[diagnostic(code(pacquet_workspace::missing_catalog_entry))]
+    MissingEntry { name: String, catalog: String },
+}
+
+impl WorkspaceManifest {
+    /// Prefix of the specifiers that refer to a catalog.
+    const CATALOG_PROTOCOL: &'static str = "catalog:";
+
+    /// Name of the catalog referenced by a bare `catalog:` specifier.
+    const DEFAULT_CATALOG: &'static str = "default";
+
+    /// Replace a `catalog:` or `catalog:{name}` specifier of dependency `name` by the version in the
+    /// matching catalog.
+    ///
+    /// Other specifiers are returned as-is.
+    pub fn resolve_catalog<'a>(
+        &'a self,
+        name: &str,
+        specifier: &'a str,
+    ) -> Result<&'a str, ResolveCatalogError> {
+        let Some(catalog) = specifier.strip_prefix(WorkspaceManifest::CATALOG_PROTOCOL) else {
+            return Ok(specifier);
+        };
+        let catalog = match catalog.trim() {
+            "" => WorkspaceManifest::DEFAULT_CATALOG,
+            catalog => catalog,
+        };
+
+        let entries =
+            self.catalog_by_name(catalog).ok_or_else(|| ResolveCatalogError::UnknownCatalog {
+                name: name.to_string(),
+                catalog: catalog.to_string(),
+            })?;
+
+        entries.get(name).map(String::as_str).ok_or_else(|| ResolveCatalogError::MissingEntry {
+            name: name.to_string(),
+            catalog: catalog.to_string(),
+        })
+    }
+
+    /// Get a catalog by its name, `default` being either [`catalog`](Self::catalog) or
+    /// `catalogs.default`.
+    fn catalog_by_name(&self, catalog: &str) -> Option<&HashMap<String, String>> {
+        if catalog == WorkspaceManifest::DEFAULT_CATALOG && !self.catalog.is_empty() {
+            return Some(&self.catalog);
+        }
+        self.catalogs.get(catalog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn workspace_manifest() -> WorkspaceManifest {
+        serde_yaml::from_str(
+            "catalog:\n  react: ^18.2.0\ncatalogs:\n  legacy:\n    react: ^16.0.0\n    react-dom: ^16.0.0\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve_default_catalog() {
+        let manifest = workspace_manifest();
+        assert_eq!(manifest.resolve_catalog("react", "catalog:").unwrap(), "^18.2.0");
+        assert_eq!(manifest.resolve_catalog("react", "catalog:default").unwrap(), "^18.2.0");
+    }
+
+    #[test]
+    fn resolve_named_catalog() {
+        let manifest = workspace_manifest();
+        assert_eq!(manifest.resolve_catalog("react", "catalog:legacy").unwrap(), "^16.0.0");
+        assert_eq!(manifest.resolve_catalog("react-dom", "catalog:legacy").unwrap(), "^16.0.0");
+    }
+
+    #[test]
+    fn keep_other_specifiers() {
+        let manifest = workspace_manifest();
+        assert_eq!(manifest.resolve_catalog("react", "^17.0.0").unwrap(), "^17.0.0");
+    }
+
+    #[test]
+    fn reject_unknown_catalog_and_missing_entry() {
+        let manifest = workspace_manifest();
+
+        let error = manifest.resolve_catalog("react", "catalog:next").unwrap_err();
+        dbg!(&error);
+        assert!(matches!(error, ResolveCatalogError::UnknownCatalog { .. }));
+
+        let error = manifest.resolve_catalog("react-dom", "catalog:").unwrap_err();
+        dbg!(&error);
+        assert!(matches!(error, ResolveCatalogError::MissingEntry { .. }));
+
+        let error = WorkspaceManifest::default().resolve_catalog("react", "catalog:").unwrap_err();
+        dbg!(&error);
+        assert!(matches!(error, ResolveCatalogError::UnknownCatalog { .. }));
+    }
+}
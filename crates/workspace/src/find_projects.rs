@@ -0,0 +1,126 @@
+use crate::WorkspaceManifest;
+use derive_more::{Display, Error};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use miette::Diagnostic;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Error type of [`WorkspaceManifest::find_projects`].
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum FindProjectsError {
+    #[display("Invalid pattern {pattern:?} in pnpm-workspace.yaml: {error}")]
+    #[diagnostic(code(pacquet_workspace::invalid_pattern))]
+    InvalidPattern {
+        pattern: String,
+        #[error(source)]
+        error: globset::Error,
+    },
+
+    #[display("Failed to read the workspace directory: {_0}")]
+    #[diagnostic(code(pacquet_workspace::walk_dir))]
+    WalkDir(#[error(source)] walkdir::Error),
+}
+
+impl WorkspaceManifest {
+    /// List the directories of the projects of the workspace at `root_dir`.
+    ///
+    /// The workspace root comes first when it has a `package.json`, the other projects follow in
+    /// alphabetical order. A directory is a project when it matches [`packages`](Self::packages)
+    /// and contains a `package.json`. `node_modules` directories are never searched.
+    pub fn find_projects(&self, root_dir: &Path) -> Result<Vec<PathBuf>, FindProjectsError> {
+        let (include, exclude) = self.build_glob_sets()?;
+        let mut projects = Vec::new();
+
+        let entries = WalkDir::new(root_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != "node_modules");
+        for entry in entries {
+            let entry = entry.map_err(FindProjectsError::WalkDir)?;
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            let dir = entry.path();
+            if !dir.join("package.json").is_file() {
+                continue;
+            }
+            let relative = dir.strip_prefix(root_dir).expect("entry is inside the root");
+            let is_root = relative.as_os_str().is_empty();
+            if is_root || (include.is_match(relative) && !exclude.is_match(relative)) {
+                projects.push(dir.to_path_buf());
+            }
+        }
+
+        Ok(projects)
+    }
+
+    /// Split [`packages`](Self::packages) into the inclusion and the exclusion glob sets.
+    fn build_glob_sets(&self) -> Result<(GlobSet, GlobSet), FindProjectsError> {
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+        for pattern in &self.packages {
+            let (builder, glob) = match pattern.strip_prefix('!') {
+                Some(glob) => (&mut exclude, glob),
+                None => (&mut include, pattern.as_str()),
+            };
+            let glob = glob.trim_start_matches("./").trim_end_matches('/');
+            let glob = GlobBuilder::new(glob).literal_separator(true).build().map_err(|error| {
+                FindProjectsError::InvalidPattern { pattern: pattern.clone(), error }
+            })?;
+            builder.add(glob);
+        }
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|error| FindProjectsError::InvalidPattern {
+                pattern: self.packages.join(", "),
+                error,
+            })
+        };
+        Ok((build(include)?, build(exclude)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn find_projects() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        for project in [
+            "",
+            "packages/a",
+            "packages/b",
+            "packages/private",
+            "packages/a/node_modules/foo",
+            "packages/a/nested",
+            "tools/c",
+        ] {
+            fs::create_dir_all(root.join(project)).unwrap();
+            fs::write(root.join(project).join("package.json"), "{}").unwrap();
+        }
+        fs::create_dir_all(root.join("packages/no-manifest")).unwrap();
+
+        let manifest = WorkspaceManifest {
+            packages: vec!["packages/*".to_string(), "!packages/private".to_string()],
+            ..Default::default()
+        };
+        let received = manifest.find_projects(root).unwrap();
+        let expected = vec![root.to_path_buf(), root.join("packages/a"), root.join("packages/b")];
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let dir = tempdir().unwrap();
+        let manifest =
+            WorkspaceManifest { packages: vec!["packages/[".to_string()], ..Default::default() };
+        let error = manifest.find_projects(dir.path()).unwrap_err();
+        dbg!(&error);
+        assert!(matches!(error, FindProjectsError::InvalidPattern { .. }));
+    }
+}
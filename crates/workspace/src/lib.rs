@@ -0,0 +1,7 @@
+mod catalog;
+mod find_projects;
+mod workspace_manifest;
+
+pub use catalog::*;
+pub use find_projects::*;
+pub use workspace_manifest::*;
@@ -0,0 +1,148 @@
+use derive_more::{Display, Error};
+use miette::Diagnostic;
+use pipe_trait::Pipe;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// Content of a `pnpm-workspace.yaml` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceManifest {
+    /// Glob patterns of the directories of the workspace projects, relative to the workspace root.
+    ///
+    /// Patterns that start with `!` exclude directories.
+    #[serde(default)]
+    pub packages: Vec<String>,
+
+    /// The default catalog, referenced by `catalog:` or `catalog:default`.
+    #[serde(default)]
+    pub catalog: HashMap<String, String>,
+
+    /// Named catalogs, referenced by `catalog:{name}`.
+    #[serde(default)]
+    pub catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+/// Error when reading `pnpm-workspace.yaml` from the filesystem.
+#[derive(Debug, Display, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum LoadWorkspaceManifestError {
+    #[display("Failed to read {}: {error}", file_path.display())]
+    #[diagnostic(code(pacquet_workspace::read_file))]
+    ReadFile {
+        file_path: PathBuf,
+        #[error(source)]
+        error: io::Error,
+    },
+
+    #[display("Failed to parse {} as YAML: {error}", file_path.display())]
+    #[diagnostic(code(pacquet_workspace::parse_yaml))]
+    ParseYaml {
+        file_path: PathBuf,
+        #[error(source)]
+        error: serde_yaml::Error,
+    },
+}
+
+impl WorkspaceManifest {
+    /// Base file name of the workspace manifest.
+    const FILE_NAME: &'static str = "pnpm-workspace.yaml";
+
+    /// Load the workspace manifest from the root directory of a workspace.
+    ///
+    /// Return `None` if `root_dir` doesn't contain a `pnpm-workspace.yaml` file.
+    pub fn load_from_dir(root_dir: &Path) -> Result<Option<Self>, LoadWorkspaceManifestError> {
+        let file_path = root_dir.join(WorkspaceManifest::FILE_NAME);
+        let content = match fs::read_to_string(&file_path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(LoadWorkspaceManifestError::ReadFile { file_path, error }),
+        };
+        content
+            .pipe_as_ref(serde_yaml::from_str::<Option<WorkspaceManifest>>)
+            .map(Option::unwrap_or_default)
+            .map(Some)
+            .map_err(|error| LoadWorkspaceManifestError::ParseYaml { file_path, error })
+    }
+
+    /// Find the closest `pnpm-workspace.yaml` in `dir` or any of its ancestors.
+    ///
+    /// Return the root directory of the workspace along with its manifest.
+    pub fn find_from_dir(
+        dir: &Path,
+    ) -> Result<Option<(PathBuf, Self)>, LoadWorkspaceManifestError> {
+        for root_dir in dir.ancestors() {
+            if let Some(manifest) = WorkspaceManifest::load_from_dir(root_dir)? {
+                return Ok(Some((root_dir.to_path_buf(), manifest)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn load_from_dir() {
+        let dir = tempdir().unwrap();
+
+        eprintln!("CASE: no pnpm-workspace.yaml");
+        assert_eq!(WorkspaceManifest::load_from_dir(dir.path()).unwrap(), None);
+
+        eprintln!("CASE: empty pnpm-workspace.yaml");
+        fs::write(dir.path().join("pnpm-workspace.yaml"), "").unwrap();
+        assert_eq!(
+            WorkspaceManifest::load_from_dir(dir.path()).unwrap(),
+            Some(WorkspaceManifest::default()),
+        );
+
+        eprintln!("CASE: pnpm-workspace.yaml with packages");
+        fs::write(
+            dir.path().join("pnpm-workspace.yaml"),
+            "packages:\n  - packages/*\n  - '!packages/private'\n",
+        )
+        .unwrap();
+        assert_eq!(
+            WorkspaceManifest::load_from_dir(dir.path()).unwrap(),
+            Some(WorkspaceManifest {
+                packages: vec!["packages/*".to_string(), "!packages/private".to_string()],
+                ..Default::default()
+            }),
+        );
+
+        eprintln!("CASE: pnpm-workspace.yaml with catalogs");
+        let content = "catalog:\n  react: ^18.2.0\ncatalogs:\n  legacy:\n    react: ^16.0.0\n";
+        fs::write(dir.path().join("pnpm-workspace.yaml"), content).unwrap();
+        let manifest = WorkspaceManifest::load_from_dir(dir.path()).unwrap().unwrap();
+        assert_eq!(manifest.catalog, [("react".to_string(), "^18.2.0".to_string())].into());
+        assert_eq!(
+            manifest.catalogs,
+            [("legacy".to_string(), [("react".to_string(), "^16.0.0".to_string())].into())].into(),
+        );
+    }
+
+    #[test]
+    fn find_from_dir() {
+        let dir = tempdir().unwrap();
+        let project_dir = dir.path().join("packages/foo");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        eprintln!("CASE: no pnpm-workspace.yaml");
+        assert_eq!(WorkspaceManifest::find_from_dir(&project_dir).unwrap(), None);
+
+        eprintln!("CASE: pnpm-workspace.yaml in an ancestor");
+        fs::write(dir.path().join("pnpm-workspace.yaml"), "packages: ['packages/*']").unwrap();
+        let (root_dir, manifest) = WorkspaceManifest::find_from_dir(&project_dir).unwrap().unwrap();
+        assert_eq!(root_dir, dir.path());
+        assert_eq!(manifest.packages, ["packages/*"]);
+    }
+}
@@ -28,27 +28,34 @@ fn bench_tarball(c: &mut Criterion, server: &mut ServerGuard, fixtures_folder: &
     let package_integrity: Integrity = "sha512-dj7vjIn1Ar8sVXj2yAXiMNCJDmS9MQ9XMlIecX2dIzzhjSHCyKo4DdXjXMs7wKW2kj6yvVRSpuQjOZ3YLrh56w==".parse().expect("parse integrity string");
 
     group.throughput(Throughput::Bytes(file.len() as u64));
-    group.bench_function("download_dependency", |b| {
-        b.to_async(&rt).iter(|| async {
-            // NOTE: the tempdir is being leaked, meaning the cleanup would be postponed until the end of the benchmark
-            let dir = tempdir().unwrap();
-            let store_dir =
-                dir.path().to_path_buf().pipe(StoreDir::from).pipe(Box::new).pipe(Box::leak);
-            let http_client = ThrottledClient::new_from_cpu_count();
+    for (name, verify_store_integrity) in
+        [("download_dependency", true), ("download_dependency_without_verification", false)]
+    {
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| async {
+                // NOTE: the tempdir is being leaked, meaning the cleanup would be postponed until the end of the benchmark
+                let dir = tempdir().unwrap();
+                let store_dir =
+                    dir.path().to_path_buf().pipe(StoreDir::from).pipe(Box::new).pipe(Box::leak);
+                let http_client = ThrottledClient::new_from_cpu_count();
 
-            let cas_map = DownloadTarballToStore {
-                http_client: &http_client,
-                store_dir,
-                package_integrity: &package_integrity,
-                package_unpacked_size: Some(16697),
-                package_url: url,
-            }
-            .run_without_mem_cache()
-            .await
-            .unwrap();
-            cas_map.len()
+                let cas_map = DownloadTarballToStore {
+                    http_client: &http_client,
+                    store_dir,
+                    package_integrity: &package_integrity,
+                    package_unpacked_size: Some(16697),
+                    package_url: url,
+                    registry: &server.url(),
+                    recorded_integrity: None,
+                    verify_store_integrity,
+                }
+                .run_without_mem_cache()
+                .await
+                .unwrap();
+                cas_map.len()
+            });
         });
-    });
+    }
 
     group.finish();
 }